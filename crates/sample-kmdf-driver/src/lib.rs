@@ -38,7 +38,7 @@
 
 #[cfg(not(test))]
 #[global_allocator]
-static GLOBAL_ALLOCATOR: WDKAllocator = WDKAllocator;
+static GLOBAL_ALLOCATOR: WDKAllocator = WDKAllocator::new();
 
 /// `DriverEntry` function required by WDF
 ///
@@ -0,0 +1,156 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! [`Allocator`] implementations of the pool allocators and arenas, and helpers
+//! to allocate collections in them without panicking when allocation fails.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    ptr::NonNull,
+};
+
+use wdk_sys::{
+    NTSTATUS,
+    POOL_FLAGS,
+    POOL_FLAG_NON_PAGED,
+    POOL_FLAG_PAGED,
+    STATUS_INSUFFICIENT_RESOURCES,
+    ULONG,
+};
+
+use crate::{allocate, free, Arena, NonPagedAllocator, PagedAllocator};
+
+// SAFETY: Memory allocated by `allocate` stays valid until it is passed to
+// `deallocate`, regardless of which copy of the allocator it is passed to,
+// since every copy allocates from and frees to the same pool.
+unsafe impl Allocator for NonPagedAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: Non-paged pool can be allocated from at any `IRQL` <=
+        // `DISPATCH_LEVEL`, which the user of this allocator guarantees.
+        unsafe {
+            allocate_slice(
+                POOL_FLAG_NON_PAGED,
+                layout,
+                self.tag(),
+                self.preferred_node(),
+            )
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: The caller guarantees that `ptr` was allocated by this allocator
+        // with `layout`.
+        unsafe { deallocate_slice(ptr, layout) }
+    }
+}
+
+// SAFETY: Memory allocated by `allocate` stays valid until it is passed to
+// `deallocate`, regardless of which copy of the allocator it is passed to,
+// since every copy allocates from and frees to the same pool.
+unsafe impl Allocator for PagedAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: Paged pool can be allocated from at any `IRQL` <= `APC_LEVEL`,
+        // which the user of this allocator guarantees.
+        unsafe { allocate_slice(POOL_FLAG_PAGED, layout, self.tag(), self.preferred_node()) }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: The caller guarantees that `ptr` was allocated by this allocator
+        // with `layout`.
+        unsafe { deallocate_slice(ptr, layout) }
+    }
+}
+
+// SAFETY: Memory allocated from the arena stays valid until it is reset or
+// dropped, which requires that no references to it, including this one, are
+// left.
+unsafe impl Allocator for &Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Arena::allocate(self, layout)
+            .map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size()))
+            .ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // Memory is only released when the arena is reset
+    }
+}
+
+/// Try to allocate a [`Box`] holding `value` with `allocator`
+///
+/// # Errors
+///
+/// This function will return an error if the [`Box`] could not be allocated,
+/// in which case the error variant will contain
+/// `STATUS_INSUFFICIENT_RESOURCES`.
+pub fn try_box_in<T, A: Allocator>(value: T, allocator: A) -> Result<Box<T, A>, NTSTATUS> {
+    Box::try_new_in(value, allocator).map_err(|_| STATUS_INSUFFICIENT_RESOURCES)
+}
+
+/// Try to allocate an empty [`Vec`] with room for at least `capacity`
+/// elements with `allocator`
+///
+/// # Errors
+///
+/// This function will return an error if the [`Vec`] could not be allocated,
+/// in which case the error variant will contain
+/// `STATUS_INSUFFICIENT_RESOURCES`.
+pub fn try_vec_with_capacity_in<T, A: Allocator>(
+    capacity: usize,
+    allocator: A,
+) -> Result<Vec<T, A>, NTSTATUS> {
+    Vec::try_with_capacity_in(capacity, allocator).map_err(|_| STATUS_INSUFFICIENT_RESOURCES)
+}
+
+/// Allocate `layout` from the pool of `pool_flags`, tagged with `tag`,
+/// preferring the memory of `node`
+///
+/// Zero-sized layouts are not allocated from the pool.
+///
+/// # Safety
+///
+/// This must be called at an `IRQL` that the pool of `pool_flags` can be
+/// allocated from.
+unsafe fn allocate_slice(
+    pool_flags: POOL_FLAGS,
+    layout: Layout,
+    tag: ULONG,
+    node: Option<u16>,
+) -> Result<NonNull<[u8]>, AllocError> {
+    if layout.size() == 0 {
+        let dangling = core::ptr::without_provenance_mut(layout.align());
+        return Ok(NonNull::slice_from_raw_parts(
+            NonNull::new(dangling).ok_or(AllocError)?,
+            0,
+        ));
+    }
+
+    let ptr;
+    // SAFETY: The caller guarantees that this is called at an `IRQL` that the pool
+    // of `pool_flags` can be allocated from.
+    unsafe {
+        ptr = allocate(pool_flags, layout, tag, node);
+    }
+    NonNull::new(ptr)
+        .map(|ptr| NonNull::slice_from_raw_parts(ptr, layout.size()))
+        .ok_or(AllocError)
+}
+
+/// Free `ptr`, which was allocated with `layout` by [`allocate_slice()`]
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`allocate_slice()`] for `layout`, and this
+/// must be called at an `IRQL` that its pool can be freed to.
+unsafe fn deallocate_slice(ptr: NonNull<u8>, layout: Layout) {
+    // Zero-sized layouts were not allocated from the pool
+    if layout.size() != 0 {
+        // SAFETY: The caller guarantees that `ptr` was allocated from the pool by
+        // `allocate` for `layout`, and that this is called at an `IRQL` that its pool
+        // can be freed to.
+        unsafe {
+            free(ptr.as_ptr(), layout);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! [`Arena`], a preallocated bump allocator that can allocate at any `IRQL`.
+
+use core::{
+    alloc::Layout,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use wdk_sys::{
+    ntddk::{ExFreePool, KeGetCurrentIrql},
+    KIRQL,
+    NTSTATUS,
+    POOL_FLAG_NON_PAGED,
+    STATUS_INSUFFICIENT_RESOURCES,
+};
+
+use crate::{allocate_pool, pool_tag};
+
+// `wdk-sys` generates the `IRQL` constants as `u32`s, but `IRQL`s are `KIRQL`s
+#[allow(clippy::cast_possible_truncation)]
+const PASSIVE_LEVEL: KIRQL = wdk_sys::PASSIVE_LEVEL as KIRQL;
+// `wdk-sys` generates the `IRQL` constants as `u32`s, but `IRQL`s are `KIRQL`s
+#[allow(clippy::cast_possible_truncation)]
+const DISPATCH_LEVEL: KIRQL = wdk_sys::DISPATCH_LEVEL as KIRQL;
+
+/// A bump allocator that hands out memory from a single non-paged buffer,
+/// allocated when the arena is constructed.
+///
+/// Allocating from the arena never calls into the pool, and only advances an
+/// atomic offset into the buffer, so it can be done at any `IRQL`, including
+/// from a DPC or an interrupt service routine, and from several processors at
+/// once. Memory is never freed individually. Instead, every allocation is
+/// released at once by [`Arena::reset()`], which requires that nothing still
+/// borrows from the arena, and is meant to be called at `PASSIVE_LEVEL`
+/// between batches of work, such as packets.
+///
+/// Values allocated in the arena are not dropped, so types that own other
+/// resources should not be allocated in it.
+///
+/// ```rust, no_run
+/// use wdk_alloc::Arena;
+///
+/// # fn example() -> Result<(), wdk_sys::NTSTATUS> {
+/// let mut arena = Arena::try_new(64 * 1024, *b"Pkts")?;
+/// let header = arena
+///     .try_alloc([0_u8; 14])
+///     .map_err(|_| wdk_sys::STATUS_INSUFFICIENT_RESOURCES)?;
+/// header[12] = 0x08;
+/// arena.reset();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Arena {
+    buffer: NonNull<u8>,
+    capacity: usize,
+    offset: AtomicUsize,
+}
+
+// SAFETY: The buffer is owned by the `Arena`, and can be freed from any thread.
+unsafe impl Send for Arena {}
+
+// SAFETY: Allocations are claimed by atomically advancing `offset`, so every
+// allocation made through a shared reference is disjoint from the others.
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Try to allocate an arena of `capacity` bytes from non-paged pool,
+    /// tagged with `tag`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the buffer could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is all zeroes, which is not a valid pool tag.
+    pub fn try_new(capacity: usize, tag: [u8; 4]) -> Result<Self, NTSTATUS> {
+        let irql;
+        // SAFETY: `KeGetCurrentIrql` can be called at any `IRQL`.
+        unsafe {
+            irql = KeGetCurrentIrql();
+        }
+        debug_assert!(
+            irql <= DISPATCH_LEVEL,
+            "Arena::try_new should be called at IRQL <= DISPATCH_LEVEL"
+        );
+
+        let ptr;
+        // SAFETY: Non-paged pool can be allocated from at any `IRQL` <=
+        // `DISPATCH_LEVEL`, which this function requires, and asserts in debug
+        // builds.
+        unsafe {
+            ptr = allocate_pool(POOL_FLAG_NON_PAGED, capacity.max(1), pool_tag(tag), None);
+        }
+        Ok(Self {
+            buffer: NonNull::new(ptr).ok_or(STATUS_INSUFFICIENT_RESOURCES)?,
+            capacity,
+            offset: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the size of the arena's buffer, in bytes
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns how many bytes of the arena's buffer have been handed out,
+    /// including padding for alignment
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many bytes of the arena's buffer have not been handed out
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.used()
+    }
+
+    /// Allocate memory for `layout` from the arena, returning [`None`] if it
+    /// does not have room for it
+    ///
+    /// The memory is uninitialized, and stays valid until the arena is reset
+    /// or dropped. This can be called at any `IRQL`.
+    #[must_use]
+    pub fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.buffer.as_ptr();
+        let mut offset = self.offset.load(Ordering::Relaxed);
+        loop {
+            let start =
+                offset.checked_add(base.wrapping_add(offset).align_offset(layout.align()))?;
+            let end = start
+                .checked_add(layout.size())
+                .filter(|&end| end <= self.capacity)?;
+            match self.offset.compare_exchange_weak(
+                offset,
+                end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return NonNull::new(base.wrapping_add(start)),
+                Err(current) => offset = current,
+            }
+        }
+    }
+
+    /// Try to move `value` into the arena, returning a mutable reference to
+    /// it
+    ///
+    /// The value is never dropped. This can be called at any `IRQL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `value` if the arena does not have room for
+    /// it.
+    // The value lives in the arena, not in `self`, so each call can hand out a
+    // distinct mutable reference
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc<T>(&self, value: T) -> Result<&mut T, T> {
+        let Some(ptr) = self.allocate(Layout::new::<T>()) else {
+            return Err(value);
+        };
+        let ptr = ptr.cast::<T>().as_ptr();
+        // SAFETY: `ptr` was just claimed from the arena for a `T`, so it is aligned,
+        // valid for writes of a `T`, and not referenced by anything else.
+        unsafe {
+            ptr.write(value);
+        }
+        let value;
+        // SAFETY: `ptr` was initialized above, and stays valid until the arena is
+        // reset or dropped, which requires that this borrow of the arena has ended.
+        unsafe {
+            value = &mut *ptr;
+        }
+        Ok(value)
+    }
+
+    /// Try to copy `values` into the arena, returning a mutable reference to
+    /// the copy, or [`None`] if the arena does not have room for it
+    ///
+    /// This can be called at any `IRQL`.
+    // The copy lives in the arena, not in `self`, so each call can hand out a
+    // distinct mutable reference
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc_slice_copy<T: Copy>(&self, values: &[T]) -> Option<&mut [T]> {
+        let ptr = self
+            .allocate(Layout::for_value(values))?
+            .cast::<T>()
+            .as_ptr();
+        // SAFETY: `ptr` was just claimed from the arena for `values.len()` `T`s, so
+        // it is aligned, valid for writes of them, and does not overlap `values`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(values.as_ptr(), ptr, values.len());
+        }
+        let copy;
+        // SAFETY: The slice was initialized above, and stays valid until the arena is
+        // reset or dropped, which requires that this borrow of the arena has ended.
+        unsafe {
+            copy = core::slice::from_raw_parts_mut(ptr, values.len());
+        }
+        Some(copy)
+    }
+
+    /// Release every allocation made from the arena, so that its whole buffer
+    /// can be allocated again
+    ///
+    /// Taking `&mut self` guarantees that nothing allocated from the arena is
+    /// still borrowed. This should be called at `PASSIVE_LEVEL`, once the
+    /// raised-`IRQL` work that allocated from the arena has finished.
+    pub fn reset(&mut self) {
+        let irql;
+        // SAFETY: `KeGetCurrentIrql` can be called at any `IRQL`.
+        unsafe {
+            irql = KeGetCurrentIrql();
+        }
+        debug_assert!(
+            irql == PASSIVE_LEVEL,
+            "Arena::reset should be called at PASSIVE_LEVEL"
+        );
+        *self.offset.get_mut() = 0;
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        // SAFETY: `buffer` was allocated from non-paged pool in `try_new`, which can
+        // be freed at any `IRQL` <= `DISPATCH_LEVEL`, and is not used after this.
+        unsafe {
+            ExFreePool(self.buffer.as_ptr().cast());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! [`GuardedAllocator`], which surrounds allocations with guard patterns to
+//! detect heap corruption.
+
+use core::alloc::{GlobalAlloc, Layout};
+
+use wdk_sys::{ntddk::KeBugCheckEx, ULONG, ULONG_PTR};
+
+/// The bug check code that [`GuardedAllocator`] bug checks with when it finds
+/// a corrupted guard, which is `SPECIAL_POOL_DETECTED_MEMORY_CORRUPTION`
+///
+/// The parameters of the bug check are:
+/// 1. the address of the allocation
+/// 2. the size of the allocation
+/// 3. the address of the first corrupted byte
+/// 4. [`FRONT_GUARD_CORRUPTED`] or [`BACK_GUARD_CORRUPTED`]
+pub const GUARD_CORRUPTION_BUG_CHECK_CODE: ULONG = 0xC1;
+
+/// The fourth bug check parameter when the guard before an allocation is
+/// corrupted, such as by an underrun
+pub const FRONT_GUARD_CORRUPTED: ULONG_PTR = 1;
+
+/// The fourth bug check parameter when the guard after an allocation is
+/// corrupted, such as by an overrun
+pub const BACK_GUARD_CORRUPTED: ULONG_PTR = 2;
+
+/// The minimum size of each guard
+const GUARD_SIZE: usize = 16;
+
+/// The byte that guards are filled with
+const GUARD_BYTE: u8 = 0xFD;
+
+/// Allocator wrapper that surrounds every allocation of `A` with guard
+/// patterns.
+///
+/// The guards are validated when the allocation is freed, bug checking with
+/// [`GUARD_CORRUPTION_BUG_CHECK_CODE`] if they were overwritten. This finds
+/// buffer overruns and underruns in the driver's allocations, like special
+/// pool, without enabling Driver Verifier. Corruption is only found when the
+/// corrupted allocation is freed, so the bug check is raised by the code that
+/// frees it, rather than the code that corrupted it.
+///
+/// # Example
+/// ```rust, no_run
+/// use wdk_alloc::{GuardedAllocator, WDKAllocator};
+///
+/// #[global_allocator]
+/// static GLOBAL_ALLOCATOR: GuardedAllocator<WDKAllocator> =
+///     GuardedAllocator::new(WDKAllocator::with_tag(*b"Samp"));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuardedAllocator<A> {
+    inner: A,
+}
+
+impl<A> GuardedAllocator<A> {
+    /// Construct an allocator that allocates from `inner`, surrounding every
+    /// allocation with guard patterns
+    #[must_use]
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the allocator that the guarded allocations are allocated from
+    #[must_use]
+    pub const fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+/// The layout of an allocation for `layout` surrounded by guards, and the
+/// offset of the memory for `layout` in it. The front guard fills everything
+/// before that offset, and the back guard everything after `layout`.
+fn guarded_layout(layout: Layout) -> Option<(Layout, usize)> {
+    let guard = Layout::new::<[u8; GUARD_SIZE]>();
+    let (front_and_layout, offset) = guard.extend(layout).ok()?;
+    let (guarded, _) = front_and_layout.extend(guard).ok()?;
+    Some((guarded, offset))
+}
+
+// SAFETY: This is safe because `GuardedAllocator`:
+//         1. never unwinds, since it bug checks instead of panicking
+//         2. returns memory for `layout` from within allocations of `A` for a
+//            layout that contains it, aligned as `layout` requires
+unsafe impl<A: GlobalAlloc> GlobalAlloc for GuardedAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some((guarded_layout, offset)) = guarded_layout(layout) else {
+            return core::ptr::null_mut();
+        };
+        let base;
+        // SAFETY: `guarded_layout` has a non-zero size, since it contains the guards,
+        // and the caller guarantees the rest of the requirements of `A`.
+        unsafe {
+            base = self.inner.alloc(guarded_layout);
+        }
+        if base.is_null() {
+            return base;
+        }
+
+        // SAFETY: `base` is valid for writes of `guarded_layout.size()` bytes.
+        unsafe {
+            base.write_bytes(GUARD_BYTE, offset);
+        }
+        let ptr = base.wrapping_add(offset);
+        let back_guard = ptr.wrapping_add(layout.size());
+        // SAFETY: The back guard is the rest of the allocation after `layout`.
+        unsafe {
+            back_guard.write_bytes(GUARD_BYTE, guarded_layout.size() - offset - layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let guarded = guarded_layout(layout);
+        debug_assert!(
+            guarded.is_some(),
+            "the layout of an allocation should be guardable"
+        );
+        // `alloc` returns null for layouts that cannot be guarded, so the caller
+        // cannot have an allocation of `layout` to free
+        let Some((guarded_layout, offset)) = guarded else {
+            return;
+        };
+        let base = ptr.wrapping_sub(offset);
+        let back_guard = ptr.wrapping_add(layout.size());
+
+        // SAFETY: The caller guarantees that `ptr` was allocated by `alloc` for
+        // `layout`, so the front guard is the `offset` bytes before it.
+        unsafe {
+            check_guard(base, offset, ptr, layout.size(), FRONT_GUARD_CORRUPTED);
+        }
+        // SAFETY: The caller guarantees that `ptr` was allocated by `alloc` for
+        // `layout`, so the back guard is the rest of the allocation after `layout`.
+        unsafe {
+            check_guard(
+                back_guard,
+                guarded_layout.size() - offset - layout.size(),
+                ptr,
+                layout.size(),
+                BACK_GUARD_CORRUPTED,
+            );
+        }
+        // SAFETY: `base` was allocated by `A` for `guarded_layout`, and the caller
+        // guarantees the rest of the requirements of `A`.
+        unsafe {
+            self.inner.dealloc(base, guarded_layout);
+        }
+    }
+}
+
+/// Bug check if any of the `len` bytes of the guard at `guard`, around the
+/// allocation of `size` bytes at `ptr`, were overwritten
+///
+/// # Safety
+///
+/// `guard` must be valid for reads of `len` bytes.
+unsafe fn check_guard(guard: *const u8, len: usize, ptr: *const u8, size: usize, which: ULONG_PTR) {
+    let bytes;
+    // SAFETY: The caller guarantees that `guard` is valid for reads of `len` bytes.
+    unsafe {
+        bytes = core::slice::from_raw_parts(guard, len);
+    }
+    if let Some(corrupted) = bytes.iter().position(|&byte| byte != GUARD_BYTE) {
+        // SAFETY: `KeBugCheckEx` can be called at any `IRQL`, and does not return.
+        unsafe {
+            KeBugCheckEx(
+                GUARD_CORRUPTION_BUG_CHECK_CODE,
+                ptr as ULONG_PTR,
+                size as ULONG_PTR,
+                guard.wrapping_add(corrupted) as ULONG_PTR,
+                which,
+            );
+        }
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright (c) Microsoft Corporation
 // License: MIT OR Apache-2.0
 
-//! Allocator implementation to use with `#[global_allocator]` to allow use of
+//! Allocator implementations to use with `#[global_allocator]` to allow use of
 //! [`core::alloc`].
 //!
 //! # Example
@@ -11,55 +11,424 @@
 //!
 //! #[cfg(not(test))]
 //! #[global_allocator]
-//! static GLOBAL_ALLOCATOR: WDKAllocator = WDKAllocator;
+//! static GLOBAL_ALLOCATOR: WDKAllocator = WDKAllocator::with_tag(*b"Samp");
 //! ```
+//!
+//! Every allocation is tagged with the pool tag the allocator is constructed
+//! with, so the driver's pool usage can be found by its tag in tools such as
+//! `!poolused` and Driver Verifier. [`WDKAllocator::new()`] uses the tag
+//! `rust`.
+//!
+//! [`NonPagedAllocator`], which [`WDKAllocator`](type@WDKAllocator) is an alias
+//! of, allocates from non-paged pool, which can be used at any `IRQL` <=
+//! `DISPATCH_LEVEL`. [`PagedAllocator`] allocates from paged pool, which is far
+//! more plentiful, but can only be used at `IRQL` <= `APC_LEVEL`. It is only
+//! suitable as the global allocator of a driver that never allocates, frees or
+//! touches allocated memory at `DISPATCH_LEVEL`, such as from a DPC or while
+//! holding a spin lock.
+//!
+//! Allocations are aligned as their [`Layout`] requires. Pool allocations are
+//! aligned to 16 bytes, so allocations that must be aligned more strictly,
+//! such as to a cache line or a page, are over-allocated by their alignment.
+//!
+//! Allocations can be made on the memory of a preferred NUMA node with
+//! [`NonPagedAllocator::with_preferred_node()`], so that the data of each queue
+//! of a high-throughput driver can be allocated on the node of the processors
+//! that service it. If the node is out of memory, other nodes are used.
+//!
+//! [`GuardedAllocator`] wraps either allocator to surround every allocation
+//! with guard patterns, and bug checks when an allocation whose guards were
+//! overwritten is freed, to find buffer overruns without Driver Verifier.
+//!
+//! [`Arena`] preallocates a non-paged buffer and hands out memory from it
+//! without calling into the pool, so that DPCs and interrupt service routines
+//! can allocate at raised `IRQL`. Its allocations are all released at once
+//! when it is reset at `PASSIVE_LEVEL`.
+//!
+//! With the `nightly` feature, both allocators, and references to an
+//! [`Arena`], also implement [`Allocator`](core::alloc::Allocator), so that
+//! individual collections can be allocated from the pool that suits them, and
+//! allocation failures can be handled as `STATUS_INSUFFICIENT_RESOURCES` with
+//! [`try_box_in()`] and [`try_vec_with_capacity_in()`].
+//!
+//! With the `allocation-tracking` feature, every live allocation is recorded
+//! with its size, pool tag and the return addresses of the calls that allocated
+//! it, and [`report_outstanding_allocations()`] prints the allocations that
+//! have not been freed to the kernel debugger. Calling it from the driver's
+//! unload routine reports the allocations the driver leaked. Tracking adds a
+//! lock and a non-paged record to every allocation, so it is meant for
+//! debugging.
 
 #![no_std]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+
+#[cfg(feature = "nightly")]
+extern crate alloc;
+
+#[cfg(feature = "nightly")]
+mod allocator_api;
+mod arena;
+mod guard;
+#[cfg(feature = "allocation-tracking")]
+mod tracking;
 
 use core::alloc::{GlobalAlloc, Layout};
 
+#[cfg(feature = "nightly")]
+pub use allocator_api::*;
+pub use arena::*;
+pub use guard::*;
+#[cfg(feature = "allocation-tracking")]
+pub use tracking::report_outstanding_allocations;
 use wdk_sys::{
-    ntddk::{ExAllocatePool2, ExFreePool},
+    ntddk::{ExAllocatePool2, ExAllocatePool3, ExFreePool, KeGetCurrentNodeNumber},
+    MEMORY_ALLOCATION_ALIGNMENT,
+    POOL_EXTENDED_PARAMETER,
+    POOL_EXTENDED_PARAMETER_TYPE,
+    POOL_FLAGS,
     POOL_FLAG_NON_PAGED,
+    POOL_FLAG_PAGED,
     SIZE_T,
     ULONG,
 };
 
+/// The alignment of every pool allocation
+const POOL_ALIGNMENT: usize = MEMORY_ALLOCATION_ALIGNMENT as usize;
+
+// The value of memory tags are stored in little-endian order, so it is
+// convenient to reverse the order for readability in tooling (ie. Windbg)
+const RUST_TAG: ULONG = u32::from_ne_bytes(*b"rust");
+
 /// Allocator implementation to use with `#[global_allocator]` to allow use of
 /// [`core::alloc`].
 ///
+/// This is the [`NonPagedAllocator`], which every driver can use as its global
+/// allocator.
+pub type WDKAllocator = NonPagedAllocator;
+
+/// The [`NonPagedAllocator`] that tags its allocations with `rust`, so that
+/// `static GLOBAL_ALLOCATOR: WDKAllocator = WDKAllocator;` keeps compiling
+// Named like the unit struct it replaces, so that it is found in the value
+// namespace wherever `WDKAllocator` was used as a value
+#[allow(non_upper_case_globals)]
+pub const WDKAllocator: WDKAllocator = WDKAllocator::new();
+
+/// Allocator that allocates from non-paged pool.
+///
 /// # Safety
 /// This allocator is only safe to use for allocations happening at `IRQL` <=
 /// `DISPATCH_LEVEL`
-pub struct WDKAllocator;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NonPagedAllocator {
+    tag: ULONG,
+    node: Option<u16>,
+}
 
-// The value of memory tags are stored in little-endian order, so it is
-// convenient to reverse the order for readability in tooling (ie. Windbg)
-const RUST_TAG: ULONG = u32::from_ne_bytes(*b"rust");
+/// Allocator that allocates from paged pool.
+///
+/// # Safety
+/// This allocator is only safe to use for allocations happening at `IRQL` <=
+/// `APC_LEVEL`, and the memory it allocates must only be accessed at `IRQL` <=
+/// `APC_LEVEL`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PagedAllocator {
+    tag: ULONG,
+    node: Option<u16>,
+}
+
+macro_rules! impl_pool_allocator {
+    ($($allocator:ident => $pool_flags:ident),* $(,)?) => {
+        $(
+            impl $allocator {
+                /// Construct an allocator that tags its allocations with `rust`
+                #[must_use]
+                pub const fn new() -> Self {
+                    Self {
+                        tag: RUST_TAG,
+                        node: None,
+                    }
+                }
+
+                /// Construct an allocator that tags its allocations with `tag`, which
+                /// is written in the order it is displayed in tooling (ie. `*b"Samp"`
+                /// is displayed as `Samp`)
+                ///
+                /// # Panics
+                ///
+                /// Panics if `tag` is all zeroes, which is not a valid pool tag.
+                /// Since this is a `const fn`, a `static` allocator with such a tag
+                /// does not compile.
+                #[must_use]
+                pub const fn with_tag(tag: [u8; 4]) -> Self {
+                    Self {
+                        tag: pool_tag(tag),
+                        node: None,
+                    }
+                }
+
+                /// Returns the pool tag that the allocator tags its allocations with
+                #[must_use]
+                pub const fn tag(&self) -> ULONG {
+                    self.tag
+                }
+
+                /// Returns a copy of the allocator that allocates from the memory of
+                /// NUMA node `node` when it can, falling back to other nodes when it
+                /// is out of memory
+                ///
+                /// Allocating the data of a queue on the node of the processors that
+                /// service it, such as the node returned by [`current_numa_node()`]
+                /// when the queue is set up on one of them, avoids accessing it
+                /// across nodes.
+                #[must_use]
+                pub const fn with_preferred_node(self, node: u16) -> Self {
+                    Self {
+                        node: Some(node),
+                        ..self
+                    }
+                }
+
+                /// Returns the NUMA node that the allocator prefers to allocate from,
+                /// or `None` if it allocates from any node
+                #[must_use]
+                pub const fn preferred_node(&self) -> Option<u16> {
+                    self.node
+                }
+            }
+
+            impl Default for $allocator {
+                fn default() -> Self {
+                    Self::new()
+                }
+            }
+
+            // SAFETY: This is safe because the WDK allocator:
+            //         1. can never unwind since it can never panic
+            //         2. has implementations of alloc and dealloc that maintain layout
+            //            constraints, including alignments stricter than pool
+            //            allocations
+            unsafe impl GlobalAlloc for $allocator {
+                unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+                    // SAFETY: The caller guarantees the `IRQL` that the pool of
+                    // `$pool_flags` can be allocated from.
+                    unsafe { allocate($pool_flags, layout, self.tag, self.node) }
+                }
+
+                unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+                    // SAFETY: `ptr` was allocated by `alloc` for `layout`, and the caller
+                    // guarantees the `IRQL` that the pool of `$pool_flags` can be freed to.
+                    unsafe { free(ptr, layout) }
+                }
+            }
+        )*
+    };
+}
+
+impl_pool_allocator! {
+    NonPagedAllocator => POOL_FLAG_NON_PAGED,
+    PagedAllocator => POOL_FLAG_PAGED,
+}
+
+/// Returns the NUMA node of the processor that the caller is running on
+///
+/// This can be called at any `IRQL`, but the caller can be moved to a processor
+/// of another node unless it is running at `DISPATCH_LEVEL` or above, or has
+/// set its affinity to the processors of the node.
+#[must_use]
+pub fn current_numa_node() -> u16 {
+    // SAFETY: `KeGetCurrentNodeNumber` can be called at any `IRQL`.
+    unsafe { KeGetCurrentNodeNumber() }
+}
+
+/// Convert `tag`, written in the order it is displayed in tooling, into a pool
+/// tag
+const fn pool_tag(tag: [u8; 4]) -> ULONG {
+    assert!(
+        u32::from_ne_bytes(tag) != 0,
+        "pool tags must not be all zeroes"
+    );
+    u32::from_ne_bytes(tag)
+}
+
+/// Allocate `layout` from the pool of `pool_flags`, tagged with `tag`,
+/// preferring the memory of `node`, and record it as live if allocations are
+/// tracked
+///
+/// # Safety
+///
+/// This must be called at an `IRQL` that the pool of `pool_flags` can be
+/// allocated from.
+unsafe fn allocate(
+    pool_flags: POOL_FLAGS,
+    layout: Layout,
+    tag: ULONG,
+    node: Option<u16>,
+) -> *mut u8 {
+    let ptr;
+    #[cfg(feature = "allocation-tracking")]
+    // SAFETY: The caller guarantees the `IRQL` that the pool of `pool_flags` can be
+    // allocated from.
+    unsafe {
+        ptr = tracking::allocate(pool_flags, layout, tag, node);
+    }
+    #[cfg(not(feature = "allocation-tracking"))]
+    // SAFETY: The caller guarantees the `IRQL` that the pool of `pool_flags` can be
+    // allocated from.
+    unsafe {
+        ptr = allocate_aligned(pool_flags, layout, tag, node);
+    }
+    ptr
+}
 
-// SAFETY: This is safe because the WDK allocator:
-//         1. can never unwind since it can never panic
-//         2. has implementations of alloc and dealloc that maintain layout
-//            constraints (FIXME: Alignment of the layout is currenty not
-//            supported)
-unsafe impl GlobalAlloc for WDKAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+/// Free `ptr`, which was allocated for `layout`, back to the pool it was
+/// allocated from
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`allocate()`] for `layout`, and this must
+/// be called at an `IRQL` that its pool can be freed to.
+unsafe fn free(ptr: *mut u8, layout: Layout) {
+    #[cfg(feature = "allocation-tracking")]
+    // SAFETY: The caller guarantees that `ptr` was returned by `allocate()`, which
+    // tracked it, for `layout`, and the `IRQL` that its pool can be freed to.
+    unsafe {
+        tracking::free(ptr, layout);
+    }
+    #[cfg(not(feature = "allocation-tracking"))]
+    // SAFETY: The caller guarantees that `ptr` was returned by `allocate()`, which
+    // did not track it, for `layout`, and the `IRQL` that its pool can be freed to.
+    unsafe {
+        free_aligned(ptr, layout);
+    }
+}
+
+/// Allocate `layout` from the pool of `pool_flags`, tagged with `tag`,
+/// preferring the memory of `node`
+///
+/// Pool allocations are aligned to `MEMORY_ALLOCATION_ALIGNMENT`. Layouts that
+/// are aligned more strictly, such as to a cache line or a page, are
+/// over-allocated by their alignment, and the address of the allocation is
+/// stored just before the aligned address that is returned.
+///
+/// # Safety
+///
+/// This must be called at an `IRQL` that the pool of `pool_flags` can be
+/// allocated from.
+unsafe fn allocate_aligned(
+    pool_flags: POOL_FLAGS,
+    layout: Layout,
+    tag: ULONG,
+    node: Option<u16>,
+) -> *mut u8 {
+    if layout.align() <= POOL_ALIGNMENT {
+        // SAFETY: The caller guarantees the `IRQL` that the pool of `pool_flags` can
+        // be allocated from.
+        return unsafe { allocate_pool(pool_flags, layout.size(), tag, node) };
+    }
+
+    let Some(size) = layout.size().checked_add(layout.align()) else {
+        return core::ptr::null_mut();
+    };
+    let ptr;
+    // SAFETY: The caller guarantees the `IRQL` that the pool of `pool_flags` can be
+    // allocated from.
+    unsafe {
+        ptr = allocate_pool(pool_flags, size, tag, node);
+    }
+    if ptr.is_null() {
+        return ptr;
+    }
+
+    // `ptr` is aligned to `POOL_ALIGNMENT`, so the aligned address is at least
+    // `POOL_ALIGNMENT` bytes into the allocation, leaving room for `ptr` before it,
+    // and at most `layout.align()` bytes into it, leaving room for `layout.size()`
+    // bytes after it
+    let offset = match ptr.align_offset(layout.align()) {
+        0 => layout.align(),
+        offset => offset,
+    };
+    let aligned = ptr.wrapping_add(offset);
+    // `aligned` is aligned to more than `POOL_ALIGNMENT`, so the `*mut u8` before
+    // it is aligned
+    #[allow(clippy::cast_ptr_alignment)]
+    let original = aligned.cast::<*mut u8>().wrapping_sub(1);
+    // SAFETY: `original` is within the allocation, and is aligned for a `*mut u8`.
+    unsafe {
+        original.write(ptr);
+    }
+    aligned
+}
+
+/// Free `ptr`, which was allocated for `layout`, back to the pool it was
+/// allocated from
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`allocate_aligned()`] for `layout`, and
+/// this must be called at an `IRQL` that its pool can be freed to.
+unsafe fn free_aligned(ptr: *mut u8, layout: Layout) {
+    let ptr = if layout.align() <= POOL_ALIGNMENT {
+        ptr
+    } else {
+        // `allocate_aligned()` aligned `ptr` to more than `POOL_ALIGNMENT`, so the
+        // `*mut u8` before it is aligned
+        #[allow(clippy::cast_ptr_alignment)]
+        let original = ptr.cast::<*mut u8>().wrapping_sub(1);
+        // SAFETY: The caller guarantees that `ptr` was returned by
+        // `allocate_aligned()` for `layout`, which stored the address of the allocation
+        // in `original`.
+        unsafe { original.read() }
+    };
+    // SAFETY: The caller guarantees that `ptr` was allocated by `ExAllocatePool2`,
+    // and that `ExFreePool` is called at an `IRQL` that its pool can be freed to
+    unsafe {
+        ExFreePool(ptr.cast());
+    }
+}
+
+/// Allocate `size` bytes from the pool of `pool_flags`, tagged with `tag`,
+/// preferring the memory of `node`
+///
+/// # Safety
+///
+/// This must be called at an `IRQL` that the pool of `pool_flags` can be
+/// allocated from.
+unsafe fn allocate_pool(
+    pool_flags: POOL_FLAGS,
+    size: usize,
+    tag: ULONG,
+    node: Option<u16>,
+) -> *mut u8 {
+    let Some(node) = node else {
         let ptr =
-            // SAFETY: `ExAllocatePool2` is safe to call from any `IRQL` <= `DISPATCH_LEVEL` since its allocating from `POOL_FLAG_NON_PAGED`
+            // SAFETY: The caller guarantees that `ExAllocatePool2` is called at an `IRQL` that the pool of `pool_flags` can be allocated from
             unsafe {
-                ExAllocatePool2(POOL_FLAG_NON_PAGED, layout.size() as SIZE_T, RUST_TAG)
+                ExAllocatePool2(pool_flags, size as SIZE_T, tag)
             };
-        if ptr.is_null() {
-            return core::ptr::null_mut();
-        }
-        ptr.cast()
-    }
+        return ptr.cast();
+    };
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        // SAFETY: `ExFreePool` is safe to call from any `IRQL` <= `DISPATCH_LEVEL`
-        // since its freeing memory allocated from `POOL_FLAG_NON_PAGED` in `alloc`
+    // `POOL_EXTENDED_PARAMETER_TYPE` values are small, positive values
+    #[allow(clippy::cast_sign_loss)]
+    let parameter_type = POOL_EXTENDED_PARAMETER_TYPE::PoolExtendedParameterNumaNode as u64;
+    let mut preferred_node = POOL_EXTENDED_PARAMETER::default();
+    preferred_node.__bindgen_anon_1.set_Type(parameter_type);
+    // Other nodes are used when `node` is out of memory
+    preferred_node.__bindgen_anon_1.set_Optional(1);
+    preferred_node.__bindgen_anon_2.PreferredNode = node.into();
+    let ptr =
+        // SAFETY: The caller guarantees that `ExAllocatePool3` is called at an `IRQL` that the pool of `pool_flags` can be allocated from, and `preferred_node` is the single extended parameter passed to it
         unsafe {
-            ExFreePool(ptr.cast());
-        }
+            ExAllocatePool3(
+                pool_flags,
+                size as SIZE_T,
+                tag,
+                core::ptr::from_ref(&preferred_node),
+                1,
+            )
+        };
+    if ptr.is_null() {
+        return core::ptr::null_mut();
     }
+    ptr.cast()
 }
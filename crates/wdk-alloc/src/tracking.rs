@@ -0,0 +1,299 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Tracking of live allocations, enabled by the `allocation-tracking`
+//! feature, so that the allocations a driver leaks can be reported when it is
+//! unloaded.
+
+use core::{alloc::Layout, cell::UnsafeCell};
+
+use wdk_sys::{
+    ntddk::{
+        DbgPrint,
+        ExFreePool,
+        KeAcquireSpinLockRaiseToDpc,
+        KeReleaseSpinLock,
+        RtlCaptureStackBackTrace,
+    },
+    KSPIN_LOCK,
+    POOL_FLAGS,
+    POOL_FLAG_NON_PAGED,
+    PVOID,
+    ULONG,
+};
+
+use crate::{allocate_aligned, allocate_pool, free_aligned};
+
+/// The number of return addresses recorded for each allocation
+const CALLERS: usize = 6;
+
+/// The record of a live allocation. Records are always allocated from
+/// non-paged pool, so that the list of them can be walked at
+/// `DISPATCH_LEVEL`, even if the allocations are paged.
+struct Record {
+    previous: *mut Self,
+    next: *mut Self,
+    address: *mut u8,
+    size: usize,
+    tag: ULONG,
+    callers: [PVOID; CALLERS],
+}
+
+/// The records of every live allocation, in a doubly linked list protected by
+/// a spin lock
+struct AllocationList {
+    lock: UnsafeCell<KSPIN_LOCK>,
+    head: UnsafeCell<*mut Record>,
+}
+
+// SAFETY: `head`, and the records it links, are only accessed while `lock` is
+// held.
+unsafe impl Sync for AllocationList {}
+
+impl AllocationList {
+    /// Run `f` on the head of the list while holding its lock
+    fn with_head<R>(&self, f: impl FnOnce(&mut *mut Record) -> R) -> R {
+        let irql;
+        // SAFETY: `lock` is a spin lock, which is initialized by being zeroed, and
+        // this is called at `IRQL` <= `DISPATCH_LEVEL`.
+        unsafe {
+            irql = KeAcquireSpinLockRaiseToDpc(self.lock.get());
+        }
+        let head;
+        // SAFETY: `head` is only accessed while `lock` is held, which it is until
+        // after `f` returns.
+        unsafe {
+            head = &mut *self.head.get();
+        }
+        let result = f(head);
+        // SAFETY: `lock` was acquired above, raising from `irql`.
+        unsafe {
+            KeReleaseSpinLock(self.lock.get(), irql);
+        }
+        result
+    }
+}
+
+static LIVE_ALLOCATIONS: AllocationList = AllocationList {
+    lock: UnsafeCell::new(0),
+    head: UnsafeCell::new(core::ptr::null_mut()),
+};
+
+/// Returns the layout of an allocation for `layout` that starts with the
+/// address of its record, and the offset of the memory for `layout` in it
+fn tracked_layout(layout: Layout) -> Option<(Layout, usize)> {
+    Layout::new::<*mut Record>().extend(layout).ok()
+}
+
+/// Allocate `layout` from the pool of `pool_flags`, tagged with `tag`,
+/// preferring the memory of `node`, and record the allocation as live
+///
+/// # Safety
+///
+/// This must be called at an `IRQL` that the pool of `pool_flags` can be
+/// allocated from.
+pub unsafe fn allocate(
+    pool_flags: POOL_FLAGS,
+    layout: Layout,
+    tag: ULONG,
+    node: Option<u16>,
+) -> *mut u8 {
+    let Some((tracked_layout, offset)) = tracked_layout(layout) else {
+        return core::ptr::null_mut();
+    };
+
+    let record: *mut Record;
+    // SAFETY: Non-paged pool can be allocated from at any `IRQL` that the pool of
+    // `pool_flags` can be allocated from.
+    unsafe {
+        record = allocate_pool(POOL_FLAG_NON_PAGED, size_of::<Record>(), tag, node).cast();
+    }
+    if record.is_null() {
+        return core::ptr::null_mut();
+    }
+    let base;
+    // SAFETY: The caller guarantees the `IRQL` that the pool of `pool_flags` can be
+    // allocated from.
+    unsafe {
+        base = allocate_aligned(pool_flags, tracked_layout, tag, node);
+    }
+    if base.is_null() {
+        // SAFETY: `record` was allocated from non-paged pool above, and is not used
+        // after this.
+        unsafe {
+            ExFreePool(record.cast());
+        }
+        return core::ptr::null_mut();
+    }
+    // `base` is aligned for `tracked_layout`, which is at least as aligned as the
+    // `*mut Record` it starts with
+    #[allow(clippy::cast_ptr_alignment)]
+    let record_address = base.cast::<*mut Record>();
+    // SAFETY: `record_address` is the start of the allocation for `tracked_layout`,
+    // which starts with a `*mut Record`.
+    unsafe {
+        record_address.write(record);
+    }
+    let address = base.wrapping_add(offset);
+
+    let mut callers = [core::ptr::null_mut(); CALLERS];
+    // `CALLERS` is much smaller than `ULONG::MAX`
+    #[allow(clippy::cast_possible_truncation)]
+    let frames_to_capture = CALLERS as ULONG;
+    // SAFETY: `callers` is valid for writes of `CALLERS` return addresses, and a
+    // null `BackTraceHash` is allowed. Frames that are not captured are left null.
+    unsafe {
+        RtlCaptureStackBackTrace(
+            1,
+            frames_to_capture,
+            callers.as_mut_ptr(),
+            core::ptr::null_mut(),
+        );
+    }
+    // SAFETY: `record` was allocated for a `Record` above, and is not shared until
+    // it is linked into the list.
+    unsafe {
+        record.write(Record {
+            previous: core::ptr::null_mut(),
+            next: core::ptr::null_mut(),
+            address,
+            size: layout.size(),
+            tag,
+            callers,
+        });
+    }
+
+    LIVE_ALLOCATIONS.with_head(|head| {
+        let next = *head;
+        if !next.is_null() {
+            // SAFETY: Records in the list are live, and are only accessed while its lock
+            // is held.
+            unsafe {
+                (*next).previous = record;
+            }
+        }
+        // SAFETY: `record` was initialized above.
+        unsafe {
+            (*record).next = next;
+        }
+        *head = record;
+    });
+    address
+}
+
+/// Remove the record of `ptr`, which was allocated for `layout`, and free it
+/// back to the pool it was allocated from
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`allocate()`] for `layout`, and this must
+/// be called at an `IRQL` that its pool can be freed to.
+pub unsafe fn free(ptr: *mut u8, layout: Layout) {
+    let tracked = tracked_layout(layout);
+    debug_assert!(
+        tracked.is_some(),
+        "the layout of a live allocation should be trackable"
+    );
+    // `allocate()` returns null for layouts that cannot be tracked, so the caller
+    // cannot have an allocation of `layout` to free
+    let Some((tracked_layout, offset)) = tracked else {
+        return;
+    };
+    let base = ptr.wrapping_sub(offset);
+    // `base` is aligned for `tracked_layout`, which is at least as aligned as the
+    // `*mut Record` it starts with
+    #[allow(clippy::cast_ptr_alignment)]
+    let record_address = base.cast::<*mut Record>();
+    let record;
+    // SAFETY: The caller guarantees that `ptr` was returned by `allocate()` for
+    // `layout`, which wrote the address of its record to `record_address`.
+    unsafe {
+        record = record_address.read();
+    }
+
+    LIVE_ALLOCATIONS.with_head(|head| {
+        let (previous, next);
+        // SAFETY: `record` is in the list, and is only accessed while its lock is
+        // held.
+        unsafe {
+            previous = (*record).previous;
+        }
+        // SAFETY: See above.
+        unsafe {
+            next = (*record).next;
+        }
+        if previous.is_null() {
+            *head = next;
+        } else {
+            // SAFETY: See above.
+            unsafe {
+                (*previous).next = next;
+            }
+        }
+        if !next.is_null() {
+            // SAFETY: See above.
+            unsafe {
+                (*next).previous = previous;
+            }
+        }
+    });
+
+    // SAFETY: `record` was allocated from non-paged pool by `allocate()`, and was
+    // removed from the list above.
+    unsafe {
+        ExFreePool(record.cast());
+    }
+    // SAFETY: `base` was returned by `allocate_aligned()` for `tracked_layout`, and
+    // the caller guarantees the `IRQL` that its pool can be freed to.
+    unsafe {
+        free_aligned(base, tracked_layout);
+    }
+}
+
+/// Print every allocation that has not been freed to the kernel debugger,
+/// returning how many there are
+///
+/// Call this when the driver is unloaded, after it has freed everything it
+/// allocated, to find the allocations it leaked. Each allocation is printed
+/// with its size, its pool tag and the return addresses of the calls that
+/// allocated it, which can be resolved with `ln` in the debugger.
+///
+/// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+#[must_use = "the number of outstanding allocations should be checked"]
+pub fn report_outstanding_allocations() -> usize {
+    LIVE_ALLOCATIONS.with_head(|head| {
+        let mut count = 0;
+        let mut record = *head;
+        while !record.is_null() {
+            let current: &Record;
+            // SAFETY: Records in the list are live, and are only accessed while its lock
+            // is held.
+            unsafe {
+                current = &*record;
+            }
+            let tag = current.tag.to_ne_bytes();
+            let callers = current.callers;
+            // SAFETY: The format string is null-terminated, and its conversions match
+            // the types of the arguments that follow it. `%.4s` reads no more than the 4
+            // bytes of `tag`.
+            unsafe {
+                DbgPrint(
+                    c"wdk-alloc: outstanding allocation of %Iu bytes tagged %.4s at %p, allocated from %p %p %p %p %p %p\n"
+                        .as_ptr(),
+                    current.size,
+                    tag.as_ptr(),
+                    current.address,
+                    callers[0],
+                    callers[1],
+                    callers[2],
+                    callers[3],
+                    callers[4],
+                    callers[5],
+                );
+            }
+            count += 1;
+            record = current.next;
+        }
+        count
+    })
+}
@@ -5,6 +5,7 @@
 //! [`wdk-sys`]'s direct bindings to the Windows Driver Kit (WDK).
 
 use std::{
+    ffi::CString,
     io::{BufReader, Read},
     path::PathBuf,
     process::{Command, Stdio},
@@ -13,9 +14,10 @@
 use cargo_metadata::{Message, MetadataCommand, PackageId};
 use itertools::Itertools;
 use proc_macro::TokenStream;
-use proc_macro2::{Span, TokenStream as TokenStream2};
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote};
 use syn::{
+    ext::IdentExt,
     parse::{Parse, ParseStream},
     parse2,
     parse_file,
@@ -24,14 +26,20 @@
     AngleBracketedGenericArguments,
     Attribute,
     BareFnArg,
+    Data,
+    DeriveInput,
     Error,
     Expr,
     ExprCall,
+    Field,
+    Fields,
     File,
     GenericArgument,
     Ident,
     Item,
+    ItemFn,
     ItemType,
+    LitStr,
     Path,
     PathArguments,
     PathSegment,
@@ -91,10 +99,144 @@ pub fn call_unsafe_wdf_function_binding(input_tokens: TokenStream) -> TokenStrea
     call_unsafe_wdf_function_binding_impl(TokenStream2::from(input_tokens)).into()
 }
 
+/// An attribute macro that generates the `DriverEntry` function of a driver
+/// from a safe Rust function.
+///
+/// The annotated function is passed the `DRIVER_OBJECT` and registry path
+/// that `DriverEntry` is called with, and must return a [`Result`] whose error
+/// type can be converted into an `NTSTATUS`. A `DriverEntry` function with the
+/// correct name and calling convention is generated, which converts the
+/// pointers it is passed into references, calls the annotated function, and
+/// converts its result into the `NTSTATUS` returned to the kernel. Since the
+/// generated function is `extern "system"`, a panic can never unwind out of
+/// it into the kernel.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// use wdk::wdf::{Driver, DriverConfig};
+/// use wdk_sys::{DRIVER_OBJECT, NTSTATUS, UNICODE_STRING};
+///
+/// #[wdk_sys::macros::driver_entry]
+/// fn driver_entry(
+///     driver_object: &mut DRIVER_OBJECT,
+///     registry_path: &UNICODE_STRING,
+/// ) -> Result<Driver, NTSTATUS> {
+///     Driver::try_new(driver_object, registry_path, DriverConfig::new())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn driver_entry(attribute_tokens: TokenStream, item_tokens: TokenStream) -> TokenStream {
+    driver_entry_impl(
+        TokenStream2::from(attribute_tokens),
+        TokenStream2::from(item_tokens),
+    )
+    .into()
+}
+
+/// A procedural macro that declares a type as a WDF object context type,
+/// replacing the `WDF_DECLARE_CONTEXT_TYPE_WITH_NAME` C macro.
+///
+/// This generates the `static` `WDF_OBJECT_CONTEXT_TYPE_INFO` of the type, with
+/// the size of the type as its context size, and an implementation of
+/// `wdk::wdf::ObjectContext` which refers to it. Values of the type can then be
+/// attached to WDF objects with `ObjectContext::attach`, and accessed with
+/// `wdk::wdf::get_context`. The value is dropped from the object's
+/// `EvtCleanupCallback`.
+///
+/// Generic types are not supported, since each context type needs its own
+/// `static` type info.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// use wdk::wdf::{get_context, ObjectContext, WdfObjectContext};
+///
+/// #[derive(WdfObjectContext)]
+/// struct DeviceContext {
+///     open_count: AtomicU32,
+/// }
+///
+/// DeviceContext::attach(&device, DeviceContext { open_count: AtomicU32::new(0) })?;
+/// let context = get_context::<DeviceContext>(&device).unwrap();
+/// ```
+#[proc_macro_derive(WdfObjectContext)]
+pub fn derive_wdf_object_context(input_tokens: TokenStream) -> TokenStream {
+    derive_wdf_object_context_impl(TokenStream2::from(input_tokens)).into()
+}
+
+/// A procedural macro that implements `wdk::wdf::FromRegistry` for a struct,
+/// which loads each of its fields from a value of a registry key.
+///
+/// Each field is read with `wdk::wdf::RegistryValue` from the value named
+/// after the field in `PascalCase` (ex. `queue_depth` is read from
+/// `QueueDepth`). Fields can be annotated with `#[registry(...)]`, which
+/// takes:
+///
+/// - `name = "..."`, the name of the value to read the field from
+/// - `default`, to use the [`Default`] of the field's type if the value does
+///   not exist
+/// - `default = ...`, to use the given expression if the value does not exist
+/// - `validate = ...`, a function that takes a reference to the field's value
+///   and returns an error `NTSTATUS` if it is not valid
+///
+/// Loading fails with the name of the first value that does not exist and has
+/// no default, that could not be read, or that is not valid.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// use wdk::wdf::FromRegistry;
+///
+/// #[derive(FromRegistry)]
+/// struct Config {
+///     #[registry(default = 16, validate = check_queue_depth)]
+///     queue_depth: u32,
+///     #[registry(name = "EnableDma", default)]
+///     dma: bool,
+/// }
+///
+/// let config: Config = driver.load_parameters()?;
+/// ```
+#[proc_macro_derive(FromRegistry, attributes(registry))]
+pub fn derive_from_registry(input_tokens: TokenStream) -> TokenStream {
+    derive_from_registry_impl(TokenStream2::from(input_tokens)).into()
+}
+
+/// A procedural macro that builds a `UNICODE_STRING` from a string literal at
+/// compile time, replacing the `RTL_CONSTANT_STRING` C macro.
+///
+/// The literal is encoded as UTF-16 when the macro is expanded, into a
+/// null-terminated buffer with `'static` lifetime, and the macro evaluates to
+/// a `UNICODE_STRING` describing it. `Length` counts the bytes of the string,
+/// and `MaximumLength` also counts the terminating null, so the expression can
+/// be used to initialize a `const`, without any conversion at runtime. Since
+/// `UNICODE_STRING` contains a raw pointer, it cannot initialize a `static`
+/// directly.
+///
+/// The literal must be short enough that `MaximumLength` fits in a `USHORT`,
+/// which is at most 32766 UTF-16 code units.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// use wdk_sys::UNICODE_STRING;
+/// use wdk_macros::unicode_string;
+///
+/// const DEVICE_NAME: UNICODE_STRING = unicode_string!(r"\Device\Sample");
+/// ```
+#[proc_macro]
+pub fn unicode_string(input_tokens: TokenStream) -> TokenStream {
+    unicode_string_impl(TokenStream2::from(input_tokens)).into()
+}
+
 /// A trait to provide additional functionality to the `String` type
 trait StringExt {
     /// Convert a string to `snake_case`
     fn to_snake_case(&self) -> String;
+
+    /// Convert a `snake_case` string to `PascalCase`
+    fn to_pascal_case(&self) -> String;
 }
 
 /// Struct storing the input tokens directly parsed from calls to
@@ -166,6 +308,73 @@ fn to_snake_case(&self) -> String {
 
         snake_case_string
     }
+
+    fn to_pascal_case(&self) -> String {
+        self.split('_')
+            .flat_map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first_char| first_char.to_ascii_uppercase())
+                    .into_iter()
+                    .chain(chars)
+            })
+            .collect()
+    }
+}
+
+/// The options of a field of a `#[derive(FromRegistry)]` struct, parsed from
+/// its `#[registry(...)]` attributes
+#[derive(Default)]
+struct RegistryFieldOptions {
+    /// The name of the value to read the field from
+    name: Option<LitStr>,
+    /// The default of the field if the value does not exist
+    default: RegistryFieldDefault,
+    /// The function that validates the field's value
+    validate: Option<Expr>,
+}
+
+/// The default of a field of a `#[derive(FromRegistry)]` struct, which it
+/// takes if its value does not exist
+#[derive(Default)]
+enum RegistryFieldDefault {
+    /// The field has no default, so the value must exist
+    #[default]
+    None,
+    /// The field takes the [`Default`] of its type
+    Trait,
+    /// The field takes the value of the expression
+    Expr(Expr),
+}
+
+impl RegistryFieldOptions {
+    fn parse(field: &Field) -> Result<Self> {
+        let mut options = Self::default();
+        for attribute in field
+            .attrs
+            .iter()
+            .filter(|attribute| attribute.path().is_ident("registry"))
+        {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    options.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    options.default = if meta.input.peek(Token![=]) {
+                        RegistryFieldDefault::Expr(meta.value()?.parse()?)
+                    } else {
+                        RegistryFieldDefault::Trait
+                    };
+                } else if meta.path.is_ident("validate") {
+                    options.validate = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unsupported `registry` option"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(options)
+    }
 }
 
 impl Parse for Inputs {
@@ -339,6 +548,242 @@ fn call_unsafe_wdf_function_binding_impl(input_tokens: TokenStream2) -> TokenStr
         .assemble_final_output()
 }
 
+fn driver_entry_impl(attribute_tokens: TokenStream2, item_tokens: TokenStream2) -> TokenStream2 {
+    if !attribute_tokens.is_empty() {
+        return Error::new_spanned(
+            attribute_tokens,
+            "`driver_entry` does not take any arguments",
+        )
+        .to_compile_error();
+    }
+
+    let driver_entry_fn = match parse2::<ItemFn>(item_tokens) {
+        Ok(driver_entry_fn) => driver_entry_fn,
+        Err(err) => return err.to_compile_error(),
+    };
+    if let Err(err) = validate_driver_entry_signature(&driver_entry_fn.sig) {
+        return err.to_compile_error();
+    }
+
+    let driver_entry_fn_ident = &driver_entry_fn.sig.ident;
+    quote! {
+        #driver_entry_fn
+
+        const _: () = {
+            #[export_name = "DriverEntry"] // WDF expects a symbol with the name DriverEntry
+            unsafe extern "system" fn driver_entry_shim(
+                driver_object: ::wdk_sys::PDRIVER_OBJECT,
+                registry_path: ::wdk_sys::PCUNICODE_STRING,
+            ) -> ::wdk_sys::NTSTATUS {
+                let driver_object = unsafe { &mut *driver_object };
+                let registry_path = unsafe { &*registry_path };
+
+                match #driver_entry_fn_ident(driver_object, registry_path) {
+                    ::core::result::Result::Ok(_) => ::wdk_sys::STATUS_SUCCESS,
+                    ::core::result::Result::Err(error) => {
+                        ::core::convert::Into::<::wdk_sys::NTSTATUS>::into(error)
+                    }
+                }
+            }
+        };
+    }
+}
+
+fn derive_wdf_object_context_impl(input_tokens: TokenStream2) -> TokenStream2 {
+    let derive_input = match parse2::<DeriveInput>(input_tokens) {
+        Ok(derive_input) => derive_input,
+        Err(err) => return err.to_compile_error(),
+    };
+    if !derive_input.generics.params.is_empty() {
+        return Error::new_spanned(
+            &derive_input.generics,
+            "`WdfObjectContext` cannot be derived for generic types",
+        )
+        .to_compile_error();
+    }
+
+    let context_type_ident = &derive_input.ident;
+    let context_name = Literal::c_string(
+        &CString::new(context_type_ident.to_string())
+            .expect("identifiers should not contain nul bytes"),
+    );
+    quote! {
+        const _: () = {
+            static CONTEXT_TYPE_INFO: ::wdk::wdf::ContextTypeInfo =
+                ::wdk::wdf::ContextTypeInfo::new::<#context_type_ident>(#context_name, &CONTEXT_TYPE_INFO);
+
+            unsafe impl ::wdk::wdf::ObjectContext for #context_type_ident {
+                fn type_info() -> &'static ::wdk::wdf::ContextTypeInfo {
+                    &CONTEXT_TYPE_INFO
+                }
+            }
+        };
+    }
+}
+
+fn derive_from_registry_impl(input_tokens: TokenStream2) -> TokenStream2 {
+    let derive_input = match parse2::<DeriveInput>(input_tokens) {
+        Ok(derive_input) => derive_input,
+        Err(err) => return err.to_compile_error(),
+    };
+    let fields = match &derive_input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Error::new_spanned(
+                    &derive_input.ident,
+                    "`FromRegistry` can only be derived for structs with named fields",
+                )
+                .to_compile_error();
+            }
+        },
+        _ => {
+            return Error::new_spanned(
+                &derive_input.ident,
+                "`FromRegistry` can only be derived for structs with named fields",
+            )
+            .to_compile_error();
+        }
+    };
+
+    let mut field_initializers = Vec::with_capacity(fields.len());
+    for field in fields {
+        let options = match RegistryFieldOptions::parse(field) {
+            Ok(options) => options,
+            Err(err) => return err.to_compile_error(),
+        };
+        let field_ident = field
+            .ident
+            .as_ref()
+            .expect("named fields should have identifiers");
+        let field_type = &field.ty;
+        let value_name = options.name.unwrap_or_else(|| {
+            LitStr::new(
+                &field_ident.unraw().to_string().to_pascal_case(),
+                field_ident.span(),
+            )
+        });
+        let missing_value = match options.default {
+            RegistryFieldDefault::Expr(default) => quote! { #default },
+            RegistryFieldDefault::Trait => quote! { ::core::default::Default::default() },
+            RegistryFieldDefault::None => quote! {
+                return ::core::result::Result::Err(::wdk::wdf::ConfigError::missing(#value_name))
+            },
+        };
+        let validation = options.validate.map(|validate| {
+            quote! {
+                if let ::core::result::Result::Err(status) = (#validate)(&value) {
+                    return ::core::result::Result::Err(::wdk::wdf::ConfigError::new(#value_name, status));
+                }
+            }
+        });
+        field_initializers.push(quote! {
+            #field_ident: {
+                let value: #field_type = match <#field_type as ::wdk::wdf::RegistryValue>::query_optional(key, #value_name) {
+                    ::core::result::Result::Ok(::core::option::Option::Some(value)) => value,
+                    ::core::result::Result::Ok(::core::option::Option::None) => #missing_value,
+                    ::core::result::Result::Err(status) => {
+                        return ::core::result::Result::Err(::wdk::wdf::ConfigError::new(#value_name, status));
+                    }
+                };
+                #validation
+                value
+            }
+        });
+    }
+
+    let struct_ident = &derive_input.ident;
+    let (impl_generics, type_generics, where_clause) = derive_input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::wdk::wdf::FromRegistry for #struct_ident #type_generics #where_clause {
+            fn from_registry(
+                key: &::wdk::wdf::RegistryKey,
+            ) -> ::core::result::Result<Self, ::wdk::wdf::ConfigError> {
+                ::core::result::Result::Ok(Self {
+                    #(#field_initializers,)*
+                })
+            }
+        }
+    }
+}
+
+fn unicode_string_impl(input_tokens: TokenStream2) -> TokenStream2 {
+    let string_literal = match parse2::<LitStr>(input_tokens) {
+        Ok(string_literal) => string_literal,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let code_units = string_literal.value().encode_utf16().collect::<Vec<_>>();
+    // `MaximumLength` is a `USHORT` count of the bytes of the string and its
+    // terminating null
+    let Some(maximum_length) = (code_units.len() + 1)
+        .checked_mul(size_of::<u16>())
+        .and_then(|maximum_length| u16::try_from(maximum_length).ok())
+    else {
+        return Error::new_spanned(
+            string_literal,
+            "`unicode_string` literal is too long for a `UNICODE_STRING`",
+        )
+        .to_compile_error();
+    };
+    let length = Literal::u16_unsuffixed(maximum_length - 2);
+    let maximum_length = Literal::u16_unsuffixed(maximum_length);
+    let code_units = code_units.into_iter().map(Literal::u16_unsuffixed);
+    quote! {
+        {
+            const BUFFER: &[u16] = &[#(#code_units,)* 0];
+            ::wdk_sys::UNICODE_STRING {
+                Length: #length,
+                MaximumLength: #maximum_length,
+                Buffer: BUFFER.as_ptr().cast_mut(),
+            }
+        }
+    }
+}
+
+/// Check that the signature of a function annotated with `#[driver_entry]`
+/// can be called by the generated `DriverEntry` function
+fn validate_driver_entry_signature(signature: &Signature) -> Result<()> {
+    if let Some(constness) = &signature.constness {
+        return Err(Error::new_spanned(
+            constness,
+            "`driver_entry` function must not be const",
+        ));
+    }
+    if let Some(asyncness) = &signature.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            "`driver_entry` function must not be async",
+        ));
+    }
+    if let Some(abi) = &signature.abi {
+        return Err(Error::new_spanned(
+            abi,
+            "`driver_entry` function must use the Rust ABI, since the `DriverEntry` function with \
+             the system ABI is generated",
+        ));
+    }
+    if !signature.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &signature.generics,
+            "`driver_entry` function must not be generic",
+        ));
+    }
+    if signature.inputs.len() != 2 {
+        return Err(Error::new_spanned(
+            &signature.inputs,
+            "`driver_entry` function must take a `&mut DRIVER_OBJECT` and a `&UNICODE_STRING`",
+        ));
+    }
+    if matches!(signature.output, ReturnType::Default) {
+        return Err(Error::new_spanned(
+            signature,
+            "`driver_entry` function must return a `Result`",
+        ));
+    }
+    Ok(())
+}
+
 /// Generate the function parameters and return type corresponding to the
 /// function signature of the `function_pointer_type` type alias in the AST for
 /// types.rs
@@ -844,8 +1289,10 @@ fn compute_fn_parameters(
                     }
                 };
 
-            parameter_type_path_segments
-                .insert(0, syn::PathSegment::from(format_ident!("wdk_sys")));
+            if !is_primitive_type(parameter_type_path_segments) {
+                parameter_type_path_segments
+                    .insert(0, syn::PathSegment::from(format_ident!("wdk_sys")));
+            }
             Ok(bare_fn_arg)
         })
         .collect::<Result<_>>()?;
@@ -894,13 +1341,15 @@ fn compute_return_type(bare_fn_type: &syn::TypeBareFn, error_span: Span) -> Resu
                             ));
                         };
                         let mut segments = segments.clone();
-                        segments.insert(
-                            0,
-                            PathSegment {
-                                ident: format_ident!("wdk_sys"),
-                                arguments: PathArguments::None,
-                            },
-                        );
+                        if !is_primitive_type(&segments) {
+                            segments.insert(
+                                0,
+                                PathSegment {
+                                    ident: format_ident!("wdk_sys"),
+                                    arguments: PathArguments::None,
+                                },
+                            );
+                        }
                         segments
                     },
                 },
@@ -910,6 +1359,20 @@ fn compute_return_type(bare_fn_type: &syn::TypeBareFn, error_span: Span) -> Resu
     Ok(return_type)
 }
 
+/// Returns whether the [`PathSegment`]s of a type are a primitive type, such as
+/// `usize`, which must not be prepended with `wdk_sys::`
+fn is_primitive_type(segments: &Punctuated<PathSegment, syn::token::PathSep>) -> bool {
+    const PRIMITIVE_TYPES: [&str; 15] = [
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+        "u32", "u64", "usize",
+    ];
+
+    segments.len() == 1
+        && PRIMITIVE_TYPES
+            .iter()
+            .any(|primitive_type| segments[0].ident == primitive_type)
+}
+
 /// Generate the `#[must_use]` attribute if the return type is not `()`
 fn generate_must_use_attribute(return_type: &ReturnType) -> Option<Attribute> {
     if matches!(return_type, ReturnType::Type(..)) {
@@ -1010,6 +1473,26 @@ fn snake_case_with_leading_underscore() {
         }
     }
 
+    mod to_pascal_case {
+        use super::*;
+
+        #[test]
+        fn snake_case() {
+            let input = "queue_depth".to_string();
+            let expected = "QueueDepth";
+
+            pretty_assert_eq!(input.to_pascal_case(), expected);
+        }
+
+        #[test]
+        fn single_word() {
+            let input = "timeout".to_string();
+            let expected = "Timeout";
+
+            pretty_assert_eq!(input.to_pascal_case(), expected);
+        }
+    }
+
     mod inputs {
         use super::*;
 
@@ -1151,6 +1634,388 @@ fn valid_input_with_no_arguments() {
         }
     }
 
+    mod driver_entry_impl {
+        use super::*;
+
+        #[test]
+        fn valid_input() {
+            let item_tokens = quote! {
+                fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS> {
+                    Driver::try_new(driver_object, registry_path, DriverConfig::new())
+                }
+            };
+            let expected = quote! {
+                fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS> {
+                    Driver::try_new(driver_object, registry_path, DriverConfig::new())
+                }
+
+                const _: () = {
+                    #[export_name = "DriverEntry"]
+                    unsafe extern "system" fn driver_entry_shim(
+                        driver_object: ::wdk_sys::PDRIVER_OBJECT,
+                        registry_path: ::wdk_sys::PCUNICODE_STRING,
+                    ) -> ::wdk_sys::NTSTATUS {
+                        let driver_object = unsafe { &mut *driver_object };
+                        let registry_path = unsafe { &*registry_path };
+
+                        match driver_entry(driver_object, registry_path) {
+                            ::core::result::Result::Ok(_) => ::wdk_sys::STATUS_SUCCESS,
+                            ::core::result::Result::Err(error) => {
+                                ::core::convert::Into::<::wdk_sys::NTSTATUS>::into(error)
+                            }
+                        }
+                    }
+                };
+            };
+
+            pretty_assert_eq!(
+                driver_entry_impl(TokenStream2::new(), item_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn attribute_arguments() {
+            let attribute_tokens = quote! { unexpected };
+            let item_tokens = quote! {
+                fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS> {
+                    Driver::try_new(driver_object, registry_path, DriverConfig::new())
+                }
+            };
+            let expected = Error::new(
+                Span::call_site(),
+                "`driver_entry` does not take any arguments",
+            );
+
+            pretty_assert_eq!(
+                driver_entry_impl(attribute_tokens, item_tokens).to_string(),
+                expected.to_compile_error().to_string()
+            );
+        }
+    }
+
+    mod derive_wdf_object_context_impl {
+        use super::*;
+
+        #[test]
+        fn valid_input() {
+            let input_tokens = quote! {
+                struct DeviceContext {
+                    open_count: u32,
+                }
+            };
+            let expected = quote! {
+                const _: () = {
+                    static CONTEXT_TYPE_INFO: ::wdk::wdf::ContextTypeInfo =
+                        ::wdk::wdf::ContextTypeInfo::new::<DeviceContext>(c"DeviceContext", &CONTEXT_TYPE_INFO);
+
+                    unsafe impl ::wdk::wdf::ObjectContext for DeviceContext {
+                        fn type_info() -> &'static ::wdk::wdf::ContextTypeInfo {
+                            &CONTEXT_TYPE_INFO
+                        }
+                    }
+                };
+            };
+
+            pretty_assert_eq!(
+                derive_wdf_object_context_impl(input_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn generic_type() {
+            let input_tokens = quote! {
+                struct DeviceContext<T> {
+                    data: T,
+                }
+            };
+            let expected = Error::new(
+                Span::call_site(),
+                "`WdfObjectContext` cannot be derived for generic types",
+            );
+
+            pretty_assert_eq!(
+                derive_wdf_object_context_impl(input_tokens).to_string(),
+                expected.to_compile_error().to_string()
+            );
+        }
+    }
+
+    mod derive_from_registry_impl {
+        use super::*;
+
+        #[test]
+        fn valid_input() {
+            let input_tokens = quote! {
+                struct Config {
+                    #[registry(default = 16, validate = check_queue_depth)]
+                    queue_depth: u32,
+                    #[registry(name = "EnableDma", default)]
+                    dma: bool,
+                    device_name: NtUnicodeString,
+                }
+            };
+            let expected = quote! {
+                impl ::wdk::wdf::FromRegistry for Config {
+                    fn from_registry(
+                        key: &::wdk::wdf::RegistryKey,
+                    ) -> ::core::result::Result<Self, ::wdk::wdf::ConfigError> {
+                        ::core::result::Result::Ok(Self {
+                            queue_depth: {
+                                let value: u32 = match <u32 as ::wdk::wdf::RegistryValue>::query_optional(key, "QueueDepth") {
+                                    ::core::result::Result::Ok(::core::option::Option::Some(value)) => value,
+                                    ::core::result::Result::Ok(::core::option::Option::None) => 16,
+                                    ::core::result::Result::Err(status) => {
+                                        return ::core::result::Result::Err(::wdk::wdf::ConfigError::new("QueueDepth", status));
+                                    }
+                                };
+                                if let ::core::result::Result::Err(status) = (check_queue_depth)(&value) {
+                                    return ::core::result::Result::Err(::wdk::wdf::ConfigError::new("QueueDepth", status));
+                                }
+                                value
+                            },
+                            dma: {
+                                let value: bool = match <bool as ::wdk::wdf::RegistryValue>::query_optional(key, "EnableDma") {
+                                    ::core::result::Result::Ok(::core::option::Option::Some(value)) => value,
+                                    ::core::result::Result::Ok(::core::option::Option::None) => ::core::default::Default::default(),
+                                    ::core::result::Result::Err(status) => {
+                                        return ::core::result::Result::Err(::wdk::wdf::ConfigError::new("EnableDma", status));
+                                    }
+                                };
+                                value
+                            },
+                            device_name: {
+                                let value: NtUnicodeString = match <NtUnicodeString as ::wdk::wdf::RegistryValue>::query_optional(key, "DeviceName") {
+                                    ::core::result::Result::Ok(::core::option::Option::Some(value)) => value,
+                                    ::core::result::Result::Ok(::core::option::Option::None) => return ::core::result::Result::Err(::wdk::wdf::ConfigError::missing("DeviceName")),
+                                    ::core::result::Result::Err(status) => {
+                                        return ::core::result::Result::Err(::wdk::wdf::ConfigError::new("DeviceName", status));
+                                    }
+                                };
+                                value
+                            },
+                        })
+                    }
+                }
+            };
+
+            pretty_assert_eq!(
+                derive_from_registry_impl(input_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn tuple_struct() {
+            let input_tokens = quote! {
+                struct Config(u32);
+            };
+            let expected = Error::new(
+                Span::call_site(),
+                "`FromRegistry` can only be derived for structs with named fields",
+            );
+
+            pretty_assert_eq!(
+                derive_from_registry_impl(input_tokens).to_string(),
+                expected.to_compile_error().to_string()
+            );
+        }
+
+        #[test]
+        fn unsupported_option() {
+            let input_tokens = quote! {
+                struct Config {
+                    #[registry(range = 1..=64)]
+                    queue_depth: u32,
+                }
+            };
+            let expected = Error::new(Span::call_site(), "unsupported `registry` option");
+
+            pretty_assert_eq!(
+                derive_from_registry_impl(input_tokens).to_string(),
+                expected.to_compile_error().to_string()
+            );
+        }
+    }
+
+    mod unicode_string_impl {
+        use super::*;
+
+        #[test]
+        fn valid_input() {
+            let input_tokens = quote! { "Rust" };
+            let expected = quote! {
+                {
+                    const BUFFER: &[u16] = &[82, 117, 115, 116, 0];
+                    ::wdk_sys::UNICODE_STRING {
+                        Length: 8,
+                        MaximumLength: 10,
+                        Buffer: BUFFER.as_ptr().cast_mut(),
+                    }
+                }
+            };
+
+            pretty_assert_eq!(
+                unicode_string_impl(input_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn empty_input() {
+            let input_tokens = quote! { "" };
+            let expected = quote! {
+                {
+                    const BUFFER: &[u16] = &[0];
+                    ::wdk_sys::UNICODE_STRING {
+                        Length: 0,
+                        MaximumLength: 2,
+                        Buffer: BUFFER.as_ptr().cast_mut(),
+                    }
+                }
+            };
+
+            pretty_assert_eq!(
+                unicode_string_impl(input_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn surrogate_pair() {
+            let input_tokens = quote! { "\u{1F980}" };
+            let expected = quote! {
+                {
+                    const BUFFER: &[u16] = &[55358, 56704, 0];
+                    ::wdk_sys::UNICODE_STRING {
+                        Length: 4,
+                        MaximumLength: 6,
+                        Buffer: BUFFER.as_ptr().cast_mut(),
+                    }
+                }
+            };
+
+            pretty_assert_eq!(
+                unicode_string_impl(input_tokens).to_string(),
+                expected.to_string()
+            );
+        }
+
+        #[test]
+        fn too_long_input() {
+            let string_literal = LitStr::new(&"a".repeat(32767), Span::call_site());
+            let expected = Error::new(
+                Span::call_site(),
+                "`unicode_string` literal is too long for a `UNICODE_STRING`",
+            );
+
+            pretty_assert_eq!(
+                unicode_string_impl(string_literal.to_token_stream()).to_string(),
+                expected.to_compile_error().to_string()
+            );
+        }
+
+        #[test]
+        fn not_a_string_literal() {
+            let input_tokens = quote! { 42 };
+
+            assert!(unicode_string_impl(input_tokens)
+                .to_string()
+                .contains("compile_error"));
+        }
+    }
+
+    mod validate_driver_entry_signature {
+        use super::*;
+
+        #[test]
+        fn valid_signature() {
+            let signature: Signature = parse_quote! {
+                fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS>
+            };
+
+            assert!(validate_driver_entry_signature(&signature).is_ok());
+        }
+
+        #[test]
+        fn async_fn() {
+            let signature: Signature = parse_quote! {
+                async fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS>
+            };
+
+            pretty_assert_eq!(
+                validate_driver_entry_signature(&signature)
+                    .unwrap_err()
+                    .to_string(),
+                "`driver_entry` function must not be async"
+            );
+        }
+
+        #[test]
+        fn generic_fn() {
+            let signature: Signature = parse_quote! {
+                fn driver_entry<T>(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                ) -> Result<Driver, NTSTATUS>
+            };
+
+            pretty_assert_eq!(
+                validate_driver_entry_signature(&signature)
+                    .unwrap_err()
+                    .to_string(),
+                "`driver_entry` function must not be generic"
+            );
+        }
+
+        #[test]
+        fn missing_argument() {
+            let signature: Signature = parse_quote! {
+                fn driver_entry(driver_object: &mut DRIVER_OBJECT) -> Result<Driver, NTSTATUS>
+            };
+
+            pretty_assert_eq!(
+                validate_driver_entry_signature(&signature)
+                    .unwrap_err()
+                    .to_string(),
+                "`driver_entry` function must take a `&mut DRIVER_OBJECT` and a `&UNICODE_STRING`"
+            );
+        }
+
+        #[test]
+        fn missing_return_type() {
+            let signature: Signature = parse_quote! {
+                fn driver_entry(
+                    driver_object: &mut DRIVER_OBJECT,
+                    registry_path: &UNICODE_STRING,
+                )
+            };
+
+            pretty_assert_eq!(
+                validate_driver_entry_signature(&signature)
+                    .unwrap_err()
+                    .to_string(),
+                "`driver_entry` function must return a `Result`"
+            );
+        }
+    }
+
     mod generate_parameters_and_return_type {
         use super::*;
 
@@ -1396,6 +2261,31 @@ fn valid_input_with_no_arguments() {
                 expected
             );
         }
+
+        #[test]
+        fn valid_input_with_primitive_types() {
+            // WdfRequestRetrieveInputBuffer has the following generated signature:
+            let bare_fn_type = parse_quote! {
+                unsafe extern "C" fn(
+                    DriverGlobals: PWDF_DRIVER_GLOBALS,
+                    Request: WDFREQUEST,
+                    MinimumRequiredLength: usize,
+                    Buffer: *mut PVOID,
+                    Length: *mut usize,
+                ) -> NTSTATUS
+            };
+            let expected = parse_quote! {
+                Request: wdk_sys::WDFREQUEST,
+                MinimumRequiredLength: usize,
+                Buffer: *mut wdk_sys::PVOID,
+                Length: *mut usize
+            };
+
+            pretty_assert_eq!(
+                compute_fn_parameters(&bare_fn_type, Span::call_site()).unwrap(),
+                expected
+            );
+        }
     }
 
     mod compute_return_type {
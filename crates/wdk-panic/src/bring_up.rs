@@ -0,0 +1,143 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Non-fatal handling of panics for development builds, enabled by the
+//! `bring-up` feature.
+
+use core::{
+    panic::PanicInfo,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering},
+};
+
+use wdk_sys::{
+    ntddk::{KeDelayExecutionThread, KeGetCurrentIrql},
+    _MODE,
+    KIRQL,
+    KPROCESSOR_MODE,
+    LARGE_INTEGER,
+};
+
+// `wdk-sys` generates the `IRQL` constants as `u32`s, but `IRQL`s are `KIRQL`s
+#[allow(clippy::cast_possible_truncation)]
+const APC_LEVEL: KIRQL = wdk_sys::APC_LEVEL as KIRQL;
+
+// `KPROCESSOR_MODE` is a `CCHAR`, while the `MODE` enumeration it holds a value
+// of is an `int`
+#[allow(clippy::cast_possible_truncation)]
+const KERNEL_MODE: KPROCESSOR_MODE = _MODE::KernelMode as KPROCESSOR_MODE;
+
+/// How long a halted thread waits at a time, in 100ns units: one minute,
+/// relative
+const HALT_INTERVAL: i64 = -60 * 10_000_000;
+
+/// What the panic handler does after reporting a panic in a development build
+/// with the `bring-up` feature enabled
+///
+/// Release builds, and builds without the `bring-up` feature, always bug
+/// check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicAction {
+    /// Halt the thread that panicked, and leave the rest of the system
+    /// running, so that it can still be inspected with a debugger
+    ///
+    /// A thread that panicked at `IRQL` <= `APC_LEVEL` waits forever, and
+    /// gives up its processor. A thread that panicked at a higher `IRQL`
+    /// cannot wait, so it spins forever on its processor, which the system
+    /// eventually bug checks for with `DPC_WATCHDOG_VIOLATION`. Any lock the
+    /// thread holds is never released, so other threads that need it hang as
+    /// well.
+    Halt,
+    /// Bug check, as release builds do
+    BugCheck,
+}
+
+/// The [`PanicAction`] of the panic handler
+static PANIC_ACTION: AtomicU8 = AtomicU8::new(PanicAction::Halt as u8);
+
+/// The hook registered with [`set_panic_hook()`], as a pointer, or null
+static PANIC_HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// Set what the panic handler does after reporting a panic, in development
+/// builds
+///
+/// The default is [`PanicAction::Halt`]. This can be called at any `IRQL`.
+pub fn set_panic_action(action: PanicAction) {
+    PANIC_ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+/// Returns what the panic handler does after reporting a panic, in
+/// development builds
+#[must_use]
+pub fn panic_action() -> PanicAction {
+    if PANIC_ACTION.load(Ordering::Relaxed) == PanicAction::BugCheck as u8 {
+        PanicAction::BugCheck
+    } else {
+        PanicAction::Halt
+    }
+}
+
+/// Register `hook` to be called with the panic, before the [`PanicAction`] is
+/// taken, or unregister the current hook with [`None`], in development builds
+///
+/// The hook lets a driver fail the operation that panicked instead of leaving
+/// it hanging, such as by completing the request that was being processed
+/// with an error. It runs on the thread that panicked, at the `IRQL` it
+/// panicked at, and must not panic itself. This can be called at any `IRQL`.
+pub fn set_panic_hook(hook: Option<fn(&PanicInfo)>) {
+    let hook = hook.map_or(ptr::null_mut(), |hook| hook as *mut ());
+    PANIC_HOOK.store(hook, Ordering::Release);
+}
+
+/// Run the registered hook, and halt the current thread unless the
+/// [`PanicAction`] is [`PanicAction::BugCheck`]
+pub fn handle(info: &PanicInfo) {
+    let hook_ptr = PANIC_HOOK.load(Ordering::Acquire);
+    if !hook_ptr.is_null() {
+        let hook;
+        // SAFETY: Non-null values of `PANIC_HOOK` are only ever stored by
+        // `set_panic_hook`, from a `fn(&PanicInfo)`.
+        unsafe {
+            hook = core::mem::transmute::<*mut (), fn(&PanicInfo)>(hook_ptr);
+        }
+        hook(info);
+    }
+
+    if panic_action() == PanicAction::Halt {
+        halt();
+    }
+}
+
+/// Stop the current thread forever, without taking down the system
+fn halt() -> ! {
+    let irql;
+    // SAFETY: `KeGetCurrentIrql` can be called at any `IRQL`.
+    unsafe {
+        irql = KeGetCurrentIrql();
+    }
+
+    if irql <= APC_LEVEL {
+        let mut interval = LARGE_INTEGER {
+            QuadPart: HALT_INTERVAL,
+        };
+        loop {
+            // SAFETY: `interval` is a valid negative (relative) interval in 100ns units
+            // that outlives the call, and the current thread is at `IRQL` <=
+            // `APC_LEVEL`.
+            unsafe {
+                // The thread delays again whether or not the wait was cut short, so its
+                // status does not matter
+                let _ = KeDelayExecutionThread(
+                    KERNEL_MODE,
+                    u8::from(false),
+                    ptr::from_mut(&mut interval),
+                );
+            }
+        }
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
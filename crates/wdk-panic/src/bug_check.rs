@@ -0,0 +1,122 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Configuration of the bug check raised when a driver panics, and the
+//! encoding of the panic into its parameters.
+
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use wdk_sys::{ntddk::KeBugCheckEx, ULONG, ULONG_PTR};
+
+/// The bug check code raised for panics until the driver sets another one
+/// with [`set_bug_check_code()`], `MANUALLY_INITIATED_CRASH1`
+pub const DEFAULT_BUG_CHECK_CODE: ULONG = 0xDEAD_DEAD;
+
+/// The most bytes of the panic message that are passed to the bug check, not
+/// counting its nul terminator
+pub const MAX_MESSAGE_LEN: usize = 255;
+
+/// The bug check code raised for panics
+static BUG_CHECK_CODE: AtomicU32 = AtomicU32::new(DEFAULT_BUG_CHECK_CODE);
+
+/// The buffer that the panic message is formatted into, so that it is still
+/// readable from the crash dump
+static MESSAGE: MessageBuffer = MessageBuffer {
+    claimed: AtomicBool::new(false),
+    bytes: UnsafeCell::new([0; MAX_MESSAGE_LEN + 1]),
+};
+
+/// Set the bug check code raised when the driver panics
+///
+/// Drivers that want their panics to be told apart from other crashes in
+/// `!analyze` can set a code of their own, typically in `DriverEntry`. This
+/// can be called at any `IRQL`.
+pub fn set_bug_check_code(code: ULONG) {
+    BUG_CHECK_CODE.store(code, Ordering::Relaxed);
+}
+
+/// Returns the bug check code raised when the driver panics
+#[must_use]
+pub fn bug_check_code() -> ULONG {
+    BUG_CHECK_CODE.load(Ordering::Relaxed)
+}
+
+/// Bug check the system with [`bug_check_code()`], encoding `info` in the
+/// bug check parameters as described in the crate documentation
+pub fn bug_check(info: &PanicInfo) -> ! {
+    let (file, line) = info
+        .location()
+        .map_or(("", 0), |location| (location.file(), location.line()));
+    let message = format_message(info);
+
+    // SAFETY: `KeBugCheckEx` can be called at any `IRQL`, and does not return.
+    unsafe {
+        KeBugCheckEx(
+            bug_check_code(),
+            file.as_ptr() as ULONG_PTR,
+            file.len() as ULONG_PTR,
+            ULONG_PTR::from(line),
+            message,
+        )
+    }
+}
+
+/// Format the message of the panic into [`MESSAGE`], truncated to
+/// [`MAX_MESSAGE_LEN`] bytes and nul-terminated, and return its address
+///
+/// Returns 0 if another processor is already panicking and has claimed the
+/// buffer.
+fn format_message(info: &PanicInfo) -> ULONG_PTR {
+    if MESSAGE.claimed.swap(true, Ordering::Acquire) {
+        return 0;
+    }
+
+    let bytes;
+    // SAFETY: `MESSAGE` was just claimed by this processor, and is never released,
+    // so nothing else accesses its bytes.
+    unsafe {
+        bytes = &mut *MESSAGE.bytes.get();
+    }
+    let mut writer = TruncatingWriter {
+        buffer: &mut bytes[..MAX_MESSAGE_LEN],
+        len: 0,
+    };
+    // `TruncatingWriter` never fails, and a message that fails to format is still
+    // worth passing on as far as it got
+    let _ = write!(writer, "{}", info.message());
+    let len = writer.len;
+    bytes[len] = 0;
+    bytes.as_ptr() as ULONG_PTR
+}
+
+/// A buffer that a single panicking processor can claim
+struct MessageBuffer {
+    claimed: AtomicBool,
+    bytes: UnsafeCell<[u8; MAX_MESSAGE_LEN + 1]>,
+}
+
+// SAFETY: `bytes` is only accessed by the processor that claimed the buffer by
+// setting `claimed`, which happens at most once.
+unsafe impl Sync for MessageBuffer {}
+
+/// A [`fmt::Write`] implementation that writes as much as fits in `buffer`,
+/// and silently drops the rest
+struct TruncatingWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for TruncatingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining = &mut self.buffer[self.len..];
+        let count = s.len().min(remaining.len());
+        remaining[..count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Reporting of panics to an attached kernel debugger, enabled by the
+//! `debugger` feature.
+
+use core::{
+    ffi::c_int,
+    fmt::{self, Write},
+    panic::PanicInfo,
+};
+
+use wdk_sys::{
+    ntddk::{DbgPrintEx, KdRefreshDebuggerNotPresent},
+    _DPFLTR_TYPE::DPFLTR_IHVDRIVER_ID,
+    DPFLTR_ERROR_LEVEL,
+    ULONG,
+};
+
+/// The most bytes printed by a single call to `DbgPrintEx`, which truncates
+/// anything past 512 bytes
+const CHUNK_LEN: usize = 256;
+
+/// Print the panic to the kernel debugger, and break into it if one is
+/// attached
+///
+/// The whole panic message is printed, regardless of its length, so that the
+/// debugger shows what the bug check parameters can only hold the start of.
+pub fn report(info: &PanicInfo) {
+    let mut writer = DbgPrintWriter {
+        buffer: [0; CHUNK_LEN],
+        len: 0,
+    };
+    // `DbgPrintWriter` never fails, and a message that fails to format is still
+    // worth printing as far as it got
+    let _ = writeln!(writer, "{info}");
+    writer.flush();
+
+    let debugger_not_present;
+    // SAFETY: `KdRefreshDebuggerNotPresent` can be called at any `IRQL`.
+    unsafe {
+        debugger_not_present = KdRefreshDebuggerNotPresent();
+    }
+    if debugger_not_present == 0 {
+        break_into_debugger();
+    }
+}
+
+/// Break into the attached kernel debugger, like `DbgBreakPoint`
+fn break_into_debugger() {
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: `brk #0xF000` only raises a breakpoint exception, which the attached
+    // debugger handles before resuming execution.
+    unsafe {
+        core::arch::asm!("brk #0xF000");
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    // SAFETY: `int 3` only raises a breakpoint exception, which the attached
+    // debugger handles before resuming execution.
+    unsafe {
+        core::arch::asm!("int 3");
+    }
+}
+
+/// A [`fmt::Write`] implementation that prints to the kernel debugger in
+/// chunks of [`CHUNK_LEN`] bytes, without allocating
+struct DbgPrintWriter {
+    buffer: [u8; CHUNK_LEN],
+    len: usize,
+}
+
+impl DbgPrintWriter {
+    /// Print the buffered bytes
+    fn flush(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        // `len` is at most `CHUNK_LEN`, which fits in a `c_int`
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let len = self.len as c_int;
+        // `DPFLTR_IHVDRIVER_ID` is a small, positive component ID
+        #[allow(clippy::cast_sign_loss)]
+        let component_id = DPFLTR_IHVDRIVER_ID as ULONG;
+        // SAFETY: The format string is nul-terminated, and its `%.*s` conversion
+        // only reads the `len` bytes of `buffer` that are initialized.
+        unsafe {
+            DbgPrintEx(
+                component_id,
+                DPFLTR_ERROR_LEVEL,
+                c"%.*s".as_ptr(),
+                len,
+                self.buffer.as_ptr(),
+            );
+        }
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for DbgPrintWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Chunks are split by bytes rather than characters, since the debugger
+        // joins them back together
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            let remaining = &mut self.buffer[self.len..];
+            let count = bytes.len().min(remaining.len());
+            remaining[..count].copy_from_slice(&bytes[..count]);
+            self.len += count;
+            bytes = &bytes[count..];
+            if self.len == CHUNK_LEN {
+                self.flush();
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,29 +2,59 @@
 // License: MIT OR Apache-2.0
 
 //! Default Panic Handlers for programs built with the WDK (Windows Drivers Kit)
+//!
+//! When a driver panics, the panic handler bug checks the system with the code
+//! set by [`set_bug_check_code()`] ([`DEFAULT_BUG_CHECK_CODE`] by default), and
+//! describes the panic in the four bug check parameters, so that it can be
+//! diagnosed from the crash dump:
+//!
+//! 1. The address of the name of the file that panicked, which is not
+//!    nul-terminated
+//! 2. The length of the file name, in bytes
+//! 3. The line that panicked
+//! 4. The address of the panic message, truncated to [`MAX_MESSAGE_LEN`] bytes
+//!    and nul-terminated, or 0 if another processor panicked first
+//!
+//! In the kernel debugger, `!analyze -v` shows the parameters,
+//! `.printf "%ma\n", <4>` prints the message and `db <1> L<2>` dumps the file
+//! name.
+//!
+//! With the `debugger` feature enabled, the panic handler first prints the
+//! whole panic message to the kernel debugger with `DbgPrintEx`, and breaks
+//! into the debugger if one is attached, so that an interactive debugging
+//! session stops where the driver panicked, with its stack intact. Continuing
+//! from the breakpoint then bug checks as usual.
+//!
+//! With the `bring-up` feature enabled, development builds (with
+//! `debug_assertions`) do not bug check when the driver panics. Instead, the
+//! panic handler calls the hook registered with [`set_panic_hook()`], which
+//! can fail the operation that panicked, and then halts the thread that
+//! panicked, leaving the rest of the system running and debuggable. This eases
+//! early bring-up on shared test machines, and can be turned back into a bug
+//! check with [`set_panic_action()`]. Release builds always bug check.
 
 #![no_std]
 
+#[cfg(feature = "bring-up")]
+mod bring_up;
+mod bug_check;
+#[cfg(feature = "debugger")]
+mod debugger;
+
 #[cfg(not(test))]
 use core::panic::PanicInfo;
 
-#[cfg(all(
-    debug_assertions,
-    // Disable inclusion of panic handlers when compiling tests for wdk crate
-    not(test)
-))]
-#[panic_handler]
-const fn panic(_info: &PanicInfo) -> ! {
-    loop {}
-}
+#[cfg(feature = "bring-up")]
+pub use bring_up::{panic_action, set_panic_action, set_panic_hook, PanicAction};
+pub use bug_check::{bug_check_code, set_bug_check_code, DEFAULT_BUG_CHECK_CODE, MAX_MESSAGE_LEN};
 
-#[cfg(all(
-    not(debug_assertions),
-    // Disable inclusion of panic handlers when compiling tests for wdk crate
-    not(test)
-))]
+// Disable inclusion of panic handlers when compiling tests for wdk crate
+#[cfg(not(test))]
 #[panic_handler]
-const fn panic(_info: &PanicInfo) -> ! {
-    loop {}
-    // FIXME: Should this trigger Bugcheck via KeBugCheckEx?
+fn panic(info: &PanicInfo) -> ! {
+    #[cfg(feature = "debugger")]
+    debugger::report(info);
+    #[cfg(all(feature = "bring-up", debug_assertions))]
+    bring_up::handle(info);
+    bug_check::bug_check(info)
 }
@@ -0,0 +1,454 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Bug check callbacks, which add driver data to crash dumps.
+//!
+//! A [`SecondaryDumpCallback`] is called while the system writes a crash
+//! dump, and adds the bytes it produces to the dump as secondary data, tagged
+//! with a `GUID`. The data can then be extracted from the dump with the
+//! `.enumtag` debugger command. A [`CrashLog`] is a ring buffer of the most
+//! recent messages written by the driver, which can add itself to crash dumps
+//! with [`CrashLog::register_dump()`], so that the events leading to a crash
+//! can be read from the dump.
+
+use core::{
+    ffi::CStr,
+    fmt::{self, Write},
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use wdk_sys::{
+    ntddk::{KeDeregisterBugCheckReasonCallback, KeRegisterBugCheckReasonCallback},
+    _KBUGCHECK_CALLBACK_REASON::KbCallbackSecondaryDumpData,
+    _KBUGCHECK_REASON_CALLBACK_RECORD,
+    KBUGCHECK_CALLBACK_REASON,
+    KBUGCHECK_SECONDARY_DUMP_DATA,
+    LIST_ENTRY,
+    NTSTATUS,
+    PUCHAR,
+    PVOID,
+    STATUS_UNSUCCESSFUL,
+    ULONG,
+    ULONG_PTR,
+};
+
+use crate::{guid::Guid, pool::NonPagedBox};
+
+/// The layout of a `KBUGCHECK_REASON_CALLBACK_RECORD`, which `wdk-sys`
+/// generates as an opaque type
+// The fields are only written and read by the kernel
+#[allow(dead_code)]
+#[repr(C)]
+struct ReasonCallbackRecord {
+    entry: LIST_ENTRY,
+    callback_routine: PVOID,
+    component: PUCHAR,
+    checksum: ULONG_PTR,
+    reason: KBUGCHECK_CALLBACK_REASON,
+    state: u8,
+}
+
+/// The state of a [`SecondaryDumpCallback`], which is found from its callback
+/// record by the kernel, so the record must be its first field
+#[repr(C)]
+struct Registration<F> {
+    record: ReasonCallbackRecord,
+    guid: Guid,
+    callback: F,
+}
+
+/// A bug check callback that adds data to crash dumps.
+///
+/// When the system bug checks, the callback is called with a buffer to fill
+/// with the data to add to the crash dump, and returns how many bytes of it
+/// it filled. The data is tagged with the callback's `GUID`, so that it can be
+/// found in the dump with `.enumtag`, and is identified by its component name
+/// in the debugger.
+///
+/// The callback runs at `IRQL` = `HIGH_LEVEL`, after the system has stopped
+/// every other processor, which may have been interrupted while holding any
+/// lock. It must only read non-paged memory, must not acquire any locks or
+/// allocate, and must not panic.
+///
+/// ```ignore
+/// static DEVICE_STATE: AtomicU32 = AtomicU32::new(0);
+///
+/// let callback = SecondaryDumpCallback::try_new(c"SampleDriver", DUMP_GUID, |buffer| {
+///     let state = DEVICE_STATE.load(Ordering::Relaxed).to_le_bytes();
+///     let len = state.len().min(buffer.len());
+///     buffer[..len].copy_from_slice(&state[..len]);
+///     len
+/// })?;
+/// ```
+pub struct SecondaryDumpCallback<F> {
+    registration: NonPagedBox<Registration<F>>,
+}
+
+// SAFETY: Bug check callbacks can be deregistered from any thread, and the
+// callback is required to be `Send` and `Sync`.
+unsafe impl<F: Send + Sync> Send for SecondaryDumpCallback<F> {}
+
+// SAFETY: See above. The callback is only ever accessed through a shared
+// reference.
+unsafe impl<F: Send + Sync> Sync for SecondaryDumpCallback<F> {}
+
+impl<F> SecondaryDumpCallback<F>
+where
+    F: Fn(&mut [u8]) -> usize + Send + Sync + 'static,
+{
+    /// Try to register `callback` to add data tagged with `guid` to crash
+    /// dumps, on behalf of the component named `component`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the registration cannot be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`, or if the kernel rejects it, in which
+    /// case the error variant will contain `STATUS_UNSUCCESSFUL`.
+    pub fn try_new(component: &'static CStr, guid: Guid, callback: F) -> Result<Self, NTSTATUS> {
+        let registration = NonPagedBox::try_new(Registration {
+            // SAFETY: `ReasonCallbackRecord` is a plain C struct, for which all zeroes is
+            // the unregistered state that `KeRegisterBugCheckReasonCallback` expects.
+            record: unsafe { core::mem::zeroed() },
+            guid,
+            callback,
+        })?;
+
+        let registered;
+        // SAFETY: The record is the first field of the `Registration<F>` that
+        // `secondary_dump_data::<F>` expects, which is kept at a stable address in
+        // non-paged pool until it is deregistered. `component` is a nul-terminated
+        // string that lives forever.
+        unsafe {
+            registered = KeRegisterBugCheckReasonCallback(
+                registration.as_ptr().cast(),
+                Some(secondary_dump_data::<F>),
+                KbCallbackSecondaryDumpData,
+                component.as_ptr().cast_mut().cast(),
+            );
+        }
+        if registered == 0 {
+            return Err(STATUS_UNSUCCESSFUL);
+        }
+
+        Ok(Self { registration })
+    }
+}
+
+impl<F> SecondaryDumpCallback<F> {
+    /// Returns the `GUID` that the data is tagged with in crash dumps
+    #[must_use]
+    pub fn guid(&self) -> Guid {
+        let guid;
+        // SAFETY: `guid` is never modified after the registration was initialized.
+        unsafe {
+            guid = (*self.registration.as_ptr()).guid;
+        }
+        guid
+    }
+}
+
+impl<F> Drop for SecondaryDumpCallback<F> {
+    /// Deregister the callback
+    ///
+    /// This must happen at `IRQL` <= `DISPATCH_LEVEL`.
+    fn drop(&mut self) {
+        let deregistered;
+        // SAFETY: The record was registered in `try_new`, and is freed with the
+        // registration only after this.
+        unsafe {
+            deregistered = KeDeregisterBugCheckReasonCallback(self.registration.as_ptr().cast());
+        }
+        debug_assert!(
+            deregistered != 0,
+            "bug check callbacks should only be deregistered once"
+        );
+    }
+}
+
+/// The `KBUGCHECK_REASON_CALLBACK_ROUTINE` of callbacks registered by
+/// [`SecondaryDumpCallback::try_new()`]
+///
+/// # Safety
+///
+/// `record` must point to the record of a live `Registration<F>`, and
+/// `reason_specific_data` must point to a `KBUGCHECK_SECONDARY_DUMP_DATA` of
+/// `reason_specific_data_length` bytes.
+unsafe extern "C" fn secondary_dump_data<F>(
+    reason: KBUGCHECK_CALLBACK_REASON,
+    record: *mut _KBUGCHECK_REASON_CALLBACK_RECORD,
+    reason_specific_data: PVOID,
+    reason_specific_data_length: ULONG,
+) where
+    F: Fn(&mut [u8]) -> usize,
+{
+    if reason != KbCallbackSecondaryDumpData
+        || (reason_specific_data_length as usize)
+            < core::mem::size_of::<KBUGCHECK_SECONDARY_DUMP_DATA>()
+    {
+        return;
+    }
+
+    let registration = record.cast::<Registration<F>>();
+    let guid;
+    // SAFETY: The caller guarantees that `record` is the first field of a live
+    // `Registration<F>`, which is `repr(C)`. Only the record is written by the
+    // kernel.
+    unsafe {
+        guid = (*registration).guid;
+    }
+    let callback;
+    // SAFETY: As above. The callback is only ever accessed through a shared
+    // reference.
+    unsafe {
+        callback = &(*registration).callback;
+    }
+    let dump_data;
+    // SAFETY: The caller guarantees that `reason_specific_data` points to a
+    // `KBUGCHECK_SECONDARY_DUMP_DATA`, which only this callback accesses while it
+    // runs.
+    unsafe {
+        dump_data = &mut *reason_specific_data.cast::<KBUGCHECK_SECONDARY_DUMP_DATA>();
+    }
+    if dump_data.InBuffer.is_null() {
+        return;
+    }
+
+    let capacity = dump_data.InBufferLength.min(dump_data.MaximumAllowed) as usize;
+    let buffer;
+    // SAFETY: `InBuffer` is a buffer of `InBufferLength` bytes provided by the
+    // system for this callback to fill.
+    unsafe {
+        buffer = core::slice::from_raw_parts_mut(dump_data.InBuffer.cast::<u8>(), capacity);
+    }
+    let len = callback(buffer).min(capacity);
+
+    dump_data.Guid = guid.into_raw();
+    dump_data.OutBuffer = dump_data.InBuffer;
+    // `len` is at most `capacity`, which came from a `ULONG`
+    #[allow(clippy::cast_possible_truncation)]
+    let len = len as ULONG;
+    dump_data.OutBufferLength = len;
+}
+
+/// A ring buffer of the most recent `N` bytes of messages written by the
+/// driver, meant to be added to crash dumps.
+///
+/// Writing to a [`CrashLog`] only copies the message into the ring with
+/// atomic operations, so it can be done at any `IRQL`, from any number of
+/// processors at once. Once the ring is full, the oldest messages are
+/// overwritten. Messages written concurrently by different processors may
+/// interleave, which is acceptable for a log that is only read after a crash.
+///
+/// A [`CrashLog`] is typically a `static`, which adds itself to crash dumps
+/// with [`CrashLog::register_dump()`] when the driver loads:
+///
+/// ```ignore
+/// static LOG: CrashLog<4096> = CrashLog::new();
+///
+/// let _ = writeln!(LOG, "starting device {index}");
+///
+/// let dump_callback = LOG.register_dump(c"SampleDriver", LOG_GUID)?;
+/// ```
+///
+/// Registering the log with
+/// [`debug_print::set_crash_log()`](crate::debug_print::set_crash_log) also
+/// copies every message printed with [`kprint!`](crate::kprint) and the
+/// related macros, and every record of the `log` crate backend of the
+/// `logger` module, into it.
+pub struct CrashLog<const N: usize> {
+    bytes: [AtomicU8; N],
+    written: AtomicUsize,
+}
+
+impl<const N: usize> CrashLog<N> {
+    /// Construct an empty [`CrashLog`]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            bytes: [const { AtomicU8::new(0) }; N],
+            written: AtomicUsize::new(0),
+        }
+    }
+
+    /// Append `bytes` to the log, overwriting the oldest bytes once it is
+    /// full
+    ///
+    /// This can be called at any `IRQL`.
+    pub fn write(&self, bytes: &[u8]) {
+        if N == 0 {
+            return;
+        }
+        // Only the last `N` bytes of a message longer than the ring survive
+        let skipped = bytes.len().saturating_sub(N);
+        let start = self.written.fetch_add(bytes.len(), Ordering::Relaxed) + skipped;
+        for (offset, &byte) in bytes[skipped..].iter().enumerate() {
+            self.bytes[(start + offset) % N].store(byte, Ordering::Relaxed);
+        }
+    }
+
+    /// Append a formatted message to the log, so that it can be written to
+    /// with `write!` and `writeln!`
+    ///
+    /// This can be called at any `IRQL`, as long as formatting the arguments
+    /// can.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if formatting one of the arguments
+    /// fails. Writing to the log itself never fails.
+    pub fn write_fmt(&self, args: fmt::Arguments) -> fmt::Result {
+        let mut log = self;
+        Write::write_fmt(&mut log, args)
+    }
+
+    /// Returns the number of bytes that have been written to the log since it
+    /// was constructed, including those that have been overwritten
+    #[must_use]
+    pub fn total_written(&self) -> usize {
+        self.written.load(Ordering::Relaxed)
+    }
+
+    /// Copy the most recent bytes of the log into `buffer`, oldest first,
+    /// returning how many bytes were copied
+    ///
+    /// This can be called at any `IRQL`.
+    pub fn copy_to(&self, buffer: &mut [u8]) -> usize {
+        let written = self.total_written();
+        let len = written.min(N).min(buffer.len());
+        let start = written - len;
+        for (offset, byte) in buffer[..len].iter_mut().enumerate() {
+            *byte = self.bytes[(start + offset) % N].load(Ordering::Relaxed);
+        }
+        len
+    }
+}
+
+impl<const N: usize> CrashLog<N> {
+    /// Try to register a [`SecondaryDumpCallback`] that adds the most recent
+    /// bytes of the log to crash dumps, tagged with `guid`, on behalf of the
+    /// component named `component`
+    ///
+    /// The log is added for as long as the returned callback is kept alive.
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the callback could not be
+    /// registered, with the error of [`SecondaryDumpCallback::try_new()`].
+    pub fn register_dump(
+        &'static self,
+        component: &'static CStr,
+        guid: Guid,
+    ) -> Result<SecondaryDumpCallback<impl Fn(&mut [u8]) -> usize + Send + Sync>, NTSTATUS> {
+        SecondaryDumpCallback::try_new(component, guid, move |buffer: &mut [u8]| {
+            self.copy_to(buffer)
+        })
+    }
+}
+
+impl<const N: usize> Default for CrashLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for &CrashLog<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl<const N: usize> fmt::Debug for CrashLog<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrashLog")
+            .field("capacity", &N)
+            .field("total_written", &self.total_written())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the most recent bytes of `log`, and how many there are
+    fn contents<const N: usize>(log: &CrashLog<N>) -> ([u8; 32], usize) {
+        let mut buffer = [0; 32];
+        let len = log.copy_to(&mut buffer);
+        (buffer, len)
+    }
+
+    #[test]
+    fn empty_log_copies_nothing() {
+        let log = CrashLog::<8>::new();
+        let (_, len) = contents(&log);
+        assert_eq!(len, 0);
+        assert_eq!(log.total_written(), 0);
+    }
+
+    #[test]
+    fn write_within_capacity() {
+        let log = CrashLog::<8>::new();
+        log.write(b"abc");
+        log.write(b"de");
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"abcde");
+        assert_eq!(log.total_written(), 5);
+    }
+
+    #[test]
+    fn write_wraps_around() {
+        let log = CrashLog::<8>::new();
+        log.write(b"abcdef");
+        log.write(b"ghij");
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"cdefghij");
+        assert_eq!(log.total_written(), 10);
+
+        log.write(b"klmnopq");
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"jklmnopq");
+    }
+
+    #[test]
+    fn write_longer_than_capacity_keeps_its_end() {
+        let log = CrashLog::<4>::new();
+        log.write(b"abcdefgh");
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"efgh");
+        assert_eq!(log.total_written(), 8);
+
+        log.write(b"ij");
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"ghij");
+    }
+
+    #[test]
+    fn copy_to_short_buffer_copies_most_recent_bytes() {
+        let log = CrashLog::<8>::new();
+        log.write(b"abcdefghij");
+        let mut buffer = [0; 3];
+        assert_eq!(log.copy_to(&mut buffer), 3);
+        assert_eq!(&buffer, b"hij");
+    }
+
+    #[test]
+    fn zero_capacity_log_ignores_writes() {
+        let log = CrashLog::<0>::new();
+        log.write(b"abc");
+        let (_, len) = contents(&log);
+        assert_eq!(len, 0);
+    }
+
+    #[test]
+    fn write_fmt_appends_formatted_message() {
+        let log = CrashLog::<32>::new();
+        writeln!(&log, "device {} started", 3).unwrap();
+        let (buffer, len) = contents(&log);
+        assert_eq!(&buffer[..len], b"device 3 started\n");
+    }
+}
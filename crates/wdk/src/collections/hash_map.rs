@@ -0,0 +1,505 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    borrow::Borrow,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use wdk_sys::{NTSTATUS, STATUS_INSUFFICIENT_RESOURCES, ULONG};
+
+use super::KVec;
+use crate::wdf::PoolType;
+
+/// The fewest buckets that a [`KHashMap`] allocates
+const MIN_BUCKETS: usize = 8;
+
+/// A hash map allocated from a pool, whose growth operations return an error
+/// instead of aborting when the pool is exhausted.
+///
+/// Entries are stored in a single array of buckets, allocated as a [`KVec`]
+/// with the same `IRQL` requirements, which is kept at most three quarters
+/// full and probed linearly. Keys are hashed with FNV-1a, which is fast but
+/// not resistant to collisions chosen by an attacker, so keys that come from
+/// user mode should be bounded in number.
+///
+/// ```ignore
+/// let mut files = KHashMap::with_capacity(64, PoolType::NonPaged, 0)?;
+/// files.try_insert(file_object_id, context)?;
+/// let context = files.get(&file_object_id);
+/// ```
+pub struct KHashMap<K, V> {
+    buckets: KVec<Option<(K, V)>>,
+    len: usize,
+}
+
+impl<K, V> KHashMap<K, V> {
+    /// Construct an empty [`KHashMap`] that allocates from `pool_type`,
+    /// without allocating
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    #[must_use]
+    pub const fn new(pool_type: PoolType, pool_tag: ULONG) -> Self {
+        Self {
+            buckets: KVec::new(pool_type, pool_tag),
+            len: 0,
+        }
+    }
+
+    /// Returns the kind of pool that the map is allocated from
+    #[must_use]
+    pub const fn pool_type(&self) -> PoolType {
+        self.buckets.pool_type()
+    }
+
+    /// Returns the pool tag that the map is allocated with
+    #[must_use]
+    pub const fn pool_tag(&self) -> ULONG {
+        self.buckets.pool_tag()
+    }
+
+    /// Returns the number of entries in the map
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map contains no entries
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns how many entries the map can hold without reallocating
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        max_entries(self.buckets.len())
+    }
+
+    /// Remove every entry, keeping the capacity of the map
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket = None;
+        }
+        self.len = 0;
+    }
+
+    /// Returns an iterator over the entries of the map, in an unspecified
+    /// order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets
+            .iter()
+            .filter_map(|bucket| bucket.as_ref().map(|(key, value)| (key, value)))
+    }
+
+    /// Returns an iterator over the entries of the map, with mutable
+    /// references to their values, in an unspecified order
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.buckets
+            .iter_mut()
+            .filter_map(|bucket| bucket.as_mut().map(|(key, value)| (&*key, value)))
+    }
+
+    /// Returns an iterator over the keys of the map, in an unspecified order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over the values of the map, in an unspecified order
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Hash + Eq, V> KHashMap<K, V> {
+    /// Try to construct an empty [`KHashMap`] that allocates from
+    /// `pool_type`, with room for at least `capacity` entries
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the map could not be allocated,
+    /// in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn with_capacity(
+        capacity: usize,
+        pool_type: PoolType,
+        pool_tag: ULONG,
+    ) -> Result<Self, NTSTATUS> {
+        let mut map = Self::new(pool_type, pool_tag);
+        map.try_reserve(capacity)?;
+        Ok(map)
+    }
+
+    /// Try to make room for at least `additional` more entries
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the map could not be allocated,
+    /// in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), NTSTATUS> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        if required <= self.capacity() {
+            return Ok(());
+        }
+
+        let buckets =
+            bucket_count(self.buckets.len(), required).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        self.rehash(buckets)
+    }
+
+    /// Try to insert `value` under `key`, returning the value it replaced, if
+    /// any
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` was not in the map and the
+    /// map could not be grown, in which case `key` and `value` are dropped and
+    /// the error variant will contain `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Option<V>, NTSTATUS> {
+        if let Some((_, existing)) = self
+            .find(&key)
+            .and_then(|index| self.buckets[index].as_mut())
+        {
+            return Ok(Some(core::mem::replace(existing, value)));
+        }
+
+        self.try_reserve(1)?;
+        insert_unique(&mut self.buckets, key, value);
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// Returns a reference to the value under `key`, if any
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.buckets[index].as_ref().map(|(_, value)| value)
+    }
+
+    /// Returns a mutable reference to the value under `key`, if any
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        self.buckets[index].as_mut().map(|(_, value)| value)
+    }
+
+    /// Returns `true` if the map contains an entry under `key`
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Remove the entry under `key`, returning its value, if any
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(key)?;
+        let (_, value) = remove_at(&mut self.buckets, index)?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Returns the index of the bucket containing `key`, if any
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        find_in(&self.buckets, key)
+    }
+
+    /// Try to move every entry into a new array of `buckets` buckets
+    fn rehash(&mut self, buckets: usize) -> Result<(), NTSTATUS> {
+        let mut new_buckets = KVec::with_capacity(buckets, self.pool_type(), self.pool_tag())?;
+        while new_buckets.push_within_capacity(None).is_ok() {}
+
+        for bucket in &mut self.buckets {
+            if let Some((key, value)) = bucket.take() {
+                insert_unique(&mut new_buckets, key, value);
+            }
+        }
+        self.buckets = new_buckets;
+        Ok(())
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for KHashMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Returns how many entries fit in `buckets` buckets while keeping them at
+/// most three quarters full
+const fn max_entries(buckets: usize) -> usize {
+    buckets / 4 * 3
+}
+
+/// Returns how many buckets a map with `buckets` buckets needs to hold
+/// `required` entries: the current number, at least [`MIN_BUCKETS`], doubled
+/// until they fit, or [`None`] if that overflows
+fn bucket_count(buckets: usize, required: usize) -> Option<usize> {
+    let mut buckets = MIN_BUCKETS.max(buckets);
+    while max_entries(buckets) < required {
+        buckets = buckets.checked_mul(2)?;
+    }
+    Some(buckets)
+}
+
+/// Returns the bucket that `key` is ideally stored in, of the buckets masked
+/// by `mask`
+fn bucket_of<Q: Hash + ?Sized>(key: &Q, mask: usize) -> usize {
+    let mut hasher = FnvHasher::new();
+    key.hash(&mut hasher);
+    // Only the low bits of the hash select a bucket
+    #[allow(clippy::cast_possible_truncation)]
+    let hash = hasher.finish() as usize;
+    hash & mask
+}
+
+/// Returns the index of the bucket containing `key`, if any, of `buckets`,
+/// which must not be full, and whose length must be a power of two
+fn find_in<K, V, Q>(buckets: &[Option<(K, V)>], key: &Q) -> Option<usize>
+where
+    K: Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    let mask = buckets.len().checked_sub(1)?;
+    let mut index = bucket_of(key, mask);
+    loop {
+        match &buckets[index] {
+            None => return None,
+            Some((existing, _)) if existing.borrow() == key => return Some(index),
+            Some(_) => index = (index + 1) & mask,
+        }
+    }
+}
+
+/// Remove and return the entry in the bucket at `index`, if any, and shift
+/// the entries that were probed past it back, so that every entry is still
+/// reachable from its ideal bucket without crossing an empty one
+fn remove_at<K: Hash, V>(buckets: &mut [Option<(K, V)>], index: usize) -> Option<(K, V)> {
+    let entry = buckets[index].take()?;
+
+    let mask = buckets.len() - 1;
+    let mut empty = index;
+    let mut index = index;
+    loop {
+        index = (index + 1) & mask;
+        let Some((key, _)) = &buckets[index] else {
+            break;
+        };
+        let ideal = bucket_of(key, mask);
+        if (index.wrapping_sub(ideal) & mask) >= (index.wrapping_sub(empty) & mask) {
+            buckets[empty] = buckets[index].take();
+            empty = index;
+        }
+    }
+    Some(entry)
+}
+
+/// Insert an entry for `key`, which is not in `buckets`, into the first empty
+/// bucket from its ideal bucket
+fn insert_unique<K: Hash, V>(buckets: &mut [Option<(K, V)>], key: K, value: V) {
+    let mask = buckets.len() - 1;
+    let mut index = bucket_of(&key, mask);
+    while buckets[index].is_some() {
+        index = (index + 1) & mask;
+    }
+    buckets[index] = Some((key, value));
+}
+
+/// The 64-bit FNV-1a hash function
+struct FnvHasher {
+    hash: u64,
+}
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01B3;
+
+    const fn new() -> Self {
+        Self {
+            hash: Self::OFFSET_BASIS,
+        }
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= u64::from(byte);
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty table of `MIN_BUCKETS` buckets
+    type Buckets = [Option<(u32, u32)>; MIN_BUCKETS];
+
+    /// Returns the first `count` keys whose ideal bucket is `bucket`, of
+    /// `MIN_BUCKETS` buckets
+    fn keys_in_bucket<const COUNT: usize>(bucket: usize) -> [u32; COUNT] {
+        let mut keys = [0; COUNT];
+        let mut candidates = (0..).filter(|key| bucket_of(key, MIN_BUCKETS - 1) == bucket);
+        for key in &mut keys {
+            *key = candidates.next().unwrap();
+        }
+        keys
+    }
+
+    #[test]
+    fn fnv_hasher_matches_reference_values() {
+        let mut hasher = FnvHasher::new();
+        assert_eq!(hasher.finish(), 0xCBF2_9CE4_8422_2325);
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn inserted_keys_are_found() {
+        let mut buckets: Buckets = [None; MIN_BUCKETS];
+        for key in 0..6 {
+            insert_unique(&mut buckets, key, key * 10);
+        }
+        for key in 0..6 {
+            let index = find_in(&buckets, &key).unwrap();
+            assert_eq!(buckets[index], Some((key, key * 10)));
+        }
+        assert_eq!(find_in(&buckets, &6), None);
+    }
+
+    #[test]
+    fn find_in_empty_table_returns_none() {
+        let buckets: [Option<(u32, u32)>; 0] = [];
+        assert_eq!(find_in(&buckets, &0), None);
+    }
+
+    #[test]
+    fn keys_are_found_after_removal() {
+        for removed in 0..6 {
+            let mut buckets: Buckets = [None; MIN_BUCKETS];
+            for key in 0..6 {
+                insert_unique(&mut buckets, key, key * 10);
+            }
+
+            let index = find_in(&buckets, &removed).unwrap();
+            assert_eq!(
+                remove_at(&mut buckets, index),
+                Some((removed, removed * 10))
+            );
+            assert_eq!(find_in(&buckets, &removed), None);
+            for key in (0..6).filter(|&key| key != removed) {
+                let index = find_in(&buckets, &key).unwrap();
+                assert_eq!(buckets[index], Some((key, key * 10)));
+            }
+        }
+    }
+
+    #[test]
+    fn remove_at_empty_bucket_returns_none() {
+        let mut buckets: Buckets = [None; MIN_BUCKETS];
+        insert_unique(&mut buckets, 1, 10);
+        let empty = buckets.iter().position(Option::is_none).unwrap();
+        assert_eq!(remove_at(&mut buckets, empty), None);
+        assert!(find_in(&buckets, &1).is_some());
+    }
+
+    #[test]
+    fn probing_wraps_around_the_end_of_the_table() {
+        let last = MIN_BUCKETS - 1;
+        let [first, second, third] = keys_in_bucket::<3>(last);
+        let mut buckets: Buckets = [None; MIN_BUCKETS];
+        insert_unique(&mut buckets, first, 1);
+        insert_unique(&mut buckets, second, 2);
+        insert_unique(&mut buckets, third, 3);
+        assert_eq!(find_in(&buckets, &first), Some(last));
+        assert_eq!(find_in(&buckets, &second), Some(0));
+        assert_eq!(find_in(&buckets, &third), Some(1));
+
+        // The entries that wrapped around are shifted back across the end
+        assert_eq!(remove_at(&mut buckets, last), Some((first, 1)));
+        assert_eq!(find_in(&buckets, &second), Some(last));
+        assert_eq!(find_in(&buckets, &third), Some(0));
+        assert_eq!(buckets[1], None);
+    }
+
+    #[test]
+    fn removal_keeps_entries_in_their_ideal_bucket() {
+        let [in_zero] = keys_in_bucket::<1>(0);
+        let [first, second] = keys_in_bucket::<2>(MIN_BUCKETS - 1);
+        let mut buckets: Buckets = [None; MIN_BUCKETS];
+        insert_unique(&mut buckets, in_zero, 0);
+        insert_unique(&mut buckets, first, 1);
+        insert_unique(&mut buckets, second, 2);
+        assert_eq!(find_in(&buckets, &second), Some(1));
+
+        // `in_zero` is already in its ideal bucket, so only `second` moves, and
+        // only as far back as the bucket `first` left empty
+        assert_eq!(remove_at(&mut buckets, MIN_BUCKETS - 1), Some((first, 1)));
+        assert_eq!(find_in(&buckets, &in_zero), Some(0));
+        assert_eq!(find_in(&buckets, &second), Some(MIN_BUCKETS - 1));
+    }
+
+    #[test]
+    fn bucket_count_grows_by_doubling() {
+        assert_eq!(bucket_count(0, 0), Some(MIN_BUCKETS));
+        assert_eq!(bucket_count(0, 6), Some(8));
+        assert_eq!(bucket_count(0, 7), Some(16));
+        assert_eq!(bucket_count(16, 7), Some(16));
+        assert_eq!(bucket_count(16, 13), Some(32));
+        assert_eq!(bucket_count(8, 100), Some(256));
+        assert_eq!(bucket_count(0, usize::MAX), None);
+    }
+
+    #[test]
+    fn entries_are_found_after_growth() {
+        let mut buckets: Buckets = [None; MIN_BUCKETS];
+        for key in 0..6 {
+            insert_unique(&mut buckets, key, key * 10);
+        }
+
+        let mut grown = [None; MIN_BUCKETS * 2];
+        for bucket in &mut buckets {
+            if let Some((key, value)) = bucket.take() {
+                insert_unique(&mut grown, key, value);
+            }
+        }
+        for key in 6..12 {
+            insert_unique(&mut grown, key, key * 10);
+        }
+        for key in 0..12 {
+            let index = find_in(&grown, &key).unwrap();
+            assert_eq!(grown[index], Some((key, key * 10)));
+        }
+    }
+}
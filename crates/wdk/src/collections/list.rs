@@ -0,0 +1,399 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::{cell::UnsafeCell, marker::PhantomData, ptr::NonNull};
+
+use wdk_sys::{LIST_ENTRY, NTSTATUS};
+
+use crate::pool::NonPagedBox;
+
+/// A link (`LIST_ENTRY`) that allows a struct to be stored in a [`List`].
+///
+/// Embed a [`ListEntry`] as a field of a struct, and use [`impl_list_item!`]
+/// to implement [`ListItem`] for the struct, so that it can be inserted into a
+/// [`List`]. The link can only be accessed by the [`List`] the item is in.
+///
+/// [`impl_list_item!`]: crate::impl_list_item
+#[repr(transparent)]
+pub struct ListEntry {
+    entry: UnsafeCell<LIST_ENTRY>,
+}
+
+// SAFETY: The `LIST_ENTRY` is only ever accessed by the `List` that the item is
+// in, which requires a mutable borrow of the `List`. Its `wdk-sys` binding is
+// only `!Send` because it contains raw pointers.
+#[allow(clippy::non_send_fields_in_send_ty)]
+unsafe impl Send for ListEntry {}
+
+// SAFETY: A shared reference to a `ListEntry` does not allow any access to the
+// `LIST_ENTRY` it contains.
+unsafe impl Sync for ListEntry {}
+
+impl ListEntry {
+    /// Construct a link that is not in any [`List`]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entry: UnsafeCell::new(LIST_ENTRY {
+                Flink: core::ptr::null_mut(),
+                Blink: core::ptr::null_mut(),
+            }),
+        }
+    }
+}
+
+impl Default for ListEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A type that contains a [`ListEntry`], and can therefore be stored in a
+/// [`List`].
+///
+/// This should be implemented with [`impl_list_item!`].
+///
+/// # Safety
+///
+/// `ENTRY_OFFSET` must be the offset in bytes of a [`ListEntry`] field within
+/// `Self`.
+///
+/// [`impl_list_item!`]: crate::impl_list_item
+pub unsafe trait ListItem {
+    /// The offset in bytes of the [`ListEntry`] field within `Self`
+    const ENTRY_OFFSET: usize;
+}
+
+/// Implement [`ListItem`](crate::collections::ListItem) for a struct, using
+/// one of its [`ListEntry`](crate::collections::ListEntry) fields as the link
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use wdk::{
+///     collections::{List, ListEntry},
+///     impl_list_item,
+/// };
+///
+/// struct Request {
+///     id: u32,
+///     link: ListEntry,
+/// }
+///
+/// impl_list_item!(Request, link);
+///
+/// # fn example() -> Result<(), wdk_sys::NTSTATUS> {
+/// let mut requests = List::<Request>::try_new()?;
+/// requests.push_back(Box::new(Request {
+///     id: 1,
+///     link: ListEntry::new(),
+/// }));
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! impl_list_item {
+    ($type:ty, $field:ident) => {
+        // SAFETY: `ENTRY_OFFSET` is the offset of `$field`, which the function below
+        // guarantees is a `ListEntry`
+        unsafe impl $crate::collections::ListItem for $type {
+            const ENTRY_OFFSET: usize = {
+                const fn assert_list_entry(item: &$type) -> &$crate::collections::ListEntry {
+                    &item.$field
+                }
+                let _ = assert_list_entry;
+                ::core::mem::offset_of!($type, $field)
+            };
+        }
+    };
+}
+
+/// An intrusive, doubly-linked list of boxed `T`s built on `LIST_ENTRY`.
+///
+/// Items are linked through a [`ListEntry`] embedded in each item, so
+/// inserting and removing items never allocates. The [`List`] owns the items
+/// it contains, and drops them when it is dropped.
+///
+/// The head of the list is stored in non-paged pool, so it never moves while
+/// items point to it.
+///
+/// Items in the list can only be accessed through shared references, since
+/// moving or swapping out an item, or its [`ListEntry`], through a mutable
+/// reference would break the links of the list. Items that change while they
+/// are in the list must use interior mutability, or be removed, changed and
+/// inserted again.
+pub struct List<T: ListItem> {
+    head: NonPagedBox<LIST_ENTRY>,
+    _marker: PhantomData<Box<T>>,
+}
+
+impl<T: ListItem> List<T> {
+    /// Try to construct an empty list
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the list head cannot be
+    /// allocated. The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn try_new() -> Result<Self, NTSTATUS> {
+        let head = NonPagedBox::try_new(LIST_ENTRY {
+            Flink: core::ptr::null_mut(),
+            Blink: core::ptr::null_mut(),
+        })?;
+
+        let head_ptr = head.as_ptr();
+        // SAFETY: `head_ptr` points to a valid `LIST_ENTRY`, that never moves for the
+        // lifetime of the `List`. An empty list's head points to itself.
+        unsafe {
+            *head_ptr = LIST_ENTRY {
+                Flink: head_ptr,
+                Blink: head_ptr,
+            };
+        }
+
+        Ok(Self {
+            head,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns `true` if the list contains no items
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.first() == self.head.as_ptr()
+    }
+
+    /// Returns the number of items in the list
+    ///
+    /// This walks the entire list.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Insert `item` at the front of the list, and return a pointer to it,
+    /// which can be passed to [`List::remove()`]
+    pub fn push_front(&mut self, item: Box<T>) -> NonNull<T> {
+        let item = NonNull::from(Box::leak(item));
+        let entry = Self::entry_of(item.as_ptr());
+        // SAFETY: The head and the entries it links to are valid while they are in
+        // the list, and `entry` was just released from its `Box`.
+        unsafe {
+            insert_between(entry, self.head.as_ptr(), self.first());
+        }
+        item
+    }
+
+    /// Insert `item` at the back of the list, and return a pointer to it,
+    /// which can be passed to [`List::remove()`]
+    pub fn push_back(&mut self, item: Box<T>) -> NonNull<T> {
+        let item = NonNull::from(Box::leak(item));
+        let entry = Self::entry_of(item.as_ptr());
+        // SAFETY: The head and the entries it links to are valid while they are in
+        // the list, and `entry` was just released from its `Box`.
+        unsafe {
+            insert_between(entry, self.last(), self.head.as_ptr());
+        }
+        item
+    }
+
+    /// Remove and return the item at the front of the list, or [`None`] if the
+    /// list is empty
+    pub fn pop_front(&mut self) -> Option<Box<T>> {
+        let entry = self.first();
+        // SAFETY: `entry` is either the head, or an entry in this list
+        (entry != self.head.as_ptr()).then(|| unsafe { Self::remove_entry(entry) })
+    }
+
+    /// Remove and return the item at the back of the list, or [`None`] if the
+    /// list is empty
+    pub fn pop_back(&mut self) -> Option<Box<T>> {
+        let entry = self.last();
+        // SAFETY: `entry` is either the head, or an entry in this list
+        (entry != self.head.as_ptr()).then(|| unsafe { Self::remove_entry(entry) })
+    }
+
+    /// Remove and return `item` from the list
+    ///
+    /// `item` is a pointer, such as the one returned by [`List::push_front()`]
+    /// or [`List::push_back()`], rather than a reference, since the returned
+    /// [`Box`] takes back ownership of the item.
+    ///
+    /// # Safety
+    ///
+    /// `item` must point to an item in this list.
+    pub unsafe fn remove(&mut self, item: NonNull<T>) -> Box<T> {
+        let entry = Self::entry_of(item.as_ptr());
+        // SAFETY: The caller guarantees that `item` is in this list.
+        unsafe { Self::remove_entry(entry) }
+    }
+
+    /// Remove every item for which `f` returns `false`, and keep the rest in
+    /// their original order
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        let head = self.head.as_ptr();
+        let mut entry = self.first();
+        while entry != head {
+            let next;
+            // SAFETY: `entry` is a valid entry in this list.
+            unsafe {
+                next = (*entry).Flink;
+            }
+
+            let item;
+            // SAFETY: `entry` is an entry in this list, so it is embedded in a `T`.
+            unsafe {
+                item = &*Self::item_of(entry);
+            }
+            if !f(item) {
+                // SAFETY: `entry` is an entry in this list.
+                drop(unsafe { Self::remove_entry(entry) });
+            }
+            entry = next;
+        }
+    }
+
+    /// Returns an iterator over shared references to the items in the list,
+    /// from front to back
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            head: self.head.as_ptr(),
+            next: self.first(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn first(&self) -> *mut LIST_ENTRY {
+        // SAFETY: `head` is always a valid, initialized `LIST_ENTRY`.
+        unsafe { (*self.head.as_ptr()).Flink }
+    }
+
+    fn last(&self) -> *mut LIST_ENTRY {
+        // SAFETY: `head` is always a valid, initialized `LIST_ENTRY`.
+        unsafe { (*self.head.as_ptr()).Blink }
+    }
+
+    /// # Safety
+    ///
+    /// `entry` must be an entry in this list, other than the head.
+    // Items are owned as `Box`es whenever they are outside of the list
+    #[allow(clippy::unnecessary_box_returns)]
+    unsafe fn remove_entry(entry: *mut LIST_ENTRY) -> Box<T> {
+        // SAFETY: The caller guarantees that `entry` is an entry in this list.
+        unsafe {
+            unlink(entry);
+        }
+
+        // SAFETY: `entry` was inserted by `push_front` or `push_back`, which released
+        // the `T` it is embedded in from its `Box`.
+        unsafe { Box::from_raw(Self::item_of(entry)) }
+    }
+
+    fn entry_of(item: *mut T) -> *mut LIST_ENTRY {
+        // `ListEntry` is a transparent wrapper around a `LIST_ENTRY`
+        item.cast::<u8>().wrapping_add(T::ENTRY_OFFSET).cast()
+    }
+
+    fn item_of(entry: *mut LIST_ENTRY) -> *mut T {
+        entry.cast::<u8>().wrapping_sub(T::ENTRY_OFFSET).cast()
+    }
+}
+
+impl<T: ListItem> Drop for List<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<'a, T: ListItem> IntoIterator for &'a List<T> {
+    type IntoIter = Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over shared references to the items in a [`List`].
+///
+/// This is returned by [`List::iter()`].
+pub struct Iter<'a, T: ListItem> {
+    head: *mut LIST_ENTRY,
+    next: *mut LIST_ENTRY,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T: ListItem> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next == self.head {
+            return None;
+        }
+        let entry = self.next;
+
+        // SAFETY: `entry` is an entry in the list, which is borrowed for `'a`.
+        unsafe {
+            self.next = (*entry).Flink;
+        }
+
+        let item;
+        // SAFETY: `entry` is an entry in the list, so it is embedded in a `T` that
+        // lives as long as the shared borrow of the list.
+        unsafe {
+            item = &*List::<T>::item_of(entry);
+        }
+        Some(item)
+    }
+}
+
+/// Link `entry` between two adjacent entries, as `InsertHeadList` and
+/// `InsertTailList` do
+///
+/// # Safety
+///
+/// `prev` and `next` must be valid, adjacent entries in the same list, and
+/// `entry` must be valid and not in any list.
+unsafe fn insert_between(entry: *mut LIST_ENTRY, prev: *mut LIST_ENTRY, next: *mut LIST_ENTRY) {
+    // SAFETY: The caller guarantees that `entry` is valid.
+    unsafe {
+        *entry = LIST_ENTRY {
+            Flink: next,
+            Blink: prev,
+        };
+    }
+    // SAFETY: The caller guarantees that `prev` is valid.
+    unsafe {
+        (*prev).Flink = entry;
+    }
+    // SAFETY: The caller guarantees that `next` is valid.
+    unsafe {
+        (*next).Blink = entry;
+    }
+}
+
+/// Unlink `entry` from the list it is in, as `RemoveEntryList` does
+///
+/// # Safety
+///
+/// `entry` must be a valid entry in a list, other than the head.
+unsafe fn unlink(entry: *mut LIST_ENTRY) {
+    let links;
+    // SAFETY: The caller guarantees that `entry` is valid.
+    unsafe {
+        links = *entry;
+    }
+    // SAFETY: The neighbors of a valid entry in a list are valid.
+    unsafe {
+        (*links.Blink).Flink = links.Flink;
+    }
+    // SAFETY: The neighbors of a valid entry in a list are valid.
+    unsafe {
+        (*links.Flink).Blink = links.Blink;
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Collection types built on kernel data structures, and collections allocated
+//! from pool whose growth operations are fallible
+//!
+//! Growing an `alloc` collection aborts when the allocation fails, which takes
+//! down the whole system in kernel mode. [`KVec`], [`KString`] and
+//! [`KHashMap`] instead return `STATUS_INSUFFICIENT_RESOURCES` from every
+//! operation that allocates, and allocate from the pool and with the tag they
+//! are constructed with. They do not require the `alloc` feature.
+
+mod hash_map;
+#[cfg(feature = "alloc")]
+mod list;
+mod string;
+mod vec;
+
+pub use hash_map::*;
+#[cfg(feature = "alloc")]
+pub use list::*;
+pub use string::*;
+pub use vec::*;
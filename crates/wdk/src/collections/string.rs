@@ -0,0 +1,247 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::fmt;
+
+use wdk_sys::{NTSTATUS, ULONG};
+
+use super::KVec;
+use crate::wdf::PoolType;
+
+/// A growable UTF-8 string allocated from a pool, whose growth operations
+/// return an error instead of aborting when the pool is exhausted.
+///
+/// This is to `alloc::string::String` what [`KVec`] is to `alloc::vec::Vec`,
+/// with the same `IRQL` requirements. It implements [`fmt::Write`], so it can
+/// be formatted into with `write!`, which fails if the string cannot grow.
+///
+/// ```ignore
+/// let mut name = KString::try_from_str("device-", PoolType::NonPaged, 0)?;
+/// write!(name, "{index}").map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+/// ```
+#[derive(PartialEq, Eq)]
+pub struct KString {
+    bytes: KVec<u8>,
+}
+
+impl KString {
+    /// Construct an empty [`KString`] that allocates from `pool_type`,
+    /// without allocating
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    #[must_use]
+    pub const fn new(pool_type: PoolType, pool_tag: ULONG) -> Self {
+        Self {
+            bytes: KVec::new(pool_type, pool_tag),
+        }
+    }
+
+    /// Try to construct an empty [`KString`] that allocates from `pool_type`,
+    /// with room for at least `capacity` bytes
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn with_capacity(
+        capacity: usize,
+        pool_type: PoolType,
+        pool_tag: ULONG,
+    ) -> Result<Self, NTSTATUS> {
+        Ok(Self {
+            bytes: KVec::with_capacity(capacity, pool_type, pool_tag)?,
+        })
+    }
+
+    /// Try to copy `string` into a [`KString`] allocated from `pool_type`
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_from_str(
+        string: &str,
+        pool_type: PoolType,
+        pool_tag: ULONG,
+    ) -> Result<Self, NTSTATUS> {
+        let mut k_string = Self::with_capacity(string.len(), pool_type, pool_tag)?;
+        k_string.try_push_str(string)?;
+        Ok(k_string)
+    }
+
+    /// Returns the kind of pool that the string is allocated from
+    #[must_use]
+    pub const fn pool_type(&self) -> PoolType {
+        self.bytes.pool_type()
+    }
+
+    /// Returns the pool tag that the string is allocated with
+    #[must_use]
+    pub const fn pool_tag(&self) -> ULONG {
+        self.bytes.pool_tag()
+    }
+
+    /// Returns the contents of the string
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        // SAFETY: `bytes` is only ever appended to with complete UTF-8 strings, and
+        // only truncated at character boundaries.
+        unsafe { core::str::from_utf8_unchecked(self.bytes.as_slice()) }
+    }
+
+    /// Returns the length of the string, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if the string is empty
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns how many bytes the string can hold without reallocating
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    /// Try to make room for at least `additional` more bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), NTSTATUS> {
+        self.bytes.try_reserve(additional)
+    }
+
+    /// Try to append `string`
+    ///
+    /// The string is left unchanged if this fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be grown,
+    /// in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_push_str(&mut self, string: &str) -> Result<(), NTSTATUS> {
+        self.bytes.try_extend_from_slice(string.as_bytes())
+    }
+
+    /// Try to append `character`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be grown,
+    /// in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_push(&mut self, character: char) -> Result<(), NTSTATUS> {
+        self.try_push_str(character.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Remove and return the last character of the string, or [`None`] if it
+    /// is empty
+    pub fn pop(&mut self) -> Option<char> {
+        let character = self.as_str().chars().next_back()?;
+        self.bytes.truncate(self.len() - character.len_utf8());
+        Some(character)
+    }
+
+    /// Shorten the string to `len` bytes, if it is longer
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is not on a character boundary.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            assert!(
+                self.as_str().is_char_boundary(len),
+                "KString should only be truncated at a character boundary"
+            );
+            self.bytes.truncate(len);
+        }
+    }
+
+    /// Remove the contents of the string, keeping its capacity
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    /// Try to clone the string into a new allocation from the same pool
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_clone(&self) -> Result<Self, NTSTATUS> {
+        Ok(Self {
+            bytes: self.bytes.try_clone()?,
+        })
+    }
+}
+
+impl core::ops::Deref for KString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for KString {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+impl core::borrow::Borrow<str> for KString {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl core::hash::Hash for KString {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialEq<str> for KString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for KString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Write for KString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.try_push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Display for KString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for KString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,462 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{fmt, marker::PhantomData, ptr::NonNull};
+
+use wdk_sys::{
+    ntddk::{ExAllocatePool2, ExFreePool},
+    MEMORY_ALLOCATION_ALIGNMENT,
+    NTSTATUS,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    ULONG,
+};
+
+use crate::{pool::POOL_TAG, wdf::PoolType};
+
+/// A contiguous, growable array of `T`s allocated from a pool, whose growth
+/// operations return an error instead of aborting when the pool is exhausted.
+///
+/// Unlike `alloc::vec::Vec`, every operation that allocates is fallible, and
+/// the pool and tag of the allocation are chosen by the driver. Growing the
+/// array must be done at `IRQL` <= `APC_LEVEL` for [`PoolType::Paged`], or
+/// `IRQL` <= `DISPATCH_LEVEL` otherwise, and a [`KVec`] of paged pool must
+/// only be accessed and dropped at `IRQL` <= `APC_LEVEL`.
+///
+/// ```ignore
+/// let mut requests = KVec::with_capacity(16, PoolType::NonPaged, u32::from_ne_bytes(*b"Samp"))?;
+/// requests.try_push(request_id)?;
+/// ```
+pub struct KVec<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    pool_type: PoolType,
+    pool_tag: ULONG,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: `KVec` owns its `T`s, like a `Vec`, and its pool allocation can be
+// freed from any thread.
+unsafe impl<T: Send> Send for KVec<T> {}
+
+// SAFETY: `KVec` only allows its `T`s to be mutated through `&mut self`.
+unsafe impl<T: Sync> Sync for KVec<T> {}
+
+impl<T> KVec<T> {
+    /// Construct an empty [`KVec`] that allocates from `pool_type`, without
+    /// allocating
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    #[must_use]
+    pub const fn new(pool_type: PoolType, pool_tag: ULONG) -> Self {
+        Self {
+            ptr: NonNull::dangling(),
+            len: 0,
+            capacity: if size_of::<T>() == 0 { usize::MAX } else { 0 },
+            pool_type,
+            pool_tag: if pool_tag == 0 { POOL_TAG } else { pool_tag },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Try to construct an empty [`KVec`] that allocates from `pool_type`,
+    /// with room for at least `capacity` elements
+    ///
+    /// A `pool_tag` of 0 tags its allocations with `rust`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn with_capacity(
+        capacity: usize,
+        pool_type: PoolType,
+        pool_tag: ULONG,
+    ) -> Result<Self, NTSTATUS> {
+        let mut vec = Self::new(pool_type, pool_tag);
+        vec.try_reserve_exact(capacity)?;
+        Ok(vec)
+    }
+
+    /// Returns the kind of pool that the array is allocated from
+    #[must_use]
+    pub const fn pool_type(&self) -> PoolType {
+        self.pool_type
+    }
+
+    /// Returns the pool tag that the array is allocated with
+    #[must_use]
+    pub const fn pool_tag(&self) -> ULONG {
+        self.pool_tag
+    }
+
+    /// Returns the number of elements in the array
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the array contains no elements
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns how many elements the array can hold without reallocating
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the elements of the array
+    #[must_use]
+    pub const fn as_slice(&self) -> &[T] {
+        // SAFETY: The first `len` elements of `ptr` are initialized, and `ptr` is
+        // aligned and non-null even when nothing is allocated.
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Returns the elements of the array, mutably
+    #[must_use]
+    pub const fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: The first `len` elements of `ptr` are initialized, and `ptr` is
+        // aligned and non-null even when nothing is allocated.
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Try to make room for at least `additional` more elements, allocating
+    /// more than that to amortize the cost of repeated growth
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), NTSTATUS> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+        self.grow(required.max(self.capacity.saturating_mul(2)).max(4))
+    }
+
+    /// Try to make room for exactly `additional` more elements
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), NTSTATUS> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+        self.grow(required)
+    }
+
+    /// Try to append `value` to the end of the array
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array was full and could not
+    /// be grown, in which case `value` is dropped and the error variant will
+    /// contain `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_push(&mut self, value: T) -> Result<(), NTSTATUS> {
+        self.try_reserve(1)?;
+        self.push_within_capacity(value)
+            .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)
+    }
+
+    /// Append `value` to the end of the array if it has room for it, without
+    /// allocating
+    ///
+    /// # Errors
+    ///
+    /// This function will return `value` if the array is full.
+    pub const fn push_within_capacity(&mut self, value: T) -> Result<(), T> {
+        if self.len == self.capacity {
+            return Err(value);
+        }
+        let slot = self.ptr.as_ptr().wrapping_add(self.len);
+        // SAFETY: `len` is less than `capacity`, so the element after the last one
+        // is allocated and uninitialized.
+        unsafe {
+            slot.write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove and return the last element of the array, or [`None`] if it is
+    /// empty
+    pub const fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let slot = self.ptr.as_ptr().wrapping_add(self.len);
+        let value;
+        // SAFETY: The element at `len` was the last initialized element, and is no
+        // longer part of the array, so it is only read once.
+        unsafe {
+            value = slot.read();
+        }
+        Some(value)
+    }
+
+    /// Try to insert `value` at `index`, shifting the elements after it back
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array was full and could not
+    /// be grown, in which case `value` is dropped and the error variant will
+    /// contain `STATUS_INSUFFICIENT_RESOURCES`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the array.
+    pub fn try_insert(&mut self, index: usize, value: T) -> Result<(), NTSTATUS> {
+        assert!(
+            index <= self.len,
+            "insertion index (is {index}) should be <= len (is {})",
+            self.len
+        );
+        self.try_reserve(1)?;
+        let slot = self.ptr.as_ptr().wrapping_add(index);
+        // SAFETY: The elements from `index` to `len` are initialized, and there is
+        // room for one more after them.
+        unsafe {
+            core::ptr::copy(slot, slot.wrapping_add(1), self.len - index);
+        }
+        // SAFETY: The element at `index` was moved back above, so the slot is
+        // uninitialized.
+        unsafe {
+            slot.write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Remove and return the element at `index`, shifting the elements after
+    /// it forward
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "removal index (is {index}) should be < len (is {})",
+            self.len
+        );
+        let slot = self.ptr.as_ptr().wrapping_add(index);
+        let value;
+        // SAFETY: The element at `index` is initialized, and is overwritten below,
+        // so it is only read once.
+        unsafe {
+            value = slot.read();
+        }
+        // SAFETY: The elements after `index` are initialized, and are moved forward
+        // into the slot that was just read.
+        unsafe {
+            core::ptr::copy(slot.wrapping_add(1), slot, self.len - index - 1);
+        }
+        self.len -= 1;
+        value
+    }
+
+    /// Remove and return the element at `index`, replacing it with the last
+    /// element
+    ///
+    /// This does not preserve the order of the elements, but does not shift
+    /// them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "swap_remove index (is {index}) should be < len (is {})",
+            self.len
+        );
+        let last = self.len - 1;
+        self.as_mut_slice().swap(index, last);
+        self.pop()
+            .expect("KVec should not be empty after an element was found in it")
+    }
+
+    /// Shorten the array to `len` elements, dropping the rest, if it is
+    /// longer
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = core::ptr::slice_from_raw_parts_mut(
+            self.ptr.as_ptr().wrapping_add(len),
+            self.len - len,
+        );
+        // The elements are removed before they are dropped, so that a panicking
+        // `Drop` leaks them instead of dropping them again
+        self.len = len;
+        // SAFETY: The elements in `tail` were initialized, and are no longer part of
+        // the array.
+        unsafe {
+            core::ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Remove and drop every element, keeping the capacity of the array
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Try to reallocate the array with room for exactly `capacity` elements
+    fn grow(&mut self, capacity: usize) -> Result<(), NTSTATUS> {
+        // Pool allocations are aligned to `MEMORY_ALLOCATION_ALIGNMENT`
+        const {
+            assert!(
+                align_of::<T>() <= MEMORY_ALLOCATION_ALIGNMENT as usize,
+                "KVec elements should not be aligned more strictly than pool allocations"
+            );
+        }
+        let size = capacity
+            .checked_mul(size_of::<T>())
+            .ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        let allocation =
+            // SAFETY: The caller guarantees the `IRQL` that `pool_type` can be allocated from
+            unsafe {
+                ExAllocatePool2(self.pool_type.as_flags(), size as SIZE_T, self.pool_tag)
+            };
+        let ptr = NonNull::new(allocation.cast::<T>()).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+
+        // SAFETY: The first `len` elements of the old allocation are initialized, and
+        // are moved into the new allocation, which has room for at least `len`
+        // elements and does not overlap it.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.ptr.as_ptr(), ptr.as_ptr(), self.len);
+        }
+        self.free();
+        self.ptr = ptr;
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Free the allocation of the array, if it has one, without dropping its
+    /// elements
+    fn free(&mut self) {
+        if size_of::<T>() != 0 && self.capacity != 0 {
+            // SAFETY: `ptr` was allocated by `ExAllocatePool2` in `grow`, and is replaced
+            // or not used after this.
+            unsafe {
+                ExFreePool(self.ptr.as_ptr().cast());
+            }
+        }
+    }
+}
+
+impl<T: Clone> KVec<T> {
+    /// Try to append clones of the elements of `values` to the end of the
+    /// array
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array could not be grown to
+    /// hold all of `values`, in which case none of them are appended and the
+    /// error variant will contain `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_extend_from_slice(&mut self, values: &[T]) -> Result<(), NTSTATUS> {
+        self.try_reserve(values.len())?;
+        for value in values {
+            // Room for every value was reserved above
+            let _ = self.push_within_capacity(value.clone());
+        }
+        Ok(())
+    }
+
+    /// Try to clone the array into a new allocation from the same pool
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the array could not be
+    /// allocated, in which case the error variant will contain
+    /// `STATUS_INSUFFICIENT_RESOURCES`.
+    pub fn try_clone(&self) -> Result<Self, NTSTATUS> {
+        let mut clone = Self::with_capacity(self.len, self.pool_type, self.pool_tag)?;
+        clone.try_extend_from_slice(self)?;
+        Ok(clone)
+    }
+}
+
+impl<T> Drop for KVec<T> {
+    fn drop(&mut self) {
+        self.clear();
+        self.free();
+    }
+}
+
+impl<T> core::ops::Deref for KVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl<T> core::ops::DerefMut for KVec<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.as_mut_slice()
+    }
+}
+
+impl<T> AsRef<[T]> for KVec<T> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T> AsMut<[T]> for KVec<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<'a, T> IntoIterator for &'a KVec<T> {
+    type IntoIter = core::slice::Iter<'a, T>;
+    type Item = &'a T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut KVec<T> {
+    type IntoIter = core::slice::IterMut<'a, T>;
+    type Item = &'a mut T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: PartialEq> PartialEq for KVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq> Eq for KVec<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for KVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Printing to the kernel debugger with `DbgPrintEx`, without allocating.
+//!
+//! [`kprint!`] and [`kprintln!`] format their arguments like `print!` and
+//! `println!`, into a buffer on the stack, and pass it to `DbgPrintEx` with the
+//! component ID set by [`set_component_id()`] (`DPFLTR_IHVDRIVER_ID` by
+//! default). [`kerror!`], [`kwarn!`], [`ktrace!`] and [`kinfo!`] print a line
+//! at their [`Level`], and [`kdbg!`] prints the value of an expression, like
+//! `dbg!`.
+//!
+//! The kernel debugger only shows the messages of a component whose level is
+//! enabled in its debug print filter. [`Level::Error`] is enabled for every
+//! component by default, so [`kprint!`], [`kprintln!`] and [`kdbg!`] print at
+//! that level. The other levels can be enabled with the `Debug Print Filter`
+//! registry key, or in the debugger (ex. `ed nt!Kd_IHVDRIVER_Mask 0xF`).
+//!
+//! Unlike [`print!`](crate::print), these macros can be used at any `IRQL`
+//! <= `DIRQL`, as long as formatting their arguments can. Messages longer than
+//! 256 bytes are passed to `DbgPrintEx` in several pieces, which messages
+//! printed by other processors at the same time may come between.
+//!
+//! Messages can also be copied into a
+//! [`CrashLog`](crate::bug_check::CrashLog), so that they reach crash dumps
+//! whether or not the debug print filter lets them through, by registering it
+//! with [`set_crash_log()`]. This includes the records of the `log` crate
+//! backend of the `logger` module, which prints through this module.
+//!
+//! [`kprint!`]: crate::kprint
+//! [`kprintln!`]: crate::kprintln
+//! [`kerror!`]: crate::kerror
+//! [`kwarn!`]: crate::kwarn
+//! [`ktrace!`]: crate::ktrace
+//! [`kinfo!`]: crate::kinfo
+//! [`kdbg!`]: crate::kdbg
+
+use core::{
+    ffi::c_int,
+    fmt::{self, Write},
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+
+use wdk_sys::{
+    ntddk::DbgPrintEx,
+    _DPFLTR_TYPE::DPFLTR_IHVDRIVER_ID,
+    DPFLTR_ERROR_LEVEL,
+    DPFLTR_INFO_LEVEL,
+    DPFLTR_TRACE_LEVEL,
+    DPFLTR_TYPE,
+    DPFLTR_WARNING_LEVEL,
+    ULONG,
+};
+
+/// The most bytes passed to a single call to `DbgPrintEx`, which truncates
+/// anything past 512 bytes
+const CHUNK_LEN: usize = 256;
+
+/// The component ID that messages are printed with
+// `DPFLTR_IHVDRIVER_ID` is a small, positive component ID
+#[allow(clippy::cast_sign_loss)]
+static COMPONENT_ID: AtomicU32 = AtomicU32::new(DPFLTR_IHVDRIVER_ID as ULONG);
+
+/// The function registered with [`set_crash_log()`], as a pointer, or null
+static CRASH_LOG: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+/// The importance of a message printed to the kernel debugger, which decides
+/// whether the debug print filter lets it through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// `DPFLTR_ERROR_LEVEL`, which is shown by default
+    Error,
+    /// `DPFLTR_WARNING_LEVEL`
+    Warning,
+    /// `DPFLTR_TRACE_LEVEL`
+    Trace,
+    /// `DPFLTR_INFO_LEVEL`
+    Info,
+}
+
+impl Level {
+    /// Returns the `DPFLTR_*_LEVEL` value of this [`Level`]
+    #[must_use]
+    pub const fn as_raw(self) -> ULONG {
+        match self {
+            Self::Error => DPFLTR_ERROR_LEVEL,
+            Self::Warning => DPFLTR_WARNING_LEVEL,
+            Self::Trace => DPFLTR_TRACE_LEVEL,
+            Self::Info => DPFLTR_INFO_LEVEL,
+        }
+    }
+}
+
+/// Set the component ID that messages are printed with, such as
+/// `DPFLTR_IHVAUDIO_ID` for an audio driver
+///
+/// The debug print filter of the component decides which messages are shown.
+/// This can be called at any `IRQL`.
+pub fn set_component_id(component_id: DPFLTR_TYPE) {
+    // Component IDs are small, positive values
+    #[allow(clippy::cast_sign_loss)]
+    let component_id = component_id as ULONG;
+    COMPONENT_ID.store(component_id, Ordering::Relaxed);
+}
+
+/// Returns the component ID that messages are printed with
+#[must_use]
+pub fn component_id() -> ULONG {
+    COMPONENT_ID.load(Ordering::Relaxed)
+}
+
+/// Register `write` to be called with the bytes of every message printed to
+/// the kernel debugger by this module, or unregister the current function
+/// with [`None`]
+///
+/// `write` is meant to append the bytes to a
+/// [`CrashLog`](crate::bug_check::CrashLog), so that the messages reach crash
+/// dumps. It is called on the thread that prints, at the `IRQL` it prints at,
+/// with messages of every [`Level`], including those that the debug print
+/// filter does not let through. This can be called at any `IRQL`.
+///
+/// ```ignore
+/// static LOG: CrashLog<4096> = CrashLog::new();
+///
+/// debug_print::set_crash_log(Some(|bytes| LOG.write(bytes)));
+/// ```
+pub fn set_crash_log(write: Option<fn(&[u8])>) {
+    let write = write.map_or(ptr::null_mut(), |write| write as *mut ());
+    CRASH_LOG.store(write, Ordering::Release);
+}
+
+/// Returns the function registered with [`set_crash_log()`], if any
+fn crash_log() -> Option<fn(&[u8])> {
+    let write_ptr = CRASH_LOG.load(Ordering::Acquire);
+    if write_ptr.is_null() {
+        return None;
+    }
+    let write;
+    // SAFETY: Non-null values of `CRASH_LOG` are only ever stored by
+    // `set_crash_log`, from a `fn(&[u8])`.
+    unsafe {
+        write = core::mem::transmute::<*mut (), fn(&[u8])>(write_ptr);
+    }
+    Some(write)
+}
+
+/// Print a message to the kernel debugger, without a newline, at
+/// [`Level::Error`](crate::debug_print::Level::Error)
+///
+/// ```ignore
+/// kprint!("queue {index}: ");
+/// ```
+#[macro_export]
+macro_rules! kprint {
+    ($($arg:tt)*) => {
+        $crate::debug_print::_kprint(
+            $crate::debug_print::Level::Error,
+            ::core::format_args!($($arg)*),
+        )
+    };
+}
+
+/// Print a message to the kernel debugger, with a newline, at
+/// [`Level::Error`](crate::debug_print::Level::Error)
+///
+/// ```ignore
+/// kprintln!("device {index} started with {count} queues");
+/// ```
+#[macro_export]
+macro_rules! kprintln {
+    () => {
+        $crate::kprint!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::kprint!("{}\n", ::core::format_args!($($arg)*))
+    };
+}
+
+/// Print a message to the kernel debugger, with a newline, at
+/// [`Level::Error`](crate::debug_print::Level::Error)
+#[macro_export]
+macro_rules! kerror {
+    ($($arg:tt)*) => {
+        $crate::debug_print::_kprint(
+            $crate::debug_print::Level::Error,
+            ::core::format_args!("{}\n", ::core::format_args!($($arg)*)),
+        )
+    };
+}
+
+/// Print a message to the kernel debugger, with a newline, at
+/// [`Level::Warning`](crate::debug_print::Level::Warning)
+#[macro_export]
+macro_rules! kwarn {
+    ($($arg:tt)*) => {
+        $crate::debug_print::_kprint(
+            $crate::debug_print::Level::Warning,
+            ::core::format_args!("{}\n", ::core::format_args!($($arg)*)),
+        )
+    };
+}
+
+/// Print a message to the kernel debugger, with a newline, at
+/// [`Level::Trace`](crate::debug_print::Level::Trace)
+#[macro_export]
+macro_rules! ktrace {
+    ($($arg:tt)*) => {
+        $crate::debug_print::_kprint(
+            $crate::debug_print::Level::Trace,
+            ::core::format_args!("{}\n", ::core::format_args!($($arg)*)),
+        )
+    };
+}
+
+/// Print a message to the kernel debugger, with a newline, at
+/// [`Level::Info`](crate::debug_print::Level::Info)
+#[macro_export]
+macro_rules! kinfo {
+    ($($arg:tt)*) => {
+        $crate::debug_print::_kprint(
+            $crate::debug_print::Level::Info,
+            ::core::format_args!("{}\n", ::core::format_args!($($arg)*)),
+        )
+    };
+}
+
+/// Print the source location, and the expressions and their values, to the
+/// kernel debugger at [`Level::Error`](crate::debug_print::Level::Error), and
+/// return the values, like `dbg!`
+///
+/// ```ignore
+/// let length = kdbg!(request.input_buffer_length());
+/// ```
+#[macro_export]
+macro_rules! kdbg {
+    () => {
+        $crate::kprintln!(
+            "[{}:{}:{}]",
+            ::core::file!(),
+            ::core::line!(),
+            ::core::column!()
+        )
+    };
+    ($val:expr $(,)?) => {
+        // Matching keeps the temporaries of `$val` alive, like `dbg!`
+        match $val {
+            tmp => {
+                $crate::kprintln!(
+                    "[{}:{}:{}] {} = {:#?}",
+                    ::core::file!(),
+                    ::core::line!(),
+                    ::core::column!(),
+                    ::core::stringify!($val),
+                    &tmp
+                );
+                tmp
+            }
+        }
+    };
+    ($($val:expr),+ $(,)?) => {
+        ($($crate::kdbg!($val)),+,)
+    };
+}
+
+/// Internal implementation of the `kprint!` family of macros. This function is
+/// an implementation detail and should never be called directly, but must be
+/// public to be useable by the macros.
+#[doc(hidden)]
+pub fn _kprint(level: Level, args: fmt::Arguments) {
+    print(level, args);
+}
+
+/// Print `args` to the kernel debugger at `level`, with the current component
+/// ID
+pub(crate) fn print(level: Level, args: fmt::Arguments) {
+    let mut writer = DbgPrintWriter {
+        component_id: component_id(),
+        level: level.as_raw(),
+        crash_log: crash_log(),
+        buffer: [0; CHUNK_LEN],
+        len: 0,
+    };
+    // `DbgPrintWriter` never fails, and a message that fails to format is still
+    // worth printing as far as it got
+    let _ = writer.write_fmt(args);
+    writer.flush();
+}
+
+/// A [`fmt::Write`] implementation that prints to the kernel debugger in
+/// chunks of [`CHUNK_LEN`] bytes, without allocating
+struct DbgPrintWriter {
+    component_id: ULONG,
+    level: ULONG,
+    crash_log: Option<fn(&[u8])>,
+    buffer: [u8; CHUNK_LEN],
+    len: usize,
+}
+
+impl DbgPrintWriter {
+    /// Print the buffered bytes, and copy them to the crash log
+    fn flush(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        if let Some(crash_log) = self.crash_log {
+            crash_log(&self.buffer[..self.len]);
+        }
+
+        // `len` is at most `CHUNK_LEN`, which fits in a `c_int`
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let len = self.len as c_int;
+        // SAFETY: The format string is nul-terminated, and its `%.*s` conversion
+        // only reads the `len` bytes of `buffer` that are initialized. `DbgPrintEx`
+        // can be called at any `IRQL` <= `DIRQL` with a format string that has no
+        // Unicode conversions.
+        unsafe {
+            DbgPrintEx(
+                self.component_id,
+                self.level,
+                c"%.*s".as_ptr(),
+                len,
+                self.buffer.as_ptr(),
+            );
+        }
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for DbgPrintWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Chunks are split by bytes rather than characters, since the debugger
+        // joins them back together
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            let remaining = &mut self.buffer[self.len..];
+            let count = bytes.len().min(remaining.len());
+            remaining[..count].copy_from_slice(&bytes[..count]);
+            self.len += count;
+            bytes = &bytes[count..];
+            if self.len == CHUNK_LEN {
+                self.flush();
+            }
+        }
+        Ok(())
+    }
+}
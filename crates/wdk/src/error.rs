@@ -0,0 +1,141 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! [`WdfError`], an error type implementing [`core::error::Error`] that
+//! drivers can propagate with `?` and complete requests with.
+
+use core::fmt;
+
+use wdk_sys::NTSTATUS;
+
+use crate::status::NtStatus;
+
+/// An error returned by a WDK or WDF operation.
+///
+/// The statuses that drivers most often need to handle get their own
+/// variants, and every other failure is kept as [`WdfError::Status`], so no
+/// information is lost converting to and from [`NtStatus`] or `NTSTATUS`. This
+/// lets a driver propagate errors from functions returning either with `?`,
+/// and convert the error back to a status at the boundary, such as when
+/// completing a request.
+///
+/// ```ignore
+/// fn read(request: &Request) -> Result<usize, WdfError> {
+///     let buffer = request.retrieve_output_buffer(1)?;
+///     ...
+/// }
+///
+/// match read(&request) {
+///     Ok(information) => request.complete(STATUS_SUCCESS, information),
+///     Err(error) => request.complete(error.into(), 0),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WdfError {
+    /// Memory or another resource could not be allocated,
+    /// `STATUS_INSUFFICIENT_RESOURCES`
+    InsufficientResources,
+    /// A parameter was not valid, `STATUS_INVALID_PARAMETER`
+    InvalidParameter,
+    /// The request is not valid for the device, `STATUS_INVALID_DEVICE_REQUEST`
+    InvalidDeviceRequest,
+    /// The device is not in a state that allows the operation,
+    /// `STATUS_INVALID_DEVICE_STATE`
+    InvalidDeviceState,
+    /// A buffer was too small for the operation, `STATUS_BUFFER_TOO_SMALL`
+    BufferTooSmall,
+    /// The operation is not supported, `STATUS_NOT_SUPPORTED`
+    NotSupported,
+    /// The operation was cancelled, `STATUS_CANCELLED`
+    Cancelled,
+    /// The operation did not complete in time, `STATUS_IO_TIMEOUT`
+    Timeout,
+    /// The device has been removed, `STATUS_DEVICE_REMOVED`
+    DeviceRemoved,
+    /// Any other unsuccessful status
+    Status(NtStatus),
+}
+
+impl WdfError {
+    /// Returns the [`NtStatus`] that this error corresponds to
+    #[must_use]
+    pub const fn status(self) -> NtStatus {
+        match self {
+            Self::InsufficientResources => NtStatus::STATUS_INSUFFICIENT_RESOURCES,
+            Self::InvalidParameter => NtStatus::STATUS_INVALID_PARAMETER,
+            Self::InvalidDeviceRequest => NtStatus::STATUS_INVALID_DEVICE_REQUEST,
+            Self::InvalidDeviceState => NtStatus::STATUS_INVALID_DEVICE_STATE,
+            Self::BufferTooSmall => NtStatus::STATUS_BUFFER_TOO_SMALL,
+            Self::NotSupported => NtStatus::STATUS_NOT_SUPPORTED,
+            Self::Cancelled => NtStatus::STATUS_CANCELLED,
+            Self::Timeout => NtStatus::STATUS_IO_TIMEOUT,
+            Self::DeviceRemoved => NtStatus::STATUS_DEVICE_REMOVED,
+            Self::Status(status) => status,
+        }
+    }
+
+    /// Construct the [`WdfError`] that corresponds to `status`
+    ///
+    /// `status` is expected to be unsuccessful. A successful status is kept
+    /// as [`WdfError::Status`], so that it is not lost.
+    #[must_use]
+    pub const fn from_status(status: NtStatus) -> Self {
+        match status {
+            NtStatus::STATUS_INSUFFICIENT_RESOURCES => Self::InsufficientResources,
+            NtStatus::STATUS_INVALID_PARAMETER => Self::InvalidParameter,
+            NtStatus::STATUS_INVALID_DEVICE_REQUEST => Self::InvalidDeviceRequest,
+            NtStatus::STATUS_INVALID_DEVICE_STATE => Self::InvalidDeviceState,
+            NtStatus::STATUS_BUFFER_TOO_SMALL => Self::BufferTooSmall,
+            NtStatus::STATUS_NOT_SUPPORTED => Self::NotSupported,
+            NtStatus::STATUS_CANCELLED => Self::Cancelled,
+            NtStatus::STATUS_IO_TIMEOUT => Self::Timeout,
+            NtStatus::STATUS_DEVICE_REMOVED => Self::DeviceRemoved,
+            status => Self::Status(status),
+        }
+    }
+}
+
+impl From<NtStatus> for WdfError {
+    fn from(status: NtStatus) -> Self {
+        Self::from_status(status)
+    }
+}
+
+impl From<NTSTATUS> for WdfError {
+    fn from(status: NTSTATUS) -> Self {
+        Self::from_status(NtStatus::from_raw(status))
+    }
+}
+
+impl From<WdfError> for NtStatus {
+    fn from(error: WdfError) -> Self {
+        error.status()
+    }
+}
+
+impl From<WdfError> for NTSTATUS {
+    fn from(error: WdfError) -> Self {
+        error.status().into_raw()
+    }
+}
+
+impl fmt::Display for WdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::InsufficientResources => "insufficient resources",
+            Self::InvalidParameter => "invalid parameter",
+            Self::InvalidDeviceRequest => "invalid device request",
+            Self::InvalidDeviceState => "invalid device state",
+            Self::BufferTooSmall => "buffer too small",
+            Self::NotSupported => "not supported",
+            Self::Cancelled => "cancelled",
+            Self::Timeout => "timed out",
+            Self::DeviceRemoved => "device removed",
+            Self::Status(status) => return write!(f, "operation failed with {status}"),
+        };
+        write!(f, "{description} ({})", self.status())
+    }
+}
+
+impl core::error::Error for WdfError {}
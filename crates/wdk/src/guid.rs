@@ -0,0 +1,332 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! `GUID`s, and their compile-time construction from their string
+//! representation.
+
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use wdk_sys::GUID;
+
+/// A globally unique identifier, such as a device interface class, a WMI data
+/// block or the category of a device property key.
+///
+/// [`Guid`] wraps a [`GUID`](wdk_sys::GUID), which it can be converted to and
+/// from, and dereferences to, so it can be passed to any function that takes a
+/// `&GUID`. Unlike a `GUID`, it can be compared and hashed, and is formatted
+/// in the registry format (ex. `{5CD3C1B6-0A4E-4C5B-9D2E-6F1A8B3C7D90}`).
+///
+/// ```ignore
+/// const GUID_DEVINTERFACE_SAMPLE: Guid = Guid::parse("5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90");
+/// device.create_device_interface(&GUID_DEVINTERFACE_SAMPLE, None)?;
+/// println!("created interface {GUID_DEVINTERFACE_SAMPLE}");
+/// ```
+#[derive(Clone, Copy, Default)]
+#[repr(transparent)]
+pub struct Guid(GUID);
+
+impl Guid {
+    /// The `GUID` whose bits are all 0, `GUID_NULL`
+    pub const NULL: Self = Self(GUID {
+        Data1: 0,
+        Data2: 0,
+        Data3: 0,
+        Data4: [0; 8],
+    });
+
+    /// Construct a [`Guid`] from a raw `GUID`
+    #[must_use]
+    pub const fn from_raw(guid: GUID) -> Self {
+        Self(guid)
+    }
+
+    /// Parse the string representation of a `GUID`, in the
+    /// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` format, optionally surrounded by
+    /// braces
+    ///
+    /// This is a `const fn`, so a [`Guid`] constant is parsed at compile time,
+    /// like [`guid!`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `guid` is not a valid `GUID` string.
+    #[must_use]
+    pub const fn parse(guid: &str) -> Self {
+        Self(parse(guid))
+    }
+
+    /// Returns the raw `GUID` wrapped by this [`Guid`]
+    #[must_use]
+    pub const fn as_raw(&self) -> &GUID {
+        &self.0
+    }
+
+    /// Returns the raw `GUID` wrapped by this [`Guid`]
+    #[must_use]
+    pub const fn into_raw(self) -> GUID {
+        self.0
+    }
+
+    /// Returns whether this is [`Guid::NULL`]
+    #[must_use]
+    pub const fn is_null(&self) -> bool {
+        let mut index = 0;
+        while index < self.0.Data4.len() {
+            if self.0.Data4[index] != 0 {
+                return false;
+            }
+            index += 1;
+        }
+        self.0.Data1 == 0 && self.0.Data2 == 0 && self.0.Data3 == 0
+    }
+
+    /// Returns the fields of the `GUID`, in the order they are compared in
+    const fn fields(&self) -> (u32, u16, u16, [u8; 8]) {
+        (self.0.Data1, self.0.Data2, self.0.Data3, self.0.Data4)
+    }
+}
+
+impl From<GUID> for Guid {
+    fn from(guid: GUID) -> Self {
+        Self(guid)
+    }
+}
+
+impl From<Guid> for GUID {
+    fn from(guid: Guid) -> Self {
+        guid.0
+    }
+}
+
+impl Deref for Guid {
+    type Target = GUID;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<GUID> for Guid {
+    fn as_ref(&self) -> &GUID {
+        &self.0
+    }
+}
+
+impl PartialEq for Guid {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields() == other.fields()
+    }
+}
+
+impl Eq for Guid {}
+
+impl PartialEq<GUID> for Guid {
+    fn eq(&self, other: &GUID) -> bool {
+        *self == Self(*other)
+    }
+}
+
+impl PartialOrd for Guid {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Guid {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.fields().cmp(&other.fields())
+    }
+}
+
+impl Hash for Guid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fields().hash(state);
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (data1, data2, data3, data4) = self.fields();
+        write!(
+            f,
+            "{{{data1:08X}-{data2:04X}-{data3:04X}-{:02X}{:02X}-",
+            data4[0], data4[1]
+        )?;
+        for byte in &data4[2..] {
+            write!(f, "{byte:02X}")?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Construct a [`GUID`](wdk_sys::GUID) constant from its string
+/// representation, with or without braces.
+///
+/// The string is parsed at compile time, so a malformed `GUID` does not
+/// compile. Use [`Guid::parse()`] to construct a [`Guid`] constant instead.
+///
+/// ```ignore
+/// const GUID_DEVINTERFACE_SAMPLE: GUID = guid!("5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90");
+/// ```
+#[macro_export]
+macro_rules! guid {
+    ($guid:literal) => {
+        const { $crate::guid::parse($guid) }
+    };
+}
+
+/// Parse the string representation of a `GUID`, in the
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` format, optionally surrounded by
+/// braces
+///
+/// This is a `const fn`, so it is usually called through [`guid!`], which
+/// evaluates it at compile time, or through [`Guid::parse()`].
+///
+/// # Panics
+///
+/// Panics if `guid` is not a valid `GUID` string.
+// The `as` casts below only truncate values that were parsed from at most as
+// many hex digits as fit in the target type
+#[allow(clippy::cast_possible_truncation)]
+#[must_use]
+pub const fn parse(guid: &str) -> GUID {
+    let bytes = guid.as_bytes();
+    let offset = match bytes.len() {
+        36 => 0,
+        38 if bytes[0] == b'{' && bytes[37] == b'}' => 1,
+        _ => panic!("GUID strings must be 36 characters long, or 38 with braces"),
+    };
+
+    let mut index = 0;
+    while index < 4 {
+        let hyphen = [8, 13, 18, 23][index];
+        assert!(
+            bytes[offset + hyphen] == b'-',
+            "GUID strings must separate their groups with hyphens"
+        );
+        index += 1;
+    }
+
+    GUID {
+        Data1: parse_hex(bytes, offset, 8) as u32,
+        Data2: parse_hex(bytes, offset + 9, 4) as u16,
+        Data3: parse_hex(bytes, offset + 14, 4) as u16,
+        Data4: [
+            parse_hex(bytes, offset + 19, 2) as u8,
+            parse_hex(bytes, offset + 21, 2) as u8,
+            parse_hex(bytes, offset + 24, 2) as u8,
+            parse_hex(bytes, offset + 26, 2) as u8,
+            parse_hex(bytes, offset + 28, 2) as u8,
+            parse_hex(bytes, offset + 30, 2) as u8,
+            parse_hex(bytes, offset + 32, 2) as u8,
+            parse_hex(bytes, offset + 34, 2) as u8,
+        ],
+    }
+}
+
+/// Parse the `digits` hex digits of `bytes` starting at `start`
+const fn parse_hex(bytes: &[u8], start: usize, digits: usize) -> u64 {
+    let mut value = 0;
+    let mut index = start;
+    while index < start + digits {
+        let digit = match bytes[index] {
+            b'0'..=b'9' => bytes[index] - b'0',
+            b'a'..=b'f' => bytes[index] - b'a' + 10,
+            b'A'..=b'F' => bytes[index] - b'A' + 10,
+            _ => panic!("GUID strings must only contain hex digits and hyphens"),
+        };
+        value = value << 4 | digit as u64;
+        index += 1;
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::format;
+
+    use super::*;
+
+    const SAMPLE: Guid = Guid::from_raw(GUID {
+        Data1: 0x5CD3_C1B6,
+        Data2: 0x0A4E,
+        Data3: 0x4C5B,
+        Data4: [0x9D, 0x2E, 0x6F, 0x1A, 0x8B, 0x3C, 0x7D, 0x90],
+    });
+
+    #[test]
+    fn parse_unbraced() {
+        assert_eq!(Guid::parse("5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90"), SAMPLE);
+        assert_eq!(Guid::parse("5CD3C1B6-0A4E-4C5B-9D2E-6F1A8B3C7D90"), SAMPLE);
+    }
+
+    #[test]
+    fn parse_braced() {
+        assert_eq!(
+            Guid::parse("{5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90}"),
+            SAMPLE
+        );
+    }
+
+    #[test]
+    fn parse_null() {
+        let guid = Guid::parse("00000000-0000-0000-0000-000000000000");
+        assert!(guid.is_null());
+        assert_eq!(guid, Guid::NULL);
+        assert!(!SAMPLE.is_null());
+    }
+
+    #[test]
+    fn guid_macro_parses_at_compile_time() {
+        const RAW: GUID = crate::guid!("{5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90}");
+        assert_eq!(SAMPLE, RAW);
+    }
+
+    #[test]
+    #[should_panic(expected = "36 characters long")]
+    fn parse_rejects_wrong_length() {
+        let _ = Guid::parse("5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d9");
+    }
+
+    #[test]
+    #[should_panic(expected = "36 characters long")]
+    fn parse_rejects_unmatched_brace() {
+        let _ = Guid::parse("{5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7d90]");
+    }
+
+    #[test]
+    #[should_panic(expected = "hyphens")]
+    fn parse_rejects_misplaced_hyphen() {
+        let _ = Guid::parse("5cd3c1b60-a4e-4c5b-9d2e-6f1a8b3c7d90");
+    }
+
+    #[test]
+    #[should_panic(expected = "hex digits")]
+    fn parse_rejects_non_hex_digit() {
+        let _ = Guid::parse("5cd3c1b6-0a4e-4c5b-9d2e-6f1a8b3c7dg0");
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let string = format!("{SAMPLE}");
+        assert_eq!(string, "{5CD3C1B6-0A4E-4C5B-9D2E-6F1A8B3C7D90}");
+        assert_eq!(Guid::parse(&string), SAMPLE);
+        assert_eq!(format!("{SAMPLE:?}"), string);
+
+        let string = format!("{}", Guid::NULL);
+        assert_eq!(string, "{00000000-0000-0000-0000-000000000000}");
+        assert_eq!(Guid::parse(&string), Guid::NULL);
+    }
+}
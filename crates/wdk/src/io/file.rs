@@ -0,0 +1,378 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    ntddk::{ZwClose, ZwCreateFile, ZwQueryInformationFile, ZwReadFile, ZwWriteFile},
+    _FILE_INFORMATION_CLASS::FileStandardInformation,
+    ACCESS_MASK,
+    FILE_ATTRIBUTE_NORMAL,
+    FILE_CREATE,
+    FILE_GENERIC_READ,
+    FILE_GENERIC_WRITE,
+    FILE_NON_DIRECTORY_FILE,
+    FILE_OPEN,
+    FILE_OPEN_IF,
+    FILE_OVERWRITE,
+    FILE_OVERWRITE_IF,
+    FILE_SHARE_READ,
+    FILE_STANDARD_INFORMATION,
+    FILE_SUPERSEDE,
+    FILE_SYNCHRONOUS_IO_NONALERT,
+    HANDLE,
+    IO_STATUS_BLOCK,
+    LARGE_INTEGER,
+    NTSTATUS,
+    OBJECT_ATTRIBUTES,
+    OBJ_CASE_INSENSITIVE,
+    OBJ_KERNEL_HANDLE,
+    STATUS_END_OF_FILE,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+};
+
+use crate::{debug_assert_irql, irql::PassiveLevel, nt_success, wdf::name::UnicodeBuffer};
+
+// `OBJECT_ATTRIBUTES` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const OBJECT_ATTRIBUTES_LENGTH: ULONG = core::mem::size_of::<OBJECT_ATTRIBUTES>() as ULONG;
+
+// `FILE_STANDARD_INFORMATION` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const FILE_STANDARD_INFORMATION_LENGTH: ULONG =
+    core::mem::size_of::<FILE_STANDARD_INFORMATION>() as ULONG;
+
+/// The access to a [`KernelFile`] that it is opened with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileAccess {
+    /// Read the contents of the file
+    #[default]
+    Read,
+    /// Write the contents of the file
+    Write,
+    /// Both read and write the contents of the file
+    ReadWrite,
+}
+
+impl FileAccess {
+    const fn as_raw(self) -> ACCESS_MASK {
+        match self {
+            Self::Read => FILE_GENERIC_READ,
+            Self::Write => FILE_GENERIC_WRITE,
+            Self::ReadWrite => FILE_GENERIC_READ | FILE_GENERIC_WRITE,
+        }
+    }
+}
+
+/// What [`KernelFile::open()`] does when the file does or does not already
+/// exist
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CreateDisposition {
+    /// Open the file if it exists, and fail if it does not
+    #[default]
+    Open,
+    /// Create the file if it does not exist, and fail if it does
+    Create,
+    /// Open the file if it exists, and create it if it does not
+    OpenOrCreate,
+    /// Open and truncate the file if it exists, and fail if it does not
+    Overwrite,
+    /// Open and truncate the file if it exists, and create it if it does not
+    OverwriteOrCreate,
+    /// Replace the file with a new file if it exists, and create it if it
+    /// does not
+    Supersede,
+}
+
+impl CreateDisposition {
+    const fn as_raw(self) -> ULONG {
+        match self {
+            Self::Open => FILE_OPEN,
+            Self::Create => FILE_CREATE,
+            Self::OpenOrCreate => FILE_OPEN_IF,
+            Self::Overwrite => FILE_OVERWRITE,
+            Self::OverwriteOrCreate => FILE_OVERWRITE_IF,
+            Self::Supersede => FILE_SUPERSEDE,
+        }
+    }
+}
+
+/// The size and state of a [`KernelFile`], returned by
+/// [`KernelFile::query_information()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileInformation {
+    /// The length of the file's contents, in bytes
+    pub end_of_file: u64,
+    /// The storage allocated for the file, in bytes, which is at least
+    /// [`end_of_file`](FileInformation::end_of_file)
+    pub allocation_size: u64,
+    /// The number of hard links to the file
+    pub number_of_links: u32,
+    /// Whether the file will be deleted once every handle to it is closed
+    pub delete_pending: bool,
+}
+
+/// Kernel File.
+///
+/// A [`KernelFile`] is a kernel handle to a file, opened with
+/// [`KernelFile::open()`] at an object manager path (ex.
+/// `\SystemRoot\System32\Drivers\sample.bin` or `\??\C:\sample.log`), whose
+/// contents can be read and written at any offset. The handle is closed when
+/// the [`KernelFile`] is dropped. Every method of [`KernelFile`] must be called
+/// at `IRQL` = `PASSIVE_LEVEL`, so opening a file takes a [`PassiveLevel`]
+/// token.
+///
+/// The file is opened for synchronous I/O, and other handles to it can only be
+/// opened to read it. Paths are encoded as UTF-16 without allocating, so they
+/// are limited to 512 UTF-16 code units.
+///
+/// ```ignore
+/// let firmware = KernelFile::open(
+///     irql,
+///     r"\SystemRoot\System32\Drivers\sample.bin",
+///     FileAccess::Read,
+///     CreateDisposition::Open,
+/// )?;
+/// let length = firmware.read_at(0, &mut buffer)?;
+/// ```
+pub struct KernelFile {
+    handle: HANDLE,
+}
+
+// SAFETY: Kernel handles can be used, and closed, from any thread.
+unsafe impl Send for KernelFile {}
+
+// SAFETY: The I/O manager serializes synchronous I/O on a file object, and
+// every read and write of a `KernelFile` gives its own offset.
+unsafe impl Sync for KernelFile {}
+
+impl KernelFile {
+    /// Try to open the file at `path` with `access`, creating or truncating
+    /// it as `disposition` says
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `path` is too long, if the file does or does not exist as `disposition` requires, if `path` names a directory, or if the driver is not allowed `access` to the file. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwCreateFile Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwcreatefile#return-value)
+    pub fn open(
+        _irql: &PassiveLevel,
+        path: &str,
+        access: FileAccess,
+        disposition: CreateDisposition,
+    ) -> Result<Self, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "files must be opened");
+
+        let path = UnicodeBuffer::new(path)?;
+        let mut path = path.as_unicode_string();
+        let mut object_attributes = OBJECT_ATTRIBUTES {
+            Length: OBJECT_ATTRIBUTES_LENGTH,
+            ObjectName: &mut path,
+            Attributes: OBJ_KERNEL_HANDLE | OBJ_CASE_INSENSITIVE,
+            ..Default::default()
+        };
+        let mut io_status_block = IO_STATUS_BLOCK::default();
+        let mut handle: HANDLE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `handle`, `io_status_block` and `object_attributes`, along with the
+        // path it points to, are valid for the duration of the call. A null
+        // `AllocationSize` and a null `EaBuffer` of length 0 are allowed.
+        unsafe {
+            nt_status = ZwCreateFile(
+                &mut handle,
+                access.as_raw(),
+                &mut object_attributes,
+                &mut io_status_block,
+                core::ptr::null_mut(),
+                FILE_ATTRIBUTE_NORMAL,
+                FILE_SHARE_READ,
+                disposition.as_raw(),
+                FILE_SYNCHRONOUS_IO_NONALERT | FILE_NON_DIRECTORY_FILE,
+                core::ptr::null_mut(),
+                0,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(Self { handle })
+    }
+
+    /// Returns the raw kernel handle wrapped by this [`KernelFile`]
+    #[must_use]
+    pub const fn as_raw(&self) -> HANDLE {
+        self.handle
+    }
+
+    /// Read from the file at `offset` into `buffer`, returning how many bytes
+    /// were read
+    ///
+    /// This reads fewer bytes than `buffer` holds if the end of the file is
+    /// reached, and returns 0 if `offset` is at or past the end of the file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `offset` is too large, if the file was not opened to be read, or if the read fails. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwReadFile Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwreadfile#return-value)
+    pub fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "files must be read");
+
+        let mut byte_offset = byte_offset(offset)?;
+        // Longer buffers are read in part, like any read that reaches the end of
+        // the file
+        let length = ULONG::try_from(buffer.len()).unwrap_or(ULONG::MAX);
+        let mut io_status_block = IO_STATUS_BLOCK::default();
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelFile`, which is a valid kernel
+        // handle to a file opened for synchronous I/O until it is dropped, so the read
+        // completes before the call returns. `buffer` is valid for writes of `length`
+        // bytes, and the other arguments are valid for the duration of the call.
+        unsafe {
+            nt_status = ZwReadFile(
+                self.handle,
+                core::ptr::null_mut(),
+                None,
+                core::ptr::null_mut(),
+                &mut io_status_block,
+                buffer.as_mut_ptr().cast(),
+                length,
+                &mut byte_offset,
+                core::ptr::null_mut(),
+            );
+        }
+        if nt_status == STATUS_END_OF_FILE {
+            return Ok(0);
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // The number of bytes read is at most `length`
+        #[allow(clippy::cast_possible_truncation)]
+        let read = io_status_block.Information as usize;
+        Ok(read)
+    }
+
+    /// Write `data` to the file at `offset`, returning how many bytes were
+    /// written
+    ///
+    /// The file is extended if `data` is written past its end. All of `data` is
+    /// written unless it is longer than `ULONG::MAX` bytes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `offset` is too large, if the file was not opened to be written, or if the write fails. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwWriteFile Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwwritefile#return-value)
+    pub fn write_at(&self, offset: u64, data: &[u8]) -> Result<usize, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "files must be written");
+
+        let mut byte_offset = byte_offset(offset)?;
+        // Longer data is written in part, which the returned length reports
+        let length = ULONG::try_from(data.len()).unwrap_or(ULONG::MAX);
+        let mut io_status_block = IO_STATUS_BLOCK::default();
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelFile`, which is a valid kernel
+        // handle to a file opened for synchronous I/O until it is dropped, so the write
+        // completes before the call returns. `data` is valid for reads of `length`
+        // bytes, which the call only reads from, and the other arguments are valid for
+        // the duration of the call.
+        unsafe {
+            nt_status = ZwWriteFile(
+                self.handle,
+                core::ptr::null_mut(),
+                None,
+                core::ptr::null_mut(),
+                &mut io_status_block,
+                data.as_ptr().cast_mut().cast(),
+                length,
+                &mut byte_offset,
+                core::ptr::null_mut(),
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // The number of bytes written is at most `length`
+        #[allow(clippy::cast_possible_truncation)]
+        let written = io_status_block.Information as usize;
+        Ok(written)
+    }
+
+    /// Returns the size and state of the file
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the information could not be queried. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryInformationFile Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryinformationfile#return-value)
+    pub fn query_information(&self) -> Result<FileInformation, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "files must be queried");
+
+        let mut information = FILE_STANDARD_INFORMATION::default();
+        let mut io_status_block = IO_STATUS_BLOCK::default();
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelFile`, which is a valid kernel
+        // handle to a file until it is dropped. `information` is valid for writes of
+        // `FILE_STANDARD_INFORMATION_LENGTH` bytes, and `io_status_block` is valid for
+        // the duration of the call.
+        unsafe {
+            nt_status = ZwQueryInformationFile(
+                self.handle,
+                &mut io_status_block,
+                core::ptr::from_mut(&mut information).cast(),
+                FILE_STANDARD_INFORMATION_LENGTH,
+                FileStandardInformation,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let (end_of_file, allocation_size);
+        // SAFETY: Every bit pattern of a `LARGE_INTEGER` is a valid `QuadPart`.
+        unsafe {
+            end_of_file = information.EndOfFile.QuadPart;
+        }
+        // SAFETY: See above.
+        unsafe {
+            allocation_size = information.AllocationSize.QuadPart;
+        }
+        Ok(FileInformation {
+            end_of_file: end_of_file.unsigned_abs(),
+            allocation_size: allocation_size.unsigned_abs(),
+            number_of_links: information.NumberOfLinks,
+            delete_pending: information.DeletePending != 0,
+        })
+    }
+
+    /// Returns the length of the file's contents, in bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the length could not be queried. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryInformationFile Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryinformationfile#return-value)
+    pub fn size(&self) -> Result<u64, NTSTATUS> {
+        self.query_information()
+            .map(|information| information.end_of_file)
+    }
+}
+
+impl Drop for KernelFile {
+    fn drop(&mut self) {
+        debug_assert_irql!(== PASSIVE_LEVEL, "files must be closed");
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelFile`, which is a valid kernel
+        // handle to a file that is not used after this.
+        unsafe {
+            nt_status = ZwClose(self.handle);
+        }
+        debug_assert!(
+            nt_success(nt_status),
+            "ZwClose should succeed on a valid kernel handle"
+        );
+    }
+}
+
+/// Convert the file offset `offset` into the `LARGE_INTEGER` that `ZwReadFile`
+/// and `ZwWriteFile` take
+fn byte_offset(offset: u64) -> Result<LARGE_INTEGER, NTSTATUS> {
+    Ok(LARGE_INTEGER {
+        QuadPart: i64::try_from(offset).map_err(|_| STATUS_INVALID_PARAMETER)?,
+    })
+}
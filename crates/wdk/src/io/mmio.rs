@@ -0,0 +1,250 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    ntddk::{MmMapIoSpaceEx, MmUnmapIoSpace},
+    NTSTATUS,
+    PAGE_NOCACHE,
+    PAGE_READWRITE,
+    PHYSICAL_ADDRESS,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_PARAMETER,
+};
+
+use crate::wdf::{Resource, ResourceList};
+
+/// A range of memory-mapped device registers, mapped into system space.
+///
+/// All accesses to the registers are volatile, and are checked against the
+/// bounds of the region. The region is unmapped when the [`MmioRegion`] is
+/// dropped, so it must be dropped at `IRQL` <= `DISPATCH_LEVEL`, typically in
+/// [`PnpPowerCallbacks::release_hardware()`](crate::wdf::PnpPowerCallbacks::release_hardware).
+///
+/// ```ignore
+/// let registers = MmioRegion::map(&resources_translated, index)?;
+/// let status = registers.read32(STATUS_REGISTER_OFFSET);
+/// registers.write32(CONTROL_REGISTER_OFFSET, status | ENABLE);
+/// ```
+pub struct MmioRegion {
+    base: NonNull<u8>,
+    length: usize,
+}
+
+// SAFETY: The mapping is in system space, so it can be accessed, and unmapped,
+// from any thread.
+unsafe impl Send for MmioRegion {}
+
+// SAFETY: Registers are only ever accessed with volatile reads and writes,
+// which can be made from multiple threads at once. Serializing accesses that
+// must not be interleaved is up to the driver, as it is for the hardware.
+unsafe impl Sync for MmioRegion {}
+
+impl MmioRegion {
+    /// Try to map the memory resource at `index` in `resources_translated`,
+    /// as uncached memory
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `resources_translated` is not a translated resource list, the resource at `index` is not a memory resource, or the system fails to map it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmMapIoSpaceEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmmapiospaceex#return-value)
+    pub fn map(resources_translated: &ResourceList<'_>, index: usize) -> Result<Self, NTSTATUS> {
+        if !resources_translated.is_translated() {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let Some(Resource::Memory { base, length, .. }) = resources_translated.iter().nth(index)
+        else {
+            return Err(STATUS_INVALID_PARAMETER);
+        };
+        let length = usize::try_from(length).map_err(|_| STATUS_INVALID_PARAMETER)?;
+
+        // SAFETY: Memory resources in a translated resource list, which the framework
+        // created, describe device memory assigned to the device.
+        unsafe { Self::map_physical(base, length) }
+    }
+
+    /// Try to map `length` bytes of device memory, starting at the physical
+    /// address `base`, as uncached memory
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Safety
+    ///
+    /// The range must be device memory that the driver owns, such as a memory
+    /// resource assigned to its device, and must not be system memory that is
+    /// in use by anything else.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is 0, or the system fails to map the range. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmMapIoSpaceEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmmapiospaceex#return-value)
+    pub unsafe fn map_physical(base: u64, length: usize) -> Result<Self, NTSTATUS> {
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let physical_address = PHYSICAL_ADDRESS {
+            QuadPart: base.cast_signed(),
+        };
+
+        let mapping;
+        // SAFETY: The caller guarantees that the range is device memory that the
+        // driver owns.
+        unsafe {
+            mapping = MmMapIoSpaceEx(
+                physical_address,
+                length as SIZE_T,
+                PAGE_READWRITE | PAGE_NOCACHE,
+            );
+        }
+        let base = NonNull::new(mapping.cast()).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        Ok(Self { base, length })
+    }
+
+    /// Returns the length of the region, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the region is empty, which mapped regions never are
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns a pointer to the start of the mapped region
+    #[must_use]
+    pub const fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Read the 8-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region.
+    #[must_use]
+    pub fn read8(&self, offset: usize) -> u8 {
+        self.read(offset)
+    }
+
+    /// Read the 16-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 2 bytes.
+    #[must_use]
+    pub fn read16(&self, offset: usize) -> u16 {
+        self.read(offset)
+    }
+
+    /// Read the 32-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 4 bytes.
+    #[must_use]
+    pub fn read32(&self, offset: usize) -> u32 {
+        self.read(offset)
+    }
+
+    /// Read the 64-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 8 bytes.
+    #[must_use]
+    pub fn read64(&self, offset: usize) -> u64 {
+        self.read(offset)
+    }
+
+    /// Write `value` to the 8-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region.
+    pub fn write8(&self, offset: usize, value: u8) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 16-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 2 bytes.
+    pub fn write16(&self, offset: usize, value: u16) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 32-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 4 bytes.
+    pub fn write32(&self, offset: usize, value: u32) {
+        self.write(offset, value);
+    }
+
+    /// Write `value` to the 64-bit register at `offset` bytes into the region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned to 8 bytes.
+    pub fn write64(&self, offset: usize, value: u64) {
+        self.write(offset, value);
+    }
+
+    fn read<T>(&self, offset: usize) -> T {
+        let register = self.register::<T>(offset);
+        // SAFETY: `register` is an aligned pointer into the mapped region.
+        unsafe { register.read_volatile() }
+    }
+
+    fn write<T>(&self, offset: usize, value: T) {
+        let register = self.register::<T>(offset);
+        // SAFETY: `register` is an aligned pointer into the mapped region.
+        unsafe { register.write_volatile(value) }
+    }
+
+    /// Returns a pointer to the `T` register at `offset` bytes into the
+    /// region
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register is not entirely within the region, or `offset`
+    /// is not aligned for `T`.
+    fn register<T>(&self, offset: usize) -> *mut T {
+        assert!(
+            offset
+                .checked_add(size_of::<T>())
+                .is_some_and(|end| end <= self.length),
+            "register at offset {offset:#x} is outside of the {:#x} byte MMIO region",
+            self.length
+        );
+        assert!(
+            offset.is_multiple_of(align_of::<T>()),
+            "register at offset {offset:#x} is not aligned to {} bytes",
+            align_of::<T>()
+        );
+        // SAFETY: `offset` is within the mapped region, which was mapped whole.
+        unsafe { self.base.add(offset) }.cast::<T>().as_ptr()
+    }
+}
+
+impl Drop for MmioRegion {
+    fn drop(&mut self) {
+        // SAFETY: `base` and `length` describe the mapping made by
+        // `MmMapIoSpaceEx`, which is no longer accessed after this.
+        unsafe {
+            MmUnmapIoSpace(self.base.as_ptr().cast(), self.length as SIZE_T);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over access to device registers, over reading and
+//! writing buffers as byte streams, and over reading and writing files
+
+mod file;
+mod mmio;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod port;
+mod stream;
+
+pub use file::*;
+pub use mmio::*;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub use port::*;
+pub use stream::*;
@@ -0,0 +1,208 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::arch::asm;
+
+use wdk_sys::{NTSTATUS, STATUS_INVALID_PARAMETER};
+
+use crate::wdf::{Resource, ResourceList};
+
+/// A range of I/O ports assigned to a device.
+///
+/// All accesses to the ports are checked against the bounds of the range.
+/// I/O ports only exist on x86 and x64 processors, so [`PortRegion`] is only
+/// available on them. On other architectures, the system translates port
+/// resources into memory resources, which are accessed with an
+/// [`MmioRegion`](super::MmioRegion).
+///
+/// Like `READ_PORT_*` and `WRITE_PORT_*`, each access is a full barrier:
+/// memory accesses are not moved across it by the compiler, and `in` and `out`
+/// instructions are not reordered with them by the processor, so a DMA buffer
+/// that is filled before a port write that starts a transfer is complete when
+/// the device reads it.
+///
+/// ```ignore
+/// let ports = PortRegion::new(&resources_translated, index)?;
+/// ports.write8(INDEX_PORT_OFFSET, register);
+/// let value = ports.read8(DATA_PORT_OFFSET);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PortRegion {
+    base: u16,
+    length: u16,
+}
+
+impl PortRegion {
+    /// Try to construct a [`PortRegion`] for the port resource at `index` in
+    /// `resources_translated`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `resources_translated` is not a
+    /// translated resource list, the resource at `index` is not a port
+    /// resource, or it is not entirely within the I/O port address space. The
+    /// error variant will contain `STATUS_INVALID_PARAMETER`.
+    pub fn new(resources_translated: &ResourceList<'_>, index: usize) -> Result<Self, NTSTATUS> {
+        if !resources_translated.is_translated() {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let Some(Resource::Port { base, length, .. }) = resources_translated.iter().nth(index)
+        else {
+            return Err(STATUS_INVALID_PARAMETER);
+        };
+        let base = u16::try_from(base).map_err(|_| STATUS_INVALID_PARAMETER)?;
+        let length = u16::try_from(length).map_err(|_| STATUS_INVALID_PARAMETER)?;
+
+        // SAFETY: Port resources in a translated resource list, which the framework
+        // created, describe I/O ports assigned to the device.
+        unsafe { Self::from_raw_parts(base, length) }
+    }
+
+    /// Try to construct a [`PortRegion`] for the `length` I/O ports starting
+    /// at `base`
+    ///
+    /// # Safety
+    ///
+    /// The ports must be owned by the driver, such as a port resource assigned
+    /// to its device.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is 0, or the range is
+    /// not entirely within the I/O port address space. The error variant will
+    /// contain `STATUS_INVALID_PARAMETER`.
+    pub unsafe fn from_raw_parts(base: u16, length: u16) -> Result<Self, NTSTATUS> {
+        if length == 0 || base.checked_add(length - 1).is_none() {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        Ok(Self { base, length })
+    }
+
+    /// Returns the first port of the range
+    #[must_use]
+    pub const fn base(&self) -> u16 {
+        self.base
+    }
+
+    /// Returns the number of ports in the range
+    #[must_use]
+    pub const fn len(&self) -> u16 {
+        self.length
+    }
+
+    /// Returns whether the range is empty, which constructed ranges never are
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Read a byte from the port at `offset` into the range
+    /// (`READ_PORT_UCHAR`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the port is not within the range.
+    #[must_use]
+    pub fn read8(&self, offset: u16) -> u8 {
+        let port = self.port(offset, 1);
+        let value;
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("in al, dx", out("al") value, in("dx") port, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    /// Read a 16-bit value from the ports at `offset` into the range
+    /// (`READ_PORT_USHORT`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ports are not entirely within the range.
+    #[must_use]
+    pub fn read16(&self, offset: u16) -> u16 {
+        let port = self.port(offset, 2);
+        let value;
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("in ax, dx", out("ax") value, in("dx") port, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    /// Read a 32-bit value from the ports at `offset` into the range
+    /// (`READ_PORT_ULONG`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ports are not entirely within the range.
+    #[must_use]
+    pub fn read32(&self, offset: u16) -> u32 {
+        let port = self.port(offset, 4);
+        let value;
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("in eax, dx", out("eax") value, in("dx") port, options(nostack, preserves_flags));
+        }
+        value
+    }
+
+    /// Write a byte to the port at `offset` into the range
+    /// (`WRITE_PORT_UCHAR`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the port is not within the range.
+    pub fn write8(&self, offset: u16, value: u8) {
+        let port = self.port(offset, 1);
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("out dx, al", in("dx") port, in("al") value, options(nostack, preserves_flags));
+        }
+    }
+
+    /// Write a 16-bit value to the ports at `offset` into the range
+    /// (`WRITE_PORT_USHORT`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ports are not entirely within the range.
+    pub fn write16(&self, offset: u16, value: u16) {
+        let port = self.port(offset, 2);
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("out dx, ax", in("dx") port, in("ax") value, options(nostack, preserves_flags));
+        }
+    }
+
+    /// Write a 32-bit value to the ports at `offset` into the range
+    /// (`WRITE_PORT_ULONG`)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ports are not entirely within the range.
+    pub fn write32(&self, offset: u16, value: u32) {
+        let port = self.port(offset, 4);
+        // SAFETY: `port` is within the range of ports owned by the driver.
+        unsafe {
+            asm!("out dx, eax", in("dx") port, in("eax") value, options(nostack, preserves_flags));
+        }
+    }
+
+    /// Returns the port at `offset` into the range, checking that the `size`
+    /// ports starting at it are within the range
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ports are not entirely within the range.
+    fn port(self, offset: u16, size: u16) -> u16 {
+        assert!(
+            offset
+                .checked_add(size)
+                .is_some_and(|end| end <= self.length),
+            "port at offset {offset:#x} is outside of the {:#x} port range",
+            self.length
+        );
+        self.base + offset
+    }
+}
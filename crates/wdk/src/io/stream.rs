@@ -0,0 +1,214 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{NTSTATUS, STATUS_BUFFER_TOO_SMALL, STATUS_END_OF_FILE};
+
+/// A source of bytes, such as the input buffer of a request.
+///
+/// This mirrors `embedded_io::Read`, with `NTSTATUS` as its error type, so that
+/// parsing code written against a generic reader can run unmodified in a
+/// driver. It is implemented for byte slices, which advance past the bytes read
+/// from them, and for a [`Cursor`] over any buffer, such as a
+/// [`Memory`](crate::wdf::Memory) object.
+///
+/// ```ignore
+/// let mut input = request.input_buffer(size_of::<Header>())?;
+/// let header = Header::read_from(&mut input)?;
+/// ```
+pub trait Read {
+    /// Read some bytes into `buffer`, returning how many were read
+    ///
+    /// Returns `Ok(0)` only if `buffer` is empty, or there are no more bytes
+    /// to read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the source fails to read. The
+    /// error variant will contain a [`NTSTATUS`] of the failure.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, NTSTATUS>;
+
+    /// Read exactly enough bytes to fill `buffer`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the source fails to read, or if it
+    /// runs out of bytes before `buffer` is filled, in which case the error
+    /// variant will contain `STATUS_END_OF_FILE`. The error variant will
+    /// contain a [`NTSTATUS`] of the failure.
+    fn read_exact(&mut self, mut buffer: &mut [u8]) -> Result<(), NTSTATUS> {
+        while !buffer.is_empty() {
+            match self.read(buffer)? {
+                0 => return Err(STATUS_END_OF_FILE),
+                read => buffer = &mut buffer[read..],
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A sink for bytes, such as the output buffer of a request.
+///
+/// This mirrors `embedded_io::Write`, with `NTSTATUS` as its error type, so
+/// that serialization code written against a generic writer can run
+/// unmodified in a driver. It is implemented for mutable byte slices, which
+/// advance past the bytes written to them, and for a [`Cursor`] over any
+/// mutable buffer, such as a [`Memory`](crate::wdf::Memory) object.
+pub trait Write {
+    /// Write some bytes from `buffer`, returning how many were written
+    ///
+    /// Returns `Ok(0)` only if `buffer` is empty, or the sink is full.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sink fails to write. The error
+    /// variant will contain a [`NTSTATUS`] of the failure.
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, NTSTATUS>;
+
+    /// Make sure that all of the bytes written so far reach their destination
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sink fails to flush. The error
+    /// variant will contain a [`NTSTATUS`] of the failure.
+    fn flush(&mut self) -> Result<(), NTSTATUS> {
+        Ok(())
+    }
+
+    /// Write all of the bytes of `buffer`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the sink fails to write, or if it
+    /// is full before all of `buffer` is written, in which case the error
+    /// variant will contain `STATUS_BUFFER_TOO_SMALL`. The error variant will
+    /// contain a [`NTSTATUS`] of the failure.
+    fn write_all(&mut self, mut buffer: &[u8]) -> Result<(), NTSTATUS> {
+        while !buffer.is_empty() {
+            match self.write(buffer)? {
+                0 => return Err(STATUS_BUFFER_TOO_SMALL),
+                written => buffer = &buffer[written..],
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + ?Sized> Read for &mut R {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, NTSTATUS> {
+        (**self).read(buffer)
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), NTSTATUS> {
+        (**self).read_exact(buffer)
+    }
+}
+
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, NTSTATUS> {
+        (**self).write(buffer)
+    }
+
+    fn flush(&mut self) -> Result<(), NTSTATUS> {
+        (**self).flush()
+    }
+
+    fn write_all(&mut self, buffer: &[u8]) -> Result<(), NTSTATUS> {
+        (**self).write_all(buffer)
+    }
+}
+
+impl Read for &[u8] {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, NTSTATUS> {
+        let length = buffer.len().min(self.len());
+        let (read, rest) = self.split_at(length);
+        buffer[..length].copy_from_slice(read);
+        *self = rest;
+        Ok(length)
+    }
+}
+
+impl Write for &mut [u8] {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, NTSTATUS> {
+        let length = buffer.len().min(self.len());
+        let (written, rest) = core::mem::take(self).split_at_mut(length);
+        written.copy_from_slice(&buffer[..length]);
+        *self = rest;
+        Ok(length)
+    }
+}
+
+/// A position in a buffer, which reads and writes advance.
+///
+/// This gives buffers that cannot advance themselves, such as
+/// [`Memory`](crate::wdf::Memory) objects, implementations of [`Read`] and
+/// [`Write`].
+///
+/// ```ignore
+/// let mut writer = Cursor::new(Memory::try_new(length, PoolType::NonPaged, 0, attributes)?);
+/// response.write_to(&mut writer)?;
+/// let memory = writer.into_inner();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Cursor<T> {
+    inner: T,
+    position: usize,
+}
+
+impl<T> Cursor<T> {
+    /// Construct a [`Cursor`] at the start of `inner`
+    pub const fn new(inner: T) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Returns the offset of the cursor into the buffer, in bytes
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Move the cursor to `position` bytes into the buffer
+    ///
+    /// Reads and writes past the end of the buffer read and write no bytes.
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Returns a reference to the buffer
+    #[must_use]
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the buffer
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns the buffer, consuming the [`Cursor`]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, NTSTATUS> {
+        let mut remaining = self.inner.as_ref().get(self.position..).unwrap_or_default();
+        let read = remaining.read(buffer)?;
+        self.position += read;
+        Ok(read)
+    }
+}
+
+impl<T: AsMut<[u8]>> Write for Cursor<T> {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, NTSTATUS> {
+        let mut remaining = self
+            .inner
+            .as_mut()
+            .get_mut(self.position..)
+            .unwrap_or_default();
+        let written = remaining.write(buffer)?;
+        self.position += written;
+        Ok(written)
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! `IRQL` tokens, which track the `IRQL` that code runs at in the type system,
+//! and runtime `IRQL` assertions.
+//!
+//! Many kernel APIs must only be called at or below a certain `IRQL`, and
+//! calling them at a higher one crashes the system, often only under load.
+//! Wrappers of these APIs that take a reference to an `IRQL` token can only be
+//! called by code that has proven that it runs at or below the `IRQL` they
+//! require:
+//!
+//! - [`PassiveLevel`]: the current thread runs at `IRQL` = `PASSIVE_LEVEL`
+//! - [`ApcLevel`]: the current thread runs at `IRQL` <= `APC_LEVEL`
+//! - [`DispatchLevel`]: the current thread runs at `IRQL` <= `DISPATCH_LEVEL`
+//!
+//! Callbacks that the system runs at a known `IRQL` are passed the token for
+//! it, such as work items and system threads ([`PassiveLevel`]) and DPCs
+//! ([`DispatchLevel`]). Other code can check the current `IRQL` for a token
+//! with `current()`. A token for a lower `IRQL` dereferences to the tokens for
+//! the higher ones, so it can be used wherever they are required, but not the
+//! other way around. Calling a wrapper that requires `PASSIVE_LEVEL` from a
+//! DPC therefore fails to compile:
+//!
+//! ```ignore
+//! let dpc = Dpc::try_new(&device, move |irql| {
+//!     // error: expected `&PassiveLevel`, found `&DispatchLevel`
+//!     let state = wait_lock.acquire(irql);
+//! })?;
+//! ```
+//!
+//! Tokens cannot be sent to or shared with other threads, which may run at a
+//! different `IRQL`. A thread that raises its `IRQL` while it holds a token,
+//! such as by acquiring a [`SpinLock`](crate::wdf::SpinLock), must not use the
+//! token until it has lowered its `IRQL` again.
+//!
+//! Code that cannot be given a token can check its `IRQL` at runtime with
+//! [`current_irql()`], or assert it with [`assert_irql!`] and
+//! [`debug_assert_irql!`], which panic with the expected and actual `IRQL`, so
+//! that the resulting bug check points at the code that ran at the wrong
+//! `IRQL`, rather than at whatever later crashes with
+//! `IRQL_NOT_LESS_OR_EQUAL`. Many wrappers with `IRQL` requirements, such as
+//! those of locks, registry keys and files, assert them this way in debug
+//! builds.
+//!
+//! [`assert_irql!`]: crate::assert_irql
+//! [`debug_assert_irql!`]: crate::debug_assert_irql
+
+use core::{fmt, marker::PhantomData, ops::Deref};
+
+use wdk_sys::{ntddk::KeGetCurrentIrql, KIRQL};
+
+/// The `IRQL` that threads normally run at
+// `wdk-sys` generates the `IRQL` constants as `u32`s, but `IRQL`s are `KIRQL`s
+#[allow(clippy::cast_possible_truncation)]
+pub const PASSIVE_LEVEL: KIRQL = wdk_sys::PASSIVE_LEVEL as KIRQL;
+/// The `IRQL` that disables the delivery of APCs to the current thread
+#[allow(clippy::cast_possible_truncation)]
+pub const APC_LEVEL: KIRQL = wdk_sys::APC_LEVEL as KIRQL;
+/// The `IRQL` that DPCs run at, and that disables thread scheduling on the
+/// current processor
+#[allow(clippy::cast_possible_truncation)]
+pub const DISPATCH_LEVEL: KIRQL = wdk_sys::DISPATCH_LEVEL as KIRQL;
+
+/// Returns the `IRQL` of the current processor
+///
+/// This can be called at any `IRQL`.
+#[must_use]
+pub fn current_irql() -> KIRQL {
+    let irql;
+    // SAFETY: `KeGetCurrentIrql` can be called at any `IRQL`.
+    unsafe {
+        irql = KeGetCurrentIrql();
+    }
+    irql
+}
+
+/// Proof that the current thread runs at `IRQL` = `PASSIVE_LEVEL`.
+///
+/// A [`PassiveLevel`] dereferences to an [`ApcLevel`] and a
+/// [`DispatchLevel`], so it can be passed to wrappers that require either.
+#[derive(Debug)]
+pub struct PassiveLevel {
+    // The `IRQL` of another thread may differ
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Proof that the current thread runs at `IRQL` <= `APC_LEVEL`.
+///
+/// An [`ApcLevel`] dereferences to a [`DispatchLevel`], so it can be passed to
+/// wrappers that require either.
+#[derive(Debug)]
+pub struct ApcLevel {
+    // The `IRQL` of another thread may differ
+    _not_send: PhantomData<*mut ()>,
+}
+
+/// Proof that the current thread runs at `IRQL` <= `DISPATCH_LEVEL`.
+#[derive(Debug)]
+pub struct DispatchLevel {
+    // The `IRQL` of another thread may differ
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl PassiveLevel {
+    /// Returns a [`PassiveLevel`] token if the current thread runs at `IRQL` =
+    /// `PASSIVE_LEVEL`, or [`None`] otherwise
+    ///
+    /// This can be called at any `IRQL`.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        if current_irql() == PASSIVE_LEVEL {
+            Some(Self {
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Construct a [`PassiveLevel`] token without checking the current `IRQL`,
+    /// such as in `DriverEntry`, which the system always calls at `IRQL` =
+    /// `PASSIVE_LEVEL`
+    ///
+    /// # Safety
+    ///
+    /// The current thread must run at `IRQL` = `PASSIVE_LEVEL` whenever the
+    /// token is used.
+    #[must_use]
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl ApcLevel {
+    /// Returns an [`ApcLevel`] token if the current thread runs at `IRQL` <=
+    /// `APC_LEVEL`, or [`None`] otherwise
+    ///
+    /// This can be called at any `IRQL`.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        if current_irql() <= APC_LEVEL {
+            Some(Self {
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Construct an [`ApcLevel`] token without checking the current `IRQL`
+    ///
+    /// # Safety
+    ///
+    /// The current thread must run at `IRQL` <= `APC_LEVEL` whenever the token
+    /// is used.
+    #[must_use]
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl DispatchLevel {
+    /// Returns a [`DispatchLevel`] token if the current thread runs at `IRQL`
+    /// <= `DISPATCH_LEVEL`, or [`None`] otherwise
+    ///
+    /// This can be called at any `IRQL`.
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        if current_irql() <= DISPATCH_LEVEL {
+            Some(Self {
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Construct a [`DispatchLevel`] token without checking the current `IRQL`
+    ///
+    /// # Safety
+    ///
+    /// The current thread must run at `IRQL` <= `DISPATCH_LEVEL` whenever the
+    /// token is used.
+    #[must_use]
+    pub const unsafe fn new_unchecked() -> Self {
+        Self {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Deref for PassiveLevel {
+    type Target = ApcLevel;
+
+    fn deref(&self) -> &Self::Target {
+        // `PASSIVE_LEVEL` is below `APC_LEVEL`
+        &ApcLevel {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Deref for ApcLevel {
+    type Target = DispatchLevel;
+
+    fn deref(&self) -> &Self::Target {
+        // `APC_LEVEL` is below `DISPATCH_LEVEL`
+        &DispatchLevel {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Assert that the current `IRQL` is at most (`<=`), or exactly (`==`), the
+/// given `IRQL`, and panic otherwise
+///
+/// The `IRQL` may be one of the constants of the [`irql`](crate::irql) module,
+/// which do not need to be imported, or any other `KIRQL` expression. It may
+/// be followed by a message, with `format!` arguments, that says what requires
+/// the `IRQL`. The panic message includes the expected and actual `IRQL`s:
+///
+/// ```ignore
+/// // Panics with "expected IRQL <= DISPATCH_LEVEL, but IRQL is 15" at `HIGH_LEVEL`
+/// assert_irql!(<= DISPATCH_LEVEL);
+/// // Panics with "device 0 must be started at IRQL = PASSIVE_LEVEL, but IRQL is 2"
+/// // at `DISPATCH_LEVEL`
+/// assert_irql!(== PASSIVE_LEVEL, "device {index} must be started");
+/// ```
+#[macro_export]
+macro_rules! assert_irql {
+    (<= $level:expr $(, $($arg:tt)+)?) => {
+        $crate::assert_irql!(@check <=, "<=", $level $(, $($arg)+)?)
+    };
+    (== $level:expr $(, $($arg:tt)+)?) => {
+        $crate::assert_irql!(@check ==, "=", $level $(, $($arg)+)?)
+    };
+    (@check $op:tt, $relation:literal, $level:expr $(, $($arg:tt)+)?) => {{
+        let irql = $crate::irql::current_irql();
+        let level = {
+            #[allow(unused_imports)]
+            use $crate::irql::*;
+            $level
+        };
+        if !(irql $op level) {
+            $crate::irql::irql_assertion_failed(
+                irql,
+                ::core::concat!($relation, " ", ::core::stringify!($level)),
+                $crate::assert_irql!(@message $($($arg)+)?),
+            );
+        }
+    }};
+    (@message) => {
+        ::core::option::Option::None
+    };
+    (@message $($arg:tt)+) => {
+        ::core::option::Option::Some(::core::format_args!($($arg)+))
+    };
+}
+
+/// Assert the current `IRQL` like [`assert_irql!`](crate::assert_irql), in
+/// debug builds only
+///
+/// Release builds do not check the `IRQL`, or evaluate the given `IRQL`.
+#[macro_export]
+macro_rules! debug_assert_irql {
+    ($($arg:tt)+) => {
+        if ::core::cfg!(debug_assertions) {
+            $crate::assert_irql!($($arg)+);
+        }
+    };
+}
+
+/// Panic for an `IRQL` assertion that failed. This function is an
+/// implementation detail of [`assert_irql!`](crate::assert_irql), and should
+/// never be called directly.
+///
+/// # Panics
+///
+/// Always panics, with the `expected` `IRQL` and the actual `irql` in the
+/// message
+#[doc(hidden)]
+#[cold]
+#[track_caller]
+pub fn irql_assertion_failed(irql: KIRQL, expected: &str, message: Option<fmt::Arguments>) -> ! {
+    if let Some(message) = message {
+        panic!("{message} at IRQL {expected}, but IRQL is {irql}");
+    }
+    panic!("expected IRQL {expected}, but IRQL is {irql}")
+}
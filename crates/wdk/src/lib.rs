@@ -12,6 +12,25 @@
 #[cfg(feature = "alloc")]
 pub use print::_print;
 pub use wdk_sys::{NT_SUCCESS as nt_success, PAGED_CODE as paged_code};
+pub mod bug_check;
+pub mod collections;
+pub mod debug_print;
+pub mod error;
+pub mod guid;
+pub mod io;
+pub mod irql;
+mod lock_order;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod memory;
+mod pool;
+pub mod registry;
+pub mod status;
+pub mod string;
+pub mod sync;
+pub mod thread;
+pub mod time;
+pub mod timer;
 pub mod wdf;
 
 /// Trigger a breakpoint in debugger via architecture-specific inline assembly.
@@ -0,0 +1,257 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Crate-internal lock ordering checks, enabled by the `lock-order-checks`
+//! feature.
+//!
+//! When enabled, the lock types in this crate ([`SpinLock`], [`WaitLock`] and
+//! [`Resource`]) record which locks each thread holds, and every time a lock
+//! is acquired while others are held, the order in which they were acquired.
+//! If a thread waits on a lock while holding another lock that some thread
+//! previously acquired *after* it, the two locks have been acquired in
+//! inconsistent orders, which can deadlock. The inversion is reported via
+//! `DbgPrint`, and then [`dbg_break()`](crate::dbg_break) breaks into the
+//! kernel debugger (or bugchecks if none is attached).
+//!
+//! Locks are identified by their address, and tracking is best-effort: the
+//! number of threads that can hold tracked locks at once, the number of locks
+//! tracked per thread, and the number of recorded orderings are all bounded,
+//! and acquisitions beyond those bounds are not checked. Attempts to acquire a
+//! lock without waiting cannot deadlock, so they are tracked but not checked.
+//!
+//! When the feature is disabled, all of these functions are empty.
+//!
+//! [`SpinLock`]: crate::wdf::SpinLock
+//! [`WaitLock`]: crate::wdf::WaitLock
+//! [`Resource`]: crate::sync::Resource
+
+use core::ffi::c_void;
+
+#[cfg(feature = "lock-order-checks")]
+mod checks {
+    use core::{
+        ffi::c_void,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use wdk_sys::ntddk::{DbgPrint, PsGetCurrentThreadId};
+
+    const MAX_THREADS: usize = 64;
+    const MAX_HELD_LOCKS: usize = 16;
+    const MAX_ORDERINGS: usize = 512;
+
+    /// The locks held by a single thread. Only the thread whose id is stored
+    /// in `thread` accesses `held` and `depth`.
+    struct ThreadLocks {
+        thread: AtomicUsize,
+        depth: AtomicUsize,
+        held: [AtomicUsize; MAX_HELD_LOCKS],
+    }
+
+    impl ThreadLocks {
+        const fn new() -> Self {
+            Self {
+                thread: AtomicUsize::new(0),
+                depth: AtomicUsize::new(0),
+                held: [const { AtomicUsize::new(0) }; MAX_HELD_LOCKS],
+            }
+        }
+    }
+
+    /// An observed acquisition of the lock `after` while `before` was held
+    struct LockOrdering {
+        before: AtomicUsize,
+        after: AtomicUsize,
+    }
+
+    impl LockOrdering {
+        const fn new() -> Self {
+            Self {
+                before: AtomicUsize::new(0),
+                after: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    static THREADS: [ThreadLocks; MAX_THREADS] = [const { ThreadLocks::new() }; MAX_THREADS];
+    static ORDERINGS: [LockOrdering; MAX_ORDERINGS] =
+        [const { LockOrdering::new() }; MAX_ORDERINGS];
+
+    fn current_thread() -> usize {
+        let thread_id;
+        // SAFETY: `PsGetCurrentThreadId` can be called at any `IRQL`.
+        unsafe {
+            thread_id = PsGetCurrentThreadId();
+        }
+        thread_id as usize
+    }
+
+    /// Find the tracking slot of the current thread, optionally claiming a
+    /// free one if it has none
+    fn thread_locks(claim: bool) -> Option<&'static ThreadLocks> {
+        let thread = current_thread();
+        if let Some(slot) = THREADS
+            .iter()
+            .find(|slot| slot.thread.load(Ordering::Relaxed) == thread)
+        {
+            return Some(slot);
+        }
+        if !claim {
+            return None;
+        }
+        THREADS.iter().find(|slot| {
+            slot.thread
+                .compare_exchange(0, thread, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        })
+    }
+
+    fn held_locks(thread_locks: &ThreadLocks) -> impl Iterator<Item = usize> + '_ {
+        let depth = thread_locks.depth.load(Ordering::Relaxed);
+        thread_locks.held[..depth.min(MAX_HELD_LOCKS)]
+            .iter()
+            .map(|lock| lock.load(Ordering::Relaxed))
+    }
+
+    fn find_ordering(before: usize, after: usize) -> bool {
+        ORDERINGS.iter().any(|ordering| {
+            ordering.before.load(Ordering::Acquire) == before
+                && ordering.after.load(Ordering::Acquire) == after
+        })
+    }
+
+    fn record_ordering(before: usize, after: usize) {
+        if find_ordering(before, after) {
+            return;
+        }
+        if let Some(ordering) = ORDERINGS.iter().find(|ordering| {
+            ordering
+                .before
+                .compare_exchange(0, usize::MAX, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        }) {
+            ordering.after.store(after, Ordering::Release);
+            ordering.before.store(before, Ordering::Release);
+        }
+    }
+
+    fn report_inversion(held: usize, acquiring: usize) {
+        // SAFETY: The format string is null-terminated, and its specifiers match the
+        // types of the arguments.
+        unsafe {
+            DbgPrint(
+                c"wdk: lock order inversion: acquiring lock %p while holding lock %p, which \
+                  was previously acquired while holding lock %p\n"
+                    .as_ptr(),
+                acquiring as *mut c_void,
+                held as *mut c_void,
+                acquiring as *mut c_void,
+            );
+        }
+        crate::dbg_break();
+    }
+
+    pub fn check_acquire(lock: *mut c_void) {
+        let lock = lock as usize;
+        let Some(thread_locks) = thread_locks(false) else {
+            // The current thread holds no tracked locks
+            return;
+        };
+        for held in held_locks(thread_locks) {
+            if held != lock && find_ordering(lock, held) {
+                report_inversion(held, lock);
+            }
+            record_ordering(held, lock);
+        }
+    }
+
+    pub fn acquired(lock: *mut c_void) {
+        let Some(thread_locks) = thread_locks(true) else {
+            return;
+        };
+        let depth = thread_locks.depth.load(Ordering::Relaxed);
+        if depth < MAX_HELD_LOCKS {
+            thread_locks.held[depth].store(lock as usize, Ordering::Relaxed);
+        }
+        // Increment past the bound, so that releases stay balanced
+        thread_locks.depth.store(depth + 1, Ordering::Relaxed);
+    }
+
+    pub fn released(lock: *mut c_void) {
+        let lock = lock as usize;
+        let Some(thread_locks) = thread_locks(false) else {
+            return;
+        };
+        let depth = thread_locks.depth.load(Ordering::Relaxed);
+        let tracked = depth.min(MAX_HELD_LOCKS);
+
+        // Locks are usually released in the reverse order they were acquired, but
+        // guards can be dropped in any order
+        if let Some(index) = (0..tracked)
+            .rev()
+            .find(|&index| thread_locks.held[index].load(Ordering::Relaxed) == lock)
+        {
+            for index in index..tracked - 1 {
+                let next = thread_locks.held[index + 1].load(Ordering::Relaxed);
+                thread_locks.held[index].store(next, Ordering::Relaxed);
+            }
+        }
+
+        let depth = depth.saturating_sub(1);
+        thread_locks.depth.store(depth, Ordering::Relaxed);
+        if depth == 0 {
+            thread_locks.thread.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn forget(lock: *mut c_void) {
+        let lock = lock as usize;
+        for ordering in &ORDERINGS {
+            if ordering.before.load(Ordering::Acquire) == lock
+                || ordering.after.load(Ordering::Acquire) == lock
+            {
+                ordering.before.store(0, Ordering::Release);
+            }
+        }
+    }
+}
+
+/// Check that waiting to acquire `lock` is consistent with the order in which
+/// the locks held by the current thread were previously acquired. This is
+/// called before waiting to acquire a lock, and must be followed by a call to
+/// [`acquired`] once it has been acquired.
+#[inline]
+pub fn check_acquire(lock: *mut c_void) {
+    #[cfg(feature = "lock-order-checks")]
+    checks::check_acquire(lock);
+    #[cfg(not(feature = "lock-order-checks"))]
+    let _ = lock;
+}
+
+/// Record that the current thread has acquired `lock`
+#[inline]
+pub fn acquired(lock: *mut c_void) {
+    #[cfg(feature = "lock-order-checks")]
+    checks::acquired(lock);
+    #[cfg(not(feature = "lock-order-checks"))]
+    let _ = lock;
+}
+
+/// Record that the current thread has released `lock`
+#[inline]
+pub fn released(lock: *mut c_void) {
+    #[cfg(feature = "lock-order-checks")]
+    checks::released(lock);
+    #[cfg(not(feature = "lock-order-checks"))]
+    let _ = lock;
+}
+
+/// Discard every recorded ordering involving `lock`, since its address may be
+/// reused by another lock after it is destroyed
+#[inline]
+pub fn forget(lock: *mut c_void) {
+    #[cfg(feature = "lock-order-checks")]
+    checks::forget(lock);
+    #[cfg(not(feature = "lock-order-checks"))]
+    let _ = lock;
+}
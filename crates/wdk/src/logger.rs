@@ -0,0 +1,151 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! A [`log`] backend for drivers, enabled by the `log` feature.
+//!
+//! Once [`init()`] has registered [`DbgPrintLogger`], the `log` macros
+//! (`error!`, `warn!`, `info!`, `debug!` and `trace!`) print their records to
+//! the kernel debugger with `DbgPrintEx`, so that `no_std` libraries which
+//! already log through the `log` crate work unchanged inside a driver. Records
+//! are formatted on the stack, like those of [`kprintln!`](crate::kprintln),
+//! and printed with the component ID set by
+//! [`debug_print::set_component_id()`](crate::debug_print::set_component_id),
+//! at the [`debug_print::Level`] of the same name:
+//!
+//! | `log` level | [`debug_print::Level`] |
+//! |-------------|------------------------|
+//! | `Error`     | `Error`                |
+//! | `Warn`      | `Warning`              |
+//! | `Info`      | `Info`                 |
+//! | `Debug`     | `Trace`                |
+//! | `Trace`     | `Trace`                |
+//!
+//! Records above the maximum level of the `log` crate are discarded before
+//! they are formatted. The maximum level can be changed at any time with
+//! [`log::set_max_level()`], or read from the `LogLevel` `REG_DWORD` value of a
+//! registry key with [`set_max_level_from_registry()`], so that the verbosity
+//! of an installed driver can be raised without rebuilding it:
+//!
+//! ```ignore
+//! wdk::logger::init(log::LevelFilter::Warn)?;
+//! // `registry_path` is the service key passed to `DriverEntry`
+//! let _ = wdk::logger::set_max_level_from_registry(irql, registry_path);
+//! log::info!("driver loaded");
+//! ```
+//!
+//! The `LogLevel` value is 0 (`Off`), 1 (`Error`), 2 (`Warn`), 3 (`Info`), 4
+//! (`Debug`) or 5 (`Trace`), and larger values are treated as `Trace`. Records
+//! that pass the maximum level are still subject to the debug print filter of
+//! the component, as described in [`debug_print`](crate::debug_print).
+//!
+//! Records can also be added to crash dumps, by registering a
+//! [`CrashLog`](crate::bug_check::CrashLog) with
+//! [`debug_print::set_crash_log()`](crate::debug_print::set_crash_log), which
+//! receives every record that passes the maximum level, whether or not the
+//! debug print filter lets it through:
+//!
+//! ```ignore
+//! static CRASH_LOG: CrashLog<8192> = CrashLog::new();
+//!
+//! wdk::debug_print::set_crash_log(Some(|bytes| CRASH_LOG.write(bytes)));
+//! let dump_callback = CRASH_LOG.register_dump(c"SampleDriver", CRASH_LOG_GUID)?;
+//! ```
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use wdk_sys::NTSTATUS;
+
+use crate::{
+    debug_print::{self, Level},
+    irql::PassiveLevel,
+    registry::KernelRegistryKey,
+    wdf::KeyAccess,
+};
+
+/// The name of the `REG_DWORD` value that
+/// [`set_max_level_from_registry()`] reads the maximum level from
+pub const LOG_LEVEL_VALUE_NAME: &str = "LogLevel";
+
+/// The [`DbgPrintLogger`] that [`init()`] registers
+static LOGGER: DbgPrintLogger = DbgPrintLogger;
+
+/// A [`Log`] implementation that prints records to the kernel debugger with
+/// `DbgPrintEx`
+///
+/// Records can be logged at any `IRQL` <= `DIRQL`, as long as formatting their
+/// arguments can.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DbgPrintLogger;
+
+impl Log for DbgPrintLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = match record.level() {
+            log::Level::Error => Level::Error,
+            log::Level::Warn => Level::Warning,
+            log::Level::Info => Level::Info,
+            log::Level::Debug | log::Level::Trace => Level::Trace,
+        };
+        debug_print::print(
+            level,
+            format_args!(
+                "[{}] {}: {}\n",
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Register [`DbgPrintLogger`] as the logger of the `log` crate, and set its
+/// maximum level to `max_level`
+///
+/// This can be called at any `IRQL`.
+///
+/// # Errors
+///
+/// This function will return an error if a logger has already been registered.
+pub fn init(max_level: LevelFilter) -> Result<(), SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Set the maximum level of the `log` crate from the `LogLevel` `REG_DWORD`
+/// value of the registry key at the absolute registry `path`, such as the
+/// service key of the driver, and return it
+///
+/// The maximum level is left unchanged if the value cannot be read. Calling
+/// this again, such as when the driver is asked to reload its configuration,
+/// picks up changes to the value.
+///
+/// # Errors
+///
+/// This function will return an error if the key cannot be opened for reading,
+/// if it has no `LogLevel` value, or if the value is not a `REG_DWORD`. The
+/// error variant will contain a [`NTSTATUS`] of the failure.
+pub fn set_max_level_from_registry(
+    irql: &PassiveLevel,
+    path: &str,
+) -> Result<LevelFilter, NTSTATUS> {
+    let key = KernelRegistryKey::open(irql, path, KeyAccess::Read)?;
+    let max_level = match key.query_u32(irql, LOG_LEVEL_VALUE_NAME)? {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_max_level(max_level);
+    Ok(max_level)
+}
@@ -0,0 +1,254 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    ntddk::{
+        MmAllocateContiguousMemorySpecifyCacheNode,
+        MmFreeContiguousMemorySpecifyCache,
+        MmGetPhysicalAddress,
+    },
+    MM_ANY_NODE_OK,
+    NODE_REQUIREMENT,
+    NTSTATUS,
+    PHYSICAL_ADDRESS,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_PARAMETER,
+};
+
+use super::CacheType;
+
+/// The NUMA node that a [`ContiguousBuffer`] is allocated from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumaNode {
+    /// Any node, preferring the node of the processor the allocation is made
+    /// on
+    #[default]
+    Any,
+    /// The node with this number, or any other node if it has no free memory
+    Preferred(u32),
+}
+
+impl NumaNode {
+    const fn as_raw(self) -> NODE_REQUIREMENT {
+        match self {
+            Self::Any => MM_ANY_NODE_OK,
+            Self::Preferred(node) => node,
+        }
+    }
+}
+
+/// The physical addresses that a [`ContiguousBuffer`] may be allocated at
+///
+/// The default places no restrictions on the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysicalAddressLimits {
+    /// The lowest physical address that the buffer may start at
+    pub lowest: u64,
+    /// The highest physical address that the buffer may end at, such as
+    /// `0xFFFF_FFFF` for a device that can only address the first 4 GB of
+    /// memory
+    pub highest: u64,
+    /// A power of two that the buffer must not cross a multiple of, or 0 if
+    /// the buffer may cross any address
+    pub boundary: u64,
+}
+
+impl PhysicalAddressLimits {
+    /// Limit the buffer to end at or below the physical address `highest`
+    #[must_use]
+    pub const fn below(highest: u64) -> Self {
+        Self {
+            lowest: 0,
+            highest,
+            boundary: 0,
+        }
+    }
+}
+
+impl Default for PhysicalAddressLimits {
+    fn default() -> Self {
+        Self::below(u64::MAX)
+    }
+}
+
+/// A buffer of physically contiguous, nonpaged memory.
+///
+/// Devices that cannot perform scatter/gather DMA need buffers that are
+/// contiguous in physical memory, which the device is programmed with the
+/// physical address of. The buffer is allocated when the [`ContiguousBuffer`]
+/// is created, and is freed when it is dropped, so it must be dropped at
+/// `IRQL` <= `DISPATCH_LEVEL`.
+///
+/// Drivers that use a DMA enabler should allocate a
+/// [`CommonBuffer`](crate::wdf::CommonBuffer) instead, whose logical address
+/// accounts for the DMA remapping of the system.
+///
+/// ```ignore
+/// let buffer = ContiguousBuffer::try_new(
+///     FRAME_SIZE,
+///     PhysicalAddressLimits::below(0xFFFF_FFFF),
+///     CacheType::Uncached,
+///     NumaNode::Any,
+/// )?;
+/// registers.write32(FRAME_BASE_REGISTER_OFFSET, buffer.physical_address() as u32);
+/// ```
+pub struct ContiguousBuffer {
+    base: NonNull<u8>,
+    length: usize,
+    physical_address: u64,
+    cache_type: CacheType,
+}
+
+// SAFETY: The buffer is nonpaged system memory, which can be accessed, and
+// freed, from any thread.
+unsafe impl Send for ContiguousBuffer {}
+
+// SAFETY: `ContiguousBuffer` only allows its buffer to be accessed through
+// `&self` by the unsafe `ContiguousBuffer::as_slice()`, whose callers guarantee
+// that it is not mutated.
+unsafe impl Sync for ContiguousBuffer {}
+
+impl ContiguousBuffer {
+    /// Try to allocate a buffer of `length` bytes of physically contiguous
+    /// memory within `limits`, mapped with `cache_type`, preferably from
+    /// `node`
+    ///
+    /// The contents of the buffer are not initialized. This must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is 0, if `limits.boundary` is not 0 or a power of two that is at least `length`, or if the system could not find enough contiguous memory within `limits`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmAllocateContiguousMemorySpecifyCacheNode Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmallocatecontiguousmemoryspecifycachenode#return-value)
+    pub fn try_new(
+        length: usize,
+        limits: PhysicalAddressLimits,
+        cache_type: CacheType,
+        node: NumaNode,
+    ) -> Result<Self, NTSTATUS> {
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        if limits.boundary != 0
+            && (!limits.boundary.is_power_of_two() || limits.boundary < length as u64)
+        {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let base;
+        // SAFETY: `MmAllocateContiguousMemorySpecifyCacheNode` has no preconditions
+        // other than its `IRQL`, and the memory it returns is only freed when `self`
+        // is dropped.
+        unsafe {
+            base = MmAllocateContiguousMemorySpecifyCacheNode(
+                length as SIZE_T,
+                PHYSICAL_ADDRESS {
+                    QuadPart: limits.lowest.cast_signed(),
+                },
+                PHYSICAL_ADDRESS {
+                    QuadPart: limits.highest.cast_signed(),
+                },
+                PHYSICAL_ADDRESS {
+                    QuadPart: limits.boundary.cast_signed(),
+                },
+                cache_type.as_raw(),
+                node.as_raw(),
+            );
+        }
+        let base = NonNull::new(base.cast::<u8>()).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+
+        let physical_address;
+        // SAFETY: `base` is the start of a nonpaged allocation, so it is always
+        // mapped to physical memory.
+        unsafe {
+            physical_address = MmGetPhysicalAddress(base.as_ptr().cast());
+        }
+
+        Ok(Self {
+            base,
+            length,
+            // SAFETY: Every bit pattern of a `PHYSICAL_ADDRESS` is a valid `QuadPart`.
+            physical_address: unsafe { physical_address.QuadPart }.cast_unsigned(),
+            cache_type,
+        })
+    }
+
+    /// Returns the physical address of the start of the buffer, to program
+    /// the device with
+    #[must_use]
+    pub const fn physical_address(&self) -> u64 {
+        self.physical_address
+    }
+
+    /// Returns the cache type that the buffer is mapped with
+    #[must_use]
+    pub const fn cache_type(&self) -> CacheType {
+        self.cache_type
+    }
+
+    /// Returns a pointer to the start of the buffer, in system space
+    #[must_use]
+    pub const fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Returns the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the buffer is empty, which buffers never are
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the contents of the buffer
+    ///
+    /// # Safety
+    ///
+    /// The device must not write to the buffer while the returned slice is
+    /// borrowed. Memory that the device writes to concurrently must instead be
+    /// accessed with volatile reads through [`ContiguousBuffer::as_ptr()`].
+    #[must_use]
+    pub const unsafe fn as_slice(&self) -> &[u8] {
+        // SAFETY: The buffer is `length` bytes of nonpaged memory, valid until it is
+        // freed when `self` is dropped, and the caller guarantees that the device does
+        // not mutate it while the slice is borrowed.
+        unsafe { core::slice::from_raw_parts(self.base.as_ptr(), self.length) }
+    }
+
+    /// Returns the contents of the buffer, mutably
+    ///
+    /// # Safety
+    ///
+    /// The device must not access the buffer while the returned slice is
+    /// borrowed. Memory that the device accesses concurrently must instead be
+    /// accessed with volatile reads and writes through
+    /// [`ContiguousBuffer::as_ptr()`].
+    #[must_use]
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: The buffer is `length` bytes of nonpaged memory, valid until it is
+        // freed when `self` is dropped, `self` is borrowed mutably, and the caller
+        // guarantees that the device does not access it while the slice is borrowed.
+        unsafe { core::slice::from_raw_parts_mut(self.base.as_ptr(), self.length) }
+    }
+}
+
+impl Drop for ContiguousBuffer {
+    fn drop(&mut self) {
+        // SAFETY: `base`, `length` and `cache_type` describe the allocation made by
+        // `MmAllocateContiguousMemorySpecifyCacheNode`, which is no longer accessed
+        // after this.
+        unsafe {
+            MmFreeContiguousMemorySpecifyCache(
+                self.base.as_ptr().cast(),
+                self.length as SIZE_T,
+                self.cache_type.as_raw(),
+            );
+        }
+    }
+}
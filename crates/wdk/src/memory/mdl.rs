@@ -0,0 +1,310 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, ptr::NonNull};
+
+use wdk_sys::{
+    ntddk::{
+        IoAllocateMdl,
+        IoFreeMdl,
+        MmMapLockedPagesSpecifyCache,
+        MmProbeAndLockPages,
+        MmUnlockPages,
+    },
+    MdlMappingNoExecute,
+    _LOCK_OPERATION,
+    _MEMORY_CACHING_TYPE,
+    _MM_PAGE_PRIORITY,
+    _MODE,
+    KPROCESSOR_MODE,
+    LOCK_OPERATION,
+    MDL,
+    MDL_MAPPED_TO_SYSTEM_VA,
+    MDL_SOURCE_IS_NONPAGED_POOL,
+    NTSTATUS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+};
+
+/// The processor mode that a buffer is accessed from, which determines
+/// whether [`Mdl::probe_and_lock()`] checks that it is in user space
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessMode {
+    /// The buffer is accessed on behalf of kernel-mode code, and is not
+    /// checked
+    Kernel,
+    /// The buffer is accessed on behalf of a user-mode caller, and must be in
+    /// user space
+    User,
+}
+
+impl AccessMode {
+    // `KPROCESSOR_MODE` is a `CCHAR`, while the `MODE` enumeration it holds a value
+    // of is an `int`
+    #[allow(clippy::cast_possible_truncation)]
+    const fn as_raw(self) -> KPROCESSOR_MODE {
+        match self {
+            Self::Kernel => _MODE::KernelMode as KPROCESSOR_MODE,
+            Self::User => _MODE::UserMode as KPROCESSOR_MODE,
+        }
+    }
+}
+
+/// The kind of access that the pages described by an [`Mdl`] are locked for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockOperation {
+    /// The pages are only read, such as when the device reads them for a
+    /// write request
+    Read,
+    /// The pages are only written, such as when the device writes to them for
+    /// a read request
+    Write,
+    /// The pages are both read and written
+    Modify,
+}
+
+impl LockOperation {
+    const fn as_raw(self) -> LOCK_OPERATION {
+        match self {
+            Self::Read => _LOCK_OPERATION::IoReadAccess,
+            Self::Write => _LOCK_OPERATION::IoWriteAccess,
+            Self::Modify => _LOCK_OPERATION::IoModifyAccess,
+        }
+    }
+}
+
+/// How important it is that mapping the pages described by an [`Mdl`] into
+/// system space succeeds when system page table entries are scarce
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PagePriority {
+    /// The mapping may fail when system resources are low, for mappings that
+    /// the driver can recover from failing to get
+    Low,
+    /// The mapping may fail when system resources are very low
+    #[default]
+    Normal,
+    /// The mapping only fails when system resources are exhausted, for
+    /// mappings that must succeed for the system to make progress, such as
+    /// paging I/O
+    High,
+}
+
+impl PagePriority {
+    const fn as_raw(self) -> ULONG {
+        match self {
+            Self::Low => _MM_PAGE_PRIORITY::LowPagePriority,
+            Self::Normal => _MM_PAGE_PRIORITY::NormalPagePriority,
+            Self::High => _MM_PAGE_PRIORITY::HighPagePriority,
+        }
+        .cast_unsigned()
+    }
+}
+
+/// Memory Descriptor List.
+///
+/// An MDL describes the physical pages behind a virtually contiguous buffer,
+/// so that the buffer can be accessed from any thread and at raised `IRQL`
+/// once its pages are locked, by mapping them into system space. The MDL is
+/// allocated when the [`Mdl`] is created, and its pages are unlocked, and it is
+/// freed, when it is dropped.
+///
+/// Buffers of requests from user mode are more easily, and safely, locked
+/// with
+/// [`Request::lock_user_input_buffer()`](crate::wdf::Request::lock_user_input_buffer)
+/// and
+/// [`Request::lock_user_output_buffer()`](crate::wdf::Request::lock_user_output_buffer).
+///
+/// ```ignore
+/// let mut mdl = Mdl::try_new(user_buffer, length)?;
+/// // SAFETY: `user_buffer` was checked to be a writable user-space buffer with
+/// // `probe_write` in the context of the calling process.
+/// unsafe { mdl.probe_and_lock(AccessMode::User, LockOperation::Write)? };
+/// let system_buffer = mdl.system_address(PagePriority::Normal)?;
+/// ```
+pub struct Mdl {
+    mdl: NonNull<MDL>,
+    locked: bool,
+}
+
+// SAFETY: An MDL can be locked, mapped, unlocked and freed from any thread, and
+// the pages it describes are only accessed through the raw pointers it returns.
+unsafe impl Send for Mdl {}
+
+// SAFETY: The only methods of `Mdl` that take `&self` read the length of the
+// MDL, which never changes. Locking and mapping its pages, which writes to the
+// MDL, requires `&mut self`.
+unsafe impl Sync for Mdl {}
+
+impl Mdl {
+    /// Try to allocate an MDL describing the `length` bytes at
+    /// `virtual_address`
+    ///
+    /// The buffer is not accessed until the MDL is locked with
+    /// [`Mdl::probe_and_lock()`]. This must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is zero or does not fit in a `ULONG`, or if the MDL could not be allocated. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [IoAllocateMdl Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-ioallocatemdl#return-value)
+    // `virtual_address` is never dereferenced, since `IoAllocateMdl` only records
+    // it in the MDL
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn try_new(virtual_address: *mut c_void, length: usize) -> Result<Self, NTSTATUS> {
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_PARAMETER)?;
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let mdl;
+        // SAFETY: `IoAllocateMdl` only records `virtual_address`, without accessing
+        // it, and the MDL is not associated with an IRP.
+        unsafe {
+            mdl = IoAllocateMdl(
+                virtual_address,
+                length,
+                false.into(),
+                false.into(),
+                core::ptr::null_mut(),
+            );
+        }
+        let mdl = NonNull::new(mdl).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        Ok(Self { mdl, locked: false })
+    }
+
+    /// Returns the raw `PMDL` wrapped by this [`Mdl`]
+    ///
+    /// The pages the MDL describes can be passed to the device with
+    /// `WdfDmaTransactionInitialize` once they are locked.
+    #[must_use]
+    pub const fn as_raw(&self) -> *mut MDL {
+        self.mdl.as_ptr()
+    }
+
+    /// Returns the length of the buffer described by the MDL, in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // SAFETY: `self.mdl` points to an MDL allocated by `IoAllocateMdl`, which is
+        // valid until `self` is dropped.
+        unsafe { self.mdl.as_ref() }.ByteCount as usize
+    }
+
+    /// Returns whether the buffer described by the MDL is empty, which it
+    /// never is
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the pages described by the MDL are locked
+    #[must_use]
+    pub const fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Probe the buffer described by the MDL for `operation` from
+    /// `access_mode`, and lock its pages in memory
+    ///
+    /// The pages stay locked until the [`Mdl`] is dropped. This must be called
+    /// at `IRQL` <= `APC_LEVEL` for pageable buffers, or `IRQL` <=
+    /// `DISPATCH_LEVEL` for nonpaged buffers.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the pages are already locked. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmProbeAndLockPages Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmprobeandlockpages)
+    ///
+    /// # Safety
+    ///
+    /// `MmProbeAndLockPages` raises an exception, rather than returning an
+    /// error, if the buffer cannot be accessed for `operation` from
+    /// `access_mode`, and Rust cannot handle the exception. The caller must
+    /// guarantee that the buffer is valid for `operation`, in the address space
+    /// of the current process for buffers in user space, and, for buffers in
+    /// system space, that it stays allocated until the [`Mdl`] is dropped.
+    pub unsafe fn probe_and_lock(
+        &mut self,
+        access_mode: AccessMode,
+        operation: LockOperation,
+    ) -> Result<(), NTSTATUS> {
+        if self.locked {
+            return Err(STATUS_INVALID_DEVICE_STATE);
+        }
+
+        // SAFETY: `self.mdl` is a valid, unlocked MDL, and the caller guarantees that
+        // the buffer it describes can be probed without raising an exception.
+        unsafe {
+            MmProbeAndLockPages(self.mdl.as_ptr(), access_mode.as_raw(), operation.as_raw());
+        }
+        self.locked = true;
+        Ok(())
+    }
+
+    /// Returns the system-space address of the buffer described by the MDL,
+    /// mapping its pages into system space if they are not already
+    ///
+    /// The mapping is not executable, and is unmapped when the [`Mdl`] is
+    /// dropped. Mapping the pages writes to the MDL, so this requires
+    /// exclusive access to it. This must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the pages are not locked, or if system page table entries for the mapping could not be allocated at `priority`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmGetSystemAddressForMdlSafe Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmgetsystemaddressformdlsafe#return-value)
+    pub fn system_address(&mut self, priority: PagePriority) -> Result<NonNull<u8>, NTSTATUS> {
+        if !self.locked {
+            return Err(STATUS_INVALID_DEVICE_STATE);
+        }
+
+        // `MmGetSystemAddressForMdlSafe` is a macro, so it is reimplemented here
+        let mdl = self.mdl.as_ptr();
+        let flags;
+        // SAFETY: `self.mdl` points to an MDL allocated by `IoAllocateMdl`, which is
+        // valid until `self` is dropped, and is not written to concurrently, since
+        // `self` is mutably borrowed.
+        unsafe {
+            flags = u32::from((*mdl).MdlFlags.cast_unsigned());
+        }
+        let address = if flags & (MDL_MAPPED_TO_SYSTEM_VA | MDL_SOURCE_IS_NONPAGED_POOL) != 0 {
+            let address;
+            // SAFETY: `self.mdl` points to an MDL allocated by `IoAllocateMdl`, which is
+            // valid until `self` is dropped, and is not written to concurrently, since
+            // `self` is mutably borrowed.
+            unsafe {
+                address = (*mdl).MappedSystemVa;
+            }
+            address
+        } else {
+            // SAFETY: The pages described by `self.mdl` are locked, and the mapping is
+            // unmapped by `MmUnlockPages` when `self` is dropped.
+            unsafe {
+                MmMapLockedPagesSpecifyCache(
+                    mdl,
+                    AccessMode::Kernel.as_raw(),
+                    _MEMORY_CACHING_TYPE::MmCached,
+                    core::ptr::null_mut(),
+                    0,
+                    priority.as_raw() | MdlMappingNoExecute,
+                )
+            }
+        };
+        NonNull::new(address.cast()).ok_or(STATUS_INSUFFICIENT_RESOURCES)
+    }
+}
+
+impl Drop for Mdl {
+    fn drop(&mut self) {
+        if self.locked {
+            // SAFETY: `self.mdl` was locked by `MmProbeAndLockPages`, and has not been
+            // unlocked since.
+            unsafe {
+                MmUnlockPages(self.mdl.as_ptr());
+            }
+        }
+        // SAFETY: `self.mdl` was allocated by `IoAllocateMdl`, and is not used after
+        // it is freed.
+        unsafe {
+            IoFreeMdl(self.mdl.as_ptr());
+        }
+    }
+}
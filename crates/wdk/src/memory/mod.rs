@@ -0,0 +1,14 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over kernel memory management
+
+mod contiguous;
+mod mdl;
+mod physical;
+mod user;
+
+pub use contiguous::*;
+pub use mdl::*;
+pub use physical::*;
+pub use user::*;
@@ -0,0 +1,274 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    ntddk::{MmMapIoSpaceEx, MmUnmapIoSpace},
+    _MEMORY_CACHING_TYPE,
+    MEMORY_CACHING_TYPE,
+    NTSTATUS,
+    PAGE_NOCACHE,
+    PAGE_READWRITE,
+    PAGE_WRITECOMBINE,
+    PHYSICAL_ADDRESS,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+};
+
+use crate::wdf::{Resource, ResourceList};
+
+/// How the processor caches accesses to memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheType {
+    /// Accesses are not cached, and reach the memory in program order, as
+    /// device registers such as doorbells require
+    Uncached,
+    /// Writes are buffered and combined into larger writes, which may reach
+    /// the memory out of order, as suits framebuffers
+    WriteCombined,
+    /// Accesses are cached, as suits memory that the device accesses
+    /// coherently with the processor caches
+    Cached,
+}
+
+impl CacheType {
+    /// Returns the `MEMORY_CACHING_TYPE` of this cache type
+    #[must_use]
+    pub const fn as_raw(self) -> MEMORY_CACHING_TYPE {
+        match self {
+            Self::Uncached => _MEMORY_CACHING_TYPE::MmNonCached,
+            Self::WriteCombined => _MEMORY_CACHING_TYPE::MmWriteCombined,
+            Self::Cached => _MEMORY_CACHING_TYPE::MmCached,
+        }
+    }
+
+    /// Returns the page protection flags that map memory with this cache type
+    const fn page_protection(self) -> ULONG {
+        match self {
+            Self::Uncached => PAGE_READWRITE | PAGE_NOCACHE,
+            Self::WriteCombined => PAGE_READWRITE | PAGE_WRITECOMBINE,
+            Self::Cached => PAGE_READWRITE,
+        }
+    }
+}
+
+/// A range of physical memory, mapped into system space with a chosen
+/// [`CacheType`].
+///
+/// Unlike an [`MmioRegion`](crate::io::MmioRegion), which is for device
+/// registers, this is for memory that the driver accesses in bulk, such as a
+/// framebuffer, or memory that is shared with the device. The range is
+/// unmapped when the [`PhysicalMapping`] is dropped, so it must be dropped at
+/// `IRQL` <= `DISPATCH_LEVEL`.
+///
+/// ```ignore
+/// let framebuffer = PhysicalMapping::map(&resources_translated, index, CacheType::WriteCombined)?;
+/// framebuffer.copy_from_slice(0, &frame);
+/// ```
+pub struct PhysicalMapping {
+    base: NonNull<u8>,
+    length: usize,
+    physical_address: u64,
+    cache_type: CacheType,
+}
+
+// SAFETY: The mapping is in system space, so it can be accessed, and unmapped,
+// from any thread.
+unsafe impl Send for PhysicalMapping {}
+
+// SAFETY: `PhysicalMapping` only allows its memory to be accessed through
+// `&self` by copying it, or by the unsafe `PhysicalMapping::as_slice()`, whose
+// callers guarantee that it is not mutated.
+unsafe impl Sync for PhysicalMapping {}
+
+impl PhysicalMapping {
+    /// Try to map the memory resource at `index` in `resources_translated`,
+    /// with `cache_type`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `resources_translated` is not a translated resource list, the resource at `index` is not a memory resource, or the system fails to map it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmMapIoSpaceEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmmapiospaceex#return-value)
+    pub fn map(
+        resources_translated: &ResourceList<'_>,
+        index: usize,
+        cache_type: CacheType,
+    ) -> Result<Self, NTSTATUS> {
+        if !resources_translated.is_translated() {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let Some(Resource::Memory { base, length, .. }) = resources_translated.iter().nth(index)
+        else {
+            return Err(STATUS_INVALID_PARAMETER);
+        };
+        let length = usize::try_from(length).map_err(|_| STATUS_INVALID_PARAMETER)?;
+
+        // SAFETY: Memory resources in a translated resource list, which the framework
+        // created, describe device memory assigned to the device.
+        unsafe { Self::map_physical(base, length, cache_type) }
+    }
+
+    /// Try to map `length` bytes of physical memory, starting at the physical
+    /// address `base`, with `cache_type`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Safety
+    ///
+    /// The range must be memory that the driver owns, such as a memory
+    /// resource assigned to its device, and must not be system memory that is
+    /// in use by anything else. Mapping memory with a different cache type than
+    /// other mappings of it is undefined behavior on most processors.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is 0, or the system fails to map the range. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmMapIoSpaceEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-mmmapiospaceex#return-value)
+    pub unsafe fn map_physical(
+        base: u64,
+        length: usize,
+        cache_type: CacheType,
+    ) -> Result<Self, NTSTATUS> {
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let physical_address = PHYSICAL_ADDRESS {
+            QuadPart: base.cast_signed(),
+        };
+
+        let mapping;
+        // SAFETY: The caller guarantees that the range is memory that the driver owns,
+        // which is not mapped with another cache type.
+        unsafe {
+            mapping = MmMapIoSpaceEx(
+                physical_address,
+                length as SIZE_T,
+                cache_type.page_protection(),
+            );
+        }
+        let mapping = NonNull::new(mapping.cast()).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        Ok(Self {
+            base: mapping,
+            length,
+            physical_address: base,
+            cache_type,
+        })
+    }
+
+    /// Returns the physical address of the start of the mapping
+    #[must_use]
+    pub const fn physical_address(&self) -> u64 {
+        self.physical_address
+    }
+
+    /// Returns the cache type that the range is mapped with
+    #[must_use]
+    pub const fn cache_type(&self) -> CacheType {
+        self.cache_type
+    }
+
+    /// Returns the length of the mapping, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the mapping is empty, which mappings never are
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns a pointer to the start of the mapping, in system space
+    #[must_use]
+    pub const fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Copy the bytes at `offset` bytes into the mapping into `destination`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes are not entirely within the mapping.
+    pub fn copy_to_slice(&self, offset: usize, destination: &mut [u8]) {
+        let source = self.range(offset, destination.len());
+        // SAFETY: `source` points to `destination.len()` bytes within the mapping,
+        // which is not Rust memory, so it does not overlap `destination`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(source, destination.as_mut_ptr(), destination.len());
+        }
+    }
+
+    /// Copy the bytes of `source` to `offset` bytes into the mapping
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes are not entirely within the mapping.
+    pub fn copy_from_slice(&self, offset: usize, source: &[u8]) {
+        let destination = self.range(offset, source.len());
+        // SAFETY: `destination` points to `source.len()` bytes within the mapping,
+        // which is not Rust memory, so it does not overlap `source`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(source.as_ptr(), destination, source.len());
+        }
+    }
+
+    /// Returns the contents of the mapping
+    ///
+    /// # Safety
+    ///
+    /// Nothing else, including the device, may write to the memory while the
+    /// returned slice is borrowed.
+    #[must_use]
+    pub const unsafe fn as_slice(&self) -> &[u8] {
+        // SAFETY: The mapping is `length` bytes, valid until it is unmapped when
+        // `self` is dropped, and the caller guarantees that it is not mutated while
+        // the slice is borrowed.
+        unsafe { core::slice::from_raw_parts(self.base.as_ptr(), self.length) }
+    }
+
+    /// Returns the contents of the mapping, mutably
+    ///
+    /// # Safety
+    ///
+    /// Nothing else, including the device, may access the memory while the
+    /// returned slice is borrowed.
+    #[must_use]
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: The mapping is `length` bytes, valid until it is unmapped when
+        // `self` is dropped, `self` is borrowed mutably, and the caller guarantees
+        // that nothing else accesses it while the slice is borrowed.
+        unsafe { core::slice::from_raw_parts_mut(self.base.as_ptr(), self.length) }
+    }
+
+    /// Returns a pointer to the `length` bytes at `offset` bytes into the
+    /// mapping
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes are not entirely within the mapping.
+    fn range(&self, offset: usize, length: usize) -> *mut u8 {
+        assert!(
+            offset
+                .checked_add(length)
+                .is_some_and(|end| end <= self.length),
+            "{length:#x} bytes at offset {offset:#x} are outside of the {:#x} byte mapping",
+            self.length
+        );
+        // SAFETY: `offset` is within the mapping, which was mapped whole.
+        unsafe { self.base.add(offset) }.as_ptr()
+    }
+}
+
+impl Drop for PhysicalMapping {
+    fn drop(&mut self) {
+        // SAFETY: `base` and `length` describe the mapping made by
+        // `MmMapIoSpaceEx`, which is no longer accessed after this.
+        unsafe {
+            MmUnmapIoSpace(self.base.as_ptr().cast(), self.length as SIZE_T);
+        }
+    }
+}
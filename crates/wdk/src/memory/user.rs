@@ -0,0 +1,201 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ffi::c_void;
+
+use wdk_sys::{
+    ntddk::{MmSecureVirtualMemory, MmUnsecureVirtualMemory},
+    MmUserProbeAddress,
+    HANDLE,
+    NTSTATUS,
+    PAGE_READONLY,
+    PAGE_READWRITE,
+    SIZE_T,
+    STATUS_ACCESS_VIOLATION,
+    STATUS_DATATYPE_MISALIGNMENT,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+};
+
+/// Check that the `length` bytes at `address` are in user space, and that
+/// `address` is aligned to `alignment`, as `ProbeForRead` and `ProbeForWrite`
+/// do before accessing the range
+fn check_user_range(address: usize, length: usize, alignment: usize) -> Result<(), NTSTATUS> {
+    if !alignment.is_power_of_two() {
+        return Err(STATUS_INVALID_PARAMETER);
+    }
+    if length == 0 {
+        return Ok(());
+    }
+    if address & (alignment - 1) != 0 {
+        return Err(STATUS_DATATYPE_MISALIGNMENT);
+    }
+
+    let end = address.checked_add(length).ok_or(STATUS_ACCESS_VIOLATION)?;
+    // SAFETY: `MmUserProbeAddress` is set when the system starts, and never
+    // modified after.
+    let user_probe_address = unsafe { MmUserProbeAddress };
+    if end as u64 > user_probe_address {
+        return Err(STATUS_ACCESS_VIOLATION);
+    }
+    Ok(())
+}
+
+/// A user-space range that is secured by `MmSecureVirtualMemory`, so that it
+/// cannot be freed, or its protection made more restrictive, until it is
+/// dropped
+struct SecuredRange(HANDLE);
+
+impl SecuredRange {
+    /// Secure the `length` bytes at `address`, which must be accessible with
+    /// `protection`
+    fn new(address: usize, length: usize, protection: ULONG) -> Result<Self, NTSTATUS> {
+        let handle;
+        // SAFETY: `MmSecureVirtualMemory` checks that the range is accessible with
+        // `protection` in the address space of the current process, returning null
+        // rather than raising an exception if it is not.
+        unsafe {
+            handle = MmSecureVirtualMemory(address as *mut c_void, length as SIZE_T, protection);
+        }
+        if handle.is_null() {
+            return Err(STATUS_ACCESS_VIOLATION);
+        }
+        Ok(Self(handle))
+    }
+}
+
+impl Drop for SecuredRange {
+    fn drop(&mut self) {
+        // SAFETY: `self.0` was returned by `MmSecureVirtualMemory`, and is only
+        // unsecured once.
+        unsafe {
+            MmUnsecureVirtualMemory(self.0);
+        }
+    }
+}
+
+/// Check that the `length` bytes at `address` are a readable range of the
+/// address space of the current process, and that `address` is aligned to
+/// `alignment` bytes
+///
+/// This performs the checks of `ProbeForRead`, and checks that the range is
+/// committed and readable, returning an error instead of raising an exception
+/// if it is not, since Rust cannot handle structured exceptions. The range can
+/// still be freed by another thread of the process after it is probed, so it
+/// must be read with [`copy_from_user()`], or locked with an
+/// [`Mdl`](super::Mdl), rather than being accessed directly.
+///
+/// This must be called at `IRQL` <= `APC_LEVEL`, in the context of the process
+/// that the range belongs to, such as in the `EvtIoInCallerContext` callback
+/// of a `METHOD_NEITHER` request.
+///
+/// # Errors
+///
+/// This function will return an error if `alignment` is not a power of two, if `address` is not aligned to `alignment`, if the range is not entirely in user space, or if it is not readable. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ProbeForRead Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-probeforread)
+pub fn probe_read(address: *const c_void, length: usize, alignment: usize) -> Result<(), NTSTATUS> {
+    let address = address as usize;
+    check_user_range(address, length, alignment)?;
+    if length != 0 {
+        // Securing the range checks that it is accessible, and it is unsecured again
+        // right away
+        SecuredRange::new(address, length, PAGE_READONLY)?;
+    }
+    Ok(())
+}
+
+/// Check that the `length` bytes at `address` are a writable range of the
+/// address space of the current process, and that `address` is aligned to
+/// `alignment` bytes
+///
+/// This performs the checks of `ProbeForWrite`, returning an error instead of
+/// raising an exception if the range is not writable, since Rust cannot handle
+/// structured exceptions. The range can still be freed by another thread of
+/// the process after it is probed, so it must be written with
+/// [`copy_to_user()`], or locked with an [`Mdl`](super::Mdl), rather than being
+/// accessed directly.
+///
+/// This must be called at `IRQL` <= `APC_LEVEL`, in the context of the process
+/// that the range belongs to.
+///
+/// # Errors
+///
+/// This function will return an error if `alignment` is not a power of two, if `address` is not aligned to `alignment`, if the range is not entirely in user space, or if it is not writable. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ProbeForWrite Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-probeforwrite)
+pub fn probe_write(address: *mut c_void, length: usize, alignment: usize) -> Result<(), NTSTATUS> {
+    let address = address as usize;
+    check_user_range(address, length, alignment)?;
+    if length != 0 {
+        // Securing the range checks that it is accessible, and it is unsecured again
+        // right away
+        SecuredRange::new(address, length, PAGE_READWRITE)?;
+    }
+    Ok(())
+}
+
+/// Copy `destination.len()` bytes from the user-space buffer at `source` into
+/// `destination`
+///
+/// The buffer is secured for the duration of the copy, so it cannot be freed
+/// by another thread of the process while it is read. It can still be written
+/// to, so a value read from it more than once may change between reads, and
+/// should be copied once and validated after.
+///
+/// This must be called at `IRQL` <= `APC_LEVEL`.
+///
+/// # Errors
+///
+/// This function will return an error if the buffer is not entirely in user space, or if it is not readable. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmSecureVirtualMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/ntddk/nf-ntddk-mmsecurevirtualmemory#return-value)
+///
+/// # Safety
+///
+/// This must be called in the context of the process that the buffer belongs
+/// to, and `source` must be a user-space address supplied by that process,
+/// such as the user buffer of the request being processed.
+pub unsafe fn copy_from_user(destination: &mut [u8], source: *const u8) -> Result<(), NTSTATUS> {
+    if destination.is_empty() {
+        return Ok(());
+    }
+    check_user_range(source as usize, destination.len(), 1)?;
+    let _secured_range = SecuredRange::new(source as usize, destination.len(), PAGE_READONLY)?;
+
+    // SAFETY: The caller guarantees that the source is in the address space of
+    // the current process. It is a readable user-space range of
+    // `destination.len()` bytes, which cannot be freed while it is secured, so it
+    // does not overlap `destination` and reading it does not raise an exception.
+    unsafe {
+        core::ptr::copy_nonoverlapping(source, destination.as_mut_ptr(), destination.len());
+    }
+    Ok(())
+}
+
+/// Copy the bytes of `source` into the user-space buffer at `destination`
+///
+/// The buffer is secured for the duration of the copy, so it cannot be freed
+/// by another thread of the process while it is written.
+///
+/// This must be called at `IRQL` <= `APC_LEVEL`.
+///
+/// # Errors
+///
+/// This function will return an error if the buffer is not entirely in user space, or if it is not writable. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [MmSecureVirtualMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/ntddk/nf-ntddk-mmsecurevirtualmemory#return-value)
+///
+/// # Safety
+///
+/// This must be called in the context of the process that the buffer belongs
+/// to, and `destination` must be a user-space address supplied by that
+/// process, such as the user buffer of the request being processed.
+pub unsafe fn copy_to_user(destination: *mut u8, source: &[u8]) -> Result<(), NTSTATUS> {
+    if source.is_empty() {
+        return Ok(());
+    }
+    check_user_range(destination as usize, source.len(), 1)?;
+    let _secured_range = SecuredRange::new(destination as usize, source.len(), PAGE_READWRITE)?;
+
+    // SAFETY: The caller guarantees that the destination is in the address space
+    // of the current process. It is a writable user-space range of `source.len()`
+    // bytes, which cannot be freed while it is secured, so it does not overlap
+    // `source` and writing it does not raise an exception.
+    unsafe {
+        core::ptr::copy_nonoverlapping(source.as_ptr(), destination, source.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Crate-internal helpers for storing kernel objects in non-paged pool.
+//!
+//! Many kernel objects (ex. `KEVENT` and `ERESOURCE`) contain self-referential
+//! list heads or are tracked by the kernel by address, so they must never move
+//! once they have been initialized. They must also reside in non-paged memory,
+//! since they can be accessed at `IRQL` = `DISPATCH_LEVEL`. [`NonPagedBox`]
+//! provides an owned allocation with a stable address for these objects.
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    ntddk::{ExAllocatePool2, ExFreePool},
+    NTSTATUS,
+    POOL_FLAG_NON_PAGED,
+    SIZE_T,
+    STATUS_INSUFFICIENT_RESOURCES,
+    ULONG,
+};
+
+// The value of memory tags are stored in little-endian order, so it is
+// convenient to reverse the order for readability in tooling (ie. Windbg)
+pub const POOL_TAG: ULONG = u32::from_ne_bytes(*b"rust");
+
+/// An owned non-paged pool allocation containing a `T` at a stable address.
+///
+/// This is intended to store kernel objects. The contained value is only ever
+/// accessed through raw pointers, since the kernel may mutate it at any time
+/// (ex. while another thread waits on a `KEVENT`). Types that use it to store
+/// values that are not kernel objects must restore the `Send` and `Sync`
+/// bounds of those values themselves (ex. with a [`PhantomData`]).
+///
+/// [`PhantomData`]: core::marker::PhantomData
+pub struct NonPagedBox<T> {
+    ptr: NonNull<T>,
+}
+
+// SAFETY: `NonPagedBox` is used to store kernel objects, which are designed to
+// be used from any thread. Their `wdk-sys` bindings are only `!Send` because
+// they contain raw pointers. Users storing other values restore their bounds.
+unsafe impl<T> Send for NonPagedBox<T> {}
+
+// SAFETY: `NonPagedBox` does not provide any access to the contained `T` beyond
+// raw pointers, and is used to store kernel objects, which the kernel
+// synchronizes access to internally. Users storing other values restore their
+// bounds.
+unsafe impl<T> Sync for NonPagedBox<T> {}
+
+impl<T> NonPagedBox<T> {
+    /// Allocate non-paged pool and move `value` into it
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `STATUS_INSUFFICIENT_RESOURCES` if the allocation fails
+    pub fn try_new(value: T) -> Result<Self, NTSTATUS> {
+        let allocation =
+            // SAFETY: `ExAllocatePool2` is safe to call from any `IRQL` <= `DISPATCH_LEVEL` since its allocating from `POOL_FLAG_NON_PAGED`
+            unsafe {
+                ExAllocatePool2(
+                    POOL_FLAG_NON_PAGED,
+                    core::mem::size_of::<T>() as SIZE_T,
+                    POOL_TAG,
+                )
+            };
+        let ptr = NonNull::new(allocation.cast::<T>()).ok_or(STATUS_INSUFFICIENT_RESOURCES)?;
+        debug_assert!(
+            ptr.as_ptr().align_offset(core::mem::align_of::<T>()) == 0,
+            "pool allocations should satisfy the alignment of kernel objects"
+        );
+
+        // SAFETY: `ptr` is non-null, properly aligned, and valid for writes of `T`
+        // since it was just allocated with the size of `T`.
+        unsafe {
+            ptr.as_ptr().write(value);
+        }
+        Ok(Self { ptr })
+    }
+
+    /// Returns a raw pointer to the contained value. The pointer is valid for
+    /// the lifetime of the [`NonPagedBox`], and never changes.
+    pub const fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+
+    /// Consumes the [`NonPagedBox`], freeing it and returning the contained
+    /// value
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn into_inner(self) -> T {
+        let ptr = self.into_raw();
+
+        let value;
+        // SAFETY: `ptr` points to a valid `T` that was written in `try_new`, and was
+        // just released from its `NonPagedBox`, so it is only read once.
+        unsafe {
+            value = ptr.as_ptr().read();
+        }
+
+        // SAFETY: `ptr` was allocated by `ExAllocatePool2` in `try_new`, and is not
+        // used after this point.
+        unsafe {
+            ExFreePool(ptr.as_ptr().cast());
+        }
+        value
+    }
+
+    /// Consumes the [`NonPagedBox`] without freeing it, returning a pointer to
+    /// the contained value
+    pub const fn into_raw(self) -> NonNull<T> {
+        let ptr = self.ptr;
+        core::mem::forget(self);
+        ptr
+    }
+
+    /// Reconstruct a [`NonPagedBox`] from a pointer returned by
+    /// [`NonPagedBox::into_raw()`]
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`NonPagedBox::into_raw()`], and must
+    /// not be used to reconstruct more than one [`NonPagedBox`].
+    pub const unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        Self { ptr }
+    }
+}
+
+impl<T> Drop for NonPagedBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` points to a valid `T` that was written in `try_new`, and is
+        // never dropped anywhere else.
+        unsafe {
+            core::ptr::drop_in_place(self.ptr.as_ptr());
+        }
+
+        // SAFETY: `ptr` was allocated by `ExAllocatePool2` in `try_new`, and is not
+        // used after this point.
+        unsafe {
+            ExFreePool(self.ptr.as_ptr().cast());
+        }
+    }
+}
@@ -0,0 +1,633 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Kernel registry keys, which can be opened at any path in the registry.
+//!
+//! Unlike [`wdf::RegistryKey`](crate::wdf::RegistryKey), which is usually
+//! opened at one of the locations the framework manages for the driver, a
+//! [`KernelRegistryKey`] can be opened at any absolute registry path, such as
+//! the `HKLM\SYSTEM\CurrentControlSet` settings of another service, and can be
+//! used by drivers that do not use WDF.
+
+use core::{fmt, iter::FusedIterator, mem::offset_of};
+
+use wdk_sys::{
+    ntddk::{ZwClose, ZwEnumerateKey, ZwOpenKey, ZwQueryValueKey, ZwSetValueKey},
+    _KEY_INFORMATION_CLASS::KeyBasicInformation,
+    _KEY_VALUE_INFORMATION_CLASS::KeyValuePartialInformation,
+    HANDLE,
+    KEY_BASIC_INFORMATION,
+    KEY_VALUE_PARTIAL_INFORMATION,
+    NTSTATUS,
+    OBJECT_ATTRIBUTES,
+    OBJ_CASE_INSENSITIVE,
+    OBJ_KERNEL_HANDLE,
+    PVOID,
+    REG_BINARY,
+    REG_DWORD,
+    REG_EXPAND_SZ,
+    REG_MULTI_SZ,
+    REG_NONE,
+    REG_QWORD,
+    REG_SZ,
+    STATUS_BUFFER_OVERFLOW,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_NO_MORE_ENTRIES,
+    STATUS_OBJECT_TYPE_MISMATCH,
+    ULONG,
+};
+
+use crate::{
+    debug_assert_irql,
+    irql::PassiveLevel,
+    nt_success,
+    string::NtUnicodeStr,
+    wdf::{name::UnicodeBuffer, KeyAccess},
+};
+
+// `OBJECT_ATTRIBUTES` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const OBJECT_ATTRIBUTES_LENGTH: ULONG = core::mem::size_of::<OBJECT_ATTRIBUTES>() as ULONG;
+
+/// The offset of the data of a value in the `KEY_VALUE_PARTIAL_INFORMATION`
+/// that `ZwQueryValueKey` returns
+const VALUE_DATA_OFFSET: usize = offset_of!(KEY_VALUE_PARTIAL_INFORMATION, Data);
+
+/// The offset of the name of a subkey in the `KEY_BASIC_INFORMATION` that
+/// `ZwEnumerateKey` returns
+const KEY_NAME_OFFSET: usize = offset_of!(KEY_BASIC_INFORMATION, Name);
+
+/// The maximum length of the name of a registry key, in UTF-16 code units
+const MAX_KEY_NAME_LENGTH: usize = 255;
+
+/// The length of a `KEY_BASIC_INFORMATION` holding the longest key name, in
+/// `u64`s, so that a buffer of them is aligned for it
+const KEY_INFORMATION_LENGTH: usize =
+    (KEY_NAME_OFFSET + MAX_KEY_NAME_LENGTH * core::mem::size_of::<u16>()).div_ceil(8);
+
+// A `KEY_BASIC_INFORMATION` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const KEY_INFORMATION_BYTES: ULONG =
+    (KEY_INFORMATION_LENGTH * core::mem::size_of::<u64>()) as ULONG;
+
+/// The type of the data of a registry value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    /// `REG_NONE`, a value with no defined type
+    None,
+    /// `REG_SZ`, a null-terminated UTF-16 string
+    String,
+    /// `REG_EXPAND_SZ`, a null-terminated UTF-16 string holding unexpanded
+    /// references to environment variables
+    ExpandString,
+    /// `REG_BINARY`, binary data of any form
+    Binary,
+    /// `REG_DWORD`, a 32-bit number
+    Dword,
+    /// `REG_MULTI_SZ`, a list of null-terminated UTF-16 strings, terminated by
+    /// an empty string
+    MultiString,
+    /// `REG_QWORD`, a 64-bit number
+    Qword,
+    /// Any other `REG_*` type
+    Other(u32),
+}
+
+impl ValueType {
+    /// Construct the [`ValueType`] of the `REG_*` type `value_type`
+    #[must_use]
+    pub const fn from_raw(value_type: ULONG) -> Self {
+        match value_type {
+            REG_NONE => Self::None,
+            REG_SZ => Self::String,
+            REG_EXPAND_SZ => Self::ExpandString,
+            REG_BINARY => Self::Binary,
+            REG_DWORD => Self::Dword,
+            REG_MULTI_SZ => Self::MultiString,
+            REG_QWORD => Self::Qword,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Returns the `REG_*` type of this [`ValueType`]
+    #[must_use]
+    pub const fn as_raw(self) -> ULONG {
+        match self {
+            Self::None => REG_NONE,
+            Self::String => REG_SZ,
+            Self::ExpandString => REG_EXPAND_SZ,
+            Self::Binary => REG_BINARY,
+            Self::Dword => REG_DWORD,
+            Self::MultiString => REG_MULTI_SZ,
+            Self::Qword => REG_QWORD,
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// Kernel Registry Key.
+///
+/// A [`KernelRegistryKey`] is a kernel handle to an open registry key, opened
+/// with [`KernelRegistryKey::open()`] at an absolute registry path, whose
+/// values can be read and written, and whose subkeys can be opened and
+/// enumerated. The handle is closed when the [`KernelRegistryKey`] is dropped.
+/// Every method of [`KernelRegistryKey`] must be called at `IRQL` =
+/// `PASSIVE_LEVEL`, so each one that uses the key takes a [`PassiveLevel`]
+/// token.
+///
+/// Paths and value names are encoded as UTF-16 without allocating, so they are
+/// limited to 512 UTF-16 code units.
+///
+/// ```ignore
+/// let key = KernelRegistryKey::open(
+///     irql,
+///     r"\Registry\Machine\System\CurrentControlSet\Services\Tcpip\Parameters",
+///     KeyAccess::Read,
+/// )?;
+/// let ttl = key.query_u32(irql, "DefaultTTL").unwrap_or(128);
+/// ```
+pub struct KernelRegistryKey {
+    handle: HANDLE,
+}
+
+// SAFETY: Kernel handles can be used, and closed, from any thread.
+unsafe impl Send for KernelRegistryKey {}
+
+// SAFETY: The registry synchronizes concurrent access to the values and
+// subkeys of a key.
+unsafe impl Sync for KernelRegistryKey {}
+
+impl KernelRegistryKey {
+    /// Try to open the registry key at the absolute registry `path` (ex.
+    /// `\Registry\Machine\System\CurrentControlSet\Services\Sample`) with
+    /// `access`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `path` is too long, if the key does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwOpenKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwopenkey#return-value)
+    pub fn open(_irql: &PassiveLevel, path: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        Self::open_key(core::ptr::null_mut(), path, access)
+    }
+
+    /// Returns the raw kernel handle wrapped by this [`KernelRegistryKey`]
+    #[must_use]
+    pub const fn as_raw(&self) -> HANDLE {
+        self.handle
+    }
+
+    /// Try to open the subkey `name` of the key with `access`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the subkey does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwOpenKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwopenkey#return-value)
+    pub fn open_subkey(
+        &self,
+        _irql: &PassiveLevel,
+        name: &str,
+        access: KeyAccess,
+    ) -> Result<Self, NTSTATUS> {
+        Self::open_key(self.handle, name, access)
+    }
+
+    /// Read the value `name` of the key into `buffer`, returning the type of
+    /// the value and the part of `buffer` that holds its data
+    ///
+    /// The kernel returns the data after a short header, so `buffer` must be
+    /// a little longer than the data. Use
+    /// [`KernelRegistryKey::query_buffer_length()`] to size `buffer`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryvaluekey#return-value)
+    pub fn query_value<'a>(
+        &self,
+        _irql: &PassiveLevel,
+        name: &str,
+        buffer: &'a mut [u8],
+    ) -> Result<(ValueType, &'a [u8]), NTSTATUS> {
+        let (value_type, length) = self.query_value_raw(name, buffer.as_mut_ptr(), buffer.len())?;
+        Ok((ValueType::from_raw(value_type), &buffer[..length]))
+    }
+
+    /// Returns the length of the buffer that
+    /// [`KernelRegistryKey::query_value()`] needs to read the value `name` of
+    /// the key, in bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the key has no such value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryvaluekey#return-value)
+    pub fn query_buffer_length(&self, _irql: &PassiveLevel, name: &str) -> Result<usize, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let name = UnicodeBuffer::new(name)?;
+        let mut name = name.as_unicode_string();
+        let mut result_length: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelRegistryKey`, which is a
+        // valid kernel handle to an open key until it is dropped. `name` and
+        // `result_length` are valid for the duration of the call, and a null buffer
+        // is not written to when its length is 0.
+        unsafe {
+            nt_status = ZwQueryValueKey(
+                self.handle,
+                &mut name,
+                KeyValuePartialInformation,
+                core::ptr::null_mut(),
+                0,
+                &mut result_length,
+            );
+        }
+        // Querying only the length of the value reports that the buffer is too small
+        if nt_status == STATUS_BUFFER_TOO_SMALL || nt_status == STATUS_BUFFER_OVERFLOW {
+            Ok(result_length as usize)
+        } else {
+            Err(nt_status)
+        }
+    }
+
+    /// Read the `REG_DWORD` value `name` of the key
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, or if the value is not a `REG_DWORD`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryvaluekey#return-value)
+    pub fn query_u32(&self, irql: &PassiveLevel, name: &str) -> Result<u32, NTSTATUS> {
+        let mut buffer = [0; VALUE_DATA_OFFSET + core::mem::size_of::<u32>()];
+        let (value_type, data) = self.query_value(irql, name, &mut buffer)?;
+        match (value_type, <[u8; 4]>::try_from(data)) {
+            (ValueType::Dword, Ok(data)) => Ok(u32::from_ne_bytes(data)),
+            _ => Err(STATUS_OBJECT_TYPE_MISMATCH),
+        }
+    }
+
+    /// Read the `REG_QWORD` value `name` of the key
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, or if the value is not a `REG_QWORD`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryvaluekey#return-value)
+    pub fn query_u64(&self, irql: &PassiveLevel, name: &str) -> Result<u64, NTSTATUS> {
+        let mut buffer = [0; VALUE_DATA_OFFSET + core::mem::size_of::<u64>()];
+        let (value_type, data) = self.query_value(irql, name, &mut buffer)?;
+        match (value_type, <[u8; 8]>::try_from(data)) {
+            (ValueType::Qword, Ok(data)) => Ok(u64::from_ne_bytes(data)),
+            _ => Err(STATUS_OBJECT_TYPE_MISMATCH),
+        }
+    }
+
+    /// Read the `REG_SZ` or `REG_EXPAND_SZ` value `name` of the key into
+    /// `buffer`, returning the part of `buffer` that holds the string, without
+    /// its null terminator
+    ///
+    /// `REG_EXPAND_SZ` values are not expanded. Like
+    /// [`KernelRegistryKey::query_value()`], `buffer` must be a little longer
+    /// than the string.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, if the value is not a string, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwQueryValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwqueryvaluekey#return-value)
+    pub fn query_string<'a>(
+        &self,
+        _irql: &PassiveLevel,
+        name: &str,
+        buffer: &'a mut [u16],
+    ) -> Result<&'a [u16], NTSTATUS> {
+        let (value_type, length) = self.query_value_raw(
+            name,
+            buffer.as_mut_ptr().cast(),
+            core::mem::size_of_val(buffer),
+        )?;
+        if value_type != REG_SZ && value_type != REG_EXPAND_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        let string = &buffer[..length / core::mem::size_of::<u16>()];
+        Ok(string.strip_suffix(&[0]).unwrap_or(string))
+    }
+
+    /// Set the value `name` of the key to `data`, as a value of type
+    /// `value_type`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` or `data` is too long, or if the driver is not allowed to write to the key. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwSetValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwsetvaluekey#return-value)
+    pub fn set_value(
+        &self,
+        _irql: &PassiveLevel,
+        name: &str,
+        value_type: ValueType,
+        data: &[u8],
+    ) -> Result<(), NTSTATUS> {
+        self.set_value_raw(
+            name,
+            value_type.as_raw(),
+            data.as_ptr().cast_mut().cast(),
+            data.len(),
+        )
+    }
+
+    /// Set the value `name` of the key to the `REG_DWORD` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the driver is not allowed to write to the key. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwSetValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwsetvaluekey#return-value)
+    pub fn set_u32(&self, irql: &PassiveLevel, name: &str, value: u32) -> Result<(), NTSTATUS> {
+        self.set_value(irql, name, ValueType::Dword, &value.to_ne_bytes())
+    }
+
+    /// Set the value `name` of the key to the `REG_QWORD` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the driver is not allowed to write to the key. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwSetValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwsetvaluekey#return-value)
+    pub fn set_u64(&self, irql: &PassiveLevel, name: &str, value: u64) -> Result<(), NTSTATUS> {
+        self.set_value(irql, name, ValueType::Qword, &value.to_ne_bytes())
+    }
+
+    /// Set the value `name` of the key to the `REG_SZ` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` or `value` is too long, or if the driver is not allowed to write to the key. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ZwSetValueKey Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-zwsetvaluekey#return-value)
+    pub fn set_string(
+        &self,
+        _irql: &PassiveLevel,
+        name: &str,
+        value: &str,
+    ) -> Result<(), NTSTATUS> {
+        let value = UnicodeBuffer::new(value)?;
+        let value = value.as_null_terminated();
+        self.set_value_raw(
+            name,
+            REG_SZ,
+            value.as_ptr().cast_mut().cast(),
+            core::mem::size_of_val(value),
+        )
+    }
+
+    /// Returns an iterator over the names of the subkeys of the key
+    ///
+    /// The iterator yields an error, and then ends, if a subkey could not be
+    /// enumerated. Subkeys that are created or deleted while iterating may or
+    /// may not be yielded.
+    #[must_use]
+    pub const fn subkeys<'a>(&'a self, irql: &'a PassiveLevel) -> Subkeys<'a> {
+        Subkeys {
+            key: self,
+            _irql: irql,
+            index: 0,
+            done: false,
+        }
+    }
+
+    /// Open the key `name`, relative to `root` if it is not null, with
+    /// `access`
+    fn open_key(root: HANDLE, name: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be opened");
+
+        let name = UnicodeBuffer::new(name)?;
+        let mut name = name.as_unicode_string();
+        let mut object_attributes = OBJECT_ATTRIBUTES {
+            Length: OBJECT_ATTRIBUTES_LENGTH,
+            RootDirectory: root,
+            ObjectName: &mut name,
+            Attributes: OBJ_KERNEL_HANDLE | OBJ_CASE_INSENSITIVE,
+            ..Default::default()
+        };
+        let mut handle: HANDLE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `root` is either null or a valid kernel handle to an open key, and
+        // `handle` and `object_attributes`, along with the name it points to, are
+        // valid for the duration of the call.
+        unsafe {
+            nt_status = ZwOpenKey(&mut handle, access.as_raw(), &mut object_attributes);
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(Self { handle })
+    }
+
+    /// Read the value `name` of the key into the `length` bytes at `buffer`,
+    /// moving the value's data to the start of `buffer`, and returning the
+    /// `REG_*` type of the value and the length of its data
+    fn query_value_raw(
+        &self,
+        name: &str,
+        buffer: *mut u8,
+        length: usize,
+    ) -> Result<(ULONG, usize), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let name = UnicodeBuffer::new(name)?;
+        let mut name = name.as_unicode_string();
+        let mut result_length: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelRegistryKey`, which is a
+        // valid kernel handle to an open key until it is dropped. `buffer` is valid
+        // for writes of `length` bytes, and the other arguments are valid for the
+        // duration of the call.
+        unsafe {
+            nt_status = ZwQueryValueKey(
+                self.handle,
+                &mut name,
+                KeyValuePartialInformation,
+                buffer.cast(),
+                length,
+                &mut result_length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // The header is read field by field, since `buffer` may be shorter than a
+        // whole `KEY_VALUE_PARTIAL_INFORMATION` when the data is shorter than its
+        // `Data` field, and is only aligned to a byte
+        let type_address = buffer.wrapping_add(offset_of!(KEY_VALUE_PARTIAL_INFORMATION, Type));
+        let value_type: ULONG;
+        // SAFETY: On success, the kernel wrote the header of a
+        // `KEY_VALUE_PARTIAL_INFORMATION`, which ends at `VALUE_DATA_OFFSET`, to the
+        // start of `buffer`.
+        unsafe {
+            value_type = type_address.cast::<ULONG>().read_unaligned();
+        }
+        let data_length_address =
+            buffer.wrapping_add(offset_of!(KEY_VALUE_PARTIAL_INFORMATION, DataLength));
+        let data_length: ULONG;
+        // SAFETY: On success, the kernel wrote the header of a
+        // `KEY_VALUE_PARTIAL_INFORMATION`, which ends at `VALUE_DATA_OFFSET`, to the
+        // start of `buffer`.
+        unsafe {
+            data_length = data_length_address.cast::<ULONG>().read_unaligned();
+        }
+        let data_length = data_length as usize;
+        // SAFETY: On success, the `data_length` bytes of data follow the header within
+        // the `length` bytes at `buffer`, and `copy` allows the source and destination
+        // to overlap.
+        unsafe {
+            core::ptr::copy(buffer.wrapping_add(VALUE_DATA_OFFSET), buffer, data_length);
+        }
+        Ok((value_type, data_length))
+    }
+
+    /// Set the value `name` of the key to the `length` bytes at `data`, as a
+    /// value of type `value_type`
+    fn set_value_raw(
+        &self,
+        name: &str,
+        value_type: ULONG,
+        data: PVOID,
+        length: usize,
+    ) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let name = UnicodeBuffer::new(name)?;
+        let mut name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelRegistryKey`, which is a
+        // valid kernel handle to an open key until it is dropped. `name` is valid for
+        // the duration of the call, and `data` points to `length` bytes, which the
+        // call only reads from.
+        unsafe {
+            nt_status = ZwSetValueKey(self.handle, &mut name, 0, value_type, data, length);
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+impl Drop for KernelRegistryKey {
+    fn drop(&mut self) {
+        let nt_status;
+        // SAFETY: `handle` is a private member of `KernelRegistryKey`, which is a
+        // valid kernel handle to an open key that is not used after this.
+        unsafe {
+            nt_status = ZwClose(self.handle);
+        }
+        debug_assert!(
+            nt_success(nt_status),
+            "ZwClose should succeed on a valid kernel handle"
+        );
+    }
+}
+
+/// An iterator over the names of the subkeys of a [`KernelRegistryKey`],
+/// created by [`KernelRegistryKey::subkeys()`]
+pub struct Subkeys<'a> {
+    key: &'a KernelRegistryKey,
+    // The subkeys are enumerated at `IRQL` = `PASSIVE_LEVEL`, on the thread that
+    // created the iterator
+    _irql: &'a PassiveLevel,
+    index: ULONG,
+    done: bool,
+}
+
+impl Iterator for Subkeys<'_> {
+    type Item = Result<KeyName, NTSTATUS>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let mut buffer = [0_u64; KEY_INFORMATION_LENGTH];
+        let mut result_length: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `handle` is a valid kernel handle to an open key for the lifetime of
+        // `key`. `buffer` is valid for writes of `KEY_INFORMATION_BYTES`, and
+        // `result_length` is valid for the duration of the call.
+        unsafe {
+            nt_status = ZwEnumerateKey(
+                self.key.handle,
+                self.index,
+                KeyBasicInformation,
+                buffer.as_mut_ptr().cast(),
+                KEY_INFORMATION_BYTES,
+                &mut result_length,
+            );
+        }
+        if nt_status == STATUS_NO_MORE_ENTRIES {
+            self.done = true;
+            return None;
+        }
+        if !nt_success(nt_status) {
+            self.done = true;
+            return Some(Err(nt_status));
+        }
+        self.index += 1;
+
+        let information;
+        // SAFETY: On success, the kernel wrote a `KEY_BASIC_INFORMATION` to the start
+        // of `buffer`, which is aligned for it.
+        unsafe {
+            information = buffer.as_ptr().cast::<KEY_BASIC_INFORMATION>().read();
+        }
+        let length = (information.NameLength as usize / core::mem::size_of::<u16>())
+            .min(MAX_KEY_NAME_LENGTH);
+        let mut name = KeyName {
+            buffer: [0; MAX_KEY_NAME_LENGTH],
+            length,
+        };
+        // `KEY_NAME_OFFSET` is a whole number of `u16`s, so `source` is aligned for
+        // them
+        let source = buffer
+            .as_ptr()
+            .cast::<u16>()
+            .wrapping_add(KEY_NAME_OFFSET / core::mem::size_of::<u16>());
+        // SAFETY: On success, the `length` code units of the name follow the header
+        // within `buffer`, and do not overlap `name`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(source, name.buffer.as_mut_ptr(), length);
+        }
+        Some(Ok(name))
+    }
+}
+
+impl FusedIterator for Subkeys<'_> {}
+
+/// The name of a subkey of a [`KernelRegistryKey`], yielded by
+/// [`Subkeys`]
+#[derive(Clone, Copy)]
+pub struct KeyName {
+    buffer: [u16; MAX_KEY_NAME_LENGTH],
+    length: usize,
+}
+
+impl KeyName {
+    /// Returns the name as UTF-16
+    #[must_use]
+    pub fn as_slice(&self) -> &[u16] {
+        &self.buffer[..self.length]
+    }
+
+    /// Returns the name as an [`NtUnicodeStr`], to pass to functions that take
+    /// a `UNICODE_STRING`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name does not fit in a `UNICODE_STRING`, which never
+    /// happens, since names are at most 255 code units long.
+    #[must_use]
+    pub fn as_unicode_str(&self) -> NtUnicodeStr<'_> {
+        NtUnicodeStr::try_from_utf16(self.as_slice())
+            .expect("registry key names should fit in a UNICODE_STRING")
+    }
+}
+
+impl fmt::Display for KeyName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_unicode_str(), f)
+    }
+}
+
+impl fmt::Debug for KeyName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_unicode_str(), f)
+    }
+}
@@ -0,0 +1,638 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! [`NtStatus`], a readable wrapper for `NTSTATUS` values, `const` helpers
+//! to inspect them and define new ones, and their conversion to and from
+//! `HRESULT`s and Win32 error codes.
+
+use core::fmt;
+
+use wdk_sys::{ntddk::RtlNtStatusToDosErrorNoTeb, HRESULT, NTSTATUS, ULONG};
+
+/// An `NTSTATUS` value, as returned by most kernel-mode and WDF functions.
+///
+/// [`NtStatus`] wraps an [`NTSTATUS`](wdk_sys::NTSTATUS), which it can be
+/// converted to and from, so it can be used with `?` in functions returning
+/// either. Unlike a bare `NTSTATUS`, it is formatted with its symbolic name
+/// (ex. `STATUS_INVALID_PARAMETER`) when it is one of the named constants, and
+/// in hexadecimal (ex. `0xC0000001`) otherwise.
+///
+/// ```ignore
+/// let status = NtStatus::from_raw(nt_status);
+/// if !status.is_success() {
+///     println!("WdfDeviceCreate failed: {status}");
+/// }
+/// status.ok()?;
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct NtStatus(NTSTATUS);
+
+/// Declares the named [`NtStatus`] constants, and the lookup of their symbolic
+/// names used when formatting
+macro_rules! named_statuses {
+    ($($name:ident),* $(,)?) => {
+        impl NtStatus {
+            $(
+                #[doc = concat!("`", stringify!($name), "`")]
+                pub const $name: Self = Self(wdk_sys::$name);
+            )*
+
+            /// Returns the symbolic name of the status, if it is one of the
+            /// named [`NtStatus`] constants
+            #[must_use]
+            pub const fn name(self) -> Option<&'static str> {
+                match self.0 {
+                    $(wdk_sys::$name => Some(stringify!($name)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+named_statuses! {
+    STATUS_SUCCESS,
+    STATUS_USER_APC,
+    STATUS_ALERTED,
+    STATUS_TIMEOUT,
+    STATUS_PENDING,
+    STATUS_REPARSE,
+    STATUS_MORE_ENTRIES,
+    STATUS_NOT_ALL_ASSIGNED,
+    STATUS_BUFFER_ALL_ZEROS,
+    STATUS_BUFFER_OVERFLOW,
+    STATUS_DEVICE_BUSY,
+    STATUS_NO_MORE_ENTRIES,
+    STATUS_UNSUCCESSFUL,
+    STATUS_NOT_IMPLEMENTED,
+    STATUS_INVALID_HANDLE,
+    STATUS_INVALID_PARAMETER,
+    STATUS_NO_SUCH_DEVICE,
+    STATUS_NO_SUCH_FILE,
+    STATUS_INVALID_DEVICE_REQUEST,
+    STATUS_END_OF_FILE,
+    STATUS_NO_MEMORY,
+    STATUS_ACCESS_VIOLATION,
+    STATUS_INFO_LENGTH_MISMATCH,
+    STATUS_ACCESS_DENIED,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_OBJECT_TYPE_MISMATCH,
+    STATUS_OBJECT_NAME_NOT_FOUND,
+    STATUS_OBJECT_NAME_COLLISION,
+    STATUS_OBJECT_PATH_NOT_FOUND,
+    STATUS_SHARING_VIOLATION,
+    STATUS_DELETE_PENDING,
+    STATUS_PRIVILEGE_NOT_HELD,
+    STATUS_DATA_ERROR,
+    STATUS_INSUFFICIENT_RESOURCES,
+    STATUS_DEVICE_NOT_READY,
+    STATUS_INTEGER_OVERFLOW,
+    STATUS_DISK_FULL,
+    STATUS_NOT_SUPPORTED,
+    STATUS_FILE_IS_A_DIRECTORY,
+    STATUS_DEVICE_DOES_NOT_EXIST,
+    STATUS_IO_TIMEOUT,
+    STATUS_CANCELLED,
+    STATUS_INVALID_USER_BUFFER,
+    STATUS_INVALID_ADDRESS,
+    STATUS_NOT_A_DIRECTORY,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_REQUEST_ABORTED,
+    STATUS_CONNECTION_REFUSED,
+    STATUS_RETRY,
+    STATUS_NOT_FOUND,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_DEVICE_REMOVED,
+    STATUS_POWER_STATE_INVALID,
+    STATUS_ALREADY_REGISTERED,
+}
+
+/// The severity of an `NTSTATUS`, stored in its two most significant bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    /// The operation succeeded, `STATUS_SEVERITY_SUCCESS`
+    Success = 0b00,
+    /// The operation succeeded with information for the caller,
+    /// `STATUS_SEVERITY_INFORMATIONAL`
+    Informational = 0b01,
+    /// The operation did not succeed, but may have returned partial results,
+    /// `STATUS_SEVERITY_WARNING`
+    Warning = 0b10,
+    /// The operation failed, `STATUS_SEVERITY_ERROR`
+    Error = 0b11,
+}
+
+impl NtStatus {
+    /// The bit that marks a status as defined by a third party rather than
+    /// by Microsoft
+    const CUSTOMER_BIT: u32 = 1 << 29;
+    /// The largest facility that fits in the 12 bits of the facility field
+    pub const MAX_FACILITY: u16 = 0x0FFF;
+
+    /// Construct an [`NtStatus`] from a raw `NTSTATUS`
+    #[must_use]
+    pub const fn from_raw(status: NTSTATUS) -> Self {
+        Self(status)
+    }
+
+    /// Construct a driver-defined status, with the customer bit set so that it
+    /// cannot collide with a status defined by Microsoft
+    ///
+    /// This is a `const fn`, so a driver can declare its own statuses as
+    /// constants:
+    ///
+    /// ```ignore
+    /// const STATUS_SAMPLE_FIRMWARE_MISMATCH: NtStatus =
+    ///     NtStatus::custom(Severity::Error, 0x100, 0x0001);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `facility` is greater than [`NtStatus::MAX_FACILITY`].
+    #[must_use]
+    pub const fn custom(severity: Severity, facility: u16, code: u16) -> Self {
+        assert!(
+            facility <= Self::MAX_FACILITY,
+            "NTSTATUS facility should fit in 12 bits"
+        );
+        let bits =
+            (severity as u32) << 30 | Self::CUSTOMER_BIT | (facility as u32) << 16 | code as u32;
+        // The status is built from its bit pattern, not its value
+        #[allow(clippy::cast_possible_wrap)]
+        let status = bits as NTSTATUS;
+        Self(status)
+    }
+
+    /// Returns the raw `NTSTATUS` wrapped by this [`NtStatus`]
+    #[must_use]
+    pub const fn into_raw(self) -> NTSTATUS {
+        self.0
+    }
+
+    /// Returns the severity of the status
+    #[must_use]
+    pub const fn severity(self) -> Severity {
+        match self.bits() >> 30 {
+            0b00 => Severity::Success,
+            0b01 => Severity::Informational,
+            0b10 => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Returns whether the status is driver-defined, with the customer bit set
+    #[must_use]
+    pub const fn is_customer(self) -> bool {
+        self.bits() & Self::CUSTOMER_BIT != 0
+    }
+
+    /// Returns the facility of the status, such as `FACILITY_USB_ERROR_CODE`,
+    /// which identifies the component that defined it
+    #[must_use]
+    pub const fn facility(self) -> u16 {
+        // The facility is masked to 12 bits
+        #[allow(clippy::cast_possible_truncation)]
+        let facility = (self.bits() >> 16) as u16 & Self::MAX_FACILITY;
+        facility
+    }
+
+    /// Returns the code of the status, which is unique within its facility
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        // The code is the low 16 bits of the status
+        #[allow(clippy::cast_possible_truncation)]
+        let code = self.bits() as u16;
+        code
+    }
+
+    /// Returns whether the status is a success or informational status, like
+    /// `NT_SUCCESS`
+    #[must_use]
+    pub const fn is_success(self) -> bool {
+        self.0 >= 0
+    }
+
+    /// Returns whether the status has the informational severity, like
+    /// `NT_INFORMATION`
+    #[must_use]
+    pub const fn is_information(self) -> bool {
+        matches!(self.severity(), Severity::Informational)
+    }
+
+    /// Returns whether the status has the warning severity, like `NT_WARNING`
+    ///
+    /// Warnings, such as `STATUS_BUFFER_OVERFLOW`, are not successes, but
+    /// may still have returned partial results.
+    #[must_use]
+    pub const fn is_warning(self) -> bool {
+        matches!(self.severity(), Severity::Warning)
+    }
+
+    /// Returns whether the status has the error severity, like `NT_ERROR`
+    #[must_use]
+    pub const fn is_error(self) -> bool {
+        matches!(self.severity(), Severity::Error)
+    }
+
+    /// Returns `Ok(())` if the status is a success, or the status as the error
+    /// otherwise
+    ///
+    /// # Errors
+    ///
+    /// This function will return the status itself if it is not a success.
+    pub const fn ok(self) -> Result<(), Self> {
+        if self.is_success() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns the bit pattern of the status
+    const fn bits(self) -> u32 {
+        // The fields of the status are read from its bit pattern, not its value
+        #[allow(clippy::cast_sign_loss)]
+        let bits = self.0 as u32;
+        bits
+    }
+}
+
+/// The Win32 error codes that statuses are converted to
+mod win32 {
+    use wdk_sys::ULONG;
+
+    pub const ERROR_SUCCESS: ULONG = 0;
+    pub const ERROR_INVALID_FUNCTION: ULONG = 1;
+    pub const ERROR_FILE_NOT_FOUND: ULONG = 2;
+    pub const ERROR_PATH_NOT_FOUND: ULONG = 3;
+    pub const ERROR_ACCESS_DENIED: ULONG = 5;
+    pub const ERROR_INVALID_HANDLE: ULONG = 6;
+    pub const ERROR_NOT_ENOUGH_MEMORY: ULONG = 8;
+    pub const ERROR_NOT_READY: ULONG = 21;
+    pub const ERROR_BAD_COMMAND: ULONG = 22;
+    pub const ERROR_BAD_LENGTH: ULONG = 24;
+    pub const ERROR_GEN_FAILURE: ULONG = 31;
+    pub const ERROR_SHARING_VIOLATION: ULONG = 32;
+    pub const ERROR_HANDLE_EOF: ULONG = 38;
+    pub const ERROR_NOT_SUPPORTED: ULONG = 50;
+    pub const ERROR_DEV_NOT_EXIST: ULONG = 55;
+    pub const ERROR_INVALID_PARAMETER: ULONG = 87;
+    pub const ERROR_DISK_FULL: ULONG = 112;
+    pub const ERROR_SEM_TIMEOUT: ULONG = 121;
+    pub const ERROR_INSUFFICIENT_BUFFER: ULONG = 122;
+    pub const ERROR_BUSY: ULONG = 170;
+    pub const ERROR_ALREADY_EXISTS: ULONG = 183;
+    pub const ERROR_MORE_DATA: ULONG = 234;
+    pub const WAIT_TIMEOUT: ULONG = 258;
+    pub const ERROR_NO_MORE_ITEMS: ULONG = 259;
+    pub const ERROR_MR_MID_NOT_FOUND: ULONG = 317;
+    pub const ERROR_ARITHMETIC_OVERFLOW: ULONG = 534;
+    pub const ERROR_OPERATION_ABORTED: ULONG = 995;
+    pub const ERROR_IO_PENDING: ULONG = 997;
+    pub const ERROR_NOACCESS: ULONG = 998;
+    pub const ERROR_NOT_FOUND: ULONG = 1168;
+    pub const ERROR_PRIVILEGE_NOT_HELD: ULONG = 1314;
+    pub const ERROR_NO_SYSTEM_RESOURCES: ULONG = 1450;
+    pub const ERROR_DEVICE_REMOVED: ULONG = 1617;
+    pub const ERROR_INVALID_USER_BUFFER: ULONG = 1784;
+}
+
+/// Conversions to and from the error codes used by user mode
+impl NtStatus {
+    /// The facility of statuses that wrap a Win32 error code,
+    /// `FACILITY_NTWIN32`
+    pub const FACILITY_NTWIN32: u16 = 0x7;
+    /// The bit that marks an `HRESULT` as wrapping an `NTSTATUS`,
+    /// `FACILITY_NT_BIT`
+    const HRESULT_FACILITY_NT_BIT: u32 = 0x1000_0000;
+    /// The Win32 error code that [`NtStatus::to_win32_error()`] returns for
+    /// statuses without a corresponding error, `ERROR_MR_MID_NOT_FOUND`
+    pub const WIN32_ERROR_UNKNOWN: ULONG = win32::ERROR_MR_MID_NOT_FOUND;
+
+    /// Returns the `HRESULT` that wraps the status, like `HRESULT_FROM_NT`
+    ///
+    /// The status can be recovered from the `HRESULT` with
+    /// [`NtStatus::from_hresult()`].
+    #[must_use]
+    pub const fn to_hresult(self) -> HRESULT {
+        // The `HRESULT` is built from the bit pattern of the status, not its value
+        #[allow(clippy::cast_possible_wrap)]
+        let hresult = (self.bits() | Self::HRESULT_FACILITY_NT_BIT) as HRESULT;
+        hresult
+    }
+
+    /// Returns the status wrapped by `hresult`, if it was built by
+    /// `HRESULT_FROM_NT` or [`NtStatus::to_hresult()`]
+    #[must_use]
+    pub const fn from_hresult(hresult: HRESULT) -> Option<Self> {
+        // The status is recovered from the bit pattern of the `HRESULT`, not its
+        // value
+        #[allow(clippy::cast_sign_loss)]
+        let bits = hresult as u32;
+        if bits & Self::HRESULT_FACILITY_NT_BIT == 0 {
+            return None;
+        }
+        #[allow(clippy::cast_possible_wrap)]
+        let status = (bits & !Self::HRESULT_FACILITY_NT_BIT) as NTSTATUS;
+        Some(Self(status))
+    }
+
+    /// Returns the status that wraps the Win32 error code `error`, like
+    /// `NTSTATUS_FROM_WIN32`
+    ///
+    /// `ERROR_SUCCESS` (0) is converted to [`NtStatus::STATUS_SUCCESS`].
+    #[must_use]
+    pub const fn from_win32_error(error: ULONG) -> Self {
+        if error == 0 {
+            return Self::STATUS_SUCCESS;
+        }
+        // Win32 error codes are 16 bits wide
+        #[allow(clippy::cast_possible_truncation)]
+        let code = error as u16;
+        let bits =
+            (Severity::Error as u32) << 30 | (Self::FACILITY_NTWIN32 as u32) << 16 | code as u32;
+        #[allow(clippy::cast_possible_wrap)]
+        let status = bits as NTSTATUS;
+        Self(status)
+    }
+
+    /// Returns the Win32 error code that corresponds to the status, using the
+    /// same table as `RtlNtStatusToDosError`
+    ///
+    /// This calls `RtlNtStatusToDosErrorNoTeb`, which, unlike
+    /// `RtlNtStatusToDosError`, does not record the status in the current
+    /// thread's `TEB`, so it can also be called from system threads. Statuses
+    /// without a corresponding error are converted to
+    /// [`NtStatus::WIN32_ERROR_UNKNOWN`].
+    #[must_use]
+    pub fn to_win32_error(self) -> ULONG {
+        let error;
+        // SAFETY: `RtlNtStatusToDosErrorNoTeb` accepts any status, and does not access
+        // the current thread's `TEB`.
+        unsafe {
+            error = RtlNtStatusToDosErrorNoTeb(self.0);
+        }
+        error
+    }
+
+    /// Returns an approximation of the Win32 error code that
+    /// [`NtStatus::to_win32_error()`] returns, in a `const fn`
+    ///
+    /// Successes are converted to `ERROR_SUCCESS` (0), statuses built by
+    /// [`NtStatus::from_win32_error()`] to the error they wrap, and the named
+    /// [`NtStatus`] constants to the same error as `RtlNtStatusToDosError`.
+    /// Every other status is converted to [`NtStatus::WIN32_ERROR_UNKNOWN`],
+    /// which is where this differs from [`NtStatus::to_win32_error()`]. This
+    /// is meant for code that is shared with user mode, or needs the error at
+    /// compile time.
+    #[must_use]
+    pub const fn to_win32_error_approximate(self) -> ULONG {
+        if !self.is_customer() && self.facility() == Self::FACILITY_NTWIN32 {
+            return self.code() as ULONG;
+        }
+        match self {
+            Self::STATUS_PENDING => win32::ERROR_IO_PENDING,
+            Self::STATUS_TIMEOUT => win32::WAIT_TIMEOUT,
+            Self::STATUS_MORE_ENTRIES | Self::STATUS_BUFFER_OVERFLOW => win32::ERROR_MORE_DATA,
+            Self::STATUS_NO_MORE_ENTRIES => win32::ERROR_NO_MORE_ITEMS,
+            Self::STATUS_DEVICE_BUSY => win32::ERROR_BUSY,
+            Self::STATUS_UNSUCCESSFUL => win32::ERROR_GEN_FAILURE,
+            Self::STATUS_NOT_IMPLEMENTED | Self::STATUS_INVALID_DEVICE_REQUEST => {
+                win32::ERROR_INVALID_FUNCTION
+            }
+            Self::STATUS_INVALID_HANDLE => win32::ERROR_INVALID_HANDLE,
+            Self::STATUS_INVALID_PARAMETER => win32::ERROR_INVALID_PARAMETER,
+            Self::STATUS_NO_SUCH_DEVICE
+            | Self::STATUS_NO_SUCH_FILE
+            | Self::STATUS_OBJECT_NAME_NOT_FOUND => win32::ERROR_FILE_NOT_FOUND,
+            Self::STATUS_END_OF_FILE => win32::ERROR_HANDLE_EOF,
+            Self::STATUS_NO_MEMORY => win32::ERROR_NOT_ENOUGH_MEMORY,
+            Self::STATUS_ACCESS_VIOLATION => win32::ERROR_NOACCESS,
+            Self::STATUS_INFO_LENGTH_MISMATCH => win32::ERROR_BAD_LENGTH,
+            Self::STATUS_ACCESS_DENIED | Self::STATUS_DELETE_PENDING => win32::ERROR_ACCESS_DENIED,
+            Self::STATUS_BUFFER_TOO_SMALL => win32::ERROR_INSUFFICIENT_BUFFER,
+            Self::STATUS_OBJECT_NAME_COLLISION => win32::ERROR_ALREADY_EXISTS,
+            Self::STATUS_OBJECT_PATH_NOT_FOUND => win32::ERROR_PATH_NOT_FOUND,
+            Self::STATUS_SHARING_VIOLATION => win32::ERROR_SHARING_VIOLATION,
+            Self::STATUS_PRIVILEGE_NOT_HELD => win32::ERROR_PRIVILEGE_NOT_HELD,
+            Self::STATUS_INSUFFICIENT_RESOURCES => win32::ERROR_NO_SYSTEM_RESOURCES,
+            Self::STATUS_DEVICE_NOT_READY => win32::ERROR_NOT_READY,
+            Self::STATUS_INTEGER_OVERFLOW => win32::ERROR_ARITHMETIC_OVERFLOW,
+            Self::STATUS_DISK_FULL => win32::ERROR_DISK_FULL,
+            Self::STATUS_NOT_SUPPORTED => win32::ERROR_NOT_SUPPORTED,
+            Self::STATUS_DEVICE_DOES_NOT_EXIST => win32::ERROR_DEV_NOT_EXIST,
+            Self::STATUS_IO_TIMEOUT => win32::ERROR_SEM_TIMEOUT,
+            Self::STATUS_CANCELLED => win32::ERROR_OPERATION_ABORTED,
+            Self::STATUS_INVALID_USER_BUFFER => win32::ERROR_INVALID_USER_BUFFER,
+            Self::STATUS_INVALID_DEVICE_STATE => win32::ERROR_BAD_COMMAND,
+            Self::STATUS_NOT_FOUND => win32::ERROR_NOT_FOUND,
+            Self::STATUS_DEVICE_REMOVED => win32::ERROR_DEVICE_REMOVED,
+            status if status.is_success() => win32::ERROR_SUCCESS,
+            _ => Self::WIN32_ERROR_UNKNOWN,
+        }
+    }
+}
+
+impl From<NTSTATUS> for NtStatus {
+    fn from(status: NTSTATUS) -> Self {
+        Self(status)
+    }
+}
+
+impl From<NtStatus> for NTSTATUS {
+    fn from(status: NtStatus) -> Self {
+        status.0
+    }
+}
+
+impl PartialEq<NTSTATUS> for NtStatus {
+    fn eq(&self, other: &NTSTATUS) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<NtStatus> for NTSTATUS {
+    fn eq(&self, other: &NtStatus) -> bool {
+        *self == other.0
+    }
+}
+
+impl fmt::Display for NtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{:#010X}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for NtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{name} ({:#010X})", self.0),
+            None => write!(f, "NtStatus({:#010X})", self.0),
+        }
+    }
+}
+
+impl core::error::Error for NtStatus {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_packs_fields() {
+        let cases = [
+            (Severity::Success, 0x000, 0x0000, 0x2000_0000_u32),
+            (Severity::Informational, 0x001, 0x0001, 0x6001_0001),
+            (Severity::Warning, 0x100, 0x1234, 0xA100_1234),
+            (Severity::Error, 0x100, 0x0001, 0xE100_0001),
+            (Severity::Error, NtStatus::MAX_FACILITY, 0xFFFF, 0xEFFF_FFFF),
+        ];
+        for (severity, facility, code, bits) in cases {
+            let status = NtStatus::custom(severity, facility, code);
+            assert_eq!(status.into_raw().cast_unsigned(), bits);
+            assert_eq!(status.severity(), severity);
+            assert_eq!(status.facility(), facility);
+            assert_eq!(status.code(), code);
+            assert!(status.is_customer());
+        }
+    }
+
+    #[test]
+    fn fields_of_microsoft_statuses() {
+        let cases = [
+            (NtStatus::STATUS_SUCCESS, Severity::Success, 0x000, 0x0000),
+            (NtStatus::STATUS_PENDING, Severity::Success, 0x000, 0x0103),
+            (
+                NtStatus::STATUS_BUFFER_OVERFLOW,
+                Severity::Warning,
+                0x000,
+                0x0005,
+            ),
+            (
+                NtStatus::STATUS_INVALID_PARAMETER,
+                Severity::Error,
+                0x000,
+                0x000D,
+            ),
+        ];
+        for (status, severity, facility, code) in cases {
+            assert_eq!(status.severity(), severity);
+            assert_eq!(status.facility(), facility);
+            assert_eq!(status.code(), code);
+            assert!(!status.is_customer());
+        }
+    }
+
+    #[test]
+    fn severity_predicates() {
+        let success = NtStatus::custom(Severity::Success, 0x100, 1);
+        let information = NtStatus::custom(Severity::Informational, 0x100, 1);
+        let warning = NtStatus::custom(Severity::Warning, 0x100, 1);
+        let error = NtStatus::custom(Severity::Error, 0x100, 1);
+
+        assert!(success.is_success() && success.ok().is_ok());
+        assert!(information.is_success() && information.is_information());
+        assert!(!warning.is_success() && warning.is_warning());
+        assert!(!error.is_success() && error.is_error());
+        assert_eq!(error.ok(), Err(error));
+    }
+
+    #[test]
+    fn to_hresult_sets_facility_nt_bit() {
+        let cases = [
+            (NtStatus::STATUS_SUCCESS, 0x1000_0000_u32),
+            (NtStatus::STATUS_PENDING, 0x1000_0103),
+            (NtStatus::STATUS_BUFFER_OVERFLOW, 0x9000_0005),
+            (NtStatus::STATUS_INVALID_PARAMETER, 0xD000_000D),
+            (NtStatus::custom(Severity::Error, 0x100, 1), 0xF100_0001),
+        ];
+        for (status, hresult) in cases {
+            assert_eq!(status.to_hresult().cast_unsigned(), hresult);
+            assert_eq!(NtStatus::from_hresult(status.to_hresult()), Some(status));
+        }
+    }
+
+    #[test]
+    fn from_hresult_rejects_hresults_without_facility_nt_bit() {
+        // `S_OK` and `E_FAIL`
+        for hresult in [0x0000_0000_u32, 0x8000_4005] {
+            assert_eq!(NtStatus::from_hresult(hresult.cast_signed()), None);
+        }
+    }
+
+    #[test]
+    fn from_win32_error_of_error_success_is_status_success() {
+        assert_eq!(
+            NtStatus::from_win32_error(win32::ERROR_SUCCESS),
+            NtStatus::STATUS_SUCCESS
+        );
+    }
+
+    #[test]
+    fn from_win32_error_wraps_error_in_facility_ntwin32() {
+        let cases = [
+            (win32::ERROR_ACCESS_DENIED, 0xC007_0005_u32),
+            (win32::ERROR_INVALID_PARAMETER, 0xC007_0057),
+            (win32::ERROR_NOT_FOUND, 0xC007_0490),
+        ];
+        for (error, bits) in cases {
+            let status = NtStatus::from_win32_error(error);
+            assert_eq!(status.into_raw().cast_unsigned(), bits);
+            assert!(status.is_error());
+            assert!(!status.is_customer());
+            assert_eq!(status.facility(), NtStatus::FACILITY_NTWIN32);
+            assert_eq!(status.to_win32_error_approximate(), error);
+        }
+    }
+
+    #[test]
+    fn to_win32_error_approximate_of_named_statuses() {
+        let cases = [
+            (NtStatus::STATUS_SUCCESS, win32::ERROR_SUCCESS),
+            (NtStatus::STATUS_PENDING, win32::ERROR_IO_PENDING),
+            (NtStatus::STATUS_BUFFER_OVERFLOW, win32::ERROR_MORE_DATA),
+            (
+                NtStatus::STATUS_INVALID_PARAMETER,
+                win32::ERROR_INVALID_PARAMETER,
+            ),
+            (
+                NtStatus::STATUS_OBJECT_NAME_NOT_FOUND,
+                win32::ERROR_FILE_NOT_FOUND,
+            ),
+        ];
+        for (status, error) in cases {
+            assert_eq!(status.to_win32_error_approximate(), error);
+        }
+    }
+
+    #[test]
+    fn to_win32_error_approximate_falls_back_to_unknown() {
+        // Successes without a named error are still converted to `ERROR_SUCCESS`
+        assert_eq!(
+            NtStatus::custom(Severity::Informational, 0x100, 1).to_win32_error_approximate(),
+            win32::ERROR_SUCCESS
+        );
+        // Other statuses, including driver-defined ones in `FACILITY_NTWIN32`, are
+        // unknown
+        for status in [
+            NtStatus::custom(Severity::Error, 0x100, 1),
+            NtStatus::custom(Severity::Error, NtStatus::FACILITY_NTWIN32, 5),
+            NtStatus::from_raw(0xC000_0999_u32.cast_signed()),
+        ] {
+            assert_eq!(
+                status.to_win32_error_approximate(),
+                NtStatus::WIN32_ERROR_UNKNOWN
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic = "NTSTATUS facility should fit in 12 bits"]
+    fn custom_rejects_facility_over_12_bits() {
+        let _ = NtStatus::custom(Severity::Error, NtStatus::MAX_FACILITY + 1, 0);
+    }
+}
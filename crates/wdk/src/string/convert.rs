@@ -0,0 +1,158 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::char::decode_utf16;
+
+use wdk_sys::{NTSTATUS, STATUS_BUFFER_TOO_SMALL, STATUS_NO_UNICODE_TRANSLATION};
+
+/// Returns the length of `string` encoded as UTF-16, in code units
+#[must_use]
+pub fn utf16_len(string: &str) -> usize {
+    string.chars().map(char::len_utf16).sum()
+}
+
+/// Returns the length of `string` decoded from UTF-16 and encoded as UTF-8,
+/// in bytes
+///
+/// # Errors
+///
+/// This function will return an error if `string` contains an unpaired
+/// surrogate, in which case the error variant will contain
+/// `STATUS_NO_UNICODE_TRANSLATION`. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub fn utf8_len(string: &[u16]) -> Result<usize, NTSTATUS> {
+    decode_utf16(string.iter().copied()).try_fold(0, |length, character| {
+        character
+            .map(|character| length + character.len_utf8())
+            .map_err(|_| STATUS_NO_UNICODE_TRANSLATION)
+    })
+}
+
+/// Encode `string` as UTF-16 into the start of `destination`, returning how
+/// many code units were written
+///
+/// `destination` is left unchanged if this fails. Use [`utf16_len()`] to size
+/// it.
+///
+/// # Errors
+///
+/// This function will return an error if `destination` is too small to hold
+/// the encoded string, in which case the error variant will contain
+/// `STATUS_BUFFER_TOO_SMALL`. The error variant will contain a [`NTSTATUS`] of
+/// the failure.
+pub fn utf8_to_utf16(string: &str, destination: &mut [u16]) -> Result<usize, NTSTATUS> {
+    let length = utf16_len(string);
+    let destination = destination
+        .get_mut(..length)
+        .ok_or(STATUS_BUFFER_TOO_SMALL)?;
+    for (code_unit, destination) in string.encode_utf16().zip(destination) {
+        *destination = code_unit;
+    }
+    Ok(length)
+}
+
+/// Decode `string` from UTF-16 and encode it as UTF-8 into the start of
+/// `destination`, returning the [`str`] that was written
+///
+/// The contents of `destination` are unspecified if this fails. Use
+/// [`utf8_len()`] to size it.
+///
+/// # Errors
+///
+/// This function will return an error if `string` contains an unpaired
+/// surrogate, in which case the error variant will contain
+/// `STATUS_NO_UNICODE_TRANSLATION`, or if `destination` is too small to hold
+/// the encoded string, in which case the error variant will contain
+/// `STATUS_BUFFER_TOO_SMALL`. The error variant will contain a [`NTSTATUS`] of
+/// the failure.
+///
+/// # Panics
+///
+/// Panics if the bytes encoded from the decoded characters are not valid
+/// UTF-8, which they always are.
+pub fn utf16_to_utf8<'a>(string: &[u16], destination: &'a mut [u8]) -> Result<&'a str, NTSTATUS> {
+    let mut length = 0;
+    for character in decode_utf16(string.iter().copied()) {
+        let character = character.map_err(|_| STATUS_NO_UNICODE_TRANSLATION)?;
+        let end = length + character.len_utf8();
+        let encoded = destination
+            .get_mut(length..end)
+            .ok_or(STATUS_BUFFER_TOO_SMALL)?;
+        character.encode_utf8(encoded);
+        length = end;
+    }
+    Ok(core::str::from_utf8(&destination[..length])
+        .expect("bytes encoded from chars should be valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "a€😀": an ASCII character, a character in the Basic Multilingual Plane,
+    /// and a character encoded as a surrogate pair
+    const STRING: &str = "a\u{20AC}\u{1F600}";
+    const UTF16: [u16; 4] = [0x0061, 0x20AC, 0xD83D, 0xDE00];
+
+    #[test]
+    fn utf8_to_utf16_encodes_surrogate_pairs() {
+        let mut destination = [0; 8];
+        assert_eq!(utf16_len(STRING), UTF16.len());
+        assert_eq!(utf8_to_utf16(STRING, &mut destination), Ok(UTF16.len()));
+        assert_eq!(destination[..UTF16.len()], UTF16);
+        assert_eq!(destination[UTF16.len()..], [0; 4]);
+    }
+
+    #[test]
+    fn utf8_to_utf16_of_empty_string() {
+        let mut destination = [];
+        assert_eq!(utf8_to_utf16("", &mut destination), Ok(0));
+    }
+
+    #[test]
+    fn utf8_to_utf16_into_too_small_buffer_leaves_it_unchanged() {
+        // One code unit short, which would split the surrogate pair
+        let mut destination = [0xFFFF; 3];
+        assert_eq!(
+            utf8_to_utf16(STRING, &mut destination),
+            Err(STATUS_BUFFER_TOO_SMALL)
+        );
+        assert_eq!(destination, [0xFFFF; 3]);
+    }
+
+    #[test]
+    fn utf16_to_utf8_decodes_surrogate_pairs() {
+        let mut destination = [0; 16];
+        assert_eq!(utf8_len(&UTF16), Ok(STRING.len()));
+        assert_eq!(utf16_to_utf8(&UTF16, &mut destination), Ok(STRING));
+    }
+
+    #[test]
+    fn utf16_to_utf8_rejects_lone_surrogates() {
+        let mut destination = [0; 16];
+        for string in [
+            &[0x0061, 0xD83D][..],
+            &[0xDE00, 0x0061],
+            &[0xD83D, 0x0061, 0xDE00],
+        ] {
+            assert_eq!(utf8_len(string), Err(STATUS_NO_UNICODE_TRANSLATION));
+            assert_eq!(
+                utf16_to_utf8(string, &mut destination),
+                Err(STATUS_NO_UNICODE_TRANSLATION)
+            );
+        }
+    }
+
+    #[test]
+    fn utf16_to_utf8_into_too_small_buffer_fails() {
+        // One byte short, which would split the last character
+        let mut destination = [0; STRING.len() - 1];
+        assert_eq!(
+            utf16_to_utf8(&UTF16, &mut destination),
+            Err(STATUS_BUFFER_TOO_SMALL)
+        );
+
+        let mut destination = [0; STRING.len()];
+        assert_eq!(utf16_to_utf8(&UTF16, &mut destination), Ok(STRING));
+    }
+}
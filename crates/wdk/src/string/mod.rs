@@ -0,0 +1,19 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over the counted UTF-16 strings used by NT APIs, and
+//! conversions between them and Rust strings
+
+mod convert;
+#[cfg(feature = "alloc")]
+mod owned;
+mod unicode_str;
+#[cfg(feature = "alloc")]
+mod unicode_string;
+
+pub use convert::*;
+#[cfg(feature = "alloc")]
+pub use owned::*;
+pub use unicode_str::*;
+#[cfg(feature = "alloc")]
+pub use unicode_string::*;
@@ -0,0 +1,70 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use core::char::{decode_utf16, REPLACEMENT_CHARACTER};
+
+use wdk_sys::{NTSTATUS, STATUS_INSUFFICIENT_RESOURCES};
+
+use super::{utf16_len, utf8_len};
+
+/// Try to encode `string` as UTF-16 into a new [`Vec`]
+///
+/// # Errors
+///
+/// This function will return an error if the [`Vec`] could not be allocated,
+/// in which case the error variant will contain
+/// `STATUS_INSUFFICIENT_RESOURCES`. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub fn str_to_utf16(string: &str) -> Result<Vec<u16>, NTSTATUS> {
+    let mut utf16 = Vec::new();
+    utf16
+        .try_reserve_exact(utf16_len(string))
+        .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+    utf16.extend(string.encode_utf16());
+    Ok(utf16)
+}
+
+/// Try to decode `string` from UTF-16 into a new [`String`]
+///
+/// # Errors
+///
+/// This function will return an error if `string` contains an unpaired
+/// surrogate, in which case the error variant will contain
+/// `STATUS_NO_UNICODE_TRANSLATION`, or if the [`String`] could not be
+/// allocated, in which case the error variant will contain
+/// `STATUS_INSUFFICIENT_RESOURCES`. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub fn utf16_to_string(string: &[u16]) -> Result<String, NTSTATUS> {
+    let mut utf8 = String::new();
+    utf8.try_reserve_exact(utf8_len(string)?)
+        .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+    utf8.extend(decode_utf16(string.iter().copied()).map_while(Result::ok));
+    Ok(utf8)
+}
+
+/// Try to decode `string` from UTF-16 into a new [`String`], replacing any
+/// unpaired surrogates with [`REPLACEMENT_CHARACTER`]
+///
+/// Registry values and file names are not guaranteed to be valid UTF-16, so
+/// this is the conversion to use for strings that are only displayed.
+///
+/// # Errors
+///
+/// This function will return an error if the [`String`] could not be
+/// allocated, in which case the error variant will contain
+/// `STATUS_INSUFFICIENT_RESOURCES`. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub fn utf16_to_string_lossy(string: &[u16]) -> Result<String, NTSTATUS> {
+    let characters = || {
+        decode_utf16(string.iter().copied())
+            .map(|character| character.unwrap_or(REPLACEMENT_CHARACTER))
+    };
+    let mut utf8 = String::new();
+    utf8.try_reserve_exact(characters().map(char::len_utf8).sum())
+        .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+    utf8.extend(characters());
+    Ok(utf8)
+}
@@ -0,0 +1,207 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    char::{decode_utf16, REPLACEMENT_CHARACTER},
+    fmt::{self, Write},
+    marker::PhantomData,
+};
+
+use wdk_sys::{NTSTATUS, PCUNICODE_STRING, STATUS_INVALID_PARAMETER, UNICODE_STRING};
+
+/// The maximum length of a `UNICODE_STRING`, in UTF-16 code units, since its
+/// `Length` is a `USHORT` count of bytes
+pub const MAX_UNICODE_STRING_LENGTH: usize = u16::MAX as usize / size_of::<u16>();
+
+/// A borrowed `UNICODE_STRING`.
+///
+/// The string is not null-terminated, and is not guaranteed to be valid
+/// UTF-16, since NT strings are not. `Length` is always an even number of
+/// bytes, no greater than `MaximumLength`, and `Buffer` always points to that
+/// many bytes, which are borrowed for `'a`. Since it is `repr(transparent)`, a
+/// pointer to an [`NtUnicodeStr`] can be passed wherever a `PCUNICODE_STRING`
+/// is expected.
+///
+/// ```ignore
+/// let name = NtUnicodeStr::try_from_utf16(&DEVICE_NAME)?;
+/// println!("opening {name}");
+/// ```
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct NtUnicodeStr<'a> {
+    raw: UNICODE_STRING,
+    _buffer: PhantomData<&'a [u16]>,
+}
+
+// SAFETY: `NtUnicodeStr` only gives shared access to the `[u16]` it borrows,
+// which is `Sync`.
+unsafe impl Send for NtUnicodeStr<'_> {}
+
+// SAFETY: See the `Send` implementation.
+unsafe impl Sync for NtUnicodeStr<'_> {}
+
+impl<'a> NtUnicodeStr<'a> {
+    /// Construct an empty [`NtUnicodeStr`]
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            raw: UNICODE_STRING {
+                Length: 0,
+                MaximumLength: 0,
+                Buffer: core::ptr::null_mut(),
+            },
+            _buffer: PhantomData,
+        }
+    }
+
+    /// Borrow `string` as an [`NtUnicodeStr`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `string` is longer than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units, in which case the error
+    /// variant will contain `STATUS_INVALID_PARAMETER`. The error variant will
+    /// contain a [`NTSTATUS`] of the failure.
+    pub const fn try_from_utf16(string: &'a [u16]) -> Result<Self, NTSTATUS> {
+        if string.len() > MAX_UNICODE_STRING_LENGTH {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        // `string.len()` is at most `MAX_UNICODE_STRING_LENGTH` code units, which is
+        // at most `u16::MAX` bytes
+        #[allow(clippy::cast_possible_truncation)]
+        let length = core::mem::size_of_val(string) as u16;
+        Ok(Self {
+            raw: UNICODE_STRING {
+                Length: length,
+                MaximumLength: length,
+                Buffer: string.as_ptr().cast_mut(),
+            },
+            _buffer: PhantomData,
+        })
+    }
+
+    /// Borrow the string described by `raw` as an [`NtUnicodeStr`]
+    ///
+    /// # Safety
+    ///
+    /// `raw.Length` must be an even number of bytes, no greater than
+    /// `raw.MaximumLength`, and, unless it is 0, `raw.Buffer` must point to
+    /// that many bytes, which are not mutated for `'a`.
+    #[must_use]
+    pub const unsafe fn from_raw(raw: UNICODE_STRING) -> Self {
+        Self {
+            raw,
+            _buffer: PhantomData,
+        }
+    }
+
+    /// Borrow the string described by the `UNICODE_STRING` at `raw` as an
+    /// [`NtUnicodeStr`]
+    ///
+    /// # Safety
+    ///
+    /// `raw` must satisfy the requirements of [`NtUnicodeStr::from_raw()`],
+    /// and must itself not be mutated for `'a`.
+    #[must_use]
+    pub const unsafe fn from_raw_ref(raw: &'a UNICODE_STRING) -> &'a Self {
+        // SAFETY: `NtUnicodeStr` is a `repr(transparent)` wrapper of `UNICODE_STRING`,
+        // and the caller guarantees that `raw` describes a valid string.
+        unsafe { &*core::ptr::from_ref(raw).cast::<Self>() }
+    }
+
+    /// Returns the `UNICODE_STRING` describing the string
+    #[must_use]
+    pub const fn as_raw(&self) -> &UNICODE_STRING {
+        &self.raw
+    }
+
+    /// Returns a pointer to the `UNICODE_STRING` describing the string, to
+    /// pass to NT APIs
+    #[must_use]
+    pub const fn as_ptr(&self) -> PCUNICODE_STRING {
+        &self.raw
+    }
+
+    /// Returns the UTF-16 code units of the string
+    #[must_use]
+    pub const fn as_slice(&self) -> &'a [u16] {
+        if self.raw.Length == 0 {
+            return &[];
+        }
+        // SAFETY: The invariants of `NtUnicodeStr` guarantee that `Buffer` points to
+        // `Length` bytes, borrowed for `'a`.
+        unsafe { core::slice::from_raw_parts(self.raw.Buffer, self.len()) }
+    }
+
+    /// Returns the length of the string, in UTF-16 code units
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.raw.Length as usize / size_of::<u16>()
+    }
+
+    /// Returns whether the string is empty
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.raw.Length == 0
+    }
+
+    /// Returns an iterator over the characters of the string, with any
+    /// unpaired surrogates replaced by [`REPLACEMENT_CHARACTER`]
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        decode_utf16(self.as_slice().iter().copied())
+            .map(|character| character.unwrap_or(REPLACEMENT_CHARACTER))
+    }
+}
+
+impl Default for NtUnicodeStr<'_> {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<'a> TryFrom<&'a [u16]> for NtUnicodeStr<'a> {
+    type Error = NTSTATUS;
+
+    fn try_from(string: &'a [u16]) -> Result<Self, Self::Error> {
+        Self::try_from_utf16(string)
+    }
+}
+
+impl fmt::Display for NtUnicodeStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars()
+            .try_for_each(|character| f.write_char(character))
+    }
+}
+
+impl fmt::Debug for NtUnicodeStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for character in self.chars() {
+            for escaped in character.escape_debug() {
+                f.write_char(escaped)?;
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+impl PartialEq for NtUnicodeStr<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for NtUnicodeStr<'_> {}
+
+impl PartialEq<str> for NtUnicodeStr<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_slice().iter().copied().eq(other.encode_utf16())
+    }
+}
+
+impl PartialEq<&str> for NtUnicodeStr<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use wdk_sys::{NTSTATUS, STATUS_BUFFER_OVERFLOW, STATUS_INSUFFICIENT_RESOURCES, UNICODE_STRING};
+
+use super::{NtUnicodeStr, MAX_UNICODE_STRING_LENGTH};
+
+/// An owned, growable `UNICODE_STRING`.
+///
+/// The string is stored in a pool allocation that grows as it is appended
+/// to, up to [`MAX_UNICODE_STRING_LENGTH`] code units, and is borrowed as an
+/// [`NtUnicodeStr`] to pass it to NT APIs. Every operation that would break
+/// the invariants of `UNICODE_STRING`, by growing it past what its `USHORT`
+/// fields can describe, returns an error instead.
+///
+/// ```ignore
+/// let mut path = NtUnicodeString::try_from_str(r"\Registry\Machine\")?;
+/// path.push_str(key)?;
+/// open_key(path.as_unicode_str())?;
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct NtUnicodeString {
+    buffer: Vec<u16>,
+}
+
+impl NtUnicodeString {
+    /// Construct an empty [`NtUnicodeString`], without allocating
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { buffer: Vec::new() }
+    }
+
+    /// Try to construct an empty [`NtUnicodeString`] with room for `capacity`
+    /// UTF-16 code units
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `capacity` is more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units, in which case the error
+    /// variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer could
+    /// not be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn with_capacity(capacity: usize) -> Result<Self, NTSTATUS> {
+        let mut string = Self::new();
+        string.reserve(capacity)?;
+        Ok(string)
+    }
+
+    /// Try to encode `string` as an [`NtUnicodeString`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `string` is more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] UTF-16 code units long, in which case the
+    /// error variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer
+    /// could not be allocated. The error variant will contain a [`NTSTATUS`] of
+    /// the failure.
+    pub fn try_from_str(string: &str) -> Result<Self, NTSTATUS> {
+        let mut unicode_string = Self::new();
+        unicode_string.push_str(string)?;
+        Ok(unicode_string)
+    }
+
+    /// Try to copy the UTF-16 code units of `string` into an
+    /// [`NtUnicodeString`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `string` is more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units long, in which case the error
+    /// variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer could
+    /// not be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn try_from_utf16(string: &[u16]) -> Result<Self, NTSTATUS> {
+        let mut unicode_string = Self::new();
+        unicode_string.push_utf16(string)?;
+        Ok(unicode_string)
+    }
+
+    /// Returns the string as an [`NtUnicodeStr`], to pass it to NT APIs
+    ///
+    /// # Panics
+    ///
+    /// Panics if the string is longer than [`MAX_UNICODE_STRING_LENGTH`] code
+    /// units, which it never is.
+    #[must_use]
+    pub fn as_unicode_str(&self) -> NtUnicodeStr<'_> {
+        NtUnicodeStr::try_from_utf16(&self.buffer)
+            .expect("NtUnicodeString should never be longer than MAX_UNICODE_STRING_LENGTH")
+    }
+
+    /// Returns the UTF-16 code units of the string
+    #[must_use]
+    pub fn as_slice(&self) -> &[u16] {
+        &self.buffer
+    }
+
+    /// Returns the length of the string, in UTF-16 code units
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns whether the string is empty
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns how many UTF-16 code units the string can hold without
+    /// reallocating
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity().min(MAX_UNICODE_STRING_LENGTH)
+    }
+
+    /// Try to make room for at least `additional` more UTF-16 code units
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string would be more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units long, in which case the error
+    /// variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer could
+    /// not be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), NTSTATUS> {
+        if self
+            .buffer
+            .len()
+            .checked_add(additional)
+            .is_none_or(|length| length > MAX_UNICODE_STRING_LENGTH)
+        {
+            return Err(STATUS_BUFFER_OVERFLOW);
+        }
+        self.buffer
+            .try_reserve(additional)
+            .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)
+    }
+
+    /// Try to append `string`, encoded as UTF-16
+    ///
+    /// The string is left unchanged if this fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string would be more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units long, in which case the error
+    /// variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer could
+    /// not be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn push_str(&mut self, string: &str) -> Result<(), NTSTATUS> {
+        self.reserve(string.encode_utf16().count())?;
+        self.buffer.extend(string.encode_utf16());
+        Ok(())
+    }
+
+    /// Try to append the UTF-16 code units of `string`
+    ///
+    /// The string is left unchanged if this fails.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string would be more than
+    /// [`MAX_UNICODE_STRING_LENGTH`] code units long, in which case the error
+    /// variant will contain `STATUS_BUFFER_OVERFLOW`, or if the buffer could
+    /// not be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn push_utf16(&mut self, string: &[u16]) -> Result<(), NTSTATUS> {
+        self.reserve(string.len())?;
+        self.buffer.extend_from_slice(string);
+        Ok(())
+    }
+
+    /// Shorten the string to `length` UTF-16 code units, if it is longer
+    pub fn truncate(&mut self, length: usize) {
+        self.buffer.truncate(length);
+    }
+
+    /// Remove the contents of the string, keeping its capacity
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Call `f` with a `UNICODE_STRING` describing the string and its
+    /// capacity, for NT APIs that write their result into a caller-allocated
+    /// `UNICODE_STRING`
+    ///
+    /// The `Length` that `f` leaves in the `UNICODE_STRING` becomes the length
+    /// of the string. Reserve enough capacity with
+    /// [`NtUnicodeString::reserve()`] before calling this.
+    ///
+    /// # Safety
+    ///
+    /// `f` may only write to the code units of the buffer within
+    /// `MaximumLength`, and must leave `Length` as an even number of bytes,
+    /// no greater than `MaximumLength`, that have all been written. It must not
+    /// change `Buffer` or `MaximumLength`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` leaves a `Length` greater than the `MaximumLength`.
+    pub unsafe fn with_raw_mut<R>(&mut self, f: impl FnOnce(&mut UNICODE_STRING) -> R) -> R {
+        // `len()` and `capacity()` are at most `MAX_UNICODE_STRING_LENGTH` code units,
+        // which is at most `u16::MAX` bytes
+        #[allow(clippy::cast_possible_truncation)]
+        let mut raw = UNICODE_STRING {
+            Length: (self.len() * size_of::<u16>()) as u16,
+            MaximumLength: (self.capacity() * size_of::<u16>()) as u16,
+            Buffer: self.buffer.as_mut_ptr(),
+        };
+        let result = f(&mut raw);
+
+        let length = usize::from(raw.Length) / size_of::<u16>();
+        assert!(
+            length <= self.capacity(),
+            "UNICODE_STRING Length should not be greater than its MaximumLength"
+        );
+        // SAFETY: `length` is within the capacity of the buffer, and the caller
+        // guarantees that `f` initialized the code units up to it.
+        unsafe {
+            self.buffer.set_len(length);
+        }
+        result
+    }
+}
+
+impl TryFrom<&str> for NtUnicodeString {
+    type Error = NTSTATUS;
+
+    fn try_from(string: &str) -> Result<Self, Self::Error> {
+        Self::try_from_str(string)
+    }
+}
+
+impl TryFrom<NtUnicodeStr<'_>> for NtUnicodeString {
+    type Error = NTSTATUS;
+
+    fn try_from(string: NtUnicodeStr<'_>) -> Result<Self, Self::Error> {
+        Self::try_from_utf16(string.as_slice())
+    }
+}
+
+impl fmt::Display for NtUnicodeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_unicode_str(), f)
+    }
+}
+
+impl fmt::Debug for NtUnicodeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_unicode_str(), f)
+    }
+}
+
+impl PartialEq<str> for NtUnicodeString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_unicode_str() == *other
+    }
+}
+
+impl PartialEq<&str> for NtUnicodeString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_unicode_str() == **other
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::time::Duration;
+
+use wdk_sys::{
+    ntddk::{KeClearEvent, KeInitializeEvent, KeReadStateEvent, KeSetEvent},
+    _EVENT_TYPE,
+    IO_NO_INCREMENT,
+    KEVENT,
+    KPRIORITY,
+    NTSTATUS,
+};
+
+use super::wait::{wait_for_single_object, WaitStatus};
+use crate::pool::NonPagedBox;
+
+// `IO_NO_INCREMENT` is generated as a `u32`, but is passed as a `KPRIORITY`
+#[allow(clippy::cast_possible_wrap)]
+const NO_INCREMENT: KPRIORITY = IO_NO_INCREMENT as KPRIORITY;
+
+/// The kind of a kernel [`Event`], which determines what happens to waiting
+/// threads when the event is set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventType {
+    /// A notification (manual-reset) event stays signaled until it is
+    /// explicitly cleared, and releases every waiting thread when it is set
+    Notification,
+    /// A synchronization (auto-reset) event releases a single waiting thread
+    /// when it is set, and is automatically cleared when that wait is
+    /// satisfied
+    Synchronization,
+}
+
+impl EventType {
+    const fn as_raw(self) -> _EVENT_TYPE::Type {
+        match self {
+            Self::Notification => _EVENT_TYPE::NotificationEvent,
+            Self::Synchronization => _EVENT_TYPE::SynchronizationEvent,
+        }
+    }
+}
+
+/// Kernel Event (`KEVENT`).
+///
+/// An [`Event`] is a synchronization object that threads can wait on until
+/// another thread sets it. Setting an event may be done at `IRQL` <=
+/// `DISPATCH_LEVEL`, so events are commonly used to signal a waiting thread
+/// from a completion routine or DPC. The behavior of waiting threads when the
+/// event is set is determined by its [`EventType`].
+///
+/// The underlying `KEVENT` is stored in non-paged pool, so it never moves
+/// while threads are waiting on it.
+pub struct Event {
+    kevent: NonPagedBox<KEVENT>,
+}
+
+impl Event {
+    /// Try to construct a kernel event of the given `event_type`, which is
+    /// initially signaled if `signaled` is `true`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `KEVENT` cannot be allocated.
+    /// The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn try_new(event_type: EventType, signaled: bool) -> Result<Self, NTSTATUS> {
+        let kevent = NonPagedBox::try_new(KEVENT::default())?;
+
+        // SAFETY: `kevent` points to non-paged memory that is large enough and properly
+        // aligned for a `KEVENT`, and it never moves for the lifetime of the `Event`.
+        unsafe {
+            KeInitializeEvent(kevent.as_ptr(), event_type.as_raw(), u8::from(signaled));
+        }
+
+        Ok(Self { kevent })
+    }
+
+    /// Set the event to the signaled state, satisfying waits on it
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn set(&self) {
+        // SAFETY: `kevent` is a private member of `Event` that was initialized in
+        // `try_new`. Passing `FALSE` for `Wait` means the call does not need to be
+        // immediately followed by a wait, so it can be made at `IRQL` <=
+        // `DISPATCH_LEVEL`.
+        unsafe {
+            KeSetEvent(self.kevent.as_ptr(), NO_INCREMENT, u8::from(false));
+        }
+    }
+
+    /// Set the event to the non-signaled state
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn clear(&self) {
+        // SAFETY: `kevent` is a private member of `Event` that was initialized in
+        // `try_new`.
+        unsafe {
+            KeClearEvent(self.kevent.as_ptr());
+        }
+    }
+
+    /// Returns `true` if the event is currently signaled
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn is_set(&self) -> bool {
+        let state;
+        // SAFETY: `kevent` is a private member of `Event` that was initialized in
+        // `try_new`.
+        unsafe {
+            state = KeReadStateEvent(self.kevent.as_ptr());
+        }
+        state != 0
+    }
+
+    /// Wait indefinitely until the event is signaled
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn wait(&self) {
+        // SAFETY: `kevent` is a private member of `Event` that was initialized in
+        // `try_new`, and cannot be freed while it is borrowed for the wait.
+        let wait_status = unsafe { wait_for_single_object(self.kevent.as_ptr().cast(), None) };
+        debug_assert_eq!(
+            wait_status,
+            WaitStatus::Signaled,
+            "an indefinite wait should only return once the event is signaled"
+        );
+    }
+
+    /// Wait until the event is signaled, or until `timeout` elapses
+    ///
+    /// A zero `timeout` tests the state of the event without waiting, which
+    /// may be done at `IRQL` <= `DISPATCH_LEVEL`. Any other `timeout` requires
+    /// `IRQL` <= `APC_LEVEL`. For synchronization events, a
+    /// [`WaitStatus::Signaled`] result means the event has been cleared again.
+    pub fn wait_timeout(&self, timeout: Duration) -> WaitStatus {
+        // SAFETY: `kevent` is a private member of `Event` that was initialized in
+        // `try_new`, and cannot be freed while it is borrowed for the wait.
+        unsafe { wait_for_single_object(self.kevent.as_ptr().cast(), Some(timeout)) }
+    }
+}
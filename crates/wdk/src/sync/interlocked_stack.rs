@@ -0,0 +1,214 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use wdk_sys::{
+    ntddk::{
+        ExQueryDepthSList,
+        ExpInterlockedPopEntrySList,
+        ExpInterlockedPushEntrySList,
+        InitializeSListHead,
+    },
+    NTSTATUS,
+    SLIST_ENTRY,
+    SLIST_HEADER,
+};
+
+use crate::pool::NonPagedBox;
+
+/// A lock-free, interlocked singly-linked stack (`SLIST_HEADER`).
+///
+/// An [`InterlockedStack`] can be pushed to and popped from at any `IRQL`,
+/// including from interrupt service routines, which makes it the standard way
+/// to pass work from ISRs and DPCs to worker threads. Because allocating
+/// memory is not possible at every `IRQL`, values are pushed inside
+/// [`InterlockedNode`]s, which are allocated ahead of time with
+/// [`InterlockedNode::try_new()`] and can be reused after they are popped.
+///
+/// The stack is last-in, first-out. `SLIST_HEADER` and `SLIST_ENTRY` require
+/// 16-byte alignment on 64-bit platforms; both are guaranteed by their
+/// `wdk-sys` bindings and by non-paged pool allocations.
+pub struct InterlockedStack<T> {
+    header: UnsafeCell<SLIST_HEADER>,
+    _marker: PhantomData<InterlockedNode<T>>,
+}
+
+// SAFETY: The `SLIST_HEADER` is only ever accessed through the kernel's
+// interlocked SList APIs. Values pushed on one thread can be popped on another,
+// so `T` must be `Send`.
+unsafe impl<T: Send> Send for InterlockedStack<T> {}
+
+// SAFETY: The kernel's interlocked SList APIs are safe to call concurrently on
+// the same `SLIST_HEADER` from multiple threads. The stack never gives out
+// references to the values it contains, so `T` only needs to be `Send`.
+unsafe impl<T: Send> Sync for InterlockedStack<T> {}
+
+impl<T> InterlockedStack<T> {
+    /// Construct an empty interlocked stack
+    #[must_use]
+    pub fn new() -> Self {
+        let stack = Self {
+            header: UnsafeCell::new(SLIST_HEADER::default()),
+            _marker: PhantomData,
+        };
+
+        // SAFETY: `header` is a valid, properly aligned pointer to an `SLIST_HEADER`.
+        // The header is not referenced by address by its entries, so it is safe to
+        // move the `InterlockedStack` after this.
+        unsafe {
+            InitializeSListHead(stack.header.get());
+        }
+        stack
+    }
+
+    /// Push `node` onto the top of the stack
+    ///
+    /// This may be called at any `IRQL`.
+    pub fn push(&self, node: InterlockedNode<T>) {
+        let entry = node.into_entry();
+
+        // SAFETY: `header` was initialized in `new`. `entry` is a properly aligned
+        // `SLIST_ENTRY` in non-paged pool, whose ownership is transferred to the stack.
+        unsafe {
+            ExpInterlockedPushEntrySList(self.header.get(), entry.as_ptr());
+        }
+    }
+
+    /// Pop the node on the top of the stack, or return [`None`] if the stack is
+    /// empty
+    ///
+    /// This may be called at any `IRQL`.
+    #[must_use]
+    pub fn pop(&self) -> Option<InterlockedNode<T>> {
+        let entry;
+        // SAFETY: `header` was initialized in `new`.
+        unsafe {
+            entry = ExpInterlockedPopEntrySList(self.header.get());
+        }
+
+        // SAFETY: Every entry in the stack was pushed by `push`, which transferred
+        // ownership of an `InterlockedNode` to the stack.
+        NonNull::new(entry).map(|entry| unsafe { InterlockedNode::from_entry(entry) })
+    }
+
+    /// Returns the number of nodes currently in the stack
+    ///
+    /// Since other threads may push or pop nodes at any time, the result may
+    /// already be out of date when it is returned. This may be called at any
+    /// `IRQL`.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        let depth;
+        // SAFETY: `header` was initialized in `new`.
+        unsafe {
+            depth = ExQueryDepthSList(self.header.get());
+        }
+        usize::from(depth)
+    }
+}
+
+impl<T> Default for InterlockedStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for InterlockedStack<T> {
+    /// Pops and frees every node remaining in the stack. This must be called
+    /// at `IRQL` <= `DISPATCH_LEVEL`.
+    fn drop(&mut self) {
+        while let Some(node) = self.pop() {
+            drop(node);
+        }
+    }
+}
+
+#[repr(C)]
+struct Node<T> {
+    // Must be the first field, so that a pointer to the entry is also a pointer
+    // to the `Node`
+    entry: SLIST_ENTRY,
+    value: T,
+}
+
+/// A value of type `T` in non-paged pool that can be pushed onto an
+/// [`InterlockedStack`].
+///
+/// The value can be accessed through the node's [`Deref`] and [`DerefMut`]
+/// implementations. Nodes must be allocated and freed at `IRQL` <=
+/// `DISPATCH_LEVEL`, but can be pushed and popped at any `IRQL`.
+pub struct InterlockedNode<T> {
+    node: NonPagedBox<Node<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> InterlockedNode<T> {
+    /// Try to allocate a node containing `value`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the node cannot be allocated.
+    /// The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn try_new(value: T) -> Result<Self, NTSTATUS> {
+        Ok(Self {
+            node: NonPagedBox::try_new(Node {
+                entry: SLIST_ENTRY::default(),
+                value,
+            })?,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Consumes the node, freeing it and returning the contained value
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.node.into_inner().value
+    }
+
+    fn into_entry(self) -> NonNull<SLIST_ENTRY> {
+        self.node.into_raw().cast()
+    }
+
+    /// # Safety
+    ///
+    /// `entry` must have been returned by [`InterlockedNode::into_entry()`] on
+    /// an `InterlockedNode<T>`, and must not be used to reconstruct more than
+    /// one node.
+    const unsafe fn from_entry(entry: NonNull<SLIST_ENTRY>) -> Self {
+        Self {
+            // SAFETY: The caller guarantees that `entry` is the first field of a
+            // `Node<T>` that was released from its `NonPagedBox`.
+            node: unsafe { NonPagedBox::from_raw(entry.cast()) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for InterlockedNode<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The node is owned by this `InterlockedNode`, and is not in any stack
+        // while it is, so the value is not accessed anywhere else.
+        unsafe { &(*self.node.as_ptr()).value }
+    }
+}
+
+impl<T> DerefMut for InterlockedNode<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The node is owned by this `InterlockedNode`, and is not in any stack
+        // while it is. The mutable borrow guarantees no other references to the value
+        // exist.
+        unsafe { &mut (*self.node.as_ptr()).value }
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over kernel synchronization primitives
+
+mod event;
+mod interlocked_stack;
+#[cfg(feature = "alloc")]
+mod per_cpu;
+mod push_lock;
+mod resource;
+mod rundown;
+mod semaphore;
+pub(crate) mod wait;
+mod waker;
+
+pub use event::*;
+pub use interlocked_stack::*;
+#[cfg(feature = "alloc")]
+pub use per_cpu::*;
+pub use push_lock::*;
+pub use resource::*;
+pub use rundown::*;
+pub use semaphore::*;
+pub use wait::WaitStatus;
+pub use waker::*;
+use wdk_sys::ntddk::{KeEnterCriticalRegion, KeLeaveCriticalRegion};
+
+/// Disable normal kernel APCs for the current thread. Every call must be
+/// balanced by a call to [`leave_critical_region`] on the same thread.
+///
+/// This must be called at `IRQL` <= `APC_LEVEL`.
+fn enter_critical_region() {
+    // SAFETY: `KeEnterCriticalRegion` can be called at `IRQL` <= `APC_LEVEL`, which
+    // is the same requirement as acquiring any of the locks in this module that
+    // require a critical region.
+    unsafe {
+        KeEnterCriticalRegion();
+    }
+}
+
+/// Re-enable normal kernel APCs for the current thread, balancing a previous
+/// call to [`enter_critical_region`]
+fn leave_critical_region() {
+    // SAFETY: This is only called to balance a previous call to
+    // `enter_critical_region` on the same thread.
+    unsafe {
+        KeLeaveCriticalRegion();
+    }
+}
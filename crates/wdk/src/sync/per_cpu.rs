@@ -0,0 +1,160 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+
+use wdk_sys::{
+    ntddk::{
+        KeGetCurrentProcessorNumberEx,
+        KeLowerIrql,
+        KeQueryMaximumProcessorCountEx,
+        KfRaiseIrql,
+    },
+    ALL_PROCESSOR_GROUPS,
+    NTSTATUS,
+    STATUS_INSUFFICIENT_RESOURCES,
+    USHORT,
+};
+
+use crate::{debug_assert_irql, irql::DISPATCH_LEVEL};
+
+// `wdk-sys` generates this as a `u32`, but it always fits in the `USHORT` that
+// kernel APIs take
+#[allow(clippy::cast_possible_truncation)]
+const ALL_GROUPS: USHORT = ALL_PROCESSOR_GROUPS as USHORT;
+
+// Keep each processor's slot on its own cache line, so that processors updating
+// their own slots do not contend with each other
+#[repr(align(64))]
+struct Slot<T>(T);
+
+/// A collection containing one `T` for every processor in the system.
+///
+/// [`PerCpu::with_current()`] gives access to the slot of the processor that
+/// the caller is running on, without any locking. This makes [`PerCpu`] well
+/// suited to hot-path counters and caches: each processor only ever touches its
+/// own slot, and the slots can be aggregated with [`PerCpu::iter()`] when
+/// needed. Since other processors can read a slot through [`PerCpu::iter()`]
+/// while it is in use, slots that are updated through a shared reference
+/// should use atomics (ex. [`AtomicU64`](core::sync::atomic::AtomicU64)).
+///
+/// A slot is allocated for the maximum number of processors the system can
+/// have, including processors that may be hot-added later.
+pub struct PerCpu<T> {
+    slots: Box<[Slot<T>]>,
+}
+
+// SAFETY: `with_current` only ever gives the processor that the caller is
+// running on, at `DISPATCH_LEVEL`, access to its own slot, so for a given slot
+// it is equivalent to sending `T` between the threads that run on the
+// processor. Access to other processors' slots with `iter` requires `T: Sync`.
+unsafe impl<T: Send> Sync for PerCpu<T> {}
+
+impl<T> PerCpu<T> {
+    /// Try to construct a per-processor collection, calling `init` to create
+    /// the initial value of each slot
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the slots cannot be allocated.
+    /// The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn try_new(mut init: impl FnMut() -> T) -> Result<Self, NTSTATUS> {
+        let processor_count;
+        // SAFETY: `KeQueryMaximumProcessorCountEx` can be called at any `IRQL`.
+        unsafe {
+            processor_count = KeQueryMaximumProcessorCountEx(ALL_GROUPS);
+        }
+        let processor_count =
+            usize::try_from(processor_count).map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+
+        let mut slots = Vec::new();
+        slots
+            .try_reserve_exact(processor_count)
+            .map_err(|_| STATUS_INSUFFICIENT_RESOURCES)?;
+        slots.extend((0..processor_count).map(|_| Slot(init())));
+
+        Ok(Self {
+            slots: slots.into_boxed_slice(),
+        })
+    }
+
+    /// Returns the number of slots, which is the maximum number of processors
+    /// the system can have
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns `true` if there are no slots. This is never the case for a
+    /// successfully constructed [`PerCpu`].
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Run `f` with access to the slot of the current processor
+    ///
+    /// `IRQL` is raised to `DISPATCH_LEVEL` for the duration of `f`, so that
+    /// the calling thread cannot be moved to another processor, and then
+    /// restored. `f` must not call any APIs that require a lower `IRQL`. This
+    /// must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn with_current<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        debug_assert_irql!(<= DISPATCH_LEVEL, "PerCpu::with_current must be called");
+
+        let previous_irql;
+        // SAFETY: The caller is at `IRQL` <= `DISPATCH_LEVEL`, so this raises (or
+        // keeps) the `IRQL`, and never lowers it.
+        unsafe {
+            previous_irql = KfRaiseIrql(DISPATCH_LEVEL);
+        }
+
+        let processor_index;
+        // SAFETY: `KeGetCurrentProcessorNumberEx` can be called at any `IRQL`, and a
+        // null `ProcNumber` is allowed.
+        unsafe {
+            processor_index = KeGetCurrentProcessorNumberEx(core::ptr::null_mut());
+        }
+        let result = f(&self.slots[processor_index as usize].0);
+
+        // SAFETY: `previous_irql` was returned by `KfRaiseIrql` above.
+        unsafe {
+            KeLowerIrql(previous_irql);
+        }
+        result
+    }
+
+    /// Returns an iterator over the slots of every processor
+    ///
+    /// This may be called at any `IRQL` that allows the values to be read.
+    pub fn iter(&self) -> impl Iterator<Item = &T>
+    where
+        T: Sync,
+    {
+        self.slots.iter().map(|slot| &slot.0)
+    }
+
+    /// Returns an iterator over mutable references to the slots of every
+    /// processor
+    ///
+    /// Since this call borrows the [`PerCpu`] mutably, no processor can be
+    /// accessing its slot at the same time.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().map(|slot| &mut slot.0)
+    }
+
+    /// Returns an iterator that takes the value out of the slot of every
+    /// processor, leaving [`T::default()`](Default::default) in its place
+    ///
+    /// This is typically used at `IRQL` = `PASSIVE_LEVEL` to aggregate and
+    /// reset per-processor statistics.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_
+    where
+        T: Default,
+    {
+        self.iter_mut().map(core::mem::take)
+    }
+}
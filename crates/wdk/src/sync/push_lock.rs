@@ -0,0 +1,228 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use wdk_sys::{
+    ntddk::{
+        ExAcquirePushLockExclusiveEx,
+        ExAcquirePushLockSharedEx,
+        ExInitializePushLock,
+        ExReleasePushLockExclusiveEx,
+        ExReleasePushLockSharedEx,
+    },
+    EX_DEFAULT_PUSH_LOCK_FLAGS,
+    ULONG_PTR,
+};
+
+use super::{enter_critical_region, leave_critical_region};
+
+/// Push lock (`EX_PUSH_LOCK`) reader-writer lock.
+///
+/// Push locks are lightweight reader-writer locks optimized for read-mostly
+/// data. They are cheaper to acquire than a [`Resource`](super::Resource) and
+/// do not need to be allocated or deleted, but do not support recursive
+/// acquisition or non-blocking acquisition attempts. Shared access is acquired
+/// with [`PushLock::acquire_shared()`], and exclusive access with
+/// [`PushLock::acquire_exclusive()`]. Both return guards that release the lock
+/// when they are dropped.
+///
+/// Push locks must be acquired at `IRQL` <= `APC_LEVEL`. Normal kernel APCs
+/// must be disabled while a push lock is held, so the guards returned by
+/// [`PushLock`] enter a critical region (via `KeEnterCriticalRegion`) before
+/// acquiring the lock, and leave it after releasing the lock.
+///
+/// A [`PushLock`] owns the data of type `T` that it protects, and the data is
+/// only accessible through the guards.
+pub struct PushLock<T = ()> {
+    push_lock: UnsafeCell<ULONG_PTR>,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: Push locks can be used from any thread, and the protected data is
+// only ever accessed by whoever holds the lock. Sending the `PushLock` to
+// another thread sends the owned `T` with it, so `T` must be `Send`.
+unsafe impl<T: Send> Send for PushLock<T> {}
+
+// SAFETY: Multiple threads can hold shared access at the same time, which gives
+// each of them a `&T`, so `T` must be `Sync`. Exclusive access gives a `&mut T`
+// to one thread at a time, so `T` must also be `Send`.
+unsafe impl<T: Send + Sync> Sync for PushLock<T> {}
+
+impl<T> PushLock<T> {
+    /// Construct a push lock protecting `data`
+    pub fn new(data: T) -> Self {
+        let push_lock = Self {
+            push_lock: UnsafeCell::new(0),
+            data: UnsafeCell::new(data),
+        };
+
+        // SAFETY: `push_lock` is a valid pointer to a `ULONG_PTR`. An initialized push
+        // lock that is not held has no waiters pointing into it, so it is safe to move
+        // the `PushLock` after this.
+        unsafe {
+            ExInitializePushLock(push_lock.push_lock.get());
+        }
+        push_lock
+    }
+
+    /// Acquire the push lock for shared (read) access, waiting until it is
+    /// available
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the PushLock will immediately be released"]
+    pub fn acquire_shared(&self) -> PushLockSharedGuard<'_, T> {
+        enter_critical_region();
+
+        // SAFETY: `push_lock` was initialized in `new`, and cannot move while it is
+        // borrowed by the returned guard. Normal kernel APCs have been disabled above.
+        unsafe {
+            ExAcquirePushLockSharedEx(self.push_lock.get(), EX_DEFAULT_PUSH_LOCK_FLAGS);
+        }
+
+        PushLockSharedGuard {
+            push_lock: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Acquire the push lock for exclusive (write) access, waiting until it is
+    /// available
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the PushLock will immediately be released"]
+    pub fn acquire_exclusive(&self) -> PushLockExclusiveGuard<'_, T> {
+        enter_critical_region();
+
+        // SAFETY: `push_lock` was initialized in `new`, and cannot move while it is
+        // borrowed by the returned guard. Normal kernel APCs have been disabled above.
+        unsafe {
+            ExAcquirePushLockExclusiveEx(self.push_lock.get(), EX_DEFAULT_PUSH_LOCK_FLAGS);
+        }
+
+        PushLockExclusiveGuard {
+            push_lock: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Acquire the push lock for shared access, run `f` with shared access to
+    /// the protected data, and release the push lock
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn with_shared<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let guard = self.acquire_shared();
+        f(&guard)
+    }
+
+    /// Acquire the push lock for exclusive access, run `f` with mutable access
+    /// to the protected data, and release the push lock
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn with_exclusive<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut guard = self.acquire_exclusive();
+        f(&mut guard)
+    }
+
+    /// Returns a mutable reference to the protected data
+    ///
+    /// Since this call borrows the [`PushLock`] mutably, no locking needs to
+    /// take place: the mutable borrow statically guarantees no guards exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the [`PushLock`], returning the protected data
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+/// RAII guard for a [`PushLock`] held for shared (read) access.
+///
+/// The protected data can be read through the guard's [`Deref`]
+/// implementation. The guard cannot be sent to another thread, since the
+/// push lock must be released by the same thread that acquired it.
+pub struct PushLockSharedGuard<'a, T = ()> {
+    push_lock: &'a PushLock<T>,
+    // `KeLeaveCriticalRegion` must be called from the thread that acquired the lock
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for PushLockSharedGuard<'_, T> {}
+
+impl<T> Deref for PushLockSharedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the push lock is held for
+        // shared access, so no other thread can be mutating the protected data.
+        unsafe { &*self.push_lock.data.get() }
+    }
+}
+
+impl<T> Drop for PushLockSharedGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The existence of this guard guarantees that the push lock is held for
+        // shared access by this thread.
+        unsafe {
+            ExReleasePushLockSharedEx(self.push_lock.push_lock.get(), EX_DEFAULT_PUSH_LOCK_FLAGS);
+        }
+        leave_critical_region();
+    }
+}
+
+/// RAII guard for a [`PushLock`] held for exclusive (write) access.
+///
+/// The protected data can be accessed through the guard's [`Deref`] and
+/// [`DerefMut`] implementations. The guard cannot be sent to another thread,
+/// since the push lock must be released by the same thread that acquired it.
+pub struct PushLockExclusiveGuard<'a, T = ()> {
+    push_lock: &'a PushLock<T>,
+    // `KeLeaveCriticalRegion` must be called from the thread that acquired the lock
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for PushLockExclusiveGuard<'_, T> {}
+
+impl<T> Deref for PushLockExclusiveGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the push lock is held for
+        // exclusive access, so no other thread can be accessing the protected data.
+        unsafe { &*self.push_lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for PushLockExclusiveGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The existence of this guard guarantees that the push lock is held for
+        // exclusive access, so no other thread can be accessing the protected data. The
+        // mutable borrow of the guard guarantees that no other references to the data
+        // exist on this thread.
+        unsafe { &mut *self.push_lock.data.get() }
+    }
+}
+
+impl<T> Drop for PushLockExclusiveGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The existence of this guard guarantees that the push lock is held for
+        // exclusive access by this thread.
+        unsafe {
+            ExReleasePushLockExclusiveEx(
+                self.push_lock.push_lock.get(),
+                EX_DEFAULT_PUSH_LOCK_FLAGS,
+            );
+        }
+        leave_critical_region();
+    }
+}
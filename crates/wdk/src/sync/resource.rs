@@ -0,0 +1,322 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use wdk_sys::{
+    ntddk::{
+        ExAcquireResourceExclusiveLite,
+        ExAcquireResourceSharedLite,
+        ExDeleteResourceLite,
+        ExInitializeResourceLite,
+        ExReleaseResourceLite,
+    },
+    ERESOURCE,
+    NTSTATUS,
+};
+
+use super::{enter_critical_region, leave_critical_region};
+use crate::{lock_order, nt_success, pool::NonPagedBox};
+
+/// Executive Resource (`ERESOURCE`) reader-writer lock.
+///
+/// A [`Resource`] allows any number of threads to hold it for shared (read)
+/// access at the same time, or a single thread to hold it for exclusive (write)
+/// access. Shared access is acquired with [`Resource::acquire_shared()`], and
+/// exclusive access with [`Resource::acquire_exclusive()`]. Both return guards
+/// that release the resource when they are dropped.
+///
+/// Resources must be acquired at `IRQL` <= `APC_LEVEL`. Normal kernel APCs
+/// must be disabled while a resource is held, so the guards returned by
+/// [`Resource`] enter a critical region (via `KeEnterCriticalRegion`) before
+/// acquiring the resource, and leave it after releasing the resource.
+///
+/// A [`Resource`] owns the data of type `T` that it protects, and the data is
+/// only accessible through the guards. The underlying `ERESOURCE` is stored in
+/// non-paged pool, and is deleted when the [`Resource`] is dropped.
+pub struct Resource<T = ()> {
+    eresource: NonPagedBox<ERESOURCE>,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: `ERESOURCE`s can be used from any thread, and the protected data is
+// only ever accessed by whoever holds the resource. Sending the `Resource` to
+// another thread sends the owned `T` with it, so `T` must be `Send`.
+unsafe impl<T: Send> Send for Resource<T> {}
+
+// SAFETY: Multiple threads can hold shared access at the same time, which gives
+// each of them a `&T`, so `T` must be `Sync`. Exclusive access gives a `&mut T`
+// to one thread at a time, so `T` must also be `Send`.
+unsafe impl<T: Send + Sync> Sync for Resource<T> {}
+
+impl<T> Resource<T> {
+    /// Try to construct an executive resource protecting `data`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `ERESOURCE` cannot be allocated or initialized. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [ExInitializeResourceLite Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-exinitializeresourcelite#return-value)
+    pub fn try_new(data: T) -> Result<Self, NTSTATUS> {
+        let eresource = NonPagedBox::try_new(ERESOURCE::default())?;
+
+        let nt_status;
+        // SAFETY: `eresource` points to zeroed, non-paged memory that is large enough
+        // and properly aligned for an `ERESOURCE`, and it never moves for the
+        // lifetime of the `Resource`.
+        unsafe {
+            nt_status = ExInitializeResourceLite(eresource.as_ptr());
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            eresource,
+            data: UnsafeCell::new(data),
+        })
+    }
+
+    /// Acquire the resource for shared (read) access, waiting until it is
+    /// available
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the Resource will immediately be released"]
+    pub fn acquire_shared(&self) -> ResourceSharedGuard<'_, T> {
+        lock_order::check_acquire(self.eresource.as_ptr().cast());
+        enter_critical_region();
+
+        let acquired;
+        // SAFETY: `eresource` is a private member of `Resource` that was initialized
+        // in `try_new` and is not deleted until the `Resource` is dropped. Normal
+        // kernel APCs have been disabled above.
+        unsafe {
+            acquired = ExAcquireResourceSharedLite(self.eresource.as_ptr(), u8::from(true));
+        }
+        debug_assert_ne!(
+            acquired, 0,
+            "ExAcquireResourceSharedLite should always succeed when waiting"
+        );
+        lock_order::acquired(self.eresource.as_ptr().cast());
+
+        ResourceSharedGuard {
+            resource: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Attempt to acquire the resource for shared (read) access without
+    /// waiting
+    ///
+    /// Returns [`None`] if the resource is currently held for exclusive access,
+    /// or if exclusive waiters should be given priority. This must be called
+    /// at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the Resource will immediately be released"]
+    pub fn try_acquire_shared(&self) -> Option<ResourceSharedGuard<'_, T>> {
+        enter_critical_region();
+
+        let acquired;
+        // SAFETY: `eresource` is a private member of `Resource` that was initialized
+        // in `try_new` and is not deleted until the `Resource` is dropped. Normal
+        // kernel APCs have been disabled above.
+        unsafe {
+            acquired = ExAcquireResourceSharedLite(self.eresource.as_ptr(), u8::from(false));
+        }
+        if acquired == 0 {
+            leave_critical_region();
+            return None;
+        }
+        lock_order::acquired(self.eresource.as_ptr().cast());
+
+        Some(ResourceSharedGuard {
+            resource: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Acquire the resource for exclusive (write) access, waiting until it is
+    /// available
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the Resource will immediately be released"]
+    pub fn acquire_exclusive(&self) -> ResourceExclusiveGuard<'_, T> {
+        lock_order::check_acquire(self.eresource.as_ptr().cast());
+        enter_critical_region();
+
+        let acquired;
+        // SAFETY: `eresource` is a private member of `Resource` that was initialized
+        // in `try_new` and is not deleted until the `Resource` is dropped. Normal
+        // kernel APCs have been disabled above.
+        unsafe {
+            acquired = ExAcquireResourceExclusiveLite(self.eresource.as_ptr(), u8::from(true));
+        }
+        debug_assert_ne!(
+            acquired, 0,
+            "ExAcquireResourceExclusiveLite should always succeed when waiting"
+        );
+        lock_order::acquired(self.eresource.as_ptr().cast());
+
+        ResourceExclusiveGuard {
+            resource: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Attempt to acquire the resource for exclusive (write) access without
+    /// waiting
+    ///
+    /// Returns [`None`] if the resource is currently held. This must be called
+    /// at `IRQL` <= `APC_LEVEL`.
+    #[must_use = "if unused the Resource will immediately be released"]
+    pub fn try_acquire_exclusive(&self) -> Option<ResourceExclusiveGuard<'_, T>> {
+        enter_critical_region();
+
+        let acquired;
+        // SAFETY: `eresource` is a private member of `Resource` that was initialized
+        // in `try_new` and is not deleted until the `Resource` is dropped. Normal
+        // kernel APCs have been disabled above.
+        unsafe {
+            acquired = ExAcquireResourceExclusiveLite(self.eresource.as_ptr(), u8::from(false));
+        }
+        if acquired == 0 {
+            leave_critical_region();
+            return None;
+        }
+        lock_order::acquired(self.eresource.as_ptr().cast());
+
+        Some(ResourceExclusiveGuard {
+            resource: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Acquire the resource for shared access, run `f` with shared access to
+    /// the protected data, and release the resource
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn with_shared<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let guard = self.acquire_shared();
+        f(&guard)
+    }
+
+    /// Acquire the resource for exclusive access, run `f` with mutable access
+    /// to the protected data, and release the resource
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn with_exclusive<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut guard = self.acquire_exclusive();
+        f(&mut guard)
+    }
+
+    /// Returns a mutable reference to the protected data
+    ///
+    /// Since this call borrows the [`Resource`] mutably, no locking needs to
+    /// take place: the mutable borrow statically guarantees no guards exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    fn release(&self) {
+        // SAFETY: This is only called from the `Drop` implementations of the guards,
+        // whose existence guarantees that the resource is held by this thread.
+        unsafe {
+            ExReleaseResourceLite(self.eresource.as_ptr());
+        }
+        lock_order::released(self.eresource.as_ptr().cast());
+        leave_critical_region();
+    }
+}
+
+impl<T> Drop for Resource<T> {
+    fn drop(&mut self) {
+        let nt_status;
+        // SAFETY: The resource was initialized in `try_new`, and the mutable borrow
+        // guarantees that no guards exist, so it is not held by any thread.
+        unsafe {
+            nt_status = ExDeleteResourceLite(self.eresource.as_ptr());
+        }
+        debug_assert!(
+            nt_success(nt_status),
+            "ExDeleteResourceLite should succeed on an unowned resource"
+        );
+        lock_order::forget(self.eresource.as_ptr().cast());
+    }
+}
+
+/// RAII guard for a [`Resource`] held for shared (read) access.
+///
+/// The protected data can be read through the guard's [`Deref`]
+/// implementation. The guard cannot be sent to another thread, since the
+/// resource must be released by the same thread that acquired it.
+pub struct ResourceSharedGuard<'a, T = ()> {
+    resource: &'a Resource<T>,
+    // `ExReleaseResourceLite` and `KeLeaveCriticalRegion` must be called from the
+    // thread that acquired the resource
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for ResourceSharedGuard<'_, T> {}
+
+impl<T> Deref for ResourceSharedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the resource is held for
+        // shared access, so no other thread can be mutating the protected data.
+        unsafe { &*self.resource.data.get() }
+    }
+}
+
+impl<T> Drop for ResourceSharedGuard<'_, T> {
+    fn drop(&mut self) {
+        self.resource.release();
+    }
+}
+
+/// RAII guard for a [`Resource`] held for exclusive (write) access.
+///
+/// The protected data can be accessed through the guard's [`Deref`] and
+/// [`DerefMut`] implementations. The guard cannot be sent to another thread,
+/// since the resource must be released by the same thread that acquired it.
+pub struct ResourceExclusiveGuard<'a, T = ()> {
+    resource: &'a Resource<T>,
+    // `ExReleaseResourceLite` and `KeLeaveCriticalRegion` must be called from the
+    // thread that acquired the resource
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for ResourceExclusiveGuard<'_, T> {}
+
+impl<T> Deref for ResourceExclusiveGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the resource is held for
+        // exclusive access, so no other thread can be accessing the protected data.
+        unsafe { &*self.resource.data.get() }
+    }
+}
+
+impl<T> DerefMut for ResourceExclusiveGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The existence of this guard guarantees that the resource is held for
+        // exclusive access, so no other thread can be accessing the protected data. The
+        // mutable borrow of the guard guarantees that no other references to the data
+        // exist on this thread.
+        unsafe { &mut *self.resource.data.get() }
+    }
+}
+
+impl<T> Drop for ResourceExclusiveGuard<'_, T> {
+    fn drop(&mut self) {
+        self.resource.release();
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::cell::UnsafeCell;
+
+use wdk_sys::{
+    ntddk::{
+        ExAcquireRundownProtection,
+        ExInitializeRundownProtection,
+        ExReInitializeRundownProtection,
+        ExReleaseRundownProtection,
+        ExWaitForRundownProtectionRelease,
+    },
+    EX_RUNDOWN_REF,
+};
+
+/// Rundown protection (`EX_RUNDOWN_REF`).
+///
+/// Rundown protection gates access to a shared object that is going to be torn
+/// down. Code that uses the object (ex. a callback) first calls
+/// [`Rundown::acquire()`], which succeeds until rundown begins, and holds the
+/// returned [`RundownGuard`] for as long as it uses the object. Code that tears
+/// down the object calls [`Rundown::wait_for_release()`], which prevents any
+/// further acquisitions and then waits for all outstanding guards to be
+/// dropped.
+///
+/// Unlike a lock, any number of threads can hold rundown protection at the same
+/// time, and acquiring or releasing it never waits.
+pub struct Rundown {
+    rundown_ref: UnsafeCell<EX_RUNDOWN_REF>,
+}
+
+// SAFETY: Rundown protection is designed to be used from any thread, and the
+// `EX_RUNDOWN_REF` is only ever accessed through the kernel's interlocked
+// rundown protection APIs. Its `wdk-sys` binding is only `!Send` because it
+// contains a raw pointer.
+#[allow(clippy::non_send_fields_in_send_ty)]
+unsafe impl Send for Rundown {}
+
+// SAFETY: The kernel's rundown protection APIs are safe to call concurrently on
+// the same `EX_RUNDOWN_REF` from multiple threads.
+unsafe impl Sync for Rundown {}
+
+impl Rundown {
+    /// Construct rundown protection that has not yet been run down
+    #[must_use]
+    pub fn new() -> Self {
+        let rundown = Self {
+            rundown_ref: UnsafeCell::new(EX_RUNDOWN_REF::default()),
+        };
+
+        // SAFETY: `rundown_ref` is a valid pointer to an `EX_RUNDOWN_REF`. Rundown
+        // protection that is not being waited on is not referenced by address, so it
+        // is safe to move the `Rundown` after this.
+        unsafe {
+            ExInitializeRundownProtection(rundown.rundown_ref.get());
+        }
+        rundown
+    }
+
+    /// Attempt to acquire rundown protection
+    ///
+    /// Returns [`None`] if rundown has already begun, in which case the
+    /// protected object must not be used. This must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    #[must_use = "if unused the rundown protection will immediately be released"]
+    pub fn acquire(&self) -> Option<RundownGuard<'_>> {
+        let acquired;
+        // SAFETY: `rundown_ref` was initialized in `new`, and cannot move while it is
+        // borrowed by the returned guard.
+        unsafe {
+            acquired = ExAcquireRundownProtection(self.rundown_ref.get());
+        }
+        (acquired != 0).then_some(RundownGuard { rundown: self })
+    }
+
+    /// Begin rundown, and wait until all outstanding [`RundownGuard`]s have
+    /// been dropped
+    ///
+    /// After this is called, all calls to [`Rundown::acquire()`] will fail
+    /// until the [`Rundown`] is reinitialized. This must be called at `IRQL`
+    /// <= `APC_LEVEL`, and must not be called while the current thread holds a
+    /// [`RundownGuard`] for this [`Rundown`], since that would deadlock.
+    pub fn wait_for_release(&self) {
+        // SAFETY: `rundown_ref` was initialized in `new`, and cannot move while it is
+        // borrowed for the wait.
+        unsafe {
+            ExWaitForRundownProtectionRelease(self.rundown_ref.get());
+        }
+    }
+
+    /// Reinitialize rundown protection that has been run down, so that it can
+    /// be acquired again
+    ///
+    /// Since this call borrows the [`Rundown`] mutably, the borrow statically
+    /// guarantees no guards exist and no thread is waiting for rundown.
+    pub fn reinitialize(&mut self) {
+        // SAFETY: `rundown_ref` was initialized in `new`, and the mutable borrow
+        // guarantees that it is not in use by any other thread.
+        unsafe {
+            ExReInitializeRundownProtection(self.rundown_ref.get());
+        }
+    }
+}
+
+impl Default for Rundown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RAII guard for acquired [`Rundown`] protection.
+///
+/// A [`RundownGuard`] is returned by [`Rundown::acquire()`], and releases the
+/// rundown protection when it goes out of scope. Unlike lock guards, rundown
+/// protection may be released from a different thread than the one that
+/// acquired it, so the guard can be sent between threads.
+pub struct RundownGuard<'a> {
+    rundown: &'a Rundown,
+}
+
+impl Drop for RundownGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `rundown_ref` was initialized in `Rundown::new`, and the existence of
+        // this guard guarantees that rundown protection is currently held.
+        unsafe {
+            ExReleaseRundownProtection(self.rundown.rundown_ref.get());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+use wdk_sys::{
+    ntddk::{KeInitializeSemaphore, KeReleaseSemaphore},
+    KPRIORITY,
+    KSEMAPHORE,
+    LONG,
+    NTSTATUS,
+    SEMAPHORE_INCREMENT,
+    STATUS_INVALID_PARAMETER,
+    STATUS_SEMAPHORE_LIMIT_EXCEEDED,
+};
+
+use super::wait::{wait_for_single_object, WaitStatus};
+use crate::pool::NonPagedBox;
+
+// `SEMAPHORE_INCREMENT` is generated as a `u32`, but is passed as a `KPRIORITY`
+#[allow(clippy::cast_possible_wrap)]
+const INCREMENT: KPRIORITY = SEMAPHORE_INCREMENT as KPRIORITY;
+
+/// Kernel Semaphore (`KSEMAPHORE`).
+///
+/// A [`Semaphore`] maintains a count between zero and a fixed limit. Acquiring
+/// the semaphore waits until the count is nonzero and then decrements it, and
+/// releasing the semaphore increments it. Semaphores are commonly used to hand
+/// off work items between producer and consumer threads, where each release
+/// signals one unit of available work.
+///
+/// `KeReleaseSemaphore` raises an exception if a release would increase the
+/// count beyond the semaphore's limit. To prevent this, [`Semaphore`] tracks
+/// an upper bound on the count, and [`Semaphore::release()`] returns an error
+/// instead of releasing past the limit.
+///
+/// The underlying `KSEMAPHORE` is stored in non-paged pool, so it never moves
+/// while threads are waiting on it.
+pub struct Semaphore {
+    ksemaphore: NonPagedBox<KSEMAPHORE>,
+    // Always at least the count of `ksemaphore`: it is incremented before the
+    // semaphore is released, and decremented after a wait on it is satisfied
+    count: AtomicI32,
+    limit: LONG,
+}
+
+impl Semaphore {
+    /// Try to construct a kernel semaphore with an initial count of
+    /// `initial_count`, which can never exceed `limit`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `limit` is zero or greater than
+    /// [`i32::MAX`], if `initial_count` is greater than `limit`, or if the
+    /// `KSEMAPHORE` cannot be allocated. The error variant will contain a
+    /// [`NTSTATUS`] of the failure.
+    pub fn try_new(initial_count: u32, limit: u32) -> Result<Self, NTSTATUS> {
+        let limit = LONG::try_from(limit).map_err(|_| STATUS_INVALID_PARAMETER)?;
+        let initial_count = LONG::try_from(initial_count).map_err(|_| STATUS_INVALID_PARAMETER)?;
+        if limit == 0 || initial_count > limit {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+
+        let ksemaphore = NonPagedBox::try_new(KSEMAPHORE::default())?;
+
+        // SAFETY: `ksemaphore` points to non-paged memory that is large enough and
+        // properly aligned for a `KSEMAPHORE`, and it never moves for the lifetime of
+        // the `Semaphore`. `initial_count` and `limit` were validated above.
+        unsafe {
+            KeInitializeSemaphore(ksemaphore.as_ptr(), initial_count, limit);
+        }
+
+        Ok(Self {
+            ksemaphore,
+            count: AtomicI32::new(initial_count),
+            limit,
+        })
+    }
+
+    /// Returns the maximum count of the semaphore
+    #[must_use]
+    pub fn limit(&self) -> u32 {
+        self.limit.unsigned_abs()
+    }
+
+    /// Wait indefinitely until the count of the semaphore is nonzero, and
+    /// decrement it
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`.
+    pub fn acquire(&self) {
+        // SAFETY: `ksemaphore` is a private member of `Semaphore` that was initialized
+        // in `try_new`, and cannot be freed while it is borrowed for the wait.
+        let wait_status = unsafe { wait_for_single_object(self.ksemaphore.as_ptr().cast(), None) };
+        debug_assert_eq!(
+            wait_status,
+            WaitStatus::Signaled,
+            "an indefinite wait should only return once the semaphore is signaled"
+        );
+        self.count.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Wait until the count of the semaphore is nonzero and decrement it, or
+    /// until `timeout` elapses
+    ///
+    /// The count is only decremented if [`WaitStatus::Signaled`] is returned.
+    /// A zero `timeout` attempts to decrement the count without waiting, which
+    /// may be done at `IRQL` <= `DISPATCH_LEVEL`. Any other `timeout` requires
+    /// `IRQL` <= `APC_LEVEL`.
+    pub fn acquire_timeout(&self, timeout: Duration) -> WaitStatus {
+        // SAFETY: `ksemaphore` is a private member of `Semaphore` that was initialized
+        // in `try_new`, and cannot be freed while it is borrowed for the wait.
+        let wait_status =
+            unsafe { wait_for_single_object(self.ksemaphore.as_ptr().cast(), Some(timeout)) };
+        if wait_status.is_signaled() {
+            self.count.fetch_sub(1, Ordering::AcqRel);
+        }
+        wait_status
+    }
+
+    /// Increment the count of the semaphore, satisfying a wait on it
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the count of the semaphore may
+    /// already be at its limit. The error variant will contain
+    /// `STATUS_SEMAPHORE_LIMIT_EXCEEDED`, and the count is left unchanged.
+    pub fn release(&self) -> Result<(), NTSTATUS> {
+        self.count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < self.limit).then_some(count + 1)
+            })
+            .map_err(|_| STATUS_SEMAPHORE_LIMIT_EXCEEDED)?;
+
+        // SAFETY: `ksemaphore` is a private member of `Semaphore` that was initialized
+        // in `try_new`. `count` is never less than the semaphore's count, and was below
+        // `limit` before being incremented, so this release cannot exceed the limit.
+        // Passing `FALSE` for `Wait` allows the call to be made at `IRQL` <=
+        // `DISPATCH_LEVEL`.
+        unsafe {
+            KeReleaseSemaphore(self.ksemaphore.as_ptr(), INCREMENT, 1, u8::from(false));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, time::Duration};
+
+use wdk_sys::{
+    ntddk::KeWaitForSingleObject,
+    _KWAIT_REASON,
+    _MODE,
+    KPROCESSOR_MODE,
+    LARGE_INTEGER,
+    STATUS_SUCCESS,
+    STATUS_TIMEOUT,
+};
+
+use crate::time::relative_timeout;
+
+// `KPROCESSOR_MODE` is a `CCHAR`, while the `MODE` enumeration it holds a value
+// of is an `int`
+#[allow(clippy::cast_possible_truncation)]
+pub const KERNEL_MODE: KPROCESSOR_MODE = _MODE::KernelMode as KPROCESSOR_MODE;
+
+/// The outcome of waiting on a kernel dispatcher object with a timeout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[must_use]
+pub enum WaitStatus {
+    /// The object was signaled before the timeout elapsed
+    Signaled,
+    /// The timeout elapsed before the object was signaled
+    TimedOut,
+}
+
+impl WaitStatus {
+    /// Returns `true` if the object was signaled before the timeout elapsed
+    #[must_use]
+    pub const fn is_signaled(self) -> bool {
+        matches!(self, Self::Signaled)
+    }
+
+    /// Returns `true` if the timeout elapsed before the object was signaled
+    #[must_use]
+    pub const fn is_timed_out(self) -> bool {
+        matches!(self, Self::TimedOut)
+    }
+}
+
+/// Wait on a kernel dispatcher object with a non-alertable kernel-mode wait.
+/// A `timeout` of [`None`] waits indefinitely.
+///
+/// # Safety
+///
+/// `object` must point to an initialized dispatcher object (ex. a `KEVENT`)
+/// that remains valid for the duration of the wait. The caller must be at
+/// `IRQL` <= `APC_LEVEL`, or at `IRQL` = `DISPATCH_LEVEL` if `timeout` is zero.
+pub unsafe fn wait_for_single_object(object: *mut c_void, timeout: Option<Duration>) -> WaitStatus {
+    let mut relative_timeout = timeout.map(|timeout| LARGE_INTEGER {
+        QuadPart: relative_timeout(timeout),
+    });
+    let timeout_ptr = relative_timeout
+        .as_mut()
+        .map_or(core::ptr::null_mut(), core::ptr::from_mut);
+
+    let nt_status;
+    // SAFETY: The caller guarantees that `object` is a valid dispatcher object and
+    // that the `IRQL` requirements are met. `timeout_ptr` is either null, which
+    // requests an indefinite wait, or points to a valid negative (relative)
+    // timeout in 100ns units that outlives the call.
+    unsafe {
+        nt_status = KeWaitForSingleObject(
+            object,
+            _KWAIT_REASON::Executive,
+            KERNEL_MODE,
+            u8::from(false),
+            timeout_ptr,
+        );
+    }
+
+    // Non-alertable kernel-mode waits can only be satisfied or time out
+    debug_assert!(
+        nt_status == STATUS_SUCCESS || nt_status == STATUS_TIMEOUT,
+        "KeWaitForSingleObject returned an unexpected status: {nt_status:#x}"
+    );
+    if nt_status == STATUS_TIMEOUT {
+        WaitStatus::TimedOut
+    } else {
+        WaitStatus::Signaled
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU8, Ordering},
+    task::Waker,
+};
+
+/// No thread is registering or waking the waker
+const IDLE: u8 = 0;
+/// A thread is registering a new waker
+const REGISTERING: u8 = 0b01;
+/// A thread is taking the waker to wake it
+const WAKING: u8 = 0b10;
+
+/// A [`Waker`] slot that a future registers its task's waker in, and that the
+/// code completing the future wakes it from.
+///
+/// Neither [`AtomicWaker::register()`] nor [`AtomicWaker::wake()`] ever waits
+/// for the other, so the future can be polled at `IRQL` = `PASSIVE_LEVEL`
+/// while it is woken from a DPC or an ISR's DPC on the same processor. The
+/// waker is called at the `IRQL` that [`AtomicWaker::wake()`] is called at,
+/// so the executor's wakers must be able to run there.
+///
+/// ```ignore
+/// fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+///     self.state.waker.register(cx.waker());
+///     if self.state.done.load(Ordering::Acquire) {
+///         return Poll::Ready(());
+///     }
+///     Poll::Pending
+/// }
+/// ```
+pub struct AtomicWaker {
+    state: AtomicU8,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// SAFETY: `waker` is only accessed by the thread that moved `state` out of
+// `IDLE`, so it is never accessed from multiple threads at once, and `Waker`s
+// are `Send`.
+unsafe impl Send for AtomicWaker {}
+
+// SAFETY: See the `Send` implementation.
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    /// Construct an [`AtomicWaker`] with no waker registered
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(IDLE),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Register `waker` to be woken by the next call to
+    /// [`AtomicWaker::wake()`], replacing any previously registered waker
+    ///
+    /// This is called from [`Future::poll()`](core::future::Future::poll)
+    /// before checking whether the future is ready, so that a wake between
+    /// the check and returning [`Poll::Pending`](core::task::Poll::Pending) is
+    /// not lost. Calls to this must not race with each other, which holds for
+    /// futures, since they are only polled by one thread at a time.
+    pub fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(IDLE, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
+            IDLE => {
+                // SAFETY: Moving `state` to `REGISTERING` gives this thread exclusive
+                // access to `waker` until it moves `state` back.
+                let slot = unsafe { &mut *self.waker.get() };
+                if !slot.as_ref().is_some_and(|old| old.will_wake(waker)) {
+                    *slot = Some(waker.clone());
+                }
+
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // `wake` was called while the waker was being registered, and left it
+                    // for this thread to wake
+                    let waker = slot.take();
+                    self.state.swap(IDLE, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // `wake` is currently taking the previous waker, so the task is woken
+            // directly to poll it again
+            WAKING => waker.wake_by_ref(),
+            // Another call to `register` is running, which the contract of this
+            // function rules out
+            _ => {}
+        }
+    }
+
+    /// Wake the registered waker, if there is one, and unregister it
+    ///
+    /// This may be called at any `IRQL` that the registered waker can run at.
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Unregister the registered waker, if there is one, and return it
+    #[must_use]
+    pub fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            IDLE => {
+                // SAFETY: Moving `state` from `IDLE` to `WAKING` gives this thread
+                // exclusive access to `waker` until it moves `state` back.
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            // A waker is being registered, which wakes itself once it sees `WAKING`,
+            // or another thread is already waking the waker
+            _ => None,
+        }
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,10 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Safe abstractions over kernel threads
+
+mod sleep;
+mod system_thread;
+
+pub use sleep::*;
+pub use system_thread::*;
@@ -0,0 +1,67 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::time::Duration;
+
+use wdk_sys::{
+    ntddk::{KeDelayExecutionThread, KeStallExecutionProcessor},
+    LARGE_INTEGER,
+    STATUS_SUCCESS,
+    ULONG,
+};
+
+use crate::{debug_assert_irql, irql::ApcLevel, sync::wait::KERNEL_MODE, time::relative_timeout};
+
+/// The longest stall that [`stall()`] should be used for, per the
+/// `KeStallExecutionProcessor` documentation
+const MAX_RECOMMENDED_STALL: Duration = Duration::from_micros(50);
+
+/// Put the current thread into a non-alertable wait for at least `duration`
+///
+/// The actual delay is rounded up to the resolution of the system clock, which
+/// is typically around 15ms. Other threads can run on the processor while the
+/// current thread is waiting.
+///
+/// Sleeping requires `IRQL` <= `APC_LEVEL`, as proven by `irql`. Use
+/// [`stall()`] for short delays at higher `IRQL`s.
+pub fn sleep(_irql: &ApcLevel, duration: Duration) {
+    debug_assert_irql!(<= APC_LEVEL, "sleep must be called");
+
+    let mut interval = LARGE_INTEGER {
+        QuadPart: relative_timeout(duration),
+    };
+
+    let nt_status;
+    // SAFETY: `interval` is a valid negative (relative) interval in 100ns units
+    // that outlives the call, and the caller is at `IRQL` <= `APC_LEVEL`.
+    unsafe {
+        nt_status = KeDelayExecutionThread(KERNEL_MODE, u8::from(false), &mut interval);
+    }
+    debug_assert_eq!(
+        nt_status, STATUS_SUCCESS,
+        "non-alertable kernel-mode delays should always complete successfully"
+    );
+}
+
+/// Busy-wait on the current processor for at least `duration`
+///
+/// Unlike [`sleep()`], this does not give up the processor, so it can be used
+/// at any `IRQL`, including `DISPATCH_LEVEL` and `DIRQL`. Durations are rounded
+/// up to the next microsecond. Since stalling wastes processor time, and
+/// prevents other work from running on the processor, `duration` should be no
+/// longer than 50 microseconds.
+pub fn stall(duration: Duration) {
+    const NANOSECONDS_PER_MICROSECOND: u128 = 1_000;
+
+    debug_assert!(
+        duration <= MAX_RECOMMENDED_STALL,
+        "stall should not be used for delays longer than 50 microseconds"
+    );
+    let microseconds = ULONG::try_from(duration.as_nanos().div_ceil(NANOSECONDS_PER_MICROSECOND))
+        .unwrap_or(ULONG::MAX);
+
+    // SAFETY: `KeStallExecutionProcessor` can be called at any `IRQL`.
+    unsafe {
+        KeStallExecutionProcessor(microseconds);
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use wdk_sys::{
+    ntddk::{
+        ObReferenceObjectByHandle,
+        ObfDereferenceObject,
+        PsCreateSystemThread,
+        PsTerminateSystemThread,
+        ZwClose,
+    },
+    PsThreadType,
+    HANDLE,
+    NTSTATUS,
+    OBJECT_ATTRIBUTES,
+    OBJ_KERNEL_HANDLE,
+    PVOID,
+    STATUS_SUCCESS,
+    THREAD_ALL_ACCESS,
+    ULONG,
+};
+
+use crate::{
+    irql::PassiveLevel,
+    nt_success,
+    pool::NonPagedBox,
+    sync::{
+        wait::{wait_for_single_object, KERNEL_MODE},
+        WaitStatus,
+    },
+};
+
+// `OBJECT_ATTRIBUTES` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const OBJECT_ATTRIBUTES_LENGTH: ULONG = core::mem::size_of::<OBJECT_ATTRIBUTES>() as ULONG;
+
+/// State shared between a [`SystemThread`] and the thread it represents. It is
+/// freed by whichever of the two releases it last.
+struct Shared<T> {
+    references: AtomicUsize,
+    result: UnsafeCell<Option<T>>,
+}
+
+/// The context passed to the start routine of a new thread
+struct StartContext<F, T> {
+    f: F,
+    shared: NonNull<Shared<T>>,
+}
+
+/// Kernel system thread.
+///
+/// A [`SystemThread`] is created by [`SystemThread::spawn()`], which runs a
+/// closure on a new system thread at `IRQL` = `PASSIVE_LEVEL`, and passes it a
+/// [`PassiveLevel`] token. The value the closure returns can be retrieved with
+/// [`SystemThread::join()`], which waits for the thread to exit. Dropping a
+/// [`SystemThread`] without joining it detaches the thread, which keeps running
+/// until the closure returns.
+///
+/// The thread always exits by calling `PsTerminateSystemThread` once the
+/// closure returns. A driver must join or otherwise wait for all of its
+/// threads to exit before it is unloaded, since the thread's code is part of
+/// the driver image.
+pub struct SystemThread<T> {
+    thread_object: PVOID,
+    shared: NonNull<Shared<T>>,
+}
+
+// SAFETY: The thread object can be waited on and dereferenced from any thread.
+// The result is only read by whoever owns the `SystemThread` after the thread
+// has exited, so sending the `SystemThread` is equivalent to sending `T`.
+unsafe impl<T: Send> Send for SystemThread<T> {}
+
+// SAFETY: A shared reference to a `SystemThread` does not allow any access to
+// the result.
+unsafe impl<T: Send> Sync for SystemThread<T> {}
+
+impl<T: Send> SystemThread<T> {
+    /// Spawn a new system thread that runs `f`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the thread's context cannot be allocated, or if the thread cannot be created. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [PsCreateSystemThread Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-pscreatesystemthread#return-value)
+    pub fn spawn<F>(_irql: &PassiveLevel, f: F) -> Result<Self, NTSTATUS>
+    where
+        F: FnOnce(&PassiveLevel) -> T + Send + 'static,
+        T: 'static,
+    {
+        // One reference is owned by the new thread, and the other by the returned
+        // `SystemThread`
+        let shared = NonPagedBox::try_new(Shared {
+            references: AtomicUsize::new(2),
+            result: UnsafeCell::new(None),
+        })?
+        .into_raw();
+        let start_context = match NonPagedBox::try_new(StartContext { f, shared }) {
+            Ok(start_context) => start_context.into_raw(),
+            Err(nt_status) => {
+                // SAFETY: `shared` was released from its `NonPagedBox` above, and has not
+                // been shared with any thread.
+                drop(unsafe { NonPagedBox::from_raw(shared) });
+                return Err(nt_status);
+            }
+        };
+
+        let mut object_attributes = OBJECT_ATTRIBUTES {
+            Length: OBJECT_ATTRIBUTES_LENGTH,
+            Attributes: OBJ_KERNEL_HANDLE,
+            ..Default::default()
+        };
+        let mut thread_handle: HANDLE = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `thread_handle` and `object_attributes` are valid for the duration
+        // of the call. A null process handle creates the thread in the system process.
+        // `start::<F, T>` expects `start_context` to be a `StartContext<F, T>`, whose
+        // ownership is transferred to the new thread if this succeeds.
+        unsafe {
+            nt_status = PsCreateSystemThread(
+                &mut thread_handle,
+                THREAD_ALL_ACCESS,
+                &mut object_attributes,
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+                Some(start::<F, T>),
+                start_context.as_ptr().cast(),
+            );
+        }
+        if !nt_success(nt_status) {
+            // SAFETY: The thread was not created, so ownership of `start_context` was
+            // not transferred, and `shared` was never shared with another thread.
+            unsafe {
+                drop(NonPagedBox::from_raw(start_context));
+            }
+            // SAFETY: See above.
+            drop(unsafe { NonPagedBox::from_raw(shared) });
+            return Err(nt_status);
+        }
+
+        let thread_type_ptr;
+        // SAFETY: `PsThreadType` is an immutable kernel export.
+        unsafe {
+            thread_type_ptr = PsThreadType;
+        }
+        let thread_type;
+        // SAFETY: `PsThreadType` points to the kernel's thread object type.
+        unsafe {
+            thread_type = *thread_type_ptr;
+        }
+
+        let mut thread_object: PVOID = core::ptr::null_mut();
+        let nt_status;
+        // SAFETY: `thread_handle` is a valid kernel handle to the thread that was just
+        // created, and `thread_object` is valid for the duration of the call.
+        unsafe {
+            nt_status = ObReferenceObjectByHandle(
+                thread_handle,
+                THREAD_ALL_ACCESS,
+                thread_type,
+                KERNEL_MODE,
+                &mut thread_object,
+                core::ptr::null_mut(),
+            );
+        }
+        // Referencing a handle that was just created with full access cannot fail
+        debug_assert!(
+            nt_success(nt_status),
+            "ObReferenceObjectByHandle should succeed on a new thread handle"
+        );
+
+        let close_status;
+        // SAFETY: `thread_handle` is a valid kernel handle that is not used after this.
+        // The thread object is kept alive by the reference taken above.
+        unsafe {
+            close_status = ZwClose(thread_handle);
+        }
+        debug_assert!(
+            nt_success(close_status),
+            "ZwClose should succeed on a valid kernel handle"
+        );
+
+        Ok(Self {
+            thread_object,
+            shared,
+        })
+    }
+
+    /// Wait for the thread to exit, and return the value returned by its
+    /// closure
+    ///
+    /// This must be called at `IRQL` <= `APC_LEVEL`, and must not be called
+    /// from the thread being joined.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread exited without its closure returning
+    // Threads are commonly joined only to wait for them to exit
+    #[allow(clippy::must_use_candidate)]
+    pub fn join(self) -> T {
+        // SAFETY: `thread_object` is a referenced thread object, which is a dispatcher
+        // object that is signaled when the thread exits.
+        let wait_status = unsafe { wait_for_single_object(self.thread_object, None) };
+        debug_assert_eq!(
+            wait_status,
+            WaitStatus::Signaled,
+            "an indefinite wait should only return once the thread has exited"
+        );
+
+        let result_ptr;
+        // SAFETY: `shared` is valid while this `SystemThread` owns a reference to it.
+        unsafe {
+            result_ptr = (*self.shared.as_ptr()).result.get();
+        }
+        let result;
+        // SAFETY: The thread has exited, so it no longer accesses the result, which it
+        // stored before exiting.
+        unsafe {
+            result = (*result_ptr).take();
+        }
+        result.expect("system thread should store its result before exiting")
+    }
+
+    /// Returns `true` if the thread has exited
+    ///
+    /// This may be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        // SAFETY: `thread_object` is a referenced thread object. A zero timeout does
+        // not wait, so this may be called at `IRQL` <= `DISPATCH_LEVEL`.
+        unsafe { wait_for_single_object(self.thread_object, Some(Duration::ZERO)) }.is_signaled()
+    }
+}
+
+impl<T> Drop for SystemThread<T> {
+    fn drop(&mut self) {
+        // SAFETY: `thread_object` was referenced in `spawn`, and is not used after
+        // this.
+        unsafe {
+            ObfDereferenceObject(self.thread_object);
+        }
+        // SAFETY: `shared` is a valid `Shared<T>` that this `SystemThread` owns a
+        // reference to.
+        unsafe {
+            release_shared(self.shared);
+        }
+    }
+}
+
+/// Release a reference to `shared`, freeing it if it was the last one
+///
+/// # Safety
+///
+/// `shared` must be a valid `Shared<T>` that the caller owns a reference to.
+/// `shared` must not be used by the caller after this.
+unsafe fn release_shared<T>(shared: NonNull<Shared<T>>) {
+    let references;
+    // SAFETY: The caller guarantees that `shared` is valid.
+    unsafe {
+        references = (*shared.as_ptr()).references.fetch_sub(1, Ordering::AcqRel);
+    }
+    if references == 1 {
+        // SAFETY: This was the last reference, so no other thread can access `shared`,
+        // which was released from its `NonPagedBox` in `spawn`.
+        drop(unsafe { NonPagedBox::from_raw(shared) });
+    }
+}
+
+/// The start routine of threads created by [`SystemThread::spawn()`]
+///
+/// # Safety
+///
+/// `context` must be a `StartContext<F, T>` released from its `NonPagedBox`,
+/// whose ownership is transferred to this thread.
+unsafe extern "C" fn start<F, T>(context: PVOID)
+where
+    F: FnOnce(&PassiveLevel) -> T,
+{
+    let context = NonNull::new(context.cast::<StartContext<F, T>>())
+        .expect("system thread start context should not be null");
+    // SAFETY: The caller guarantees that `context` is an owned `StartContext<F, T>`
+    // released from its `NonPagedBox`.
+    let start_context = unsafe { NonPagedBox::from_raw(context) };
+    let StartContext { f, shared } = start_context.into_inner();
+
+    let irql;
+    // SAFETY: System threads start at `IRQL` = `PASSIVE_LEVEL`, which `f` is called
+    // at.
+    unsafe {
+        irql = PassiveLevel::new_unchecked();
+    }
+    let result = f(&irql);
+
+    let result_ptr;
+    // SAFETY: `shared` is valid while this thread owns a reference to it.
+    unsafe {
+        result_ptr = (*shared.as_ptr()).result.get();
+    }
+    // SAFETY: The result is not read until this thread has exited.
+    unsafe {
+        *result_ptr = Some(result);
+    }
+    // SAFETY: This thread owns a reference to `shared`, and does not use it after
+    // this.
+    unsafe {
+        release_shared(shared);
+    }
+
+    // SAFETY: This is a system thread created by `PsCreateSystemThread`, so
+    // `PsTerminateSystemThread` terminates it and does not return.
+    unsafe {
+        let _ = PsTerminateSystemThread(STATUS_SUCCESS);
+    }
+}
@@ -0,0 +1,387 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Kernel clocks, exposed through [`Duration`]s instead of raw 100ns
+//! intervals.
+//!
+//! [`Instant`] is a monotonic clock based on the interrupt time, and is the
+//! right choice for measuring elapsed time and computing deadlines.
+//! [`SystemTime`] is the wall-clock time, which can jump when the system time
+//! is changed. [`PerformanceCounter`] exposes the high-resolution performance
+//! counter for fine-grained measurements.
+
+use core::{
+    ops::{Add, AddAssign, Sub, SubAssign},
+    time::Duration,
+};
+
+use wdk_sys::{
+    ntddk::{
+        KeQueryInterruptTimePrecise,
+        KeQueryPerformanceCounter,
+        KeQuerySystemTimePrecise,
+        KeQueryTimeIncrement,
+        KeQueryUnbiasedInterruptTime,
+    },
+    LARGE_INTEGER,
+};
+
+const NANOSECONDS_PER_INTERVAL: u64 = 100;
+const INTERVALS_PER_SECOND: u64 = 10_000_000;
+
+/// The number of 100ns intervals between the start of 1601 (the epoch of
+/// [`SystemTime`]) and the start of 1970 (the Unix epoch)
+const UNIX_EPOCH_INTERVALS: u64 = 116_444_736_000_000_000;
+
+/// Convert a number of 100ns intervals into a [`Duration`]
+const fn duration_from_intervals(intervals: u64) -> Duration {
+    // The remainder is always less than `INTERVALS_PER_SECOND`, so the number of
+    // nanoseconds always fits in a `u32`
+    #[allow(clippy::cast_possible_truncation)]
+    let nanoseconds = ((intervals % INTERVALS_PER_SECOND) * NANOSECONDS_PER_INTERVAL) as u32;
+    Duration::new(intervals / INTERVALS_PER_SECOND, nanoseconds)
+}
+
+/// Convert a [`Duration`] into a number of 100ns intervals, truncating any
+/// partial interval. Returns [`None`] if it does not fit in a `u64`.
+fn intervals_from_duration(duration: Duration) -> Option<u64> {
+    u64::try_from(duration.as_nanos() / u128::from(NANOSECONDS_PER_INTERVAL)).ok()
+}
+
+/// Convert a [`Duration`] into a positive number of 100ns intervals, as
+/// expected by kernel APIs that take a period. Durations too long to be
+/// represented saturate to [`i64::MAX`].
+pub(crate) fn interval_count(duration: Duration) -> i64 {
+    intervals_from_duration(duration)
+        .and_then(|intervals| i64::try_from(intervals).ok())
+        .unwrap_or(i64::MAX)
+}
+
+/// Convert a [`Duration`] into a relative timeout in the 100ns units expected
+/// by WDF and kernel wait APIs. Relative timeouts are represented as negative
+/// values. Durations too long to be represented saturate to the longest
+/// possible relative timeout.
+pub(crate) fn relative_timeout(duration: Duration) -> i64 {
+    -interval_count(duration)
+}
+
+/// A measurement of the monotonic interrupt time.
+///
+/// The interrupt time counts the 100ns intervals since the system was booted,
+/// including time spent in sleep or hibernation. It is not affected by changes
+/// to the system time, so [`Instant`]s are suited to measuring elapsed time
+/// and computing deadlines. [`Instant`]s are opaque, and are only useful when
+/// compared with each other.
+///
+/// This may be used at any `IRQL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    intervals: u64,
+}
+
+impl Instant {
+    /// Returns an [`Instant`] corresponding to "now"
+    #[must_use]
+    pub fn now() -> Self {
+        let mut qpc_time_stamp = 0;
+        let intervals;
+        // SAFETY: `qpc_time_stamp` is valid for the duration of the call.
+        // `KeQueryInterruptTimePrecise` can be called at any `IRQL`.
+        unsafe {
+            intervals = KeQueryInterruptTimePrecise(&mut qpc_time_stamp);
+        }
+        Self { intervals }
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or zero if
+    /// `earlier` is later than `self`
+    #[must_use]
+    pub const fn duration_since(&self, earlier: Self) -> Duration {
+        duration_from_intervals(self.intervals.saturating_sub(earlier.intervals))
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or
+    /// [`None`] if `earlier` is later than `self`
+    #[must_use]
+    pub const fn checked_duration_since(&self, earlier: Self) -> Option<Duration> {
+        match self.intervals.checked_sub(earlier.intervals) {
+            Some(intervals) => Some(duration_from_intervals(intervals)),
+            None => None,
+        }
+    }
+
+    /// Returns the amount of time elapsed since this [`Instant`]
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    /// Returns the [`Instant`] `duration` after `self`, or [`None`] if it
+    /// cannot be represented
+    #[must_use]
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        intervals_from_duration(duration)
+            .and_then(|intervals| self.intervals.checked_add(intervals))
+            .map(|intervals| Self { intervals })
+    }
+
+    /// Returns the [`Instant`] `duration` before `self`, or [`None`] if it
+    /// cannot be represented
+    #[must_use]
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        intervals_from_duration(duration)
+            .and_then(|intervals| self.intervals.checked_sub(intervals))
+            .map(|intervals| Self { intervals })
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Self;
+
+    /// # Panics
+    ///
+    /// Panics if the result cannot be represented. See
+    /// [`Instant::checked_add()`] for a version that does not panic.
+    fn add(self, duration: Duration) -> Self {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Self;
+
+    /// # Panics
+    ///
+    /// Panics if the result cannot be represented. See
+    /// [`Instant::checked_sub()`] for a version that does not panic.
+    fn sub(self, duration: Duration) -> Self {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or zero if
+    /// `earlier` is later than `self`
+    fn sub(self, earlier: Self) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// A measurement of the system (wall-clock) time.
+///
+/// The system time counts the 100ns intervals since the start of January 1,
+/// 1601 in UTC. Unlike [`Instant`], it can move backwards or forwards when the
+/// system time is changed, so it should not be used to measure elapsed time.
+///
+/// This may be used at any `IRQL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemTime {
+    intervals: u64,
+}
+
+impl SystemTime {
+    /// The start of January 1, 1970 in UTC
+    pub const UNIX_EPOCH: Self = Self {
+        intervals: UNIX_EPOCH_INTERVALS,
+    };
+
+    /// Returns the current system time
+    #[must_use]
+    pub fn now() -> Self {
+        let mut current_time = LARGE_INTEGER::default();
+        // SAFETY: `current_time` is valid for the duration of the call.
+        // `KeQuerySystemTimePrecise` can be called at any `IRQL`.
+        unsafe {
+            KeQuerySystemTimePrecise(&mut current_time);
+        }
+
+        let intervals;
+        // SAFETY: Every field of `LARGE_INTEGER` is an integer, so reading any of them
+        // is sound.
+        unsafe {
+            intervals = current_time.QuadPart;
+        }
+        // The system time is never before 1601
+        Self {
+            intervals: u64::try_from(intervals).unwrap_or_default(),
+        }
+    }
+
+    /// Construct a [`SystemTime`] from a raw system time, in 100ns intervals
+    /// since the start of 1601 (ex. a `LARGE_INTEGER` returned by a kernel
+    /// API)
+    #[must_use]
+    pub const fn from_raw(intervals: u64) -> Self {
+        Self { intervals }
+    }
+
+    /// Returns the raw system time, in 100ns intervals since the start of 1601
+    #[must_use]
+    pub const fn as_raw(&self) -> u64 {
+        self.intervals
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or
+    /// [`None`] if `earlier` is later than `self`
+    #[must_use]
+    pub const fn duration_since(&self, earlier: Self) -> Option<Duration> {
+        match self.intervals.checked_sub(earlier.intervals) {
+            Some(intervals) => Some(duration_from_intervals(intervals)),
+            None => None,
+        }
+    }
+
+    /// Returns the amount of time elapsed since this [`SystemTime`], or
+    /// [`None`] if the system time is now earlier than `self`
+    #[must_use]
+    pub fn elapsed(&self) -> Option<Duration> {
+        Self::now().duration_since(*self)
+    }
+}
+
+/// A sample of the high-resolution performance counter.
+///
+/// The performance counter has a much finer resolution than [`Instant`], but
+/// querying it is more expensive, so it is best suited to profiling short
+/// operations. Its frequency is fixed at boot, and is recorded alongside each
+/// sample so that samples can be converted into [`Duration`]s.
+///
+/// This may be used at any `IRQL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PerformanceCounter {
+    count: u64,
+    frequency: u64,
+}
+
+impl PerformanceCounter {
+    /// Returns the current value of the performance counter
+    #[must_use]
+    pub fn now() -> Self {
+        let mut frequency = LARGE_INTEGER::default();
+        let count;
+        // SAFETY: `frequency` is valid for the duration of the call.
+        // `KeQueryPerformanceCounter` can be called at any `IRQL`.
+        unsafe {
+            count = KeQueryPerformanceCounter(&mut frequency);
+        }
+
+        let count_value;
+        // SAFETY: Every field of `LARGE_INTEGER` is an integer, so reading any of them
+        // is sound.
+        unsafe {
+            count_value = count.QuadPart;
+        }
+        let frequency_value;
+        // SAFETY: See above.
+        unsafe {
+            frequency_value = frequency.QuadPart;
+        }
+        // The performance counter and its frequency are never negative
+        Self {
+            count: u64::try_from(count_value).unwrap_or_default(),
+            frequency: u64::try_from(frequency_value).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the raw value of the performance counter, in ticks
+    #[must_use]
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the frequency of the performance counter, in ticks per second
+    #[must_use]
+    pub const fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    /// Returns the amount of time elapsed from `earlier` to `self`, or zero if
+    /// `earlier` is later than `self`
+    #[must_use]
+    pub fn duration_since(&self, earlier: Self) -> Duration {
+        const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+        if self.frequency == 0 {
+            return Duration::ZERO;
+        }
+        let ticks = u128::from(self.count.saturating_sub(earlier.count));
+        let nanoseconds = ticks * NANOSECONDS_PER_SECOND / u128::from(self.frequency);
+        Duration::from_nanos(u64::try_from(nanoseconds).unwrap_or(u64::MAX))
+    }
+
+    /// Returns the amount of time elapsed since this sample was taken
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+}
+
+/// Returns the unbiased interrupt time: the time since the system was booted,
+/// excluding time spent in sleep or hibernation
+///
+/// This may be called at any `IRQL`.
+#[must_use]
+pub fn unbiased_interrupt_time() -> Duration {
+    let intervals;
+    // SAFETY: `KeQueryUnbiasedInterruptTime` can be called at any `IRQL`.
+    unsafe {
+        intervals = KeQueryUnbiasedInterruptTime();
+    }
+    duration_from_intervals(intervals)
+}
+
+/// Returns the amount of time that the interrupt time is incremented by on
+/// every clock tick
+///
+/// This may be called at any `IRQL`.
+#[must_use]
+pub fn time_increment() -> Duration {
+    let intervals;
+    // SAFETY: `KeQueryTimeIncrement` can be called at any `IRQL`.
+    unsafe {
+        intervals = KeQueryTimeIncrement();
+    }
+    duration_from_intervals(u64::from(intervals))
+}
+
+/// Returns the number of clock ticks since the system was booted
+///
+/// This is equivalent to `KeQueryTickCount`, which is a macro that is not
+/// available through `wdk-sys`. The tick count is derived from the interrupt
+/// time and [`time_increment()`]. Prefer [`Instant`] for measuring time, since
+/// the length of a tick varies between systems.
+///
+/// This may be called at any `IRQL`.
+#[must_use]
+pub fn tick_count() -> u64 {
+    let mut qpc_time_stamp = 0;
+    let intervals;
+    // SAFETY: `qpc_time_stamp` is valid for the duration of the call.
+    // `KeQueryInterruptTimePrecise` can be called at any `IRQL`.
+    unsafe {
+        intervals = KeQueryInterruptTimePrecise(&mut qpc_time_stamp);
+    }
+
+    let increment;
+    // SAFETY: `KeQueryTimeIncrement` can be called at any `IRQL`.
+    unsafe {
+        increment = KeQueryTimeIncrement();
+    }
+    intervals / u64::from(increment.max(1))
+}
@@ -0,0 +1,214 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Kernel `EX_TIMER` timers, which run a closure when they expire.
+//!
+//! Unlike [`wdf::Timer`](crate::wdf::Timer), an [`ExTimer`] can be created as
+//! a high-resolution timer, whose expiration is not rounded to the system
+//! clock interval (typically around 15ms), or as a no-wake timer, which does
+//! not wake the processor from an idle state to expire.
+
+use core::time::Duration;
+
+use wdk_sys::{
+    ntddk::{ExAllocateTimer, ExCancelTimer, ExDeleteTimer, ExSetTimer},
+    EX_TIMER_HIGH_RESOLUTION,
+    EX_TIMER_NO_WAKE,
+    NTSTATUS,
+    PEX_TIMER,
+    PVOID,
+    STATUS_INSUFFICIENT_RESOURCES,
+    ULONG,
+};
+
+use crate::{
+    pool::NonPagedBox,
+    time::{interval_count, relative_timeout},
+};
+
+/// The kind of an [`ExTimer`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimerKind {
+    /// A timer whose expiration is rounded to the system clock interval
+    #[default]
+    Standard,
+    /// A timer that expires as close as possible to its due time, by
+    /// temporarily increasing the system clock rate while it is set. This is
+    /// needed for periodic timers shorter than the system clock interval, but
+    /// it increases power consumption.
+    HighResolution,
+    /// A timer that does not wake the processor from an idle state, and
+    /// expires the next time the processor wakes after its due time
+    NoWake,
+}
+
+impl TimerKind {
+    const fn attributes(self) -> ULONG {
+        match self {
+            Self::Standard => 0,
+            Self::HighResolution => EX_TIMER_HIGH_RESOLUTION,
+            Self::NoWake => EX_TIMER_NO_WAKE,
+        }
+    }
+}
+
+/// Kernel `EX_TIMER`.
+///
+/// An [`ExTimer`] runs its callback at `IRQL` = `DISPATCH_LEVEL` every time it
+/// expires. Timers are set with [`ExTimer::start()`] or
+/// [`ExTimer::start_periodic()`], and can be canceled with
+/// [`ExTimer::cancel()`]. The callback may run on any processor, and for
+/// periodic timers, may run concurrently with itself.
+///
+/// Closure types cannot be named, so to store an [`ExTimer`] in a struct, use
+/// a function pointer or a boxed closure (ex.
+/// `ExTimer<Box<dyn Fn() + Send + Sync>>`) as the callback.
+pub struct ExTimer<F> {
+    timer: PEX_TIMER,
+    // Only accessed by the timer's callback, through its context
+    _callback: NonPagedBox<F>,
+}
+
+// SAFETY: `EX_TIMER`s can be set, canceled and deleted from any thread, and
+// the callback is required to be `Send` and `Sync`.
+unsafe impl<F: Send + Sync> Send for ExTimer<F> {}
+
+// SAFETY: See above. The callback is only ever accessed through a shared
+// reference.
+unsafe impl<F: Send + Sync> Sync for ExTimer<F> {}
+
+impl<F> ExTimer<F>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    /// Try to construct an [`ExTimer`] of the given kind, that runs `callback`
+    /// every time it expires
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the timer or its callback cannot
+    /// be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn try_new(kind: TimerKind, callback: F) -> Result<Self, NTSTATUS> {
+        let callback = NonPagedBox::try_new(callback)?;
+
+        let timer;
+        // SAFETY: `timer_callback::<F>` expects its context to be an `F`, which
+        // `callback` points to. `callback` is kept alive until the timer is deleted.
+        unsafe {
+            timer = ExAllocateTimer(
+                Some(timer_callback::<F>),
+                callback.as_ptr().cast(),
+                kind.attributes(),
+            );
+        }
+        if timer.is_null() {
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+
+        Ok(Self {
+            timer,
+            _callback: callback,
+        })
+    }
+}
+
+impl<F> ExTimer<F> {
+    /// Set the timer to expire once, after `due_time`. If the timer is already
+    /// set, it is canceled first.
+    ///
+    /// Returns `true` if the timer was already set.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    // Timers are commonly restarted without caring whether they were set
+    #[allow(clippy::must_use_candidate)]
+    pub fn start(&self, due_time: Duration) -> bool {
+        self.set(due_time, 0)
+    }
+
+    /// Set the timer to expire after `due_time`, and then every `period`
+    /// until it is canceled. If the timer is already set, it is canceled
+    /// first.
+    ///
+    /// Returns `true` if the timer was already set.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    // Timers are commonly restarted without caring whether they were set
+    #[allow(clippy::must_use_candidate)]
+    pub fn start_periodic(&self, due_time: Duration, period: Duration) -> bool {
+        self.set(due_time, interval_count(period))
+    }
+
+    /// Cancel the timer. A callback that is already running is not waited
+    /// for.
+    ///
+    /// Returns `true` if the timer was set.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    // Timers are commonly canceled without caring whether they were set
+    #[allow(clippy::must_use_candidate)]
+    pub fn cancel(&self) -> bool {
+        let result;
+        // SAFETY: `timer` was allocated by `ExAllocateTimer`, and is only deleted when
+        // this `ExTimer` is dropped. `Parameters` is reserved and must be null.
+        unsafe {
+            result = ExCancelTimer(self.timer, core::ptr::null_mut());
+        }
+        result != 0
+    }
+
+    fn set(&self, due_time: Duration, period: i64) -> bool {
+        let result;
+        // SAFETY: `timer` was allocated by `ExAllocateTimer`, and is only deleted when
+        // this `ExTimer` is dropped. The due time is relative, and null `Parameters`
+        // select the default tolerance.
+        unsafe {
+            result = ExSetTimer(
+                self.timer,
+                relative_timeout(due_time),
+                period,
+                core::ptr::null_mut(),
+            );
+        }
+        result != 0
+    }
+}
+
+impl<F> Drop for ExTimer<F> {
+    /// Cancel and delete the timer, waiting for any running callback to
+    /// return
+    ///
+    /// This must happen at `IRQL` <= `APC_LEVEL`, and not from within the
+    /// timer's own callback.
+    fn drop(&mut self) {
+        // SAFETY: `timer` was allocated by `ExAllocateTimer`, and is not used after
+        // this. Waiting guarantees that the callback is no longer running once this
+        // returns, so `callback` can then be freed.
+        unsafe {
+            let _ = ExDeleteTimer(
+                self.timer,
+                u8::from(true),
+                u8::from(true),
+                core::ptr::null_mut(),
+            );
+        }
+    }
+}
+
+/// The `EXT_CALLBACK` of timers created by [`ExTimer::try_new()`]
+///
+/// # Safety
+///
+/// `context` must point to an `F` that is valid for the duration of the call.
+unsafe extern "C" fn timer_callback<F>(_timer: PEX_TIMER, context: PVOID)
+where
+    F: Fn(),
+{
+    let callback = context.cast::<F>().cast_const();
+    // SAFETY: The caller guarantees that `context` points to a valid `F`.
+    unsafe {
+        (*callback)();
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+use wdk_sys::{
+    _WDF_EXECUTION_LEVEL,
+    _WDF_SYNCHRONIZATION_SCOPE,
+    WDF_EXECUTION_LEVEL,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_SYNCHRONIZATION_SCOPE,
+};
+
+use super::{context::object_attributes, object::drop_context, ObjectContext, WdfObject};
+
+/// The maximum `IRQL` at which the framework calls the event callbacks of an
+/// object
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionLevel {
+    /// Use the execution level of the parent object
+    #[default]
+    InheritFromParent,
+    /// Call the event callbacks at `IRQL` = `PASSIVE_LEVEL`
+    Passive,
+    /// Call the event callbacks at `IRQL` <= `DISPATCH_LEVEL`
+    Dispatch,
+}
+
+impl ExecutionLevel {
+    const fn as_raw(self) -> WDF_EXECUTION_LEVEL {
+        match self {
+            Self::InheritFromParent => _WDF_EXECUTION_LEVEL::WdfExecutionLevelInheritFromParent,
+            Self::Passive => _WDF_EXECUTION_LEVEL::WdfExecutionLevelPassive,
+            Self::Dispatch => _WDF_EXECUTION_LEVEL::WdfExecutionLevelDispatch,
+        }
+    }
+}
+
+/// How the framework synchronizes the event callbacks of an object with each
+/// other
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SynchronizationScope {
+    /// Use the synchronization scope of the parent object
+    #[default]
+    InheritFromParent,
+    /// Synchronize the callbacks of the object with those of all objects under
+    /// the same device
+    Device,
+    /// Synchronize the callbacks of the object with those of all objects under
+    /// the same queue
+    Queue,
+    /// Do not synchronize the callbacks of the object
+    None,
+}
+
+impl SynchronizationScope {
+    const fn as_raw(self) -> WDF_SYNCHRONIZATION_SCOPE {
+        match self {
+            Self::InheritFromParent => {
+                _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeInheritFromParent
+            }
+            Self::Device => _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeDevice,
+            Self::Queue => _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeQueue,
+            Self::None => _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeNone,
+        }
+    }
+}
+
+/// Attributes of a WDF object, passed to the constructors of WDF object
+/// wrappers such as [`SpinLock::try_new()`](super::SpinLock::try_new).
+///
+/// [`ObjectAttributes::new()`] is the equivalent of
+/// `WDF_OBJECT_ATTRIBUTES_INIT`, and the other methods replace setting the
+/// fields of `WDF_OBJECT_ATTRIBUTES` directly. The `EvtCleanupCallback` and
+/// `EvtDestroyCallback` of an object are registered as closures with
+/// [`WdfObject::on_cleanup()`] and [`WdfObject::on_destroy()`] once it is
+/// created, and resources stored in its context type are dropped on cleanup.
+///
+/// The lifetime `'p` is the lifetime of the borrow of the parent object set
+/// with [`ObjectAttributes::parent()`]. Wrappers constructed with the
+/// attributes have the same lifetime, so they cannot outlive their parent.
+///
+/// ```ignore
+/// let spin_lock = SpinLock::try_new(0, ObjectAttributes::new().parent(&device))?;
+/// ```
+#[derive(Clone, Copy)]
+#[must_use]
+pub struct ObjectAttributes<'p> {
+    attributes: WDF_OBJECT_ATTRIBUTES,
+    _parent: PhantomData<&'p ()>,
+}
+
+impl ObjectAttributes<'static> {
+    /// Construct [`ObjectAttributes`] without a parent, which inherit their
+    /// execution level and synchronization scope from the parent object
+    ///
+    /// Objects without a parent are parented to the driver object, which lives
+    /// until the driver is unloaded.
+    pub fn new() -> Self {
+        Self {
+            attributes: object_attributes(core::ptr::null_mut()),
+            _parent: PhantomData,
+        }
+    }
+}
+
+impl Default for ObjectAttributes<'static> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjectAttributes<'_> {
+    /// Set the parent of the object, which deletes the object when it is
+    /// deleted
+    pub fn parent(self, parent: &impl WdfObject) -> ObjectAttributes<'_> {
+        ObjectAttributes {
+            attributes: WDF_OBJECT_ATTRIBUTES {
+                ParentObject: parent.as_object_handle(),
+                ..self.attributes
+            },
+            _parent: PhantomData,
+        }
+    }
+
+    /// Set the maximum `IRQL` at which the framework calls the event callbacks
+    /// of the object
+    pub const fn execution_level(mut self, execution_level: ExecutionLevel) -> Self {
+        self.attributes.ExecutionLevel = execution_level.as_raw();
+        self
+    }
+
+    /// Set how the framework synchronizes the event callbacks of the object
+    pub const fn synchronization_scope(
+        mut self,
+        synchronization_scope: SynchronizationScope,
+    ) -> Self {
+        self.attributes.SynchronizationScope = synchronization_scope.as_raw();
+        self
+    }
+
+    /// Allocate a context of type `T` along with the object, equivalent to
+    /// `WDF_OBJECT_ATTRIBUTES_SET_CONTEXT_TYPE`
+    ///
+    /// The framework zero-initializes the context, and it is dropped from the
+    /// object's `EvtCleanupCallback`. To attach a context with any other
+    /// initial value, use [`ObjectContext::attach()`] once the object is
+    /// created instead.
+    ///
+    /// # Safety
+    ///
+    /// A value of type `T` whose bytes are all zero must be a valid `T`.
+    pub unsafe fn context_type<T: ObjectContext>(mut self) -> Self {
+        self.attributes.ContextTypeInfo = T::type_info().as_raw();
+        self.attributes.EvtCleanupCallback = Some(drop_context::<T>);
+        self
+    }
+
+    /// Returns the `WDF_OBJECT_ATTRIBUTES` built by these [`ObjectAttributes`]
+    #[must_use]
+    pub const fn as_raw(&self) -> &WDF_OBJECT_ATTRIBUTES {
+        &self.attributes
+    }
+
+    /// Returns a mutable reference to the `WDF_OBJECT_ATTRIBUTES`, to pass to
+    /// WDF functions which take a non-`const` pointer to them
+    pub(crate) fn as_raw_mut(&mut self) -> &mut WDF_OBJECT_ATTRIBUTES {
+        &mut self.attributes
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Crate-internal ownership of the WDF objects created by wrappers, which
+//! bounds the lifetime of the wrappers by the lifetime of their parent.
+
+use core::marker::PhantomData;
+
+use wdk_sys::{macros, WDFOBJECT};
+
+/// Ownership of a WDF object created by a wrapper, whose lifetime is bounded
+/// by the parent object's lifetime `'p`.
+///
+/// By default, the object is deleted when the [`ChildObject`] is dropped, so
+/// wrappers that hold one delete their WDF object explicitly when they are
+/// dropped. The object can instead be left to be deleted along with its
+/// parent, in which case the wrapper's lifetime is no longer bounded by the
+/// parent's.
+pub struct ChildObject<'p> {
+    object: WDFOBJECT,
+    delete_on_drop: bool,
+    _parent: PhantomData<&'p ()>,
+}
+
+// SAFETY: WDF objects can be deleted from any thread.
+unsafe impl Send for ChildObject<'_> {}
+
+// SAFETY: `ChildObject` has no methods that use the handle through `&self`.
+unsafe impl Sync for ChildObject<'_> {}
+
+impl ChildObject<'_> {
+    /// Take ownership of `object`, which is deleted when the returned
+    /// [`ChildObject`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// `object` must be a valid handle to a WDF object created by the driver,
+    /// which is not deleted by anything else, and whose parent lives at least
+    /// as long as the returned [`ChildObject`].
+    pub const unsafe fn new(object: WDFOBJECT) -> Self {
+        Self {
+            object,
+            delete_on_drop: true,
+            _parent: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the object is only deleted along with its parent
+    #[must_use]
+    pub const fn is_parent_owned(&self) -> bool {
+        !self.delete_on_drop
+    }
+}
+
+impl ChildObject<'static> {
+    /// Refer to `object` without taking ownership of it, so that it is only
+    /// deleted along with its parent
+    #[must_use]
+    pub const fn parent_owned(object: WDFOBJECT) -> Self {
+        Self {
+            object,
+            delete_on_drop: false,
+            _parent: PhantomData,
+        }
+    }
+}
+
+impl Drop for ChildObject<'_> {
+    fn drop(&mut self) {
+        if self.delete_on_drop {
+            // SAFETY: The contract of `ChildObject::new` guarantees that `object` is a
+            // valid handle that nothing else deletes, and it is not used after this.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(WdfObjectDelete, self.object);
+            }
+        }
+    }
+}
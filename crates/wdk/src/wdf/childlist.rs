@@ -0,0 +1,449 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{any::TypeId, marker::PhantomData, ops::Deref};
+
+use wdk_sys::{
+    macros,
+    BOOLEAN,
+    NTSTATUS,
+    PWDFDEVICE_INIT,
+    PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    STATUS_NOT_SUPPORTED,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFCHILDLIST,
+    WDFDEVICE,
+    WDFOBJECT,
+    WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    WDF_CHILD_LIST_CONFIG,
+};
+
+use super::{
+    context::{attach_closure_in, closure_in, try_closure_in, ClosureSlot},
+    ContextTypeInfo,
+    Device,
+    DeviceInit,
+    WdfObject,
+};
+use crate::nt_success;
+
+// `WDF_CHILD_LIST_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const CHILD_LIST_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_CHILD_LIST_CONFIG>() as ULONG;
+
+/// The context type of the [`ChildListCallbacks`] attached to devices
+static CHILD_LIST_CALLBACKS_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::ChildListCallbacks",
+    1,
+    &CHILD_LIST_CALLBACKS_CONTEXT_TYPE_INFO,
+);
+
+/// WDF Child List.
+///
+/// A [`ChildList`] is the list of child devices that a bus driver enumerates
+/// dynamically. Each child is identified by a description of type `T`, and
+/// the framework creates a child device for every description reported
+/// present with [`ChildList::add_or_update_as_present()`], by calling
+/// [`ChildListCallbacks::create_device()`]. Descriptions are compared with
+/// [`PartialEq`], so reporting a child that is already present does not
+/// create another device.
+///
+/// Bus drivers rescan their bus with [`ChildList::scan()`], which reports
+/// every child that is not reported present during the scan as missing once
+/// the scan ends.
+///
+/// The default child list of a device is set up with
+/// [`DeviceBuilder::child_list_callbacks()`](super::DeviceBuilder::child_list_callbacks),
+/// and retrieved with [`Device::child_list()`].
+pub struct ChildList<T> {
+    wdf_child_list: WDFCHILDLIST,
+    description: PhantomData<fn(T) -> T>,
+}
+
+impl<T> Clone for ChildList<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ChildList<T> {}
+
+// SAFETY: `WDFCHILDLIST` handles can be used from any thread, and the
+// framework synchronizes access to the descriptions in the list.
+unsafe impl<T: Send> Send for ChildList<T> {}
+
+// SAFETY: The framework synchronizes access to the descriptions in the list.
+unsafe impl<T: Send> Sync for ChildList<T> {}
+
+// SAFETY: The default child list of a device lives as long as the device,
+// which outlasts the callbacks and devices that `ChildList`s are retrieved
+// from.
+unsafe impl<T> WdfObject for ChildList<T> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_child_list.cast()
+    }
+}
+
+impl<T: Copy + PartialEq> ChildList<T> {
+    /// Wrap an existing WDF Child List
+    ///
+    /// # Safety
+    ///
+    /// `wdf_child_list` must be a valid handle to a WDF Child List whose
+    /// identification descriptions are `T`s, and must remain valid for the
+    /// lifetime of the returned [`ChildList`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_child_list: WDFCHILDLIST) -> Self {
+        Self {
+            wdf_child_list,
+            description: PhantomData,
+        }
+    }
+
+    /// Returns the raw `WDFCHILDLIST` handle wrapped by this [`ChildList`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFCHILDLIST {
+        self.wdf_child_list
+    }
+
+    /// Returns the device that the child list belongs to
+    #[must_use]
+    pub fn device(&self) -> Device {
+        let wdf_device;
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, and the
+        // framework keeps the child list valid while it is used.
+        unsafe {
+            wdf_device = macros::call_unsafe_wdf_function_binding!(
+                WdfChildListGetDevice,
+                self.wdf_child_list
+            );
+        }
+        // SAFETY: The device of a child list lives at least as long as the child list.
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Begin a scan of the bus, which reports every child that is not reported
+    /// present during the scan as missing once the returned [`ChildListScan`]
+    /// is dropped
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn scan(&self) -> ChildListScan<'_, T> {
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, and the
+        // framework keeps the child list valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfChildListBeginScan, self.wdf_child_list);
+        }
+        ChildListScan { child_list: self }
+    }
+
+    /// Report the child identified by `description` as present, so that the
+    /// framework creates a device for it unless it is already present
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate storage for the description. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFChildList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nf-wdfchildlist-wdfchildlistaddorupdatechilddescriptionaspresent#return-value)
+    pub fn add_or_update_as_present(&self, description: &T) -> Result<(), NTSTATUS> {
+        let mut identification_description = IdentificationDescription::new(*description);
+
+        let nt_status;
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, and the
+        // framework keeps the child list valid while it is used. The description was
+        // created with the size that the child list was configured with, and is valid
+        // for the duration of the call, which copies it.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfChildListAddOrUpdateChildDescriptionAsPresent,
+                self.wdf_child_list,
+                &mut identification_description.header,
+                core::ptr::null_mut()
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Report the child identified by `description` as missing, so that the
+    /// framework removes its device
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no child identified by `description` is in the child list. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFChildList Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfchildlist/nf-wdfchildlist-wdfchildlistupdatechilddescriptionasmissing#return-value)
+    pub fn update_as_missing(&self, description: &T) -> Result<(), NTSTATUS> {
+        let mut identification_description = IdentificationDescription::new(*description);
+
+        let nt_status;
+        // SAFETY: `wdf_child_list` is a private member of `ChildList`, and the
+        // framework keeps the child list valid while it is used. The description was
+        // created with the size that the child list was configured with, and is valid
+        // for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfChildListUpdateChildDescriptionAsMissing,
+                self.wdf_child_list,
+                &mut identification_description.header
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+}
+
+/// A scan of a [`ChildList`], begun by [`ChildList::scan()`]. Children are
+/// reported present during the scan through the [`ChildList`] that the scan
+/// dereferences to.
+///
+/// Dropping the [`ChildListScan`] ends the scan, which reports every child
+/// that was not reported present during the scan as missing.
+#[must_use]
+pub struct ChildListScan<'a, T: Copy + PartialEq> {
+    child_list: &'a ChildList<T>,
+}
+
+impl<T: Copy + PartialEq> Deref for ChildListScan<'_, T> {
+    type Target = ChildList<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.child_list
+    }
+}
+
+impl<T: Copy + PartialEq> Drop for ChildListScan<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The child list is valid while it is borrowed, and a scan was begun
+        // on it by `ChildList::scan()`.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfChildListEndScan,
+                self.child_list.wdf_child_list
+            );
+        }
+    }
+}
+
+/// Callbacks of the default [`ChildList`] of a bus driver's [`Device`], set
+/// with
+/// [`DeviceBuilder::child_list_callbacks()`](super::DeviceBuilder::child_list_callbacks).
+pub trait ChildListCallbacks: Send + Sync + 'static {
+    /// The description that identifies each child of the list, such as its
+    /// address on the bus. The framework stores copies of the descriptions
+    /// reported present, and compares them with [`PartialEq`].
+    type Description: Copy + PartialEq + Send + Sync + 'static;
+
+    /// Called at `IRQL` = `PASSIVE_LEVEL` to create the device of a child that
+    /// was reported present, which must be created from `child_init`,
+    /// typically with a [`PdoBuilder`](super::PdoBuilder)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the creation of the child's device, and the
+    /// framework reports the child as missing.
+    fn create_device(
+        &self,
+        child_list: &ChildList<Self::Description>,
+        description: &Self::Description,
+        child_init: &mut DeviceInit,
+    ) -> Result<(), NTSTATUS>;
+}
+
+/// [`ChildListCallbacks`] of devices created without a child list, which are
+/// never called
+pub struct NoChildListCallbacks;
+
+impl ChildListCallbacks for NoChildListCallbacks {
+    type Description = ();
+
+    fn create_device(
+        &self,
+        _child_list: &ChildList<()>,
+        _description: &(),
+        _child_init: &mut DeviceInit,
+    ) -> Result<(), NTSTATUS> {
+        Err(STATUS_NOT_SUPPORTED)
+    }
+}
+
+impl Device {
+    /// Returns the default child list of the device, if it was created with
+    /// [`ChildListCallbacks`] whose descriptions are `T`s
+    #[must_use]
+    pub fn child_list<T: Copy + PartialEq + 'static>(&self) -> Option<ChildList<T>> {
+        // SAFETY: `self` is a valid device handle, and every `ChildListState` starts
+        // with the `TypeId` of its descriptions.
+        let description_type =
+            unsafe { try_closure_in::<ChildListCallbacksSlot, TypeId>(self.as_raw().cast()) }?;
+        if *description_type != TypeId::of::<T>() {
+            return None;
+        }
+
+        let wdf_child_list;
+        // SAFETY: `self` is a valid device handle, which was created with a default
+        // child list.
+        unsafe {
+            wdf_child_list =
+                macros::call_unsafe_wdf_function_binding!(WdfFdoGetDefaultChildList, self.as_raw());
+        }
+        // SAFETY: The default child list of the device lives as long as the device, and
+        // its descriptions are `T`s.
+        Some(unsafe { ChildList::from_raw(wdf_child_list) })
+    }
+}
+
+/// The identification description passed to WDF for a `T`, which starts with
+/// the header that WDF expects
+#[repr(C)]
+struct IdentificationDescription<T> {
+    header: WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    description: T,
+}
+
+impl<T> IdentificationDescription<T> {
+    // `IdentificationDescription`s of child descriptions are much smaller than
+    // `ULONG::MAX` bytes
+    #[allow(clippy::cast_possible_truncation)]
+    const SIZE: ULONG = core::mem::size_of::<Self>() as ULONG;
+
+    const fn new(description: T) -> Self {
+        Self {
+            header: WDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER {
+                IdentificationDescriptionSize: Self::SIZE,
+            },
+            description,
+        }
+    }
+
+    /// Returns the description of the identification description that `header`
+    /// belongs to
+    ///
+    /// # Safety
+    ///
+    /// `header` must point to the header of a valid
+    /// [`IdentificationDescription<T>`](IdentificationDescription), which
+    /// outlives the returned reference.
+    unsafe fn from_header<'a>(header: PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER) -> &'a T {
+        // SAFETY: The caller guarantees that `header` is the first field of an
+        // `IdentificationDescription<T>`, which is `repr(C)`.
+        unsafe { &(*header.cast::<Self>()).description }
+    }
+}
+
+/// The state attached to devices with a default child list. It starts with
+/// the `TypeId` of the descriptions, so that [`Device::child_list()`] can check
+/// the type of the descriptions without knowing `L`.
+#[repr(C)]
+struct ChildListState<L: ChildListCallbacks> {
+    description_type: TypeId,
+    callbacks: L,
+}
+
+/// The [`ClosureSlot`] of the [`ChildListState`] attached to devices
+struct ChildListCallbacksSlot;
+
+impl ClosureSlot for ChildListCallbacksSlot {
+    fn type_info() -> &'static ContextTypeInfo {
+        &CHILD_LIST_CALLBACKS_CONTEXT_TYPE_INFO
+    }
+}
+
+/// Returns the `WDF_CHILD_LIST_CONFIG` of devices with `L` as their child list
+/// callbacks, initialized the same way as by `WDF_CHILD_LIST_CONFIG_INIT`
+pub(super) fn child_list_config<L: ChildListCallbacks>() -> WDF_CHILD_LIST_CONFIG {
+    WDF_CHILD_LIST_CONFIG {
+        Size: CHILD_LIST_CONFIG_SIZE,
+        IdentificationDescriptionSize: IdentificationDescription::<L::Description>::SIZE,
+        EvtChildListCreateDevice: Some(evt_child_list_create_device::<L>),
+        EvtChildListIdentificationDescriptionCompare: Some(
+            evt_child_list_identification_description_compare::<L::Description>,
+        ),
+        ..Default::default()
+    }
+}
+
+/// Attach `callbacks` to `wdf_device`, for the callbacks of
+/// [`child_list_config::<L>()`](child_list_config) to call
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that was created with the
+/// default child list configuration of `L`, and that does not already have
+/// child list callbacks attached.
+///
+/// # Errors
+///
+/// This function will return an error if WDF fails to allocate storage for
+/// `callbacks`. The error variant will contain a [`NTSTATUS`] of the failure.
+pub(super) unsafe fn attach_child_list_callbacks<L: ChildListCallbacks>(
+    wdf_device: WDFDEVICE,
+    callbacks: L,
+) -> Result<(), NTSTATUS> {
+    let state = ChildListState {
+        description_type: TypeId::of::<L::Description>(),
+        callbacks,
+    };
+    // SAFETY: The caller guarantees that `wdf_device` is a valid handle without
+    // child list callbacks attached.
+    unsafe { attach_closure_in::<ChildListCallbacksSlot, _>(wdf_device.cast(), state) }
+}
+
+/// The `EvtChildListCreateDevice` of default child lists created with
+/// [`ChildListCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_child_list` must be a valid handle to the default child list of a
+/// device that an `L` was attached to, `header` must point to one of its
+/// descriptions, and `child_init` must be a valid `WDFDEVICE_INIT` of a child
+/// device.
+unsafe extern "C" fn evt_child_list_create_device<L: ChildListCallbacks>(
+    wdf_child_list: WDFCHILDLIST,
+    header: PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    child_init: PWDFDEVICE_INIT,
+) -> NTSTATUS {
+    // SAFETY: The caller guarantees that `wdf_child_list` is a valid child list of
+    // `L::Description`s.
+    let child_list = unsafe { ChildList::<L::Description>::from_raw(wdf_child_list) };
+    // SAFETY: The caller guarantees that an `L` was attached to the device of the
+    // child list, which outlives the child list.
+    let state = unsafe {
+        closure_in::<ChildListCallbacksSlot, ChildListState<L>>(child_list.device().as_raw().cast())
+    };
+    // SAFETY: The caller guarantees that `header` points to a description of the
+    // child list, which remains valid for the duration of the call.
+    let description = unsafe { IdentificationDescription::from_header(header) };
+    // SAFETY: The caller guarantees that `child_init` is a valid `WDFDEVICE_INIT`,
+    // which the framework owns until the call returns.
+    let mut child_init = unsafe { DeviceInit::from_raw(child_init) };
+
+    state
+        .callbacks
+        .create_device(&child_list, description, &mut child_init)
+        .map_or_else(|nt_status| nt_status, |()| STATUS_SUCCESS)
+}
+
+/// The `EvtChildListIdentificationDescriptionCompare` of default child lists
+/// created with [`ChildListCallbacks`]
+///
+/// # Safety
+///
+/// `first` and `second` must point to descriptions of a child list of `T`s.
+unsafe extern "C" fn evt_child_list_identification_description_compare<T: PartialEq>(
+    _wdf_child_list: WDFCHILDLIST,
+    first: PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+    second: PWDF_CHILD_IDENTIFICATION_DESCRIPTION_HEADER,
+) -> BOOLEAN {
+    // SAFETY: The caller guarantees that `first` points to a description of `T`,
+    // which remains valid for the duration of the call.
+    let first = unsafe { IdentificationDescription::<T>::from_header(first) };
+    // SAFETY: The caller guarantees that `second` points to a description of `T`,
+    // which remains valid for the duration of the call.
+    let second = unsafe { IdentificationDescription::<T>::from_header(second) };
+    BOOLEAN::from(first == second)
+}
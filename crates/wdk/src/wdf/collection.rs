@@ -0,0 +1,416 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{iter::FusedIterator, marker::PhantomData};
+
+use wdk_sys::{
+    macros,
+    NTSTATUS,
+    ULONG,
+    WDFCHILDLIST,
+    WDFCMRESLIST,
+    WDFCOLLECTION,
+    WDFCOMMONBUFFER,
+    WDFDEVICE,
+    WDFDMAENABLER,
+    WDFDMATRANSACTION,
+    WDFDPC,
+    WDFDRIVER,
+    WDFFILEOBJECT,
+    WDFINTERRUPT,
+    WDFIOTARGET,
+    WDFKEY,
+    WDFMEMORY,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFSPINLOCK,
+    WDFSTRING,
+    WDFTIMER,
+    WDFWAITLOCK,
+    WDFWORKITEM,
+};
+
+use super::{child::ChildObject, ObjectAttributes, WdfObject};
+use crate::nt_success;
+
+/// A raw handle to a WDF object, which can be stored in a [`Collection`].
+///
+/// # Safety
+///
+/// The handle must be a pointer to a WDF object, so that converting it to and
+/// from a `WDFOBJECT` does not change it.
+pub unsafe trait WdfHandle: Copy {
+    /// Returns the handle as a `WDFOBJECT`
+    fn as_object_handle(self) -> WDFOBJECT;
+
+    /// Returns the `WDFOBJECT` `object` as this type of handle
+    fn from_object_handle(object: WDFOBJECT) -> Self;
+}
+
+macro_rules! impl_wdf_handle {
+    ($($handle:ty),+ $(,)?) => {
+        $(
+            // SAFETY: Every WDF handle type is a pointer to a WDF object.
+            unsafe impl WdfHandle for $handle {
+                fn as_object_handle(self) -> WDFOBJECT {
+                    self.cast()
+                }
+
+                fn from_object_handle(object: WDFOBJECT) -> Self {
+                    object.cast()
+                }
+            }
+        )+
+    };
+}
+
+impl_wdf_handle!(
+    WDFCHILDLIST,
+    WDFCMRESLIST,
+    WDFCOLLECTION,
+    WDFCOMMONBUFFER,
+    WDFDEVICE,
+    WDFDMAENABLER,
+    WDFDMATRANSACTION,
+    WDFDPC,
+    WDFDRIVER,
+    WDFFILEOBJECT,
+    WDFINTERRUPT,
+    WDFIOTARGET,
+    WDFKEY,
+    WDFMEMORY,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDFSPINLOCK,
+    WDFSTRING,
+    WDFTIMER,
+    WDFWAITLOCK,
+    WDFWORKITEM,
+);
+
+/// WDF Collection.
+///
+/// A collection is a list of handles to WDF objects of type `T`, each of which
+/// the collection holds a reference to, so that the object is not freed while
+/// it is in the collection, even if it is deleted.
+///
+/// The framework does not synchronize access to collections. [`Collection`]
+/// only mutates the collection through `&mut self`, so a collection that is
+/// shared between callbacks must be protected by a lock, such as a
+/// [`SpinLock`](super::SpinLock) or a [`WaitLock`](super::WaitLock). Handles
+/// read from the collection are only valid while the object is in it, unless
+/// the driver holds another reference to the object.
+///
+/// The lifetime `'p` is bounded by the parent object set in the
+/// [`ObjectAttributes`] the collection is constructed with. The collection is
+/// deleted, releasing its references to its items, when the [`Collection`] is
+/// dropped. Use [`Collection::into_parent_owned()`] to leave it to be deleted
+/// with its parent instead.
+///
+/// ```ignore
+/// let mut pending = Collection::<WDFREQUEST>::try_new(ObjectAttributes::new().parent(&device))?;
+/// pending.add(request.as_raw())?;
+/// for request in &pending {
+///     // ...
+/// }
+/// ```
+// `wdf_collection` is named consistently with the handles of the other wrappers
+#[allow(clippy::struct_field_names)]
+pub struct Collection<'p, T: WdfHandle> {
+    wdf_collection: WDFCOLLECTION,
+    object: ChildObject<'p>,
+    _items: PhantomData<T>,
+}
+
+// SAFETY: `WDFCOLLECTION` handles can be used, and deleted, from any thread,
+// and the collection is only mutated through `&mut self`.
+unsafe impl<T: WdfHandle> Send for Collection<'_, T> {}
+
+// SAFETY: `Collection` only reads the collection through `&self`, which the
+// framework allows concurrently as long as it is not mutated.
+unsafe impl<T: WdfHandle> Sync for Collection<'_, T> {}
+
+// SAFETY: `wdf_collection` is a private member of `Collection`, originally
+// created by WDF, and the lifetime of a `Collection` is bounded by its parent.
+unsafe impl<T: WdfHandle> WdfObject for Collection<'_, T> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_collection.cast()
+    }
+}
+
+impl<'p, T: WdfHandle> Collection<'p, T> {
+    /// Try to create an empty collection
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to create the collection. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFCollection Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfcollection/nf-wdfcollection-wdfcollectioncreate#return-value)
+    pub fn try_new(mut attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        let mut wdf_collection: WDFCOLLECTION = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. `wdf_collection` is valid for the duration of the
+        // call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionCreate,
+                attributes.as_raw_mut(),
+                &mut wdf_collection,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            wdf_collection,
+            // SAFETY: `wdf_collection` is a valid handle to the collection that was just
+            // created, which is only deleted by this `ChildObject`, and whose parent lives
+            // for `'p`.
+            object: unsafe { ChildObject::new(wdf_collection.cast()) },
+            _items: PhantomData,
+        })
+    }
+
+    /// Returns the raw `WDFCOLLECTION` handle wrapped by this [`Collection`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFCOLLECTION {
+        self.wdf_collection
+    }
+
+    /// Returns the number of items in the collection
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let count: ULONG;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state.
+        unsafe {
+            count = macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionGetCount,
+                self.wdf_collection,
+            );
+        }
+        count as usize
+    }
+
+    /// Returns whether the collection is empty
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the item at `index`, or `None` if `index` is out of bounds
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        // `index` is less than the count of the collection, which is a `ULONG`
+        #[allow(clippy::cast_possible_truncation)]
+        let index = index as ULONG;
+
+        let object: WDFOBJECT;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `index` is within the bounds of the collection.
+        unsafe {
+            object = macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionGetItem,
+                self.wdf_collection,
+                index,
+            );
+        }
+        Some(T::from_object_handle(object))
+    }
+
+    /// Returns the first item of the collection, or `None` if it is empty
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn first(&self) -> Option<T> {
+        self.get(0)
+    }
+
+    /// Returns the last item of the collection, or `None` if it is empty
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn last(&self) -> Option<T> {
+        self.len().checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    /// Try to add `item` to the end of the collection, which takes a reference
+    /// to it
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate storage for the item. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFCollection Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfcollection/nf-wdfcollection-wdfcollectionadd#return-value)
+    pub fn add(&mut self, item: T) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. The contract of `WdfHandle` guarantees that `item` is a handle to a
+        // WDF object, and `self` is borrowed mutably for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionAdd,
+                self.wdf_collection,
+                item.as_object_handle(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Remove the item at `index` from the collection, releasing the
+    /// collection's reference to it, and return it, or `None` if `index` is
+    /// out of bounds
+    ///
+    /// The returned handle is only valid if the driver holds another reference
+    /// to the object. This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let item = self.get(index)?;
+        // `get` returned an item, so `index` is less than the count of the
+        // collection, which is a `ULONG`
+        #[allow(clippy::cast_possible_truncation)]
+        let index = index as ULONG;
+
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `index` is within the bounds of the collection, and `self` is
+        // borrowed mutably for the duration of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionRemoveItem,
+                self.wdf_collection,
+                index,
+            );
+        }
+        Some(item)
+    }
+
+    /// Remove the first occurrence of `item` from the collection, releasing the
+    /// collection's reference to it, and return whether it was in the
+    /// collection
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn remove_item(&mut self, item: T) -> bool {
+        let object = item.as_object_handle();
+        if !self.iter().any(|other| other.as_object_handle() == object) {
+            return false;
+        }
+
+        // SAFETY: `wdf_collection` is a private member of `Collection`, originally
+        // created by WDF, and this module guarantees that it is always in a valid
+        // state. `object` is in the collection, which WDF would otherwise break into
+        // the debugger for, and `self` is borrowed mutably for the duration of the
+        // call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfCollectionRemove,
+                self.wdf_collection,
+                object,
+            );
+        }
+        true
+    }
+
+    /// Returns an iterator over the items of the collection, from first to
+    /// last
+    ///
+    /// This must be called, and the iterator used, at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn iter(&self) -> CollectionIter<'_, 'p, T> {
+        CollectionIter {
+            collection: self,
+            front: 0,
+            back: self.len(),
+        }
+    }
+
+    /// Leave the WDF collection to be deleted along with its parent, rather
+    /// than when the [`Collection`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// The returned [`Collection`] must not be used after its parent is
+    /// deleted. Storing it in the context of its parent satisfies this, as long
+    /// as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> Collection<'static, T> {
+        let Self {
+            wdf_collection,
+            object,
+            _items,
+        } = self;
+        core::mem::forget(object);
+        Collection {
+            wdf_collection,
+            object: ChildObject::parent_owned(wdf_collection.cast()),
+            _items: PhantomData,
+        }
+    }
+}
+
+impl<'a, 'p, T: WdfHandle> IntoIterator for &'a Collection<'p, T> {
+    type IntoIter = CollectionIter<'a, 'p, T>;
+    type Item = T;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the items of a [`Collection`], returned by
+/// [`Collection::iter()`]
+pub struct CollectionIter<'a, 'p, T: WdfHandle> {
+    collection: &'a Collection<'p, T>,
+    front: usize,
+    back: usize,
+}
+
+impl<T: WdfHandle> Iterator for CollectionIter<'_, '_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        let item = self.collection.get(self.front);
+        self.front += 1;
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: WdfHandle> DoubleEndedIterator for CollectionIter<'_, '_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.collection.get(self.back)
+    }
+}
+
+impl<T: WdfHandle> ExactSizeIterator for CollectionIter<'_, '_, T> {}
+
+impl<T: WdfHandle> FusedIterator for CollectionIter<'_, '_, T> {}
@@ -0,0 +1,302 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    macros,
+    ntddk::KeGetRecommendedSharedDataAlignment,
+    NTSTATUS,
+    PVOID,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+    WDFCOMMONBUFFER,
+    WDFOBJECT,
+    WDF_COMMON_BUFFER_CONFIG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+use super::{child::ChildObject, DmaEnabler, WdfObject};
+use crate::nt_success;
+
+// `WDF_COMMON_BUFFER_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const COMMON_BUFFER_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_COMMON_BUFFER_CONFIG>() as ULONG;
+
+/// The alignment of the start of a [`CommonBuffer`], in both its virtual and
+/// logical address spaces
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommonBufferAlignment {
+    /// The alignment requirement of the DMA enabler's device
+    #[default]
+    Device,
+    /// The size of the largest processor cache line in the system, so that
+    /// the buffer does not share cache lines with other data
+    CacheLine,
+    /// A number of bytes, which must be a power of two
+    Bytes(usize),
+}
+
+impl CommonBufferAlignment {
+    /// Returns the alignment as a `WDF_COMMON_BUFFER_CONFIG`
+    /// `AlignmentRequirement`, which is one less than the alignment in bytes,
+    /// or [`None`] to use the device's alignment requirement
+    fn as_raw(self) -> Result<Option<ULONG>, NTSTATUS> {
+        match self {
+            Self::Device => Ok(None),
+            Self::CacheLine => {
+                // SAFETY: This function has no preconditions.
+                let alignment = unsafe { KeGetRecommendedSharedDataAlignment() };
+                Ok(Some(alignment.max(1) - 1))
+            }
+            Self::Bytes(alignment) => {
+                if !alignment.is_power_of_two() {
+                    return Err(STATUS_INVALID_PARAMETER);
+                }
+                ULONG::try_from(alignment - 1)
+                    .map(Some)
+                    .map_err(|_| STATUS_INVALID_PARAMETER)
+            }
+        }
+    }
+}
+
+/// WDF Common Buffer.
+///
+/// A common buffer is an area of memory that both the driver and the device
+/// can access at any time, such as the descriptor rings of a network or
+/// storage controller. The driver accesses it through its virtual address,
+/// and programs the device with its logical address. The buffer is allocated
+/// when the [`CommonBuffer`] is created, and is freed when it is dropped.
+///
+/// The lifetime `'p` is bounded by the buffer's DMA enabler. Use
+/// [`CommonBuffer::into_parent_owned()`] to leave the buffer to be freed with
+/// its DMA enabler instead.
+///
+/// ```ignore
+/// let ring = CommonBuffer::try_new(&dma_enabler, RING_SIZE, CommonBufferAlignment::CacheLine)?;
+/// registers.write64(RING_BASE_REGISTER_OFFSET, ring.logical_address());
+/// ```
+// `wdf_common_buffer` is named consistently with the handles of the other
+// wrappers
+#[allow(clippy::struct_field_names)]
+pub struct CommonBuffer<'p> {
+    wdf_common_buffer: WDFCOMMONBUFFER,
+    virtual_address: NonNull<u8>,
+    logical_address: u64,
+    length: usize,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFCOMMONBUFFER` handles can be used, and deleted, from any thread,
+// and the buffer is in nonpaged system space.
+unsafe impl Send for CommonBuffer<'_> {}
+
+// SAFETY: `CommonBuffer` only allows its buffer to be accessed through `&self`
+// by the unsafe `CommonBuffer::as_slice()`, whose callers guarantee that it is
+// not mutated.
+unsafe impl Sync for CommonBuffer<'_> {}
+
+// SAFETY: `wdf_common_buffer` is a private member of `CommonBuffer`, originally
+// created by WDF, and the lifetime of a `CommonBuffer` is bounded by its DMA
+// enabler.
+unsafe impl WdfObject for CommonBuffer<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_common_buffer.cast()
+    }
+}
+
+impl<'p> CommonBuffer<'p> {
+    /// Try to allocate a common buffer of `length` bytes for the device of
+    /// `dma_enabler`, aligned to `alignment`
+    ///
+    /// The contents of the buffer are not initialized by the framework. This
+    /// must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `alignment` is a number of bytes that is not a power of two, or does not fit in a `ULONG`, or if WDF fails to allocate the buffer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFCommonBuffer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfcommonbuffer/nf-wdfcommonbuffer-wdfcommonbuffercreatewithconfig#return-value)
+    ///
+    /// # Panics
+    ///
+    /// Panics if WDF returns a null virtual address for the buffer it
+    /// allocated.
+    pub fn try_new(
+        dma_enabler: &'p DmaEnabler,
+        length: usize,
+        alignment: CommonBufferAlignment,
+    ) -> Result<Self, NTSTATUS> {
+        let mut wdf_common_buffer: WDFCOMMONBUFFER = core::ptr::null_mut();
+
+        let nt_status;
+        if let Some(alignment_requirement) = alignment.as_raw()? {
+            let mut common_buffer_config = WDF_COMMON_BUFFER_CONFIG {
+                Size: COMMON_BUFFER_CONFIG_SIZE,
+                AlignmentRequirement: alignment_requirement,
+            };
+            // SAFETY: The resulting ffi object is stored in a private member and not
+            // accessible outside of this module, and this module guarantees that it is
+            // always in a valid state. `common_buffer_config` is valid for the duration
+            // of the call, and `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfCommonBufferCreateWithConfig,
+                    dma_enabler.as_raw(),
+                    length,
+                    &mut common_buffer_config,
+                    WDF_NO_OBJECT_ATTRIBUTES,
+                    &mut wdf_common_buffer,
+                );
+            }
+        } else {
+            // SAFETY: The resulting ffi object is stored in a private member and not
+            // accessible outside of this module, and this module guarantees that it is
+            // always in a valid state. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfCommonBufferCreate,
+                    dma_enabler.as_raw(),
+                    length,
+                    WDF_NO_OBJECT_ATTRIBUTES,
+                    &mut wdf_common_buffer,
+                );
+            }
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        // SAFETY: `wdf_common_buffer` is a valid handle to the buffer that was just
+        // created, which is only deleted by this `ChildObject`, and whose parent lives
+        // for `'p`.
+        let object = unsafe { ChildObject::new(wdf_common_buffer.cast()) };
+
+        let virtual_address: PVOID;
+        // SAFETY: `wdf_common_buffer` is a valid handle to the buffer that was just
+        // created.
+        unsafe {
+            virtual_address = macros::call_unsafe_wdf_function_binding!(
+                WdfCommonBufferGetAlignedVirtualAddress,
+                wdf_common_buffer
+            );
+        }
+        let logical_address;
+        // SAFETY: `wdf_common_buffer` is a valid handle to the buffer that was just
+        // created.
+        unsafe {
+            logical_address = macros::call_unsafe_wdf_function_binding!(
+                WdfCommonBufferGetAlignedLogicalAddress,
+                wdf_common_buffer
+            );
+        }
+        let length;
+        // SAFETY: `wdf_common_buffer` is a valid handle to the buffer that was just
+        // created.
+        unsafe {
+            length = macros::call_unsafe_wdf_function_binding!(
+                WdfCommonBufferGetLength,
+                wdf_common_buffer
+            );
+        }
+
+        Ok(Self {
+            wdf_common_buffer,
+            virtual_address: NonNull::new(virtual_address.cast())
+                .expect("WDF should return the virtual address of a common buffer it created"),
+            // SAFETY: Every bit pattern of a `PHYSICAL_ADDRESS` is a valid `QuadPart`.
+            logical_address: unsafe { logical_address.QuadPart }.cast_unsigned(),
+            length,
+            object,
+        })
+    }
+
+    /// Returns the raw `WDFCOMMONBUFFER` handle wrapped by this
+    /// [`CommonBuffer`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFCOMMONBUFFER {
+        self.wdf_common_buffer
+    }
+
+    /// Returns the device-logical address of the start of the buffer, to
+    /// program the device with
+    #[must_use]
+    pub const fn logical_address(&self) -> u64 {
+        self.logical_address
+    }
+
+    /// Returns a pointer to the start of the buffer, in system space
+    #[must_use]
+    pub const fn as_ptr(&self) -> *mut u8 {
+        self.virtual_address.as_ptr()
+    }
+
+    /// Returns the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the buffer is empty
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the contents of the buffer
+    ///
+    /// # Safety
+    ///
+    /// The device must not write to the buffer while the returned slice is
+    /// borrowed. Memory that the device writes to concurrently must instead be
+    /// accessed with volatile reads through [`CommonBuffer::as_ptr()`].
+    #[must_use]
+    pub const unsafe fn as_slice(&self) -> &[u8] {
+        // SAFETY: The buffer is `length` bytes of nonpaged memory, valid until it is
+        // freed when `self` is dropped, and the caller guarantees that the device does
+        // not mutate it while the slice is borrowed.
+        unsafe { core::slice::from_raw_parts(self.virtual_address.as_ptr(), self.length) }
+    }
+
+    /// Returns the contents of the buffer, mutably
+    ///
+    /// # Safety
+    ///
+    /// The device must not access the buffer while the returned slice is
+    /// borrowed. Memory that the device accesses concurrently must instead be
+    /// accessed with volatile reads and writes through
+    /// [`CommonBuffer::as_ptr()`].
+    #[must_use]
+    pub unsafe fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: The buffer is `length` bytes of nonpaged memory, valid until it is
+        // freed when `self` is dropped, `self` is borrowed mutably, and the caller
+        // guarantees that the device does not access it while the slice is borrowed.
+        unsafe { core::slice::from_raw_parts_mut(self.virtual_address.as_ptr(), self.length) }
+    }
+
+    /// Leave the WDF common buffer object to be freed along with its DMA
+    /// enabler, rather than when the [`CommonBuffer`] is dropped, so that the
+    /// [`CommonBuffer`] can be stored in the context of its device
+    ///
+    /// # Safety
+    ///
+    /// The returned [`CommonBuffer`] must not be used after its DMA enabler is
+    /// deleted. Storing it in the context of the enabler's device satisfies
+    /// this, as long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> CommonBuffer<'static> {
+        let Self {
+            wdf_common_buffer,
+            virtual_address,
+            logical_address,
+            length,
+            object,
+        } = self;
+        core::mem::forget(object);
+        CommonBuffer {
+            wdf_common_buffer,
+            virtual_address,
+            logical_address,
+            length,
+            object: ChildObject::parent_owned(wdf_common_buffer.cast()),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::fmt;
+
+use wdk_sys::{NTSTATUS, STATUS_INVALID_PARAMETER, STATUS_OBJECT_NAME_NOT_FOUND};
+
+use super::{Driver, KeyAccess, RegistryKey};
+#[cfg(feature = "alloc")]
+use crate::string::NtUnicodeString;
+
+/// A configuration that can be loaded from the values of a registry key, such
+/// as the `Parameters` subkey of the driver's service key.
+///
+/// This is implemented with
+/// [`#[derive(FromRegistry)]`](macro@crate::wdf::FromRegistry) for structs
+/// whose fields are all [`RegistryValue`]s. Each field is read from the value
+/// named after the field in `PascalCase`, or the name given with
+/// `#[registry(name = "...")]`. A field with `#[registry(default)]` or
+/// `#[registry(default = ...)]` takes its default if the value does not
+/// exist, and a field with `#[registry(validate = ...)]` is checked by the
+/// given function, which returns an error for invalid values.
+///
+/// ```ignore
+/// #[derive(FromRegistry)]
+/// struct Config {
+///     #[registry(default = 16, validate = check_queue_depth)]
+///     queue_depth: u32,
+///     #[registry(name = "EnableDma", default)]
+///     dma: bool,
+/// }
+///
+/// fn check_queue_depth(queue_depth: &u32) -> Result<(), NTSTATUS> {
+///     (1..=64).contains(queue_depth).then_some(()).ok_or(STATUS_INVALID_PARAMETER)
+/// }
+///
+/// let config: Config = driver.load_parameters()?;
+/// ```
+pub trait FromRegistry: Sized {
+    /// Load the configuration from the values of `key`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a value without a default does
+    /// not exist, or if a value could not be read or is not valid. The error
+    /// will contain the name of the value and a [`NTSTATUS`] of the failure.
+    fn from_registry(key: &RegistryKey) -> Result<Self, ConfigError>;
+}
+
+/// A type that the fields of a [`FromRegistry`] configuration can have, which
+/// is read from a single registry value.
+///
+/// This is implemented for [`u32`], [`u16`] and [`u8`], which are read from
+/// `REG_DWORD` values that must fit in the type, for [`bool`], which is read
+/// from a `REG_DWORD` value that is `true` unless it is 0, and, with the
+/// `alloc` feature, for [`NtUnicodeString`], which is read from a `REG_SZ`
+/// value.
+pub trait RegistryValue: Sized {
+    /// Read the value `name` of `key`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key has no such value, in
+    /// which case the error variant will contain
+    /// `STATUS_OBJECT_NAME_NOT_FOUND`, or if the value could not be read as
+    /// this type. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS>;
+
+    /// Read the value `name` of `key`, returning `None` if the key has no
+    /// such value
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value could not be read as
+    /// this type. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    fn query_optional(key: &RegistryKey, name: &str) -> Result<Option<Self>, NTSTATUS> {
+        match Self::query(key, name) {
+            Ok(value) => Ok(Some(value)),
+            Err(STATUS_OBJECT_NAME_NOT_FOUND) => Ok(None),
+            Err(nt_status) => Err(nt_status),
+        }
+    }
+}
+
+impl RegistryValue for u32 {
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS> {
+        key.query_u32(name)
+    }
+}
+
+impl RegistryValue for u16 {
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS> {
+        Self::try_from(key.query_u32(name)?).map_err(|_| STATUS_INVALID_PARAMETER)
+    }
+}
+
+impl RegistryValue for u8 {
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS> {
+        Self::try_from(key.query_u32(name)?).map_err(|_| STATUS_INVALID_PARAMETER)
+    }
+}
+
+impl RegistryValue for bool {
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS> {
+        Ok(key.query_u32(name)? != 0)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl RegistryValue for NtUnicodeString {
+    fn query(key: &RegistryKey, name: &str) -> Result<Self, NTSTATUS> {
+        let mut string = Self::new();
+        key.query_unicode_string(name, &mut string)?;
+        Ok(string)
+    }
+}
+
+/// The error of loading a [`FromRegistry`] configuration, which names the
+/// value that could not be loaded
+///
+/// It converts into the [`NTSTATUS`] of the failure, so it can be returned
+/// from a `#[driver_entry]` function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    value_name: Option<&'static str>,
+    status: NTSTATUS,
+}
+
+impl ConfigError {
+    /// Construct the error of reading the value `value_name`, which failed
+    /// with `status`
+    #[must_use]
+    pub const fn new(value_name: &'static str, status: NTSTATUS) -> Self {
+        Self {
+            value_name: Some(value_name),
+            status,
+        }
+    }
+
+    /// Construct the error of the value `value_name`, which has no default,
+    /// not existing
+    #[must_use]
+    pub const fn missing(value_name: &'static str) -> Self {
+        Self::new(value_name, STATUS_OBJECT_NAME_NOT_FOUND)
+    }
+
+    /// Returns the name of the value that could not be loaded, or `None` if
+    /// the key holding the configuration could not be opened
+    #[must_use]
+    pub const fn value_name(&self) -> Option<&'static str> {
+        self.value_name
+    }
+
+    /// Returns the [`NTSTATUS`] of the failure
+    #[must_use]
+    pub const fn status(&self) -> NTSTATUS {
+        self.status
+    }
+}
+
+impl From<ConfigError> for NTSTATUS {
+    fn from(error: ConfigError) -> Self {
+        error.status
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value_name {
+            Some(value_name) => write!(
+                f,
+                "failed to load registry value {value_name}: {:#010x}",
+                self.status
+            ),
+            None => write!(f, "failed to open registry key: {:#010x}", self.status),
+        }
+    }
+}
+
+impl Driver {
+    /// Load the configuration of the driver from the `Parameters` subkey of
+    /// its service key
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the `Parameters` key could not
+    /// be opened, or if the configuration could not be loaded from it. The
+    /// error will contain the name of the value that could not be loaded, if
+    /// any, and a [`NTSTATUS`] of the failure.
+    pub fn load_parameters<T: FromRegistry>(&self) -> Result<T, ConfigError> {
+        let key = self
+            .open_parameters_key(KeyAccess::Read)
+            .map_err(|status| ConfigError {
+                value_name: None,
+                status,
+            })?;
+        T::from_registry(&key)
+    }
+}
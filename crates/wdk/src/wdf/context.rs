@@ -0,0 +1,364 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Crate-internal helpers for storing Rust closures in the context space of
+//! WDF objects, so that WDF event callbacks can be implemented with closures.
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    macros,
+    _WDF_EXECUTION_LEVEL,
+    _WDF_SYNCHRONIZATION_SCOPE,
+    MEMORY_ALLOCATION_ALIGNMENT,
+    NTSTATUS,
+    PVOID,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_OBJECT_NAME_EXISTS,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFOBJECT,
+    WDF_OBJECT_ATTRIBUTES,
+};
+
+use super::ContextTypeInfo;
+
+// `WDF_OBJECT_ATTRIBUTES` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const OBJECT_ATTRIBUTES_SIZE: ULONG = core::mem::size_of::<WDF_OBJECT_ATTRIBUTES>() as ULONG;
+
+/// The context type of closures stored by [`attach_closure`]. The size of each
+/// closure's context is set with `ContextSizeOverride`, so a single context
+/// type is used for closures of every type.
+static CLOSURE_CONTEXT_TYPE_INFO: ContextTypeInfo =
+    ContextTypeInfo::with_size(c"wdk::wdf::Closure", 1, &CLOSURE_CONTEXT_TYPE_INFO);
+
+/// The context type of closures stored by [`attach_cleanup_callback`]
+static CLEANUP_CALLBACK_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::CleanupCallback",
+    1,
+    &CLEANUP_CALLBACK_CONTEXT_TYPE_INFO,
+);
+
+/// The context type of closures stored by [`attach_destroy_callback`]
+static DESTROY_CALLBACK_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::DestroyCallback",
+    1,
+    &DESTROY_CALLBACK_CONTEXT_TYPE_INFO,
+);
+
+/// A slot that closures can be attached to WDF objects in. Each slot stores
+/// its closures in a context of its own type, so an object can have a closure
+/// attached in every slot.
+pub trait ClosureSlot: 'static {
+    /// Returns the context type of closures stored in this slot, which must
+    /// not be used for anything else
+    fn type_info() -> &'static ContextTypeInfo;
+}
+
+/// The slot of closures stored by [`attach_closure`]
+pub struct DefaultSlot;
+
+impl ClosureSlot for DefaultSlot {
+    fn type_info() -> &'static ContextTypeInfo {
+        &CLOSURE_CONTEXT_TYPE_INFO
+    }
+}
+
+/// Returns `WDF_OBJECT_ATTRIBUTES` initialized the same way as by
+/// `WDF_OBJECT_ATTRIBUTES_INIT`, with `parent` as the parent object
+pub fn object_attributes(parent: WDFOBJECT) -> WDF_OBJECT_ATTRIBUTES {
+    WDF_OBJECT_ATTRIBUTES {
+        Size: OBJECT_ATTRIBUTES_SIZE,
+        ExecutionLevel: _WDF_EXECUTION_LEVEL::WdfExecutionLevelInheritFromParent,
+        SynchronizationScope: _WDF_SYNCHRONIZATION_SCOPE::WdfSynchronizationScopeInheritFromParent,
+        ParentObject: parent,
+        ..Default::default()
+    }
+}
+
+/// Store `closure` in a new context of `object`. The closure is dropped when
+/// the object is destroyed.
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object that does not already have
+/// a closure attached.
+///
+/// # Errors
+///
+/// This function will return an error if a closure is already attached, or if
+/// WDF fails to allocate the context. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub unsafe fn attach_closure<F>(object: WDFOBJECT, closure: F) -> Result<(), NTSTATUS> {
+    // SAFETY: The caller guarantees that `object` is a valid handle that does not
+    // already have a closure attached.
+    unsafe { attach_closure_in::<DefaultSlot, F>(object, closure) }
+}
+
+/// Store `closure` in a new context of `object`, in the slot `S`. The closure
+/// is dropped when the object is destroyed.
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object that does not already have
+/// a closure attached in the slot `S`.
+///
+/// # Errors
+///
+/// This function will return an error if a closure is already attached, or if
+/// WDF fails to allocate the context. The error variant will contain a
+/// [`NTSTATUS`] of the failure.
+pub unsafe fn attach_closure_in<S: ClosureSlot, F>(
+    object: WDFOBJECT,
+    closure: F,
+) -> Result<(), NTSTATUS> {
+    let attributes = WDF_OBJECT_ATTRIBUTES {
+        EvtDestroyCallback: Some(destroy_closure::<S, F>),
+        ..closure_attributes::<F>(S::type_info())
+    };
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    unsafe { allocate_closure_context(object, attributes, closure) }
+}
+
+/// Store `callback` in a new context of `object`, and call it when the object
+/// is cleaned up
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object.
+///
+/// # Errors
+///
+/// This function will return an error if a cleanup callback is already
+/// attached to `object`, or if WDF fails to allocate the context. The error
+/// variant will contain a [`NTSTATUS`] of the failure.
+pub unsafe fn attach_cleanup_callback<F>(object: WDFOBJECT, callback: F) -> Result<(), NTSTATUS>
+where
+    F: FnOnce(),
+{
+    let attributes = WDF_OBJECT_ATTRIBUTES {
+        EvtCleanupCallback: Some(cleanup_callback::<F>),
+        ..closure_attributes::<F>(&CLEANUP_CALLBACK_CONTEXT_TYPE_INFO)
+    };
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    unsafe { allocate_closure_context(object, attributes, callback) }
+}
+
+/// Store `callback` in a new context of `object`, and call it when the object
+/// is destroyed
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object.
+///
+/// # Errors
+///
+/// This function will return an error if a destroy callback is already
+/// attached to `object`, or if WDF fails to allocate the context. The error
+/// variant will contain a [`NTSTATUS`] of the failure.
+pub unsafe fn attach_destroy_callback<F>(object: WDFOBJECT, callback: F) -> Result<(), NTSTATUS>
+where
+    F: FnOnce(),
+{
+    let attributes = WDF_OBJECT_ATTRIBUTES {
+        EvtDestroyCallback: Some(destroy_callback::<F>),
+        ..closure_attributes::<F>(&DESTROY_CALLBACK_CONTEXT_TYPE_INFO)
+    };
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    unsafe { allocate_closure_context(object, attributes, callback) }
+}
+
+/// Returns the closure attached to `object` by [`attach_closure`]
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object that an `F` was attached to
+/// with [`attach_closure`]. The returned reference must not be used after the
+/// object is destroyed.
+pub unsafe fn closure<'a, F>(object: WDFOBJECT) -> &'a F {
+    // SAFETY: The caller guarantees that an `F` was attached to `object`, and that
+    // the reference does not outlive it.
+    unsafe { closure_in::<DefaultSlot, F>(object) }
+}
+
+/// Returns the closure attached to `object` in the slot `S` by
+/// [`attach_closure_in`]
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object that an `F` was attached to
+/// in the slot `S`. The returned reference must not be used after the object
+/// is destroyed.
+pub unsafe fn closure_in<'a, S: ClosureSlot, F>(object: WDFOBJECT) -> &'a F {
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    let context = unsafe { closure_context::<F>(object, S::type_info()) };
+    // SAFETY: The caller guarantees that an `F` was attached to `object`, and that
+    // the reference does not outlive it.
+    unsafe { context.as_ref() }
+}
+
+/// Returns the closure attached to `object` in the slot `S`, if one was
+/// attached
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object. If a closure was attached
+/// in the slot `S`, it must be an `F`, or a `repr(C)` type that starts with an
+/// `F`. The returned reference must not be used after the object is
+/// destroyed.
+pub unsafe fn try_closure_in<'a, S: ClosureSlot, F>(object: WDFOBJECT) -> Option<&'a F> {
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    let context = unsafe { try_closure_context::<F>(object, S::type_info()) }?;
+    // SAFETY: The caller guarantees that the closure starts with an `F`, and that
+    // the reference does not outlive it.
+    Some(unsafe { context.as_ref() })
+}
+
+/// Returns the attributes of a context of `type_info`, sized to hold an `F`
+fn closure_attributes<F>(type_info: &'static ContextTypeInfo) -> WDF_OBJECT_ATTRIBUTES {
+    const {
+        assert!(
+            core::mem::align_of::<F>() <= MEMORY_ALLOCATION_ALIGNMENT as usize,
+            "closures stored in WDF object contexts must not be over-aligned"
+        );
+    }
+
+    WDF_OBJECT_ATTRIBUTES {
+        ContextSizeOverride: core::mem::size_of::<F>().max(1),
+        ContextTypeInfo: type_info.as_raw(),
+        ..object_attributes(core::ptr::null_mut())
+    }
+}
+
+/// Allocate a context of `object` with `attributes`, and move `closure` into
+/// it
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object, and `attributes` must have
+/// been returned by [`closure_attributes::<F>()`](closure_attributes).
+unsafe fn allocate_closure_context<F>(
+    object: WDFOBJECT,
+    mut attributes: WDF_OBJECT_ATTRIBUTES,
+    closure: F,
+) -> Result<(), NTSTATUS> {
+    let mut context: PVOID = core::ptr::null_mut();
+
+    let nt_status;
+    // SAFETY: The caller guarantees that `object` is a valid handle. `attributes`
+    // and `context` are valid for the duration of the call.
+    unsafe {
+        nt_status = macros::call_unsafe_wdf_function_binding!(
+            WdfObjectAllocateContext,
+            object,
+            &mut attributes,
+            &mut context,
+        );
+    }
+    match nt_status {
+        STATUS_SUCCESS => {}
+        // WDF returns the existing context of the type, which holds a closure of
+        // another type and size that must not be overwritten
+        STATUS_OBJECT_NAME_EXISTS => return Err(STATUS_INVALID_DEVICE_STATE),
+        _ => return Err(nt_status),
+    }
+
+    // SAFETY: WDF allocated a context of at least `size_of::<F>()` bytes, aligned
+    // to `MEMORY_ALLOCATION_ALIGNMENT`, which is at least the alignment of `F`.
+    unsafe {
+        context.cast::<F>().write(closure);
+    }
+    Ok(())
+}
+
+/// Returns a pointer to the `F` in the context of `type_info` of `object`
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object.
+unsafe fn closure_context<F>(object: WDFOBJECT, type_info: &'static ContextTypeInfo) -> NonNull<F> {
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    unsafe { try_closure_context(object, type_info) }
+        .expect("WDF object should have a closure context attached")
+}
+
+/// Returns a pointer to the `F` in the context of `type_info` of `object`, if
+/// `object` has such a context
+///
+/// # Safety
+///
+/// `object` must be a valid handle to a WDF object.
+unsafe fn try_closure_context<F>(
+    object: WDFOBJECT,
+    type_info: &'static ContextTypeInfo,
+) -> Option<NonNull<F>> {
+    let context: PVOID;
+    // SAFETY: The caller guarantees that `object` is a valid handle.
+    unsafe {
+        context = macros::call_unsafe_wdf_function_binding!(
+            WdfObjectGetTypedContextWorker,
+            object,
+            type_info.as_raw(),
+        );
+    }
+    NonNull::new(context.cast::<F>())
+}
+
+/// The `EvtDestroyCallback` of closure contexts
+///
+/// # Safety
+///
+/// `object` must be a WDF object that an `F` was attached to in the slot `S`
+/// with [`attach_closure_in`].
+unsafe extern "C" fn destroy_closure<S: ClosureSlot, F>(object: WDFOBJECT) {
+    // SAFETY: The framework only calls this with the object being destroyed, which
+    // remains valid for the duration of the call.
+    let context = unsafe { closure_context::<F>(object, S::type_info()) };
+    // SAFETY: The caller guarantees that an `F` was attached to `object`. The
+    // object is being destroyed, so none of its callbacks can access the closure
+    // after this.
+    unsafe {
+        core::ptr::drop_in_place(context.as_ptr());
+    }
+}
+
+/// The `EvtCleanupCallback` of cleanup callback contexts
+///
+/// # Safety
+///
+/// `object` must be a WDF object that an `F` was attached to with
+/// [`attach_cleanup_callback`].
+unsafe extern "C" fn cleanup_callback<F>(object: WDFOBJECT)
+where
+    F: FnOnce(),
+{
+    // SAFETY: The framework only calls this with the object being cleaned up, which
+    // remains valid for the duration of the call.
+    let context = unsafe { closure_context::<F>(object, &CLEANUP_CALLBACK_CONTEXT_TYPE_INFO) };
+    // SAFETY: The caller guarantees that an `F` was attached to `object`. The
+    // framework only cleans up an object once, so the callback is only moved out
+    // of the context once.
+    let callback = unsafe { context.read() };
+    callback();
+}
+
+/// The `EvtDestroyCallback` of destroy callback contexts
+///
+/// # Safety
+///
+/// `object` must be a WDF object that an `F` was attached to with
+/// [`attach_destroy_callback`].
+unsafe extern "C" fn destroy_callback<F>(object: WDFOBJECT)
+where
+    F: FnOnce(),
+{
+    // SAFETY: The framework only calls this with the object being destroyed, which
+    // remains valid for the duration of the call.
+    let context = unsafe { closure_context::<F>(object, &DESTROY_CALLBACK_CONTEXT_TYPE_INFO) };
+    // SAFETY: The caller guarantees that an `F` was attached to `object`. The
+    // framework only destroys an object once, so the callback is only moved out of
+    // the context once.
+    let callback = unsafe { context.read() };
+    callback();
+}
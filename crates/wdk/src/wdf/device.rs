@@ -0,0 +1,1308 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    macros,
+    _WDF_DEVICE_FAILED_ACTION,
+    _WDF_DEVICE_IO_TYPE,
+    _WDF_POWER_DEVICE_STATE,
+    _WDF_TRI_STATE,
+    GUID,
+    NTSTATUS,
+    PWDFDEVICE_INIT,
+    STATUS_SUCCESS,
+    ULONG,
+    UNICODE_STRING,
+    WDFCMRESLIST,
+    WDFDEVICE,
+    WDFIOTARGET,
+    WDFOBJECT,
+    WDFREQUEST,
+    WDF_DEVICE_FAILED_ACTION,
+    WDF_DEVICE_IO_TYPE,
+    WDF_DEVICE_PNP_CAPABILITIES,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_PNPPOWER_EVENT_CALLBACKS,
+    WDF_POWER_DEVICE_STATE,
+    WDF_TRI_STATE,
+};
+
+use super::{
+    childlist::{attach_child_list_callbacks, child_list_config},
+    context::{attach_closure, closure},
+    fileobject::{attach_file_object_callbacks, file_object_config},
+    name::UnicodeBuffer,
+    power::{attach_wake_callbacks, power_policy_event_callbacks},
+    ChildListCallbacks,
+    FileObjectCallbacks,
+    IoTarget,
+    NoChildListCallbacks,
+    NoFileObjectCallbacks,
+    NoWakeCallbacks,
+    Request,
+    ResourceList,
+    Sddl,
+    WakeCallbacks,
+    WdfObject,
+};
+use crate::nt_success;
+
+// `WDF_PNPPOWER_EVENT_CALLBACKS` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const PNPPOWER_EVENT_CALLBACKS_SIZE: ULONG =
+    core::mem::size_of::<WDF_PNPPOWER_EVENT_CALLBACKS>() as ULONG;
+
+/// WDF device initialization state.
+///
+/// A [`DeviceInit`] is passed to the `EvtDriverDeviceAdd` callback registered
+/// with [`DriverConfig::device_add()`](super::DriverConfig::device_add), and
+/// is used to configure and create the device being added, typically with a
+/// [`DeviceBuilder`]. It is only valid for the duration of the callback.
+pub struct DeviceInit {
+    device_init: PWDFDEVICE_INIT,
+}
+
+impl DeviceInit {
+    /// Wrap an existing `WDFDEVICE_INIT`
+    ///
+    /// # Safety
+    ///
+    /// `device_init` must be a valid pointer to a `WDFDEVICE_INIT` provided by
+    /// the framework, and must remain valid for the lifetime of the returned
+    /// [`DeviceInit`].
+    #[must_use]
+    pub const unsafe fn from_raw(device_init: PWDFDEVICE_INIT) -> Self {
+        Self { device_init }
+    }
+
+    /// Returns the raw `PWDFDEVICE_INIT` wrapped by this [`DeviceInit`]
+    #[must_use]
+    pub const fn as_raw(&self) -> PWDFDEVICE_INIT {
+        self.device_init
+    }
+
+    /// Returns a mutable reference to the raw `PWDFDEVICE_INIT` wrapped by
+    /// this [`DeviceInit`], as expected by `WdfDeviceCreate`, which sets it to
+    /// null once the device is created
+    pub fn as_raw_mut(&mut self) -> &mut PWDFDEVICE_INIT {
+        &mut self.device_init
+    }
+}
+
+/// The method a device uses to access the data buffers of read and write
+/// requests
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IoType {
+    /// The framework copies data between the requester's buffers and
+    /// intermediate system buffers
+    #[default]
+    Buffered,
+    /// The framework locks the requester's buffers in memory, and describes
+    /// them with MDLs
+    Direct,
+    /// The requester's buffers are passed to the driver as-is
+    Neither,
+    /// The framework chooses between buffered and direct I/O for each request,
+    /// based on the size of its buffers
+    BufferedOrDirect,
+}
+
+impl IoType {
+    const fn as_raw(self) -> WDF_DEVICE_IO_TYPE {
+        match self {
+            Self::Buffered => _WDF_DEVICE_IO_TYPE::WdfDeviceIoBuffered,
+            Self::Direct => _WDF_DEVICE_IO_TYPE::WdfDeviceIoDirect,
+            Self::Neither => _WDF_DEVICE_IO_TYPE::WdfDeviceIoNeither,
+            Self::BufferedOrDirect => _WDF_DEVICE_IO_TYPE::WdfDeviceIoBufferedOrDirect,
+        }
+    }
+}
+
+/// A device power state, as passed to the power callbacks of
+/// [`PnpPowerCallbacks`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerDeviceState {
+    /// The device is fully on
+    D0,
+    /// The device is in the D1 low-power state
+    D1,
+    /// The device is in the D2 low-power state
+    D2,
+    /// The device is in the D3 low-power state
+    D3,
+    /// The device is about to be turned off permanently, because it is being
+    /// stopped or removed, or the system is being shut down
+    D3Final,
+    /// The device is about to enter D3 because the system is hibernating
+    PrepareForHibernation,
+    /// The framework passed a state that is not recognized
+    Invalid,
+}
+
+impl PowerDeviceState {
+    const fn from_raw(power_device_state: WDF_POWER_DEVICE_STATE) -> Self {
+        match power_device_state {
+            _WDF_POWER_DEVICE_STATE::WdfPowerDeviceD0 => Self::D0,
+            _WDF_POWER_DEVICE_STATE::WdfPowerDeviceD1 => Self::D1,
+            _WDF_POWER_DEVICE_STATE::WdfPowerDeviceD2 => Self::D2,
+            _WDF_POWER_DEVICE_STATE::WdfPowerDeviceD3 => Self::D3,
+            _WDF_POWER_DEVICE_STATE::WdfPowerDeviceD3Final => Self::D3Final,
+            _WDF_POWER_DEVICE_STATE::WdfPowerDevicePrepareForHibernation => {
+                Self::PrepareForHibernation
+            }
+            _ => Self::Invalid,
+        }
+    }
+}
+
+/// The action that the framework takes for a device that its driver reports
+/// as failed with [`Device::set_failed()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailedAction {
+    /// Remove the device's drivers and then try to restart the device, which
+    /// recovers from most transient hardware errors. If the device keeps
+    /// failing, the framework stops restarting it.
+    AttemptRestart,
+    /// Remove the device's drivers and leave the device stopped, until it is
+    /// re-enumerated or the system is restarted
+    NoRestart,
+}
+
+impl FailedAction {
+    const fn as_raw(self) -> WDF_DEVICE_FAILED_ACTION {
+        match self {
+            Self::AttemptRestart => _WDF_DEVICE_FAILED_ACTION::WdfDeviceFailedAttemptRestart,
+            Self::NoRestart => _WDF_DEVICE_FAILED_ACTION::WdfDeviceFailedNoRestart,
+        }
+    }
+}
+
+/// Plug and Play and power management callbacks of a [`Device`].
+///
+/// Every method has a default implementation that does nothing and succeeds,
+/// so implementations only need to override the callbacks they handle. All
+/// callbacks are called at `IRQL` = `PASSIVE_LEVEL`. Callbacks that return an
+/// error fail the transition they are notifying the driver of. Errors that
+/// are detected outside of these callbacks are reported with
+/// [`Device::set_failed()`].
+pub trait PnpPowerCallbacks: Send + Sync + 'static {
+    /// Called when the device's hardware resources are assigned, to make the
+    /// hardware accessible to the driver (`EvtDevicePrepareHardware`).
+    /// `resources_raw` are the resources as seen by the device's bus, and
+    /// `resources_translated` are the same resources as seen by the
+    /// processor, which are the ones that drivers map and connect to.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the device's start.
+    fn prepare_hardware(
+        &self,
+        device: &Device,
+        resources_raw: ResourceList<'_>,
+        resources_translated: ResourceList<'_>,
+    ) -> Result<(), NTSTATUS> {
+        let _ = (device, resources_raw, resources_translated);
+        Ok(())
+    }
+
+    /// Called when the device's hardware resources are released, after the
+    /// device is stopped or removed (`EvtDeviceReleaseHardware`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error marks the device as failed.
+    fn release_hardware(
+        &self,
+        device: &Device,
+        resources_translated: ResourceList<'_>,
+    ) -> Result<(), NTSTATUS> {
+        let _ = (device, resources_translated);
+        Ok(())
+    }
+
+    /// Called every time the device enters the working (D0) state, from
+    /// `previous_state` (`EvtDeviceD0Entry`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the power transition, and marks the device as
+    /// failed.
+    fn d0_entry(&self, device: &Device, previous_state: PowerDeviceState) -> Result<(), NTSTATUS> {
+        let _ = (device, previous_state);
+        Ok(())
+    }
+
+    /// Called every time the device leaves the working (D0) state, for
+    /// `target_state` (`EvtDeviceD0Exit`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error marks the device as failed.
+    fn d0_exit(&self, device: &Device, target_state: PowerDeviceState) -> Result<(), NTSTATUS> {
+        let _ = (device, target_state);
+        Ok(())
+    }
+
+    /// Called every time the device enters the working (D0) state, after its
+    /// interrupts are enabled (`EvtDeviceD0EntryPostInterruptsEnabled`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the power transition, and marks the device as
+    /// failed.
+    fn d0_entry_post_interrupts_enabled(
+        &self,
+        device: &Device,
+        previous_state: PowerDeviceState,
+    ) -> Result<(), NTSTATUS> {
+        let _ = (device, previous_state);
+        Ok(())
+    }
+
+    /// Called every time the device leaves the working (D0) state, before its
+    /// interrupts are disabled (`EvtDeviceD0ExitPreInterruptsDisabled`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error marks the device as failed.
+    fn d0_exit_pre_interrupts_disabled(
+        &self,
+        device: &Device,
+        target_state: PowerDeviceState,
+    ) -> Result<(), NTSTATUS> {
+        let _ = (device, target_state);
+        Ok(())
+    }
+
+    /// Called once, the first time the device enters the working (D0) state,
+    /// to start its self-managed I/O (`EvtDeviceSelfManagedIoInit`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the device's start.
+    fn self_managed_io_init(&self, device: &Device) -> Result<(), NTSTATUS> {
+        let _ = device;
+        Ok(())
+    }
+
+    /// Called once the device is removed, to release its self-managed I/O
+    /// (`EvtDeviceSelfManagedIoCleanup`)
+    fn self_managed_io_cleanup(&self, device: &Device) {
+        let _ = device;
+    }
+
+    /// Called when the device is unexpectedly removed
+    /// (`EvtDeviceSurpriseRemoval`)
+    fn surprise_removal(&self, device: &Device) {
+        let _ = device;
+    }
+}
+
+/// The type of the callbacks of a [`DeviceBuilder`] that does not register
+/// any
+pub struct NoPnpPowerCallbacks;
+
+impl PnpPowerCallbacks for NoPnpPowerCallbacks {}
+
+/// Builder of a WDF Device object.
+///
+/// A [`DeviceBuilder`] configures the [`DeviceInit`] passed to
+/// `EvtDriverDeviceAdd`, and then creates the device with
+/// [`DeviceBuilder::create()`]. Each configuration method applies its setting
+/// to the [`DeviceInit`] immediately.
+///
+/// ```ignore
+/// let device = DeviceBuilder::new(device_init)
+///     .device_type(FILE_DEVICE_UNKNOWN)
+///     .io_type(IoType::Direct)
+///     .pnp_power_callbacks(MyCallbacks)
+///     .create()?;
+/// ```
+#[must_use]
+pub struct DeviceBuilder<
+    'a,
+    C = NoPnpPowerCallbacks,
+    F = NoFileObjectCallbacks,
+    L = NoChildListCallbacks,
+    W = NoWakeCallbacks,
+> {
+    device_init: &'a mut DeviceInit,
+    name: Option<&'a str>,
+    security_descriptor: Option<&'a Sddl>,
+    pnp_power_callbacks: Option<C>,
+    file_object_callbacks: Option<F>,
+    child_list_callbacks: Option<L>,
+    wake_callbacks: Option<W>,
+}
+
+impl<'a> DeviceBuilder<'a> {
+    /// Construct a [`DeviceBuilder`] that configures `device_init`
+    pub fn new(device_init: &'a mut DeviceInit) -> Self {
+        Self {
+            device_init,
+            name: None,
+            security_descriptor: None,
+            pnp_power_callbacks: None,
+            file_object_callbacks: None,
+            child_list_callbacks: None,
+            wake_callbacks: None,
+        }
+    }
+}
+
+impl<'a, C, F, L, W> DeviceBuilder<'a, C, F, L, W>
+where
+    C: PnpPowerCallbacks,
+    F: FileObjectCallbacks,
+    L: ChildListCallbacks,
+    W: WakeCallbacks,
+{
+    /// Set the device type (ex. `FILE_DEVICE_UNKNOWN`). Function drivers do
+    /// not usually need to set this, since the framework uses the type
+    /// reported by the bus driver.
+    pub fn device_type(self, device_type: ULONG) -> Self {
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+        // been created yet.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceInitSetDeviceType,
+                self.device_init.as_raw(),
+                device_type
+            );
+        }
+        self
+    }
+
+    /// Add the `FILE_*` device characteristics in `characteristics` (ex.
+    /// `FILE_DEVICE_SECURE_OPEN`) to the device's characteristics
+    pub fn characteristics(self, characteristics: ULONG) -> Self {
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+        // been created yet.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceInitSetCharacteristics,
+                self.device_init.as_raw(),
+                characteristics,
+                u8::from(true)
+            );
+        }
+        self
+    }
+
+    /// Set the method the device uses to access the data buffers of read and
+    /// write requests. The default is [`IoType::Buffered`].
+    pub fn io_type(self, io_type: IoType) -> Self {
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+        // been created yet.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceInitSetIoType,
+                self.device_init.as_raw(),
+                io_type.as_raw()
+            );
+        }
+        self
+    }
+
+    /// Set whether only one handle to the device can be open at a time
+    pub fn exclusive(self, exclusive: bool) -> Self {
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+        // been created yet.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceInitSetExclusive,
+                self.device_init.as_raw(),
+                u8::from(exclusive)
+            );
+        }
+        self
+    }
+
+    /// Name the device `\Device\<name>`, so that it can be opened by name,
+    /// typically through a symbolic link created with
+    /// [`Device::create_dos_device_name()`]
+    ///
+    /// Only control devices and some legacy devices need a name. Plug and Play
+    /// devices should expose a device interface with
+    /// [`Device::create_interface()`] instead. `name` must not contain a `\`.
+    pub const fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set the security descriptor of the device object, which controls who
+    /// can open the device
+    ///
+    /// Named devices should always set a security descriptor, since the
+    /// default one of their device type may grant access to every user.
+    pub const fn security_descriptor(mut self, sddl: &'a Sddl) -> Self {
+        self.security_descriptor = Some(sddl);
+        self
+    }
+
+    /// Set the `EvtIoInCallerContext` callback of the device, which the
+    /// framework calls with every request of the device before queuing it
+    ///
+    /// The callback runs in the context of the thread that sent the request,
+    /// so it can capture the buffers of `METHOD_NEITHER` requests with
+    /// [`Request::lock_user_input_buffer()`] and
+    /// [`Request::lock_user_output_buffer()`]. It must then either complete the
+    /// request, or pass it back to the framework with
+    /// [`Device::enqueue_request()`] to dispatch it to the device's queues.
+    ///
+    /// `callback` must not capture any variables, since there is nowhere to
+    /// store them. Drivers can store state in the context of the [`Device`]
+    /// passed to it instead.
+    pub fn io_in_caller_context<I>(self, callback: I) -> Self
+    where
+        I: Fn(&Device, Request) + Send + Sync + 'static,
+    {
+        const {
+            assert!(
+                core::mem::size_of::<I>() == 0,
+                "`EvtIoInCallerContext` callbacks must not capture any variables"
+            );
+        }
+        // `I` is zero-sized, so it is recreated by `evt_io_in_caller_context` instead
+        // of being stored
+        core::mem::forget(callback);
+
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+        // been created yet.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceInitSetIoInCallerContextCallback,
+                self.device_init.as_raw(),
+                Some(evt_io_in_caller_context::<I>)
+            );
+        }
+        self
+    }
+
+    /// Set the Plug and Play and power management callbacks of the device
+    pub fn pnp_power_callbacks<P: PnpPowerCallbacks>(
+        self,
+        callbacks: P,
+    ) -> DeviceBuilder<'a, P, F, L, W> {
+        DeviceBuilder {
+            device_init: self.device_init,
+            name: self.name,
+            security_descriptor: self.security_descriptor,
+            pnp_power_callbacks: Some(callbacks),
+            file_object_callbacks: self.file_object_callbacks,
+            child_list_callbacks: self.child_list_callbacks,
+            wake_callbacks: self.wake_callbacks,
+        }
+    }
+
+    /// Set the callbacks for the file objects of the device, which are called
+    /// when handles to the device are opened and closed
+    pub fn file_object_callbacks<P: FileObjectCallbacks>(
+        self,
+        callbacks: P,
+    ) -> DeviceBuilder<'a, C, P, L, W> {
+        DeviceBuilder {
+            device_init: self.device_init,
+            name: self.name,
+            security_descriptor: self.security_descriptor,
+            pnp_power_callbacks: self.pnp_power_callbacks,
+            file_object_callbacks: Some(callbacks),
+            child_list_callbacks: self.child_list_callbacks,
+            wake_callbacks: self.wake_callbacks,
+        }
+    }
+
+    /// Give the device a default [`ChildList`](super::ChildList), whose
+    /// children are created by `callbacks`. This is only supported for the
+    /// function devices of bus drivers that enumerate their children
+    /// dynamically.
+    pub fn child_list_callbacks<P: ChildListCallbacks>(
+        self,
+        callbacks: P,
+    ) -> DeviceBuilder<'a, C, F, P, W> {
+        DeviceBuilder {
+            device_init: self.device_init,
+            name: self.name,
+            security_descriptor: self.security_descriptor,
+            pnp_power_callbacks: self.pnp_power_callbacks,
+            file_object_callbacks: self.file_object_callbacks,
+            child_list_callbacks: Some(callbacks),
+            wake_callbacks: self.wake_callbacks,
+        }
+    }
+
+    /// Set the callbacks for arming the device to wake itself or the system,
+    /// which are called when the driver is the power policy owner of the
+    /// device
+    pub fn wake_callbacks<P: WakeCallbacks>(self, callbacks: P) -> DeviceBuilder<'a, C, F, L, P> {
+        DeviceBuilder {
+            device_init: self.device_init,
+            name: self.name,
+            security_descriptor: self.security_descriptor,
+            pnp_power_callbacks: self.pnp_power_callbacks,
+            file_object_callbacks: self.file_object_callbacks,
+            child_list_callbacks: self.child_list_callbacks,
+            wake_callbacks: Some(callbacks),
+        }
+    }
+
+    /// Try to create the WDF Device object from the configured
+    /// [`DeviceInit`]
+    ///
+    /// This must be called from `EvtDriverDeviceAdd` at `IRQL` =
+    /// `PASSIVE_LEVEL`. If this fails after the device is created, the
+    /// framework deletes the device once `EvtDriverDeviceAdd` returns the
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device name is invalid, if the security descriptor cannot be assigned, or if WDF fails to contruct the device, or to allocate storage for its callbacks. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreate#return-value)
+    pub fn create(self) -> Result<Device, NTSTATUS> {
+        if let Some(name) = self.name {
+            let name = UnicodeBuffer::object_path("\\Device\\", name)?;
+            let name = name.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet. `name` borrows a buffer that outlives the call, which
+            // copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfDeviceInitAssignName,
+                    self.device_init.as_raw(),
+                    &name
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        if let Some(sddl) = self.security_descriptor {
+            let sddl = UnicodeBuffer::new(sddl.as_str())?;
+            let sddl = sddl.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet. `sddl` borrows a buffer that outlives the call, which
+            // copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfDeviceInitAssignSDDLString,
+                    self.device_init.as_raw(),
+                    &sddl
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        self.set_event_callbacks();
+
+        let mut device = Device {
+            wdf_device: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, which the framework sets
+        // to null once it creates the device. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceCreate,
+                self.device_init.as_raw_mut(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut device.wdf_device,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        if let Some(callbacks) = self.pnp_power_callbacks {
+            // SAFETY: `wdf_device` is a valid handle to the device that was just created,
+            // and nothing else has been attached to it. The framework does not call the
+            // device's Plug and Play or power callbacks until `EvtDriverDeviceAdd`
+            // returns.
+            unsafe { attach_closure(device.wdf_device.cast(), callbacks) }?;
+        }
+        if let Some(callbacks) = self.file_object_callbacks {
+            // SAFETY: `wdf_device` is a valid handle to the device that was just created,
+            // and no file object callbacks have been attached to it. The framework does
+            // not open handles to the device until `EvtDriverDeviceAdd` returns.
+            unsafe { attach_file_object_callbacks(device.wdf_device, callbacks) }?;
+        }
+        if let Some(callbacks) = self.child_list_callbacks {
+            // SAFETY: `wdf_device` is a valid handle to the device that was just created
+            // with the default child list configuration of `L`, and no child list
+            // callbacks have been attached to it. The framework does not create child
+            // devices until `EvtDriverDeviceAdd` returns.
+            unsafe { attach_child_list_callbacks(device.wdf_device, callbacks) }?;
+        }
+        if let Some(callbacks) = self.wake_callbacks {
+            // SAFETY: `wdf_device` is a valid handle to the device that was just created,
+            // and no wake callbacks have been attached to it. The framework does not arm
+            // the device for wake until `EvtDriverDeviceAdd` returns.
+            unsafe { attach_wake_callbacks(device.wdf_device, callbacks) }?;
+        }
+        Ok(device)
+    }
+
+    /// Set the event callbacks of the configured callback types in the
+    /// [`DeviceInit`], before the device is created
+    fn set_event_callbacks(&self) {
+        if self.pnp_power_callbacks.is_some() {
+            let mut pnp_power_event_callbacks = WDF_PNPPOWER_EVENT_CALLBACKS {
+                Size: PNPPOWER_EVENT_CALLBACKS_SIZE,
+                EvtDevicePrepareHardware: Some(evt_device_prepare_hardware::<C>),
+                EvtDeviceReleaseHardware: Some(evt_device_release_hardware::<C>),
+                EvtDeviceD0Entry: Some(evt_device_d0_entry::<C>),
+                EvtDeviceD0Exit: Some(evt_device_d0_exit::<C>),
+                EvtDeviceD0EntryPostInterruptsEnabled: Some(
+                    evt_device_d0_entry_post_interrupts_enabled::<C>,
+                ),
+                EvtDeviceD0ExitPreInterruptsDisabled: Some(
+                    evt_device_d0_exit_pre_interrupts_disabled::<C>,
+                ),
+                EvtDeviceSelfManagedIoInit: Some(evt_device_self_managed_io_init::<C>),
+                EvtDeviceSelfManagedIoCleanup: Some(evt_device_self_managed_io_cleanup::<C>),
+                EvtDeviceSurpriseRemoval: Some(evt_device_surprise_removal::<C>),
+                ..Default::default()
+            };
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet, and `pnp_power_event_callbacks` is valid for the duration
+            // of the call.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(
+                    WdfDeviceInitSetPnpPowerEventCallbacks,
+                    self.device_init.as_raw(),
+                    &mut pnp_power_event_callbacks
+                );
+            }
+        }
+
+        if self.file_object_callbacks.is_some() {
+            let mut file_object_config = file_object_config::<F>();
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet, and `file_object_config` is valid for the duration of the
+            // call. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(
+                    WdfDeviceInitSetFileObjectConfig,
+                    self.device_init.as_raw(),
+                    &mut file_object_config,
+                    WDF_NO_OBJECT_ATTRIBUTES
+                );
+            }
+        }
+
+        if self.wake_callbacks.is_some() {
+            let mut power_policy_event_callbacks = power_policy_event_callbacks::<W>();
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet, and `power_policy_event_callbacks` is valid for the
+            // duration of the call.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(
+                    WdfDeviceInitSetPowerPolicyEventCallbacks,
+                    self.device_init.as_raw(),
+                    &mut power_policy_event_callbacks
+                );
+            }
+        }
+
+        if self.child_list_callbacks.is_some() {
+            let mut child_list_config = child_list_config::<L>();
+            // SAFETY: `device_init` is a valid `WDFDEVICE_INIT`, since the device has not
+            // been created yet, and `child_list_config` is valid for the duration of the
+            // call. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(
+                    WdfFdoInitSetDefaultChildListConfig,
+                    self.device_init.as_raw(),
+                    &mut child_list_config,
+                    WDF_NO_OBJECT_ATTRIBUTES
+                );
+            }
+        }
+    }
+}
+
+// `WDF_DEVICE_PNP_CAPABILITIES` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const DEVICE_PNP_CAPABILITIES_SIZE: ULONG =
+    core::mem::size_of::<WDF_DEVICE_PNP_CAPABILITIES>() as ULONG;
+
+/// Plug and Play capabilities of a [`Device`], set with
+/// [`Device::set_pnp_capabilities()`].
+///
+/// Capabilities that are not set keep the value reported by the bus driver.
+///
+/// ```ignore
+/// device.set_pnp_capabilities(
+///     PnpCapabilities::new()
+///         .removable(true)
+///         .surprise_removal_ok(true),
+/// );
+/// ```
+#[must_use]
+#[derive(Clone, Copy, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PnpCapabilities {
+    lock_supported: Option<bool>,
+    eject_supported: Option<bool>,
+    removable: Option<bool>,
+    dock_device: Option<bool>,
+    unique_id: Option<bool>,
+    silent_install: Option<bool>,
+    surprise_removal_ok: Option<bool>,
+    hardware_disabled: Option<bool>,
+    no_display_in_ui: Option<bool>,
+    address: Option<ULONG>,
+    ui_number: Option<ULONG>,
+}
+
+impl PnpCapabilities {
+    /// Construct [`PnpCapabilities`] that leave every capability unchanged
+    pub const fn new() -> Self {
+        Self {
+            lock_supported: None,
+            eject_supported: None,
+            removable: None,
+            dock_device: None,
+            unique_id: None,
+            silent_install: None,
+            surprise_removal_ok: None,
+            hardware_disabled: None,
+            no_display_in_ui: None,
+            address: None,
+            ui_number: None,
+        }
+    }
+
+    /// Set whether the device can be locked to prevent its ejection
+    pub const fn lock_supported(mut self, lock_supported: bool) -> Self {
+        self.lock_supported = Some(lock_supported);
+        self
+    }
+
+    /// Set whether the device can be ejected from its slot by software
+    pub const fn eject_supported(mut self, eject_supported: bool) -> Self {
+        self.eject_supported = Some(eject_supported);
+        self
+    }
+
+    /// Set whether the device can be removed from its parent while the system
+    /// is running, which makes it appear in the "Safely Remove Hardware" UI
+    pub const fn removable(mut self, removable: bool) -> Self {
+        self.removable = Some(removable);
+        self
+    }
+
+    /// Set whether the device is a docking peripheral
+    pub const fn dock_device(mut self, dock_device: bool) -> Self {
+        self.dock_device = Some(dock_device);
+        self
+    }
+
+    /// Set whether the device's instance ID is unique across the whole system,
+    /// rather than only among the children of its parent
+    pub const fn unique_id(mut self, unique_id: bool) -> Self {
+        self.unique_id = Some(unique_id);
+        self
+    }
+
+    /// Set whether the Plug and Play manager hides the device's installation
+    /// from the user
+    pub const fn silent_install(mut self, silent_install: bool) -> Self {
+        self.silent_install = Some(silent_install);
+        self
+    }
+
+    /// Set whether the device can be removed without warning, without the
+    /// system notifying the user of the unexpected removal
+    pub const fn surprise_removal_ok(mut self, surprise_removal_ok: bool) -> Self {
+        self.surprise_removal_ok = Some(surprise_removal_ok);
+        self
+    }
+
+    /// Set whether the device is disabled in hardware
+    pub const fn hardware_disabled(mut self, hardware_disabled: bool) -> Self {
+        self.hardware_disabled = Some(hardware_disabled);
+        self
+    }
+
+    /// Set whether Device Manager hides the device
+    pub const fn no_display_in_ui(mut self, no_display_in_ui: bool) -> Self {
+        self.no_display_in_ui = Some(no_display_in_ui);
+        self
+    }
+
+    /// Set the address of the device on its bus, whose meaning depends on the
+    /// bus
+    pub const fn address(mut self, address: ULONG) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Set the number of the device's slot, as shown to the user
+    pub const fn ui_number(mut self, ui_number: ULONG) -> Self {
+        self.ui_number = Some(ui_number);
+        self
+    }
+
+    /// Returns the `WDF_DEVICE_PNP_CAPABILITIES` of these capabilities,
+    /// initialized the same way as by `WDF_DEVICE_PNP_CAPABILITIES_INIT`
+    const fn to_raw(self) -> WDF_DEVICE_PNP_CAPABILITIES {
+        WDF_DEVICE_PNP_CAPABILITIES {
+            Size: DEVICE_PNP_CAPABILITIES_SIZE,
+            LockSupported: tri_state(self.lock_supported),
+            EjectSupported: tri_state(self.eject_supported),
+            Removable: tri_state(self.removable),
+            DockDevice: tri_state(self.dock_device),
+            UniqueID: tri_state(self.unique_id),
+            SilentInstall: tri_state(self.silent_install),
+            SurpriseRemovalOK: tri_state(self.surprise_removal_ok),
+            HardwareDisabled: tri_state(self.hardware_disabled),
+            NoDisplayInUI: tri_state(self.no_display_in_ui),
+            // `ULONG::MAX` leaves the address and UI number unchanged
+            Address: match self.address {
+                Some(address) => address,
+                None => ULONG::MAX,
+            },
+            UINumber: match self.ui_number {
+                Some(ui_number) => ui_number,
+                None => ULONG::MAX,
+            },
+        }
+    }
+}
+
+/// Returns the `WDF_TRI_STATE` of an optional setting, where `None` uses the
+/// default of the framework
+pub(super) const fn tri_state(value: Option<bool>) -> WDF_TRI_STATE {
+    match value {
+        None => _WDF_TRI_STATE::WdfUseDefault,
+        Some(true) => _WDF_TRI_STATE::WdfTrue,
+        Some(false) => _WDF_TRI_STATE::WdfFalse,
+    }
+}
+
+/// WDF Device.
+///
+/// A [`Device`] is created with a [`DeviceBuilder`], and is owned by the
+/// framework, which deletes it when the device is removed.
+#[derive(Clone, Copy)]
+pub struct Device {
+    wdf_device: WDFDEVICE,
+}
+
+// SAFETY: `WDFDEVICE` handles can be used from any thread.
+unsafe impl Send for Device {}
+
+// SAFETY: `Device` has no methods that mutate the device object.
+unsafe impl Sync for Device {}
+
+// SAFETY: The framework device object lives until the device is removed, and
+// `Device` handles are only passed to the driver while the device exists.
+unsafe impl WdfObject for Device {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_device.cast()
+    }
+}
+
+impl Device {
+    /// Wrap an existing WDF Device object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_device` must be a valid handle to a WDF Device object, and must
+    /// remain valid for the lifetime of the returned [`Device`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_device: WDFDEVICE) -> Self {
+        Self { wdf_device }
+    }
+
+    /// Returns the raw `WDFDEVICE` handle wrapped by this [`Device`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDEVICE {
+        self.wdf_device
+    }
+
+    /// Report that the device failed in a way that the driver cannot recover
+    /// from, such as an unrecoverable hardware error, and have the framework
+    /// take `action`
+    ///
+    /// This is used for errors that are detected outside of the
+    /// [`PnpPowerCallbacks`], such as in an interrupt's DPC, instead of
+    /// bugchecking. It can be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn set_failed(&self, action: FailedAction) {
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceSetFailed,
+                self.wdf_device,
+                action.as_raw()
+            );
+        }
+    }
+
+    /// Returns the local I/O target of the device, which sends requests to the
+    /// next lower driver in the device's driver stack
+    #[must_use]
+    pub fn io_target(&self) -> IoTarget {
+        let wdf_io_target: WDFIOTARGET;
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used.
+        unsafe {
+            wdf_io_target =
+                macros::call_unsafe_wdf_function_binding!(WdfDeviceGetIoTarget, self.wdf_device);
+        }
+        // SAFETY: The local I/O target of a device lives as long as the device.
+        unsafe { IoTarget::from_raw(wdf_io_target) }
+    }
+
+    /// Set the Plug and Play capabilities of the device that are set in
+    /// `capabilities`, overriding the values reported by the bus driver
+    ///
+    /// This is usually called from `EvtDriverDeviceAdd`, after the device is
+    /// created, or from `EvtDevicePrepareHardware`. It must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    pub fn set_pnp_capabilities(&self, capabilities: PnpCapabilities) {
+        let mut capabilities = capabilities.to_raw();
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used. `capabilities` is valid for the duration
+        // of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceSetPnpCapabilities,
+                self.wdf_device,
+                &mut capabilities
+            );
+        }
+    }
+
+    /// Create a device interface of `interface_class` for the device, so that
+    /// applications and other drivers can find and open it
+    ///
+    /// `reference_string` distinguishes multiple interfaces of the same class
+    /// of the device, and is appended to the interface's symbolic link name.
+    /// The framework enables the interface once the device starts, and
+    /// disables it when the device is removed. This must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`, and is usually called from `EvtDriverDeviceAdd`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the device interface. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreatedeviceinterface#return-value)
+    pub fn create_interface(
+        &self,
+        interface_class: &GUID,
+        reference_string: Option<&UNICODE_STRING>,
+    ) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used. `interface_class` and `reference_string`
+        // are only read from, and are copied by the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceCreateDeviceInterface,
+                self.wdf_device,
+                interface_class,
+                reference_string.map_or(core::ptr::null(), core::ptr::from_ref)
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Enable or disable the device interface of `interface_class` with
+    /// `reference_string` that was created with [`Device::create_interface()`]
+    ///
+    /// Drivers only need to call this to disable an interface while the device
+    /// is running, or to enable it again afterwards. This must be called at
+    /// `IRQL` = `PASSIVE_LEVEL`.
+    pub fn set_interface_state(
+        &self,
+        interface_class: &GUID,
+        reference_string: Option<&UNICODE_STRING>,
+        enabled: bool,
+    ) {
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used. `interface_class` and `reference_string`
+        // are only read from for the duration of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceSetDeviceInterfaceState,
+                self.wdf_device,
+                interface_class,
+                reference_string.map_or(core::ptr::null(), core::ptr::from_ref),
+                u8::from(enabled)
+            );
+        }
+    }
+
+    /// Create the symbolic link `\DosDevices\<name>` to the device, which
+    /// must have been named with [`DeviceBuilder::name()`]
+    ///
+    /// This lets applications open the device as `\\.\<name>`. The framework
+    /// deletes the symbolic link when the device is removed. This must be
+    /// called at `IRQL` = `PASSIVE_LEVEL`, and `name` must not contain a `\`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is invalid, or if WDF fails to contruct the symbolic link. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreatesymboliclink#return-value)
+    pub fn create_dos_device_name(&self, name: &str) -> Result<(), NTSTATUS> {
+        let name = UnicodeBuffer::object_path("\\DosDevices\\", name)?;
+        let name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used. `name` borrows a buffer that outlives the
+        // call, which copies it.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceCreateSymbolicLink,
+                self.wdf_device,
+                &name
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Pass `request` back to the framework, which adds it to the queue that
+    /// the device's requests of its type are dispatched to
+    ///
+    /// This is called from the callback set with
+    /// [`DeviceBuilder::io_in_caller_context()`] once the request has been
+    /// preprocessed. If the request cannot be queued, it is completed with the
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request cannot be queued, such as when the queue is not accepting requests. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceenqueuerequest#return-value)
+    pub fn enqueue_request(&self, request: Request) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `wdf_device` is a private member of `Device`, and the framework keeps
+        // the device valid while it is used. `request` is a valid request that the
+        // driver owns.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceEnqueueRequest,
+                self.wdf_device,
+                request.as_raw()
+            );
+        }
+        if !nt_success(nt_status) {
+            request.complete(nt_status, 0);
+            return Err(nt_status);
+        }
+
+        // Ownership of the request has passed to the framework
+        let _ = request.into_raw();
+        Ok(())
+    }
+}
+
+fn nt_status_from(result: Result<(), NTSTATUS>) -> NTSTATUS {
+    result.map_or_else(|nt_status| nt_status, |()| STATUS_SUCCESS)
+}
+
+/// Returns the [`PnpPowerCallbacks`] attached to `wdf_device`
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to
+/// by [`DeviceBuilder::create()`].
+unsafe fn pnp_power_callbacks<'a, C>(wdf_device: WDFDEVICE) -> &'a C {
+    // SAFETY: The caller guarantees that a `C` was attached to `wdf_device`.
+    unsafe { closure::<C>(wdf_device.cast()) }
+}
+
+/// The `EvtDevicePrepareHardware` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_prepare_hardware<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    resources_raw: WDFCMRESLIST,
+    resources_translated: WDFCMRESLIST,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    // SAFETY: The framework passes valid resource lists, which remain valid for the
+    // duration of the call.
+    let resources_raw = unsafe { ResourceList::from_raw(resources_raw, false) };
+    // SAFETY: The framework passes valid resource lists, which remain valid for the
+    // duration of the call.
+    let resources_translated = unsafe { ResourceList::from_raw(resources_translated, true) };
+    nt_status_from(callbacks.prepare_hardware(
+        &Device { wdf_device },
+        resources_raw,
+        resources_translated,
+    ))
+}
+
+/// The `EvtDeviceReleaseHardware` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_release_hardware<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    resources_translated: WDFCMRESLIST,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    // SAFETY: The framework passes a valid resource list, which remains valid for
+    // the duration of the call.
+    let resources_translated = unsafe { ResourceList::from_raw(resources_translated, true) };
+    nt_status_from(callbacks.release_hardware(&Device { wdf_device }, resources_translated))
+}
+
+/// The `EvtDeviceD0Entry` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_d0_entry<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    previous_state: WDF_POWER_DEVICE_STATE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    nt_status_from(callbacks.d0_entry(
+        &Device { wdf_device },
+        PowerDeviceState::from_raw(previous_state),
+    ))
+}
+
+/// The `EvtDeviceD0Exit` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_d0_exit<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    target_state: WDF_POWER_DEVICE_STATE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    nt_status_from(callbacks.d0_exit(
+        &Device { wdf_device },
+        PowerDeviceState::from_raw(target_state),
+    ))
+}
+
+/// The `EvtDeviceD0EntryPostInterruptsEnabled` of devices created by
+/// [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_d0_entry_post_interrupts_enabled<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    previous_state: WDF_POWER_DEVICE_STATE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    nt_status_from(callbacks.d0_entry_post_interrupts_enabled(
+        &Device { wdf_device },
+        PowerDeviceState::from_raw(previous_state),
+    ))
+}
+
+/// The `EvtDeviceD0ExitPreInterruptsDisabled` of devices created by
+/// [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_d0_exit_pre_interrupts_disabled<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+    target_state: WDF_POWER_DEVICE_STATE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    nt_status_from(callbacks.d0_exit_pre_interrupts_disabled(
+        &Device { wdf_device },
+        PowerDeviceState::from_raw(target_state),
+    ))
+}
+
+/// The `EvtDeviceSelfManagedIoInit` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_self_managed_io_init<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    nt_status_from(callbacks.self_managed_io_init(&Device { wdf_device }))
+}
+
+/// The `EvtDeviceSelfManagedIoCleanup` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_self_managed_io_cleanup<C: PnpPowerCallbacks>(
+    wdf_device: WDFDEVICE,
+) {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    callbacks.self_managed_io_cleanup(&Device { wdf_device });
+}
+
+/// The `EvtDeviceSurpriseRemoval` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `C` was attached to.
+unsafe extern "C" fn evt_device_surprise_removal<C: PnpPowerCallbacks>(wdf_device: WDFDEVICE) {
+    // SAFETY: The framework only calls this with the device, which had a `C`
+    // attached when it was created.
+    let callbacks = unsafe { pnp_power_callbacks::<C>(wdf_device) };
+    callbacks.surprise_removal(&Device { wdf_device });
+}
+
+/// The `EvtIoInCallerContext` of devices created by [`DeviceBuilder`]
+///
+/// # Safety
+///
+/// `wdf_device` and `wdf_request` must be valid handles to a device whose
+/// `EvtIoInCallerContext` callback is an `F`, which must be zero-sized, and to
+/// a request that the framework passed to the driver.
+unsafe extern "C" fn evt_io_in_caller_context<F>(wdf_device: WDFDEVICE, wdf_request: WDFREQUEST)
+where
+    F: Fn(&Device, Request),
+{
+    // SAFETY: `F` is zero-sized, so a dangling pointer is valid for reads of it,
+    // and `DeviceBuilder::io_in_caller_context()` forgot the `F` it was passed,
+    // so this refers to the value that it moved into the framework.
+    let callback = unsafe { NonNull::<F>::dangling().as_ref() };
+    // SAFETY: The framework only calls this with the device the callback was set
+    // for, which remains valid for the duration of the call.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The driver owns the request until it completes it or passes it back
+    // to the framework with `Device::enqueue_request()`.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    callback(&device, request);
+}
@@ -0,0 +1,1057 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    ffi::c_void,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicI32, AtomicPtr, AtomicU8, Ordering},
+    task::{Context, Poll},
+};
+
+use wdk_sys::{
+    macros,
+    _WDF_DMA_DIRECTION,
+    _WDF_DMA_PROFILE,
+    BOOLEAN,
+    NTSTATUS,
+    PFN_WDF_PROGRAM_DMA,
+    PSCATTER_GATHER_LIST,
+    SCATTER_GATHER_ELEMENT,
+    SCATTER_GATHER_LIST,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFCONTEXT,
+    WDFDEVICE,
+    WDFDMAENABLER,
+    WDFDMATRANSACTION,
+    WDFOBJECT,
+    WDF_DMA_DIRECTION,
+    WDF_DMA_ENABLER_CONFIG,
+    WDF_DMA_PROFILE,
+    WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+use super::{
+    child::ChildObject,
+    context::{attach_closure_in, closure_in, ClosureSlot},
+    ContextTypeInfo,
+    Device,
+    Request,
+    WdfObject,
+};
+use crate::{nt_success, sync::AtomicWaker};
+
+// `WDF_DMA_ENABLER_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const DMA_ENABLER_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_DMA_ENABLER_CONFIG>() as ULONG;
+
+/// The context type of the [`DmaTransactionState`] attached to DMA
+/// transactions
+static DMA_TRANSACTION_STATE_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::DmaTransactionState",
+    1,
+    &DMA_TRANSACTION_STATE_CONTEXT_TYPE_INFO,
+);
+
+/// The transaction is not being awaited by a [`DmaTransactionFuture`]
+const NOT_AWAITED: u8 = 0;
+/// The transaction is being awaited, and has not finished
+const AWAITED: u8 = 1;
+/// The transaction is finishing, and its status is being recorded
+const FINISHING: u8 = 2;
+/// The transaction has finished, and its future has not returned yet
+const FINISHED: u8 = 3;
+/// The future awaiting the transaction was dropped before it finished
+const ABANDONED: u8 = 4;
+
+/// The kind of DMA that a device's bus-master DMA controller supports, passed
+/// to [`DmaEnabler::create()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaProfile {
+    /// Packet-based DMA, with 32-bit addresses
+    Packet,
+    /// Scatter/gather DMA, with 32-bit addresses
+    ScatterGather,
+    /// Packet-based DMA, with 64-bit addresses
+    Packet64,
+    /// Scatter/gather DMA, with 64-bit addresses
+    ScatterGather64,
+    /// Scatter/gather DMA, with 32-bit addresses, where reads and writes can
+    /// run at the same time
+    ScatterGatherDuplex,
+    /// Scatter/gather DMA, with 64-bit addresses, where reads and writes can
+    /// run at the same time
+    ScatterGather64Duplex,
+}
+
+impl DmaProfile {
+    const fn as_raw(self) -> WDF_DMA_PROFILE {
+        match self {
+            Self::Packet => _WDF_DMA_PROFILE::WdfDmaProfilePacket,
+            Self::ScatterGather => _WDF_DMA_PROFILE::WdfDmaProfileScatterGather,
+            Self::Packet64 => _WDF_DMA_PROFILE::WdfDmaProfilePacket64,
+            Self::ScatterGather64 => _WDF_DMA_PROFILE::WdfDmaProfileScatterGather64,
+            Self::ScatterGatherDuplex => _WDF_DMA_PROFILE::WdfDmaProfileScatterGatherDuplex,
+            Self::ScatterGather64Duplex => _WDF_DMA_PROFILE::WdfDmaProfileScatterGather64Duplex,
+        }
+    }
+}
+
+/// The direction of a DMA transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaDirection {
+    /// The device writes to system memory, as for a read request
+    ReadFromDevice,
+    /// The device reads from system memory, as for a write request
+    WriteToDevice,
+}
+
+impl DmaDirection {
+    const fn as_raw(self) -> WDF_DMA_DIRECTION {
+        match self {
+            Self::ReadFromDevice => _WDF_DMA_DIRECTION::WdfDmaDirectionReadFromDevice,
+            Self::WriteToDevice => _WDF_DMA_DIRECTION::WdfDmaDirectionWriteToDevice,
+        }
+    }
+
+    const fn from_raw(direction: WDF_DMA_DIRECTION) -> Self {
+        if direction == _WDF_DMA_DIRECTION::WdfDmaDirectionReadFromDevice {
+            Self::ReadFromDevice
+        } else {
+            Self::WriteToDevice
+        }
+    }
+}
+
+/// WDF DMA Enabler.
+///
+/// A framework DMA enabler object represents a device's bus-master DMA
+/// controller, and is used to create the [`DmaTransaction`]s that transfer
+/// data with it. DMA enablers are typically created in `EvtDriverDeviceAdd`,
+/// and are deleted by the framework along with their device.
+pub struct DmaEnabler {
+    wdf_dma_enabler: WDFDMAENABLER,
+}
+
+// SAFETY: `WDFDMAENABLER` handles can be used from any thread.
+unsafe impl Send for DmaEnabler {}
+
+// SAFETY: `DmaEnabler` has no methods that mutate the enabler through `&self`
+// other than `set_maximum_scatter_gather_elements`, which WDF allows to be
+// called from any thread.
+unsafe impl Sync for DmaEnabler {}
+
+// SAFETY: The contract of `DmaEnabler::from_raw` guarantees that
+// `wdf_dma_enabler` is valid for the lifetime of the `DmaEnabler`.
+unsafe impl WdfObject for DmaEnabler {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_dma_enabler.cast()
+    }
+}
+
+impl DmaEnabler {
+    /// Try to create a DMA enabler for `device`, whose DMA controller
+    /// supports `profile`, and transfers at most `maximum_length` bytes at a
+    /// time
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`. The enabler is deleted
+    /// along with `device`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the DMA enabler. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDmaEnabler Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmaenabler/nf-wdfdmaenabler-wdfdmaenablercreate#return-value)
+    pub fn create(
+        device: &Device,
+        profile: DmaProfile,
+        maximum_length: usize,
+    ) -> Result<Self, NTSTATUS> {
+        let mut dma_enabler_config = WDF_DMA_ENABLER_CONFIG {
+            Size: DMA_ENABLER_CONFIG_SIZE,
+            Profile: profile.as_raw(),
+            MaximumLength: maximum_length,
+            ..Default::default()
+        };
+
+        let mut dma_enabler = Self {
+            wdf_dma_enabler: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid handle to a device object, and
+        // `dma_enabler_config` is valid for the duration of the call.
+        // `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerCreate,
+                device.as_raw(),
+                &mut dma_enabler_config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut dma_enabler.wdf_dma_enabler,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(dma_enabler)
+    }
+
+    /// Wrap an existing WDF DMA Enabler object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_dma_enabler` must be a valid handle to a WDF DMA Enabler object,
+    /// and must remain valid for the lifetime of the returned [`DmaEnabler`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_dma_enabler: WDFDMAENABLER) -> Self {
+        Self { wdf_dma_enabler }
+    }
+
+    /// Returns the raw `WDFDMAENABLER` handle wrapped by this [`DmaEnabler`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDMAENABLER {
+        self.wdf_dma_enabler
+    }
+
+    /// Returns the maximum number of bytes that the device transfers at a
+    /// time
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn maximum_length(&self) -> usize {
+        let maximum_length;
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, and the
+        // contract of `DmaEnabler::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            maximum_length = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerGetMaximumLength,
+                self.wdf_dma_enabler
+            );
+        }
+        maximum_length
+    }
+
+    /// Returns the maximum number of elements in the scatter/gather lists
+    /// passed to the program DMA callbacks of the enabler's transactions
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn maximum_scatter_gather_elements(&self) -> usize {
+        let maximum_elements;
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, and the
+        // contract of `DmaEnabler::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            maximum_elements = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerGetMaximumScatterGatherElements,
+                self.wdf_dma_enabler
+            );
+        }
+        maximum_elements
+    }
+
+    /// Set the maximum number of elements in the scatter/gather lists passed
+    /// to the program DMA callbacks of the enabler's transactions, which
+    /// otherwise is not limited
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`, from the callback
+    /// that created the enabler.
+    pub fn set_maximum_scatter_gather_elements(&self, maximum_elements: usize) {
+        // SAFETY: `wdf_dma_enabler` is a private member of `DmaEnabler`, and the
+        // contract of `DmaEnabler::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDmaEnablerSetMaximumScatterGatherElements,
+                self.wdf_dma_enabler,
+                maximum_elements
+            );
+        }
+    }
+}
+
+/// The scatter/gather list of a DMA transfer, passed to the program DMA
+/// callback of a [`DmaTransaction`].
+///
+/// Each element is a range of device-logical addresses for the device to
+/// transfer to or from, which are iterated over in order with
+/// [`ScatterGatherList::iter()`]. The list is only valid for the duration of
+/// the callback.
+///
+/// ```ignore
+/// for (descriptor, element) in descriptors.iter_mut().zip(sg_list) {
+///     descriptor.set(element.address(), element.length());
+/// }
+/// ```
+#[repr(transparent)]
+pub struct ScatterGatherList(SCATTER_GATHER_LIST);
+
+impl ScatterGatherList {
+    /// Wrap a raw `SCATTER_GATHER_LIST`, such as one built by the WDM
+    /// `GetScatterGatherList` of a DMA adapter
+    ///
+    /// # Safety
+    ///
+    /// `sg_list` must point to a valid `SCATTER_GATHER_LIST`, whose
+    /// `NumberOfElements` elements directly follow its header, and which is
+    /// neither freed nor mutated for `'a`.
+    #[must_use]
+    pub const unsafe fn from_raw<'a>(sg_list: *const SCATTER_GATHER_LIST) -> &'a Self {
+        // SAFETY: The caller guarantees that `sg_list` points to a valid list for
+        // `'a`, and `ScatterGatherList` is `repr(transparent)`.
+        unsafe { &*sg_list.cast::<Self>() }
+    }
+
+    /// Returns a pointer to the raw `SCATTER_GATHER_LIST` wrapped by this
+    /// [`ScatterGatherList`]
+    #[must_use]
+    pub const fn as_raw(&self) -> *const SCATTER_GATHER_LIST {
+        &self.0
+    }
+
+    /// Returns the elements of the list
+    #[must_use]
+    pub fn elements(&self) -> &[ScatterGatherElement] {
+        let elements = core::ptr::addr_of!(self.0.Elements).cast::<ScatterGatherElement>();
+        // SAFETY: A `ScatterGatherList` is only constructed from a list whose
+        // `NumberOfElements` elements directly follow its header, and which is valid
+        // for as long as `self` is borrowed. `ScatterGatherElement` is
+        // `repr(transparent)`.
+        unsafe { core::slice::from_raw_parts(elements, self.len()) }
+    }
+
+    /// Returns an iterator over the elements of the list, in order
+    pub fn iter(&self) -> core::slice::Iter<'_, ScatterGatherElement> {
+        self.elements().iter()
+    }
+
+    /// Returns the number of elements in the list
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.NumberOfElements as usize
+    }
+
+    /// Returns whether the list has no elements
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.NumberOfElements == 0
+    }
+
+    /// Returns the total number of bytes in the list's elements
+    #[must_use]
+    pub fn total_length(&self) -> usize {
+        self.iter().map(|element| element.length() as usize).sum()
+    }
+}
+
+impl<'a> IntoIterator for &'a ScatterGatherList {
+    type IntoIter = core::slice::Iter<'a, ScatterGatherElement>;
+    type Item = &'a ScatterGatherElement;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl core::fmt::Debug for ScatterGatherList {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A range of device-logical addresses in a [`ScatterGatherList`]
+#[repr(transparent)]
+pub struct ScatterGatherElement(SCATTER_GATHER_ELEMENT);
+
+impl ScatterGatherElement {
+    /// Returns the device-logical address of the first byte of the range
+    #[must_use]
+    pub fn address(&self) -> u64 {
+        // SAFETY: Every bit pattern of a `PHYSICAL_ADDRESS` is a valid `QuadPart`.
+        unsafe { self.0.Address.QuadPart }.cast_unsigned()
+    }
+
+    /// Returns the number of bytes in the range
+    #[must_use]
+    pub const fn length(&self) -> u32 {
+        self.0.Length
+    }
+}
+
+impl core::fmt::Debug for ScatterGatherElement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ScatterGatherElement")
+            .field("address", &format_args!("{:#X}", self.address()))
+            .field("length", &self.length())
+            .finish()
+    }
+}
+
+/// The result of reporting that a DMA transfer completed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaCompletion {
+    /// The transaction has more transfers, and the framework calls its
+    /// program DMA callback again for the next one
+    MoreTransfers,
+    /// The transaction is complete, with the final status of its transfers.
+    /// Unless the transaction is awaited, the driver must call
+    /// [`DmaTransaction::complete_request()`] next.
+    Complete(NTSTATUS),
+}
+
+impl DmaCompletion {
+    const fn from_raw(complete: BOOLEAN, nt_status: NTSTATUS) -> Self {
+        if complete == 0 {
+            Self::MoreTransfers
+        } else {
+            Self::Complete(nt_status)
+        }
+    }
+}
+
+/// WDF DMA Transaction.
+///
+/// A [`DmaTransaction`] transfers the buffer of a [`Request`] with the device,
+/// in as many transfers as the DMA enabler's maximum length and the system's
+/// map registers require. The framework calls the transaction's program DMA
+/// callback with the scatter/gather list of each transfer, which programs the
+/// device to start it. Once the device reports that a transfer finished,
+/// typically from an interrupt's DPC, the driver calls
+/// [`DmaTransaction::dma_completed()`], and once that returns
+/// [`DmaCompletion::Complete`], completes the request with
+/// [`DmaTransaction::complete_request()`]. The transaction can then be reused
+/// for another request.
+///
+/// Alternatively, [`DmaTransaction::execute()`] returns a future that resolves
+/// once the transaction completes, so that multi-stage transfers can be
+/// written as `async` code rather than as a state machine driven by the DPC.
+///
+/// The lifetime `'p` is bounded by the transaction's DMA enabler. Dropping a
+/// [`DmaTransaction`] deletes it, so it must not be dropped while it is
+/// executing. Use [`DmaTransaction::into_parent_owned()`] to leave the
+/// transaction to be deleted with its DMA enabler instead.
+///
+/// ```ignore
+/// let transaction = DmaTransaction::try_new(&dma_enabler, |transaction, device, direction, sg_list| {
+///     program_descriptors(device, direction, sg_list.elements())
+/// })?;
+/// transaction.execute_request(request, DmaDirection::ReadFromDevice)?;
+///
+/// // Or, from `async` code:
+/// let (request, result) = transaction.execute(request, DmaDirection::ReadFromDevice)?.await;
+/// ```
+pub struct DmaTransaction<'p> {
+    wdf_dma_transaction: WDFDMATRANSACTION,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFDMATRANSACTION` handles can be used from any thread, the
+// request is stored in an atomic, and the callback is required to be `Send` and
+// `Sync`.
+unsafe impl Send for DmaTransaction<'_> {}
+
+// SAFETY: All methods of `DmaTransaction` that take `&self` can be called from
+// multiple threads, and the request of the transaction is only taken by one of
+// them.
+unsafe impl Sync for DmaTransaction<'_> {}
+
+// SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+// originally created by WDF, and the lifetime of a `DmaTransaction` is bounded
+// by its DMA enabler.
+unsafe impl WdfObject for DmaTransaction<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_dma_transaction.cast()
+    }
+}
+
+impl<'p> DmaTransaction<'p> {
+    /// Try to construct a WDF DMA Transaction object, parented to
+    /// `dma_enabler`, that calls `program_dma` to start each of its transfers
+    ///
+    /// `program_dma` is called at `IRQL` = `DISPATCH_LEVEL`, with the
+    /// transaction, its device, the direction of the transfer, and the
+    /// scatter/gather list to program the device with. If it returns an
+    /// error, the transaction is completed with no bytes transferred, and its
+    /// request is completed with the error.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a DMA transaction, or to allocate storage for `program_dma`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactioncreate#return-value)
+    pub fn try_new<F>(dma_enabler: &'p DmaEnabler, program_dma: F) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&Self, &Device, DmaDirection, &ScatterGatherList) -> Result<(), NTSTATUS>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let mut wdf_dma_transaction: WDFDMATRANSACTION = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. `WDF_NO_OBJECT_ATTRIBUTES` is allowed, and
+        // parents the transaction to `dma_enabler`.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionCreate,
+                dma_enabler.as_raw(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_dma_transaction,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        let dma_transaction = Self {
+            wdf_dma_transaction,
+            // SAFETY: `wdf_dma_transaction` is a valid handle to the transaction that
+            // was just created, which is only deleted by this `ChildObject`, and whose
+            // parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_dma_transaction.cast()) },
+        };
+
+        let state = DmaTransactionState {
+            header: DmaTransactionHeader {
+                request: AtomicPtr::new(core::ptr::null_mut()),
+                evt_program_dma: Some(evt_program_dma::<'p, F>),
+                awaiter: AtomicU8::new(NOT_AWAITED),
+                status: AtomicI32::new(STATUS_SUCCESS),
+                waker: AtomicWaker::new(),
+            },
+            program_dma,
+        };
+        // SAFETY: `wdf_dma_transaction` is a valid handle to the transaction that was
+        // just created, and nothing else has been attached to it. The transaction has
+        // not been initialized, so `evt_program_dma` cannot run before the state is
+        // attached. If this fails, dropping `dma_transaction` deletes the
+        // transaction.
+        unsafe {
+            attach_closure_in::<DmaTransactionStateSlot, _>(
+                dma_transaction.wdf_dma_transaction.cast(),
+                state,
+            )
+        }?;
+        Ok(dma_transaction)
+    }
+
+    /// Initialize the transaction with the buffer of `request`, and start
+    /// transferring it in `direction`
+    ///
+    /// The framework calls the transaction's program DMA callback for the
+    /// first transfer, possibly before this returns. The transaction keeps the
+    /// request until [`DmaTransaction::complete_request()`] completes it.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transaction is already executing a request, or if WDF fails to initialize or execute the transaction, in which case the request is completed with the [`NTSTATUS`] of the failure, which the error variant will contain. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactionexecute#return-value)
+    pub fn execute_request(
+        &self,
+        request: Request,
+        direction: DmaDirection,
+    ) -> Result<(), NTSTATUS> {
+        self.start(request, direction, NOT_AWAITED)
+    }
+
+    /// Initialize the transaction with the buffer of `request`, and start
+    /// transferring it in `direction`, returning a future that resolves once
+    /// the transaction completes
+    ///
+    /// The future resolves once [`DmaTransaction::dma_completed()`], or one of
+    /// its variants, returns [`DmaCompletion::Complete`], or the program DMA
+    /// callback fails. It then releases the transaction, and returns the
+    /// request, along with the number of bytes transferred or the error that
+    /// the transaction failed with, for the driver to complete the request or
+    /// start another transfer with it. The future is woken at the `IRQL` that
+    /// the transaction completes at, typically `DISPATCH_LEVEL`.
+    ///
+    /// If the future is dropped before it resolves, the request is completed
+    /// with the transaction's status once it completes instead.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the transaction is already executing a request, or if WDF fails to initialize or execute the transaction, in which case the request is completed with the [`NTSTATUS`] of the failure, which the error variant will contain. Full error documentation is available in the [WDFDmaTransaction Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdmatransaction/nf-wdfdmatransaction-wdfdmatransactionexecute#return-value)
+    pub fn execute(
+        &self,
+        request: Request,
+        direction: DmaDirection,
+    ) -> Result<DmaTransactionFuture<'_, 'p>, NTSTATUS> {
+        self.start(request, direction, AWAITED)?;
+        Ok(DmaTransactionFuture {
+            dma_transaction: self,
+            resolved: false,
+        })
+    }
+
+    /// Report that the device finished the current transfer
+    /// (`WdfDmaTransactionDmaCompleted`)
+    ///
+    /// If the transaction has more transfers, the framework calls its program
+    /// DMA callback for the next one before this returns.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`, typically from the
+    /// DPC of the device's interrupt.
+    #[must_use]
+    pub fn dma_completed(&self) -> DmaCompletion {
+        let completion = self.raw_dma_completed();
+        if let DmaCompletion::Complete(nt_status) = completion {
+            self.finish(nt_status);
+        }
+        completion
+    }
+
+    /// Calls `WdfDmaTransactionDmaCompleted`, without finishing the transaction
+    /// if it completes
+    fn raw_dma_completed(&self) -> DmaCompletion {
+        let mut nt_status = STATUS_SUCCESS;
+        let complete;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. `nt_status` is valid for the duration of the call.
+        unsafe {
+            complete = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionDmaCompleted,
+                self.wdf_dma_transaction,
+                &mut nt_status
+            );
+        }
+        DmaCompletion::from_raw(complete, nt_status)
+    }
+
+    /// Report that the device finished the current transfer, after
+    /// transferring `transferred_length` bytes of it
+    /// (`WdfDmaTransactionDmaCompletedWithLength`)
+    ///
+    /// This is used by devices that can transfer fewer bytes than they were
+    /// programmed with. This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn dma_completed_with_length(&self, transferred_length: usize) -> DmaCompletion {
+        let completion = self.raw_dma_completed_with_length(transferred_length);
+        if let DmaCompletion::Complete(nt_status) = completion {
+            self.finish(nt_status);
+        }
+        completion
+    }
+
+    /// Calls `WdfDmaTransactionDmaCompletedWithLength`, without finishing the
+    /// transaction if it completes
+    fn raw_dma_completed_with_length(&self, transferred_length: usize) -> DmaCompletion {
+        let mut nt_status = STATUS_SUCCESS;
+        let complete;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. `nt_status` is valid for the duration of the call.
+        unsafe {
+            complete = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionDmaCompletedWithLength,
+                self.wdf_dma_transaction,
+                transferred_length,
+                &mut nt_status
+            );
+        }
+        DmaCompletion::from_raw(complete, nt_status)
+    }
+
+    /// Report that the device finished the current transfer after
+    /// transferring `final_transferred_length` bytes of it, and that the
+    /// transaction has no more transfers (`WdfDmaTransactionDmaCompletedFinal`)
+    ///
+    /// This is used to end a transaction early, such as when the device
+    /// reports an error. This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn dma_completed_final(&self, final_transferred_length: usize) -> DmaCompletion {
+        let completion = self.raw_dma_completed_final(final_transferred_length);
+        if let DmaCompletion::Complete(nt_status) = completion {
+            self.finish(nt_status);
+        }
+        completion
+    }
+
+    /// Calls `WdfDmaTransactionDmaCompletedFinal`, without finishing the
+    /// transaction if it completes
+    fn raw_dma_completed_final(&self, final_transferred_length: usize) -> DmaCompletion {
+        let mut nt_status = STATUS_SUCCESS;
+        let complete;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. `nt_status` is valid for the duration of the call.
+        unsafe {
+            complete = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionDmaCompletedFinal,
+                self.wdf_dma_transaction,
+                final_transferred_length,
+                &mut nt_status
+            );
+        }
+        DmaCompletion::from_raw(complete, nt_status)
+    }
+
+    /// Returns the number of bytes that the transaction has transferred so
+    /// far
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn bytes_transferred(&self) -> usize {
+        let bytes_transferred;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state.
+        unsafe {
+            bytes_transferred = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionGetBytesTransferred,
+                self.wdf_dma_transaction
+            );
+        }
+        bytes_transferred
+    }
+
+    /// Release the transaction, and complete its request with `nt_status` and
+    /// the number of bytes that the transaction transferred
+    ///
+    /// This is called once [`DmaTransaction::dma_completed()`] returns
+    /// [`DmaCompletion::Complete`], after which the transaction can be reused
+    /// for another request. It does nothing if the transaction has no request,
+    /// such as when its request was already completed. If the transaction was
+    /// started with [`DmaTransaction::execute()`], this resolves its future
+    /// with `nt_status` instead, unless it has already resolved.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn complete_request(&self, nt_status: NTSTATUS) {
+        if !self.finish(nt_status) {
+            self.release_and_complete(nt_status);
+        }
+    }
+
+    /// Leave the WDF DMA transaction object to be deleted along with its DMA
+    /// enabler, rather than when the [`DmaTransaction`] is dropped, so that
+    /// the [`DmaTransaction`] can be stored in the context of its device
+    ///
+    /// # Safety
+    ///
+    /// The returned [`DmaTransaction`] must not be used after its DMA enabler
+    /// is deleted. Storing it in the context of the enabler's device satisfies
+    /// this, as long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> DmaTransaction<'static> {
+        let Self {
+            wdf_dma_transaction,
+            object,
+        } = self;
+        core::mem::forget(object);
+        DmaTransaction {
+            wdf_dma_transaction,
+            object: ChildObject::parent_owned(wdf_dma_transaction.cast()),
+        }
+    }
+
+    /// Initialize the transaction with `request`, and execute it, with
+    /// `awaiter` as its initial awaiter state
+    fn start(
+        &self,
+        request: Request,
+        direction: DmaDirection,
+        awaiter: u8,
+    ) -> Result<(), NTSTATUS> {
+        let header = self.header();
+        let wdf_request = request.into_raw();
+        if header
+            .request
+            .compare_exchange(
+                core::ptr::null_mut(),
+                wdf_request.cast(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // SAFETY: `wdf_request` was taken from a `Request` above, and is not used
+            // other than through this one.
+            let request = unsafe { Request::from_raw(wdf_request) };
+            request.complete(STATUS_INVALID_DEVICE_STATE, 0);
+            return Err(STATUS_INVALID_DEVICE_STATE);
+        }
+        header.awaiter.store(awaiter, Ordering::Release);
+
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. `wdf_request` is owned by the driver, and `evt_program_dma` is
+        // the callback of the state attached to the transaction.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionInitializeUsingRequest,
+                self.wdf_dma_transaction,
+                wdf_request,
+                header.evt_program_dma,
+                direction.as_raw()
+            );
+        }
+        if !nt_success(nt_status) {
+            header.awaiter.store(NOT_AWAITED, Ordering::Release);
+            if let Some(request) = self.take_request() {
+                request.complete(nt_status, 0);
+            }
+            return Err(nt_status);
+        }
+
+        let nt_status;
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. The program DMA callback does not use its context.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionExecute,
+                self.wdf_dma_transaction,
+                core::ptr::null_mut()
+            );
+        }
+        if !nt_success(nt_status) {
+            header.awaiter.store(NOT_AWAITED, Ordering::Release);
+            self.release_and_complete(nt_status);
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Hand the final status of the transaction to the future awaiting it, if
+    /// there is one
+    ///
+    /// Returns `false` if the transaction is not being awaited, in which case
+    /// the caller completes the request.
+    fn finish(&self, nt_status: NTSTATUS) -> bool {
+        let header = self.header();
+        match header.awaiter.compare_exchange(
+            AWAITED,
+            FINISHING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                header.status.store(nt_status, Ordering::Relaxed);
+                header.awaiter.store(FINISHED, Ordering::Release);
+                header.waker.wake();
+                true
+            }
+            // The future already has the status, and releases the transaction
+            Err(FINISHING | FINISHED) => true,
+            Err(ABANDONED) => {
+                header.awaiter.store(NOT_AWAITED, Ordering::Release);
+                self.release_and_complete(nt_status);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Release the transaction, returning its request and the number of bytes
+    /// it transferred, if it has a request
+    fn release(&self) -> Option<(Request, usize)> {
+        let request = self.take_request()?;
+        let bytes_transferred = self.bytes_transferred();
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`,
+        // originally created by WDF, and this module guarantees that it is always in a
+        // valid state. The transaction was initialized with `request`, which it has
+        // not been released from yet.
+        unsafe {
+            let _ = macros::call_unsafe_wdf_function_binding!(
+                WdfDmaTransactionRelease,
+                self.wdf_dma_transaction
+            );
+        }
+        Some((request, bytes_transferred))
+    }
+
+    /// Release the transaction, and complete its request with `nt_status`, if
+    /// it has a request
+    fn release_and_complete(&self, nt_status: NTSTATUS) {
+        if let Some((request, bytes_transferred)) = self.release() {
+            request.complete(nt_status, bytes_transferred);
+        }
+    }
+
+    /// Take the request that the transaction is executing, if it has one
+    fn take_request(&self) -> Option<Request> {
+        let wdf_request = self
+            .header()
+            .request
+            .swap(core::ptr::null_mut(), Ordering::AcqRel);
+        if wdf_request.is_null() {
+            return None;
+        }
+        // SAFETY: Only `start` stores requests, which it takes ownership of,
+        // and swapping it out ensures that only one `Request` is made from it.
+        Some(unsafe { Request::from_raw(wdf_request.cast()) })
+    }
+
+    /// Returns the header of the [`DmaTransactionState`] attached to the
+    /// transaction
+    fn header(&self) -> &DmaTransactionHeader {
+        // SAFETY: `wdf_dma_transaction` is a private member of `DmaTransaction`, which
+        // is only created by `DmaTransaction::try_new()`, which attaches a
+        // `DmaTransactionState` that starts with a `DmaTransactionHeader`. The state is
+        // destroyed with the transaction, which outlives `self`.
+        unsafe {
+            closure_in::<DmaTransactionStateSlot, DmaTransactionHeader>(
+                self.wdf_dma_transaction.cast(),
+            )
+        }
+    }
+}
+
+/// The part of the [`DmaTransactionState`] that does not depend on the type of
+/// the program DMA callback
+#[repr(C)]
+struct DmaTransactionHeader {
+    /// The `WDFREQUEST` that the transaction is executing, or null
+    request: AtomicPtr<c_void>,
+    /// The `EvtProgramDma` that calls the transaction's callback
+    evt_program_dma: PFN_WDF_PROGRAM_DMA,
+    /// The state of the [`DmaTransactionFuture`] awaiting the transaction
+    awaiter: AtomicU8,
+    /// The final status of the transaction, for its [`DmaTransactionFuture`]
+    status: AtomicI32,
+    /// The waker of the task awaiting the transaction
+    waker: AtomicWaker,
+}
+
+/// A future that resolves once a [`DmaTransaction`] completes, returned by
+/// [`DmaTransaction::execute()`].
+///
+/// The future's output is the transaction's request, along with the number
+/// of bytes that the transaction transferred, or the error that it failed
+/// with. The transaction is released before the future resolves, so it can
+/// be executed again right away.
+#[must_use = "dropping the future completes the request once the transaction completes"]
+pub struct DmaTransactionFuture<'a, 'p> {
+    dma_transaction: &'a DmaTransaction<'p>,
+    resolved: bool,
+}
+
+impl Future for DmaTransactionFuture<'_, '_> {
+    type Output = (Request, Result<usize, NTSTATUS>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(
+            !self.resolved,
+            "DmaTransactionFuture polled after it resolved"
+        );
+        let header = self.dma_transaction.header();
+        header.waker.register(cx.waker());
+        if header.awaiter.load(Ordering::Acquire) != FINISHED {
+            return Poll::Pending;
+        }
+
+        let nt_status = header.status.load(Ordering::Relaxed);
+        let (request, bytes_transferred) = self
+            .dma_transaction
+            .release()
+            .expect("a finished DMA transaction should keep its request until it is awaited");
+        header.awaiter.store(NOT_AWAITED, Ordering::Release);
+        self.resolved = true;
+        if nt_success(nt_status) {
+            Poll::Ready((request, Ok(bytes_transferred)))
+        } else {
+            Poll::Ready((request, Err(nt_status)))
+        }
+    }
+}
+
+impl Drop for DmaTransactionFuture<'_, '_> {
+    fn drop(&mut self) {
+        if self.resolved {
+            return;
+        }
+        let header = self.dma_transaction.header();
+        loop {
+            match header.awaiter.compare_exchange(
+                AWAITED,
+                ABANDONED,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                // The transaction completes the request once it finishes
+                Ok(_) => return,
+                // The transaction's status is being recorded, which does not wait on
+                // anything
+                Err(FINISHING) => core::hint::spin_loop(),
+                Err(_) => break,
+            }
+        }
+
+        // The transaction finished, but the future was not polled since
+        let nt_status = header.status.load(Ordering::Relaxed);
+        header.awaiter.store(NOT_AWAITED, Ordering::Release);
+        self.dma_transaction.release_and_complete(nt_status);
+    }
+}
+
+/// The state attached to transactions created by [`DmaTransaction::try_new()`].
+/// It starts with a [`DmaTransactionHeader`], so that it can be accessed
+/// without knowing `F`.
+#[repr(C)]
+struct DmaTransactionState<F> {
+    header: DmaTransactionHeader,
+    program_dma: F,
+}
+
+/// The [`ClosureSlot`] of the [`DmaTransactionState`] attached to transactions
+struct DmaTransactionStateSlot;
+
+impl ClosureSlot for DmaTransactionStateSlot {
+    fn type_info() -> &'static ContextTypeInfo {
+        &DMA_TRANSACTION_STATE_CONTEXT_TYPE_INFO
+    }
+}
+
+/// The `EvtProgramDma` of transactions created by [`DmaTransaction::try_new()`]
+///
+/// # Safety
+///
+/// `wdf_dma_transaction` must be a valid handle to a transaction that a
+/// `DmaTransactionState<F>` was attached to, `wdf_device` must be its device,
+/// and `sg_list` must point to a valid scatter/gather list.
+unsafe extern "C" fn evt_program_dma<'p, F>(
+    wdf_dma_transaction: WDFDMATRANSACTION,
+    wdf_device: WDFDEVICE,
+    _context: WDFCONTEXT,
+    direction: WDF_DMA_DIRECTION,
+    sg_list: PSCATTER_GATHER_LIST,
+) -> BOOLEAN
+where
+    F: Fn(&DmaTransaction<'p>, &Device, DmaDirection, &ScatterGatherList) -> Result<(), NTSTATUS>,
+{
+    // SAFETY: The framework only calls this with the transaction, which is valid
+    // for the duration of the call, and which had a `DmaTransactionState<F>`
+    // attached when it was created.
+    let state = unsafe {
+        closure_in::<DmaTransactionStateSlot, DmaTransactionState<F>>(wdf_dma_transaction.cast())
+    };
+    let dma_transaction = DmaTransaction {
+        wdf_dma_transaction,
+        object: ChildObject::parent_owned(wdf_dma_transaction.cast()),
+    };
+    // SAFETY: The framework passes the device of the transaction's DMA enabler,
+    // which outlives it.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes a valid scatter/gather list, which remains valid
+    // for the duration of the call.
+    let sg_list = unsafe { ScatterGatherList::from_raw(sg_list) };
+
+    match (state.program_dma)(
+        &dma_transaction,
+        &device,
+        DmaDirection::from_raw(direction),
+        sg_list,
+    ) {
+        Ok(()) => BOOLEAN::from(true),
+        Err(nt_status) => {
+            let _ = dma_transaction.raw_dma_completed_final(0);
+            dma_transaction.complete_request(nt_status);
+            BOOLEAN::from(false)
+        }
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::mem::ManuallyDrop;
+
+use wdk_sys::{
+    macros,
+    ntddk::{KeGetProcessorNumberFromIndex, KeSetImportanceDpc, KeSetTargetProcessorDpcEx},
+    _KDPC_IMPORTANCE,
+    KDPC_IMPORTANCE,
+    NTSTATUS,
+    PKDPC,
+    PROCESSOR_NUMBER,
+    ULONG,
+    WDFDPC,
+    WDFOBJECT,
+    WDF_DPC_CONFIG,
+};
+
+use super::{
+    child::ChildObject,
+    context::{attach_closure, closure, object_attributes},
+    Device,
+    WdfObject,
+};
+use crate::{irql::DispatchLevel, nt_success};
+
+// `WDF_DPC_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const DPC_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_DPC_CONFIG>() as ULONG;
+
+/// The importance of a [`Dpc`], which decides where it is placed in the DPC
+/// queue and how soon the queue is drained, set with
+/// [`Dpc::set_importance()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DpcImportance {
+    /// Placed at the end of the queue, which is not necessarily drained
+    /// immediately when the DPC is enqueued on another processor
+    Low,
+    /// Placed at the end of the queue, which is drained immediately when the
+    /// DPC is enqueued on the current processor
+    #[default]
+    Medium,
+    /// Placed at the end of the queue, which is drained immediately when the
+    /// DPC is enqueued on any processor
+    MediumHigh,
+    /// Placed at the front of the queue, which is drained immediately
+    High,
+}
+
+impl DpcImportance {
+    const fn as_raw(self) -> KDPC_IMPORTANCE {
+        match self {
+            Self::Low => _KDPC_IMPORTANCE::LowImportance,
+            Self::Medium => _KDPC_IMPORTANCE::MediumImportance,
+            Self::MediumHigh => _KDPC_IMPORTANCE::MediumHighImportance,
+            Self::High => _KDPC_IMPORTANCE::HighImportance,
+        }
+    }
+}
+
+/// WDF DPC.
+///
+/// A [`Dpc`] runs a closure at `IRQL` = `DISPATCH_LEVEL` every time it is
+/// enqueued with [`Dpc::enqueue()`], and passes it a [`DispatchLevel`] token,
+/// so that the closure cannot call wrappers that require a lower `IRQL`. DPCs
+/// are typically enqueued by an interrupt service routine, to defer the work
+/// that does not need to run at the device's `DIRQL`.
+///
+/// Dropping a [`Dpc`] cancels it, waits for its closure to finish running, and
+/// then deletes the DPC, so it must be dropped at `IRQL` = `PASSIVE_LEVEL`, and
+/// not from within its own closure. The lifetime `'p` is bounded by the DPC's
+/// device, so it cannot outlive it. Use [`Dpc::into_parent_owned()`] to leave
+/// the DPC to be deleted with its device instead.
+///
+/// By default, the closure runs on the processor that enqueued the DPC. Use
+/// [`Dpc::set_target_processor()`] to run it on a specific processor instead,
+/// such as one in the same NUMA node as the device.
+pub struct Dpc<'p> {
+    wdf_dpc: WDFDPC,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFDPC` handles can be enqueued, canceled and deleted from any
+// thread, and the closure is required to be `Send` and `Sync`.
+unsafe impl Send for Dpc<'_> {}
+
+// SAFETY: All methods of `Dpc` that take `&self` can be called concurrently
+// from multiple threads.
+unsafe impl Sync for Dpc<'_> {}
+
+// SAFETY: `wdf_dpc` is a private member of `Dpc`, originally created by WDF,
+// and the lifetime of a `Dpc` is bounded by its device.
+unsafe impl WdfObject for Dpc<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_dpc.cast()
+    }
+}
+
+impl<'p> Dpc<'p> {
+    /// Try to construct a WDF DPC object, parented to `device`, that runs
+    /// `callback` every time it is enqueued
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a DPC, or to allocate storage for `callback`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDpc Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdpc/nf-wdfdpc-wdfdpccreate#return-value)
+    pub fn try_new<F>(device: &'p Device, callback: F) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&DispatchLevel) + Send + Sync + 'static,
+    {
+        let mut dpc_config = WDF_DPC_CONFIG {
+            Size: DPC_CONFIG_SIZE,
+            EvtDpcFunc: Some(evt_dpc_func::<F>),
+            ..Default::default()
+        };
+        let mut attributes = object_attributes(device.as_object_handle());
+        let mut wdf_dpc: WDFDPC = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDpcCreate,
+                &mut dpc_config,
+                &mut attributes,
+                &mut wdf_dpc,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        let dpc = Self {
+            wdf_dpc,
+            // SAFETY: `wdf_dpc` is a valid handle to the DPC that was just created,
+            // which is only deleted by this `ChildObject`, and whose parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_dpc.cast()) },
+        };
+
+        // SAFETY: `wdf_dpc` is a valid handle to the DPC that was just created, and
+        // nothing else has been attached to it. The DPC has not been enqueued, so
+        // `evt_dpc_func` cannot run before the closure is attached. If this fails,
+        // dropping `dpc` deletes the DPC.
+        unsafe { attach_closure(dpc.wdf_dpc.cast(), callback) }?;
+        Ok(dpc)
+    }
+
+    /// Enqueue the DPC, so that its closure runs at `IRQL` = `DISPATCH_LEVEL`
+    ///
+    /// Returns `false` if the DPC was already enqueued, in which case its
+    /// closure still only runs once.
+    ///
+    /// This may be called at any `IRQL`.
+    // DPCs are commonly enqueued without caring whether they were already enqueued
+    #[allow(clippy::must_use_candidate)]
+    pub fn enqueue(&self) -> bool {
+        let result;
+        // SAFETY: `wdf_dpc` is a private member of `Dpc`, originally created by WDF,
+        // and this module guarantees that it is always in a valid state.
+        unsafe {
+            result = macros::call_unsafe_wdf_function_binding!(WdfDpcEnqueue, self.wdf_dpc);
+        }
+        result != 0
+    }
+
+    /// Remove the DPC from the DPC queue, if it is enqueued. If `wait` is
+    /// `true`, this also waits for the DPC's closure to finish running, if it
+    /// is running.
+    ///
+    /// Returns `true` if the DPC was enqueued, and was removed from the queue.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`, or at `IRQL` =
+    /// `PASSIVE_LEVEL` if `wait` is `true`. It must not be called from within
+    /// the DPC's own closure with `wait` set to `true`.
+    // DPCs are commonly canceled without caring whether they were enqueued
+    #[allow(clippy::must_use_candidate)]
+    pub fn cancel(&self, wait: bool) -> bool {
+        let result;
+        // SAFETY: `wdf_dpc` is a private member of `Dpc`, originally created by WDF,
+        // and this module guarantees that it is always in a valid state.
+        unsafe {
+            result = macros::call_unsafe_wdf_function_binding!(
+                WdfDpcCancel,
+                self.wdf_dpc,
+                u8::from(wait)
+            );
+        }
+        result != 0
+    }
+
+    /// Run the DPC's closure on the processor with the system-wide index
+    /// `processor_index`, instead of the processor that enqueued it
+    ///
+    /// Processor indices are the same as those of
+    /// [`PerCpu`](crate::sync::PerCpu). This should be called before the DPC
+    /// is first enqueued, and may be called at any `IRQL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `processor_index` is not the index of a processor in the system. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [KeSetTargetProcessorDpcEx Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-kesettargetprocessordpcex#return-value)
+    pub fn set_target_processor(&self, processor_index: u32) -> Result<(), NTSTATUS> {
+        let mut processor_number = PROCESSOR_NUMBER::default();
+
+        let nt_status;
+        // SAFETY: `processor_number` is valid for writes for the duration of the call.
+        unsafe {
+            nt_status = KeGetProcessorNumberFromIndex(processor_index, &mut processor_number);
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let nt_status;
+        // SAFETY: `kdpc` returns the DPC's valid `KDPC`, and `processor_number` is
+        // valid for the duration of the call.
+        unsafe {
+            nt_status = KeSetTargetProcessorDpcEx(self.kdpc(), &mut processor_number);
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Set the importance of the DPC, which decides how soon its closure runs
+    /// after it is enqueued
+    ///
+    /// This should be called before the DPC is first enqueued, and may be
+    /// called at any `IRQL`.
+    pub fn set_importance(&self, importance: DpcImportance) {
+        // SAFETY: `kdpc` returns the DPC's valid `KDPC`.
+        unsafe {
+            KeSetImportanceDpc(self.kdpc(), importance.as_raw());
+        }
+    }
+
+    /// Returns the WDM `KDPC` of the DPC
+    fn kdpc(&self) -> PKDPC {
+        let kdpc;
+        // SAFETY: `wdf_dpc` is a private member of `Dpc`, originally created by WDF,
+        // and this module guarantees that it is always in a valid state.
+        unsafe {
+            kdpc = macros::call_unsafe_wdf_function_binding!(WdfDpcWdmGetDpc, self.wdf_dpc);
+        }
+        kdpc
+    }
+
+    /// Leave the WDF DPC object to be deleted along with its device, rather
+    /// than when the [`Dpc`] is dropped, so that the [`Dpc`] can be stored in
+    /// the context of its device
+    ///
+    /// # Safety
+    ///
+    /// The returned [`Dpc`] must not be used after its device is deleted.
+    /// Storing it in the context of its device satisfies this, as long as it
+    /// is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> Dpc<'static> {
+        let dpc = ManuallyDrop::new(self);
+        Dpc {
+            wdf_dpc: dpc.wdf_dpc,
+            object: ChildObject::parent_owned(dpc.wdf_dpc.cast()),
+        }
+    }
+}
+
+impl Drop for Dpc<'_> {
+    fn drop(&mut self) {
+        // The DPC is deleted by `object` after this, once its closure is no longer
+        // running
+        if !self.object.is_parent_owned() {
+            self.cancel(true);
+        }
+    }
+}
+
+/// The `EvtDpcFunc` of DPCs created by [`Dpc::try_new()`]
+///
+/// # Safety
+///
+/// `wdf_dpc` must be a valid handle to a DPC that an `F` was attached to.
+unsafe extern "C" fn evt_dpc_func<F>(wdf_dpc: WDFDPC)
+where
+    F: Fn(&DispatchLevel),
+{
+    // SAFETY: The framework only calls this with the DPC, which is valid for the
+    // duration of the call, and which had an `F` attached when it was created.
+    let callback = unsafe { closure::<F>(wdf_dpc.cast()) };
+    let irql;
+    // SAFETY: The framework calls `EvtDpcFunc` at `IRQL` = `DISPATCH_LEVEL`.
+    unsafe {
+        irql = DispatchLevel::new_unchecked();
+    }
+    callback(&irql);
+}
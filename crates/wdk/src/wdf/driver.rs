@@ -0,0 +1,324 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    macros,
+    _WDF_DRIVER_INIT_FLAGS,
+    DRIVER_OBJECT,
+    NTSTATUS,
+    PWDFDEVICE_INIT,
+    STATUS_SUCCESS,
+    ULONG,
+    UNICODE_STRING,
+    WDFDRIVER,
+    WDFOBJECT,
+    WDF_DRIVER_CONFIG,
+    WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+use super::{
+    context::{attach_closure, closure},
+    DeviceInit,
+    WdfObject,
+};
+use crate::nt_success;
+
+// `WDF_DRIVER_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const DRIVER_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_DRIVER_CONFIG>() as ULONG;
+
+// `DriverInitFlags` is a `ULONG`, while the `WDF_DRIVER_INIT_FLAGS` enumeration
+// it holds values of is an `int`
+#[allow(clippy::cast_sign_loss)]
+const NON_PNP_DRIVER: ULONG = _WDF_DRIVER_INIT_FLAGS::WdfDriverInitNonPnpDriver as ULONG;
+
+/// The type of the `EvtDriverDeviceAdd` callback of a [`DriverConfig`] that
+/// does not register one
+pub type NoDeviceAdd = fn(&Driver, &mut DeviceInit) -> Result<(), NTSTATUS>;
+
+/// The type of the `EvtDriverUnload` callback of a [`DriverConfig`] that does
+/// not register one
+pub type NoUnload = fn(&Driver);
+
+/// Configuration of a WDF Driver object, used with [`Driver::try_new()`].
+///
+/// A [`DriverConfig`] is built by chaining methods onto
+/// [`DriverConfig::new()`], which mirrors `WDF_DRIVER_CONFIG_INIT`. Callbacks
+/// are Rust closures, so they can capture driver-wide state.
+///
+/// ```ignore
+/// let config = DriverConfig::new()
+///     .device_add(|driver, device_init| {
+///         // Create the device
+///         Ok(())
+///     })
+///     .unload(|driver| {
+///         // Release driver-wide resources
+///     });
+/// ```
+#[must_use]
+pub struct DriverConfig<A = NoDeviceAdd, U = NoUnload> {
+    device_add: Option<A>,
+    unload: Option<U>,
+    init_flags: ULONG,
+    pool_tag: ULONG,
+}
+
+impl DriverConfig {
+    /// Construct a [`DriverConfig`] without any callbacks
+    pub const fn new() -> Self {
+        Self {
+            device_add: None,
+            unload: None,
+            init_flags: 0,
+            pool_tag: 0,
+        }
+    }
+}
+
+impl Default for DriverConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A, U> DriverConfig<A, U> {
+    /// Set the `EvtDriverDeviceAdd` callback, which the framework calls at
+    /// `IRQL` = `PASSIVE_LEVEL` every time the Plug and Play manager reports a
+    /// device that the driver supports. The callback should use the
+    /// [`DeviceInit`] it is passed to create the device.
+    ///
+    /// Returning an error fails the device's start.
+    pub fn device_add<F>(self, callback: F) -> DriverConfig<F, U>
+    where
+        F: Fn(&Driver, &mut DeviceInit) -> Result<(), NTSTATUS> + Send + Sync + 'static,
+    {
+        DriverConfig {
+            device_add: Some(callback),
+            unload: self.unload,
+            init_flags: self.init_flags,
+            pool_tag: self.pool_tag,
+        }
+    }
+
+    /// Set the `EvtDriverUnload` callback, which the framework calls at `IRQL`
+    /// = `PASSIVE_LEVEL` before the driver is unloaded
+    pub fn unload<F>(self, callback: F) -> DriverConfig<A, F>
+    where
+        F: Fn(&Driver) + Send + Sync + 'static,
+    {
+        DriverConfig {
+            device_add: self.device_add,
+            unload: Some(callback),
+            init_flags: self.init_flags,
+            pool_tag: self.pool_tag,
+        }
+    }
+
+    /// Mark the driver as a non-Plug and Play driver, which does not support
+    /// Plug and Play or power management, and does not receive
+    /// `EvtDriverDeviceAdd` callbacks
+    pub const fn non_pnp(mut self) -> Self {
+        self.init_flags |= NON_PNP_DRIVER;
+        self
+    }
+
+    /// Set the pool tag that the framework uses for memory it allocates on
+    /// behalf of the driver. By default, the framework derives one from the
+    /// driver's name.
+    pub const fn pool_tag(mut self, pool_tag: ULONG) -> Self {
+        self.pool_tag = pool_tag;
+        self
+    }
+}
+
+/// The callbacks of a [`Driver`], stored in its context space
+struct DriverCallbacks<A, U> {
+    device_add: Option<A>,
+    unload: Option<U>,
+}
+
+/// WDF Driver.
+///
+/// A [`Driver`] represents the framework driver object of the driver, which
+/// is created from `DriverEntry` with [`Driver::try_new()`], and lives until
+/// the driver is unloaded.
+#[derive(Clone, Copy)]
+pub struct Driver {
+    wdf_driver: WDFDRIVER,
+}
+
+// SAFETY: The framework driver object lives until the driver is unloaded, and
+// `WDFDRIVER` handles can be used from any thread.
+unsafe impl Send for Driver {}
+
+// SAFETY: `Driver` has no methods that mutate the driver object.
+unsafe impl Sync for Driver {}
+
+// SAFETY: The framework driver object lives until the driver is unloaded.
+unsafe impl WdfObject for Driver {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_driver.cast()
+    }
+}
+
+impl Driver {
+    /// Try to construct the framework driver object of the driver, using
+    /// `config`
+    ///
+    /// This must be called from `DriverEntry` at `IRQL` = `PASSIVE_LEVEL`,
+    /// with the `driver_object` and `registry_path` it was passed. If this
+    /// fails, `DriverEntry` must return the error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the driver object, or to allocate storage for its callbacks. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDriver Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdriver/nf-wdfdriver-wdfdrivercreate#return-value)
+    pub fn try_new<A, U>(
+        driver_object: &mut DRIVER_OBJECT,
+        registry_path: &UNICODE_STRING,
+        config: DriverConfig<A, U>,
+    ) -> Result<Self, NTSTATUS>
+    where
+        A: Fn(&Self, &mut DeviceInit) -> Result<(), NTSTATUS> + Send + Sync + 'static,
+        U: Fn(&Self) + Send + Sync + 'static,
+    {
+        let mut driver_config = WDF_DRIVER_CONFIG {
+            Size: DRIVER_CONFIG_SIZE,
+            EvtDriverDeviceAdd: config
+                .device_add
+                .is_some()
+                .then_some(evt_driver_device_add::<A, U> as _),
+            EvtDriverUnload: config
+                .unload
+                .is_some()
+                .then_some(evt_driver_unload::<A, U> as _),
+            DriverInitFlags: config.init_flags,
+            DriverPoolTag: config.pool_tag,
+        };
+        let mut driver = Self {
+            wdf_driver: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `driver_object` and `registry_path` are references, so they are
+        // valid for the duration of the call. `WDF_NO_OBJECT_ATTRIBUTES` is allowed,
+        // and `driver_config` is a valid `WDF_DRIVER_CONFIG`.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDriverCreate,
+                driver_object,
+                registry_path,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut driver_config,
+                &mut driver.wdf_driver,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: `wdf_driver` is a valid handle to the driver object that was just
+        // created, and nothing else has been attached to it. The framework does not
+        // call any driver callbacks until `DriverEntry` returns.
+        unsafe {
+            attach_closure(
+                driver.wdf_driver.cast(),
+                DriverCallbacks {
+                    device_add: config.device_add,
+                    unload: config.unload,
+                },
+            )
+        }?;
+        Ok(driver)
+    }
+
+    /// Try to construct the framework driver object of the driver, using
+    /// `config`
+    ///
+    /// This must be called from `DriverEntry` at `IRQL` = `PASSIVE_LEVEL`,
+    /// with the `driver_object` and `registry_path` it was passed. If this
+    /// fails, `DriverEntry` must return the error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the driver object, or to allocate storage for its callbacks. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDriver Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdriver/nf-wdfdriver-wdfdrivercreate#return-value)
+    pub fn create<A, U>(
+        driver_object: &mut DRIVER_OBJECT,
+        registry_path: &UNICODE_STRING,
+        config: DriverConfig<A, U>,
+    ) -> Result<Self, NTSTATUS>
+    where
+        A: Fn(&Self, &mut DeviceInit) -> Result<(), NTSTATUS> + Send + Sync + 'static,
+        U: Fn(&Self) + Send + Sync + 'static,
+    {
+        Self::try_new(driver_object, registry_path, config)
+    }
+
+    /// Wrap an existing WDF Driver object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_driver` must be a valid handle to the framework driver object.
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_driver: WDFDRIVER) -> Self {
+        Self { wdf_driver }
+    }
+
+    /// Returns the raw `WDFDRIVER` handle wrapped by this [`Driver`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFDRIVER {
+        self.wdf_driver
+    }
+}
+
+/// The `EvtDriverDeviceAdd` of drivers created by [`Driver::try_new()`]
+///
+/// # Safety
+///
+/// `wdf_driver` must be a valid handle to a driver object that a
+/// `DriverCallbacks<A, U>` was attached to, and `device_init` must be a valid
+/// `WDFDEVICE_INIT` for the duration of the call.
+unsafe extern "C" fn evt_driver_device_add<A, U>(
+    wdf_driver: WDFDRIVER,
+    device_init: PWDFDEVICE_INIT,
+) -> NTSTATUS
+where
+    A: Fn(&Driver, &mut DeviceInit) -> Result<(), NTSTATUS>,
+{
+    // SAFETY: The framework only calls this with the driver object, which had a
+    // `DriverCallbacks<A, U>` attached when it was created.
+    let callbacks = unsafe { closure::<DriverCallbacks<A, U>>(wdf_driver.cast()) };
+    // SAFETY: The framework passes a valid `WDFDEVICE_INIT`, which is valid for the
+    // duration of the call.
+    let mut device_init = unsafe { DeviceInit::from_raw(device_init) };
+
+    let device_add = callbacks
+        .device_add
+        .as_ref()
+        .expect("EvtDriverDeviceAdd is only registered when a callback is set");
+    match device_add(&Driver { wdf_driver }, &mut device_init) {
+        Ok(()) => STATUS_SUCCESS,
+        Err(nt_status) => nt_status,
+    }
+}
+
+/// The `EvtDriverUnload` of drivers created by [`Driver::try_new()`]
+///
+/// # Safety
+///
+/// `wdf_driver` must be a valid handle to a driver object that a
+/// `DriverCallbacks<A, U>` was attached to.
+unsafe extern "C" fn evt_driver_unload<A, U>(wdf_driver: WDFDRIVER)
+where
+    U: Fn(&Driver),
+{
+    // SAFETY: The framework only calls this with the driver object, which had a
+    // `DriverCallbacks<A, U>` attached when it was created.
+    let callbacks = unsafe { closure::<DriverCallbacks<A, U>>(wdf_driver.cast()) };
+
+    let unload = callbacks
+        .unload
+        .as_ref()
+        .expect("EvtDriverUnload is only registered when a callback is set");
+    unload(&Driver { wdf_driver });
+}
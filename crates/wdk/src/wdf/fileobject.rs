@@ -0,0 +1,251 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    macros,
+    _WDF_FILEOBJECT_CLASS,
+    _WDF_TRI_STATE,
+    NTSTATUS,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFDEVICE,
+    WDFFILEOBJECT,
+    WDFOBJECT,
+    WDFREQUEST,
+    WDF_FILEOBJECT_CONFIG,
+};
+
+use super::{
+    context::{attach_closure_in, closure_in, ClosureSlot},
+    ContextTypeInfo,
+    Device,
+    Request,
+    WdfObject,
+};
+
+// `WDF_FILEOBJECT_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const FILEOBJECT_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_FILEOBJECT_CONFIG>() as ULONG;
+
+/// The context type of the [`FileObjectCallbacks`] attached to devices
+static FILE_OBJECT_CALLBACKS_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::FileObjectCallbacks",
+    1,
+    &FILE_OBJECT_CALLBACKS_CONTEXT_TYPE_INFO,
+);
+
+/// WDF File Object.
+///
+/// A [`FileObject`] represents a handle that an application or another driver
+/// opened to a device. The framework deletes it once the handle is closed and
+/// all of its requests are completed.
+///
+/// Per-handle state is stored in a context of the file object, typically
+/// attached with [`ObjectContext::attach()`](super::ObjectContext::attach)
+/// from [`FileObjectCallbacks::file_create()`], and retrieved with
+/// [`get_context()`](super::get_context) when the handle's requests are
+/// handled. The context is dropped once the file object is deleted.
+#[derive(Clone, Copy)]
+pub struct FileObject {
+    wdf_file_object: WDFFILEOBJECT,
+}
+
+// SAFETY: `WDFFILEOBJECT` handles can be used from any thread.
+unsafe impl Send for FileObject {}
+
+// SAFETY: `FileObject` has no methods that mutate the file object.
+unsafe impl Sync for FileObject {}
+
+// SAFETY: The framework file object lives until its handle is closed and all
+// of its requests are completed, which outlasts the requests and callbacks
+// that `FileObject`s are retrieved from.
+unsafe impl WdfObject for FileObject {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_file_object.cast()
+    }
+}
+
+impl FileObject {
+    /// Wrap an existing WDF File Object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_file_object` must be a valid handle to a WDF File Object, and must
+    /// remain valid for the lifetime of the returned [`FileObject`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_file_object: WDFFILEOBJECT) -> Self {
+        Self { wdf_file_object }
+    }
+
+    /// Returns the raw `WDFFILEOBJECT` handle wrapped by this [`FileObject`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFFILEOBJECT {
+        self.wdf_file_object
+    }
+
+    /// Returns the device that the file object was opened on
+    #[must_use]
+    pub fn device(&self) -> Device {
+        let wdf_device;
+        // SAFETY: `wdf_file_object` is a private member of `FileObject`, and the
+        // framework keeps the file object valid while it is used.
+        unsafe {
+            wdf_device = macros::call_unsafe_wdf_function_binding!(
+                WdfFileObjectGetDevice,
+                self.wdf_file_object
+            );
+        }
+        // SAFETY: The device of a file object lives at least as long as the file
+        // object.
+        unsafe { Device::from_raw(wdf_device) }
+    }
+}
+
+/// Callbacks for the file objects of a [`Device`], set with
+/// [`DeviceBuilder::file_object_callbacks()`](super::DeviceBuilder::file_object_callbacks).
+///
+/// Every method has a default implementation, so implementations only need to
+/// override the callbacks they handle. All callbacks are called at `IRQL` =
+/// `PASSIVE_LEVEL`.
+pub trait FileObjectCallbacks: Send + Sync + 'static {
+    /// Called when a handle to the device is opened, with the create request
+    /// and the new file object. The handle is only opened once `request` is
+    /// completed with a success status. Completing it with an error fails the
+    /// open.
+    ///
+    /// The default implementation completes the request with
+    /// `STATUS_SUCCESS`.
+    fn file_create(&self, device: &Device, request: Request, file_object: &FileObject) {
+        let _ = (device, file_object);
+        request.complete(STATUS_SUCCESS, 0);
+    }
+
+    /// Called when the last handle to the file object is closed. Requests of
+    /// the file object may still be pending, and should be canceled.
+    fn file_cleanup(&self, file_object: &FileObject) {
+        let _ = file_object;
+    }
+
+    /// Called once every request of the file object is completed, right
+    /// before the file object is deleted
+    fn file_close(&self, file_object: &FileObject) {
+        let _ = file_object;
+    }
+}
+
+/// [`FileObjectCallbacks`] that accept every open, used when a [`Device`] is
+/// created without file object callbacks
+pub struct NoFileObjectCallbacks;
+
+impl FileObjectCallbacks for NoFileObjectCallbacks {}
+
+/// The [`ClosureSlot`] of the [`FileObjectCallbacks`] attached to devices
+struct FileObjectCallbacksSlot;
+
+impl ClosureSlot for FileObjectCallbacksSlot {
+    fn type_info() -> &'static ContextTypeInfo {
+        &FILE_OBJECT_CALLBACKS_CONTEXT_TYPE_INFO
+    }
+}
+
+/// Returns the `WDF_FILEOBJECT_CONFIG` of devices with `F` as their file
+/// object callbacks, initialized the same way as by
+/// `WDF_FILEOBJECT_CONFIG_INIT`
+pub(super) fn file_object_config<F: FileObjectCallbacks>() -> WDF_FILEOBJECT_CONFIG {
+    WDF_FILEOBJECT_CONFIG {
+        Size: FILEOBJECT_CONFIG_SIZE,
+        EvtDeviceFileCreate: Some(evt_device_file_create::<F>),
+        EvtFileClose: Some(evt_file_close::<F>),
+        EvtFileCleanup: Some(evt_file_cleanup::<F>),
+        AutoForwardCleanupClose: _WDF_TRI_STATE::WdfUseDefault,
+        FileObjectClass: _WDF_FILEOBJECT_CLASS::WdfFileObjectWdfCannotUseFsContexts,
+    }
+}
+
+/// Attach `callbacks` to `wdf_device`, for the callbacks of
+/// [`file_object_config::<F>()`](file_object_config) to call
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that does not already have
+/// file object callbacks attached.
+///
+/// # Errors
+///
+/// This function will return an error if WDF fails to allocate storage for
+/// `callbacks`. The error variant will contain a [`NTSTATUS`] of the failure.
+pub(super) unsafe fn attach_file_object_callbacks<F: FileObjectCallbacks>(
+    wdf_device: WDFDEVICE,
+    callbacks: F,
+) -> Result<(), NTSTATUS> {
+    // SAFETY: The caller guarantees that `wdf_device` is a valid handle without
+    // file object callbacks attached.
+    unsafe { attach_closure_in::<FileObjectCallbacksSlot, F>(wdf_device.cast(), callbacks) }
+}
+
+/// Returns the [`FileObjectCallbacks`] attached to `wdf_device`
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that an `F` was attached to
+/// by [`attach_file_object_callbacks`].
+unsafe fn file_object_callbacks<'a, F>(wdf_device: WDFDEVICE) -> &'a F {
+    // SAFETY: The caller guarantees that an `F` was attached to `wdf_device`.
+    unsafe { closure_in::<FileObjectCallbacksSlot, F>(wdf_device.cast()) }
+}
+
+/// The `EvtDeviceFileCreate` of devices created with [`FileObjectCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that an `F` was attached
+/// to, and `wdf_request` and `wdf_file_object` must be its create request and
+/// new file object.
+unsafe extern "C" fn evt_device_file_create<F: FileObjectCallbacks>(
+    wdf_device: WDFDEVICE,
+    wdf_request: WDFREQUEST,
+    wdf_file_object: WDFFILEOBJECT,
+) {
+    // SAFETY: The caller guarantees that an `F` was attached to `wdf_device`, which
+    // outlives its file objects.
+    let callbacks = unsafe { file_object_callbacks::<F>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    // SAFETY: The framework passes ownership of the create request to the driver.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    // SAFETY: The new file object lives until its handle is closed.
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    callbacks.file_create(&device, request, &file_object);
+}
+
+/// The `EvtFileCleanup` of devices created with [`FileObjectCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_file_object` must be a valid handle to a file object of a device that
+/// an `F` was attached to.
+unsafe extern "C" fn evt_file_cleanup<F: FileObjectCallbacks>(wdf_file_object: WDFFILEOBJECT) {
+    // SAFETY: The framework only calls this with a valid file object, which remains
+    // valid for the duration of the call.
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    // SAFETY: The caller guarantees that an `F` was attached to the device of the
+    // file object.
+    let callbacks = unsafe { file_object_callbacks::<F>(file_object.device().as_raw()) };
+    callbacks.file_cleanup(&file_object);
+}
+
+/// The `EvtFileClose` of devices created with [`FileObjectCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_file_object` must be a valid handle to a file object of a device that
+/// an `F` was attached to.
+unsafe extern "C" fn evt_file_close<F: FileObjectCallbacks>(wdf_file_object: WDFFILEOBJECT) {
+    // SAFETY: The framework only calls this with a valid file object, which remains
+    // valid for the duration of the call.
+    let file_object = unsafe { FileObject::from_raw(wdf_file_object) };
+    // SAFETY: The caller guarantees that an `F` was attached to the device of the
+    // file object.
+    let callbacks = unsafe { file_object_callbacks::<F>(file_object.device().as_raw()) };
+    callbacks.file_close(&file_object);
+}
@@ -0,0 +1,201 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::c_void, ptr::NonNull};
+
+use wdk_sys::{
+    macros,
+    ntddk::{IoRegisterPlugPlayNotification, IoUnregisterPlugPlayNotificationEx},
+    _IO_NOTIFICATION_EVENT_CATEGORY,
+    DEVICE_INTERFACE_CHANGE_NOTIFICATION,
+    GUID,
+    NTSTATUS,
+    PNPNOTIFY_DEVICE_INTERFACE_INCLUDE_EXISTING_INTERFACES,
+    PVOID,
+    STATUS_SUCCESS,
+    UNICODE_STRING,
+};
+
+use super::Driver;
+use crate::{guid::Guid, nt_success, pool::NonPagedBox};
+
+/// `GUID_DEVICE_INTERFACE_ARRIVAL`, the `Event` of notifications of a device
+/// interface being enabled
+const GUID_DEVICE_INTERFACE_ARRIVAL: Guid = Guid::parse("cb3a4004-46f0-11d0-b08f-00609713053f");
+
+/// `GUID_DEVICE_INTERFACE_REMOVAL`, the `Event` of notifications of a device
+/// interface being disabled
+const GUID_DEVICE_INTERFACE_REMOVAL: Guid = Guid::parse("cb3a4005-46f0-11d0-b08f-00609713053f");
+
+/// A change to a device interface, passed to the callback of an
+/// [`InterfaceNotification`]
+#[derive(Clone, Copy)]
+pub enum InterfaceChange<'a> {
+    /// A device interface of the class was enabled, and can be opened by its
+    /// symbolic link name with
+    /// [`RemoteIoTarget::open_by_name()`](super::RemoteIoTarget::open_by_name)
+    Arrival {
+        /// The symbolic link name of the device interface
+        symbolic_link_name: &'a UNICODE_STRING,
+    },
+    /// A device interface of the class was disabled
+    Removal {
+        /// The symbolic link name of the device interface
+        symbolic_link_name: &'a UNICODE_STRING,
+    },
+}
+
+/// Registration for notifications of device interfaces of a class arriving and
+/// being removed.
+///
+/// The callback of the registration is called at `IRQL` = `PASSIVE_LEVEL`
+/// every time a device interface of the class is enabled or disabled. The
+/// registration is removed when the [`InterfaceNotification`] is dropped, which
+/// waits for callbacks that are running to return, so it must be dropped before
+/// the driver is unloaded.
+///
+/// Opening a device from the callback can deadlock, since the Plug and Play
+/// manager may be holding locks that opening it needs. Drivers should open the
+/// device from a [`WorkItem`](super::WorkItem) queued by the callback instead.
+pub struct InterfaceNotification {
+    notification_entry: PVOID,
+    callback: NonNull<c_void>,
+    drop_callback: unsafe fn(NonNull<c_void>),
+}
+
+// SAFETY: The notification entry can be unregistered from any thread, and the
+// callback is `Send + Sync`, since the Plug and Play manager calls it from any
+// thread.
+unsafe impl Send for InterfaceNotification {}
+
+// SAFETY: `InterfaceNotification` has no methods that access the notification
+// entry or the callback through `&self`.
+unsafe impl Sync for InterfaceNotification {}
+
+impl InterfaceNotification {
+    /// Register `callback` to be called when device interfaces of
+    /// `interface_class` arrive or are removed
+    ///
+    /// If `include_existing` is `true`, `callback` is also called with
+    /// [`InterfaceChange::Arrival`] for device interfaces of the class that are
+    /// already enabled. This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the callback cannot be allocated, or if the registration fails. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [IoRegisterPlugPlayNotification Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-ioregisterplugplaynotification#return-value)
+    pub fn register<F>(
+        driver: &Driver,
+        interface_class: &GUID,
+        include_existing: bool,
+        callback: F,
+    ) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(InterfaceChange<'_>) + Send + Sync + 'static,
+    {
+        let callback = NonPagedBox::try_new(callback)?;
+
+        let driver_object;
+        // SAFETY: `driver` is a valid handle to the driver object.
+        unsafe {
+            driver_object = macros::call_unsafe_wdf_function_binding!(
+                WdfDriverWdmGetDriverObject,
+                driver.as_raw()
+            );
+        }
+
+        let flags = if include_existing {
+            PNPNOTIFY_DEVICE_INTERFACE_INCLUDE_EXISTING_INTERFACES
+        } else {
+            0
+        };
+        let mut notification_entry: PVOID = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `interface_class` is only read from, and is copied by the call.
+        // `callback` stays allocated until the notification is unregistered, and
+        // `notification_entry` is valid for the duration of the call.
+        unsafe {
+            nt_status = IoRegisterPlugPlayNotification(
+                _IO_NOTIFICATION_EVENT_CATEGORY::EventCategoryDeviceInterfaceChange,
+                flags,
+                core::ptr::from_ref(interface_class).cast_mut().cast(),
+                driver_object,
+                Some(interface_change_callback::<F>),
+                callback.as_ptr().cast(),
+                &mut notification_entry,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            notification_entry,
+            callback: callback.into_raw().cast(),
+            drop_callback: drop_callback::<F>,
+        })
+    }
+}
+
+impl Drop for InterfaceNotification {
+    fn drop(&mut self) {
+        // SAFETY: `notification_entry` was returned by `IoRegisterPlugPlayNotification`
+        // and has not been unregistered. The `Ex` variant waits for running callbacks
+        // to return, so the callback is no longer used after this.
+        unsafe {
+            let _ = IoUnregisterPlugPlayNotificationEx(self.notification_entry);
+        }
+        // SAFETY: `callback` was released from a `NonPagedBox` of the type that
+        // `drop_callback` was instantiated with, and is not used after this.
+        unsafe {
+            (self.drop_callback)(self.callback);
+        }
+    }
+}
+
+/// Drop the callback of an [`InterfaceNotification`]
+///
+/// # Safety
+///
+/// `callback` must have been released from a `NonPagedBox<F>`, and must not be
+/// used after this.
+unsafe fn drop_callback<F>(callback: NonNull<c_void>) {
+    // SAFETY: The caller guarantees that `callback` was released from a
+    // `NonPagedBox<F>`, and is only reconstructed once.
+    drop(unsafe { NonPagedBox::from_raw(callback.cast::<F>()) });
+}
+
+/// The `DRIVER_NOTIFICATION_CALLBACK_ROUTINE` of [`InterfaceNotification`]s
+///
+/// # Safety
+///
+/// `notification_structure` must point to a valid
+/// `DEVICE_INTERFACE_CHANGE_NOTIFICATION`, and `context` must point to the `F`
+/// of the registration.
+unsafe extern "C" fn interface_change_callback<F>(
+    notification_structure: PVOID,
+    context: PVOID,
+) -> NTSTATUS
+where
+    F: Fn(InterfaceChange<'_>),
+{
+    // SAFETY: The Plug and Play manager always passes a
+    // `DEVICE_INTERFACE_CHANGE_NOTIFICATION` for
+    // `EventCategoryDeviceInterfaceChange` registrations, which is valid for
+    // the duration of the call.
+    let notification =
+        unsafe { &*notification_structure.cast::<DEVICE_INTERFACE_CHANGE_NOTIFICATION>() };
+    // SAFETY: The symbolic link name of the notification is valid for the duration
+    // of the call.
+    let symbolic_link_name = unsafe { &*notification.SymbolicLinkName };
+    // SAFETY: `context` is the `F` passed to `InterfaceNotification::register()`,
+    // which stays allocated until the notification is unregistered.
+    let callback = unsafe { &*context.cast::<F>() };
+
+    if GUID_DEVICE_INTERFACE_ARRIVAL == notification.Event {
+        callback(InterfaceChange::Arrival { symbolic_link_name });
+    } else if GUID_DEVICE_INTERFACE_REMOVAL == notification.Event {
+        callback(InterfaceChange::Removal { symbolic_link_name });
+    }
+    STATUS_SUCCESS
+}
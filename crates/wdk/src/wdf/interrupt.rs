@@ -0,0 +1,809 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+use wdk_sys::{
+    macros,
+    _WDF_INTERRUPT_POLICY,
+    _WDF_INTERRUPT_PRIORITY,
+    _WDF_TRI_STATE,
+    CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    CM_RESOURCE_INTERRUPT_MESSAGE,
+    GROUP_AFFINITY,
+    KAFFINITY,
+    NTSTATUS,
+    STATUS_INVALID_PARAMETER,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFCONTEXT,
+    WDFDEVICE,
+    WDFINTERRUPT,
+    WDFOBJECT,
+    WDF_INTERRUPT_CONFIG,
+    WDF_INTERRUPT_EXTENDED_POLICY,
+    WDF_INTERRUPT_POLICY,
+    WDF_INTERRUPT_PRIORITY,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_TRI_STATE,
+};
+
+use super::{
+    context::{attach_closure, closure},
+    Device,
+    ResourceList,
+    WdfObject,
+};
+use crate::nt_success;
+
+// `WDF_INTERRUPT_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const INTERRUPT_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_INTERRUPT_CONFIG>() as ULONG;
+
+// `WDF_INTERRUPT_EXTENDED_POLICY` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const INTERRUPT_EXTENDED_POLICY_SIZE: ULONG =
+    core::mem::size_of::<WDF_INTERRUPT_EXTENDED_POLICY>() as ULONG;
+
+/// WDF Interrupt.
+///
+/// A framework interrupt object represents a hardware interrupt that a device
+/// can generate. Each interrupt object has an associated interrupt spin lock
+/// that the framework acquires before it calls the driver's `EvtInterruptIsr`
+/// callback. Code outside of the interrupt service routine that accesses data
+/// shared with the ISR, such as device registers or the interrupt object's
+/// context space, must hold the interrupt lock while doing so. Use
+/// [`Interrupt::acquire_lock()`] to acquire it.
+///
+/// Interrupts are created with an [`InterruptBuilder`], and are deleted by the
+/// framework along with their device.
+pub struct Interrupt {
+    wdf_interrupt: WDFINTERRUPT,
+}
+
+// SAFETY: `WDFINTERRUPT` handles can be used from any thread, and all access to
+// state shared with the ISR is serialized by the interrupt lock.
+unsafe impl Send for Interrupt {}
+
+// SAFETY: All methods of `Interrupt` that take `&self` are safe to call
+// concurrently from multiple threads, since WDF serializes them with the
+// interrupt lock.
+unsafe impl Sync for Interrupt {}
+
+// SAFETY: The contract of `Interrupt::from_raw` guarantees that `wdf_interrupt`
+// is valid for the lifetime of the `Interrupt`.
+unsafe impl WdfObject for Interrupt {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_interrupt.cast()
+    }
+}
+
+impl Interrupt {
+    /// Wrap an existing WDF Interrupt object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_interrupt` must be a valid handle to a WDF Interrupt object, and
+    /// must remain valid for the lifetime of the returned [`Interrupt`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_interrupt: WDFINTERRUPT) -> Self {
+        Self { wdf_interrupt }
+    }
+
+    /// Returns the raw `WDFINTERRUPT` handle wrapped by this [`Interrupt`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFINTERRUPT {
+        self.wdf_interrupt
+    }
+
+    /// Returns the device that the interrupt belongs to
+    #[must_use]
+    pub fn device(&self) -> Device {
+        let wdf_device;
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            wdf_device = macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptGetDevice,
+                self.wdf_interrupt
+            );
+        }
+        // SAFETY: Interrupts are deleted along with their device, so it is valid for
+        // at least as long as the interrupt.
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Set the processors that the system delivers the interrupt to, and the
+    /// interrupt's priority
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`, from the callback
+    /// that created the interrupt, and takes effect the next time the
+    /// device's resources are assigned. The system only honors it for
+    /// message-signaled interrupts, and on versions of Windows that support
+    /// interrupt affinity and priority policies.
+    pub fn set_policy(&self, policy: InterruptPolicy, priority: InterruptPriority) {
+        let (raw_policy, target_processor_set_and_group) = policy.as_raw();
+        let mut extended_policy = WDF_INTERRUPT_EXTENDED_POLICY {
+            Size: INTERRUPT_EXTENDED_POLICY_SIZE,
+            Policy: raw_policy,
+            Priority: priority.as_raw(),
+            TargetProcessorSetAndGroup: target_processor_set_and_group,
+        };
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle.
+        // `extended_policy` is valid for the duration of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptSetExtendedPolicy,
+                self.wdf_interrupt,
+                &mut extended_policy
+            );
+        }
+    }
+
+    /// Acquire the interrupt's spin lock
+    ///
+    /// For interrupts handled at `DIRQL`, acquiring the lock raises the
+    /// calling thread's `IRQL` to the device's `DIRQL`, and the lock must be
+    /// acquired from `IRQL` <= `DISPATCH_LEVEL`. For passive-level interrupts,
+    /// the lock is a wait lock that must be acquired at `IRQL` =
+    /// `PASSIVE_LEVEL`. The returned [`InterruptLockGuard`] releases the lock,
+    /// and restores the previous `IRQL`, when it is dropped.
+    ///
+    /// This must not be called from the interrupt's `EvtInterruptIsr` or
+    /// `EvtInterruptSynchronize` callbacks, since the framework already holds
+    /// the lock while calling them.
+    #[must_use = "if unused the interrupt lock will immediately be released"]
+    pub fn acquire_lock(&self) -> InterruptLockGuard<'_> {
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfInterruptAcquireLock, self.wdf_interrupt);
+        }
+        InterruptLockGuard {
+            interrupt: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Attempt to acquire the interrupt's spin lock without waiting
+    ///
+    /// Returns [`None`] if the lock is currently held. This is only supported
+    /// for passive-level interrupts, and must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`.
+    #[must_use = "if unused the interrupt lock will immediately be released"]
+    pub fn try_acquire_lock(&self) -> Option<InterruptLockGuard<'_>> {
+        let acquired;
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle.
+        unsafe {
+            acquired = macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptTryToAcquireLock,
+                self.wdf_interrupt
+            );
+        }
+        (acquired != 0).then_some(InterruptLockGuard {
+            interrupt: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Acquire the interrupt's spin lock, run `f`, and release the lock
+    ///
+    /// The lock is released when `f` returns, regardless of which path `f`
+    /// returns through. For interrupts handled at `DIRQL`, `f` runs at the
+    /// device's `DIRQL` and must be kept as short as possible.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.acquire_lock();
+        f()
+    }
+
+    /// Run `f` at the device's `DIRQL`, synchronized with the interrupt's
+    /// ISR, and return its result (`WdfInterruptSynchronize`)
+    ///
+    /// The framework acquires the interrupt lock before calling `f`, and
+    /// releases it after `f` returns, so `f` can safely access state that is
+    /// shared with the ISR, such as device registers. `f` runs at `DIRQL`,
+    /// so it must be kept as short as possible and must not block or access
+    /// paged memory.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`, or at `IRQL` =
+    /// `PASSIVE_LEVEL` for passive-level interrupts. Like
+    /// [`Interrupt::acquire_lock()`], it must not be called from the
+    /// interrupt's ISR, or from within another call to
+    /// [`Interrupt::synchronize()`] on the same interrupt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` panics, or if the framework returns without calling `f`,
+    /// which it never does.
+    pub fn synchronize<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut context = SynchronizeContext::<F, R> {
+            callback: Some(f),
+            result: None,
+        };
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle. `context`
+        // outlives the call, which only runs `evt_interrupt_synchronize` before it
+        // returns. Its result is the return value of `evt_interrupt_synchronize`.
+        unsafe {
+            let _ = macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptSynchronize,
+                self.wdf_interrupt,
+                Some(evt_interrupt_synchronize::<F, R>),
+                core::ptr::from_mut(&mut context).cast(),
+            );
+        }
+        context
+            .result
+            .expect("WdfInterruptSynchronize should call its callback before returning")
+    }
+}
+
+/// RAII guard for a held [`Interrupt`] spin lock.
+///
+/// An [`InterruptLockGuard`] is returned by [`Interrupt::acquire_lock()`], and
+/// releases the interrupt lock when it goes out of scope. The guard cannot be
+/// sent to another thread, since the lock must be released by the same thread
+/// that acquired it.
+pub struct InterruptLockGuard<'a> {
+    interrupt: &'a Interrupt,
+    // `WdfInterruptReleaseLock` must be called from the thread that acquired the lock
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl Drop for InterruptLockGuard<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `wdf_interrupt` is a private member of `Interrupt`, and the contract
+        // of `Interrupt::from_raw` guarantees that it is a valid handle. The existence
+        // of this guard guarantees that the lock is currently held by this thread.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptReleaseLock,
+                self.interrupt.wdf_interrupt
+            );
+        }
+    }
+}
+
+/// The processors that the system delivers an [`Interrupt`] to, set with
+/// [`Interrupt::set_policy()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InterruptPolicy {
+    /// The system's default policy
+    #[default]
+    MachineDefault,
+    /// The processors that are closest to the device
+    AllCloseProcessors,
+    /// One of the processors that are closest to the device
+    OneCloseProcessor,
+    /// Every processor in the machine
+    AllProcessorsInMachine,
+    /// The processors in `mask`, from the processor group `group`
+    SpecifiedProcessors {
+        /// The processor group of the processors
+        group: u16,
+        /// The processors, as a bit for each processor in the group
+        mask: KAFFINITY,
+    },
+    /// Each message of a device with multiple message-signaled interrupts is
+    /// delivered to a different processor
+    SpreadMessagesAcrossAllProcessors,
+}
+
+impl InterruptPolicy {
+    const fn as_raw(self) -> (WDF_INTERRUPT_POLICY, GROUP_AFFINITY) {
+        let policy = match self {
+            Self::MachineDefault => _WDF_INTERRUPT_POLICY::WdfIrqPolicyMachineDefault,
+            Self::AllCloseProcessors => _WDF_INTERRUPT_POLICY::WdfIrqPolicyAllCloseProcessors,
+            Self::OneCloseProcessor => _WDF_INTERRUPT_POLICY::WdfIrqPolicyOneCloseProcessor,
+            Self::AllProcessorsInMachine => {
+                _WDF_INTERRUPT_POLICY::WdfIrqPolicyAllProcessorsInMachine
+            }
+            Self::SpecifiedProcessors { group, mask } => {
+                return (
+                    _WDF_INTERRUPT_POLICY::WdfIrqPolicySpecifiedProcessors,
+                    GROUP_AFFINITY {
+                        Mask: mask,
+                        Group: group,
+                        Reserved: [0; 3],
+                    },
+                );
+            }
+            Self::SpreadMessagesAcrossAllProcessors => {
+                _WDF_INTERRUPT_POLICY::WdfIrqPolicySpreadMessagesAcrossAllProcessors
+            }
+        };
+        (
+            policy,
+            GROUP_AFFINITY {
+                Mask: 0,
+                Group: 0,
+                Reserved: [0; 3],
+            },
+        )
+    }
+}
+
+/// The priority of an [`Interrupt`], set with [`Interrupt::set_policy()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InterruptPriority {
+    /// The system's default priority
+    #[default]
+    Undefined,
+    /// Low priority
+    Low,
+    /// Normal priority
+    Normal,
+    /// High priority
+    High,
+}
+
+impl InterruptPriority {
+    const fn as_raw(self) -> WDF_INTERRUPT_PRIORITY {
+        match self {
+            Self::Undefined => _WDF_INTERRUPT_PRIORITY::WdfIrqPriorityUndefined,
+            Self::Low => _WDF_INTERRUPT_PRIORITY::WdfIrqPriorityLow,
+            Self::Normal => _WDF_INTERRUPT_PRIORITY::WdfIrqPriorityNormal,
+            Self::High => _WDF_INTERRUPT_PRIORITY::WdfIrqPriorityHigh,
+        }
+    }
+}
+
+/// The raw and translated resource descriptors of one of a device's
+/// interrupts, returned by [`InterruptResources`].
+///
+/// Pass its descriptors to [`InterruptBuilder::resources()`] to create an
+/// [`Interrupt`] for it.
+#[derive(Clone, Copy)]
+pub struct InterruptResource<'a> {
+    raw: &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    translated: &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+}
+
+impl<'a> InterruptResource<'a> {
+    /// Returns the interrupt's descriptor from the raw resource list
+    #[must_use]
+    pub const fn raw(&self) -> &'a CM_PARTIAL_RESOURCE_DESCRIPTOR {
+        self.raw
+    }
+
+    /// Returns the interrupt's descriptor from the translated resource list
+    #[must_use]
+    pub const fn translated(&self) -> &'a CM_PARTIAL_RESOURCE_DESCRIPTOR {
+        self.translated
+    }
+
+    /// Returns whether the interrupt is a message-signaled (MSI or MSI-X)
+    /// interrupt
+    #[must_use]
+    pub fn is_message_signaled(&self) -> bool {
+        u32::from(self.raw.Flags) & CM_RESOURCE_INTERRUPT_MESSAGE != 0
+    }
+}
+
+/// An iterator over the interrupts in a device's resource lists.
+///
+/// The framework describes each message of a device with multiple
+/// message-signaled interrupts with a descriptor of its own, so creating an
+/// [`Interrupt`] for each item, in
+/// [`PnpPowerCallbacks::prepare_hardware()`](super::PnpPowerCallbacks::prepare_hardware),
+/// creates one interrupt per message. The framework passes the index of each
+/// message to the ISR of its interrupt.
+///
+/// ```ignore
+/// for (message, resource) in InterruptResources::new(resources_raw, resources_translated)?.enumerate() {
+///     InterruptBuilder::new(MessageCallbacks::new(message))
+///         .resources(resource.raw(), resource.translated())
+///         .create(device)?;
+/// }
+/// ```
+#[must_use]
+pub struct InterruptResources<'a> {
+    resources_raw: ResourceList<'a>,
+    resources_translated: ResourceList<'a>,
+    index: usize,
+}
+
+impl<'a> InterruptResources<'a> {
+    /// Construct an iterator over the interrupts in `resources_raw` and
+    /// `resources_translated`, as passed to
+    /// [`PnpPowerCallbacks::prepare_hardware()`](super::PnpPowerCallbacks::prepare_hardware)
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `resources_raw` is not a raw
+    /// resource list, `resources_translated` is not a translated resource
+    /// list, or they have different numbers of descriptors. The error variant
+    /// will contain `STATUS_INVALID_PARAMETER`.
+    pub fn new(
+        resources_raw: ResourceList<'a>,
+        resources_translated: ResourceList<'a>,
+    ) -> Result<Self, NTSTATUS> {
+        if resources_raw.is_translated()
+            || !resources_translated.is_translated()
+            || resources_raw.len() != resources_translated.len()
+        {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        Ok(Self {
+            resources_raw,
+            resources_translated,
+            index: 0,
+        })
+    }
+}
+
+impl<'a> Iterator for InterruptResources<'a> {
+    type Item = InterruptResource<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let raw = self.resources_raw.descriptor(self.index)?;
+            let translated = self.resources_translated.descriptor(self.index)?;
+            self.index += 1;
+            if u32::from(translated.Type) == wdk_sys::CmResourceTypeInterrupt {
+                return Some(InterruptResource { raw, translated });
+            }
+        }
+    }
+}
+
+/// Event callbacks of an [`Interrupt`].
+///
+/// [`InterruptCallbacks::isr()`] runs at the device's `DIRQL` (or at `IRQL` =
+/// `PASSIVE_LEVEL` for passive-level interrupts), with the interrupt lock
+/// held. It should only determine whether the device raised the interrupt,
+/// save any volatile state, and queue a DPC with [`IsrContext::queue_dpc()`]
+/// for the remaining work, which then runs in
+/// [`InterruptCallbacks::dpc()`] at `IRQL` = `DISPATCH_LEVEL`.
+///
+/// Every method other than [`InterruptCallbacks::isr()`] has a default
+/// implementation that does nothing.
+pub trait InterruptCallbacks: Send + Sync + 'static {
+    /// Called when the interrupt is signaled, for the message `message_id`
+    /// of message-signaled interrupts or 0 otherwise. Returns whether the
+    /// device raised the interrupt (`EvtInterruptIsr`)
+    ///
+    /// This runs at `DIRQL` with the interrupt lock held, so it must not
+    /// block, access paged memory, or acquire the interrupt lock.
+    fn isr(&self, context: &IsrContext<'_>, message_id: u32) -> bool;
+
+    /// Called at `IRQL` = `DISPATCH_LEVEL` after the ISR has queued a DPC
+    /// with [`IsrContext::queue_dpc()`] (`EvtInterruptDpc`)
+    fn dpc(&self, interrupt: &Interrupt, device: &Device) {
+        let _ = (interrupt, device);
+    }
+
+    /// Called at `DIRQL`, with the interrupt lock held, after the device
+    /// enters its working (D0) state, to enable the interrupt in the
+    /// hardware (`EvtInterruptEnable`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error fails the device's transition to D0.
+    fn enable(&self, interrupt: &Interrupt, device: &Device) -> Result<(), NTSTATUS> {
+        let _ = (interrupt, device);
+        Ok(())
+    }
+
+    /// Called at `DIRQL`, with the interrupt lock held, before the device
+    /// leaves its working (D0) state, to disable the interrupt in the
+    /// hardware (`EvtInterruptDisable`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error is reported to the framework, which still
+    /// continues to power down the device.
+    fn disable(&self, interrupt: &Interrupt, device: &Device) -> Result<(), NTSTATUS> {
+        let _ = (interrupt, device);
+        Ok(())
+    }
+}
+
+/// The interrupt passed to [`InterruptCallbacks::isr()`].
+///
+/// An [`IsrContext`] only exposes the operations that are allowed at the
+/// `DIRQL` that ISRs run at, while the framework holds the interrupt lock.
+/// In particular, it does not allow acquiring the interrupt lock, which
+/// would deadlock.
+pub struct IsrContext<'a> {
+    interrupt: &'a Interrupt,
+}
+
+impl IsrContext<'_> {
+    /// Returns the raw `WDFINTERRUPT` handle of the interrupt
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFINTERRUPT {
+        self.interrupt.wdf_interrupt
+    }
+
+    /// Queue the interrupt's [`InterruptCallbacks::dpc()`] to run at `IRQL` =
+    /// `DISPATCH_LEVEL`
+    ///
+    /// Returns `false` if the DPC was already queued, in which case it still
+    /// only runs once.
+    // DPCs are commonly queued without caring whether they were already queued
+    #[allow(clippy::must_use_candidate)]
+    pub fn queue_dpc(&self) -> bool {
+        let result;
+        // SAFETY: `interrupt` is the interrupt whose ISR is running, which is valid for
+        // the duration of the ISR.
+        unsafe {
+            result = macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptQueueDpcForIsr,
+                self.interrupt.wdf_interrupt
+            );
+        }
+        result != 0
+    }
+}
+
+/// Builder of a WDF Interrupt object.
+///
+/// [`InterruptBuilder::new()`] mirrors `WDF_INTERRUPT_CONFIG_INIT`. Interrupts
+/// are typically created in `EvtDriverDeviceAdd`, or for interrupts described
+/// by [`InterruptBuilder::resources()`], in
+/// [`PnpPowerCallbacks::prepare_hardware()`](super::PnpPowerCallbacks::prepare_hardware).
+/// Use [`InterruptResources`] to create one interrupt for each message of a
+/// device with multiple message-signaled interrupts.
+///
+/// ```ignore
+/// let interrupt = InterruptBuilder::new(MyInterruptCallbacks::default()).create(&device)?;
+/// ```
+#[must_use]
+pub struct InterruptBuilder<'a, C> {
+    share_vector: WDF_TRI_STATE,
+    floating_save: bool,
+    automatic_serialization: bool,
+    resources: Option<(
+        &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+        &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    )>,
+    callbacks: C,
+}
+
+impl<'a, C: InterruptCallbacks> InterruptBuilder<'a, C> {
+    /// Construct an [`InterruptBuilder`] for an interrupt with `callbacks`
+    pub const fn new(callbacks: C) -> Self {
+        Self {
+            share_vector: _WDF_TRI_STATE::WdfUseDefault,
+            floating_save: false,
+            automatic_serialization: false,
+            resources: None,
+            callbacks,
+        }
+    }
+
+    /// Set whether the interrupt's vector can be shared with other devices.
+    /// By default, the resource descriptor of the interrupt decides.
+    pub const fn share_vector(mut self, share_vector: bool) -> Self {
+        self.share_vector = if share_vector {
+            _WDF_TRI_STATE::WdfTrue
+        } else {
+            _WDF_TRI_STATE::WdfFalse
+        };
+        self
+    }
+
+    /// Set whether the system saves the processor's floating-point state
+    /// while the ISR runs
+    pub const fn floating_save(mut self, floating_save: bool) -> Self {
+        self.floating_save = floating_save;
+        self
+    }
+
+    /// Set whether the framework synchronizes the interrupt's DPC with the
+    /// callbacks of the device's other objects, according to the device's
+    /// synchronization scope
+    pub const fn automatic_serialization(mut self, automatic_serialization: bool) -> Self {
+        self.automatic_serialization = automatic_serialization;
+        self
+    }
+
+    /// Set the raw and translated resource descriptors of the interrupt,
+    /// from the resource lists passed to
+    /// [`PnpPowerCallbacks::prepare_hardware()`](super::PnpPowerCallbacks::prepare_hardware)
+    ///
+    /// This is required for interrupts created there, and by default the
+    /// framework assigns the device's interrupt resources to interrupts in
+    /// the order they are created.
+    pub const fn resources(
+        mut self,
+        raw: &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+        translated: &'a CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    ) -> Self {
+        self.resources = Some((raw, translated));
+        self
+    }
+
+    /// Try to create the interrupt, for `device`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`. The interrupt is
+    /// deleted along with `device`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the interrupt, or to allocate storage for its callbacks. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFInterrupt Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfinterrupt/nf-wdfinterrupt-wdfinterruptcreate#return-value)
+    pub fn create(self, device: &Device) -> Result<Interrupt, NTSTATUS> {
+        let (interrupt_raw, interrupt_translated) = self.resources.map_or(
+            (core::ptr::null_mut(), core::ptr::null_mut()),
+            |(raw, translated)| {
+                (
+                    core::ptr::from_ref(raw).cast_mut(),
+                    core::ptr::from_ref(translated).cast_mut(),
+                )
+            },
+        );
+        let mut interrupt_config = WDF_INTERRUPT_CONFIG {
+            Size: INTERRUPT_CONFIG_SIZE,
+            ShareVector: self.share_vector,
+            FloatingSave: u8::from(self.floating_save),
+            AutomaticSerialization: u8::from(self.automatic_serialization),
+            EvtInterruptIsr: Some(evt_interrupt_isr::<C>),
+            EvtInterruptDpc: Some(evt_interrupt_dpc::<C>),
+            EvtInterruptEnable: Some(evt_interrupt_enable::<C>),
+            EvtInterruptDisable: Some(evt_interrupt_disable::<C>),
+            InterruptRaw: interrupt_raw,
+            InterruptTranslated: interrupt_translated,
+            ReportInactiveOnPowerDown: _WDF_TRI_STATE::WdfUseDefault,
+            ..Default::default()
+        };
+
+        let mut interrupt = Interrupt {
+            wdf_interrupt: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid handle to a device object, and
+        // `interrupt_config` is valid for the duration of the call, which copies the
+        // resource descriptors it points to. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfInterruptCreate,
+                device.as_raw(),
+                &mut interrupt_config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut interrupt.wdf_interrupt,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: `wdf_interrupt` is a valid handle to the interrupt that was just
+        // created, and nothing else has been attached to it. The framework does not
+        // connect the interrupt until the device enters D0, after the callback that
+        // creates it returns, and failing that callback prevents it from being
+        // connected.
+        unsafe { attach_closure(interrupt.wdf_interrupt.cast(), self.callbacks) }?;
+        Ok(interrupt)
+    }
+}
+
+/// The closure passed to [`Interrupt::synchronize()`], and its result
+struct SynchronizeContext<F, R> {
+    callback: Option<F>,
+    result: Option<R>,
+}
+
+/// The `EvtInterruptSynchronize` of [`Interrupt::synchronize()`]
+///
+/// # Safety
+///
+/// `context` must point to a `SynchronizeContext<F, R>` that is not accessed
+/// by anything else for the duration of the call.
+unsafe extern "C" fn evt_interrupt_synchronize<F, R>(
+    _wdf_interrupt: WDFINTERRUPT,
+    context: WDFCONTEXT,
+) -> u8
+where
+    F: FnOnce() -> R,
+{
+    // SAFETY: `Interrupt::synchronize()` passes a pointer to its
+    // `SynchronizeContext<F, R>`, which it does not access until the framework
+    // returns from the call that runs this.
+    let context = unsafe { &mut *context.cast::<SynchronizeContext<F, R>>() };
+    if let Some(callback) = context.callback.take() {
+        context.result = Some(callback());
+    }
+    u8::from(true)
+}
+
+fn nt_status_from(result: Result<(), NTSTATUS>) -> NTSTATUS {
+    result.map_or_else(|nt_status| nt_status, |()| STATUS_SUCCESS)
+}
+
+/// Returns the [`InterruptCallbacks`] attached to `wdf_interrupt`, and the
+/// [`Interrupt`] itself
+///
+/// # Safety
+///
+/// `wdf_interrupt` must be a valid handle to an interrupt that a `C` was
+/// attached to by [`InterruptBuilder::create()`].
+unsafe fn interrupt_callbacks<'a, C>(wdf_interrupt: WDFINTERRUPT) -> (&'a C, Interrupt) {
+    // SAFETY: The caller guarantees that a `C` was attached to `wdf_interrupt`.
+    let callbacks = unsafe { closure::<C>(wdf_interrupt.cast()) };
+    (callbacks, Interrupt { wdf_interrupt })
+}
+
+/// The `EvtInterruptIsr` of interrupts created by [`InterruptBuilder`]
+///
+/// # Safety
+///
+/// `wdf_interrupt` must be a valid handle to an interrupt that a `C` was
+/// attached to.
+unsafe extern "C" fn evt_interrupt_isr<C: InterruptCallbacks>(
+    wdf_interrupt: WDFINTERRUPT,
+    message_id: ULONG,
+) -> u8 {
+    // SAFETY: The framework only calls this with the interrupt, which had a `C`
+    // attached before it was connected.
+    let (callbacks, interrupt) = unsafe { interrupt_callbacks::<C>(wdf_interrupt) };
+    u8::from(callbacks.isr(
+        &IsrContext {
+            interrupt: &interrupt,
+        },
+        message_id,
+    ))
+}
+
+/// The `EvtInterruptDpc` of interrupts created by [`InterruptBuilder`]
+///
+/// # Safety
+///
+/// `wdf_interrupt` must be a valid handle to an interrupt that a `C` was
+/// attached to, and `associated_object` must be its device.
+unsafe extern "C" fn evt_interrupt_dpc<C: InterruptCallbacks>(
+    wdf_interrupt: WDFINTERRUPT,
+    associated_object: WDFOBJECT,
+) {
+    // SAFETY: The framework only calls this with the interrupt, which had a `C`
+    // attached before it was connected.
+    let (callbacks, interrupt) = unsafe { interrupt_callbacks::<C>(wdf_interrupt) };
+    // SAFETY: The associated object of an interrupt is the device it was created
+    // for, which outlives it.
+    let device = unsafe { Device::from_raw(associated_object.cast()) };
+    callbacks.dpc(&interrupt, &device);
+}
+
+/// The `EvtInterruptEnable` of interrupts created by [`InterruptBuilder`]
+///
+/// # Safety
+///
+/// `wdf_interrupt` must be a valid handle to an interrupt that a `C` was
+/// attached to, and `wdf_device` must be its device.
+unsafe extern "C" fn evt_interrupt_enable<C: InterruptCallbacks>(
+    wdf_interrupt: WDFINTERRUPT,
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the interrupt, which had a `C`
+    // attached before it was connected.
+    let (callbacks, interrupt) = unsafe { interrupt_callbacks::<C>(wdf_interrupt) };
+    // SAFETY: The framework passes the device the interrupt was created for, which
+    // outlives it.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    nt_status_from(callbacks.enable(&interrupt, &device))
+}
+
+/// The `EvtInterruptDisable` of interrupts created by [`InterruptBuilder`]
+///
+/// # Safety
+///
+/// `wdf_interrupt` must be a valid handle to an interrupt that a `C` was
+/// attached to, and `wdf_device` must be its device.
+unsafe extern "C" fn evt_interrupt_disable<C: InterruptCallbacks>(
+    wdf_interrupt: WDFINTERRUPT,
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    // SAFETY: The framework only calls this with the interrupt, which had a `C`
+    // attached before it was connected.
+    let (callbacks, interrupt) = unsafe { interrupt_callbacks::<C>(wdf_interrupt) };
+    // SAFETY: The framework passes the device the interrupt was created for, which
+    // outlives it.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    nt_status_from(callbacks.disable(&interrupt, &device))
+}
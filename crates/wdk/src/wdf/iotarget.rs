@@ -0,0 +1,505 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ops::Deref, time::Duration};
+
+use wdk_sys::{
+    _WDF_MEMORY_DESCRIPTOR__bindgen_ty_1,
+    _WDF_MEMORY_DESCRIPTOR__bindgen_ty_1__bindgen_ty_1,
+    macros,
+    ntddk::{ExFreePool, IoGetDeviceInterfaces},
+    _WDF_IO_TARGET_OPEN_TYPE,
+    _WDF_MEMORY_DESCRIPTOR_TYPE,
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS,
+    ACCESS_MASK,
+    FILE_NON_DIRECTORY_FILE,
+    FILE_OPEN,
+    GUID,
+    NTSTATUS,
+    PVOID,
+    PZZWSTR,
+    STATUS_INVALID_PARAMETER,
+    STATUS_OBJECT_NAME_NOT_FOUND,
+    ULONG,
+    ULONG_PTR,
+    UNICODE_STRING,
+    WDFIOTARGET,
+    WDFOBJECT,
+    WDF_IO_TARGET_OPEN_PARAMS,
+    WDF_MEMORY_DESCRIPTOR,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_REQUEST_SEND_OPTIONS,
+};
+
+use super::{child::ChildObject, Device, WdfObject};
+use crate::{nt_success, time::relative_timeout};
+
+// `WDF_REQUEST_SEND_OPTIONS` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const REQUEST_SEND_OPTIONS_SIZE: ULONG = core::mem::size_of::<WDF_REQUEST_SEND_OPTIONS>() as ULONG;
+
+// `WDF_IO_TARGET_OPEN_PARAMS` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const IO_TARGET_OPEN_PARAMS_SIZE: ULONG =
+    core::mem::size_of::<WDF_IO_TARGET_OPEN_PARAMS>() as ULONG;
+
+// `Flags` is a `ULONG`, while the `WDF_REQUEST_SEND_OPTIONS_FLAGS` enumeration
+// it holds values of is an `int`
+#[allow(clippy::cast_sign_loss)]
+const SEND_OPTION_TIMEOUT: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_TIMEOUT as ULONG;
+#[allow(clippy::cast_sign_loss)]
+const SEND_OPTION_IGNORE_TARGET_STATE: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_IGNORE_TARGET_STATE as ULONG;
+
+/// Options for sending a request to an [`IoTarget`], used with the
+/// synchronous send methods of [`IoTarget`] and with
+/// [`Request::send()`](super::Request::send).
+///
+/// [`SendOptions::new()`] is the equivalent of `WDF_REQUEST_SEND_OPTIONS_INIT`
+/// without any flags, and [`SendOptions::timeout()`] the equivalent of
+/// `WDF_REQUEST_SEND_OPTIONS_SET_TIMEOUT`.
+#[derive(Clone, Copy, Debug, Default)]
+#[must_use]
+pub struct SendOptions {
+    timeout: Option<Duration>,
+    ignore_target_state: bool,
+}
+
+impl SendOptions {
+    /// Construct [`SendOptions`] that send the request without a timeout
+    pub const fn new() -> Self {
+        Self {
+            timeout: None,
+            ignore_target_state: false,
+        }
+    }
+
+    /// Cancel the request if the target does not complete it within `timeout`
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set whether the request is sent even if the target is stopped, instead
+    /// of being queued or failed
+    pub const fn ignore_target_state(mut self, ignore_target_state: bool) -> Self {
+        self.ignore_target_state = ignore_target_state;
+        self
+    }
+
+    /// Returns the `WDF_REQUEST_SEND_OPTIONS` built by these [`SendOptions`],
+    /// with `flags` set in addition to the flags of the options
+    pub(crate) fn to_raw(self, flags: ULONG) -> WDF_REQUEST_SEND_OPTIONS {
+        let mut send_options = WDF_REQUEST_SEND_OPTIONS {
+            Size: REQUEST_SEND_OPTIONS_SIZE,
+            Flags: flags,
+            ..Default::default()
+        };
+        if let Some(timeout) = self.timeout {
+            send_options.Flags |= SEND_OPTION_TIMEOUT;
+            send_options.Timeout = relative_timeout(timeout);
+        }
+        if self.ignore_target_state {
+            send_options.Flags |= SEND_OPTION_IGNORE_TARGET_STATE;
+        }
+        send_options
+    }
+}
+
+/// WDF I/O Target.
+///
+/// An [`IoTarget`] represents a device object that the driver sends requests
+/// to. The local I/O target of a device, which is the next lower driver in its
+/// driver stack, is returned by
+/// [`Device::io_target()`](super::Device::io_target), and targets for other
+/// devices are opened as a [`RemoteIoTarget`], which dereferences to an
+/// [`IoTarget`].
+///
+/// Requests can be sent synchronously with the `send_*_sync` methods, which
+/// create a request for the caller, or asynchronously with
+/// [`Request::send()`](super::Request::send).
+///
+/// [`IoTarget`] is not [`Copy`], so that references to the [`IoTarget`] of a
+/// [`RemoteIoTarget`] cannot outlive it.
+pub struct IoTarget {
+    wdf_io_target: WDFIOTARGET,
+}
+
+// SAFETY: `WDFIOTARGET` handles can be used from any thread.
+unsafe impl Send for IoTarget {}
+
+// SAFETY: `IoTarget` has no methods that mutate the I/O target object.
+unsafe impl Sync for IoTarget {}
+
+// SAFETY: The local I/O target of a device lives as long as the device, and
+// `IoTarget` handles are only passed to the driver while the device exists.
+// `RemoteIoTarget`s only give out references to their `IoTarget` that are
+// bound by their own lifetime.
+unsafe impl WdfObject for IoTarget {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_io_target.cast()
+    }
+}
+
+impl IoTarget {
+    /// Wrap an existing WDF I/O Target object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_io_target` must be a valid handle to a WDF I/O Target object, and
+    /// must remain valid for the lifetime of the returned [`IoTarget`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_io_target: WDFIOTARGET) -> Self {
+        Self { wdf_io_target }
+    }
+
+    /// Returns the raw `WDFIOTARGET` handle wrapped by this [`IoTarget`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFIOTARGET {
+        self.wdf_io_target
+    }
+
+    /// Send a device I/O control request for `io_control_code` to the target,
+    /// and wait for it to be completed, returning the number of bytes written
+    /// to `output`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a buffer is longer than `ULONG::MAX` bytes, if the request times out, or if the target fails the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendioctlsynchronously#return-value)
+    pub fn send_ioctl_sync(
+        &self,
+        io_control_code: ULONG,
+        input: Option<&[u8]>,
+        output: Option<&mut [u8]>,
+        options: SendOptions,
+    ) -> Result<usize, NTSTATUS> {
+        let mut input_descriptor = input
+            .map(|input| memory_descriptor(input.as_ptr().cast_mut().cast(), input.len()))
+            .transpose()?;
+        let mut output_descriptor = output
+            .map(|output| memory_descriptor(output.as_mut_ptr().cast(), output.len()))
+            .transpose()?;
+        let mut send_options = options.to_raw(0);
+        let mut bytes_returned: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, and is valid for
+        // the lifetime of the `IoTarget`. The memory descriptors describe buffers that
+        // are borrowed for the duration of the call, and the input buffer is only read
+        // from. Not passing a request is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendIoctlSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                io_control_code,
+                optional_ptr(input_descriptor.as_mut()),
+                optional_ptr(output_descriptor.as_mut()),
+                &mut send_options,
+                &mut bytes_returned,
+            );
+        }
+        transferred_bytes(nt_status, bytes_returned)
+    }
+
+    /// Send a read request to the target, for `output.len()` bytes starting at
+    /// `device_offset`, and wait for it to be completed, returning the number
+    /// of bytes read
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `output` is longer than `ULONG::MAX` bytes, if the request times out, or if the target fails the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendreadsynchronously#return-value)
+    pub fn send_read_sync(
+        &self,
+        output: &mut [u8],
+        device_offset: Option<i64>,
+        options: SendOptions,
+    ) -> Result<usize, NTSTATUS> {
+        let mut output_descriptor = memory_descriptor(output.as_mut_ptr().cast(), output.len())?;
+        let mut device_offset = device_offset;
+        let mut send_options = options.to_raw(0);
+        let mut bytes_read: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, and is valid for
+        // the lifetime of the `IoTarget`. The memory descriptor describes a buffer
+        // that is mutably borrowed for the duration of the call. Not passing a
+        // request or device offset is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendReadSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                &mut output_descriptor,
+                optional_ptr(device_offset.as_mut()),
+                &mut send_options,
+                &mut bytes_read,
+            );
+        }
+        transferred_bytes(nt_status, bytes_read)
+    }
+
+    /// Send a write request to the target, for the bytes of `input` starting
+    /// at `device_offset`, and wait for it to be completed, returning the
+    /// number of bytes written
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `input` is longer than `ULONG::MAX` bytes, if the request times out, or if the target fails the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetsendwritesynchronously#return-value)
+    pub fn send_write_sync(
+        &self,
+        input: &[u8],
+        device_offset: Option<i64>,
+        options: SendOptions,
+    ) -> Result<usize, NTSTATUS> {
+        let mut input_descriptor =
+            memory_descriptor(input.as_ptr().cast_mut().cast(), input.len())?;
+        let mut device_offset = device_offset;
+        let mut send_options = options.to_raw(0);
+        let mut bytes_written: ULONG_PTR = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a private member of `IoTarget`, and is valid for
+        // the lifetime of the `IoTarget`. The memory descriptor describes a buffer
+        // that is borrowed for the duration of the call, and is only read from. Not
+        // passing a request or device offset is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetSendWriteSynchronously,
+                self.wdf_io_target,
+                core::ptr::null_mut(),
+                &mut input_descriptor,
+                optional_ptr(device_offset.as_mut()),
+                &mut send_options,
+                &mut bytes_written,
+            );
+        }
+        transferred_bytes(nt_status, bytes_written)
+    }
+}
+
+/// WDF I/O Target for a device other than the next lower driver in the
+/// driver's device stack.
+///
+/// The lifetime `'p` is bounded by the device that the target is created for,
+/// and the target is closed and deleted when the [`RemoteIoTarget`] is
+/// dropped. Use [`RemoteIoTarget::into_parent_owned()`] to leave it to be
+/// deleted with its device instead.
+pub struct RemoteIoTarget<'p> {
+    io_target: IoTarget,
+    object: ChildObject<'p>,
+}
+
+impl<'p> RemoteIoTarget<'p> {
+    /// Try to open the device named `name`, such as `\Device\Foo`, as an I/O
+    /// target of `device`, with `desired_access`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the I/O target, or fails to open the device. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetopen#return-value)
+    pub fn open_by_name(
+        device: &'p Device,
+        name: &UNICODE_STRING,
+        desired_access: ACCESS_MASK,
+    ) -> Result<Self, NTSTATUS> {
+        let mut wdf_io_target: WDFIOTARGET = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `device` is a valid handle to a device object, and `wdf_io_target` is
+        // valid for the duration of the call. `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetCreate,
+                device.as_raw(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_io_target,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let remote_io_target = Self {
+            io_target: IoTarget { wdf_io_target },
+            // SAFETY: `wdf_io_target` is a valid handle to the I/O target that was just
+            // created, which is only deleted by this `ChildObject`, and whose parent
+            // lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_io_target.cast()) },
+        };
+
+        let mut open_params = WDF_IO_TARGET_OPEN_PARAMS {
+            Size: IO_TARGET_OPEN_PARAMS_SIZE,
+            Type: _WDF_IO_TARGET_OPEN_TYPE::WdfIoTargetOpenByName,
+            TargetDeviceName: *name,
+            DesiredAccess: desired_access,
+            CreateDisposition: FILE_OPEN,
+            CreateOptions: FILE_NON_DIRECTORY_FILE,
+            ..Default::default()
+        };
+
+        let nt_status;
+        // SAFETY: `wdf_io_target` is a valid handle to the I/O target that was just
+        // created, and `open_params` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetOpen,
+                wdf_io_target,
+                &mut open_params,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(remote_io_target)
+    }
+
+    /// Try to open the first enabled device interface of `interface_class` as
+    /// an I/O target of `device`, with `desired_access`
+    ///
+    /// Use an [`InterfaceNotification`](super::InterfaceNotification) to be
+    /// notified when device interfaces of the class arrive, to open them as
+    /// they do.
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no device interface of `interface_class` is enabled, if WDF fails to contruct the I/O target, or if it fails to open the device. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [IoGetDeviceInterfaces Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdm/nf-wdm-iogetdeviceinterfaces#return-value)
+    pub fn open_by_interface(
+        device: &'p Device,
+        interface_class: &GUID,
+        desired_access: ACCESS_MASK,
+    ) -> Result<Self, NTSTATUS> {
+        let mut symbolic_link_list: PZZWSTR = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `interface_class` and `symbolic_link_list` are valid for the duration
+        // of the call. Not passing a physical device object is allowed.
+        unsafe {
+            nt_status = IoGetDeviceInterfaces(
+                interface_class,
+                core::ptr::null_mut(),
+                0,
+                &mut symbolic_link_list,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: `IoGetDeviceInterfaces` returned a list of null-terminated strings,
+        // terminated by an empty string.
+        let result = unsafe { first_string(symbolic_link_list) }
+            .ok_or(STATUS_OBJECT_NAME_NOT_FOUND)
+            .and_then(|name| Self::open_by_name(device, &name, desired_access));
+
+        // SAFETY: `symbolic_link_list` was allocated by `IoGetDeviceInterfaces`, and
+        // the name borrowed from it is not used after this.
+        unsafe {
+            ExFreePool(symbolic_link_list.cast());
+        }
+        result
+    }
+
+    /// Leave the WDF I/O target object to be deleted along with its device,
+    /// rather than when the [`RemoteIoTarget`] is dropped, so that the
+    /// [`RemoteIoTarget`] can be stored in the context of its device
+    ///
+    /// # Safety
+    ///
+    /// The returned [`RemoteIoTarget`] must not be used after its device is
+    /// deleted. Storing it in the context of its device satisfies this, as
+    /// long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> RemoteIoTarget<'static> {
+        let Self { io_target, object } = self;
+        core::mem::forget(object);
+        let wdf_io_target = io_target.wdf_io_target;
+        RemoteIoTarget {
+            io_target,
+            object: ChildObject::parent_owned(wdf_io_target.cast()),
+        }
+    }
+}
+
+impl Deref for RemoteIoTarget<'_> {
+    type Target = IoTarget;
+
+    fn deref(&self) -> &Self::Target {
+        &self.io_target
+    }
+}
+
+/// Returns a `WDF_MEMORY_DESCRIPTOR` for the `length` bytes at `buffer`,
+/// equivalent to `WDF_MEMORY_DESCRIPTOR_INIT_BUFFER`
+fn memory_descriptor(buffer: PVOID, length: usize) -> Result<WDF_MEMORY_DESCRIPTOR, NTSTATUS> {
+    let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_PARAMETER)?;
+    Ok(WDF_MEMORY_DESCRIPTOR {
+        Type: _WDF_MEMORY_DESCRIPTOR_TYPE::WdfMemoryDescriptorTypeBuffer,
+        u: _WDF_MEMORY_DESCRIPTOR__bindgen_ty_1 {
+            BufferType: _WDF_MEMORY_DESCRIPTOR__bindgen_ty_1__bindgen_ty_1 {
+                Buffer: buffer,
+                Length: length,
+            },
+        },
+    })
+}
+
+/// Returns the first string of a list of null-terminated strings, or [`None`]
+/// if the list is empty or the string is too long for a `UNICODE_STRING`
+///
+/// # Safety
+///
+/// `list` must point to a list of null-terminated strings, terminated by an
+/// empty string. The returned `UNICODE_STRING` borrows from `list`.
+unsafe fn first_string(list: PZZWSTR) -> Option<UNICODE_STRING> {
+    let mut length = 0;
+    loop {
+        let character;
+        // SAFETY: The caller guarantees that every string of the list is
+        // null-terminated, so reads stop at the terminator of the first string.
+        unsafe {
+            character = *list.wrapping_add(length);
+        }
+        if character == 0 {
+            break;
+        }
+        length += 1;
+    }
+    if length == 0 {
+        return None;
+    }
+    let length = u16::try_from(length * core::mem::size_of::<u16>()).ok()?;
+    Some(UNICODE_STRING {
+        Length: length,
+        MaximumLength: length,
+        Buffer: list,
+    })
+}
+
+/// Returns a pointer to the value of `value`, or a null pointer if it is
+/// [`None`]
+fn optional_ptr<T>(value: Option<&mut T>) -> *mut T {
+    value.map_or(core::ptr::null_mut(), core::ptr::from_mut)
+}
+
+/// Returns the number of bytes transferred by a synchronous send that returned
+/// `nt_status`
+fn transferred_bytes(nt_status: NTSTATUS, bytes: ULONG_PTR) -> Result<usize, NTSTATUS> {
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+    // `ULONG_PTR` is pointer-sized, so it always fits in a `usize`
+    #[allow(clippy::cast_possible_truncation)]
+    let bytes = bytes as usize;
+    Ok(bytes)
+}
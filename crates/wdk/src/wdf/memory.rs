@@ -0,0 +1,336 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    macros,
+    _POOL_TYPE,
+    NTSTATUS,
+    POOL_FLAGS,
+    POOL_FLAG_NON_PAGED,
+    POOL_FLAG_PAGED,
+    POOL_TYPE,
+    PVOID,
+    STATUS_BUFFER_TOO_SMALL,
+    STATUS_INVALID_PARAMETER,
+    ULONG,
+    WDFMEMORY,
+    WDFOBJECT,
+};
+
+use super::{child::ChildObject, ObjectAttributes, WdfObject};
+use crate::nt_success;
+
+/// The kind of pool that the buffer of a [`Memory`] object, or a pool
+/// collection such as a [`KVec`](crate::collections::KVec), is allocated from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoolType {
+    /// Nonpaged, non-executable pool, which can be accessed at any `IRQL`
+    #[default]
+    NonPaged,
+    /// Paged pool, which can only be accessed at `IRQL` <= `APC_LEVEL`
+    Paged,
+}
+
+impl PoolType {
+    const fn as_raw(self) -> POOL_TYPE {
+        match self {
+            Self::NonPaged => _POOL_TYPE::NonPagedPoolNx,
+            Self::Paged => _POOL_TYPE::PagedPool,
+        }
+    }
+
+    pub(crate) const fn as_flags(self) -> POOL_FLAGS {
+        match self {
+            Self::NonPaged => POOL_FLAG_NON_PAGED,
+            Self::Paged => POOL_FLAG_PAGED,
+        }
+    }
+}
+
+/// WDF Memory.
+///
+/// A memory object describes a buffer, and is how buffers are passed to the
+/// framework's request formatting functions. The buffer is either allocated
+/// by the framework along with the object, with [`Memory::try_new()`], or
+/// borrowed from the driver, with [`Memory::try_from_buffer()`].
+///
+/// The lifetime `'p` is bounded by the parent object set in the
+/// [`ObjectAttributes`] the memory object is constructed with, and by the
+/// buffer it borrows, if any. The object, along with any buffer that the
+/// framework allocated for it, is deleted when the [`Memory`] is dropped. Use
+/// [`Memory::into_parent_owned()`] to leave it to be deleted with its parent
+/// instead.
+///
+/// ```ignore
+/// let mut memory = Memory::try_new(size_of::<Command>(), PoolType::NonPaged, 0, ObjectAttributes::new().parent(&request))?;
+/// memory.copy_from_buffer(0, command.as_bytes())?;
+/// ```
+// `wdf_memory` is named consistently with the handles of the other wrappers
+#[allow(clippy::struct_field_names)]
+pub struct Memory<'p> {
+    wdf_memory: WDFMEMORY,
+    buffer: NonNull<u8>,
+    length: usize,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFMEMORY` handles can be used, and deleted, from any thread, and
+// the buffer is only accessed through `&self` immutably.
+unsafe impl Send for Memory<'_> {}
+
+// SAFETY: `Memory` only allows its buffer to be mutated through `&mut self`.
+unsafe impl Sync for Memory<'_> {}
+
+// SAFETY: `wdf_memory` is a private member of `Memory`, originally created by
+// WDF, and the lifetime of a `Memory` is bounded by its parent.
+unsafe impl WdfObject for Memory<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_memory.cast()
+    }
+}
+
+impl<'p> Memory<'p> {
+    /// Try to allocate a buffer of `length` bytes from `pool_type`, along with
+    /// a memory object describing it
+    ///
+    /// A `pool_tag` of 0 tags the buffer with the driver's pool tag. The
+    /// contents of the buffer are not initialized by the framework. This must
+    /// be called at `IRQL` = `PASSIVE_LEVEL` for [`PoolType::Paged`], or
+    /// `IRQL` <= `DISPATCH_LEVEL` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `length` is 0, or if WDF fails to allocate the buffer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycreate#return-value)
+    ///
+    /// # Panics
+    ///
+    /// Panics if WDF returns a null buffer for the memory object it created,
+    /// which it never does.
+    pub fn try_new(
+        length: usize,
+        pool_type: PoolType,
+        pool_tag: ULONG,
+        mut attributes: ObjectAttributes<'p>,
+    ) -> Result<Self, NTSTATUS> {
+        if length == 0 {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+        let mut buffer: PVOID = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. `wdf_memory` and `buffer` are valid for the
+        // duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfMemoryCreate,
+                attributes.as_raw_mut(),
+                pool_type.as_raw(),
+                pool_tag,
+                length,
+                &mut wdf_memory,
+                &mut buffer,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            wdf_memory,
+            buffer: NonNull::new(buffer.cast())
+                .expect("WDF should return the buffer of a memory object it created"),
+            length,
+            // SAFETY: `wdf_memory` is a valid handle to the memory object that was just
+            // created, which is only deleted by this `ChildObject`, and whose parent lives
+            // for `'p`.
+            object: unsafe { ChildObject::new(wdf_memory.cast()) },
+        })
+    }
+
+    /// Try to create a memory object describing `buffer`
+    ///
+    /// The buffer is borrowed for as long as the memory object exists. This
+    /// must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `buffer` is empty, or if WDF fails to create the memory object. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycreatepreallocated#return-value)
+    pub fn try_from_buffer(
+        buffer: &'p mut [u8],
+        mut attributes: ObjectAttributes<'p>,
+    ) -> Result<Self, NTSTATUS> {
+        if buffer.is_empty() {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let mut wdf_memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. `buffer` is mutably borrowed for as long as the
+        // memory object exists, and `wdf_memory` is valid for the duration of the
+        // call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfMemoryCreatePreallocated,
+                attributes.as_raw_mut(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                &mut wdf_memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            wdf_memory,
+            buffer: NonNull::from(&mut *buffer).cast(),
+            length: buffer.len(),
+            // SAFETY: `wdf_memory` is a valid handle to the memory object that was just
+            // created, which is only deleted by this `ChildObject`, and whose parent lives
+            // for `'p`.
+            object: unsafe { ChildObject::new(wdf_memory.cast()) },
+        })
+    }
+
+    /// Returns the raw `WDFMEMORY` handle wrapped by this [`Memory`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFMEMORY {
+        self.wdf_memory
+    }
+
+    /// Returns the length of the buffer, in bytes
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns whether the buffer is empty, which it never is
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the contents of the buffer
+    #[must_use]
+    pub const fn as_slice(&self) -> &[u8] {
+        // SAFETY: The buffer is `length` bytes, valid until the memory object is
+        // deleted when `self` is dropped, and is only mutated through `&mut self`.
+        unsafe { core::slice::from_raw_parts(self.buffer.as_ptr(), self.length) }
+    }
+
+    /// Returns the contents of the buffer, mutably
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: The buffer is `length` bytes, valid until the memory object is
+        // deleted when `self` is dropped, and `self` is borrowed mutably.
+        unsafe { core::slice::from_raw_parts_mut(self.buffer.as_ptr(), self.length) }
+    }
+
+    /// Copy the bytes at `offset` bytes into the buffer into `destination`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes are not entirely within the buffer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycopytobuffer#return-value)
+    pub fn copy_to_buffer(&self, offset: usize, destination: &mut [u8]) -> Result<(), NTSTATUS> {
+        self.check_range(offset, destination.len())?;
+
+        let nt_status;
+        // SAFETY: `wdf_memory` is a private member of `Memory`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state.
+        // `destination` is mutably borrowed for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfMemoryCopyToBuffer,
+                self.wdf_memory,
+                offset,
+                destination.as_mut_ptr().cast(),
+                destination.len(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Copy the bytes of `source` to `offset` bytes into the buffer
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes are not entirely within the buffer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFMemory Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfmemory/nf-wdfmemory-wdfmemorycopyfrombuffer#return-value)
+    pub fn copy_from_buffer(&mut self, offset: usize, source: &[u8]) -> Result<(), NTSTATUS> {
+        self.check_range(offset, source.len())?;
+
+        let nt_status;
+        // SAFETY: `wdf_memory` is a private member of `Memory`, originally created by
+        // WDF, and this module guarantees that it is always in a valid state. `source`
+        // is only read from, and `self` is borrowed mutably for the duration of the
+        // call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfMemoryCopyFromBuffer,
+                self.wdf_memory,
+                offset,
+                source.as_ptr().cast_mut().cast(),
+                source.len(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Leave the WDF memory object to be deleted along with its parent, rather
+    /// than when the [`Memory`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// The returned [`Memory`] must not be used after its parent is deleted,
+    /// nor, if it was created from a buffer, after the buffer is dropped.
+    /// Storing it in the context of its parent satisfies the first, as long as
+    /// it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> Memory<'static> {
+        let Self {
+            wdf_memory,
+            buffer,
+            length,
+            object,
+        } = self;
+        core::mem::forget(object);
+        Memory {
+            wdf_memory,
+            buffer,
+            length,
+            object: ChildObject::parent_owned(wdf_memory.cast()),
+        }
+    }
+
+    /// Check that the `length` bytes at `offset` bytes into the buffer are
+    /// entirely within it, which WDF would otherwise break into the debugger
+    /// for
+    fn check_range(&self, offset: usize, length: usize) -> Result<(), NTSTATUS> {
+        if offset
+            .checked_add(length)
+            .is_some_and(|end| end <= self.length)
+        {
+            Ok(())
+        } else {
+            Err(STATUS_BUFFER_TOO_SMALL)
+        }
+    }
+}
+
+impl AsRef<[u8]> for Memory<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsMut<[u8]> for Memory<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
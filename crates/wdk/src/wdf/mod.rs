@@ -1,7 +1,65 @@
 //! Safe abstractions over WDF APIs
 
+mod attributes;
+mod child;
+mod childlist;
+mod collection;
+mod commonbuffer;
+mod config;
+mod context;
+mod device;
+mod dma;
+mod dpc;
+mod driver;
+mod fileobject;
+mod interface;
+mod interrupt;
+mod iotarget;
+mod memory;
+pub(crate) mod name;
+mod object;
+mod pdo;
+mod power;
+mod property;
+mod queue;
+mod registry;
+mod request;
+mod resource;
+mod security;
 mod spinlock;
+#[cfg(feature = "alloc")]
+mod string;
 mod timer;
+mod waitlock;
+mod workitem;
 
+pub use attributes::*;
+pub use childlist::*;
+pub use collection::*;
+pub use commonbuffer::*;
+pub use config::*;
+pub use device::*;
+pub use dma::*;
+pub use dpc::*;
+pub use driver::*;
+pub use fileobject::*;
+pub use interface::*;
+pub use interrupt::*;
+pub use iotarget::*;
+pub use memory::*;
+pub use object::*;
+pub use pdo::*;
+pub use power::*;
+pub use property::*;
+pub use queue::*;
+pub use registry::*;
+pub use request::*;
+pub use resource::*;
+pub use security::*;
 pub use spinlock::*;
+#[cfg(feature = "alloc")]
+pub use string::*;
 pub use timer::*;
+pub use waitlock::*;
+pub use wdk_sys::macros::{FromRegistry, WdfObjectContext};
+pub use workitem::*;
@@ -0,0 +1,81 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+//! Crate-internal helpers for building the `UNICODE_STRING`s passed to WDF and
+//! the kernel, such as the names of devices, symbolic links and registry keys,
+//! from Rust strings.
+
+use wdk_sys::{NTSTATUS, STATUS_NAME_TOO_LONG, STATUS_OBJECT_NAME_INVALID, UNICODE_STRING};
+
+/// The maximum length of a [`UnicodeBuffer`], in UTF-16 code units
+const MAX_LENGTH: usize = 512;
+
+/// A string encoded as UTF-16 in a fixed-size buffer, so that building it
+/// never allocates. The buffer always has room for a null terminator after
+/// the string.
+pub struct UnicodeBuffer {
+    buffer: [u16; MAX_LENGTH + 1],
+    length: usize,
+}
+
+impl UnicodeBuffer {
+    /// Encode `string` as UTF-16
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `string` is longer than
+    /// [`MAX_LENGTH`] UTF-16 code units. The error variant will contain a
+    /// [`NTSTATUS`] of the failure.
+    pub fn new(string: &str) -> Result<Self, NTSTATUS> {
+        Self::concat(&[string])
+    }
+
+    /// Construct the object manager path of `name` in the directory
+    /// `directory` (ex. `\DosDevices\`)
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is empty or contains a
+    /// `\`, or if the path is longer than [`MAX_LENGTH`] UTF-16 code units.
+    /// The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn object_path(directory: &str, name: &str) -> Result<Self, NTSTATUS> {
+        if name.is_empty() || name.contains('\\') {
+            return Err(STATUS_OBJECT_NAME_INVALID);
+        }
+        Self::concat(&[directory, name])
+    }
+
+    /// Encode the concatenation of `strings` as UTF-16
+    fn concat(strings: &[&str]) -> Result<Self, NTSTATUS> {
+        let mut unicode_buffer = Self {
+            buffer: [0; MAX_LENGTH + 1],
+            length: 0,
+        };
+        for code_unit in strings.iter().flat_map(|string| string.encode_utf16()) {
+            if unicode_buffer.length == MAX_LENGTH {
+                return Err(STATUS_NAME_TOO_LONG);
+            }
+            unicode_buffer.buffer[unicode_buffer.length] = code_unit;
+            unicode_buffer.length += 1;
+        }
+        Ok(unicode_buffer)
+    }
+
+    /// Returns a `UNICODE_STRING` that borrows the string, which is only valid
+    /// while `self` is neither moved nor dropped
+    pub fn as_unicode_string(&self) -> UNICODE_STRING {
+        // `MAX_LENGTH` code units is much smaller than `USHORT::MAX` bytes
+        #[allow(clippy::cast_possible_truncation)]
+        let length = (self.length * core::mem::size_of::<u16>()) as u16;
+        UNICODE_STRING {
+            Length: length,
+            MaximumLength: length,
+            Buffer: self.buffer.as_ptr().cast_mut(),
+        }
+    }
+
+    /// Returns the string followed by its null terminator
+    pub fn as_null_terminated(&self) -> &[u16] {
+        &self.buffer[..=self.length]
+    }
+}
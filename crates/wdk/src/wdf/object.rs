@@ -0,0 +1,234 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{ffi::CStr, ptr::NonNull};
+
+use wdk_sys::{
+    macros,
+    MEMORY_ALLOCATION_ALIGNMENT,
+    NTSTATUS,
+    PVOID,
+    STATUS_INVALID_DEVICE_STATE,
+    STATUS_OBJECT_NAME_EXISTS,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFOBJECT,
+    WDF_OBJECT_ATTRIBUTES,
+    WDF_OBJECT_CONTEXT_TYPE_INFO,
+};
+
+use super::context::{attach_cleanup_callback, attach_destroy_callback, object_attributes};
+
+// `WDF_OBJECT_CONTEXT_TYPE_INFO` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const CONTEXT_TYPE_INFO_SIZE: ULONG = core::mem::size_of::<WDF_OBJECT_CONTEXT_TYPE_INFO>() as ULONG;
+
+/// A wrapper of a handle to a WDF object.
+///
+/// # Safety
+///
+/// [`WdfObject::as_object_handle()`] must return a valid handle to a WDF
+/// object, which must remain valid for as long as `self` is borrowed.
+pub unsafe trait WdfObject {
+    /// Returns the handle of the wrapped WDF object, as a `WDFOBJECT`
+    fn as_object_handle(&self) -> WDFOBJECT;
+
+    /// Register `callback` to be called from the object's
+    /// `EvtCleanupCallback`, when the object is being deleted
+    ///
+    /// The framework cleans up an object at `IRQL` <= `DISPATCH_LEVEL`, or at
+    /// `IRQL` = `PASSIVE_LEVEL` if its execution level is
+    /// [`ExecutionLevel::Passive`](super::ExecutionLevel::Passive). Cleanup
+    /// callbacks are the place to release resources that the object's event
+    /// callbacks use, since none of them run after cleanup. Each object can
+    /// have one cleanup callback registered this way.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a cleanup callback is already registered on the object, or if WDF fails to allocate storage for `callback`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WdfObjectAllocateContext Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfobject/nf-wdfobject-wdfobjectallocatecontext#return-value)
+    fn on_cleanup<F>(&self, callback: F) -> Result<(), NTSTATUS>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // SAFETY: The contract of `WdfObject` guarantees that the handle is valid.
+        unsafe { attach_cleanup_callback(self.as_object_handle(), callback) }
+    }
+
+    /// Register `callback` to be called from the object's
+    /// `EvtDestroyCallback`, once the object's reference count reaches zero
+    /// after it is deleted
+    ///
+    /// The framework destroys an object at `IRQL` <= `DISPATCH_LEVEL`. Each
+    /// object can have one destroy callback registered this way.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a destroy callback is already registered on the object, or if WDF fails to allocate storage for `callback`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WdfObjectAllocateContext Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfobject/nf-wdfobject-wdfobjectallocatecontext#return-value)
+    fn on_destroy<F>(&self, callback: F) -> Result<(), NTSTATUS>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // SAFETY: The contract of `WdfObject` guarantees that the handle is valid.
+        unsafe { attach_destroy_callback(self.as_object_handle(), callback) }
+    }
+}
+
+/// The type information of a WDF object context, equivalent to the
+/// `WDF_OBJECT_CONTEXT_TYPE_INFO` declared by `WDF_DECLARE_CONTEXT_TYPE`.
+///
+/// [`ContextTypeInfo`]s are declared as `static`s by
+/// [`#[derive(WdfObjectContext)]`](macro@crate::wdf::WdfObjectContext), since
+/// the framework identifies context types by the address of their type
+/// information.
+#[repr(transparent)]
+pub struct ContextTypeInfo(WDF_OBJECT_CONTEXT_TYPE_INFO);
+
+// SAFETY: The type info is immutable, and its pointers only refer to immutable
+// statics.
+unsafe impl Sync for ContextTypeInfo {}
+
+impl ContextTypeInfo {
+    /// Construct the type information of a context of type `T`
+    ///
+    /// `this` must be the `static` that is initialized with the result.
+    #[must_use]
+    pub const fn new<T>(name: &'static CStr, this: &'static Self) -> Self {
+        Self::with_size(name, core::mem::size_of::<T>(), this)
+    }
+
+    /// Construct the type information of a context of `size` bytes
+    pub(crate) const fn with_size(name: &'static CStr, size: usize, this: &'static Self) -> Self {
+        Self(WDF_OBJECT_CONTEXT_TYPE_INFO {
+            Size: CONTEXT_TYPE_INFO_SIZE,
+            ContextName: name.as_ptr(),
+            ContextSize: size,
+            UniqueType: core::ptr::addr_of!(this.0),
+            EvtDriverGetUniqueContextType: None,
+        })
+    }
+
+    /// Returns a pointer to the raw `WDF_OBJECT_CONTEXT_TYPE_INFO`
+    #[must_use]
+    pub const fn as_raw(&'static self) -> &'static WDF_OBJECT_CONTEXT_TYPE_INFO {
+        &self.0
+    }
+}
+
+/// A type that can be stored in the context space of WDF objects.
+///
+/// This is implemented with
+/// [`#[derive(WdfObjectContext)]`](macro@crate::wdf::WdfObjectContext), which
+/// replaces the `WDF_DECLARE_CONTEXT_TYPE_WITH_NAME` C macro. A context is
+/// attached to an object with [`ObjectContext::attach()`], and accessed with
+/// [`get_context()`]. The framework runs the context's [`Drop`]
+/// implementation from the object's `EvtCleanupCallback`.
+///
+/// # Safety
+///
+/// [`ObjectContext::type_info()`] must always return a reference to the same
+/// `static` [`ContextTypeInfo`], which must have been constructed with
+/// [`ContextTypeInfo::new::<Self>()`](ContextTypeInfo::new), and must not be
+/// used by any other type.
+pub unsafe trait ObjectContext: Sized + Send + Sync + 'static {
+    /// Returns the type information of this context type
+    fn type_info() -> &'static ContextTypeInfo;
+
+    /// Store `value` in a new context of `object`
+    ///
+    /// `value` is dropped from `object`'s `EvtCleanupCallback`. This must be
+    /// called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `object` already has a context of this type, or if WDF fails to allocate the context. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WdfObjectAllocateContext Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfobject/nf-wdfobject-wdfobjectallocatecontext#return-value)
+    fn attach(object: &impl WdfObject, value: Self) -> Result<(), NTSTATUS> {
+        const {
+            assert!(
+                core::mem::align_of::<Self>() <= MEMORY_ALLOCATION_ALIGNMENT as usize,
+                "WDF object contexts must not be over-aligned"
+            );
+        }
+
+        let mut attributes = WDF_OBJECT_ATTRIBUTES {
+            EvtCleanupCallback: Some(drop_context::<Self>),
+            ContextTypeInfo: Self::type_info().as_raw(),
+            ..object_attributes(core::ptr::null_mut())
+        };
+        let mut context: PVOID = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The contract of `WdfObject` guarantees that the handle is valid.
+        // `attributes` and `context` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfObjectAllocateContext,
+                object.as_object_handle(),
+                &mut attributes,
+                &mut context,
+            );
+        }
+        match nt_status {
+            STATUS_SUCCESS => {}
+            // WDF returns the existing context, which holds a live `Self` that may be
+            // borrowed, and must not be overwritten
+            STATUS_OBJECT_NAME_EXISTS => return Err(STATUS_INVALID_DEVICE_STATE),
+            _ => return Err(nt_status),
+        }
+
+        // SAFETY: WDF allocated a context of `size_of::<Self>()` bytes, aligned to
+        // `MEMORY_ALLOCATION_ALIGNMENT`, which is at least the alignment of `Self`.
+        // The cleanup callback cannot run before this returns, since `object` is
+        // borrowed.
+        unsafe {
+            context.cast::<Self>().write(value);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the context of type `T` of `object`, or [`None`] if it does not
+/// have one
+///
+/// This may be called at any `IRQL`.
+pub fn get_context<T: ObjectContext>(object: &impl WdfObject) -> Option<&T> {
+    let context: PVOID;
+    // SAFETY: The contract of `WdfObject` guarantees that the handle is valid.
+    unsafe {
+        context = macros::call_unsafe_wdf_function_binding!(
+            WdfObjectGetTypedContextWorker,
+            object.as_object_handle(),
+            T::type_info().as_raw(),
+        );
+    }
+    let context = NonNull::new(context.cast::<T>())?;
+    // SAFETY: Contexts of type `T` are only allocated by `ObjectContext::attach`,
+    // which initializes them before returning. The context is only dropped once the
+    // object is cleaned up, after which the contract of `WdfObject` guarantees that
+    // it cannot be borrowed.
+    Some(unsafe { context.as_ref() })
+}
+
+/// The `EvtCleanupCallback` of contexts attached by [`ObjectContext::attach()`]
+/// or allocated with
+/// [`ObjectAttributes::context_type()`](super::ObjectAttributes::context_type)
+///
+/// # Safety
+///
+/// `object` must be a WDF object that has an initialized context of type `T`.
+pub(super) unsafe extern "C" fn drop_context<T: ObjectContext>(object: WDFOBJECT) {
+    let context: PVOID;
+    // SAFETY: The framework only calls this with the object being cleaned up, which
+    // remains valid for the duration of the call.
+    unsafe {
+        context = macros::call_unsafe_wdf_function_binding!(
+            WdfObjectGetTypedContextWorker,
+            object,
+            T::type_info().as_raw(),
+        );
+    }
+    // SAFETY: The caller guarantees that a `T` was attached to `object`. The object
+    // is being cleaned up, so its context is not accessed after this.
+    unsafe {
+        core::ptr::drop_in_place(context.cast::<T>());
+    }
+}
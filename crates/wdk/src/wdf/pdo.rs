@@ -0,0 +1,370 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ops::{Deref, DerefMut};
+
+use wdk_sys::{macros, GUID, LCID, NTSTATUS, STATUS_INSUFFICIENT_RESOURCES};
+
+use super::{name::UnicodeBuffer, Device, DeviceBuilder, DeviceInit};
+use crate::nt_success;
+
+/// Initialization state of a child device that a bus driver enumerates
+/// statically, allocated with [`PdoInit::allocate()`].
+///
+/// A [`PdoInit`] dereferences to the [`DeviceInit`] that the child device is
+/// configured and created from, typically with a [`PdoBuilder`]. If it is
+/// dropped before the device is created, the `WDFDEVICE_INIT` is freed.
+///
+/// ```ignore
+/// let mut pdo_init = PdoInit::allocate(&device)?;
+/// let child = PdoBuilder::new(&mut pdo_init)
+///     .device_id("Bus\\Child")
+///     .instance_id("0")
+///     .create()?;
+/// device.add_static_child(&child)?;
+/// ```
+pub struct PdoInit {
+    device_init: DeviceInit,
+}
+
+impl PdoInit {
+    /// Try to allocate the initialization state of a child device of `parent`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to allocate the `WDFDEVICE_INIT`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFPdo Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitallocate#return-value)
+    pub fn allocate(parent: &Device) -> Result<Self, NTSTATUS> {
+        let device_init;
+        // SAFETY: `parent` is a valid device handle.
+        unsafe {
+            device_init =
+                macros::call_unsafe_wdf_function_binding!(WdfPdoInitAllocate, parent.as_raw());
+        }
+        if device_init.is_null() {
+            return Err(STATUS_INSUFFICIENT_RESOURCES);
+        }
+
+        Ok(Self {
+            // SAFETY: `device_init` was just allocated, and is owned by the `PdoInit`
+            // until the device is created or it is freed.
+            device_init: unsafe { DeviceInit::from_raw(device_init) },
+        })
+    }
+}
+
+impl Deref for PdoInit {
+    type Target = DeviceInit;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device_init
+    }
+}
+
+impl DerefMut for PdoInit {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.device_init
+    }
+}
+
+impl Drop for PdoInit {
+    fn drop(&mut self) {
+        // `WdfDeviceCreate` sets the `WDFDEVICE_INIT` to null once it creates the
+        // device, which then owns it
+        if self.device_init.as_raw().is_null() {
+            return;
+        }
+
+        // SAFETY: The `WDFDEVICE_INIT` was allocated by `WdfPdoInitAllocate`, and no
+        // device was created from it.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfDeviceInitFree, self.device_init.as_raw());
+        }
+    }
+}
+
+/// Builder for the physical device object (PDO) of a child device enumerated
+/// by a bus driver.
+///
+/// A [`PdoBuilder`] configures either the [`DeviceInit`] passed to
+/// [`ChildListCallbacks::create_device()`](super::ChildListCallbacks::create_device),
+/// or a [`PdoInit`] allocated for a static child, with the identifiers that
+/// Plug and Play uses to find the child's drivers. Every child needs at least
+/// a device ID. The child device is then created with
+/// [`PdoBuilder::create()`], or further configured with the [`DeviceBuilder`]
+/// returned by [`PdoBuilder::device_builder()`].
+///
+/// ```ignore
+/// let child = PdoBuilder::new(child_init)
+///     .device_id("Bus\\Child")
+///     .hardware_ids(&["Bus\\Child"])
+///     .compatible_ids(&["Bus\\GenericChild"])
+///     .instance_id("0")
+///     .device_text("Sample Child Device", "Sample Bus", 0x409)
+///     .create()?;
+/// ```
+#[must_use]
+pub struct PdoBuilder<'a> {
+    device_init: &'a mut DeviceInit,
+    device_id: Option<&'a str>,
+    hardware_ids: &'a [&'a str],
+    compatible_ids: &'a [&'a str],
+    instance_id: Option<&'a str>,
+    device_text: Option<DeviceText<'a>>,
+    raw_device_class: Option<&'a GUID>,
+}
+
+/// The text that describes a child device to users, in one locale
+struct DeviceText<'a> {
+    description: &'a str,
+    location: &'a str,
+    locale: LCID,
+}
+
+impl<'a> PdoBuilder<'a> {
+    /// Construct a [`PdoBuilder`] that configures `device_init`, which must be
+    /// the initialization state of a child device
+    pub fn new(device_init: &'a mut DeviceInit) -> Self {
+        Self {
+            device_init,
+            device_id: None,
+            hardware_ids: &[],
+            compatible_ids: &[],
+            instance_id: None,
+            device_text: None,
+            raw_device_class: None,
+        }
+    }
+
+    /// Set the device ID of the child (ex. `Bus\Child`)
+    pub const fn device_id(mut self, device_id: &'a str) -> Self {
+        self.device_id = Some(device_id);
+        self
+    }
+
+    /// Set the hardware IDs of the child, from the most to the least specific
+    pub const fn hardware_ids(mut self, hardware_ids: &'a [&'a str]) -> Self {
+        self.hardware_ids = hardware_ids;
+        self
+    }
+
+    /// Set the compatible IDs of the child, from the most to the least
+    /// specific
+    pub const fn compatible_ids(mut self, compatible_ids: &'a [&'a str]) -> Self {
+        self.compatible_ids = compatible_ids;
+        self
+    }
+
+    /// Set the instance ID of the child, which distinguishes it from the other
+    /// children of the bus with the same device ID
+    pub const fn instance_id(mut self, instance_id: &'a str) -> Self {
+        self.instance_id = Some(instance_id);
+        self
+    }
+
+    /// Set the description and location of the child shown to users, in the
+    /// locale `locale` (ex. `0x409` for English (United States)), which also
+    /// becomes the default locale of the child
+    pub const fn device_text(
+        mut self,
+        description: &'a str,
+        location: &'a str,
+        locale: LCID,
+    ) -> Self {
+        self.device_text = Some(DeviceText {
+            description,
+            location,
+            locale,
+        });
+        self
+    }
+
+    /// Make the child a raw device of the device setup class
+    /// `device_class`, which is started without a function driver
+    pub const fn raw_device(mut self, device_class: &'a GUID) -> Self {
+        self.raw_device_class = Some(device_class);
+        self
+    }
+
+    /// Try to create the child device
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the child's identifiers or text cannot be assigned, or if WDF fails to contruct the device. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicecreate#return-value)
+    pub fn create(self) -> Result<Device, NTSTATUS> {
+        self.device_builder()?.create()
+    }
+
+    /// Try to assign the child's identifiers and text, and return a
+    /// [`DeviceBuilder`] that further configures and then creates the child
+    /// device
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an identifier is longer than 512 characters, or if WDF fails to allocate storage for the identifiers or text. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFPdo Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfpdo/nf-wdfpdo-wdfpdoinitassigndeviceid#return-value)
+    pub fn device_builder(self) -> Result<DeviceBuilder<'a>, NTSTATUS> {
+        if let Some(device_id) = self.device_id {
+            let device_id = UnicodeBuffer::new(device_id)?;
+            let device_id = device_id.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet. `device_id` borrows a buffer that outlives the
+            // call, which copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAssignDeviceID,
+                    self.device_init.as_raw(),
+                    &device_id
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        for hardware_id in self.hardware_ids {
+            let hardware_id = UnicodeBuffer::new(hardware_id)?;
+            let hardware_id = hardware_id.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet. `hardware_id` borrows a buffer that outlives the
+            // call, which copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAddHardwareID,
+                    self.device_init.as_raw(),
+                    &hardware_id
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        for compatible_id in self.compatible_ids {
+            let compatible_id = UnicodeBuffer::new(compatible_id)?;
+            let compatible_id = compatible_id.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet. `compatible_id` borrows a buffer that outlives
+            // the call, which copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAddCompatibleID,
+                    self.device_init.as_raw(),
+                    &compatible_id
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        if let Some(instance_id) = self.instance_id {
+            let instance_id = UnicodeBuffer::new(instance_id)?;
+            let instance_id = instance_id.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet. `instance_id` borrows a buffer that outlives the
+            // call, which copies it.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAssignInstanceID,
+                    self.device_init.as_raw(),
+                    &instance_id
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        if let Some(device_text) = self.device_text {
+            let description = UnicodeBuffer::new(device_text.description)?;
+            let description = description.as_unicode_string();
+            let location = UnicodeBuffer::new(device_text.location)?;
+            let location = location.as_unicode_string();
+
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet. `description` and `location` borrow buffers that
+            // outlive the call, which copies them.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAddDeviceText,
+                    self.device_init.as_raw(),
+                    &description,
+                    &location,
+                    device_text.locale
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet.
+            unsafe {
+                macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitSetDefaultLocale,
+                    self.device_init.as_raw(),
+                    device_text.locale
+                );
+            }
+        }
+
+        if let Some(device_class) = self.raw_device_class {
+            let nt_status;
+            // SAFETY: `device_init` is a valid child `WDFDEVICE_INIT`, since the device
+            // has not been created yet, and `device_class` is valid for the duration of
+            // the call.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfPdoInitAssignRawDevice,
+                    self.device_init.as_raw(),
+                    device_class
+                );
+            }
+            if !nt_success(nt_status) {
+                return Err(nt_status);
+            }
+        }
+
+        Ok(DeviceBuilder::new(self.device_init))
+    }
+}
+
+impl Device {
+    /// Add `child`, a device created from a [`PdoInit`] of this device, to
+    /// the children that this device enumerates statically
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `child` is already a child of this device, or if WDF fails to allocate storage for it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFFdo Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdffdo/nf-wdffdo-wdffdoaddstaticchild#return-value)
+    pub fn add_static_child(&self, child: &Self) -> Result<(), NTSTATUS> {
+        let nt_status;
+        // SAFETY: `self` and `child` are valid device handles.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfFdoAddStaticChild,
+                self.as_raw(),
+                child.as_raw()
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,649 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use wdk_sys::{
+    macros,
+    _DEVICE_POWER_STATE,
+    _WDF_POWER_POLICY_IDLE_TIMEOUT_TYPE,
+    _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES,
+    _WDF_POWER_POLICY_S0_IDLE_USER_CONTROL,
+    _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL,
+    _WDF_TRI_STATE,
+    BOOLEAN,
+    DEVICE_POWER_STATE,
+    NTSTATUS,
+    STATUS_SUCCESS,
+    ULONG,
+    WDFDEVICE,
+    WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS,
+    WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS,
+    WDF_POWER_POLICY_EVENT_CALLBACKS,
+    WDF_POWER_POLICY_S0_IDLE_CAPABILITIES,
+};
+
+use super::{
+    context::{attach_closure_in, closure_in, ClosureSlot},
+    device::tri_state,
+    ContextTypeInfo,
+    Device,
+};
+use crate::nt_success;
+
+// `WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS` is much smaller than `ULONG::MAX`
+// bytes
+#[allow(clippy::cast_possible_truncation)]
+const DEVICE_POWER_POLICY_IDLE_SETTINGS_SIZE: ULONG =
+    core::mem::size_of::<WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS>() as ULONG;
+
+// `WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS` is much smaller than `ULONG::MAX`
+// bytes
+#[allow(clippy::cast_possible_truncation)]
+const DEVICE_POWER_POLICY_WAKE_SETTINGS_SIZE: ULONG =
+    core::mem::size_of::<WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS>() as ULONG;
+
+// `WDF_POWER_POLICY_EVENT_CALLBACKS` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const POWER_POLICY_EVENT_CALLBACKS_SIZE: ULONG =
+    core::mem::size_of::<WDF_POWER_POLICY_EVENT_CALLBACKS>() as ULONG;
+
+/// The context type of the [`WakeCallbacks`] attached to devices
+static WAKE_CALLBACKS_CONTEXT_TYPE_INFO: ContextTypeInfo = ContextTypeInfo::with_size(
+    c"wdk::wdf::WakeCallbacks",
+    1,
+    &WAKE_CALLBACKS_CONTEXT_TYPE_INFO,
+);
+
+/// A low-power state that a device enters while it is idle or armed for wake
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DxState {
+    /// The D1 low-power state
+    D1,
+    /// The D2 low-power state
+    D2,
+    /// The D3 low-power state
+    D3,
+    /// The deepest low-power state from which the device can still wake the
+    /// system or itself, as reported by the bus driver
+    #[default]
+    Deepest,
+}
+
+impl DxState {
+    pub(super) const fn as_raw(self) -> DEVICE_POWER_STATE {
+        match self {
+            Self::D1 => _DEVICE_POWER_STATE::PowerDeviceD1,
+            Self::D2 => _DEVICE_POWER_STATE::PowerDeviceD2,
+            Self::D3 => _DEVICE_POWER_STATE::PowerDeviceD3,
+            Self::Deepest => _DEVICE_POWER_STATE::PowerDeviceMaximum,
+        }
+    }
+}
+
+/// Whether a device can wake itself from the low-power state it enters while
+/// the system is working (S0) and the device is idle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleCapabilities {
+    /// The device cannot wake itself, so the framework only powers it back up
+    /// once the driver has work for it
+    CannotWakeFromS0,
+    /// The device can wake itself, for example when it receives data
+    CanWakeFromS0,
+    /// The device is a USB device that can wake itself with USB selective
+    /// suspend
+    UsbSelectiveSuspend,
+}
+
+impl IdleCapabilities {
+    const fn as_raw(self) -> WDF_POWER_POLICY_S0_IDLE_CAPABILITIES {
+        match self {
+            Self::CannotWakeFromS0 => _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleCannotWakeFromS0,
+            Self::CanWakeFromS0 => _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleCanWakeFromS0,
+            Self::UsbSelectiveSuspend => {
+                _WDF_POWER_POLICY_S0_IDLE_CAPABILITIES::IdleUsbSelectiveSuspend
+            }
+        }
+    }
+}
+
+/// Settings for powering down a [`Device`] while it is idle and the system is
+/// working (S0), assigned with [`Device::assign_s0_idle_settings()`].
+///
+/// Settings that are not set use the framework's defaults. In particular,
+/// devices power down after 5 seconds of idleness, to the deepest state they
+/// can wake from, or to D3 if they cannot wake themselves.
+///
+/// ```ignore
+/// device.assign_s0_idle_settings(
+///     IdleSettings::new(IdleCapabilities::CanWakeFromS0)
+///         .idle_timeout(10_000)
+///         .user_control(false),
+/// )?;
+/// ```
+#[must_use]
+#[derive(Clone, Copy, Debug)]
+pub struct IdleSettings {
+    capabilities: IdleCapabilities,
+    dx_state: Option<DxState>,
+    idle_timeout: Option<ULONG>,
+    user_control: Option<bool>,
+    enabled: Option<bool>,
+    power_up_on_system_wake: Option<bool>,
+}
+
+impl IdleSettings {
+    /// Construct [`IdleSettings`] for a device with the idle capabilities
+    /// `capabilities`
+    pub const fn new(capabilities: IdleCapabilities) -> Self {
+        Self {
+            capabilities,
+            dx_state: None,
+            idle_timeout: None,
+            user_control: None,
+            enabled: None,
+            power_up_on_system_wake: None,
+        }
+    }
+
+    /// Set the low-power state that the device enters while it is idle
+    pub const fn dx_state(mut self, dx_state: DxState) -> Self {
+        self.dx_state = Some(dx_state);
+        self
+    }
+
+    /// Set how long the device must be idle before it is powered down, in
+    /// milliseconds
+    pub const fn idle_timeout(mut self, idle_timeout: ULONG) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Set whether users can enable and disable powering down the device
+    /// while it is idle, in the device's Device Manager property page
+    pub const fn user_control(mut self, user_control: bool) -> Self {
+        self.user_control = Some(user_control);
+        self
+    }
+
+    /// Set whether the device is powered down while it is idle. Unless users
+    /// were allowed to change it, this is enabled by default.
+    pub const fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Set whether the framework powers the device up when the system wakes
+    /// from a sleep state, even if the device was idle before it slept
+    pub const fn power_up_on_system_wake(mut self, power_up_on_system_wake: bool) -> Self {
+        self.power_up_on_system_wake = Some(power_up_on_system_wake);
+        self
+    }
+
+    /// Returns the `WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS` of these settings,
+    /// initialized the same way as by
+    /// `WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS_INIT`
+    const fn to_raw(self) -> WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS {
+        let dx_state = match (self.dx_state, self.capabilities) {
+            (Some(dx_state), _) => dx_state,
+            (None, IdleCapabilities::CannotWakeFromS0) => DxState::D3,
+            (None, _) => DxState::Deepest,
+        };
+
+        WDF_DEVICE_POWER_POLICY_IDLE_SETTINGS {
+            Size: DEVICE_POWER_POLICY_IDLE_SETTINGS_SIZE,
+            IdleCaps: self.capabilities.as_raw(),
+            DxState: dx_state.as_raw(),
+            // `IdleTimeoutDefaultValue` is 0
+            IdleTimeout: match self.idle_timeout {
+                Some(idle_timeout) => idle_timeout,
+                None => 0,
+            },
+            UserControlOfIdleSettings: match self.user_control {
+                Some(false) => _WDF_POWER_POLICY_S0_IDLE_USER_CONTROL::IdleDoNotAllowUserControl,
+                Some(true) | None => _WDF_POWER_POLICY_S0_IDLE_USER_CONTROL::IdleAllowUserControl,
+            },
+            Enabled: tri_state(self.enabled),
+            PowerUpIdleDeviceOnSystemWake: tri_state(self.power_up_on_system_wake),
+            IdleTimeoutType: _WDF_POWER_POLICY_IDLE_TIMEOUT_TYPE::DriverManagedIdleTimeout,
+            ExcludeD3Cold: _WDF_TRI_STATE::WdfUseDefault,
+        }
+    }
+}
+
+/// Settings for arming a [`Device`] to wake the system from a sleep state
+/// (Sx), assigned with [`Device::assign_sx_wake_settings()`].
+///
+/// Settings that are not set use the framework's defaults, which arm the
+/// device from the deepest state it can wake the system from, and let users
+/// control whether it does.
+///
+/// ```ignore
+/// device.assign_sx_wake_settings(WakeSettings::new().dx_state(DxState::D2))?;
+/// ```
+#[must_use]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WakeSettings {
+    dx_state: Option<DxState>,
+    user_control: Option<bool>,
+    enabled: Option<bool>,
+    arm_for_wake_if_children_are_armed: bool,
+    indicate_child_wake_on_parent_wake: bool,
+}
+
+impl WakeSettings {
+    /// Construct [`WakeSettings`] that use the framework's default for every
+    /// setting
+    pub const fn new() -> Self {
+        Self {
+            dx_state: None,
+            user_control: None,
+            enabled: None,
+            arm_for_wake_if_children_are_armed: false,
+            indicate_child_wake_on_parent_wake: false,
+        }
+    }
+
+    /// Set the low-power state that the device enters when the system sleeps,
+    /// from which it wakes the system
+    pub const fn dx_state(mut self, dx_state: DxState) -> Self {
+        self.dx_state = Some(dx_state);
+        self
+    }
+
+    /// Set whether users can enable and disable waking the system with the
+    /// device, in the device's Device Manager property page
+    pub const fn user_control(mut self, user_control: bool) -> Self {
+        self.user_control = Some(user_control);
+        self
+    }
+
+    /// Set whether the device is armed to wake the system. Unless users were
+    /// allowed to change it, this is enabled by default.
+    pub const fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Set whether the device is armed to wake the system whenever one of its
+    /// children is, regardless of whether the device itself is enabled to wake
+    /// the system. This is only supported for bus drivers.
+    pub const fn arm_for_wake_if_children_are_armed(mut self, arm: bool) -> Self {
+        self.arm_for_wake_if_children_are_armed = arm;
+        self
+    }
+
+    /// Set whether the children of the device that are armed for wake are
+    /// notified that they woke the system when the device does. This is only
+    /// supported for bus drivers.
+    pub const fn indicate_child_wake_on_parent_wake(mut self, indicate: bool) -> Self {
+        self.indicate_child_wake_on_parent_wake = indicate;
+        self
+    }
+
+    /// Returns the `WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS` of these settings,
+    /// initialized the same way as by
+    /// `WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS_INIT`
+    const fn to_raw(self) -> WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS {
+        WDF_DEVICE_POWER_POLICY_WAKE_SETTINGS {
+            Size: DEVICE_POWER_POLICY_WAKE_SETTINGS_SIZE,
+            DxState: match self.dx_state {
+                Some(dx_state) => dx_state.as_raw(),
+                None => DxState::Deepest.as_raw(),
+            },
+            UserControlOfWakeSettings: match self.user_control {
+                Some(false) => _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL::WakeDoNotAllowUserControl,
+                Some(true) | None => _WDF_POWER_POLICY_SX_WAKE_USER_CONTROL::WakeAllowUserControl,
+            },
+            Enabled: tri_state(self.enabled),
+            ArmForWakeIfChildrenAreArmedForWake: self.arm_for_wake_if_children_are_armed as BOOLEAN,
+            IndicateChildWakeOnParentWake: self.indicate_child_wake_on_parent_wake as BOOLEAN,
+        }
+    }
+}
+
+/// Callbacks for arming a [`Device`] to wake itself or the system, set with
+/// [`DeviceBuilder::wake_callbacks()`](super::DeviceBuilder::wake_callbacks).
+///
+/// The framework arms the device for wake according to its [`IdleSettings`]
+/// and [`WakeSettings`], and calls these callbacks to arm and disarm the
+/// device-specific wake signals, such as wake-on-LAN patterns. Every method
+/// has a default implementation that does nothing and succeeds, so
+/// implementations only need to override the callbacks they handle. All
+/// callbacks are called at `IRQL` = `PASSIVE_LEVEL`.
+pub trait WakeCallbacks: Send + Sync + 'static {
+    /// Called before the device enters a low-power state while the system is
+    /// working (S0), to arm it to wake itself (`EvtDeviceArmWakeFromS0`)
+    ///
+    /// # Errors
+    ///
+    /// Returning an error keeps the device in the working (D0) state.
+    fn arm_wake_from_s0(&self, device: &Device) -> Result<(), NTSTATUS> {
+        let _ = device;
+        Ok(())
+    }
+
+    /// Called after the device returns to the working (D0) state while the
+    /// system is working (S0), to disarm it (`EvtDeviceDisarmWakeFromS0`)
+    fn disarm_wake_from_s0(&self, device: &Device) {
+        let _ = device;
+    }
+
+    /// Called when the device woke itself while the system is working (S0),
+    /// before it is disarmed (`EvtDeviceWakeFromS0Triggered`)
+    fn wake_from_s0_triggered(&self, device: &Device) {
+        let _ = device;
+    }
+
+    /// Called before the system enters a sleep state, to arm the device to
+    /// wake the system (`EvtDeviceArmWakeFromSxWithReason`).
+    /// `device_wake_enabled` is whether waking the system with the device is
+    /// enabled, and `children_armed_for_wake` is whether any of its children
+    /// are armed for wake.
+    ///
+    /// # Errors
+    ///
+    /// Returning an error prevents the device from waking the system.
+    fn arm_wake_from_sx(
+        &self,
+        device: &Device,
+        device_wake_enabled: bool,
+        children_armed_for_wake: bool,
+    ) -> Result<(), NTSTATUS> {
+        let _ = (device, device_wake_enabled, children_armed_for_wake);
+        Ok(())
+    }
+
+    /// Called after the system returns to the working state (S0), to disarm
+    /// the device (`EvtDeviceDisarmWakeFromSx`)
+    fn disarm_wake_from_sx(&self, device: &Device) {
+        let _ = device;
+    }
+
+    /// Called when the device woke the system, before it is disarmed
+    /// (`EvtDeviceWakeFromSxTriggered`)
+    fn wake_from_sx_triggered(&self, device: &Device) {
+        let _ = device;
+    }
+}
+
+/// [`WakeCallbacks`] that do nothing, used when a [`Device`] is created
+/// without wake callbacks
+pub struct NoWakeCallbacks;
+
+impl WakeCallbacks for NoWakeCallbacks {}
+
+impl Device {
+    /// Assign the settings for powering down the device while it is idle and
+    /// the system is working (S0)
+    ///
+    /// The driver must be the power policy owner of the device. This is
+    /// usually called from `EvtDriverDeviceAdd`, after the device is created,
+    /// and can be called again later to change the settings. It must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver is not the power policy owner of the device, or if the settings are not supported by the device. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassigns0idlesettings#return-value)
+    pub fn assign_s0_idle_settings(&self, settings: IdleSettings) -> Result<(), NTSTATUS> {
+        let mut settings = settings.to_raw();
+
+        let nt_status;
+        // SAFETY: `self` is a valid device handle, and `settings` is valid for the
+        // duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceAssignS0IdleSettings,
+                self.as_raw(),
+                &mut settings
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+
+    /// Take a power reference on the device, which keeps it in the working
+    /// (D0) state until the returned [`PowerReference`] is dropped, even if
+    /// it is idle (`WdfDeviceStopIdle`)
+    ///
+    /// If the device is powered down because it is idle, the framework starts
+    /// powering it back up. With `wait_for_d0`, this waits until the device
+    /// is back in D0, and must be called at `IRQL` = `PASSIVE_LEVEL`.
+    /// Otherwise, this returns immediately, and can be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`, but the device may not be in D0 yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device is being removed, or if it fails to return to D0. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicestopidle#return-value)
+    pub fn request_power_reference(&self, wait_for_d0: bool) -> Result<PowerReference, NTSTATUS> {
+        let nt_status;
+        // SAFETY: `self` is a valid device handle.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceStopIdleNoTrack,
+                self.as_raw(),
+                BOOLEAN::from(wait_for_d0)
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(PowerReference { device: *self })
+    }
+
+    /// Assign the settings for arming the device to wake the system from a
+    /// sleep state (Sx)
+    ///
+    /// The driver must be the power policy owner of the device. This is
+    /// usually called from `EvtDriverDeviceAdd`, after the device is created,
+    /// and can be called again later to change the settings. It must be called
+    /// at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the driver is not the power policy owner of the device, or if the device cannot wake the system from `dx_state`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignsxwakesettings#return-value)
+    pub fn assign_sx_wake_settings(&self, settings: WakeSettings) -> Result<(), NTSTATUS> {
+        let mut settings = settings.to_raw();
+
+        let nt_status;
+        // SAFETY: `self` is a valid device handle, and `settings` is valid for the
+        // duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceAssignSxWakeSettings,
+                self.as_raw(),
+                &mut settings
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+}
+
+/// A power reference on a [`Device`], taken with
+/// [`Device::request_power_reference()`], which keeps the device in the
+/// working (D0) state while it is held.
+///
+/// Dropping the [`PowerReference`] releases it (`WdfDeviceResumeIdle`), which
+/// allows the device to power down again once it is idle. It can be dropped
+/// at `IRQL` <= `DISPATCH_LEVEL`.
+#[must_use]
+pub struct PowerReference {
+    device: Device,
+}
+
+impl PowerReference {
+    /// Returns the device that the power reference is held on
+    #[must_use]
+    pub const fn device(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl Drop for PowerReference {
+    fn drop(&mut self) {
+        // SAFETY: `device` is a valid device handle, and a power reference was taken
+        // on it by `Device::request_power_reference()`.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceResumeIdleNoTrack,
+                self.device.as_raw()
+            );
+        }
+    }
+}
+
+/// The [`ClosureSlot`] of the [`WakeCallbacks`] attached to devices
+struct WakeCallbacksSlot;
+
+impl ClosureSlot for WakeCallbacksSlot {
+    fn type_info() -> &'static ContextTypeInfo {
+        &WAKE_CALLBACKS_CONTEXT_TYPE_INFO
+    }
+}
+
+/// Returns the `WDF_POWER_POLICY_EVENT_CALLBACKS` of devices with `W` as their
+/// wake callbacks, initialized the same way as by
+/// `WDF_POWER_POLICY_EVENT_CALLBACKS_INIT`
+pub(super) fn power_policy_event_callbacks<W: WakeCallbacks>() -> WDF_POWER_POLICY_EVENT_CALLBACKS {
+    WDF_POWER_POLICY_EVENT_CALLBACKS {
+        Size: POWER_POLICY_EVENT_CALLBACKS_SIZE,
+        EvtDeviceArmWakeFromS0: Some(evt_device_arm_wake_from_s0::<W>),
+        EvtDeviceDisarmWakeFromS0: Some(evt_device_disarm_wake_from_s0::<W>),
+        EvtDeviceWakeFromS0Triggered: Some(evt_device_wake_from_s0_triggered::<W>),
+        EvtDeviceDisarmWakeFromSx: Some(evt_device_disarm_wake_from_sx::<W>),
+        EvtDeviceWakeFromSxTriggered: Some(evt_device_wake_from_sx_triggered::<W>),
+        EvtDeviceArmWakeFromSxWithReason: Some(evt_device_arm_wake_from_sx_with_reason::<W>),
+        ..Default::default()
+    }
+}
+
+/// Attach `callbacks` to `wdf_device`, for the callbacks of
+/// [`power_policy_event_callbacks::<W>()`](power_policy_event_callbacks) to
+/// call
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that does not already have
+/// wake callbacks attached.
+///
+/// # Errors
+///
+/// This function will return an error if WDF fails to allocate storage for
+/// `callbacks`. The error variant will contain a [`NTSTATUS`] of the failure.
+pub(super) unsafe fn attach_wake_callbacks<W: WakeCallbacks>(
+    wdf_device: WDFDEVICE,
+    callbacks: W,
+) -> Result<(), NTSTATUS> {
+    // SAFETY: The caller guarantees that `wdf_device` is a valid handle without
+    // wake callbacks attached.
+    unsafe { attach_closure_in::<WakeCallbacksSlot, W>(wdf_device.cast(), callbacks) }
+}
+
+/// Returns the [`WakeCallbacks`] attached to `wdf_device`
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to
+/// by [`attach_wake_callbacks`].
+unsafe fn wake_callbacks<'a, W>(wdf_device: WDFDEVICE) -> &'a W {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    unsafe { closure_in::<WakeCallbacksSlot, W>(wdf_device.cast()) }
+}
+
+/// The `EvtDeviceArmWakeFromS0` of devices created with [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_arm_wake_from_s0<W: WakeCallbacks>(
+    wdf_device: WDFDEVICE,
+) -> NTSTATUS {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks
+        .arm_wake_from_s0(&device)
+        .map_or_else(|nt_status| nt_status, |()| STATUS_SUCCESS)
+}
+
+/// The `EvtDeviceDisarmWakeFromS0` of devices created with [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_disarm_wake_from_s0<W: WakeCallbacks>(wdf_device: WDFDEVICE) {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks.disarm_wake_from_s0(&device);
+}
+
+/// The `EvtDeviceWakeFromS0Triggered` of devices created with
+/// [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_wake_from_s0_triggered<W: WakeCallbacks>(wdf_device: WDFDEVICE) {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks.wake_from_s0_triggered(&device);
+}
+
+/// The `EvtDeviceArmWakeFromSxWithReason` of devices created with
+/// [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_arm_wake_from_sx_with_reason<W: WakeCallbacks>(
+    wdf_device: WDFDEVICE,
+    device_wake_enabled: BOOLEAN,
+    children_armed_for_wake: BOOLEAN,
+) -> NTSTATUS {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks
+        .arm_wake_from_sx(
+            &device,
+            device_wake_enabled != 0,
+            children_armed_for_wake != 0,
+        )
+        .map_or_else(|nt_status| nt_status, |()| STATUS_SUCCESS)
+}
+
+/// The `EvtDeviceDisarmWakeFromSx` of devices created with [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_disarm_wake_from_sx<W: WakeCallbacks>(wdf_device: WDFDEVICE) {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks.disarm_wake_from_sx(&device);
+}
+
+/// The `EvtDeviceWakeFromSxTriggered` of devices created with
+/// [`WakeCallbacks`]
+///
+/// # Safety
+///
+/// `wdf_device` must be a valid handle to a device that a `W` was attached to.
+unsafe extern "C" fn evt_device_wake_from_sx_triggered<W: WakeCallbacks>(wdf_device: WDFDEVICE) {
+    // SAFETY: The caller guarantees that a `W` was attached to `wdf_device`.
+    let callbacks = unsafe { wake_callbacks::<W>(wdf_device) };
+    // SAFETY: The framework only calls this with a valid device.
+    let device = unsafe { Device::from_raw(wdf_device) };
+    callbacks.wake_from_sx_triggered(&device);
+}
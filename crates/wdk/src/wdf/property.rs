@@ -0,0 +1,343 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::mem::MaybeUninit;
+
+use wdk_sys::{
+    macros,
+    DEVPROPKEY,
+    DEVPROPTYPE,
+    DEVPROP_TYPE_BINARY,
+    DEVPROP_TYPE_BOOLEAN,
+    DEVPROP_TYPE_BYTE,
+    DEVPROP_TYPE_GUID,
+    DEVPROP_TYPE_INT16,
+    DEVPROP_TYPE_INT32,
+    DEVPROP_TYPE_INT64,
+    DEVPROP_TYPE_SBYTE,
+    DEVPROP_TYPE_STRING,
+    DEVPROP_TYPE_UINT16,
+    DEVPROP_TYPE_UINT32,
+    DEVPROP_TYPE_UINT64,
+    GUID,
+    NTSTATUS,
+    PVOID,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_OBJECT_TYPE_MISMATCH,
+    ULONG,
+    WDF_DEVICE_PROPERTY_DATA,
+};
+
+use super::{name::UnicodeBuffer, Device};
+use crate::{guid::Guid, nt_success};
+
+// `WDF_DEVICE_PROPERTY_DATA` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const DEVICE_PROPERTY_DATA_SIZE: ULONG = core::mem::size_of::<WDF_DEVICE_PROPERTY_DATA>() as ULONG;
+
+/// `DEVPROP_TRUE`, the value of `DEVPROP_TYPE_BOOLEAN` properties that are
+/// `true`
+const DEVPROP_TRUE: u8 = 0xFF;
+
+/// A fixed-size type that device properties can be read as and written from.
+///
+/// This is implemented for the integer types, [`bool`], and [`GUID`] and
+/// [`Guid`], which correspond to the `DEVPROP_TYPE_*` types of the same
+/// names. Strings and
+/// binary data are read and written with the dedicated methods of [`Device`],
+/// since their length is not fixed.
+///
+/// # Safety
+///
+/// [`PropertyValue::TYPE`] must be a `DEVPROP_TYPE_*` type whose data is a
+/// [`PropertyValue::Raw`], and every bit pattern of `size_of::<Self::Raw>()`
+/// bytes must be a valid [`PropertyValue::Raw`].
+pub unsafe trait PropertyValue: Sized {
+    /// The `DEVPROP_TYPE_*` type of properties of this type
+    const TYPE: DEVPROPTYPE;
+
+    /// The representation of the property's data
+    type Raw: Copy;
+
+    /// Convert the property's data to a value of this type
+    fn from_raw(raw: Self::Raw) -> Self;
+
+    /// Convert a value of this type to the property's data
+    fn into_raw(self) -> Self::Raw;
+}
+
+macro_rules! impl_property_value {
+    ($($type:ty => $devprop_type:ident),* $(,)?) => {
+        $(
+            // SAFETY: Properties of this type hold a `$type`, for which every bit pattern
+            // is valid.
+            unsafe impl PropertyValue for $type {
+                const TYPE: DEVPROPTYPE = $devprop_type;
+
+                type Raw = Self;
+
+                fn from_raw(raw: Self::Raw) -> Self {
+                    raw
+                }
+
+                fn into_raw(self) -> Self::Raw {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_property_value! {
+    u8 => DEVPROP_TYPE_BYTE,
+    i8 => DEVPROP_TYPE_SBYTE,
+    u16 => DEVPROP_TYPE_UINT16,
+    i16 => DEVPROP_TYPE_INT16,
+    u32 => DEVPROP_TYPE_UINT32,
+    i32 => DEVPROP_TYPE_INT32,
+    u64 => DEVPROP_TYPE_UINT64,
+    i64 => DEVPROP_TYPE_INT64,
+    GUID => DEVPROP_TYPE_GUID,
+}
+
+// SAFETY: `DEVPROP_TYPE_BOOLEAN` properties hold a `DEVPROP_BOOLEAN`, which
+// is one byte, for which every bit pattern is valid.
+unsafe impl PropertyValue for bool {
+    type Raw = u8;
+
+    const TYPE: DEVPROPTYPE = DEVPROP_TYPE_BOOLEAN;
+
+    fn from_raw(raw: Self::Raw) -> Self {
+        raw != 0
+    }
+
+    fn into_raw(self) -> Self::Raw {
+        if self {
+            DEVPROP_TRUE
+        } else {
+            0
+        }
+    }
+}
+
+// SAFETY: `DEVPROP_TYPE_GUID` properties hold a `GUID`, for which every bit
+// pattern is valid.
+unsafe impl PropertyValue for Guid {
+    type Raw = GUID;
+
+    const TYPE: DEVPROPTYPE = DEVPROP_TYPE_GUID;
+
+    fn from_raw(raw: Self::Raw) -> Self {
+        raw.into()
+    }
+
+    fn into_raw(self) -> Self::Raw {
+        self.into()
+    }
+}
+
+impl Device {
+    /// Read the property `key` of the device as a `T`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device has no such property, or if the property is not of the type that `T` corresponds to. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicequerypropertyex#return-value)
+    pub fn query_property<T: PropertyValue>(&self, key: &DEVPROPKEY) -> Result<T, NTSTATUS> {
+        let mut raw = MaybeUninit::<T::Raw>::uninit();
+        let length = self.query_property_raw(
+            key,
+            T::TYPE,
+            raw.as_mut_ptr().cast(),
+            core::mem::size_of::<T::Raw>(),
+        )?;
+        if length != core::mem::size_of::<T::Raw>() {
+            return Err(STATUS_INVALID_BUFFER_SIZE);
+        }
+        // SAFETY: The framework wrote `size_of::<T::Raw>()` bytes of the property to
+        // `raw`, and the contract of `PropertyValue` guarantees that they are a valid
+        // `T::Raw`.
+        Ok(T::from_raw(unsafe { raw.assume_init() }))
+    }
+
+    /// Read the string property `key` of the device into `buffer`, returning
+    /// the part of `buffer` that holds the string, without its null terminator
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device has no such property, if the property is not a string, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicequerypropertyex#return-value)
+    pub fn query_string_property<'a>(
+        &self,
+        key: &DEVPROPKEY,
+        buffer: &'a mut [u16],
+    ) -> Result<&'a [u16], NTSTATUS> {
+        let length = self.query_property_raw(
+            key,
+            DEVPROP_TYPE_STRING,
+            buffer.as_mut_ptr().cast(),
+            core::mem::size_of_val(buffer),
+        )?;
+        let string = &buffer[..length / core::mem::size_of::<u16>()];
+        Ok(string.strip_suffix(&[0]).unwrap_or(string))
+    }
+
+    /// Read the binary property `key` of the device into `buffer`, returning
+    /// the part of `buffer` that holds the property's data
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the device has no such property, if the property is not binary data, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdevicequerypropertyex#return-value)
+    pub fn query_binary_property<'a>(
+        &self,
+        key: &DEVPROPKEY,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], NTSTATUS> {
+        let length = self.query_property_raw(
+            key,
+            DEVPROP_TYPE_BINARY,
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+        )?;
+        Ok(&buffer[..length])
+    }
+
+    /// Set the property `key` of the device to `value`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to set the property. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignproperty#return-value)
+    pub fn assign_property<T: PropertyValue>(
+        &self,
+        key: &DEVPROPKEY,
+        value: T,
+    ) -> Result<(), NTSTATUS> {
+        let mut raw = value.into_raw();
+        self.assign_property_raw(
+            key,
+            T::TYPE,
+            core::ptr::from_mut(&mut raw).cast(),
+            core::mem::size_of::<T::Raw>(),
+        )
+    }
+
+    /// Set the string property `key` of the device to `value`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `value` is too long, or if WDF fails to set the property. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignproperty#return-value)
+    pub fn assign_string_property(&self, key: &DEVPROPKEY, value: &str) -> Result<(), NTSTATUS> {
+        let value = UnicodeBuffer::new(value)?;
+        let value = value.as_null_terminated();
+        self.assign_property_raw(
+            key,
+            DEVPROP_TYPE_STRING,
+            value.as_ptr().cast_mut().cast(),
+            core::mem::size_of_val(value),
+        )
+    }
+
+    /// Set the binary property `key` of the device to `value`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `value` is too long, or if WDF fails to set the property. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceassignproperty#return-value)
+    pub fn assign_binary_property(&self, key: &DEVPROPKEY, value: &[u8]) -> Result<(), NTSTATUS> {
+        self.assign_property_raw(
+            key,
+            DEVPROP_TYPE_BINARY,
+            value.as_ptr().cast_mut().cast(),
+            value.len(),
+        )
+    }
+
+    /// Read the property `key` of the device, which must be of type
+    /// `property_type`, into the `length` bytes at `buffer`, returning the
+    /// length of the property's data
+    fn query_property_raw(
+        &self,
+        key: &DEVPROPKEY,
+        property_type: DEVPROPTYPE,
+        buffer: PVOID,
+        length: usize,
+    ) -> Result<usize, NTSTATUS> {
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let mut property_data = property_data(key);
+        let mut required_length: ULONG = 0;
+        let mut actual_type: DEVPROPTYPE = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a valid handle to the device. `key` outlives the
+        // call, `buffer` is valid for writes of `length` bytes, and the other
+        // arguments are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceQueryPropertyEx,
+                self.as_raw(),
+                &mut property_data,
+                length,
+                buffer,
+                &mut required_length,
+                &mut actual_type,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        if actual_type != property_type {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        Ok(required_length as usize)
+    }
+
+    /// Set the property `key` of the device to the `length` bytes at `data`,
+    /// as a property of type `property_type`
+    fn assign_property_raw(
+        &self,
+        key: &DEVPROPKEY,
+        property_type: DEVPROPTYPE,
+        data: PVOID,
+        length: usize,
+    ) -> Result<(), NTSTATUS> {
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let mut property_data = property_data(key);
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a valid handle to the device. `key` outlives the
+        // call, and `data` points to `length` bytes, which the call only reads from.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceAssignProperty,
+                self.as_raw(),
+                &mut property_data,
+                property_type,
+                length,
+                data,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the `WDF_DEVICE_PROPERTY_DATA` of the property `key`, initialized
+/// the same way as by `WDF_DEVICE_PROPERTY_DATA_INIT`
+fn property_data(key: &DEVPROPKEY) -> WDF_DEVICE_PROPERTY_DATA {
+    WDF_DEVICE_PROPERTY_DATA {
+        Size: DEVICE_PROPERTY_DATA_SIZE,
+        PropertyKey: key,
+        ..Default::default()
+    }
+}
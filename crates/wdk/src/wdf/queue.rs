@@ -0,0 +1,845 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ptr::NonNull;
+
+use wdk_sys::{
+    macros,
+    _WDF_IO_QUEUE_DISPATCH_TYPE,
+    _WDF_IO_QUEUE_STATE,
+    _WDF_TRI_STATE,
+    NTSTATUS,
+    STATUS_INVALID_DEVICE_REQUEST,
+    STATUS_NO_MORE_ENTRIES,
+    ULONG,
+    WDFCONTEXT,
+    WDFOBJECT,
+    WDFQUEUE,
+    WDFREQUEST,
+    WDF_IO_QUEUE_CONFIG,
+    WDF_IO_QUEUE_DISPATCH_TYPE,
+    WDF_IO_QUEUE_STATE,
+    WDF_NO_OBJECT_ATTRIBUTES,
+    WDF_TRI_STATE,
+};
+
+use super::{
+    context::{attach_closure, closure},
+    Device,
+    FileObject,
+    Request,
+    WdfObject,
+};
+use crate::nt_success;
+
+// `WDF_IO_QUEUE_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const IO_QUEUE_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_IO_QUEUE_CONFIG>() as ULONG;
+
+/// How a [`Queue`] presents requests to the driver
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DispatchType {
+    /// The queue presents requests to the driver's request handlers one at a
+    /// time, once the previous request is completed
+    #[default]
+    Sequential,
+    /// The queue presents requests to the driver's request handlers as soon as
+    /// they arrive
+    Parallel,
+    /// The queue does not present requests to the driver, which must retrieve
+    /// them from the queue instead
+    Manual,
+}
+
+impl DispatchType {
+    const fn as_raw(self) -> WDF_IO_QUEUE_DISPATCH_TYPE {
+        match self {
+            Self::Sequential => _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchSequential,
+            Self::Parallel => _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchParallel,
+            Self::Manual => _WDF_IO_QUEUE_DISPATCH_TYPE::WdfIoQueueDispatchManual,
+        }
+    }
+}
+
+/// Request handlers of a [`Queue`].
+///
+/// The framework calls the request handler matching the type of each request
+/// that the queue presents to the driver, at `IRQL` <= `DISPATCH_LEVEL`. The
+/// handler takes ownership of the [`Request`], and must either complete it, or
+/// keep it to complete it later.
+///
+/// Every method has a default implementation. [`QueueCallbacks::io_default()`]
+/// completes the request with `STATUS_INVALID_DEVICE_REQUEST`, and every
+/// other handler passes the request on to [`QueueCallbacks::io_default()`], so
+/// implementations only need to override the handlers for the requests they
+/// support.
+pub trait QueueCallbacks: Send + Sync + 'static {
+    /// Called with requests that no other request handler handles
+    /// (`EvtIoDefault`)
+    fn io_default(&self, queue: &Queue, request: Request) {
+        let _ = queue;
+        request.complete(STATUS_INVALID_DEVICE_REQUEST, 0);
+    }
+
+    /// Called with read requests, for `length` bytes (`EvtIoRead`)
+    fn io_read(&self, queue: &Queue, request: Request, length: usize) {
+        let _ = length;
+        self.io_default(queue, request);
+    }
+
+    /// Called with write requests, for `length` bytes (`EvtIoWrite`)
+    fn io_write(&self, queue: &Queue, request: Request, length: usize) {
+        let _ = length;
+        self.io_default(queue, request);
+    }
+
+    /// Called with device I/O control requests for `io_control_code`, with
+    /// output and input buffers of `output_buffer_length` and
+    /// `input_buffer_length` bytes (`EvtIoDeviceControl`)
+    fn io_device_control(
+        &self,
+        queue: &Queue,
+        request: Request,
+        output_buffer_length: usize,
+        input_buffer_length: usize,
+        io_control_code: ULONG,
+    ) {
+        let _ = (output_buffer_length, input_buffer_length, io_control_code);
+        self.io_default(queue, request);
+    }
+
+    /// Called with internal device I/O control requests for
+    /// `io_control_code`, with output and input buffers of
+    /// `output_buffer_length` and `input_buffer_length` bytes
+    /// (`EvtIoInternalDeviceControl`)
+    fn io_internal_device_control(
+        &self,
+        queue: &Queue,
+        request: Request,
+        output_buffer_length: usize,
+        input_buffer_length: usize,
+        io_control_code: ULONG,
+    ) {
+        let _ = (output_buffer_length, input_buffer_length, io_control_code);
+        self.io_default(queue, request);
+    }
+}
+
+/// The type of the callbacks of a [`QueueBuilder`] that does not register
+/// any, which fails every request that the queue presents to the driver
+pub struct NoQueueCallbacks;
+
+impl QueueCallbacks for NoQueueCallbacks {}
+
+/// Builder of a WDF I/O Queue object.
+///
+/// [`QueueBuilder::new()`] mirrors `WDF_IO_QUEUE_CONFIG_INIT`, and
+/// [`QueueBuilder::default_queue()`] mirrors
+/// `WDF_IO_QUEUE_CONFIG_INIT_DEFAULT_QUEUE`.
+///
+/// ```ignore
+/// let queue = QueueBuilder::new(DispatchType::Sequential)
+///     .default_queue()
+///     .callbacks(MyQueueCallbacks)
+///     .create(&device)?;
+/// ```
+#[must_use]
+pub struct QueueBuilder<C = NoQueueCallbacks> {
+    dispatch_type: DispatchType,
+    default_queue: bool,
+    power_managed: WDF_TRI_STATE,
+    allow_zero_length_requests: bool,
+    callbacks: C,
+}
+
+impl QueueBuilder {
+    /// Construct a [`QueueBuilder`] for a queue that presents requests
+    /// according to `dispatch_type`
+    pub const fn new(dispatch_type: DispatchType) -> Self {
+        Self {
+            dispatch_type,
+            default_queue: false,
+            power_managed: _WDF_TRI_STATE::WdfUseDefault,
+            allow_zero_length_requests: false,
+            callbacks: NoQueueCallbacks,
+        }
+    }
+}
+
+impl<C: QueueCallbacks> QueueBuilder<C> {
+    /// Make the queue the device's default queue, which receives every request
+    /// that is not forwarded to another queue
+    pub const fn default_queue(mut self) -> Self {
+        self.default_queue = true;
+        self
+    }
+
+    /// Set whether the framework stops presenting requests from the queue
+    /// while the device is not in its working (D0) state. By default, queues
+    /// are power-managed, unless the driver is a filter driver.
+    pub const fn power_managed(mut self, power_managed: bool) -> Self {
+        self.power_managed = if power_managed {
+            _WDF_TRI_STATE::WdfTrue
+        } else {
+            _WDF_TRI_STATE::WdfFalse
+        };
+        self
+    }
+
+    /// Set whether the queue presents read and write requests with zero-length
+    /// buffers to the driver, instead of completing them itself
+    pub const fn allow_zero_length_requests(mut self, allow_zero_length_requests: bool) -> Self {
+        self.allow_zero_length_requests = allow_zero_length_requests;
+        self
+    }
+
+    /// Set the request handlers of the queue. They are not called for queues
+    /// with [`DispatchType::Manual`].
+    pub fn callbacks<P: QueueCallbacks>(self, callbacks: P) -> QueueBuilder<P> {
+        QueueBuilder {
+            dispatch_type: self.dispatch_type,
+            default_queue: self.default_queue,
+            power_managed: self.power_managed,
+            allow_zero_length_requests: self.allow_zero_length_requests,
+            callbacks,
+        }
+    }
+
+    /// Try to create the queue, for `device`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`. The queue is deleted
+    /// along with `device`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct the queue, or to allocate storage for its callbacks. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueuecreate#return-value)
+    pub fn create(self, device: &Device) -> Result<Queue, NTSTATUS> {
+        let mut io_queue_config = WDF_IO_QUEUE_CONFIG {
+            Size: IO_QUEUE_CONFIG_SIZE,
+            DispatchType: self.dispatch_type.as_raw(),
+            PowerManaged: self.power_managed,
+            AllowZeroLengthRequests: u8::from(self.allow_zero_length_requests),
+            DefaultQueue: u8::from(self.default_queue),
+            ..Default::default()
+        };
+        match self.dispatch_type {
+            DispatchType::Sequential | DispatchType::Parallel => {
+                io_queue_config.EvtIoDefault = Some(evt_io_default::<C>);
+                io_queue_config.EvtIoRead = Some(evt_io_read::<C>);
+                io_queue_config.EvtIoWrite = Some(evt_io_write::<C>);
+                io_queue_config.EvtIoDeviceControl = Some(evt_io_device_control::<C>);
+                io_queue_config.EvtIoInternalDeviceControl =
+                    Some(evt_io_internal_device_control::<C>);
+            }
+            DispatchType::Manual => {}
+        }
+        if self.dispatch_type == DispatchType::Parallel {
+            // Equivalent to the `WDF_IO_QUEUE_CONFIG_INIT` default of presenting an
+            // unlimited number of requests at once
+            io_queue_config.Settings.Parallel.NumberOfPresentedRequests = ULONG::MAX;
+        }
+
+        let mut queue = Queue {
+            wdf_queue: core::ptr::null_mut(),
+        };
+
+        let nt_status;
+        // SAFETY: `device` is a valid handle to a device object, and
+        // `io_queue_config` is valid for the duration of the call.
+        // `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueCreate,
+                device.as_raw(),
+                &mut io_queue_config,
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut queue.wdf_queue,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: `wdf_queue` is a valid handle to the queue that was just created, and
+        // nothing else has been attached to it. The framework does not present
+        // requests from the queue until the device is started, after
+        // `EvtDriverDeviceAdd` returns.
+        unsafe { attach_closure(queue.wdf_queue.cast(), self.callbacks) }?;
+        Ok(queue)
+    }
+}
+
+/// The state of a [`Queue`], returned by [`Queue::state()`].
+///
+/// A [`QueueState`] is a set of flags, which can be combined with `|` and
+/// tested with [`QueueState::contains()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueState(WDF_IO_QUEUE_STATE);
+
+impl QueueState {
+    /// The queue accepts new requests
+    pub const ACCEPT_REQUESTS: Self = Self(_WDF_IO_QUEUE_STATE::WdfIoQueueAcceptRequests);
+    /// The queue dispatches its requests to the driver
+    pub const DISPATCH_REQUESTS: Self = Self(_WDF_IO_QUEUE_STATE::WdfIoQueueDispatchRequests);
+    /// The driver owns no requests that the queue dispatched to it
+    pub const DRIVER_NO_REQUESTS: Self = Self(_WDF_IO_QUEUE_STATE::WdfIoQueueDriverNoRequests);
+    /// The queue holds no requests
+    pub const NO_REQUESTS: Self = Self(_WDF_IO_QUEUE_STATE::WdfIoQueueNoRequests);
+    /// The framework stopped the queue because the device is changing power
+    /// state, or is being stopped or removed
+    pub const PNP_HELD: Self = Self(_WDF_IO_QUEUE_STATE::WdfIoQueuePnpHeld);
+
+    /// Returns the raw `WDF_IO_QUEUE_STATE` flags of this [`QueueState`]
+    #[must_use]
+    pub const fn bits(self) -> WDF_IO_QUEUE_STATE {
+        self.0
+    }
+
+    /// Returns whether every flag of `other` is set in `self`
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether the queue is idle: neither it nor the driver holds any
+    /// of its requests. This is the equivalent of the `WDF_IO_QUEUE_IDLE` C
+    /// macro.
+    #[must_use]
+    pub const fn is_idle(self) -> bool {
+        self.contains(Self(Self::NO_REQUESTS.0 | Self::DRIVER_NO_REQUESTS.0))
+    }
+
+    /// Returns whether the queue is ready: it accepts and dispatches requests.
+    /// This is the equivalent of the `WDF_IO_QUEUE_READY` C macro.
+    #[must_use]
+    pub const fn is_ready(self) -> bool {
+        self.contains(Self(Self::ACCEPT_REQUESTS.0 | Self::DISPATCH_REQUESTS.0))
+    }
+
+    /// Returns whether the queue is stopped: it accepts requests but does not
+    /// dispatch them, and the driver owns none of its requests. This is the
+    /// equivalent of the `WDF_IO_QUEUE_STOPPED` C macro.
+    #[must_use]
+    pub const fn is_stopped(self) -> bool {
+        self.0 & (Self::DISPATCH_REQUESTS.0 | Self::ACCEPT_REQUESTS.0 | Self::DRIVER_NO_REQUESTS.0)
+            == Self::ACCEPT_REQUESTS.0 | Self::DRIVER_NO_REQUESTS.0
+    }
+
+    /// Returns whether the queue is drained or purged: it neither accepts nor
+    /// holds requests, and the driver owns none of its requests. This is the
+    /// equivalent of the `WDF_IO_QUEUE_DRAINED` and `WDF_IO_QUEUE_PURGED` C
+    /// macros.
+    #[must_use]
+    pub const fn is_purged(self) -> bool {
+        self.0 & (Self::ACCEPT_REQUESTS.0 | Self::NO_REQUESTS.0 | Self::DRIVER_NO_REQUESTS.0)
+            == Self::NO_REQUESTS.0 | Self::DRIVER_NO_REQUESTS.0
+    }
+}
+
+impl core::ops::BitOr for QueueState {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// WDF I/O Queue.
+///
+/// A [`Queue`] is created with a [`QueueBuilder`], and receives the I/O
+/// requests of its device. It is owned by the framework, which deletes it
+/// along with its device.
+#[derive(Clone, Copy)]
+pub struct Queue {
+    wdf_queue: WDFQUEUE,
+}
+
+// SAFETY: `WDFQUEUE` handles can be used from any thread.
+unsafe impl Send for Queue {}
+
+// SAFETY: `Queue` has no methods that mutate the queue object.
+unsafe impl Sync for Queue {}
+
+// SAFETY: The framework queue object lives until its device is removed, and
+// `Queue` handles are only passed to the driver while the device exists.
+unsafe impl WdfObject for Queue {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_queue.cast()
+    }
+}
+
+impl Queue {
+    /// Wrap an existing WDF I/O Queue object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_queue` must be a valid handle to a WDF I/O Queue object, and must
+    /// remain valid for the lifetime of the returned [`Queue`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_queue: WDFQUEUE) -> Self {
+        Self { wdf_queue }
+    }
+
+    /// Returns the raw `WDFQUEUE` handle wrapped by this [`Queue`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFQUEUE {
+        self.wdf_queue
+    }
+
+    /// Returns the device that the queue belongs to
+    #[must_use]
+    pub fn device(&self) -> Device {
+        let wdf_device;
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used.
+        unsafe {
+            wdf_device =
+                macros::call_unsafe_wdf_function_binding!(WdfIoQueueGetDevice, self.wdf_queue);
+        }
+        // SAFETY: The device of a queue lives at least as long as the queue.
+        unsafe { Device::from_raw(wdf_device) }
+    }
+
+    /// Returns the state of the queue
+    ///
+    /// This may be called at any `IRQL` <= `DISPATCH_LEVEL`. The state can
+    /// change as soon as it is returned, unless the driver prevents it.
+    #[must_use]
+    pub fn state(&self) -> QueueState {
+        let state;
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. The request counts are optional.
+        unsafe {
+            state = macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueGetState,
+                self.wdf_queue,
+                core::ptr::null_mut(),
+                core::ptr::null_mut()
+            );
+        }
+        QueueState(state)
+    }
+
+    /// Start the queue, so that it accepts new requests and dispatches them to
+    /// the driver
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn start(&self) {
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfIoQueueStart, self.wdf_queue);
+        }
+    }
+
+    /// Stop the queue from dispatching requests to the driver, and call
+    /// `on_stopped` once the driver has completed or requeued every request
+    /// that the queue dispatched to it
+    ///
+    /// The queue keeps accepting new requests, which it dispatches once it is
+    /// restarted with [`Queue::start()`]. This returns immediately, and must
+    /// be called at `IRQL` <= `DISPATCH_LEVEL`. `on_stopped` is called at
+    /// `IRQL` <= `DISPATCH_LEVEL`, and must not capture any variables, since
+    /// there is nowhere to store them.
+    pub fn stop<F>(&self, on_stopped: F)
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        forget_state_callback(on_stopped);
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. `F` is zero-sized, so the callback needs
+        // no context.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueStop,
+                self.wdf_queue,
+                Some(evt_io_queue_state::<F>),
+                core::ptr::null_mut()
+            );
+        }
+    }
+
+    /// Stop the queue from dispatching requests to the driver, and wait until
+    /// the driver has completed or requeued every request that the queue
+    /// dispatched to it
+    ///
+    /// The queue keeps accepting new requests, which it dispatches once it is
+    /// restarted with [`Queue::start()`]. This must be called at `IRQL` =
+    /// `PASSIVE_LEVEL`, and not from a request handler of the queue, since the
+    /// request being handled would never be completed.
+    pub fn stop_synchronously(&self) {
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfIoQueueStopSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Stop the queue from accepting new requests, and call `on_drained` once
+    /// the queue is empty and the driver has completed every request that the
+    /// queue dispatched to it
+    ///
+    /// Requests that are already in the queue are still dispatched to the
+    /// driver. New requests are completed with `STATUS_INVALID_DEVICE_STATE`
+    /// until the queue is restarted with [`Queue::start()`]. This returns
+    /// immediately, and must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    /// `on_drained` is called at `IRQL` <= `DISPATCH_LEVEL`, and must not
+    /// capture any variables, since there is nowhere to store them.
+    pub fn drain<F>(&self, on_drained: F)
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        forget_state_callback(on_drained);
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. `F` is zero-sized, so the callback needs
+        // no context.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueDrain,
+                self.wdf_queue,
+                Some(evt_io_queue_state::<F>),
+                core::ptr::null_mut()
+            );
+        }
+    }
+
+    /// Stop the queue from accepting new requests, and wait until the queue is
+    /// empty and the driver has completed every request that the queue
+    /// dispatched to it
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`, and not from a request
+    /// handler of the queue, since the request being handled would never be
+    /// completed.
+    pub fn drain_synchronously(&self) {
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfIoQueueDrainSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Stop the queue from accepting new requests, cancel the requests that it
+    /// holds, and call `on_purged` once the driver has completed every request
+    /// that the queue dispatched to it
+    ///
+    /// Requests that the driver owns are only canceled if they are marked as
+    /// cancelable. New requests are completed with
+    /// `STATUS_INVALID_DEVICE_STATE` until the queue is restarted with
+    /// [`Queue::start()`]. This returns immediately, and must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`. `on_purged` is called at `IRQL` <=
+    /// `DISPATCH_LEVEL`, and must not capture any variables, since there is
+    /// nowhere to store them.
+    pub fn purge<F>(&self, on_purged: F)
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        forget_state_callback(on_purged);
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. `F` is zero-sized, so the callback needs
+        // no context.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueuePurge,
+                self.wdf_queue,
+                Some(evt_io_queue_state::<F>),
+                core::ptr::null_mut()
+            );
+        }
+    }
+
+    /// Stop the queue from accepting new requests, cancel the requests that it
+    /// holds, and wait until the driver has completed every request that the
+    /// queue dispatched to it
+    ///
+    /// This is typically called when the device is surprise-removed. It must
+    /// be called at `IRQL` = `PASSIVE_LEVEL`, and not from a request handler
+    /// of the queue, since the request being handled would never be completed.
+    pub fn purge_synchronously(&self) {
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfIoQueuePurgeSynchronously, self.wdf_queue);
+        }
+    }
+
+    /// Retrieve the next request from the queue, or [`None`] if the queue is
+    /// empty
+    ///
+    /// This is how drivers receive the requests of queues with
+    /// [`DispatchType::Manual`]. It must be called at `IRQL` <=
+    /// `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue is not accepting requests, or if WDF fails to retrieve the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueueretrievenextrequest#return-value)
+    pub fn retrieve_next_request(&self) -> Result<Option<Request>, NTSTATUS> {
+        let mut wdf_request: WDFREQUEST = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. `wdf_request` is valid for the duration of
+        // the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueRetrieveNextRequest,
+                self.wdf_queue,
+                &mut wdf_request,
+            );
+        }
+        // SAFETY: The framework passes ownership of retrieved requests to the driver.
+        unsafe { retrieved_request(nt_status, wdf_request) }
+    }
+
+    /// Retrieve the next request from the queue that was sent through
+    /// `file_object`, or [`None`] if there is no such request
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue is not accepting requests, or if WDF fails to retrieve the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueueretrieverequestbyfileobject#return-value)
+    pub fn retrieve_request_by_file_object(
+        &self,
+        file_object: &FileObject,
+    ) -> Result<Option<Request>, NTSTATUS> {
+        let mut wdf_request: WDFREQUEST = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_queue` is a private member of `Queue`, and the framework keeps
+        // the queue valid while it is used. `file_object` is a valid handle to a file
+        // object, and `wdf_request` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoQueueRetrieveRequestByFileObject,
+                self.wdf_queue,
+                file_object.as_raw(),
+                &mut wdf_request,
+            );
+        }
+        // SAFETY: The framework passes ownership of retrieved requests to the driver.
+        unsafe { retrieved_request(nt_status, wdf_request) }
+    }
+
+    /// Returns an iterator that retrieves requests from the queue until it is
+    /// empty
+    pub const fn requests(&self) -> QueueRequests<'_> {
+        QueueRequests {
+            queue: self,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over the requests retrieved from a [`Queue`], returned by
+/// [`Queue::requests()`].
+///
+/// The iterator ends once the queue is empty, or once retrieving a request
+/// fails. Use [`QueueRequests::try_next()`] to tell the two apart.
+#[must_use]
+pub struct QueueRequests<'a> {
+    queue: &'a Queue,
+    done: bool,
+}
+
+impl QueueRequests<'_> {
+    /// Retrieve the next request from the queue, or [`None`] if the queue is
+    /// empty
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the queue is not accepting requests, or if WDF fails to retrieve the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoQueue Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfio/nf-wdfio-wdfioqueueretrievenextrequest#return-value)
+    pub fn try_next(&mut self) -> Result<Option<Request>, NTSTATUS> {
+        if self.done {
+            return Ok(None);
+        }
+        let request = self.queue.retrieve_next_request();
+        if !matches!(request, Ok(Some(_))) {
+            self.done = true;
+        }
+        request
+    }
+}
+
+impl Iterator for QueueRequests<'_> {
+    type Item = Request;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().ok().flatten()
+    }
+}
+
+/// Returns the request retrieved by a `WdfIoQueueRetrieveXxx` function that
+/// returned `nt_status`
+///
+/// # Safety
+///
+/// If `nt_status` is a success, `wdf_request` must be a valid handle to a
+/// request that the framework passed ownership of to the driver.
+unsafe fn retrieved_request(
+    nt_status: NTSTATUS,
+    wdf_request: WDFREQUEST,
+) -> Result<Option<Request>, NTSTATUS> {
+    if nt_status == STATUS_NO_MORE_ENTRIES {
+        return Ok(None);
+    }
+    if !nt_success(nt_status) {
+        return Err(nt_status);
+    }
+    // SAFETY: The caller guarantees that the driver owns the retrieved request.
+    Ok(Some(unsafe { Request::from_raw(wdf_request) }))
+}
+
+/// Returns the [`QueueCallbacks`] attached to `wdf_queue`, and the [`Queue`]
+/// and [`Request`] of a request handler
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to by
+/// [`QueueBuilder::create()`], and `wdf_request` must be a valid handle to a
+/// request that the queue is presenting to the driver.
+unsafe fn request_handler_args<'a, C>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+) -> (&'a C, Queue, Request) {
+    // SAFETY: The caller guarantees that a `C` was attached to `wdf_queue`.
+    let callbacks = unsafe { closure::<C>(wdf_queue.cast()) };
+    // SAFETY: The caller guarantees that the request is being presented to the
+    // driver, so it has not been completed, and is now owned by the driver.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    (callbacks, Queue { wdf_queue }, request)
+}
+
+/// The `EvtIoDefault` of queues created by [`QueueBuilder`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to,
+/// and `wdf_request` must be a request that it is presenting to the driver.
+unsafe extern "C" fn evt_io_default<C: QueueCallbacks>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+) {
+    // SAFETY: The framework only calls this with the queue, which had a `C`
+    // attached when it was created, and a request that it is presenting.
+    let (callbacks, queue, request) = unsafe { request_handler_args::<C>(wdf_queue, wdf_request) };
+    callbacks.io_default(&queue, request);
+}
+
+/// The `EvtIoRead` of queues created by [`QueueBuilder`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to,
+/// and `wdf_request` must be a request that it is presenting to the driver.
+unsafe extern "C" fn evt_io_read<C: QueueCallbacks>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    length: usize,
+) {
+    // SAFETY: The framework only calls this with the queue, which had a `C`
+    // attached when it was created, and a request that it is presenting.
+    let (callbacks, queue, request) = unsafe { request_handler_args::<C>(wdf_queue, wdf_request) };
+    callbacks.io_read(&queue, request, length);
+}
+
+/// The `EvtIoWrite` of queues created by [`QueueBuilder`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to,
+/// and `wdf_request` must be a request that it is presenting to the driver.
+unsafe extern "C" fn evt_io_write<C: QueueCallbacks>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    length: usize,
+) {
+    // SAFETY: The framework only calls this with the queue, which had a `C`
+    // attached when it was created, and a request that it is presenting.
+    let (callbacks, queue, request) = unsafe { request_handler_args::<C>(wdf_queue, wdf_request) };
+    callbacks.io_write(&queue, request, length);
+}
+
+/// The `EvtIoDeviceControl` of queues created by [`QueueBuilder`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to,
+/// and `wdf_request` must be a request that it is presenting to the driver.
+unsafe extern "C" fn evt_io_device_control<C: QueueCallbacks>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    output_buffer_length: usize,
+    input_buffer_length: usize,
+    io_control_code: ULONG,
+) {
+    // SAFETY: The framework only calls this with the queue, which had a `C`
+    // attached when it was created, and a request that it is presenting.
+    let (callbacks, queue, request) = unsafe { request_handler_args::<C>(wdf_queue, wdf_request) };
+    callbacks.io_device_control(
+        &queue,
+        request,
+        output_buffer_length,
+        input_buffer_length,
+        io_control_code,
+    );
+}
+
+/// The `EvtIoInternalDeviceControl` of queues created by [`QueueBuilder`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that a `C` was attached to,
+/// and `wdf_request` must be a request that it is presenting to the driver.
+unsafe extern "C" fn evt_io_internal_device_control<C: QueueCallbacks>(
+    wdf_queue: WDFQUEUE,
+    wdf_request: WDFREQUEST,
+    output_buffer_length: usize,
+    input_buffer_length: usize,
+    io_control_code: ULONG,
+) {
+    // SAFETY: The framework only calls this with the queue, which had a `C`
+    // attached when it was created, and a request that it is presenting.
+    let (callbacks, queue, request) = unsafe { request_handler_args::<C>(wdf_queue, wdf_request) };
+    callbacks.io_internal_device_control(
+        &queue,
+        request,
+        output_buffer_length,
+        input_buffer_length,
+        io_control_code,
+    );
+}
+
+/// Check that the state callback `callback` does not capture any variables,
+/// and move it into the framework, which calls [`evt_io_queue_state`] to
+/// recreate it
+fn forget_state_callback<F>(callback: F) {
+    const {
+        assert!(
+            core::mem::size_of::<F>() == 0,
+            "queue state callbacks must not capture any variables"
+        );
+    }
+    core::mem::forget(callback);
+}
+
+/// The `EvtIoQueueState` of [`Queue::stop()`], [`Queue::drain()`] and
+/// [`Queue::purge()`]
+///
+/// # Safety
+///
+/// `wdf_queue` must be a valid handle to a queue that was passed to one of
+/// them with an `F`, which must be zero-sized, and the framework must only call
+/// this once for each `F` it was passed.
+unsafe extern "C" fn evt_io_queue_state<F: FnOnce(Queue)>(
+    wdf_queue: WDFQUEUE,
+    _context: WDFCONTEXT,
+) {
+    // SAFETY: `F` is zero-sized, so reading it from a dangling pointer is valid,
+    // and `forget_state_callback()` forgot the `F` it was passed, so this
+    // recreates the value that it moved into the framework.
+    let callback = unsafe { NonNull::<F>::dangling().read() };
+    // SAFETY: The framework only calls this with a valid queue, which remains
+    // valid for the duration of the call.
+    let queue = unsafe { Queue::from_raw(wdf_queue) };
+    callback(queue);
+}
@@ -0,0 +1,677 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+#[cfg(feature = "alloc")]
+use wdk_sys::UNICODE_STRING;
+use wdk_sys::{
+    macros,
+    ACCESS_MASK,
+    KEY_READ,
+    KEY_WRITE,
+    NTSTATUS,
+    PLUGPLAY_REGKEY_DEVICE,
+    PLUGPLAY_REGKEY_DRIVER,
+    PVOID,
+    REG_BINARY,
+    REG_EXPAND_SZ,
+    REG_MULTI_SZ,
+    REG_OPTION_NON_VOLATILE,
+    REG_SZ,
+    STATUS_BUFFER_OVERFLOW,
+    STATUS_INVALID_BUFFER_SIZE,
+    STATUS_INVALID_PARAMETER,
+    STATUS_OBJECT_TYPE_MISMATCH,
+    ULONG,
+    WDFKEY,
+    WDFOBJECT,
+    WDF_NO_OBJECT_ATTRIBUTES,
+};
+
+#[cfg(feature = "alloc")]
+use super::WdfString;
+use super::{name::UnicodeBuffer, Device, Driver, PoolType, WdfObject};
+#[cfg(feature = "alloc")]
+use crate::string::NtUnicodeString;
+use crate::{collections::KVec, debug_assert_irql, nt_success};
+
+/// The access to a [`RegistryKey`] that it is opened with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyAccess {
+    /// Read the values and subkeys of the key
+    #[default]
+    Read,
+    /// Write the values and subkeys of the key
+    Write,
+    /// Both read and write the values and subkeys of the key
+    ReadWrite,
+}
+
+impl KeyAccess {
+    pub(crate) const fn as_raw(self) -> ACCESS_MASK {
+        match self {
+            Self::Read => KEY_READ,
+            Self::Write => KEY_WRITE,
+            Self::ReadWrite => KEY_READ | KEY_WRITE,
+        }
+    }
+}
+
+/// The registry key of a device that [`Device::open_registry_key()`] opens
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceKeyType {
+    /// The hardware key of the device, under its device instance key, which
+    /// holds the `Device Parameters` of the device
+    Hardware,
+    /// The software key of the device, under the driver's class key
+    Software,
+}
+
+impl DeviceKeyType {
+    const fn as_raw(self) -> ULONG {
+        match self {
+            Self::Hardware => PLUGPLAY_REGKEY_DEVICE,
+            Self::Software => PLUGPLAY_REGKEY_DRIVER,
+        }
+    }
+}
+
+/// WDF Registry Key.
+///
+/// A [`RegistryKey`] is an open registry key, opened with
+/// [`Driver::open_parameters_key()`], [`Device::open_registry_key()`] or
+/// [`RegistryKey::open()`], whose values can be read and written, and whose
+/// subkeys can be opened. The key is closed when the [`RegistryKey`] is
+/// dropped. Every method of [`RegistryKey`] must be called at `IRQL` =
+/// `PASSIVE_LEVEL`.
+///
+/// Value and key names are encoded as UTF-16 without allocating, so they are
+/// limited to 512 UTF-16 code units.
+///
+/// ```ignore
+/// let parameters = driver.open_parameters_key(KeyAccess::Read)?;
+/// let queue_depth = parameters.query_u32("QueueDepth").unwrap_or(DEFAULT_QUEUE_DEPTH);
+/// ```
+pub struct RegistryKey {
+    wdf_key: WDFKEY,
+}
+
+// SAFETY: `WDFKEY` handles can be used, and closed, from any thread.
+unsafe impl Send for RegistryKey {}
+
+// SAFETY: The registry synchronizes concurrent access to the values and
+// subkeys of a key.
+unsafe impl Sync for RegistryKey {}
+
+// SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+// by WDF, which is only closed when the `RegistryKey` is dropped.
+unsafe impl WdfObject for RegistryKey {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_key.cast()
+    }
+}
+
+impl RegistryKey {
+    /// Try to open the registry key at the absolute registry `path` (ex.
+    /// `\Registry\Machine\System\CurrentControlSet\Services\Sample`) with
+    /// `access`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `path` is too long, if the key does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryopenkey#return-value)
+    pub fn open(path: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        Self::open_key(core::ptr::null_mut(), path, access)
+    }
+
+    /// Returns the raw `WDFKEY` handle wrapped by this [`RegistryKey`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFKEY {
+        self.wdf_key
+    }
+
+    /// Try to open the subkey `name` of the key with `access`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the subkey does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryopenkey#return-value)
+    pub fn open_subkey(&self, name: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        Self::open_key(self.wdf_key, name, access)
+    }
+
+    /// Try to open the subkey `name` of the key with `access`, creating it if
+    /// it does not exist
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the driver is not allowed to create the subkey. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistrycreatekey#return-value)
+    pub fn create_subkey(&self, name: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be opened");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // and `wdf_key` are valid for the duration of the call, a null
+        // `CreateDisposition` is allowed, and `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryCreateKey,
+                self.wdf_key,
+                &name,
+                access.as_raw(),
+                REG_OPTION_NON_VOLATILE,
+                core::ptr::null_mut(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_key,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(Self { wdf_key })
+    }
+
+    /// Read the `REG_DWORD` value `name` of the key
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, or if the value is not a `REG_DWORD`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryulong#return-value)
+    pub fn query_u32(&self, name: &str) -> Result<u32, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+        let mut value: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // and `value` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryQueryULong,
+                self.wdf_key,
+                &name,
+                &mut value,
+            );
+        }
+        nt_success(nt_status).then_some(value).ok_or(nt_status)
+    }
+
+    /// Read the `REG_SZ` or `REG_EXPAND_SZ` value `name` of the key into
+    /// `buffer`, returning the part of `buffer` that holds the string, without
+    /// its null terminator
+    ///
+    /// `REG_EXPAND_SZ` values are not expanded. Use
+    /// [`RegistryKey::value_length()`] to size `buffer`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, if the value is not a string, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryvalue#return-value)
+    pub fn query_string<'a>(
+        &self,
+        name: &str,
+        buffer: &'a mut [u16],
+    ) -> Result<&'a [u16], NTSTATUS> {
+        let (length, value_type) = self.query_value_raw(
+            name,
+            buffer.as_mut_ptr().cast(),
+            core::mem::size_of_val(buffer),
+        )?;
+        if value_type != REG_SZ && value_type != REG_EXPAND_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        let string = &buffer[..length / core::mem::size_of::<u16>()];
+        Ok(string.strip_suffix(&[0]).unwrap_or(string))
+    }
+
+    /// Read the `REG_SZ` value `name` of the key into the string object
+    /// `string`, replacing its contents
+    ///
+    /// Unlike [`RegistryKey::query_string()`], this reads strings of any
+    /// length, which the framework allocates storage for.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, or if the value is not a `REG_SZ`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryquerystring#return-value)
+    #[cfg(feature = "alloc")]
+    pub fn query_wdf_string(&self, name: &str, string: &mut WdfString<'_>) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // is valid for the duration of the call, and `string` is a valid string object,
+        // which is borrowed mutably while the framework replaces its contents.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryQueryString,
+                self.wdf_key,
+                &name,
+                string.as_raw(),
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Read the `REG_SZ` value `name` of the key into `string`, replacing its
+    /// contents
+    ///
+    /// `string` grows to hold the value, which can be of any length that fits
+    /// in a `UNICODE_STRING`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, if the value is not a `REG_SZ`, or if storage for the value could not be allocated. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryunicodestring#return-value)
+    #[cfg(feature = "alloc")]
+    pub fn query_unicode_string(
+        &self,
+        name: &str,
+        string: &mut NtUnicodeString,
+    ) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let length = self.value_length(name)?;
+        string.clear();
+        string.reserve(length / core::mem::size_of::<u16>())?;
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+
+        let query = |raw: &mut UNICODE_STRING| {
+            let nt_status;
+            // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+            // by WDF, and this module guarantees that it is always in a valid state.
+            // `name` and `raw` are valid for the duration of the call, and a null
+            // `ValueByteLength` is allowed.
+            unsafe {
+                nt_status = macros::call_unsafe_wdf_function_binding!(
+                    WdfRegistryQueryUnicodeString,
+                    self.wdf_key,
+                    &name,
+                    core::ptr::null_mut(),
+                    raw,
+                );
+            }
+            nt_status
+        };
+        let nt_status;
+        // SAFETY: WDF only writes within the `MaximumLength` of `raw`, and sets its
+        // `Length` to the number of bytes it wrote, or leaves it at 0 if it fails.
+        unsafe {
+            nt_status = string.with_raw_mut(query);
+        }
+        if !nt_success(nt_status) {
+            string.clear();
+            return Err(nt_status);
+        }
+        if string.as_slice().last() == Some(&0) {
+            string.truncate(string.len() - 1);
+        }
+        Ok(())
+    }
+
+    /// Read the `REG_MULTI_SZ` value `name` of the key into `buffer`,
+    /// returning an iterator over the strings it holds, without their null
+    /// terminators
+    ///
+    /// Use [`RegistryKey::value_length()`] to size `buffer`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, if the value is not a `REG_MULTI_SZ`, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryvalue#return-value)
+    pub fn query_multi_string<'a>(
+        &self,
+        name: &str,
+        buffer: &'a mut [u16],
+    ) -> Result<impl Iterator<Item = &'a [u16]>, NTSTATUS> {
+        let (length, value_type) = self.query_value_raw(
+            name,
+            buffer.as_mut_ptr().cast(),
+            core::mem::size_of_val(buffer),
+        )?;
+        if value_type != REG_MULTI_SZ {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        let strings: &'a [u16] = &buffer[..length / core::mem::size_of::<u16>()];
+        // The strings are each null-terminated, and the list is terminated by an
+        // empty string
+        Ok(strings
+            .split(|&code_unit| code_unit == 0)
+            .take_while(|string| !string.is_empty()))
+    }
+
+    /// Read the `REG_BINARY` value `name` of the key into `buffer`, returning
+    /// the part of `buffer` that holds the value's data
+    ///
+    /// Use [`RegistryKey::value_length()`] to size `buffer`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if the key has no such value, if the value is not a `REG_BINARY`, or if `buffer` is too small to hold it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryvalue#return-value)
+    pub fn query_binary<'a>(&self, name: &str, buffer: &'a mut [u8]) -> Result<&'a [u8], NTSTATUS> {
+        let (length, value_type) =
+            self.query_value_raw(name, buffer.as_mut_ptr().cast(), buffer.len())?;
+        if value_type != REG_BINARY {
+            return Err(STATUS_OBJECT_TYPE_MISMATCH);
+        }
+        Ok(&buffer[..length])
+    }
+
+    /// Returns the length of the data of the value `name` of the key, in bytes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the key has no such value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryqueryvalue#return-value)
+    pub fn value_length(&self, name: &str) -> Result<usize, NTSTATUS> {
+        self.query_value_raw(name, core::ptr::null_mut(), 0)
+            .map(|(length, _)| length)
+    }
+
+    /// Set the value `name` of the key to the `REG_DWORD` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if WDF fails to set the value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignulong#return-value)
+    pub fn assign_u32(&self, name: &str, value: u32) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryAssignULong,
+                self.wdf_key,
+                &name,
+                value,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Set the value `name` of the key to the `REG_SZ` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` or `value` is too long, or if WDF fails to set the value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignvalue#return-value)
+    pub fn assign_string(&self, name: &str, value: &str) -> Result<(), NTSTATUS> {
+        let value = UnicodeBuffer::new(value)?;
+        let value = value.as_null_terminated();
+        self.assign_value_raw(
+            name,
+            REG_SZ,
+            value.as_ptr().cast_mut().cast(),
+            core::mem::size_of_val(value),
+        )
+    }
+
+    /// Set the value `name` of the key to a `REG_MULTI_SZ` holding `values`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, if any of `values` is empty or contains a null character, which `REG_MULTI_SZ` values cannot hold, if storage for the value could not be allocated, or if WDF fails to set the value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignvalue#return-value)
+    pub fn assign_multi_string(&self, name: &str, values: &[&str]) -> Result<(), NTSTATUS> {
+        if values
+            .iter()
+            .any(|value| value.is_empty() || value.contains('\0'))
+        {
+            return Err(STATUS_INVALID_PARAMETER);
+        }
+        let length = values
+            .iter()
+            .map(|value| value.encode_utf16().count() + 1)
+            .sum::<usize>()
+            + 1;
+        let mut multi_string = KVec::with_capacity(length, PoolType::Paged, 0)?;
+        for value in values {
+            for code_unit in value.encode_utf16() {
+                multi_string.try_push(code_unit)?;
+            }
+            multi_string.try_push(0)?;
+        }
+        multi_string.try_push(0)?;
+
+        self.assign_value_raw(
+            name,
+            REG_MULTI_SZ,
+            multi_string.as_mut_ptr().cast(),
+            core::mem::size_of_val(multi_string.as_slice()),
+        )
+    }
+
+    /// Set the value `name` of the key to the `REG_BINARY` `value`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` or `value` is too long, or if WDF fails to set the value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryassignvalue#return-value)
+    pub fn assign_binary(&self, name: &str, value: &[u8]) -> Result<(), NTSTATUS> {
+        self.assign_value_raw(
+            name,
+            REG_BINARY,
+            value.as_ptr().cast_mut().cast(),
+            value.len(),
+        )
+    }
+
+    /// Remove the value `name` from the key
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `name` is too long, or if the key has no such value. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRegistry Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfregistry/nf-wdfregistry-wdfregistryremovevalue#return-value)
+    pub fn remove_value(&self, name: &str) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryRemoveValue,
+                self.wdf_key,
+                &name,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+
+    /// Open the key `name`, relative to `parent` if it is not null, with
+    /// `access`
+    fn open_key(parent: WDFKEY, name: &str, access: KeyAccess) -> Result<Self, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be opened");
+
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `parent` is either null or a valid key, `name` and `wdf_key` are
+        // valid for the duration of the call, and `WDF_NO_OBJECT_ATTRIBUTES` is
+        // allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryOpenKey,
+                parent,
+                &name,
+                access.as_raw(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_key,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(Self { wdf_key })
+    }
+
+    /// Read the value `name` of the key into the `length` bytes at `buffer`,
+    /// returning the length of the value's data and its `REG_*` type
+    ///
+    /// If `length` is 0, only the length and type of the value are returned.
+    fn query_value_raw(
+        &self,
+        name: &str,
+        buffer: PVOID,
+        length: usize,
+    ) -> Result<(usize, ULONG), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+        let mut value_length: ULONG = 0;
+        let mut value_type: ULONG = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state.
+        // `buffer` is valid for writes of `length` bytes, or is not written to if
+        // `length` is 0, and the other arguments are valid for the duration of the
+        // call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryQueryValue,
+                self.wdf_key,
+                &name,
+                length,
+                buffer,
+                &mut value_length,
+                &mut value_type,
+            );
+        }
+        // Querying only the length of the value reports that the buffer is too small
+        if nt_success(nt_status) || (length == 0 && nt_status == STATUS_BUFFER_OVERFLOW) {
+            Ok((value_length as usize, value_type))
+        } else {
+            Err(nt_status)
+        }
+    }
+
+    /// Set the value `name` of the key to the `length` bytes at `data`, as a
+    /// value of type `value_type`
+    fn assign_value_raw(
+        &self,
+        name: &str,
+        value_type: ULONG,
+        data: PVOID,
+        length: usize,
+    ) -> Result<(), NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be used");
+
+        let length = ULONG::try_from(length).map_err(|_| STATUS_INVALID_BUFFER_SIZE)?;
+        let name = UnicodeBuffer::new(name)?;
+        let name = name.as_unicode_string();
+
+        let nt_status;
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `name`
+        // is valid for the duration of the call, and `data` points to `length` bytes,
+        // which the call only reads from.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRegistryAssignValue,
+                self.wdf_key,
+                &name,
+                value_type,
+                length,
+                data,
+            );
+        }
+        nt_success(nt_status).then_some(()).ok_or(nt_status)
+    }
+}
+
+impl Drop for RegistryKey {
+    fn drop(&mut self) {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be closed");
+
+        // SAFETY: `wdf_key` is a private member of `RegistryKey`, originally created
+        // by WDF, and it is not used after this.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfRegistryClose, self.wdf_key);
+        }
+    }
+}
+
+impl Driver {
+    /// Try to open the `Parameters` subkey of the driver's service key with
+    /// `access`, where the driver's configuration is stored
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDriver Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdriver/nf-wdfdriver-wdfdriveropenparametersregistrykey#return-value)
+    pub fn open_parameters_key(&self, access: KeyAccess) -> Result<RegistryKey, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be opened");
+
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `as_raw()` is a valid handle to the framework driver object.
+        // `wdf_key` is valid for the duration of the call, and
+        // `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDriverOpenParametersRegistryKey,
+                self.as_raw(),
+                access.as_raw(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_key,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(RegistryKey { wdf_key })
+    }
+}
+
+impl Device {
+    /// Try to open the `key_type` registry key of the device with `access`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key does not exist, or if the driver is not allowed `access` to it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFDevice Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfdevice/nf-wdfdevice-wdfdeviceopenregistrykey#return-value)
+    pub fn open_registry_key(
+        &self,
+        key_type: DeviceKeyType,
+        access: KeyAccess,
+    ) -> Result<RegistryKey, NTSTATUS> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "registry keys must be opened");
+
+        let mut wdf_key: WDFKEY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_device` is a valid handle to the device. `wdf_key` is valid for
+        // the duration of the call, and `WDF_NO_OBJECT_ATTRIBUTES` is allowed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfDeviceOpenRegistryKey,
+                self.as_raw(),
+                key_type.as_raw(),
+                access.as_raw(),
+                WDF_NO_OBJECT_ATTRIBUTES,
+                &mut wdf_key,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(RegistryKey { wdf_key })
+    }
+}
@@ -0,0 +1,1082 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{mem::ManuallyDrop, ptr::NonNull};
+
+use wdk_sys::{
+    macros,
+    _MODE,
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS,
+    _WDF_REQUEST_TYPE,
+    NTSTATUS,
+    PVOID,
+    STATUS_DATATYPE_MISALIGNMENT,
+    STATUS_SUCCESS,
+    ULONG,
+    ULONG_PTR,
+    USHORT,
+    WDFCONTEXT,
+    WDFIOTARGET,
+    WDFMEMORY,
+    WDFOBJECT,
+    WDFREQUEST,
+    WDF_REQUEST_COMPLETION_PARAMS,
+    WDF_REQUEST_PARAMETERS,
+    WDF_REQUEST_SEND_OPTIONS,
+    WDF_REQUEST_TYPE,
+};
+
+use super::{FileObject, IoTarget, Queue, SendOptions, WdfObject};
+use crate::nt_success;
+
+// `WDF_REQUEST_PARAMETERS` is much smaller than `USHORT::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const REQUEST_PARAMETERS_SIZE: USHORT = core::mem::size_of::<WDF_REQUEST_PARAMETERS>() as USHORT;
+
+// `Flags` is a `ULONG`, while the `WDF_REQUEST_SEND_OPTIONS_FLAGS` enumeration
+// it holds values of is an `int`
+#[allow(clippy::cast_sign_loss)]
+const SEND_AND_FORGET: ULONG =
+    _WDF_REQUEST_SEND_OPTIONS_FLAGS::WDF_REQUEST_SEND_OPTION_SEND_AND_FORGET as ULONG;
+
+/// The type of I/O operation that a [`Request`] represents
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestType {
+    /// A request to open a file handle to the device (`IRP_MJ_CREATE`)
+    Create,
+    /// A request to close the last handle to a file object (`IRP_MJ_CLOSE`)
+    Close,
+    /// A request sent when the last handle to a file object is closed
+    /// (`IRP_MJ_CLEANUP`)
+    Cleanup,
+    /// A read request (`IRP_MJ_READ`)
+    Read,
+    /// A write request (`IRP_MJ_WRITE`)
+    Write,
+    /// A device I/O control request (`IRP_MJ_DEVICE_CONTROL`)
+    DeviceControl,
+    /// An internal device I/O control request
+    /// (`IRP_MJ_INTERNAL_DEVICE_CONTROL`)
+    InternalDeviceControl,
+    /// A request to flush buffered data to the device
+    /// (`IRP_MJ_FLUSH_BUFFERS`)
+    FlushBuffers,
+    /// A request sent when the system is shutting down (`IRP_MJ_SHUTDOWN`)
+    Shutdown,
+    /// Any other type of request, with its raw `WDF_REQUEST_TYPE`
+    Other(WDF_REQUEST_TYPE),
+}
+
+impl RequestType {
+    const fn from_raw(request_type: WDF_REQUEST_TYPE) -> Self {
+        match request_type {
+            _WDF_REQUEST_TYPE::WdfRequestTypeCreate => Self::Create,
+            _WDF_REQUEST_TYPE::WdfRequestTypeClose => Self::Close,
+            _WDF_REQUEST_TYPE::WdfRequestTypeCleanup => Self::Cleanup,
+            _WDF_REQUEST_TYPE::WdfRequestTypeRead => Self::Read,
+            _WDF_REQUEST_TYPE::WdfRequestTypeWrite => Self::Write,
+            _WDF_REQUEST_TYPE::WdfRequestTypeDeviceControl => Self::DeviceControl,
+            _WDF_REQUEST_TYPE::WdfRequestTypeDeviceControlInternal => Self::InternalDeviceControl,
+            _WDF_REQUEST_TYPE::WdfRequestTypeFlushBuffers => Self::FlushBuffers,
+            _WDF_REQUEST_TYPE::WdfRequestTypeShutdown => Self::Shutdown,
+            _ => Self::Other(request_type),
+        }
+    }
+}
+
+/// The processor mode of the thread that sent a [`Request`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestorMode {
+    /// The request was sent by a kernel-mode component, and its buffers can be
+    /// trusted
+    Kernel,
+    /// The request was sent by a user-mode application, so its buffers and
+    /// parameters must be validated before they are used
+    User,
+}
+
+/// WDF Request.
+///
+/// A [`Request`] is passed to the driver by the request handlers of a
+/// [`Queue`](super::Queue), and represents an I/O request that the driver
+/// must complete exactly once. [`Request::complete()`] consumes the
+/// [`Request`], so completing a request twice does not compile. A [`Request`]
+/// can be stored to complete it later, but in debug builds, dropping a
+/// [`Request`] without completing it prints a message to the kernel debugger
+/// and breaks into it, since the request would otherwise never be completed.
+pub struct Request {
+    wdf_request: WDFREQUEST,
+    formatted: bool,
+}
+
+// SAFETY: `WDFREQUEST` handles can be used and completed from any thread.
+unsafe impl Send for Request {}
+
+// SAFETY: `Request` has no methods that mutate the request object through
+// `&self`.
+unsafe impl Sync for Request {}
+
+// SAFETY: The request object is valid until it is completed, which consumes
+// the `Request`.
+unsafe impl WdfObject for Request {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_request.cast()
+    }
+}
+
+impl Request {
+    /// Wrap an existing WDF Request object
+    ///
+    /// # Safety
+    ///
+    /// `wdf_request` must be a valid handle to a WDF Request object that is
+    /// owned by the driver and has not been completed, and that is not
+    /// completed other than through the returned [`Request`].
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_request: WDFREQUEST) -> Self {
+        Self {
+            wdf_request,
+            formatted: false,
+        }
+    }
+
+    /// Returns the raw `WDFREQUEST` handle wrapped by this [`Request`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFREQUEST {
+        self.wdf_request
+    }
+
+    /// Consumes the [`Request`] without completing it, returning the raw
+    /// `WDFREQUEST` handle. The caller becomes responsible for completing the
+    /// request.
+    #[must_use]
+    pub fn into_raw(self) -> WDFREQUEST {
+        ManuallyDrop::new(self).wdf_request
+    }
+
+    /// Complete the request with `nt_status`, and set the number of bytes it
+    /// transferred, or other request-specific information, to `information`
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn complete(self, nt_status: NTSTATUS, information: usize) {
+        let wdf_request = self.into_raw();
+        // SAFETY: The contract of `Request::from_raw` guarantees that `wdf_request` has
+        // not been completed. Completing it consumes the `Request`, so it is not used
+        // after this.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfRequestCompleteWithInformation,
+                wdf_request,
+                nt_status,
+                information as ULONG_PTR
+            );
+        }
+    }
+
+    /// Returns the completion status of the request, once it has been sent to
+    /// an I/O target and completed by it
+    #[must_use]
+    pub fn status(&self) -> NTSTATUS {
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            nt_status =
+                macros::call_unsafe_wdf_function_binding!(WdfRequestGetStatus, self.wdf_request);
+        }
+        nt_status
+    }
+
+    /// Returns the number of bytes the request transferred, or other
+    /// request-specific information, once it has been sent to an I/O target
+    /// and completed by it
+    #[must_use]
+    pub fn information(&self) -> usize {
+        let information;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            information = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestGetInformation,
+                self.wdf_request
+            );
+        }
+        // `ULONG_PTR` is pointer-sized, so it always fits in a `usize`
+        #[allow(clippy::cast_possible_truncation)]
+        let information = information as usize;
+        information
+    }
+
+    /// Format the request to be sent to an I/O target as the same type of
+    /// request, with the same parameters, that the driver received it as
+    ///
+    /// Requests that are sent without being formatted are formatted this way.
+    pub fn format_using_current_type(&mut self) {
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfRequestFormatRequestUsingCurrentType,
+                self.wdf_request
+            );
+        }
+        self.formatted = true;
+    }
+
+    /// Format the request as a read request for `target`, that reads into the
+    /// output buffer the request was received with
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no output buffer, or if WDF fails to format the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforread#return-value)
+    pub fn format_for_read(&mut self, target: &IoTarget) -> Result<(), NTSTATUS> {
+        let output_memory = self.output_memory()?;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `target` and `output_memory` are valid handles, and the buffer
+        // offset and device offset are optional.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForRead,
+                target.as_raw(),
+                self.wdf_request,
+                output_memory,
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+            );
+        }
+        self.formatted_with(nt_status)
+    }
+
+    /// Format the request as a write request for `target`, that writes from
+    /// the input buffer the request was received with
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no input buffer, or if WDF fails to format the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforwrite#return-value)
+    pub fn format_for_write(&mut self, target: &IoTarget) -> Result<(), NTSTATUS> {
+        let input_memory = self.input_memory()?;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `target` and `input_memory` are valid handles, and the buffer
+        // offset and device offset are optional.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForWrite,
+                target.as_raw(),
+                self.wdf_request,
+                input_memory,
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+            );
+        }
+        self.formatted_with(nt_status)
+    }
+
+    /// Format the request as a device I/O control request for `target`, for
+    /// `io_control_code`, with the input and output buffers the request was
+    /// received with, if it has them
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to format the request. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFIoTarget Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfiotarget/nf-wdfiotarget-wdfiotargetformatrequestforioctl#return-value)
+    pub fn format_for_ioctl(
+        &mut self,
+        target: &IoTarget,
+        io_control_code: ULONG,
+    ) -> Result<(), NTSTATUS> {
+        let input_memory = self.input_memory().unwrap_or(core::ptr::null_mut());
+        let output_memory = self.output_memory().unwrap_or(core::ptr::null_mut());
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `target` is a valid handle, and the memory objects and their
+        // offsets are optional.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfIoTargetFormatRequestForIoctl,
+                target.as_raw(),
+                self.wdf_request,
+                io_control_code,
+                input_memory,
+                core::ptr::null_mut(),
+                output_memory,
+                core::ptr::null_mut(),
+            );
+        }
+        self.formatted_with(nt_status)
+    }
+
+    /// Forward the request to `target`, without being notified when it is
+    /// completed
+    ///
+    /// The request is formatted using its current type, and ownership of it is
+    /// passed to `target`, which completes it. This is how filter drivers pass
+    /// requests that they do not handle down their driver stack.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to send the request, in which case the request is completed with the [`NTSTATUS`] of the failure, which the error variant will contain. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestsend#return-value)
+    pub fn forward_to(mut self, target: &IoTarget) -> Result<(), NTSTATUS> {
+        self.format_using_current_type();
+        let mut send_options = SendOptions::new().to_raw(SEND_AND_FORGET);
+        // SAFETY: `send_options` is valid for the duration of the call.
+        unsafe { self.send_with_options(target, &mut send_options) }
+    }
+
+    /// Send the request to `target` with `options`, and call `on_complete`
+    /// with the request once `target` completes it
+    ///
+    /// Requests that have not been formatted with one of the `format_*`
+    /// methods are formatted using their current type. If `options` has a
+    /// timeout, the request is canceled if `target` does not complete it in
+    /// time, and `on_complete` is called with the request once its
+    /// cancellation completes. `on_complete` is called
+    /// at `IRQL` <= `DISPATCH_LEVEL`, and must complete the request, or send it
+    /// again. It cannot capture any variables, since it is zero-sized to be
+    /// stored without an allocation, so state it needs must be stored in the
+    /// context of the request's queue or device.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to send the request, in which case `on_complete` is not called, and the request is completed with the [`NTSTATUS`] of the failure, which the error variant will contain. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestsend#return-value)
+    pub fn send<F>(
+        mut self,
+        target: &IoTarget,
+        options: SendOptions,
+        on_complete: F,
+    ) -> Result<(), NTSTATUS>
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        const {
+            assert!(
+                core::mem::size_of::<F>() == 0,
+                "request completion callbacks must not capture any variables"
+            );
+        }
+
+        if !self.formatted {
+            self.format_using_current_type();
+        }
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. The completion routine does not use its context.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfRequestSetCompletionRoutine,
+                self.wdf_request,
+                Some(evt_request_completion::<F>),
+                core::ptr::null_mut()
+            );
+        }
+        // `on_complete` is zero-sized, so `evt_request_completion` recreates it instead
+        // of it being stored anywhere
+        core::mem::forget(on_complete);
+        let mut send_options = options.to_raw(0);
+        // SAFETY: `send_options` is valid for the duration of the call.
+        unsafe { self.send_with_options(target, &mut send_options) }
+    }
+
+    /// Send the request to `target` with `send_options`, completing the request
+    /// if sending it fails
+    ///
+    /// # Safety
+    ///
+    /// `send_options` must be valid for the duration of the call.
+    unsafe fn send_with_options(
+        self,
+        target: &IoTarget,
+        send_options: *mut WDF_REQUEST_SEND_OPTIONS,
+    ) -> Result<(), NTSTATUS> {
+        let sent;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `target` is a valid handle, and the caller guarantees that
+        // `send_options` is valid.
+        unsafe {
+            sent = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestSend,
+                self.wdf_request,
+                target.as_raw(),
+                send_options
+            );
+        }
+        if sent == 0 {
+            let nt_status = self.status();
+            self.complete(nt_status, 0);
+            return Err(nt_status);
+        }
+
+        // Ownership of the request has passed to `target`
+        let _ = self.into_raw();
+        Ok(())
+    }
+
+    /// Returns the memory object of the request's input buffer
+    fn input_memory(&self) -> Result<WDFMEMORY, NTSTATUS> {
+        let mut memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `memory` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveInputMemory,
+                self.wdf_request,
+                &mut memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(memory)
+    }
+
+    /// Returns the memory object of the request's output buffer
+    fn output_memory(&self) -> Result<WDFMEMORY, NTSTATUS> {
+        let mut memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `memory` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveOutputMemory,
+                self.wdf_request,
+                &mut memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(memory)
+    }
+
+    /// Record that the request was formatted, if `nt_status` is a success
+    fn formatted_with(&mut self, nt_status: NTSTATUS) -> Result<(), NTSTATUS> {
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        self.formatted = true;
+        Ok(())
+    }
+
+    /// Mark the request as cancelable, so that `on_cancel` is called with the
+    /// request if it is canceled before it is completed
+    ///
+    /// The returned [`CancelableRequest`] cannot be completed, so
+    /// [`CancelableRequest::unmark_cancelable()`] must be called to get the
+    /// [`Request`] back before completing it. `on_cancel` is called at
+    /// `IRQL` <= `DISPATCH_LEVEL`, and must complete the request. It cannot
+    /// capture any variables, since WDF provides no storage for it, so state
+    /// it needs must be stored in the context of the request's queue or
+    /// device, which can be retrieved with [`Request::queue()`].
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the [`Request`] as the error if it has already been canceled, in which case `on_cancel` is not called, and the driver must complete the request with `STATUS_CANCELLED`. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestmarkcancelableex#return-value)
+    pub fn mark_cancelable<F>(self, on_cancel: F) -> Result<CancelableRequest, Self>
+    where
+        F: FnOnce(Self) + Send + 'static,
+    {
+        const {
+            assert!(
+                core::mem::size_of::<F>() == 0,
+                "request cancel callbacks must not capture any variables"
+            );
+        }
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestMarkCancelableEx,
+                self.wdf_request,
+                Some(evt_request_cancel::<F>)
+            );
+        }
+        if nt_status != STATUS_SUCCESS {
+            return Err(self);
+        }
+
+        // `on_cancel` is zero-sized, so `evt_request_cancel` recreates it instead of
+        // it being stored anywhere
+        core::mem::forget(on_cancel);
+        Ok(CancelableRequest { request: self })
+    }
+
+    /// Returns the queue that delivered the request to the driver, or [`None`]
+    /// if the request was not delivered by a queue
+    #[must_use]
+    pub fn queue(&self) -> Option<Queue> {
+        let wdf_queue;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            wdf_queue =
+                macros::call_unsafe_wdf_function_binding!(WdfRequestGetIoQueue, self.wdf_request);
+        }
+        if wdf_queue.is_null() {
+            return None;
+        }
+        // SAFETY: The queue of a request lives at least as long as its device, which
+        // outlives all of its requests.
+        Some(unsafe { Queue::from_raw(wdf_queue) })
+    }
+
+    /// Returns the file object that the request was sent through, or [`None`]
+    /// if it was not sent through a file object that the framework tracks
+    #[must_use]
+    pub fn file_object(&self) -> Option<FileObject> {
+        let wdf_file_object;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            wdf_file_object = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestGetFileObject,
+                self.wdf_request
+            );
+        }
+        if wdf_file_object.is_null() {
+            return None;
+        }
+        // SAFETY: A file object lives until all of the requests sent through it are
+        // completed.
+        Some(unsafe { FileObject::from_raw(wdf_file_object) })
+    }
+
+    /// Returns the type of I/O operation that the request represents
+    #[must_use]
+    pub fn request_type(&self) -> RequestType {
+        RequestType::from_raw(self.parameters().Type)
+    }
+
+    /// Returns the I/O control code of device I/O control and internal device
+    /// I/O control requests, or [`None`] for other types of requests
+    #[must_use]
+    pub fn io_control_code(&self) -> Option<ULONG> {
+        let parameters = self.parameters();
+        match RequestType::from_raw(parameters.Type) {
+            RequestType::DeviceControl | RequestType::InternalDeviceControl => {
+                // SAFETY: `Parameters.DeviceIoControl` is the active member of the union for
+                // device I/O control requests.
+                Some(unsafe { parameters.Parameters.DeviceIoControl.IoControlCode })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the processor mode of the thread that sent the request
+    #[must_use]
+    pub fn requestor_mode(&self) -> RequestorMode {
+        let requestor_mode;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed.
+        unsafe {
+            requestor_mode = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestGetRequestorMode,
+                self.wdf_request
+            );
+        }
+        if i32::from(requestor_mode) == _MODE::KernelMode {
+            RequestorMode::Kernel
+        } else {
+            RequestorMode::User
+        }
+    }
+
+    /// Returns the input buffer of the request, which must be at least
+    /// `minimum_length` bytes long
+    ///
+    /// The buffer is borrowed from the [`Request`], so it cannot be used after
+    /// the request is completed. For requests that use buffered I/O, the input
+    /// and output buffers are the same memory, so the input buffer cannot be
+    /// borrowed while the output buffer is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no input buffer, or if it is shorter than `minimum_length` bytes. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveinputbuffer#return-value)
+    pub fn input_buffer(&self, minimum_length: usize) -> Result<&[u8], NTSTATUS> {
+        let mut buffer: PVOID = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `buffer` and `length` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveInputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &mut buffer,
+                &mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: WDF returned a buffer of `length` bytes, which stays valid until the
+        // request is completed, which consumes the `Request` that the slice borrows.
+        Ok(unsafe { buffer_slice(buffer, length) })
+    }
+
+    /// Returns the output buffer of the request, which must be at least
+    /// `minimum_length` bytes long
+    ///
+    /// The buffer is borrowed from the [`Request`], so it cannot be used after
+    /// the request is completed. For requests that use buffered I/O, the input
+    /// and output buffers are the same memory, so the output buffer cannot be
+    /// borrowed while the input buffer is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no output buffer, or if it is shorter than `minimum_length` bytes. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveoutputbuffer#return-value)
+    pub fn output_buffer(&mut self, minimum_length: usize) -> Result<&mut [u8], NTSTATUS> {
+        let mut buffer: PVOID = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `buffer` and `length` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveOutputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &mut buffer,
+                &mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        // SAFETY: WDF returned a buffer of `length` bytes, which stays valid until the
+        // request is completed, which consumes the `Request` that the slice mutably
+        // borrows.
+        Ok(unsafe { buffer_slice_mut(buffer, length) })
+    }
+
+    /// Returns the input buffer of the request as a `T`
+    ///
+    /// # Safety
+    ///
+    /// Every bit pattern of `size_of::<T>()` bytes must be a valid `T`, since
+    /// the contents of the buffer are controlled by the sender of the request.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no input buffer, if the buffer is shorter than a `T`, or if it is not aligned for a `T`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveinputbuffer#return-value)
+    pub unsafe fn input_as_struct<T>(&self) -> Result<&T, NTSTATUS> {
+        let buffer = self.input_buffer(core::mem::size_of::<T>())?;
+        let buffer = struct_pointer::<T>(buffer.as_ptr().cast_mut())?;
+        // SAFETY: `buffer` is aligned and points to at least `size_of::<T>()` bytes
+        // borrowed from `self`, and the caller guarantees that they are a valid `T`.
+        Ok(unsafe { &*buffer })
+    }
+
+    /// Returns the output buffer of the request as a `T`
+    ///
+    /// # Safety
+    ///
+    /// Every bit pattern of `size_of::<T>()` bytes must be a valid `T`, since
+    /// the output buffer may contain data from the sender of the request.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no output buffer, if the buffer is shorter than a `T`, or if it is not aligned for a `T`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestretrieveoutputbuffer#return-value)
+    pub unsafe fn output_as_struct<T>(&mut self) -> Result<&mut T, NTSTATUS> {
+        let buffer = self.output_buffer(core::mem::size_of::<T>())?;
+        let buffer = struct_pointer::<T>(buffer.as_mut_ptr())?;
+        // SAFETY: `buffer` is aligned and points to at least `size_of::<T>()` bytes
+        // mutably borrowed from `self`, and the caller guarantees that they are a
+        // valid `T`.
+        Ok(unsafe { &mut *buffer })
+    }
+
+    /// Probe and lock the `METHOD_NEITHER` input buffer of the request, which
+    /// must be at least `minimum_length` bytes long, so that it can be read
+    /// after the request leaves the context of the thread that sent it
+    ///
+    /// This must only be called from the callback set with
+    /// [`DeviceBuilder::io_in_caller_context()`](super::DeviceBuilder::io_in_caller_context),
+    /// at `IRQL` = `PASSIVE_LEVEL`. The returned [`UserBuffer`] is typically
+    /// stored in a context of the request, and read with
+    /// [`Request::user_buffer()`] once the request is dispatched from its
+    /// queue.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no input buffer, if it is shorter than `minimum_length` bytes, or if it cannot be probed and locked. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestprobeandlockuserbufferforread#return-value)
+    pub fn lock_user_input_buffer(&self, minimum_length: usize) -> Result<UserBuffer, NTSTATUS> {
+        let mut buffer: PVOID = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `buffer` and `length` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveUnsafeUserInputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &mut buffer,
+                &mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let mut memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is valid, and `buffer` and `length` describe its
+        // unprobed input buffer, which the framework probes before locking it.
+        // `memory` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestProbeAndLockUserBufferForRead,
+                self.wdf_request,
+                buffer,
+                length,
+                &mut memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(UserBuffer {
+            wdf_request: self.wdf_request,
+            memory,
+        })
+    }
+
+    /// Probe and lock the `METHOD_NEITHER` output buffer of the request, which
+    /// must be at least `minimum_length` bytes long, so that it can be written
+    /// after the request leaves the context of the thread that sent it
+    ///
+    /// This must only be called from the callback set with
+    /// [`DeviceBuilder::io_in_caller_context()`](super::DeviceBuilder::io_in_caller_context),
+    /// at `IRQL` = `PASSIVE_LEVEL`. The returned [`UserBuffer`] is typically
+    /// stored in a context of the request, and written through
+    /// [`Request::user_buffer_mut()`] once the request is dispatched from its
+    /// queue.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has no output buffer, if it is shorter than `minimum_length` bytes, or if it cannot be probed and locked. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestprobeandlockuserbufferforwrite#return-value)
+    pub fn lock_user_output_buffer(&self, minimum_length: usize) -> Result<UserBuffer, NTSTATUS> {
+        let mut buffer: PVOID = core::ptr::null_mut();
+        let mut length = 0;
+
+        let nt_status;
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `buffer` and `length` are valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestRetrieveUnsafeUserOutputBuffer,
+                self.wdf_request,
+                minimum_length,
+                &mut buffer,
+                &mut length,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let mut memory: WDFMEMORY = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: `wdf_request` is valid, and `buffer` and `length` describe its
+        // unprobed output buffer, which the framework probes before locking it.
+        // `memory` is valid for the duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestProbeAndLockUserBufferForWrite,
+                self.wdf_request,
+                buffer,
+                length,
+                &mut memory,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        Ok(UserBuffer {
+            wdf_request: self.wdf_request,
+            memory,
+        })
+    }
+
+    /// Returns the contents of `buffer`, which was locked by
+    /// [`Request::lock_user_input_buffer()`] or
+    /// [`Request::lock_user_output_buffer()`], or [`None`] if it was locked for
+    /// a different request
+    ///
+    /// The buffer is borrowed from the [`Request`], so it cannot be used after
+    /// the request is completed. The thread that sent the request can still
+    /// modify the buffer while it is borrowed, so drivers should copy values
+    /// out of it before validating them.
+    #[must_use]
+    pub fn user_buffer(&self, buffer: UserBuffer) -> Option<&[u8]> {
+        let (data, length) = self.locked_buffer(buffer)?;
+        // SAFETY: The memory object is a child of the request, so its buffer of
+        // `length` bytes stays locked until the request is completed, which
+        // consumes the `Request` that the slice borrows.
+        Some(unsafe { buffer_slice(data, length) })
+    }
+
+    /// Returns the contents of `buffer`, which was locked by
+    /// [`Request::lock_user_input_buffer()`] or
+    /// [`Request::lock_user_output_buffer()`], as a mutable slice, or [`None`]
+    /// if it was locked for a different request
+    ///
+    /// The buffer is borrowed from the [`Request`], so it cannot be used after
+    /// the request is completed. The input and output buffers of
+    /// `METHOD_NEITHER` requests may be the same memory, so only one of them
+    /// can be borrowed mutably at a time.
+    #[must_use]
+    pub fn user_buffer_mut(&mut self, buffer: UserBuffer) -> Option<&mut [u8]> {
+        let (data, length) = self.locked_buffer(buffer)?;
+        // SAFETY: The memory object is a child of the request, so its buffer of
+        // `length` bytes stays locked until the request is completed, which
+        // consumes the `Request` that the slice mutably borrows.
+        Some(unsafe { buffer_slice_mut(data, length) })
+    }
+
+    /// Returns the address and length of the locked buffer of `buffer`, if it
+    /// was locked for this request
+    fn locked_buffer(&self, buffer: UserBuffer) -> Option<(PVOID, usize)> {
+        if buffer.wdf_request != self.wdf_request {
+            return None;
+        }
+        let mut length = 0;
+
+        let data;
+        // SAFETY: `buffer.memory` was created as a child of this request, which has not
+        // been completed, so it is still valid. `length` is valid for the duration of
+        // the call.
+        unsafe {
+            data = macros::call_unsafe_wdf_function_binding!(
+                WdfMemoryGetBuffer,
+                buffer.memory,
+                &mut length
+            );
+        }
+        Some((data, length))
+    }
+
+    /// Returns the `WDF_REQUEST_PARAMETERS` of the request, initialized the
+    /// same way as by `WDF_REQUEST_PARAMETERS_INIT` before they are retrieved
+    fn parameters(&self) -> WDF_REQUEST_PARAMETERS {
+        let mut parameters = WDF_REQUEST_PARAMETERS {
+            Size: REQUEST_PARAMETERS_SIZE,
+            ..Default::default()
+        };
+        // SAFETY: `wdf_request` is a private member of `Request`, and the contract of
+        // `Request::from_raw` guarantees that it is valid until the `Request` is
+        // consumed. `parameters` is valid for the duration of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfRequestGetParameters,
+                self.wdf_request,
+                &mut parameters
+            );
+        }
+        parameters
+    }
+}
+
+/// A probed and locked `METHOD_NEITHER` buffer of a [`Request`].
+///
+/// [`UserBuffer`]s are returned by [`Request::lock_user_input_buffer()`] and
+/// [`Request::lock_user_output_buffer()`] in the context of the thread that
+/// sent the request. A [`UserBuffer`] is only a handle to the locked buffer,
+/// which stays locked until its request is completed. Its contents are accessed
+/// through the request with [`Request::user_buffer()`] and
+/// [`Request::user_buffer_mut()`].
+#[derive(Clone, Copy)]
+pub struct UserBuffer {
+    wdf_request: WDFREQUEST,
+    memory: WDFMEMORY,
+}
+
+// SAFETY: `UserBuffer` only holds handles, which can be used from any thread,
+// and its buffer is only accessed through its `Request`.
+unsafe impl Send for UserBuffer {}
+
+// SAFETY: `UserBuffer` has no methods that access its buffer.
+unsafe impl Sync for UserBuffer {}
+
+/// A [`Request`] that is marked as cancelable, returned by
+/// [`Request::mark_cancelable()`].
+///
+/// A [`CancelableRequest`] cannot be completed, since completing a request
+/// that is still marked as cancelable would race with its cancellation. Call
+/// [`CancelableRequest::unmark_cancelable()`] to get the [`Request`] back once
+/// the operation it is waiting on finishes.
+///
+/// The [`Request`] cannot be accessed until then either, since the framework
+/// may call the cancel callback at any time, which completes the request and
+/// frees its handle. Anything the driver needs from the request, such as its
+/// parameters, buffers or queue, must be retrieved before it is marked as
+/// cancelable.
+pub struct CancelableRequest {
+    request: Request,
+}
+
+impl CancelableRequest {
+    /// Unmark the request as cancelable, returning the [`Request`] so that it
+    /// can be completed
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request has been canceled, in which case the cancel callback passed to [`Request::mark_cancelable()`] owns the request and completes it. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFRequest Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfrequest/nf-wdfrequest-wdfrequestunmarkcancelable#return-value)
+    pub fn unmark_cancelable(self) -> Result<Request, NTSTATUS> {
+        let nt_status;
+        // SAFETY: `request` is a private member of `CancelableRequest`, which is only
+        // constructed by `Request::mark_cancelable()` for requests that are marked
+        // as cancelable.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfRequestUnmarkCancelable,
+                self.request.wdf_request
+            );
+        }
+        if !nt_success(nt_status) {
+            // The cancel callback has been or is about to be called with the request, and
+            // completes it
+            let _ = self.request.into_raw();
+            return Err(nt_status);
+        }
+        Ok(self.request)
+    }
+}
+
+/// The completion routine of requests sent by [`Request::send()`]
+///
+/// # Safety
+///
+/// `wdf_request` must be a valid handle to a request that was sent with an
+/// `F` as its completion callback, which must be zero-sized.
+unsafe extern "C" fn evt_request_completion<F: FnOnce(Request)>(
+    wdf_request: WDFREQUEST,
+    _target: WDFIOTARGET,
+    _params: *mut WDF_REQUEST_COMPLETION_PARAMS,
+    _context: WDFCONTEXT,
+) {
+    // SAFETY: `F` is zero-sized, so reading it from a dangling pointer is valid,
+    // and `Request::send()` forgot the `F` it was passed, so this recreates the
+    // value that it moved into the framework.
+    let on_complete = unsafe { NonNull::<F>::dangling().read() };
+    // SAFETY: The framework only calls this with a request that the I/O target
+    // completed, which is owned by the driver again.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    on_complete(request);
+}
+
+/// The `EvtRequestCancel` of requests marked as cancelable by
+/// [`Request::mark_cancelable()`]
+///
+/// # Safety
+///
+/// `wdf_request` must be a valid handle to a request that was marked as
+/// cancelable with an `F`, which must be zero-sized.
+unsafe extern "C" fn evt_request_cancel<F: FnOnce(Request)>(wdf_request: WDFREQUEST) {
+    // SAFETY: `F` is zero-sized, so reading it from a dangling pointer is valid,
+    // and `Request::mark_cancelable()` forgot the `F` it was passed, so this
+    // recreates the value that it moved into the framework.
+    let on_cancel = unsafe { NonNull::<F>::dangling().read() };
+    // SAFETY: The framework only calls this with a request that was canceled while
+    // it was marked as cancelable, which the driver then owns and must complete.
+    let request = unsafe { Request::from_raw(wdf_request) };
+    on_cancel(request);
+}
+
+/// Returns `buffer` as a pointer to a `T`, if it is aligned for a `T`
+fn struct_pointer<T>(buffer: *mut u8) -> Result<*mut T, NTSTATUS> {
+    let buffer = buffer.cast::<T>();
+    if !buffer.is_aligned() {
+        return Err(STATUS_DATATYPE_MISALIGNMENT);
+    }
+    Ok(buffer)
+}
+
+/// Returns the `length` bytes at `buffer` as a slice, or an empty slice if
+/// `buffer` is null
+///
+/// # Safety
+///
+/// If `buffer` is not null, it must point to `length` bytes that are valid for
+/// reads for `'a`, and that are not mutated during `'a`.
+unsafe fn buffer_slice<'a>(buffer: PVOID, length: usize) -> &'a [u8] {
+    if buffer.is_null() {
+        return &[];
+    }
+    // SAFETY: The caller guarantees that `buffer` points to `length` bytes that
+    // are valid for `'a`.
+    unsafe { core::slice::from_raw_parts(buffer.cast::<u8>(), length) }
+}
+
+/// Returns the `length` bytes at `buffer` as a mutable slice, or an empty slice
+/// if `buffer` is null
+///
+/// # Safety
+///
+/// If `buffer` is not null, it must point to `length` bytes that are valid for
+/// reads and writes for `'a`, and that are not accessed in any other way
+/// during `'a`.
+unsafe fn buffer_slice_mut<'a>(buffer: PVOID, length: usize) -> &'a mut [u8] {
+    if buffer.is_null() {
+        return &mut [];
+    }
+    // SAFETY: The caller guarantees that `buffer` points to `length` bytes that
+    // are exclusively valid for `'a`.
+    unsafe { core::slice::from_raw_parts_mut(buffer.cast::<u8>(), length) }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for Request {
+    fn drop(&mut self) {
+        // SAFETY: The format string is null-terminated, and its specifier matches the
+        // type of the argument.
+        unsafe {
+            wdk_sys::ntddk::DbgPrint(
+                c"wdk: request %p was dropped without being completed\n".as_ptr(),
+                self.wdf_request,
+            );
+        }
+        crate::dbg_break();
+    }
+}
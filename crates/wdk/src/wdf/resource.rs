@@ -0,0 +1,330 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::marker::PhantomData;
+
+use wdk_sys::{
+    macros,
+    CM_PARTIAL_RESOURCE_DESCRIPTOR,
+    CM_RESOURCE_INTERRUPT_MESSAGE,
+    CM_RESOURCE_MEMORY_LARGE_40,
+    CM_RESOURCE_MEMORY_LARGE_48,
+    CM_RESOURCE_MEMORY_LARGE_64,
+    KAFFINITY,
+    PHYSICAL_ADDRESS,
+    WDFCMRESLIST,
+};
+
+/// The hardware resources assigned to a [`Device`](super::Device).
+///
+/// Resource lists are passed to
+/// [`PnpPowerCallbacks::prepare_hardware()`](super::PnpPowerCallbacks::prepare_hardware)
+/// and [`PnpPowerCallbacks::release_hardware()`](super::PnpPowerCallbacks::release_hardware).
+/// A [`ResourceList`] is only valid for the duration of the callback that it
+/// is passed to, which its lifetime `'a` is bound to. Iterating over it
+/// yields a typed [`Resource`] for each of its descriptors.
+///
+/// ```ignore
+/// for resource in resources_translated {
+///     if let Resource::Memory { base, length, .. } = resource {
+///         // Map the device's registers
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct ResourceList<'a> {
+    wdf_cm_res_list: WDFCMRESLIST,
+    translated: bool,
+    callback: PhantomData<&'a ()>,
+}
+
+impl ResourceList<'_> {
+    /// Wrap an existing WDF resource list
+    ///
+    /// # Safety
+    ///
+    /// `wdf_cm_res_list` must be a valid handle to a WDF resource list, and
+    /// must remain valid for the lifetime of the returned [`ResourceList`].
+    /// `translated` must be whether it is a list of translated resources.
+    #[must_use]
+    pub const unsafe fn from_raw(wdf_cm_res_list: WDFCMRESLIST, translated: bool) -> Self {
+        Self {
+            wdf_cm_res_list,
+            translated,
+            callback: PhantomData,
+        }
+    }
+
+    /// Returns the raw `WDFCMRESLIST` handle wrapped by this [`ResourceList`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFCMRESLIST {
+        self.wdf_cm_res_list
+    }
+
+    /// Returns whether the list contains the resources as seen by the
+    /// processor, rather than as seen by the device's bus
+    #[must_use]
+    pub const fn is_translated(&self) -> bool {
+        self.translated
+    }
+
+    /// Returns the number of resource descriptors in the list
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let count;
+        // SAFETY: `wdf_cm_res_list` is a private member of `ResourceList`, which is
+        // only used while the resource list is valid.
+        unsafe {
+            count = macros::call_unsafe_wdf_function_binding!(
+                WdfCmResourceListGetCount,
+                self.wdf_cm_res_list
+            );
+        }
+        count as usize
+    }
+
+    /// Returns whether the list has no resource descriptors
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a> ResourceList<'a> {
+    /// Returns the resource descriptor at `index` in the list, or `None` if
+    /// `index` is out of bounds
+    #[must_use]
+    pub fn descriptor(&self, index: usize) -> Option<&'a CM_PARTIAL_RESOURCE_DESCRIPTOR> {
+        let index = index.try_into().ok()?;
+
+        let descriptor;
+        // SAFETY: `wdf_cm_res_list` is a private member of `ResourceList`, which is
+        // only used while the resource list is valid. Out of bounds indices
+        // return null.
+        unsafe {
+            descriptor = macros::call_unsafe_wdf_function_binding!(
+                WdfCmResourceListGetDescriptor,
+                self.wdf_cm_res_list,
+                index
+            );
+        }
+        // SAFETY: Non-null descriptors are part of the resource list, which outlives
+        // `'a`.
+        unsafe { descriptor.as_ref() }
+    }
+
+    /// Returns an iterator over the resources in the list
+    pub const fn iter(&self) -> Resources<'a> {
+        Resources {
+            resource_list: *self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a> IntoIterator for ResourceList<'a> {
+    type IntoIter = Resources<'a>;
+    type Item = Resource;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &ResourceList<'a> {
+    type IntoIter = Resources<'a>;
+    type Item = Resource;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the resources of a [`ResourceList`], returned by
+/// [`ResourceList::iter()`]
+#[must_use]
+pub struct Resources<'a> {
+    resource_list: ResourceList<'a>,
+    index: usize,
+}
+
+impl Iterator for Resources<'_> {
+    type Item = Resource;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let descriptor = self.resource_list.descriptor(self.index)?;
+        self.index += 1;
+        Some(Resource::from_descriptor(
+            descriptor,
+            self.resource_list.translated,
+        ))
+    }
+}
+
+/// A hardware resource assigned to a device, decoded from a
+/// `CM_PARTIAL_RESOURCE_DESCRIPTOR`.
+///
+/// `flags` are the `CM_RESOURCE_*` flags of the descriptor for its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// A range of I/O ports (`CmResourceTypePort`)
+    Port {
+        /// The address of the first port
+        base: u64,
+        /// The number of ports
+        length: u32,
+        /// The `CM_RESOURCE_PORT_*` flags of the range
+        flags: u16,
+    },
+    /// A range of memory, such as device registers (`CmResourceTypeMemory`
+    /// and `CmResourceTypeMemoryLarge`)
+    Memory {
+        /// The physical address of the range
+        base: u64,
+        /// The length of the range, in bytes
+        length: u64,
+        /// The `CM_RESOURCE_MEMORY_*` flags of the range
+        flags: u16,
+    },
+    /// An interrupt (`CmResourceTypeInterrupt`). Message-signaled interrupts
+    /// have this form in translated resource lists.
+    Interrupt {
+        /// The interrupt's level, which is its `DIRQL` in translated resource
+        /// lists
+        level: u32,
+        /// The interrupt's vector
+        vector: u32,
+        /// The processors that the interrupt can be delivered to
+        affinity: KAFFINITY,
+        /// The `CM_RESOURCE_INTERRUPT_*` flags of the interrupt
+        flags: u16,
+    },
+    /// Message-signaled interrupts (MSI or MSI-X), in a raw resource list
+    /// (`CmResourceTypeInterrupt` with `CM_RESOURCE_INTERRUPT_MESSAGE`)
+    MessageInterrupt {
+        /// The number of messages allocated to the device
+        message_count: u16,
+        /// The vector of the first message
+        vector: u32,
+        /// The processors that the messages can be delivered to
+        affinity: KAFFINITY,
+        /// The `CM_RESOURCE_INTERRUPT_*` flags of the interrupts
+        flags: u16,
+    },
+    /// A DMA channel (`CmResourceTypeDma`)
+    Dma {
+        /// The DMA channel number
+        channel: u32,
+        /// The DMA port number
+        port: u32,
+        /// The `CM_RESOURCE_DMA_*` flags of the channel
+        flags: u16,
+    },
+    /// A range of bus numbers (`CmResourceTypeBusNumber`)
+    BusNumber {
+        /// The first bus number
+        start: u32,
+        /// The number of buses
+        length: u32,
+    },
+    /// A resource of another type, whose descriptor can be read with
+    /// [`ResourceList::descriptor()`]
+    Other {
+        /// The `CmResourceType*` of the resource
+        resource_type: u8,
+        /// The flags of the descriptor
+        flags: u16,
+    },
+}
+
+impl Resource {
+    /// Decode `descriptor`, from a translated resource list if `translated`
+    fn from_descriptor(descriptor: &CM_PARTIAL_RESOURCE_DESCRIPTOR, translated: bool) -> Self {
+        let flags = descriptor.Flags;
+        // The `CmResourceType*` constants are qualified, since single-segment patterns
+        // are linted for not being upper case
+        match u32::from(descriptor.Type) {
+            wdk_sys::CmResourceTypePort => {
+                // SAFETY: `Port` is the member of `u` used by port descriptors.
+                let port = unsafe { descriptor.u.Port };
+                Self::Port {
+                    base: physical_address(port.Start),
+                    length: port.Length,
+                    flags,
+                }
+            }
+            wdk_sys::CmResourceTypeMemory | wdk_sys::CmResourceTypeMemoryLarge => {
+                // SAFETY: `Memory` and the large memory members of `u` all start with the
+                // address of the range, followed by its encoded length.
+                let memory = unsafe { descriptor.u.Memory };
+                let length = u64::from(memory.Length);
+                let length = match u32::from(flags) {
+                    flags if flags & CM_RESOURCE_MEMORY_LARGE_40 != 0 => length << 8,
+                    flags if flags & CM_RESOURCE_MEMORY_LARGE_48 != 0 => length << 16,
+                    flags if flags & CM_RESOURCE_MEMORY_LARGE_64 != 0 => length << 32,
+                    _ => length,
+                };
+                Self::Memory {
+                    base: physical_address(memory.Start),
+                    length,
+                    flags,
+                }
+            }
+            wdk_sys::CmResourceTypeInterrupt
+                if !translated && u32::from(flags) & CM_RESOURCE_INTERRUPT_MESSAGE != 0 =>
+            {
+                // SAFETY: `MessageInterrupt` is the member of `u` used by message-signaled
+                // interrupt descriptors.
+                let message_interrupt = unsafe { descriptor.u.MessageInterrupt };
+                // SAFETY: `Raw` is the member used by raw message-signaled interrupt
+                // descriptors.
+                let message_interrupt = unsafe { message_interrupt.__bindgen_anon_1.Raw };
+                Self::MessageInterrupt {
+                    message_count: message_interrupt.MessageCount,
+                    vector: message_interrupt.Vector,
+                    affinity: message_interrupt.Affinity,
+                    flags,
+                }
+            }
+            wdk_sys::CmResourceTypeInterrupt => {
+                // SAFETY: `Interrupt` is the member of `u` used by line-based interrupt
+                // descriptors, and has the same layout as the member used by translated
+                // message-signaled interrupt descriptors.
+                let interrupt = unsafe { descriptor.u.Interrupt };
+                Self::Interrupt {
+                    level: interrupt.Level,
+                    vector: interrupt.Vector,
+                    affinity: interrupt.Affinity,
+                    flags,
+                }
+            }
+            wdk_sys::CmResourceTypeDma => {
+                // SAFETY: `Dma` is the member of `u` used by DMA descriptors.
+                let dma = unsafe { descriptor.u.Dma };
+                Self::Dma {
+                    channel: dma.Channel,
+                    port: dma.Port,
+                    flags,
+                }
+            }
+            wdk_sys::CmResourceTypeBusNumber => {
+                // SAFETY: `BusNumber` is the member of `u` used by bus number descriptors.
+                let bus_number = unsafe { descriptor.u.BusNumber };
+                Self::BusNumber {
+                    start: bus_number.Start,
+                    length: bus_number.Length,
+                }
+            }
+            _ => Self::Other {
+                resource_type: descriptor.Type,
+                flags,
+            },
+        }
+    }
+}
+
+/// Returns `address` as an unsigned integer
+fn physical_address(address: PHYSICAL_ADDRESS) -> u64 {
+    // SAFETY: Every bit pattern of a `PHYSICAL_ADDRESS` is a valid `QuadPart`.
+    unsafe { address.QuadPart }.cast_unsigned()
+}
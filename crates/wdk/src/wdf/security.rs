@@ -0,0 +1,213 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::ops::BitOr;
+
+/// The maximum number of access control entries in an [`Sddl`]
+const MAX_ACES: usize = 8;
+
+/// The length of the longest access control entry, `(A;;GRGWGX;;;XX)`
+const MAX_ACE_LENGTH: usize = 16;
+
+/// The prefix of every [`Sddl`], a protected DACL
+const PROTECTED_DACL: &[u8] = b"D:P";
+
+/// The maximum length of an [`Sddl`]
+const MAX_SDDL_LENGTH: usize = PROTECTED_DACL.len() + MAX_ACES * MAX_ACE_LENGTH;
+
+/// A well-known security principal that an [`Sddl`] grants access to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trustee {
+    /// The operating system (`SY`)
+    System,
+    /// The built-in Administrators group (`BA`)
+    Administrators,
+    /// The built-in Users group (`BU`)
+    Users,
+    /// Users that were authenticated (`AU`)
+    AuthenticatedUsers,
+    /// Every user, including anonymous users (`WD`)
+    Everyone,
+    /// Code running with a restricted token (`RC`)
+    RestrictedCode,
+    /// The local service account (`LS`)
+    LocalService,
+    /// The network service account (`NS`)
+    NetworkService,
+}
+
+impl Trustee {
+    const fn sid_string(self) -> &'static [u8] {
+        match self {
+            Self::System => b"SY",
+            Self::Administrators => b"BA",
+            Self::Users => b"BU",
+            Self::AuthenticatedUsers => b"AU",
+            Self::Everyone => b"WD",
+            Self::RestrictedCode => b"RC",
+            Self::LocalService => b"LS",
+            Self::NetworkService => b"NS",
+        }
+    }
+}
+
+/// The generic access rights that an [`Sddl`] grants a [`Trustee`].
+///
+/// Rights can be combined with `|`, or with [`Access::union()`] in constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access(u8);
+
+impl Access {
+    /// All access rights (`GA`)
+    pub const ALL: Self = Self(1 << 3);
+    /// Execute access (`GX`)
+    pub const EXECUTE: Self = Self(1 << 2);
+    /// Read access (`GR`)
+    pub const READ: Self = Self(1 << 0);
+    /// Write access (`GW`)
+    pub const WRITE: Self = Self(1 << 1);
+
+    /// Returns the access rights of both `self` and `other`
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns whether every right of `other` is granted by `self`
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Access {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.union(rhs)
+    }
+}
+
+/// A security descriptor of a device object, in Security Descriptor
+/// Definition Language.
+///
+/// An [`Sddl`] is a protected DACL that only grants the access listed with
+/// [`Sddl::allow()`], built at compile time so that device object ACLs do not
+/// need to be written by hand. It is assigned to a device with
+/// [`DeviceBuilder::security_descriptor()`](super::DeviceBuilder::security_descriptor).
+/// The constants of [`Sddl`] are the `SDDL_DEVOBJ_*` strings of `wdmsec.h`.
+///
+/// ```ignore
+/// const SDDL: Sddl = Sddl::KERNEL_ONLY
+///     .allow(Trustee::System, Access::ALL)
+///     .allow(Trustee::Administrators, Access::READ.union(Access::WRITE));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Sddl {
+    buffer: [u8; MAX_SDDL_LENGTH],
+    length: usize,
+    aces: usize,
+}
+
+impl Sddl {
+    /// Grants no access, so that only kernel-mode code can open the device
+    /// (`SDDL_DEVOBJ_KERNEL_ONLY`)
+    pub const KERNEL_ONLY: Self = {
+        let mut sddl = Self {
+            buffer: [0; MAX_SDDL_LENGTH],
+            length: 0,
+            aces: 0,
+        };
+        sddl.push(PROTECTED_DACL);
+        sddl
+    };
+    /// Grants all access to the operating system (`SDDL_DEVOBJ_SYS_ALL`)
+    pub const SYS_ALL: Self = Self::KERNEL_ONLY.allow(Trustee::System, Access::ALL);
+    /// Grants all access to the operating system and administrators
+    /// (`SDDL_DEVOBJ_SYS_ALL_ADM_ALL`)
+    pub const SYS_ALL_ADM_ALL: Self = Self::SYS_ALL.allow(Trustee::Administrators, Access::ALL);
+    /// Grants all access to the operating system, read, write and execute
+    /// access to administrators, and read access to everyone
+    /// (`SDDL_DEVOBJ_SYS_ALL_ADM_RWX_WORLD_R`)
+    pub const SYS_ALL_ADM_RWX_WORLD_R: Self = Self::SYS_ALL
+        .allow(Trustee::Administrators, RWX)
+        .allow(Trustee::Everyone, Access::READ);
+    /// Grants all access to the operating system, and read, write and execute
+    /// access to administrators, everyone and restricted code
+    /// (`SDDL_DEVOBJ_SYS_ALL_ADM_RWX_WORLD_RWX_RES_RWX`)
+    pub const SYS_ALL_ADM_RWX_WORLD_RWX_RES_RWX: Self = Self::SYS_ALL
+        .allow(Trustee::Administrators, RWX)
+        .allow(Trustee::Everyone, RWX)
+        .allow(Trustee::RestrictedCode, RWX);
+    /// Grants all access to the operating system, read, write and execute
+    /// access to administrators, read and write access to everyone, and read
+    /// access to restricted code (`SDDL_DEVOBJ_SYS_ALL_ADM_RWX_WORLD_RW_RES_R`)
+    pub const SYS_ALL_ADM_RWX_WORLD_RW_RES_R: Self = Self::SYS_ALL
+        .allow(Trustee::Administrators, RWX)
+        .allow(Trustee::Everyone, Access::READ.union(Access::WRITE))
+        .allow(Trustee::RestrictedCode, Access::READ);
+    /// Grants all access to the operating system, and read and execute access
+    /// to administrators (`SDDL_DEVOBJ_SYS_ALL_ADM_RX`)
+    pub const SYS_ALL_ADM_RX: Self =
+        Self::SYS_ALL.allow(Trustee::Administrators, Access::READ.union(Access::EXECUTE));
+
+    /// Grant `access` to `trustee`, in addition to the access already granted
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Sddl`] already grants access to 8 trustees. Building
+    /// the [`Sddl`] as a constant turns this into a compile error.
+    #[must_use]
+    pub const fn allow(mut self, trustee: Trustee, access: Access) -> Self {
+        assert!(
+            self.aces < MAX_ACES,
+            "an `Sddl` can grant access to at most 8 trustees"
+        );
+        self.aces += 1;
+
+        self.push(b"(A;;");
+        if access.contains(Access::ALL) {
+            self.push(b"GA");
+        } else {
+            if access.contains(Access::READ) {
+                self.push(b"GR");
+            }
+            if access.contains(Access::WRITE) {
+                self.push(b"GW");
+            }
+            if access.contains(Access::EXECUTE) {
+                self.push(b"GX");
+            }
+        }
+        self.push(b";;;");
+        self.push(trustee.sid_string());
+        self.push(b")");
+        self
+    }
+
+    /// Returns the SDDL string (ex. `D:P(A;;GA;;;SY)`)
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // `Sddl`s only ever contain the ASCII strings pushed by its constructors
+        core::str::from_utf8(&self.buffer[..self.length]).unwrap_or_default()
+    }
+
+    /// Append `bytes` to the SDDL string
+    const fn push(&mut self, bytes: &[u8]) {
+        let mut index = 0;
+        while index < bytes.len() {
+            self.buffer[self.length] = bytes[index];
+            self.length += 1;
+            index += 1;
+        }
+    }
+}
+
+impl core::fmt::Debug for Sddl {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Sddl").field(&self.as_str()).finish()
+    }
+}
+
+/// Read, write and execute access
+const RWX: Access = Access::READ.union(Access::WRITE).union(Access::EXECUTE);
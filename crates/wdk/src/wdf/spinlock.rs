@@ -1,6 +1,16 @@
-use wdk_sys::{macros, NTSTATUS, WDFSPINLOCK, WDF_OBJECT_ATTRIBUTES};
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
 
-use crate::nt_success;
+use wdk_sys::{macros, NTSTATUS, WDFSPINLOCK};
+
+#[cfg(feature = "lock-order-checks")]
+use super::context::attach_cleanup_callback;
+use super::{child::ChildObject, ObjectAttributes};
+use crate::{debug_assert_irql, lock_order, nt_success};
 
 /// WDF Spin Lock.
 ///
@@ -13,21 +23,53 @@
 /// the context space is writable and if more than one of the driver's event
 /// callback functions access the space. Before a driver can use a framework
 /// spin lock it must call [`SpinLock::try_new()`] to create a [`SpinLock`]. The
-/// driver can then call [`SpinLock::acquire`] to acquire the lock and
-/// [`SpinLock::release()`] to release it.
-pub struct SpinLock {
+/// driver can then call [`SpinLock::acquire()`] to acquire the lock, which
+/// returns a [`SpinLockGuard`] that releases the lock when it is dropped, or
+/// use [`SpinLock::with_lock()`] or [`SpinLock::with_lock_mut()`] to hold the
+/// lock for the duration of a closure.
+///
+/// A [`SpinLock`] owns the data of type `T` that it protects, and the data is
+/// only accessible through the [`SpinLockGuard`] returned by
+/// [`SpinLock::acquire()`]. This makes it impossible to access the protected
+/// data without holding the lock. A [`SpinLock<()>`] can be used when the lock
+/// only needs to guard a critical section rather than specific data.
+///
+/// Releasing the lock is tied to dropping its [`SpinLockGuard`], so releasing
+/// a lock that is not held, or releasing a lock twice, cannot be expressed.
+/// WDF spin locks are not recursive: calling [`SpinLock::acquire()`] on a lock
+/// that is already held by the current thread will deadlock.
+///
+/// The lifetime `'p` is bounded by the parent object set in the
+/// [`ObjectAttributes`] the lock is constructed with, and the WDF spin lock
+/// object is deleted when the [`SpinLock`] is dropped. Use
+/// [`SpinLock::into_parent_owned()`] to leave it to be deleted with its parent
+/// instead.
+// `wdf_spin_lock` is named consistently with the handles of the other wrappers
+#[allow(clippy::struct_field_names)]
+pub struct SpinLock<'p, T = ()> {
     wdf_spin_lock: WDFSPINLOCK,
+    data: UnsafeCell<T>,
+    object: ChildObject<'p>,
 }
-impl SpinLock {
-    /// Try to construct a WDF Spin Lock object
+
+// SAFETY: `WDFSPINLOCK` handles can be used from any thread, and the protected
+// data is only ever accessed by whoever holds the lock. Sending the `SpinLock`
+// to another thread sends the owned `T` with it, so `T` must be `Send`.
+unsafe impl<T: Send> Send for SpinLock<'_, T> {}
+
+// SAFETY: `WdfSpinLockAcquire` guarantees that only one thread at a time can
+// hold a `SpinLockGuard` and therefore access the protected data, so sharing a
+// `SpinLock` between threads is equivalent to sending `T` between them.
+unsafe impl<T: Send> Sync for SpinLock<'_, T> {}
+
+impl<'p, T> SpinLock<'p, T> {
+    /// Try to construct a WDF Spin Lock object protecting `data`
     ///
     /// # Errors
     ///
     /// This function will return an error if WDF fails to contruct a timer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFSpinLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfspinlockcreate#return-value)
-    pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
-        let mut spin_lock = Self {
-            wdf_spin_lock: core::ptr::null_mut(),
-        };
+    pub fn try_new(data: T, mut attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        let mut wdf_spin_lock: WDFSPINLOCK = core::ptr::null_mut();
 
         let nt_status;
         // SAFETY: The resulting ffi object is stored in a private member and not
@@ -36,38 +78,207 @@ pub fn try_new(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS>
         unsafe {
             nt_status = macros::call_unsafe_wdf_function_binding!(
                 WdfSpinLockCreate,
-                attributes,
-                &mut spin_lock.wdf_spin_lock,
+                attributes.as_raw_mut(),
+                &mut wdf_spin_lock,
             );
         }
-        nt_success(nt_status).then_some(spin_lock).ok_or(nt_status)
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let spin_lock = Self {
+            wdf_spin_lock,
+            data: UnsafeCell::new(data),
+            // SAFETY: `wdf_spin_lock` is a valid handle to the spin lock that was just created,
+            // which is only deleted by this `ChildObject`, and whose parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_spin_lock.cast()) },
+        };
+
+        // WDF reuses the handles of deleted spin locks, so the lock orderings recorded
+        // for this one are forgotten when it is deleted, including along with its
+        // parent after `SpinLock::into_parent_owned()`
+        #[cfg(feature = "lock-order-checks")]
+        // SAFETY: `wdf_spin_lock` is a valid handle to the spin lock that was just
+        // created.
+        unsafe {
+            attach_cleanup_callback(wdf_spin_lock.cast(), move || {
+                lock_order::forget(wdf_spin_lock.cast());
+            })?;
+        }
+        Ok(spin_lock)
     }
 
-    /// Try to construct a WDF Spin Lock object. This is an alias for
-    /// [`SpinLock::try_new()`]
+    /// Try to construct a WDF Spin Lock object protecting `data`. This is an
+    /// alias for [`SpinLock::try_new()`]
     ///
     /// # Errors
     ///
     /// This function will return an error if WDF fails to contruct a timer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFSpinLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfspinlockcreate#return-value)
-    pub fn create(attributes: &mut WDF_OBJECT_ATTRIBUTES) -> Result<Self, NTSTATUS> {
-        Self::try_new(attributes)
+    pub fn create(data: T, attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        Self::try_new(data, attributes)
     }
 
     /// Acquire the spinlock
-    pub fn acquire(&self) {
+    ///
+    /// The returned [`SpinLockGuard`] keeps the lock held, and the calling
+    /// thread at `DISPATCH_LEVEL`, until it is dropped. Dropping the guard
+    /// releases the lock and restores the previous `IRQL`.
+    #[must_use = "if unused the SpinLock will immediately be released"]
+    pub fn acquire(&self) -> SpinLockGuard<'_, T> {
+        debug_assert_irql!(<= DISPATCH_LEVEL, "spin locks must be acquired");
+        lock_order::check_acquire(self.wdf_spin_lock.cast());
         // SAFETY: `wdf_spin_lock` is a private member of `SpinLock`, originally created
         // by WDF, and this module guarantees that it is always in a valid state.
         unsafe {
             macros::call_unsafe_wdf_function_binding!(WdfSpinLockAcquire, self.wdf_spin_lock);
         }
+        lock_order::acquired(self.wdf_spin_lock.cast());
+        SpinLockGuard {
+            spin_lock: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Acquire the spinlock, run `f` with mutable access to the protected data,
+    /// and release the spinlock
+    ///
+    /// The lock is released when `f` returns, regardless of which path `f`
+    /// returns through. `f` runs at `DISPATCH_LEVEL`, so it must not call any
+    /// APIs that require a lower `IRQL`.
+    pub fn with_lock_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut guard = self.acquire();
+        f(&mut guard)
+    }
+
+    /// Returns a mutable reference to the protected data
+    ///
+    /// Since this call borrows the [`SpinLock`] mutably, no locking needs to
+    /// take place: the mutable borrow statically guarantees no guards exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the [`SpinLock`], returning the protected data
+    pub fn into_inner(self) -> T {
+        let spin_lock = ManuallyDrop::new(self);
+        if !spin_lock.object.is_parent_owned() {
+            lock_order::forget(spin_lock.wdf_spin_lock.cast());
+        }
+        let data;
+        // SAFETY: `spin_lock` is never dropped, so `data` is only moved out of it
+        // once.
+        unsafe {
+            data = core::ptr::read(core::ptr::from_ref(&spin_lock.data));
+        }
+        let object;
+        // SAFETY: `spin_lock` is never dropped, so `object` is only moved out of it
+        // once.
+        unsafe {
+            object = core::ptr::read(core::ptr::from_ref(&spin_lock.object));
+        }
+        drop(object);
+        data.into_inner()
     }
 
-    /// Release the spinlock
-    pub fn release(&self) {
+    /// Leave the WDF spin lock object to be deleted along with its parent
+    /// object, rather than when the [`SpinLock`] is dropped, so that the
+    /// [`SpinLock`] can be stored in the context of its parent
+    ///
+    /// # Safety
+    ///
+    /// The returned [`SpinLock`] must not be used after its parent object is
+    /// deleted. Storing it in the context of its parent satisfies this, as
+    /// long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> SpinLock<'static, T> {
+        // The `ChildObject` of `spin_lock` is never dropped, so the spin lock object
+        // is left to its parent
+        let spin_lock = ManuallyDrop::new(self);
+        let data;
+        // SAFETY: `spin_lock` is never dropped, so `data` is only moved out of it
+        // once.
+        unsafe {
+            data = core::ptr::read(core::ptr::from_ref(&spin_lock.data));
+        }
+        SpinLock {
+            wdf_spin_lock: spin_lock.wdf_spin_lock,
+            data,
+            object: ChildObject::parent_owned(spin_lock.wdf_spin_lock.cast()),
+        }
+    }
+}
+
+impl SpinLock<'_> {
+    /// Acquire the spinlock, run `f`, and release the spinlock
+    ///
+    /// The lock is released when `f` returns, regardless of which path `f`
+    /// returns through. `f` runs at `DISPATCH_LEVEL`, so it must not call any
+    /// APIs that require a lower `IRQL`.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.acquire();
+        f()
+    }
+}
+
+impl<T> Drop for SpinLock<'_, T> {
+    fn drop(&mut self) {
+        // The spin lock object is deleted right after this by `object`, and WDF can
+        // reuse its handle for another spin lock
+        if !self.object.is_parent_owned() {
+            lock_order::forget(self.wdf_spin_lock.cast());
+        }
+    }
+}
+
+/// RAII guard for a held [`SpinLock`].
+///
+/// A [`SpinLockGuard`] is returned by [`SpinLock::acquire()`], and releases
+/// the lock when it goes out of scope. This guarantees that every acquisition
+/// is paired with exactly one release, including on early-return paths. The
+/// protected data can be accessed through the guard's [`Deref`] and
+/// [`DerefMut`] implementations. The guard cannot be sent to another thread,
+/// since a spin lock must be released by the same thread that acquired it.
+pub struct SpinLockGuard<'a, T = ()> {
+    spin_lock: &'a SpinLock<'a, T>,
+    // `WdfSpinLockRelease` must be called from the thread that acquired the lock
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for SpinLockGuard<'_, T> {}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the lock is held, so no
+        // other thread can be accessing the protected data.
+        unsafe { &*self.spin_lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The existence of this guard guarantees that the lock is held, so no
+        // other thread can be accessing the protected data. The mutable borrow of the
+        // guard guarantees that no other references to the data exist on this thread.
+        unsafe { &mut *self.spin_lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
         // SAFETY: `wdf_spin_lock` is a private member of `SpinLock`, originally created
-        // by WDF, and this module guarantees that it is always in a valid state.
+        // by WDF, and this module guarantees that it is always in a valid state. The
+        // existence of this guard guarantees that the lock is currently held by this
+        // thread.
         unsafe {
-            macros::call_unsafe_wdf_function_binding!(WdfSpinLockRelease, self.wdf_spin_lock);
+            macros::call_unsafe_wdf_function_binding!(
+                WdfSpinLockRelease,
+                self.spin_lock.wdf_spin_lock
+            );
         }
+        lock_order::released(self.spin_lock.wdf_spin_lock.cast());
     }
 }
@@ -0,0 +1,218 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+extern crate alloc;
+
+use alloc::string::String;
+use core::fmt;
+
+use wdk_sys::{macros, NTSTATUS, UNICODE_STRING, WDFOBJECT, WDFSTRING};
+
+use super::{child::ChildObject, ObjectAttributes, WdfObject};
+use crate::{
+    nt_success,
+    string::{utf16_to_string, NtUnicodeStr, NtUnicodeString},
+};
+
+/// WDF String.
+///
+/// A string object holds a copy of a `UNICODE_STRING`, and is how the
+/// framework returns strings whose length the driver cannot know in advance,
+/// such as registry values and device interface names. Create an empty string
+/// object with [`WdfString::try_new_empty()`] to pass to those functions.
+///
+/// The lifetime `'p` is bounded by the parent object set in the
+/// [`ObjectAttributes`] the string object is constructed with. The object is
+/// deleted when the [`WdfString`] is dropped. Use
+/// [`WdfString::into_parent_owned()`] to leave it to be deleted with its parent
+/// instead.
+///
+/// ```ignore
+/// let string = WdfString::try_from_str("Sample", ObjectAttributes::new().parent(&device))?;
+/// println!("{string}");
+/// ```
+pub struct WdfString<'p> {
+    wdf_string: WDFSTRING,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFSTRING` handles can be used, and deleted, from any thread, and
+// the string is only mutated by framework functions given `&mut self`.
+unsafe impl Send for WdfString<'_> {}
+
+// SAFETY: `WdfString` only allows its string to be mutated through
+// `&mut self`.
+unsafe impl Sync for WdfString<'_> {}
+
+// SAFETY: `wdf_string` is a private member of `WdfString`, originally created
+// by WDF, and the lifetime of a `WdfString` is bounded by its parent.
+unsafe impl WdfObject for WdfString<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_string.cast()
+    }
+}
+
+impl<'p> WdfString<'p> {
+    /// Try to create a string object holding a copy of `string`
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to create the string object. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFString Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfstring/nf-wdfstring-wdfstringcreate#return-value)
+    pub fn try_new(
+        string: NtUnicodeStr<'_>,
+        attributes: ObjectAttributes<'p>,
+    ) -> Result<Self, NTSTATUS> {
+        Self::create(string.as_ptr(), attributes)
+    }
+
+    /// Try to create an empty string object, for a framework function to
+    /// write a string into
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to create the string object. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFString Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfstring/nf-wdfstring-wdfstringcreate#return-value)
+    pub fn try_new_empty(attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        Self::create(core::ptr::null(), attributes)
+    }
+
+    /// Try to create a string object holding `string`, encoded as UTF-16
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `string` is too long for a `UNICODE_STRING`, if it could not be encoded, or if WDF fails to create the string object. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFString Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfstring/nf-wdfstring-wdfstringcreate#return-value)
+    pub fn try_from_str(string: &str, attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        Self::try_new(
+            NtUnicodeString::try_from_str(string)?.as_unicode_str(),
+            attributes,
+        )
+    }
+
+    /// Returns the raw `WDFSTRING` handle wrapped by this [`WdfString`]
+    #[must_use]
+    pub const fn as_raw(&self) -> WDFSTRING {
+        self.wdf_string
+    }
+
+    /// Returns the string held by the string object
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use]
+    pub fn as_unicode_str(&self) -> NtUnicodeStr<'_> {
+        let mut raw = UNICODE_STRING {
+            Length: 0,
+            MaximumLength: 0,
+            Buffer: core::ptr::null_mut(),
+        };
+        // SAFETY: `wdf_string` is a private member of `WdfString`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. `raw`
+        // is valid for the duration of the call.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfStringGetUnicodeString,
+                self.wdf_string,
+                &mut raw,
+            );
+        }
+        // SAFETY: WDF returns a valid `UNICODE_STRING` describing the buffer of the
+        // string object, which is only changed by framework functions given
+        // `&mut self`, and is freed when the object is deleted when `self` is dropped.
+        unsafe { NtUnicodeStr::from_raw(raw) }
+    }
+
+    /// Try to copy the string held by the string object into an
+    /// [`NtUnicodeString`]
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the [`NtUnicodeString`] could not
+    /// be allocated. The error variant will contain a [`NTSTATUS`] of the
+    /// failure.
+    pub fn to_unicode_string(&self) -> Result<NtUnicodeString, NTSTATUS> {
+        NtUnicodeString::try_from(self.as_unicode_str())
+    }
+
+    /// Try to decode the string held by the string object into a [`String`]
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the string is not valid UTF-16,
+    /// in which case the error variant will contain
+    /// `STATUS_NO_UNICODE_TRANSLATION`, or if the [`String`] could not be
+    /// allocated. The error variant will contain a [`NTSTATUS`] of the failure.
+    pub fn try_to_string(&self) -> Result<String, NTSTATUS> {
+        utf16_to_string(self.as_unicode_str().as_slice())
+    }
+
+    /// Leave the WDF string object to be deleted along with its parent, rather
+    /// than when the [`WdfString`] is dropped
+    ///
+    /// # Safety
+    ///
+    /// The returned [`WdfString`] must not be used after its parent is
+    /// deleted. Storing it in the context of its parent satisfies this, as long
+    /// as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> WdfString<'static> {
+        let Self { wdf_string, object } = self;
+        core::mem::forget(object);
+        WdfString {
+            wdf_string,
+            object: ChildObject::parent_owned(wdf_string.cast()),
+        }
+    }
+
+    fn create(
+        string: *const UNICODE_STRING,
+        mut attributes: ObjectAttributes<'p>,
+    ) -> Result<Self, NTSTATUS> {
+        let mut wdf_string: WDFSTRING = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state. `string` is either null or a valid
+        // `UNICODE_STRING`, which WDF copies, and `wdf_string` is valid for the
+        // duration of the call.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfStringCreate,
+                string,
+                attributes.as_raw_mut(),
+                &mut wdf_string,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            wdf_string,
+            // SAFETY: `wdf_string` is a valid handle to the string object that was just
+            // created, which is only deleted by this `ChildObject`, and whose parent lives
+            // for `'p`.
+            object: unsafe { ChildObject::new(wdf_string.cast()) },
+        })
+    }
+}
+
+impl fmt::Display for WdfString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_unicode_str(), f)
+    }
+}
+
+impl fmt::Debug for WdfString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_unicode_str(), f)
+    }
+}
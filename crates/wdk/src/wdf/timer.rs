@@ -1,12 +1,43 @@
-use wdk_sys::{macros, NTSTATUS, WDFTIMER, WDF_OBJECT_ATTRIBUTES, WDF_TIMER_CONFIG};
+use core::{mem::ManuallyDrop, time::Duration};
 
-use crate::nt_success;
+use wdk_sys::{macros, NTSTATUS, ULONG, WDFOBJECT, WDFTIMER, WDF_TIMER_CONFIG};
+
+use super::{
+    child::ChildObject,
+    context::{attach_closure, closure},
+    ObjectAttributes,
+    WdfObject,
+};
+use crate::{nt_success, time::relative_timeout};
+
+// `WDF_TIMER_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const TIMER_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_TIMER_CONFIG>() as ULONG;
 
 /// WDF Timer.
-pub struct Timer {
+///
+/// A [`Timer`] created with [`Timer::try_new_with_callback()`] runs a closure
+/// every time it expires.
+///
+/// The lifetime `'p` is bounded by the timer's parent object. Dropping a
+/// [`Timer`] stops it, waits for its callback to finish running, and then
+/// deletes the timer, so it must be dropped at `IRQL` = `PASSIVE_LEVEL`, and
+/// not from within its own callback. Use [`Timer::into_parent_owned()`] to
+/// leave the timer to be deleted with its parent instead.
+pub struct Timer<'p> {
     wdf_timer: WDFTIMER,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `wdf_timer` is a private member of `Timer`, originally created by
+// WDF, and the lifetime of a `Timer` is bounded by its parent object.
+unsafe impl WdfObject for Timer<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_timer.cast()
+    }
 }
-impl Timer {
+
+impl<'p> Timer<'p> {
     /// Try to construct a WDF Timer object
     ///
     /// # Errors
@@ -14,11 +45,9 @@ impl Timer {
     /// This function will return an error if WDF fails to contruct a timer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFTimer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdftimer/nf-wdftimer-wdftimercreate#return-value)
     pub fn try_new(
         timer_config: &mut WDF_TIMER_CONFIG,
-        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+        mut attributes: ObjectAttributes<'p>,
     ) -> Result<Self, NTSTATUS> {
-        let mut timer = Self {
-            wdf_timer: core::ptr::null_mut(),
-        };
+        let mut wdf_timer: WDFTIMER = core::ptr::null_mut();
 
         let nt_status;
         // SAFETY: The resulting ffi object is stored in a private member and not
@@ -28,11 +57,20 @@ pub fn try_new(
             nt_status = macros::call_unsafe_wdf_function_binding!(
                 WdfTimerCreate,
                 timer_config,
-                attributes,
-                &mut timer.wdf_timer,
+                attributes.as_raw_mut(),
+                &mut wdf_timer,
             );
         }
-        nt_success(nt_status).then_some(timer).ok_or(nt_status)
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        Ok(Self {
+            wdf_timer,
+            // SAFETY: `wdf_timer` is a valid handle to the timer that was just created,
+            // which is only deleted by this `ChildObject`, and whose parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_timer.cast()) },
+        })
     }
 
     /// Try to construct a WDF Timer object
@@ -42,11 +80,66 @@ pub fn try_new(
     /// This function will return an error if WDF fails to contruct a timer. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFTimer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdftimer/nf-wdftimer-wdftimercreate#return-value)
     pub fn create(
         timer_config: &mut WDF_TIMER_CONFIG,
-        attributes: &mut WDF_OBJECT_ATTRIBUTES,
+        attributes: ObjectAttributes<'p>,
     ) -> Result<Self, NTSTATUS> {
         Self::try_new(timer_config, attributes)
     }
 
+    /// Try to construct a WDF Timer object, parented to `parent`, that runs
+    /// `callback` at `IRQL` = `DISPATCH_LEVEL` every time it expires
+    ///
+    /// If `period` is [`Some`], the timer is periodic, and after it first
+    /// expires, it expires again every `period` until it is stopped. WDF
+    /// timer periods have a resolution of 1ms, so `period` is rounded down to
+    /// the nearest millisecond, and up to 1ms if it is shorter. The callback
+    /// may run on any processor, and may run concurrently with itself.
+    ///
+    /// `parent` must be a WDF device or queue object, or an object descended
+    /// from one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a timer, or to allocate storage for `callback`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFTimer Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdftimer/nf-wdftimer-wdftimercreate#return-value)
+    pub fn try_new_with_callback<F>(
+        parent: &'p impl WdfObject,
+        period: Option<Duration>,
+        callback: F,
+    ) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&Self) + Send + Sync + 'static,
+    {
+        let period = period.map_or(0, |period| {
+            ULONG::try_from(period.as_millis())
+                .unwrap_or(ULONG::MAX)
+                .max(1)
+        });
+        let mut timer_config = WDF_TIMER_CONFIG {
+            Size: TIMER_CONFIG_SIZE,
+            EvtTimerFunc: Some(evt_timer_func::<'p, F>),
+            Period: period,
+            ..Default::default()
+        };
+
+        let timer = Self::try_new(&mut timer_config, ObjectAttributes::new().parent(parent))?;
+
+        // SAFETY: `wdf_timer` is a valid handle to the timer that was just created,
+        // and nothing else has been attached to it. The timer has not been started,
+        // so `evt_timer_func` cannot run before the closure is attached. If this
+        // fails, dropping `timer` deletes the timer.
+        unsafe { attach_closure(timer.wdf_timer.cast(), callback) }?;
+        Ok(timer)
+    }
+
+    /// Start the [`Timer`]'s clock, so that it expires once `due_time` has
+    /// elapsed. If the timer is already started, it is restarted.
+    ///
+    /// Returns `true` if the timer was already started.
+    // Timers are commonly restarted without caring whether they were started
+    #[allow(clippy::must_use_candidate)]
+    pub fn start_after(&self, due_time: Duration) -> bool {
+        self.start(relative_timeout(due_time))
+    }
+
     /// Start the [`Timer`]'s clock
     #[must_use]
     pub fn start(&self, due_time: i64) -> bool {
@@ -75,4 +168,48 @@ pub fn stop(&self, wait: bool) -> bool {
         }
         result != 0
     }
+
+    /// Leave the WDF timer object to be deleted along with its parent object,
+    /// rather than when the [`Timer`] is dropped, so that the [`Timer`] can be
+    /// stored in the context of its parent
+    ///
+    /// # Safety
+    ///
+    /// The returned [`Timer`] must not be used after its parent object is
+    /// deleted. Storing it in the context of its parent satisfies this, as
+    /// long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> Timer<'static> {
+        let timer = ManuallyDrop::new(self);
+        Timer {
+            wdf_timer: timer.wdf_timer,
+            object: ChildObject::parent_owned(timer.wdf_timer.cast()),
+        }
+    }
+}
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        if !self.object.is_parent_owned() {
+            let _ = self.stop(true);
+        }
+    }
+}
+
+/// The `EvtTimerFunc` of timers created by [`Timer::try_new_with_callback()`]
+///
+/// # Safety
+///
+/// `wdf_timer` must be a valid handle to a timer that an `F` was attached to.
+unsafe extern "C" fn evt_timer_func<'p, F>(wdf_timer: WDFTIMER)
+where
+    F: Fn(&Timer<'p>),
+{
+    // SAFETY: The framework only calls this with the timer, which is valid for the
+    // duration of the call, and which had an `F` attached when it was created.
+    let callback = unsafe { closure::<F>(wdf_timer.cast()) };
+    callback(&Timer {
+        wdf_timer,
+        object: ChildObject::parent_owned(wdf_timer.cast()),
+    });
 }
@@ -0,0 +1,359 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+use wdk_sys::{macros, NTSTATUS, STATUS_SUCCESS, WDFWAITLOCK};
+
+#[cfg(feature = "lock-order-checks")]
+use super::context::attach_cleanup_callback;
+use super::{child::ChildObject, ObjectAttributes};
+use crate::{
+    debug_assert_irql,
+    irql::PassiveLevel,
+    lock_order,
+    nt_success,
+    time::relative_timeout,
+};
+
+/// WDF Wait Lock.
+///
+/// Framework wait locks synchronize access to driver data from code that runs
+/// at `IRQL` = `PASSIVE_LEVEL`. Unlike a [`SpinLock`](super::SpinLock), a
+/// thread that cannot immediately acquire a wait lock is put into a wait state
+/// instead of spinning, and holding a wait lock does not raise `IRQL`. While a
+/// wait lock is held, the holding thread is in a critical region, so normal
+/// kernel APCs are disabled.
+///
+/// Before a driver can use a framework wait lock it must call
+/// [`WaitLock::try_new()`] to create a [`WaitLock`]. The driver can then call
+/// [`WaitLock::acquire()`] to acquire the lock, which returns a
+/// [`WaitLockGuard`] that releases the lock when it is dropped, or use
+/// [`WaitLock::with_lock()`] or [`WaitLock::with_lock_mut()`] to hold the lock
+/// for the duration of a closure. Since waiting for the lock requires `IRQL` =
+/// `PASSIVE_LEVEL`, these take a [`PassiveLevel`] token. Code running at
+/// `IRQL` <= `DISPATCH_LEVEL` can still use [`WaitLock::try_acquire()`].
+///
+/// A [`WaitLock`] owns the data of type `T` that it protects, and the data is
+/// only accessible through the [`WaitLockGuard`]. WDF wait locks are not
+/// recursive: acquiring a lock that is already held by the current thread
+/// will deadlock.
+///
+/// The lifetime `'p` is bounded by the parent object set in the
+/// [`ObjectAttributes`] the lock is constructed with, and the WDF wait lock
+/// object is deleted when the [`WaitLock`] is dropped. Use
+/// [`WaitLock::into_parent_owned()`] to leave it to be deleted with its parent
+/// instead.
+// `wdf_wait_lock` is named consistently with the handles of the other wrappers
+#[allow(clippy::struct_field_names)]
+pub struct WaitLock<'p, T = ()> {
+    wdf_wait_lock: WDFWAITLOCK,
+    data: UnsafeCell<T>,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFWAITLOCK` handles can be used from any thread, and the protected
+// data is only ever accessed by whoever holds the lock. Sending the `WaitLock`
+// to another thread sends the owned `T` with it, so `T` must be `Send`.
+unsafe impl<T: Send> Send for WaitLock<'_, T> {}
+
+// SAFETY: `WdfWaitLockAcquire` guarantees that only one thread at a time can
+// hold a `WaitLockGuard` and therefore access the protected data, so sharing a
+// `WaitLock` between threads is equivalent to sending `T` between them.
+unsafe impl<T: Send> Sync for WaitLock<'_, T> {}
+
+impl<'p, T> WaitLock<'p, T> {
+    /// Try to construct a WDF Wait Lock object protecting `data`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a wait lock. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFWaitLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn try_new(data: T, mut attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        let mut wdf_wait_lock: WDFWAITLOCK = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfWaitLockCreate,
+                attributes.as_raw_mut(),
+                &mut wdf_wait_lock,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+
+        let wait_lock = Self {
+            wdf_wait_lock,
+            data: UnsafeCell::new(data),
+            // SAFETY: `wdf_wait_lock` is a valid handle to the wait lock that was just created,
+            // which is only deleted by this `ChildObject`, and whose parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_wait_lock.cast()) },
+        };
+
+        // WDF reuses the handles of deleted wait locks, so the lock orderings recorded
+        // for this one are forgotten when it is deleted, including along with its
+        // parent after `WaitLock::into_parent_owned()`
+        #[cfg(feature = "lock-order-checks")]
+        // SAFETY: `wdf_wait_lock` is a valid handle to the wait lock that was just
+        // created.
+        unsafe {
+            attach_cleanup_callback(wdf_wait_lock.cast(), move || {
+                lock_order::forget(wdf_wait_lock.cast());
+            })?;
+        }
+        Ok(wait_lock)
+    }
+
+    /// Try to construct a WDF Wait Lock object protecting `data`. This is an
+    /// alias for [`WaitLock::try_new()`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a wait lock. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFWaitLock Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfsync/nf-wdfsync-wdfwaitlockcreate#return-value)
+    pub fn create(data: T, attributes: ObjectAttributes<'p>) -> Result<Self, NTSTATUS> {
+        Self::try_new(data, attributes)
+    }
+
+    /// Acquire the wait lock, waiting indefinitely until it is available
+    ///
+    /// The returned [`WaitLockGuard`] keeps the lock held until it is dropped.
+    #[must_use = "if unused the WaitLock will immediately be released"]
+    pub fn acquire(&self, _irql: &PassiveLevel) -> WaitLockGuard<'_, T> {
+        debug_assert_irql!(== PASSIVE_LEVEL, "wait locks must be waited for");
+        lock_order::check_acquire(self.wdf_wait_lock.cast());
+
+        let nt_status;
+        // SAFETY: `wdf_wait_lock` is a private member of `WaitLock`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. A null
+        // timeout requests an indefinite wait.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfWaitLockAcquire,
+                self.wdf_wait_lock,
+                core::ptr::null_mut(),
+            );
+        }
+        debug_assert_eq!(
+            nt_status, STATUS_SUCCESS,
+            "WdfWaitLockAcquire should always succeed when waiting indefinitely"
+        );
+        lock_order::acquired(self.wdf_wait_lock.cast());
+        WaitLockGuard {
+            wait_lock: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Acquire the wait lock, waiting at most `timeout` for it to become
+    /// available
+    ///
+    /// Returns [`None`] if the lock could not be acquired before `timeout`
+    /// elapsed. Use [`WaitLock::try_acquire()`] to attempt to acquire the lock
+    /// without waiting at `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use = "if unused the WaitLock will immediately be released"]
+    pub fn acquire_with_timeout(
+        &self,
+        _irql: &PassiveLevel,
+        timeout: Duration,
+    ) -> Option<WaitLockGuard<'_, T>> {
+        self.acquire_within(timeout)
+    }
+
+    /// Attempt to acquire the wait lock without waiting
+    ///
+    /// Returns [`None`] if the lock is currently held. This may be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    #[must_use = "if unused the WaitLock will immediately be released"]
+    pub fn try_acquire(&self) -> Option<WaitLockGuard<'_, T>> {
+        self.acquire_within(Duration::ZERO)
+    }
+
+    /// Acquire the wait lock, waiting at most `timeout` for it to become
+    /// available
+    ///
+    /// A zero `timeout` may be used at `IRQL` <= `DISPATCH_LEVEL`. Any other
+    /// `timeout` requires `IRQL` = `PASSIVE_LEVEL`.
+    fn acquire_within(&self, timeout: Duration) -> Option<WaitLockGuard<'_, T>> {
+        if timeout.is_zero() {
+            debug_assert_irql!(<= DISPATCH_LEVEL, "wait locks must be acquired without waiting");
+        } else {
+            debug_assert_irql!(== PASSIVE_LEVEL, "wait locks must be waited for");
+            lock_order::check_acquire(self.wdf_wait_lock.cast());
+        }
+        let mut relative_timeout = relative_timeout(timeout);
+
+        let nt_status;
+        // SAFETY: `wdf_wait_lock` is a private member of `WaitLock`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state.
+        // `relative_timeout` is a valid negative (relative) timeout in 100ns units.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfWaitLockAcquire,
+                self.wdf_wait_lock,
+                &mut relative_timeout,
+            );
+        }
+
+        // `WdfWaitLockAcquire` returns `STATUS_TIMEOUT`, which is a success status,
+        // when the lock could not be acquired in time
+        if nt_status != STATUS_SUCCESS {
+            return None;
+        }
+        lock_order::acquired(self.wdf_wait_lock.cast());
+
+        Some(WaitLockGuard {
+            wait_lock: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Acquire the wait lock, run `f` with mutable access to the protected
+    /// data, and release the wait lock
+    ///
+    /// The lock is released when `f` returns, regardless of which path `f`
+    /// returns through.
+    pub fn with_lock_mut<R>(&self, irql: &PassiveLevel, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut guard = self.acquire(irql);
+        f(&mut guard)
+    }
+
+    /// Returns a mutable reference to the protected data
+    ///
+    /// Since this call borrows the [`WaitLock`] mutably, no locking needs to
+    /// take place: the mutable borrow statically guarantees no guards exist.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Consumes the [`WaitLock`], returning the protected data
+    pub fn into_inner(self) -> T {
+        let wait_lock = ManuallyDrop::new(self);
+        if !wait_lock.object.is_parent_owned() {
+            lock_order::forget(wait_lock.wdf_wait_lock.cast());
+        }
+        let data;
+        // SAFETY: `wait_lock` is never dropped, so `data` is only moved out of it
+        // once.
+        unsafe {
+            data = core::ptr::read(core::ptr::from_ref(&wait_lock.data));
+        }
+        let object;
+        // SAFETY: `wait_lock` is never dropped, so `object` is only moved out of it
+        // once.
+        unsafe {
+            object = core::ptr::read(core::ptr::from_ref(&wait_lock.object));
+        }
+        drop(object);
+        data.into_inner()
+    }
+
+    /// Leave the WDF wait lock object to be deleted along with its parent
+    /// object, rather than when the [`WaitLock`] is dropped, so that the
+    /// [`WaitLock`] can be stored in the context of its parent
+    ///
+    /// # Safety
+    ///
+    /// The returned [`WaitLock`] must not be used after its parent object is
+    /// deleted. Storing it in the context of its parent satisfies this, as
+    /// long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> WaitLock<'static, T> {
+        // The `ChildObject` of `wait_lock` is never dropped, so the wait lock object
+        // is left to its parent
+        let wait_lock = ManuallyDrop::new(self);
+        let data;
+        // SAFETY: `wait_lock` is never dropped, so `data` is only moved out of it
+        // once.
+        unsafe {
+            data = core::ptr::read(core::ptr::from_ref(&wait_lock.data));
+        }
+        WaitLock {
+            wdf_wait_lock: wait_lock.wdf_wait_lock,
+            data,
+            object: ChildObject::parent_owned(wait_lock.wdf_wait_lock.cast()),
+        }
+    }
+}
+
+impl WaitLock<'_> {
+    /// Acquire the wait lock, run `f`, and release the wait lock
+    ///
+    /// The lock is released when `f` returns, regardless of which path `f`
+    /// returns through.
+    pub fn with_lock<R>(&self, irql: &PassiveLevel, f: impl FnOnce() -> R) -> R {
+        let _guard = self.acquire(irql);
+        f()
+    }
+}
+
+impl<T> Drop for WaitLock<'_, T> {
+    fn drop(&mut self) {
+        // The wait lock object is deleted right after this by `object`, and WDF can
+        // reuse its handle for another wait lock
+        if !self.object.is_parent_owned() {
+            lock_order::forget(self.wdf_wait_lock.cast());
+        }
+    }
+}
+
+/// RAII guard for a held [`WaitLock`].
+///
+/// A [`WaitLockGuard`] is returned by the acquisition functions of
+/// [`WaitLock`], and releases the lock when it goes out of scope. The
+/// protected data can be accessed through the guard's [`Deref`] and
+/// [`DerefMut`] implementations. The guard cannot be sent to another thread,
+/// since a wait lock must be released by the same thread that acquired it.
+pub struct WaitLockGuard<'a, T = ()> {
+    wait_lock: &'a WaitLock<'a, T>,
+    // `WdfWaitLockRelease` must be called from the thread that acquired the lock
+    _not_send: PhantomData<*mut ()>,
+}
+
+// SAFETY: A shared reference to the guard only allows shared access to `T`, so
+// it is safe to share the guard between threads if `T` is `Sync`.
+unsafe impl<T: Sync> Sync for WaitLockGuard<'_, T> {}
+
+impl<T> Deref for WaitLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The existence of this guard guarantees that the lock is held, so no
+        // other thread can be accessing the protected data.
+        unsafe { &*self.wait_lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for WaitLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The existence of this guard guarantees that the lock is held, so no
+        // other thread can be accessing the protected data. The mutable borrow of the
+        // guard guarantees that no other references to the data exist on this thread.
+        unsafe { &mut *self.wait_lock.data.get() }
+    }
+}
+
+impl<T> Drop for WaitLockGuard<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: `wdf_wait_lock` is a private member of `WaitLock`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state. The
+        // existence of this guard guarantees that the lock is currently held by this
+        // thread.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(
+                WdfWaitLockRelease,
+                self.wait_lock.wdf_wait_lock
+            );
+        }
+        lock_order::released(self.wait_lock.wdf_wait_lock.cast());
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright (c) Microsoft Corporation
+// License: MIT OR Apache-2.0
+
+use core::mem::ManuallyDrop;
+
+use wdk_sys::{macros, NTSTATUS, ULONG, WDFOBJECT, WDFWORKITEM, WDF_WORKITEM_CONFIG};
+
+use super::{
+    child::ChildObject,
+    context::{attach_closure, closure, object_attributes},
+    WdfObject,
+};
+use crate::{irql::PassiveLevel, nt_success};
+
+// `WDF_WORKITEM_CONFIG` is much smaller than `ULONG::MAX` bytes
+#[allow(clippy::cast_possible_truncation)]
+const WORKITEM_CONFIG_SIZE: ULONG = core::mem::size_of::<WDF_WORKITEM_CONFIG>() as ULONG;
+
+/// WDF Work Item.
+///
+/// A [`WorkItem`] runs a closure on a system worker thread at `IRQL` =
+/// `PASSIVE_LEVEL` every time it is enqueued with [`WorkItem::enqueue()`], and
+/// passes it a [`PassiveLevel`] token.
+/// This is the standard way to defer work that must run at `PASSIVE_LEVEL`
+/// from code running at `DISPATCH_LEVEL`, such as DPCs and timer callbacks.
+///
+/// Dropping a [`WorkItem`] waits for its closure to finish running, and then
+/// deletes the work item, so it must be dropped at `IRQL` = `PASSIVE_LEVEL`,
+/// and not from within its own closure. The lifetime `'p` is bounded by the
+/// work item's parent object, so it cannot outlive it. Use
+/// [`WorkItem::into_parent_owned()`] to leave the work item to be deleted with
+/// its parent instead.
+pub struct WorkItem<'p> {
+    wdf_work_item: WDFWORKITEM,
+    object: ChildObject<'p>,
+}
+
+// SAFETY: `WDFWORKITEM` handles can be enqueued, flushed and deleted from any
+// thread, and the closure is required to be `Send` and `Sync`.
+unsafe impl Send for WorkItem<'_> {}
+
+// SAFETY: All methods of `WorkItem` that take `&self` can be called
+// concurrently from multiple threads.
+unsafe impl Sync for WorkItem<'_> {}
+
+// SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally created
+// by WDF, and the lifetime of a `WorkItem` is bounded by its parent object.
+unsafe impl WdfObject for WorkItem<'_> {
+    fn as_object_handle(&self) -> WDFOBJECT {
+        self.wdf_work_item.cast()
+    }
+}
+
+impl<'p> WorkItem<'p> {
+    /// Try to construct a WDF Work Item object, parented to `parent`, that
+    /// runs `callback` every time it is enqueued
+    ///
+    /// `parent` must be a WDF device or queue object. This must be called at
+    /// `IRQL` <= `DISPATCH_LEVEL`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if WDF fails to contruct a work item, or to allocate storage for `callback`. The error variant will contain a [`NTSTATUS`] of the failure. Full error documentation is available in the [WDFWorkItem Documentation](https://learn.microsoft.com/en-us/windows-hardware/drivers/ddi/wdfworkitem/nf-wdfworkitem-wdfworkitemcreate#return-value)
+    pub fn try_new<F>(parent: &'p impl WdfObject, callback: F) -> Result<Self, NTSTATUS>
+    where
+        F: Fn(&PassiveLevel) + Send + Sync + 'static,
+    {
+        let mut work_item_config = WDF_WORKITEM_CONFIG {
+            Size: WORKITEM_CONFIG_SIZE,
+            EvtWorkItemFunc: Some(evt_work_item_func::<F>),
+            ..Default::default()
+        };
+        let mut attributes = object_attributes(parent.as_object_handle());
+        let mut wdf_work_item: WDFWORKITEM = core::ptr::null_mut();
+
+        let nt_status;
+        // SAFETY: The resulting ffi object is stored in a private member and not
+        // accessible outside of this module, and this module guarantees that it is
+        // always in a valid state.
+        unsafe {
+            nt_status = macros::call_unsafe_wdf_function_binding!(
+                WdfWorkItemCreate,
+                &mut work_item_config,
+                &mut attributes,
+                &mut wdf_work_item,
+            );
+        }
+        if !nt_success(nt_status) {
+            return Err(nt_status);
+        }
+        let work_item = Self {
+            wdf_work_item,
+            // SAFETY: `wdf_work_item` is a valid handle to the work item that was just created,
+            // which is only deleted by this `ChildObject`, and whose parent lives for `'p`.
+            object: unsafe { ChildObject::new(wdf_work_item.cast()) },
+        };
+
+        // SAFETY: `wdf_work_item` is a valid handle to the work item that was just
+        // created, and nothing else has been attached to it. The work item has not
+        // been enqueued, so `evt_work_item_func` cannot run before the closure is
+        // attached. If this fails, dropping `work_item` deletes the work item.
+        unsafe { attach_closure(work_item.wdf_work_item.cast(), callback) }?;
+        Ok(work_item)
+    }
+
+    /// Enqueue the work item, so that its closure runs on a system worker
+    /// thread. If the work item is already enqueued and its closure has not
+    /// started running, this has no effect.
+    ///
+    /// This must be called at `IRQL` <= `DISPATCH_LEVEL`.
+    pub fn enqueue(&self) {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfWorkItemEnqueue, self.wdf_work_item);
+        }
+    }
+
+    /// Wait until the work item's closure has finished running, if it is
+    /// enqueued or running. If it is neither, this returns immediately.
+    ///
+    /// This must be called at `IRQL` = `PASSIVE_LEVEL`, and not from within
+    /// the work item's own closure.
+    pub fn flush(&self) {
+        // SAFETY: `wdf_work_item` is a private member of `WorkItem`, originally created
+        // by WDF, and this module guarantees that it is always in a valid state.
+        unsafe {
+            macros::call_unsafe_wdf_function_binding!(WdfWorkItemFlush, self.wdf_work_item);
+        }
+    }
+
+    /// Leave the WDF work item object to be deleted along with its parent
+    /// object, rather than when the [`WorkItem`] is dropped, so that the
+    /// [`WorkItem`] can be stored in the context of its parent
+    ///
+    /// # Safety
+    ///
+    /// The returned [`WorkItem`] must not be used after its parent object is
+    /// deleted. Storing it in the context of its parent satisfies this, as
+    /// long as it is not used when the context is dropped.
+    #[must_use]
+    pub unsafe fn into_parent_owned(self) -> WorkItem<'static> {
+        let work_item = ManuallyDrop::new(self);
+        WorkItem {
+            wdf_work_item: work_item.wdf_work_item,
+            object: ChildObject::parent_owned(work_item.wdf_work_item.cast()),
+        }
+    }
+}
+
+impl Drop for WorkItem<'_> {
+    fn drop(&mut self) {
+        // The work item is deleted by `object` after this, once its closure is no
+        // longer running
+        if !self.object.is_parent_owned() {
+            self.flush();
+        }
+    }
+}
+
+/// The `EvtWorkItemFunc` of work items created by [`WorkItem::try_new()`]
+///
+/// # Safety
+///
+/// `wdf_work_item` must be a valid handle to a work item that an `F` was
+/// attached to.
+unsafe extern "C" fn evt_work_item_func<F>(wdf_work_item: WDFWORKITEM)
+where
+    F: Fn(&PassiveLevel),
+{
+    // SAFETY: The framework only calls this with the work item, which is valid for
+    // the duration of the call, and which had an `F` attached when it was created.
+    let callback = unsafe { closure::<F>(wdf_work_item.cast()) };
+    let irql;
+    // SAFETY: The framework calls `EvtWorkItemFunc` at `IRQL` = `PASSIVE_LEVEL`.
+    unsafe {
+        irql = PassiveLevel::new_unchecked();
+    }
+    callback(&irql);
+}